@@ -6,7 +6,8 @@
 
 use std::borrow::Cow;
 
-use directory::Directory;
+use ahash::AHashSet;
+use directory::{backend::internal::PrincipalField, Directory, QueryBy, Type};
 use utils::config::{utils::AsKey, Config};
 
 use crate::{
@@ -17,6 +18,14 @@ use crate::{
     Core,
 };
 
+// Accounts an envelope recipient resolves to after alias and list expansion
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RecipientExpansion {
+    Accounts(Vec<u32>),
+    Unknown,
+    TooDeep,
+}
+
 impl Core {
     pub async fn email_to_ids(
         &self,
@@ -54,6 +63,87 @@ impl Core {
         Ok(vec![])
     }
 
+    // Resolves a recipient into the accounts it delivers to. Aliases and
+    // catch-all addresses are resolved by the directory, the catch-all is only
+    // used for domains that define an "@domain" address. Lists that contain
+    // other lists are expanded recursively up to the configured depth, members
+    // that no longer exist or that would loop back are logged and skipped.
+    pub async fn expand_recipient(
+        &self,
+        directory: &Directory,
+        email: &str,
+        session_id: u64,
+    ) -> trc::Result<RecipientExpansion> {
+        let ids = self.email_to_ids(directory, email, session_id).await?;
+        if ids.is_empty() {
+            return Ok(RecipientExpansion::Unknown);
+        }
+
+        let max_depth = self.jmap.delivery_max_expansion_depth;
+        let mut accounts = Vec::with_capacity(ids.len());
+        let mut expanded = AHashSet::new();
+        let mut skipped = false;
+        let mut pending = ids
+            .into_iter()
+            .rev()
+            .map(|id| (id, Vec::new()))
+            .collect::<Vec<(u32, Vec<u32>)>>();
+
+        while let Some((id, path)) = pending.pop() {
+            if accounts.contains(&id) {
+                continue;
+            }
+            let principal = if let Some(principal) = directory.query(QueryBy::Id(id), false).await?
+            {
+                principal
+            } else {
+                trc::event!(
+                    MessageIngest(trc::MessageIngestEvent::Error),
+                    Reason = "Alias points to an account that does not exist.",
+                    To = email.to_string(),
+                    AccountId = id,
+                    SpanId = session_id,
+                );
+                skipped = true;
+                continue;
+            };
+            if principal.typ() != Type::List {
+                accounts.push(id);
+                continue;
+            }
+
+            // Nested lists are expanded through their primary address
+            if path.contains(&id) {
+                trc::event!(
+                    MessageIngest(trc::MessageIngestEvent::Error),
+                    Reason = "Mailing list expansion loop detected.",
+                    To = email.to_string(),
+                    AccountId = id,
+                    SpanId = session_id,
+                );
+                skipped = true;
+                continue;
+            } else if path.len() >= max_depth {
+                return Ok(RecipientExpansion::TooDeep);
+            } else if !expanded.insert(id) {
+                continue;
+            }
+            if let Some(list_email) = principal.iter_str(PrincipalField::Emails).next() {
+                let mut path = path;
+                path.push(id);
+                for member_id in directory.email_to_ids(list_email).await?.into_iter().rev() {
+                    pending.push((member_id, path.clone()));
+                }
+            }
+        }
+
+        if !accounts.is_empty() || !skipped {
+            Ok(RecipientExpansion::Accounts(accounts))
+        } else {
+            Ok(RecipientExpansion::Unknown)
+        }
+    }
+
     pub async fn rcpt(
         &self,
         directory: &Directory,
@@ -14,7 +14,7 @@ use jmap_proto::{
 };
 use std::{
     hash::{DefaultHasher, Hash, Hasher},
-    sync::Arc,
+    sync::{atomic::Ordering, Arc},
     time::Instant,
 };
 use store::query::acl::AclQuery;
@@ -31,6 +31,8 @@ use super::{roles::RolePermissions, AccessToken, ResourceToken, TenantInfo};
 
 impl Core {
     pub async fn build_access_token(&self, mut principal: Principal) -> trc::Result<AccessToken> {
+        // Read the version first, so a concurrent role change marks the token as stale
+        let permissions_version = self.security.permissions_version.load(Ordering::Relaxed);
         let mut role_permissions = RolePermissions::default();
 
         // Apply role permissions
@@ -107,6 +109,7 @@ impl Core {
             description: principal.take_str(PrincipalField::Description),
             quota: principal.quota(),
             permissions,
+            permissions_version,
         })
     }
 
@@ -129,15 +132,19 @@ impl Core {
             Err(err) => Err(err),
         };
 
-        match &self.jmap.fallback_admin {
-            Some((_, secret)) if account_id == u32::MAX => {
+        match self.jmap.fallback_admin(account_id) {
+            Some(fallback_admin) => {
                 self.update_access_token(
-                    self.build_access_token(Principal::fallback_admin(secret))
-                        .await?,
+                    self.build_access_token(Principal::fallback_admin(
+                        fallback_admin.id,
+                        &fallback_admin.user,
+                        &fallback_admin.secret,
+                    ))
+                    .await?,
                 )
                 .await
             }
-            _ => err,
+            None => err,
         }
     }
 
@@ -199,15 +206,16 @@ impl Core {
     }
 
     pub async fn get_cached_access_token(&self, primary_id: u32) -> trc::Result<Arc<AccessToken>> {
-        if let Some(access_token) = self.security.access_tokens.get_with_ttl(&primary_id) {
-            Ok(access_token)
-        } else {
-            // Refresh ACL token
-            self.get_access_token(primary_id).await.map(|access_token| {
-                let access_token = Arc::new(access_token);
-                self.cache_access_token(access_token.clone());
-                access_token
-            })
+        match self.security.access_tokens.get_with_ttl(&primary_id) {
+            Some(access_token) if !self.security.is_outdated(&access_token) => Ok(access_token),
+            _ => {
+                // Refresh ACL token, also when roles changed since it was cached
+                self.get_access_token(primary_id).await.map(|access_token| {
+                    let access_token = Arc::new(access_token);
+                    self.cache_access_token(access_token.clone());
+                    access_token
+                })
+            }
         }
     }
 }
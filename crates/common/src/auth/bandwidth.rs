@@ -0,0 +1,222 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use serde::Serialize;
+use store::write::now;
+use utils::config::Config;
+
+use crate::Core;
+
+const DAY: u64 = 86400;
+
+#[derive(Debug, Clone)]
+pub struct BandwidthAccounting {
+    pub flush_interval: Duration,
+    pub retention: Duration,
+}
+
+#[derive(Debug, Default)]
+pub struct BandwidthCounter {
+    pub bytes_in: AtomicU64,
+    pub bytes_out: AtomicU64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Transfer {
+    // Messages delivered to the account
+    In,
+    // Messages and blobs downloaded by the account
+    Out,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BandwidthUsage {
+    pub bytes_in: u64,
+    pub bytes_out: u64,
+}
+
+impl BandwidthAccounting {
+    pub fn parse(config: &mut Config) -> Option<Self> {
+        if !config
+            .property_or_default("server.bandwidth.enable", "false")
+            .unwrap_or(false)
+        {
+            return None;
+        }
+
+        Some(BandwidthAccounting {
+            flush_interval: config
+                .property_or_default("server.bandwidth.flush-interval", "1m")
+                .unwrap_or_else(|| Duration::from_secs(60)),
+            retention: config
+                .property_or_default("server.bandwidth.retention", "90d")
+                .unwrap_or_else(|| Duration::from_secs(90 * DAY)),
+        })
+    }
+}
+
+impl Core {
+    pub fn record_bandwidth(&self, account_id: u32, transfer: Transfer, bytes: usize) {
+        if self.network.bandwidth.is_none() || bytes == 0 {
+            return;
+        }
+
+        // Usage is accumulated in memory and written to the store on flush
+        let counter = if let Some(counter) = self.security.bandwidth.get(&account_id) {
+            counter.clone()
+        } else {
+            self.security
+                .bandwidth
+                .entry(account_id)
+                .or_insert_with(|| Arc::new(BandwidthCounter::default()))
+                .clone()
+        };
+        match transfer {
+            Transfer::In => counter.bytes_in.fetch_add(bytes as u64, Ordering::Relaxed),
+            Transfer::Out => counter.bytes_out.fetch_add(bytes as u64, Ordering::Relaxed),
+        };
+    }
+
+    pub async fn flush_bandwidth(&self) {
+        let retention = match &self.network.bandwidth {
+            Some(accounting) => accounting.retention.as_secs(),
+            None => return,
+        };
+
+        // Usage is stored in daily buckets that expire after the retention period
+        let day = now() / DAY;
+        let mut usage = Vec::new();
+        let mut idle_accounts = Vec::new();
+        for entry in self.security.bandwidth.iter() {
+            let bytes_in = entry.bytes_in.swap(0, Ordering::Relaxed);
+            let bytes_out = entry.bytes_out.swap(0, Ordering::Relaxed);
+            if bytes_in != 0 || bytes_out != 0 {
+                usage.push((*entry.key(), bytes_in, bytes_out));
+            } else {
+                idle_accounts.push(*entry.key());
+            }
+        }
+
+        // Counters for accounts without activity are released, unless usage was
+        // recorded after they were read
+        for account_id in idle_accounts {
+            self.security
+                .bandwidth
+                .remove_if(&account_id, |_, counter| {
+                    Arc::strong_count(counter) == 1
+                        && counter.bytes_in.load(Ordering::Relaxed) == 0
+                        && counter.bytes_out.load(Ordering::Relaxed) == 0
+                });
+        }
+
+        for (account_id, bytes_in, bytes_out) in usage {
+            for (transfer, bytes) in [(Transfer::In, bytes_in), (Transfer::Out, bytes_out)] {
+                if bytes == 0 {
+                    continue;
+                }
+
+                if let Err(err) = self
+                    .storage
+                    .lookup
+                    .counter_incr(
+                        bandwidth_key(account_id, day, transfer),
+                        bytes as i64,
+                        Some(retention),
+                        false,
+                    )
+                    .await
+                {
+                    trc::error!(err
+                        .account_id(account_id)
+                        .details("Failed to store bandwidth usage"));
+                }
+            }
+        }
+    }
+
+    pub async fn get_bandwidth_usage(
+        &self,
+        account_id: u32,
+        from: u64,
+        to: u64,
+    ) -> trc::Result<BandwidthUsage> {
+        let mut usage = BandwidthUsage::default();
+        let retention = match &self.network.bandwidth {
+            Some(accounting) => accounting.retention.as_secs(),
+            None => return Ok(usage),
+        };
+
+        // Buckets older than the retention period have already expired
+        let to = to.min(now()) / DAY;
+        let from = from.max(now().saturating_sub(retention)) / DAY;
+        for day in from..=to {
+            usage.bytes_in += self
+                .storage
+                .lookup
+                .counter_get(bandwidth_key(account_id, day, Transfer::In))
+                .await?
+                .max(0) as u64;
+            usage.bytes_out += self
+                .storage
+                .lookup
+                .counter_get(bandwidth_key(account_id, day, Transfer::Out))
+                .await?
+                .max(0) as u64;
+        }
+
+        // Include usage that has not been flushed yet
+        if to == now() / DAY {
+            if let Some(counter) = self.security.bandwidth.get(&account_id) {
+                usage.bytes_in += counter.bytes_in.load(Ordering::Relaxed);
+                usage.bytes_out += counter.bytes_out.load(Ordering::Relaxed);
+            }
+        }
+
+        Ok(usage)
+    }
+
+    pub async fn reset_bandwidth_usage(&self, account_id: u32) -> trc::Result<()> {
+        let retention = match &self.network.bandwidth {
+            Some(accounting) => accounting.retention.as_secs(),
+            None => return Ok(()),
+        };
+
+        if let Some(counter) = self.security.bandwidth.get(&account_id) {
+            counter.bytes_in.store(0, Ordering::Relaxed);
+            counter.bytes_out.store(0, Ordering::Relaxed);
+        }
+
+        let to = now() / DAY;
+        let from = now().saturating_sub(retention) / DAY;
+        for day in from..=to {
+            for transfer in [Transfer::In, Transfer::Out] {
+                self.storage
+                    .lookup
+                    .counter_delete(bandwidth_key(account_id, day, transfer))
+                    .await?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn bandwidth_key(account_id: u32, day: u64, transfer: Transfer) -> Vec<u8> {
+    match transfer {
+        Transfer::In => format!("bwi:{account_id}:{day}"),
+        Transfer::Out => format!("bwo:{account_id}:{day}"),
+    }
+    .into_bytes()
+}
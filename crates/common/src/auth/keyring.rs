@@ -0,0 +1,92 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use store::rand::{distributions::Alphanumeric, thread_rng, Rng};
+use utils::config::Config;
+
+pub const KEY_ID_MAX_LEN: usize = 32;
+
+#[derive(Debug, Clone, Default)]
+pub struct OAuthKeyRing {
+    active: OAuthKey,
+    retired: Vec<OAuthKey>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct OAuthKey {
+    pub id: String,
+    pub secret: String,
+}
+
+impl OAuthKeyRing {
+    pub fn parse(config: &mut Config) -> Self {
+        // The legacy "oauth.key" is always part of the ring, identified by an empty key id
+        let mut keys = vec![OAuthKey {
+            id: String::new(),
+            secret: config
+                .value("oauth.key")
+                .map(|s| s.to_string())
+                .unwrap_or_else(|| {
+                    thread_rng()
+                        .sample_iter(Alphanumeric)
+                        .take(64)
+                        .map(char::from)
+                        .collect::<String>()
+                }),
+        }];
+
+        for id in config
+            .sub_keys("oauth.key-ring", ".secret")
+            .map(|k| k.to_string())
+            .collect::<Vec<_>>()
+        {
+            if id.len() > KEY_ID_MAX_LEN {
+                config.new_build_error(
+                    ("oauth.key-ring", id.as_str()),
+                    format!("Key id is too long, maximum length is {KEY_ID_MAX_LEN}."),
+                );
+            } else if let Some(secret) = config
+                .value_require(("oauth.key-ring", id.as_str(), "secret"))
+                .filter(|secret| !secret.is_empty())
+            {
+                keys.push(OAuthKey {
+                    secret: secret.to_string(),
+                    id,
+                });
+            }
+        }
+
+        // Tokens are issued with the active key, all other keys are only used for validation
+        let active = match config.value("oauth.key-ring-active") {
+            Some(active_id) => match keys.iter().position(|key| key.id == active_id) {
+                Some(active) => active,
+                None => {
+                    let err = format!("Key id {active_id:?} not found in the OAuth key ring.");
+                    config.new_build_error("oauth.key-ring-active", err);
+                    0
+                }
+            },
+            None => 0,
+        };
+
+        OAuthKeyRing {
+            active: keys.swap_remove(active),
+            retired: keys,
+        }
+    }
+
+    pub fn active(&self) -> &OAuthKey {
+        &self.active
+    }
+
+    pub fn get(&self, id: &str) -> Option<&OAuthKey> {
+        if self.active.id == id {
+            Some(&self.active)
+        } else {
+            self.retired.iter().find(|key| key.id == id)
+        }
+    }
+}
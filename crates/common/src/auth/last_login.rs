@@ -0,0 +1,174 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use std::{net::IpAddr, time::Duration};
+
+use ahash::AHashMap;
+use serde::Serialize;
+use store::{write::now, LookupStore};
+use utils::config::Config;
+
+use crate::Core;
+
+#[derive(Debug, Clone)]
+pub struct LastLoginTracking {
+    pub interval: Duration,
+    pub track_tokens: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LastLogin {
+    pub timestamp: u64,
+    pub remote_ip: IpAddr,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LoginKind {
+    // Interactive authentication with credentials
+    Login,
+    // Use of a previously issued OAuth token
+    Seen,
+}
+
+impl LastLoginTracking {
+    pub fn parse(config: &mut Config) -> Option<Self> {
+        let interval = config
+            .property_or_default::<Option<Duration>>("server.last-login.interval", "1h")
+            .unwrap_or_default()?;
+
+        Some(LastLoginTracking {
+            interval,
+            track_tokens: config
+                .property_or_default("server.last-login.track-tokens", "false")
+                .unwrap_or(false),
+        })
+    }
+}
+
+impl Core {
+    pub async fn record_login(
+        &self,
+        account_id: u32,
+        remote_ip: IpAddr,
+        kind: LoginKind,
+        session_id: u64,
+    ) {
+        let tracking = match &self.network.last_login {
+            Some(tracking) if kind == LoginKind::Login || tracking.track_tokens => tracking,
+            _ => return,
+        };
+
+        // Writes are skipped until the interval elapses or the address changes
+        let login = LastLogin {
+            timestamp: now(),
+            remote_ip,
+        };
+        if let Some(last_login) = self.security.last_logins.get(&(account_id, kind)) {
+            if last_login.remote_ip == remote_ip
+                && last_login.timestamp + tracking.interval.as_secs() > login.timestamp
+            {
+                return;
+            }
+        }
+        self.security.last_logins.insert((account_id, kind), login);
+
+        if let Err(err) = self
+            .storage
+            .lookup
+            .key_set(
+                last_login_key(account_id, kind),
+                login.serialize().into_bytes(),
+                None,
+            )
+            .await
+        {
+            trc::error!(err
+                .span_id(session_id)
+                .account_id(account_id)
+                .details("Failed to store last login"));
+        }
+    }
+
+    pub async fn get_last_login(
+        &self,
+        account_id: u32,
+        kind: LoginKind,
+    ) -> trc::Result<Option<LastLogin>> {
+        self.storage
+            .lookup
+            .key_get::<String>(last_login_key(account_id, kind))
+            .await
+            .map(|value| value.and_then(|value| LastLogin::deserialize(&value)))
+    }
+
+    // Returns None when the lookup store does not support prefix scans
+    pub async fn get_all_last_logins(
+        &self,
+        kind: LoginKind,
+    ) -> trc::Result<Option<AHashMap<u32, LastLogin>>> {
+        if !matches!(self.storage.lookup, LookupStore::Store(_)) {
+            return Ok(None);
+        }
+
+        let prefix = last_login_prefix(kind);
+        self.storage
+            .lookup
+            .key_get_prefix::<String>(prefix.as_bytes())
+            .await
+            .map(|values| {
+                Some(
+                    values
+                        .into_iter()
+                        .filter_map(|(key, value)| {
+                            let account_id = std::str::from_utf8(key.get(prefix.len()..)?)
+                                .ok()?
+                                .parse()
+                                .ok()?;
+                            Some((account_id, LastLogin::deserialize(&value)?))
+                        })
+                        .collect(),
+                )
+            })
+    }
+
+    pub async fn remove_last_login(&self, account_id: u32) -> trc::Result<()> {
+        for kind in [LoginKind::Login, LoginKind::Seen] {
+            self.security.last_logins.remove(&(account_id, kind));
+            self.storage
+                .lookup
+                .key_delete(last_login_key(account_id, kind))
+                .await?;
+        }
+
+        Ok(())
+    }
+}
+
+impl LastLogin {
+    fn serialize(&self) -> String {
+        format!("{} {}", self.timestamp, self.remote_ip)
+    }
+
+    fn deserialize(value: &str) -> Option<Self> {
+        let (timestamp, remote_ip) = value.split_once(' ')?;
+        Some(LastLogin {
+            timestamp: timestamp.parse().ok()?,
+            remote_ip: remote_ip.parse().ok()?,
+        })
+    }
+}
+
+fn last_login_key(account_id: u32, kind: LoginKind) -> Vec<u8> {
+    format!("{}{account_id}", last_login_prefix(kind)).into_bytes()
+}
+
+fn last_login_prefix(kind: LoginKind) -> &'static str {
+    match kind {
+        LoginKind::Login => "ll:",
+        LoginKind::Seen => "ls:",
+    }
+}
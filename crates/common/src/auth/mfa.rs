@@ -0,0 +1,217 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use std::{net::IpAddr, time::Duration};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use hyper::{
+    header::{AUTHORIZATION, CONTENT_TYPE},
+    HeaderMap,
+};
+use serde::{Deserialize, Serialize};
+use utils::config::Config;
+
+use crate::{
+    config::parse_http_headers,
+    expr::{
+        functions::ResolveVariable, if_block::IfBlock, tokenizer::TokenMap, Variable,
+        V_AUTHENTICATED_AS, V_REMOTE_IP,
+    },
+    Core, HttpLimitResponse,
+};
+
+const MAX_RESPONSE_SIZE: usize = 1024;
+
+#[derive(Clone)]
+pub struct MfaWebhook {
+    pub enable: IfBlock,
+    pub url: String,
+    pub client: reqwest::Client,
+    pub headers: HeaderMap,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct MfaRequest<'x> {
+    account_name: &'x str,
+    account_id: u32,
+    remote_ip: IpAddr,
+}
+
+#[derive(Debug, Deserialize)]
+struct MfaResponse {
+    action: MfaAction,
+}
+
+#[derive(Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+enum MfaAction {
+    Allow,
+    Deny,
+}
+
+struct MfaResolver<'x> {
+    account_name: &'x str,
+    remote_ip: IpAddr,
+}
+
+impl MfaWebhook {
+    pub fn parse(config: &mut Config) -> Option<Self> {
+        let url = config
+            .value("authentication.mfa.webhook.url")?
+            .to_string();
+        let mut headers = parse_http_headers(config, "authentication.mfa.webhook.headers");
+
+        headers.insert(CONTENT_TYPE, "application/json".parse().unwrap());
+        if let (Some(name), Some(secret)) = (
+            config.value("authentication.mfa.webhook.auth.username"),
+            config.value("authentication.mfa.webhook.auth.secret"),
+        ) {
+            headers.insert(
+                AUTHORIZATION,
+                format!("Basic {}", STANDARD.encode(format!("{}:{}", name, secret)))
+                    .parse()
+                    .unwrap(),
+            );
+        }
+
+        let client = reqwest::Client::builder()
+            .timeout(
+                config
+                    .property_or_default("authentication.mfa.webhook.timeout", "30s")
+                    .unwrap_or_else(|| Duration::from_secs(30)),
+            )
+            .danger_accept_invalid_certs(
+                config
+                    .property_or_default("authentication.mfa.webhook.allow-invalid-certs", "false")
+                    .unwrap_or_default(),
+            )
+            .build()
+            .map_err(|err| {
+                config.new_build_error(
+                    "authentication.mfa.webhook.url",
+                    format!("Failed to build HTTP client: {err}"),
+                )
+            })
+            .ok()?;
+
+        Some(MfaWebhook {
+            enable: IfBlock::try_parse(
+                config,
+                "authentication.mfa.webhook.enable",
+                &TokenMap::default().with_variables(&[V_AUTHENTICATED_AS, V_REMOTE_IP]),
+            )
+            .unwrap_or_else(|| {
+                IfBlock::new::<()>("authentication.mfa.webhook.enable", [], "false")
+            }),
+            url,
+            client,
+            headers,
+        })
+    }
+}
+
+impl Core {
+    // Returns true if the second factor was not required or the
+    // webhook allowed the login, any error or timeout denies access.
+    pub async fn verify_mfa(
+        &self,
+        account_name: &str,
+        account_id: u32,
+        remote_ip: IpAddr,
+        session_id: u64,
+    ) -> bool {
+        let webhook = if let Some(webhook) = &self.jmap.mfa_webhook {
+            webhook
+        } else {
+            return true;
+        };
+
+        if !self
+            .eval_if::<bool, _>(
+                &webhook.enable,
+                &MfaResolver {
+                    account_name,
+                    remote_ip,
+                },
+                session_id,
+            )
+            .await
+            .unwrap_or(false)
+        {
+            return true;
+        }
+
+        match send_mfa_request(
+            webhook,
+            &MfaRequest {
+                account_name,
+                account_id,
+                remote_ip,
+            },
+        )
+        .await
+        {
+            Ok(response) => response.action == MfaAction::Allow,
+            Err(err) => {
+                trc::event!(
+                    Auth(trc::AuthEvent::Error),
+                    SpanId = session_id,
+                    AccountName = account_name.to_string(),
+                    Url = webhook.url.clone(),
+                    Reason = err,
+                );
+
+                false
+            }
+        }
+    }
+}
+
+async fn send_mfa_request(
+    webhook: &MfaWebhook,
+    request: &MfaRequest<'_>,
+) -> Result<MfaResponse, String> {
+    let response = webhook
+        .client
+        .post(&webhook.url)
+        .headers(webhook.headers.clone())
+        .body(
+            serde_json::to_string(request)
+                .map_err(|err| format!("Failed to serialize MFA request: {}", err))?,
+        )
+        .send()
+        .await
+        .map_err(|err| format!("MFA request failed: {err}"))?;
+
+    if response.status().is_success() {
+        serde_json::from_slice(
+            response
+                .bytes_with_limit(MAX_RESPONSE_SIZE)
+                .await
+                .map_err(|err| format!("Failed to parse MFA response: {}", err))?
+                .ok_or_else(|| "MFA response too large".to_string())?
+                .as_ref(),
+        )
+        .map_err(|err| format!("Failed to parse MFA response: {}", err))
+    } else {
+        Err(format!(
+            "MFA request failed with code {}: {}",
+            response.status().as_u16(),
+            response.status().canonical_reason().unwrap_or("Unknown")
+        ))
+    }
+}
+
+impl ResolveVariable for MfaResolver<'_> {
+    fn resolve_variable(&self, variable: u32) -> Variable<'_> {
+        match variable {
+            V_AUTHENTICATED_AS => self.account_name.into(),
+            V_REMOTE_IP => self.remote_ip.to_string().into(),
+            _ => "".into(),
+        }
+    }
+}
@@ -9,7 +9,16 @@ use jmap_proto::types::collection::Collection;
 use utils::map::{bitmap::Bitmap, vec_map::VecMap};
 
 pub mod access_token;
+pub mod bandwidth;
+pub mod keyring;
+pub mod last_login;
+pub mod mfa;
+pub mod oauth;
+pub mod oidc;
 pub mod roles;
+pub mod scram;
+pub mod sessions;
+pub mod throttle;
 
 #[derive(Debug, Clone, Default)]
 pub struct AccessToken {
@@ -21,6 +30,7 @@ pub struct AccessToken {
     pub quota: u64,
     pub permissions: Permissions,
     pub tenant: Option<TenantInfo>,
+    pub permissions_version: u8,
 }
 
 #[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
@@ -0,0 +1,148 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use utils::map::bitmap::{Bitmap, BitmapItem};
+
+pub mod crypto;
+pub mod token;
+
+pub const RANDOM_CODE_LEN: usize = 32;
+pub const CLIENT_ID_MAX_LEN: usize = 20;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum OAuthScope {
+    Imap = 0,
+    Pop3 = 1,
+    Smtp = 2,
+    Jmap = 3,
+    Sieve = 4,
+    Admin = 5,
+    OpenId = 6,
+    Email = 7,
+    Profile = 8,
+    OfflineAccess = 9,
+    None = 10,
+}
+
+pub type OAuthScopes = Bitmap<OAuthScope>;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TokenInfo {
+    pub account_id: u32,
+    pub client_id: String,
+    pub grant_id: u64,
+    pub scopes: OAuthScopes,
+    pub expires_in: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CachedToken {
+    pub grant_type: String,
+    pub audience: Option<String>,
+    pub account_id: u32,
+    pub client_id: String,
+    pub grant_id: u64,
+    pub scopes: OAuthScopes,
+    pub expiry: u64,
+    pub cached_at: u64,
+}
+
+impl OAuthScope {
+    pub fn parse(scope: &str) -> Option<Self> {
+        match scope {
+            "imap" => Some(OAuthScope::Imap),
+            "pop3" => Some(OAuthScope::Pop3),
+            "smtp" => Some(OAuthScope::Smtp),
+            "jmap" => Some(OAuthScope::Jmap),
+            "sieve" => Some(OAuthScope::Sieve),
+            "admin" => Some(OAuthScope::Admin),
+            "openid" => Some(OAuthScope::OpenId),
+            "email" => Some(OAuthScope::Email),
+            "profile" => Some(OAuthScope::Profile),
+            "offline_access" => Some(OAuthScope::OfflineAccess),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OAuthScope::Imap => "imap",
+            OAuthScope::Pop3 => "pop3",
+            OAuthScope::Smtp => "smtp",
+            OAuthScope::Jmap => "jmap",
+            OAuthScope::Sieve => "sieve",
+            OAuthScope::Admin => "admin",
+            OAuthScope::OpenId => "openid",
+            OAuthScope::Email => "email",
+            OAuthScope::Profile => "profile",
+            OAuthScope::OfflineAccess => "offline_access",
+            OAuthScope::None => "",
+        }
+    }
+
+    // Parses a space-delimited scope parameter, a missing scope grants all scopes
+    pub fn parse_scopes(scope: Option<&str>) -> Result<OAuthScopes, &'static str> {
+        match scope
+            .map(|scope| scope.trim())
+            .filter(|scope| !scope.is_empty())
+        {
+            Some(scope) => {
+                let mut scopes = OAuthScopes::new();
+                for scope in scope.split_ascii_whitespace() {
+                    scopes.insert(OAuthScope::parse(scope).ok_or("Invalid scope.")?);
+                }
+                Ok(scopes)
+            }
+            None => Ok(OAuthScopes::all()),
+        }
+    }
+
+    pub fn list_scopes(scopes: OAuthScopes) -> Vec<&'static str> {
+        (0..OAuthScope::max())
+            .map(OAuthScope::from)
+            .filter(|scope| scopes.contains(*scope))
+            .map(|scope| scope.as_str())
+            .collect()
+    }
+
+    pub fn format_scopes(scopes: OAuthScopes) -> String {
+        OAuthScope::list_scopes(scopes).join(" ")
+    }
+}
+
+impl From<u64> for OAuthScope {
+    fn from(value: u64) -> Self {
+        match value {
+            0 => OAuthScope::Imap,
+            1 => OAuthScope::Pop3,
+            2 => OAuthScope::Smtp,
+            3 => OAuthScope::Jmap,
+            4 => OAuthScope::Sieve,
+            5 => OAuthScope::Admin,
+            6 => OAuthScope::OpenId,
+            7 => OAuthScope::Email,
+            8 => OAuthScope::Profile,
+            9 => OAuthScope::OfflineAccess,
+            _ => OAuthScope::None,
+        }
+    }
+}
+
+impl From<OAuthScope> for u64 {
+    fn from(scope: OAuthScope) -> u64 {
+        scope as u64
+    }
+}
+
+impl BitmapItem for OAuthScope {
+    fn max() -> u64 {
+        OAuthScope::None as u64
+    }
+
+    fn is_valid(&self) -> bool {
+        !matches!(self, OAuthScope::None)
+    }
+}
@@ -0,0 +1,550 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use std::{
+    net::IpAddr,
+    time::{Duration, Instant, SystemTime},
+};
+
+use directory::{backend::internal::PrincipalField, DirectoryInner, QueryBy};
+use mail_builder::encoders::base64::base64_encode;
+use mail_parser::decoders::base64::base64_decode;
+use store::{
+    blake3,
+    rand::{thread_rng, Rng},
+};
+use utils::{
+    codec::leb128::{Leb128Iterator, Leb128Vec},
+    map::ttl_dashmap::TtlMap,
+};
+
+use crate::{auth::last_login::LoginKind, Core};
+
+use super::{
+    crypto::SymmetricEncrypt, CachedToken, OAuthScopes, TokenInfo, CLIENT_ID_MAX_LEN,
+    RANDOM_CODE_LEN,
+};
+
+const NONCE_SALT_LEN: usize = 16;
+const TOKEN_VERSION: u8 = 1;
+const TOKEN_CIPHERTEXT_LEN: usize = RANDOM_CODE_LEN + SymmetricEncrypt::ENCRYPT_TAG_LEN;
+pub const AUDIENCE_GRANT_TYPE: &str = "audience";
+
+impl Core {
+    #[allow(clippy::too_many_arguments)]
+    pub fn encode_access_token(
+        &self,
+        grant_type: &str,
+        account_id: u32,
+        secret: &TokenSecret,
+        client_id: &str,
+        grant_id: u64,
+        scopes: OAuthScopes,
+        audience: Option<&str>,
+        expiry_in: u64,
+    ) -> Result<String, &'static str> {
+        // Build context
+        if client_id.len() > CLIENT_ID_MAX_LEN {
+            return Err("ClientId is too long");
+        }
+        let key = self.jmap.oauth_keys.active();
+        let context = secret.context(
+            grant_type, client_id, account_id, grant_id, scopes, audience,
+        );
+        let context_nonce = secret.nonce_context(grant_type);
+
+        // Set expiration time
+        let expiry = SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0)
+                .saturating_sub(946684800) // Jan 1, 2000
+                + expiry_in;
+
+        // Calculate nonce, the salt keeps it unique for identical contexts and expiries
+        let salt = thread_rng().gen::<[u8; NONCE_SALT_LEN]>();
+        let nonce = token_nonce(&context_nonce, expiry, &salt);
+
+        // Encrypt random bytes
+        let mut token = Vec::with_capacity(1 + TOKEN_CIPHERTEXT_LEN + NONCE_SALT_LEN + 32);
+        token.push(TOKEN_VERSION);
+        token.extend_from_slice(
+            &SymmetricEncrypt::new(key.secret.as_bytes(), &context)
+                .encrypt(&thread_rng().gen::<[u8; RANDOM_CODE_LEN]>(), &nonce)
+                .map_err(|_| "Failed to encrypt token.")?,
+        );
+        token.extend_from_slice(&salt);
+        token.push_leb128(account_id);
+        token.push_leb128(expiry);
+        token.push_leb128(grant_id);
+        token.push_leb128(*scopes);
+        token.push_leb128(key.id.len());
+        token.extend_from_slice(key.id.as_bytes());
+        token.extend_from_slice(client_id.as_bytes());
+
+        Ok(String::from_utf8(base64_encode(&token).unwrap_or_default()).unwrap())
+    }
+
+    pub async fn validate_access_token(
+        &self,
+        grant_type: &str,
+        audience: Option<&str>,
+        token_: &str,
+    ) -> trc::Result<TokenInfo> {
+        let now = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0)
+            .saturating_sub(946684800); // Jan 1, 2000
+
+        // Tokens validated recently skip the store lookups and decryption, they are
+        // dropped from the cache as soon as they are revoked on any node
+        let token = match self
+            .security
+            .oauth_tokens
+            .get_with_ttl(token_)
+            .filter(|token| token.grant_type == grant_type && token.audience.as_deref() == audience)
+        {
+            Some(token) if token.expiry > now => token,
+            Some(_) => {
+                self.security.oauth_tokens.remove(token_);
+                return Err(trc::AuthEvent::TokenExpired
+                    .into_err()
+                    .ctx(trc::Key::Reason, "Token expired"));
+            }
+            None => {
+                let token = self
+                    .decrypt_access_token(grant_type, audience, token_, now)
+                    .await?;
+
+                // Revoking all the account's sessions or moving it to another tenant
+                // changes the encryption context, individual revocations are stored
+                if self.is_token_revoked(token_, token.grant_id).await? {
+                    return Err(trc::AuthEvent::TokenRevoked
+                        .into_err()
+                        .ctx(trc::Key::Reason, "Token revoked"));
+                }
+
+                self.security.oauth_tokens.insert_with_ttl(
+                    token_.to_string(),
+                    token.clone(),
+                    Instant::now()
+                        + std::cmp::min(
+                            self.jmap.session_cache_ttl,
+                            Duration::from_secs(token.expiry - now),
+                        ),
+                );
+                token
+            }
+        };
+
+        // Success
+        Ok(TokenInfo {
+            account_id: token.account_id,
+            client_id: token.client_id,
+            grant_id: token.grant_id,
+            scopes: token.scopes,
+            expires_in: token.expiry - now,
+        })
+    }
+
+    // Validates an access token presented by a client, which counts as account use
+    pub async fn authenticate_access_token(
+        &self,
+        token: &str,
+        remote_ip: IpAddr,
+        session_id: u64,
+    ) -> trc::Result<TokenInfo> {
+        let token_info = self
+            .validate_access_token("access_token", None, token)
+            .await?;
+        self.record_login(
+            token_info.account_id,
+            remote_ip,
+            LoginKind::Seen,
+            session_id,
+        )
+        .await;
+        Ok(token_info)
+    }
+
+    async fn decrypt_access_token(
+        &self,
+        grant_type: &str,
+        audience: Option<&str>,
+        token_: &str,
+        now: u64,
+    ) -> trc::Result<CachedToken> {
+        // Base64 decode token
+        let token = base64_decode(token_.as_bytes()).ok_or_else(|| {
+            trc::AuthEvent::TokenInvalid
+                .into_err()
+                .ctx(trc::Key::Reason, "Failed to decode token")
+                .caused_by(trc::location!())
+                .details(token_.to_string())
+        })?;
+
+        // Tokens issued before versioning was introduced start with the
+        // ciphertext, which may also begin with the version byte by chance
+        if token.first() == Some(&TOKEN_VERSION) {
+            match self
+                .decrypt_token_v1(grant_type, audience, token_, &token[1..], now)
+                .await
+            {
+                Ok(token) => Ok(token),
+                Err(err) if audience.is_none() => self
+                    .decrypt_legacy_token(grant_type, &token, now)
+                    .await
+                    .map_err(|_| err),
+                Err(err) => Err(err),
+            }
+        } else if audience.is_none() {
+            self.decrypt_legacy_token(grant_type, &token, now).await
+        } else {
+            Err(trc::AuthEvent::TokenInvalid
+                .into_err()
+                .ctx(trc::Key::Reason, "Unsupported token version")
+                .caused_by(trc::location!()))
+        }
+    }
+
+    async fn decrypt_token_v1(
+        &self,
+        grant_type: &str,
+        audience: Option<&str>,
+        token_: &str,
+        token: &[u8],
+        now: u64,
+    ) -> trc::Result<CachedToken> {
+        let (salt, account_id, expiry, grant_id, scopes, key_id, client_id) = token
+            .get(TOKEN_CIPHERTEXT_LEN..)
+            .and_then(|bytes| {
+                let (salt, bytes) = (bytes.get(..NONCE_SALT_LEN)?, bytes.get(NONCE_SALT_LEN..)?);
+                let mut bytes = bytes.iter();
+                (
+                    salt,
+                    bytes.next_leb128()?,
+                    bytes.next_leb128::<u64>()?,
+                    bytes.next_leb128::<u64>()?,
+                    OAuthScopes::from(bytes.next_leb128::<u64>()?),
+                    {
+                        let key_id_len = bytes.next_leb128::<usize>()?;
+                        bytes
+                            .by_ref()
+                            .take(key_id_len)
+                            .copied()
+                            .map(char::from)
+                            .collect::<String>()
+                    },
+                    bytes.copied().map(char::from).collect::<String>(),
+                )
+                    .into()
+            })
+            .ok_or_else(|| {
+                trc::AuthEvent::TokenInvalid
+                    .into_err()
+                    .ctx(trc::Key::Reason, "Failed to decode token")
+                    .caused_by(trc::location!())
+                    .details(token_.to_string())
+            })?;
+
+        // Validate expiration
+        if expiry <= now {
+            return Err(trc::AuthEvent::TokenExpired
+                .into_err()
+                .ctx(trc::Key::Reason, "Token expired"));
+        }
+
+        // Obtain password hash and realm
+        let secret = self
+            .token_secret(account_id)
+            .await
+            .map_err(|err| trc::AuthEvent::Error.into_err().ctx(trc::Key::Details, err))?;
+
+        // Obtain the key the token was issued with
+        let key = self.jmap.oauth_keys.get(&key_id).ok_or_else(|| {
+            trc::AuthEvent::TokenInvalid
+                .into_err()
+                .ctx(trc::Key::Reason, "Unknown token key")
+                .id(key_id.clone())
+        })?;
+
+        // Build context, tokens issued for another tenant will fail to decrypt
+        let context = secret.context(
+            grant_type, &client_id, account_id, grant_id, scopes, audience,
+        );
+        let context_nonce = secret.nonce_context(grant_type);
+
+        // Calculate nonce
+        let nonce = token_nonce(&context_nonce, expiry, &salt);
+
+        // Decrypt
+        SymmetricEncrypt::new(key.secret.as_bytes(), &context)
+            .decrypt(&token[..TOKEN_CIPHERTEXT_LEN], &nonce)
+            .map_err(|err| {
+                trc::AuthEvent::TokenInvalid
+                    .into_err()
+                    .ctx(trc::Key::Details, "Failed to decode token")
+                    .caused_by(trc::location!())
+                    .reason(err)
+            })?;
+
+        Ok(CachedToken {
+            grant_type: grant_type.to_string(),
+            audience: audience.map(|audience| audience.to_string()),
+            account_id,
+            client_id,
+            grant_id,
+            scopes,
+            expiry,
+            cached_at: store::write::now(),
+        })
+    }
+
+    // Tokens issued by previous versions carry the account id, expiry and
+    // client id only, are encrypted with the "oauth.key" secret and grant all
+    // scopes. Each one is treated as its own grant, and they are no longer
+    // valid once the account's sessions are revoked.
+    async fn decrypt_legacy_token(
+        &self,
+        grant_type: &str,
+        token: &[u8],
+        now: u64,
+    ) -> trc::Result<CachedToken> {
+        let (account_id, expiry, client_id) = token
+            .get(TOKEN_CIPHERTEXT_LEN..)
+            .and_then(|bytes| {
+                let mut bytes = bytes.iter();
+                (
+                    bytes.next_leb128::<u32>()?,
+                    bytes.next_leb128::<u64>()?,
+                    bytes.copied().map(char::from).collect::<String>(),
+                )
+                    .into()
+            })
+            .ok_or_else(|| {
+                trc::AuthEvent::TokenInvalid
+                    .into_err()
+                    .ctx(trc::Key::Reason, "Failed to decode token")
+                    .caused_by(trc::location!())
+            })?;
+
+        // Validate expiration
+        if expiry <= now {
+            return Err(trc::AuthEvent::TokenExpired
+                .into_err()
+                .ctx(trc::Key::Reason, "Token expired"));
+        }
+
+        // Obtain password hash
+        let secret = self
+            .token_secret(account_id)
+            .await
+            .map_err(|err| trc::AuthEvent::Error.into_err().ctx(trc::Key::Details, err))?;
+        let key = self
+            .jmap
+            .oauth_keys
+            .get("")
+            .filter(|_| secret.epoch == 0)
+            .ok_or_else(|| {
+                trc::AuthEvent::TokenInvalid
+                    .into_err()
+                    .ctx(trc::Key::Reason, "Token revoked")
+            })?;
+
+        // Decrypt
+        let context = format!(
+            "{} {} {} {}",
+            grant_type, client_id, account_id, secret.password_hash
+        );
+        let context_nonce = format!("{} nonce {}", grant_type, secret.password_hash);
+        SymmetricEncrypt::new(key.secret.as_bytes(), &context)
+            .decrypt(
+                &token[..TOKEN_CIPHERTEXT_LEN],
+                &token_nonce(&context_nonce, expiry, &[]),
+            )
+            .map_err(|err| {
+                trc::AuthEvent::TokenInvalid
+                    .into_err()
+                    .ctx(trc::Key::Details, "Failed to decode token")
+                    .caused_by(trc::location!())
+                    .reason(err)
+            })?;
+
+        Ok(CachedToken {
+            grant_type: grant_type.to_string(),
+            audience: None,
+            account_id,
+            client_id,
+            grant_id: u64::from_be_bytes(
+                blake3::hash(token).as_bytes()[..8]
+                    .try_into()
+                    .unwrap_or_default(),
+            ),
+            scopes: OAuthScopes::all(),
+            expiry,
+            cached_at: store::write::now(),
+        })
+    }
+
+    async fn is_token_revoked(&self, token: &str, grant_id: u64) -> trc::Result<bool> {
+        Ok(self
+            .storage
+            .lookup
+            .key_exists(format!("oauth:revoked-grant:{grant_id}").into_bytes())
+            .await?
+            || self
+                .storage
+                .lookup
+                .key_exists(
+                    format!("oauth:revoked:{}", blake3::hash(token.as_bytes()).to_hex())
+                        .into_bytes(),
+                )
+                .await?)
+    }
+
+    pub async fn token_secret(&self, account_id: u32) -> Result<TokenSecret, &'static str> {
+        let epoch = self
+            .token_epoch(account_id)
+            .await
+            .map_err(|_| "Temporary lookup error")?;
+
+        if let Some(fallback_admin) = self.jmap.fallback_admin(account_id) {
+            Ok(TokenSecret {
+                password_hash: fallback_admin.secret.clone(),
+                password_bound: true,
+                realm: None,
+                epoch,
+            })
+        } else {
+            let mut principal = self
+                .storage
+                .directory
+                .query(QueryBy::Id(account_id), false)
+                .await
+                .map_err(|_| "Temporary lookup error")?
+                .ok_or("Account no longer exists")?;
+
+            Ok(TokenSecret {
+                realm: principal.tenant(),
+                epoch,
+                // Password changes in the internal directory bump the epoch instead,
+                // which keeps tokens valid when the stored hash is upgraded on login
+                password_bound: !matches!(
+                    self.storage.directory.store,
+                    DirectoryInner::Internal(_)
+                ),
+                password_hash: principal
+                    .take_str_array(PrincipalField::Secrets)
+                    .unwrap_or_default()
+                    .into_iter()
+                    .next()
+                    .ok_or("Failed to obtain password hash")?,
+            })
+        }
+    }
+}
+
+fn token_nonce(context_nonce: &str, expiry: u64, salt: &[u8]) -> Vec<u8> {
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(context_nonce.as_bytes());
+    hasher.update(expiry.to_be_bytes().as_slice());
+    hasher.update(salt);
+    hasher
+        .finalize()
+        .as_bytes()
+        .iter()
+        .take(SymmetricEncrypt::NONCE_LEN)
+        .copied()
+        .collect()
+}
+
+// Secrets the token is bound to, the realm is the tenant the account belongs to
+// and the epoch is bumped every time the account's sessions are revoked
+pub struct TokenSecret {
+    password_hash: String,
+    password_bound: bool,
+    realm: Option<u32>,
+    epoch: u64,
+}
+
+impl TokenSecret {
+    fn context(
+        &self,
+        grant_type: &str,
+        client_id: &str,
+        account_id: u32,
+        grant_id: u64,
+        scopes: OAuthScopes,
+        audience: Option<&str>,
+    ) -> String {
+        let mut context = format!(
+            "{} {} {} {} {} {}",
+            grant_type,
+            client_id,
+            account_id,
+            grant_id,
+            *scopes,
+            self.password_binding()
+        );
+
+        // The audience is length prefixed so it cannot be confused with the realm
+        if let Some(audience) = audience {
+            context.push_str(&format!(" audience:{}:{audience}", audience.len()));
+        }
+
+        // Tokens issued for the default tenant do not include a realm
+        if let Some(realm) = self.realm {
+            context.push_str(&format!(" realm:{realm}"));
+        }
+
+        // Accounts whose sessions were never revoked do not include an epoch
+        if self.epoch > 0 {
+            context.push_str(&format!(" epoch:{}", self.epoch));
+        }
+
+        context
+    }
+
+    fn nonce_context(&self, grant_type: &str) -> String {
+        format!("{} nonce {}", grant_type, self.password_binding())
+    }
+
+    fn password_binding(&self) -> &str {
+        if self.password_bound {
+            &self.password_hash
+        } else {
+            ""
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{token_nonce, SymmetricEncrypt, NONCE_SALT_LEN, RANDOM_CODE_LEN};
+
+    #[test]
+    fn token_nonce_is_unique() {
+        // Two tokens for the same account and grant minted in the same second
+        let context_nonce = "access_token nonce hash";
+        let expiry = 1_000_000;
+        let salts = [[1u8; NONCE_SALT_LEN], [2u8; NONCE_SALT_LEN]];
+        let nonces = salts.map(|salt| token_nonce(context_nonce, expiry, &salt));
+        assert_eq!(nonces[0].len(), SymmetricEncrypt::NONCE_LEN);
+        assert_ne!(nonces[0], nonces[1]);
+        assert_eq!(nonces[0], token_nonce(context_nonce, expiry, &salts[0]));
+
+        // The same payload encrypts differently under each nonce
+        let cipher = SymmetricEncrypt::new(b"secret", "access_token web 1 2 3 hash");
+        let payload = [0u8; RANDOM_CODE_LEN];
+        let tokens = nonces
+            .each_ref()
+            .map(|nonce| cipher.encrypt(&payload, nonce).unwrap());
+        assert_ne!(tokens[0], tokens[1]);
+        assert_eq!(cipher.decrypt(&tokens[1], &nonces[1]).unwrap(), payload);
+        assert!(cipher.decrypt(&tokens[1], &nonces[0]).is_err());
+    }
+}
@@ -4,7 +4,7 @@
  * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
  */
 
-use std::sync::{Arc, LazyLock};
+use std::sync::{atomic::Ordering, Arc, LazyLock};
 
 use ahash::AHashSet;
 use directory::{
@@ -13,7 +13,9 @@ use directory::{
 };
 use trc::AddContext;
 
-use crate::Core;
+use crate::{Core, Security};
+
+use super::AccessToken;
 
 #[derive(Debug, Clone, Default)]
 pub struct RolePermissions {
@@ -156,6 +158,41 @@ impl Core {
     }
 }
 
+impl Security {
+    // Drops the cached permissions of a role or tenant and bumps the version,
+    // so access tokens built before the change are resolved again on their
+    // next use. Roles inheriting from it cache their own copy, use
+    // `bump_permissions_version` when other roles may include it.
+    pub fn invalidate_role_permissions(&self, role_id: u32) {
+        self.permissions.remove(&role_id);
+        self.permissions_version.fetch_add(1, Ordering::Relaxed);
+    }
+
+    // Drops all cached role permissions and returns the new version. The
+    // counter wraps around, tokens are only compared for equality with it.
+    pub fn bump_permissions_version(&self) -> u8 {
+        self.permissions.clear();
+        self.permissions_version
+            .fetch_add(1, Ordering::Relaxed)
+            .wrapping_add(1)
+    }
+
+    // Drops the cached permissions after a change announced by another node,
+    // without bumping the local version that is gossiped back to the cluster.
+    pub fn clear_permissions(&self) {
+        self.permissions.clear();
+        self.access_tokens.clear();
+    }
+
+    pub fn invalidate_access_token(&self, account_id: u32) {
+        self.access_tokens.remove(&account_id);
+    }
+
+    pub fn is_outdated(&self, access_token: &AccessToken) -> bool {
+        access_token.permissions_version != self.permissions_version.load(Ordering::Relaxed)
+    }
+}
+
 impl RolePermissions {
     pub fn union(&mut self, other: &RolePermissions) {
         self.enabled.union(&other.enabled);
@@ -206,3 +243,45 @@ fn admin_permissions() -> Arc<RolePermissions> {
         disabled: Permissions::new(),
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{atomic::Ordering, Arc};
+
+    use crate::{auth::AccessToken, Security};
+
+    use super::RolePermissions;
+
+    #[test]
+    fn permissions_version() {
+        let security = Security::default();
+        security
+            .permissions_version
+            .store(u8::MAX, Ordering::Relaxed);
+        security
+            .permissions
+            .insert(100, Arc::new(RolePermissions::default()));
+        let access_token = AccessToken {
+            permissions_version: u8::MAX,
+            ..AccessToken::from_id(1)
+        };
+        assert!(!security.is_outdated(&access_token));
+
+        // The version wraps around and still invalidates older tokens
+        assert_eq!(security.bump_permissions_version(), 0);
+        assert!(security.permissions.is_empty());
+        assert!(security.is_outdated(&access_token));
+        assert!(!security.is_outdated(&AccessToken::from_id(1)));
+
+        // Invalidating a single role only drops its own entry
+        for role_id in [100, 101] {
+            security
+                .permissions
+                .insert(role_id, Arc::new(RolePermissions::default()));
+        }
+        security.invalidate_role_permissions(100);
+        assert!(!security.permissions.contains_key(&100));
+        assert!(security.permissions.contains_key(&101));
+        assert!(security.is_outdated(&AccessToken::from_id(1)));
+    }
+}
@@ -0,0 +1,115 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use std::net::IpAddr;
+
+use directory::{
+    backend::internal::{PrincipalField, SpecialSecrets},
+    core::scram::ScramExchange,
+    Directory, Principal, QueryBy,
+};
+
+use crate::{directory_unavailable, Core};
+
+use super::last_login::LoginKind;
+
+impl Core {
+    // Returns the server-first message of a SCRAM exchange. Accounts that
+    // require a TOTP code cannot use SCRAM as the code cannot be conveyed.
+    pub async fn scram_challenge(
+        &self,
+        directory: &Directory,
+        exchange: &mut ScramExchange,
+    ) -> trc::Result<Vec<u8>> {
+        let secret = directory
+            .query(QueryBy::Name(&exchange.username), false)
+            .await
+            .map_err(|err| directory_unavailable(err, &exchange.username))?
+            .filter(|principal| {
+                !principal
+                    .iter_str(PrincipalField::Secrets)
+                    .any(|secret| secret.is_otp_auth())
+            })
+            .and_then(|principal| principal.scram_secret());
+
+        Ok(exchange.server_first(secret))
+    }
+
+    // Verifies the client proof and returns the authenticated principal
+    // along with the server-final message
+    #[allow(clippy::too_many_arguments)]
+    pub async fn authenticate_scram(
+        &self,
+        directory: &Directory,
+        session_id: u64,
+        exchange: &ScramExchange,
+        client_final: &[u8],
+        channel_binding: Option<&[u8]>,
+        remote_ip: IpAddr,
+        return_member_of: bool,
+    ) -> trc::Result<(Principal, Vec<u8>)> {
+        let login = exchange.username.as_str();
+        let principal = match exchange.verify(client_final, channel_binding) {
+            Ok(server_final) => directory
+                .query(QueryBy::Name(login), return_member_of)
+                .await
+                .map_err(|err| directory_unavailable(err, login))?
+                .map(|principal| (principal, server_final)),
+            Err(reason) => {
+                trc::event!(
+                    Auth(trc::AuthEvent::Error),
+                    AccountName = login.to_string(),
+                    SpanId = session_id,
+                    Reason = reason,
+                );
+                None
+            }
+        };
+
+        if let Some((principal, server_final)) = principal {
+            if self
+                .verify_mfa(login, principal.id(), remote_ip, session_id)
+                .await
+            {
+                trc::event!(
+                    Auth(trc::AuthEvent::Success),
+                    AccountName = login.to_string(),
+                    AccountId = principal.id(),
+                    SpanId = session_id,
+                    Type = principal.typ().as_str(),
+                );
+
+                self.reset_auth_failures(remote_ip, login, session_id).await;
+                self.record_login(principal.id(), remote_ip, LoginKind::Login, session_id)
+                    .await;
+
+                return Ok((principal, server_final));
+            }
+
+            trc::event!(
+                Auth(trc::AuthEvent::MfaDenied),
+                AccountName = login.to_string(),
+                AccountId = principal.id(),
+                SpanId = session_id,
+                RemoteIp = remote_ip,
+            );
+        }
+
+        self.throttle_auth_failure(remote_ip, login, session_id)
+            .await;
+
+        if self.has_auth_fail2ban() && self.is_auth_fail2banned(remote_ip, login).await? {
+            Err(trc::SecurityEvent::AuthenticationBan
+                .into_err()
+                .ctx(trc::Key::RemoteIp, remote_ip)
+                .ctx(trc::Key::AccountName, login.to_string()))
+        } else {
+            Err(trc::AuthEvent::Failed
+                .ctx(trc::Key::RemoteIp, remote_ip)
+                .ctx(trc::Key::AccountName, login.to_string()))
+        }
+    }
+}
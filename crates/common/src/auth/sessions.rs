@@ -0,0 +1,150 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use std::{
+    hash::Hash,
+    net::IpAddr,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
+
+use directory::Permission;
+use tokio::sync::watch;
+use utils::map::ttl_dashmap::ADashMap;
+
+use crate::{
+    listener::limiter::{ConcurrencyLimiter, InFlight},
+    Core, Security,
+};
+
+use super::AccessToken;
+
+// Holds the account and remote IP session slots until dropped
+#[derive(Default)]
+pub struct SessionInFlight {
+    _account: Option<InFlight>,
+    _ip: Option<InFlight>,
+}
+
+impl Core {
+    // Reserves a long-lived session slot (IMAP or JMAP EventSource) for the
+    // account and the remote IP.
+    pub fn is_session_allowed(
+        &self,
+        access_token: &AccessToken,
+        remote_ip: IpAddr,
+    ) -> trc::Result<SessionInFlight> {
+        if access_token.has_permission(Permission::UnlimitedRequests) {
+            return Ok(SessionInFlight::default());
+        }
+
+        let mut in_flight = SessionInFlight::default();
+
+        if let Some(max_sessions) = self.network.max_sessions_account {
+            in_flight._account = Some(
+                session_slot(
+                    &self.security.account_sessions,
+                    access_token.primary_id(),
+                    max_sessions,
+                )
+                .ok_or_else(|| {
+                    trc::LimitEvent::ConcurrentConnection
+                        .into_err()
+                        .account_id(access_token.primary_id())
+                        .details("Too many concurrent sessions for this account.")
+                })?,
+            );
+        }
+
+        if let Some(max_sessions) = self.network.max_sessions_ip {
+            in_flight._ip = Some(
+                session_slot(&self.security.ip_sessions, remote_ip, max_sessions).ok_or_else(
+                    || {
+                        trc::LimitEvent::ConcurrentConnection
+                            .into_err()
+                            .ctx(trc::Key::RemoteIp, remote_ip)
+                            .details("Too many concurrent sessions from this address.")
+                    },
+                )?,
+            );
+        }
+
+        Ok(in_flight)
+    }
+
+    // Signs the account out everywhere. Cached access tokens are dropped, OAuth
+    // tokens issued before the new epoch no longer validate and the live
+    // sessions on this node are asked to disconnect.
+    pub async fn revoke_account_sessions(&self, account_id: u32) -> trc::Result<u64> {
+        let epoch = self
+            .storage
+            .lookup
+            .counter_incr(token_epoch_key(account_id), 1, None, true)
+            .await?
+            .max(0) as u64;
+
+        self.security.access_tokens.remove(&account_id);
+        self.invalidate_auth_cache(account_id, &[]).await;
+        if let Some(revocation) = self.security.session_revocations.get(&account_id) {
+            revocation.send_replace(epoch);
+        }
+
+        trc::event!(
+            Auth(trc::AuthEvent::SessionsRevoked),
+            AccountId = account_id,
+            Id = epoch,
+        );
+
+        Ok(epoch)
+    }
+
+    // Epoch included in the encryption context of the OAuth tokens issued to the account
+    pub async fn token_epoch(&self, account_id: u32) -> trc::Result<u64> {
+        self.storage
+            .lookup
+            .counter_get(token_epoch_key(account_id))
+            .await
+            .map(|epoch| epoch.max(0) as u64)
+    }
+
+    // Notifies long-lived sessions when the account's sessions are revoked
+    pub fn subscribe_session_revocation(&self, account_id: u32) -> watch::Receiver<u64> {
+        self.security
+            .session_revocations
+            .entry(account_id)
+            .or_insert_with(|| Arc::new(watch::channel(0).0))
+            .subscribe()
+    }
+}
+
+impl Security {
+    pub fn purge_sessions(&self) {
+        self.account_sessions
+            .retain(|_, sessions| sessions.load(Ordering::Relaxed) > 0);
+        self.ip_sessions
+            .retain(|_, sessions| sessions.load(Ordering::Relaxed) > 0);
+        self.session_revocations
+            .retain(|_, revocation| revocation.receiver_count() > 0);
+    }
+}
+
+fn token_epoch_key(account_id: u32) -> Vec<u8> {
+    format!("auth:epoch:{account_id}").into_bytes()
+}
+
+fn session_slot<K: Hash + Eq>(
+    sessions: &ADashMap<K, Arc<AtomicU64>>,
+    key: K,
+    max_sessions: u64,
+) -> Option<InFlight> {
+    ConcurrencyLimiter {
+        max_concurrent: max_sessions,
+        concurrent: sessions.entry(key).or_default().clone(),
+    }
+    .is_allowed()
+}
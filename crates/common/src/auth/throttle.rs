@@ -0,0 +1,149 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use std::{net::IpAddr, time::Duration};
+
+use utils::config::Config;
+
+use crate::Core;
+
+#[derive(Debug, Clone)]
+pub struct AuthThrottle {
+    pub threshold: u64,
+    pub delay: Duration,
+    pub max_delay: Duration,
+    pub expiry: Duration,
+}
+
+impl AuthThrottle {
+    pub fn parse(config: &mut Config) -> Option<Self> {
+        let delay = config
+            .property_or_default::<Option<Duration>>("server.auth-throttle.delay", "false")
+            .unwrap_or_default()?;
+
+        Some(AuthThrottle {
+            threshold: config
+                .property_or_default("server.auth-throttle.threshold", "3")
+                .unwrap_or(3),
+            delay,
+            max_delay: config
+                .property_or_default("server.auth-throttle.max-delay", "30s")
+                .unwrap_or_else(|| Duration::from_secs(30)),
+            expiry: config
+                .property_or_default("server.auth-throttle.expiry", "1h")
+                .unwrap_or_else(|| Duration::from_secs(3600)),
+        })
+    }
+
+    // The delay doubles with every failure past the threshold
+    pub fn delay_for(&self, failures: u64) -> Option<Duration> {
+        if failures > self.threshold {
+            let exponent = (failures - self.threshold - 1).min(31) as u32;
+            Some(self.delay.saturating_mul(1 << exponent).min(self.max_delay))
+        } else {
+            None
+        }
+    }
+}
+
+impl Core {
+    pub(crate) async fn throttle_auth_failure(&self, ip: IpAddr, login: &str, session_id: u64) {
+        if let Some(throttle) = &self.network.auth_throttle {
+            if self.is_ip_allowed(&ip) {
+                return;
+            }
+
+            let failures = match self
+                .storage
+                .lookup
+                .counter_incr(
+                    throttle_key(ip, login),
+                    1,
+                    Some(throttle.expiry.as_secs()),
+                    true,
+                )
+                .await
+            {
+                Ok(failures) => failures.max(0) as u64,
+                Err(err) => {
+                    trc::error!(err
+                        .span_id(session_id)
+                        .details("Failed to increment authentication failure counter"));
+                    return;
+                }
+            };
+
+            if let Some(delay) = throttle.delay_for(failures) {
+                trc::event!(
+                    Auth(trc::AuthEvent::Throttled),
+                    SpanId = session_id,
+                    RemoteIp = ip,
+                    AccountName = login.to_string(),
+                    Total = failures,
+                    Elapsed = delay,
+                );
+
+                tokio::time::sleep(delay).await;
+            }
+        }
+    }
+
+    pub(crate) async fn reset_auth_failures(&self, ip: IpAddr, login: &str, session_id: u64) {
+        if self.network.auth_throttle.is_some() {
+            // Avoid a write on every successful login
+            let key = throttle_key(ip, login);
+            let result = match self.storage.lookup.counter_get(key.clone()).await {
+                Ok(failures) if failures > 0 => self.storage.lookup.counter_delete(key).await,
+                Ok(_) => Ok(()),
+                Err(err) => Err(err),
+            };
+
+            if let Err(err) = result {
+                trc::error!(err
+                    .span_id(session_id)
+                    .details("Failed to reset authentication failure counter"));
+            }
+        }
+    }
+}
+
+fn throttle_key(ip: IpAddr, login: &str) -> Vec<u8> {
+    format!("t:{ip}:{login}").into_bytes()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::AuthThrottle;
+
+    #[test]
+    fn auth_throttle_delay() {
+        let throttle = AuthThrottle {
+            threshold: 3,
+            delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(10),
+            expiry: Duration::from_secs(3600),
+        };
+
+        for (failures, expected) in [
+            (0, None),
+            (3, None),
+            (4, Some(1)),
+            (5, Some(2)),
+            (6, Some(4)),
+            (7, Some(8)),
+            (8, Some(10)),
+            (1000, Some(10)),
+        ] {
+            assert_eq!(
+                throttle.delay_for(failures),
+                expected.map(Duration::from_secs),
+                "failures: {failures}"
+            );
+        }
+    }
+}
@@ -4,22 +4,27 @@
  * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
  */
 
-use std::time::Duration;
+use std::{net::IpAddr, time::Duration};
 
-use utils::config::{Config, Rate};
+use utils::config::{ipmask::IpAddrMask, Config, Rate};
 
 #[derive(Default, Clone)]
 pub struct ImapConfig {
     pub max_request_size: usize,
+    pub max_message_size: usize,
     pub max_auth_failures: u32,
     pub allow_plain_auth: bool,
 
     pub timeout_auth: Duration,
     pub timeout_unauth: Duration,
     pub timeout_idle: Duration,
+    pub timeout_idle_done: Duration,
+    pub idle_max_duration: Option<Duration>,
 
     pub rate_requests: Option<Rate>,
     pub rate_concurrent: Option<u64>,
+
+    pub unauthenticate_networks: Vec<IpAddrMask>,
 }
 
 impl ImapConfig {
@@ -28,6 +33,9 @@ impl ImapConfig {
             max_request_size: config
                 .property_or_default("imap.request.max-size", "52428800")
                 .unwrap_or(52428800),
+            max_message_size: config
+                .property_or_default("imap.append.max-size", "52428800")
+                .unwrap_or(52428800),
             max_auth_failures: config
                 .property_or_default("imap.auth.max-failures", "3")
                 .unwrap_or(3),
@@ -40,6 +48,12 @@ impl ImapConfig {
             timeout_idle: config
                 .property_or_default("imap.timeout.idle", "30m")
                 .unwrap_or_else(|| Duration::from_secs(1800)),
+            timeout_idle_done: config
+                .property_or_default("imap.timeout.idle-done", "1m")
+                .unwrap_or_else(|| Duration::from_secs(60)),
+            idle_max_duration: config
+                .property_or_default::<Option<Duration>>("imap.idle.max-duration", "1h")
+                .unwrap_or_else(|| Some(Duration::from_secs(3600))),
             rate_requests: config
                 .property_or_default::<Option<Rate>>("imap.rate-limit.requests", "2000/1m")
                 .unwrap_or_default(),
@@ -49,6 +63,19 @@ impl ImapConfig {
             allow_plain_auth: config
                 .property_or_default("imap.auth.allow-plain-text", "false")
                 .unwrap_or(false),
+            unauthenticate_networks: config
+                .properties::<IpAddrMask>("imap.auth.unauthenticate.trusted-networks")
+                .into_iter()
+                .map(|(_, network)| network)
+                .collect(),
         }
     }
+
+    // UNAUTHENTICATE lets a connection be reused by another user, so it is
+    // only offered to trusted proxies that pool connections
+    pub fn is_unauthenticate_allowed(&self, remote_ip: &IpAddr) -> bool {
+        self.unauthenticate_networks
+            .iter()
+            .any(|network| network.matches(remote_ip))
+    }
 }
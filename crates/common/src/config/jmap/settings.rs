@@ -4,14 +4,136 @@
  * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
  */
 
-use std::{str::FromStr, time::Duration};
+use std::{path::PathBuf, sync::Arc, time::Duration};
 
+use ahash::AHashMap;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use directory::ROLE_USER;
+use hyper::{header::AUTHORIZATION, HeaderMap};
 use jmap_proto::request::capability::BaseCapabilities;
 use mail_parser::HeaderName;
 use nlp::language::Language;
-use store::rand::{distributions::Alphanumeric, thread_rng, Rng};
 use utils::config::{cron::SimpleCron, utils::ParseValue, Config, Rate};
 
+use crate::{
+    auth::{keyring::OAuthKeyRing, mfa::MfaWebhook, oidc::OidcSigningKey},
+    config::parse_http_headers,
+    expr::{if_block::IfBlock, tokenizer::TokenMap, V_AUTHENTICATED_AS},
+};
+
+// The single fallback administrator keeps the account id used by previous
+// versions, named ones are assigned an id derived from their user name that
+// stays clear of the reserved role ids.
+pub const FALLBACK_ADMIN_ID: u32 = u32::MAX;
+const FALLBACK_ADMIN_ID_LAST: u32 = ROLE_USER - 1;
+const FALLBACK_ADMIN_ID_RANGE: u32 = 1 << 20;
+
+#[derive(Clone)]
+pub struct FallbackAdmin {
+    pub id: u32,
+    pub user: String,
+    pub secret: String,
+    pub config_key: String,
+}
+
+#[derive(Clone)]
+pub struct AppendDedup {
+    pub window: IfBlock,
+    pub key: DedupKey,
+}
+
+#[derive(Clone, Debug)]
+pub struct RetentionPolicy {
+    pub mailbox: String,
+    pub period: Duration,
+}
+
+#[derive(Clone)]
+pub struct UploadPolicy {
+    pub max_size: IfBlock,
+    pub allowed_types: IfBlock,
+    pub denied_types: IfBlock,
+}
+
+#[derive(Clone, Debug)]
+pub struct QuotaWarning {
+    pub thresholds: Vec<u64>,
+    pub interval: Duration,
+    pub notify: bool,
+    pub from: String,
+}
+
+#[derive(Clone, Debug)]
+pub struct OAuthClient {
+    pub redirect_uris: Vec<String>,
+    pub grant_types: Vec<OAuthGrant>,
+    pub expiry_token: Option<u64>,
+    pub expiry_refresh_token: Option<u64>,
+    pub require_pkce: Option<bool>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum OAuthGrant {
+    AuthorizationCode,
+    DeviceCode,
+    RefreshToken,
+}
+
+#[derive(Clone)]
+pub struct SpamScanner {
+    pub url: String,
+    pub client: reqwest::Client,
+    pub headers: HeaderMap,
+    pub fail_open: bool,
+}
+
+#[derive(Clone, Debug)]
+pub struct HeaderRewrite {
+    pub action: HeaderRewriteAction,
+    pub name: String,
+    pub value: String,
+    pub sources: Vec<String>,
+}
+
+#[derive(Clone, Debug)]
+pub struct IngestMirror {
+    pub address: String,
+    pub domains: Vec<String>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum HeaderRewriteAction {
+    Add,
+    Remove,
+    Replace,
+}
+
+#[derive(Clone, Debug)]
+pub struct KeywordPolicy {
+    pub rules: Vec<KeywordRule>,
+    pub action: KeywordAction,
+    pub max_custom: Option<usize>,
+}
+
+#[derive(Clone, Debug)]
+pub struct KeywordRule {
+    pub mailbox: Option<String>,
+    pub allow: Vec<String>,
+    pub deny: Vec<String>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum KeywordAction {
+    Reject,
+    Drop,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum DedupKey {
+    MessageId,
+    BodyHash,
+}
+
 #[derive(Default, Clone)]
 pub struct JmapConfig {
     pub default_language: Language,
@@ -34,6 +156,7 @@ pub struct JmapConfig {
     pub upload_tmp_quota_size: usize,
     pub upload_tmp_quota_amount: usize,
     pub upload_tmp_ttl: u64,
+    pub upload_policy: Option<UploadPolicy>,
 
     pub mailbox_max_depth: usize,
     pub mailbox_name_max_len: usize,
@@ -41,6 +164,11 @@ pub struct JmapConfig {
     pub mail_parse_max_items: usize,
     pub mail_max_size: usize,
     pub mail_autoexpunge_after: Option<Duration>,
+    pub mail_retention: Vec<RetentionPolicy>,
+    pub mail_retention_exempt: Option<String>,
+    pub mail_import_dir: Option<PathBuf>,
+    pub delivery_max_expansion_depth: usize,
+    pub delivery_max_recipients: usize,
 
     pub sieve_max_script_name: usize,
     pub sieve_max_scripts: usize,
@@ -49,8 +177,12 @@ pub struct JmapConfig {
     pub rate_authenticated: Option<Rate>,
     pub rate_authenticate_req: Option<Rate>,
     pub rate_anonymous: Option<Rate>,
+    pub rate_oauth: Option<Rate>,
 
     pub event_source_throttle: Duration,
+    pub event_source_keepalive: Option<Duration>,
+    pub event_source_idle_timeout: Option<Duration>,
+    pub event_source_buffer: usize,
     pub push_max_total: usize,
     pub push_attempt_interval: Duration,
     pub push_attempts_max: u32,
@@ -59,29 +191,42 @@ pub struct JmapConfig {
     pub push_verify_timeout: Duration,
     pub push_throttle: Duration,
 
+    pub state_change_debounce: Option<Duration>,
+
     pub web_socket_throttle: Duration,
     pub web_socket_timeout: Duration,
     pub web_socket_heartbeat: Duration,
 
-    pub oauth_key: String,
+    pub oauth_keys: OAuthKeyRing,
+    pub oauth_signing_key: Option<Arc<OidcSigningKey>>,
     pub oauth_expiry_user_code: u64,
     pub oauth_expiry_auth_code: u64,
     pub oauth_expiry_token: u64,
     pub oauth_expiry_refresh_token: u64,
     pub oauth_expiry_refresh_token_renew: u64,
     pub oauth_max_auth_attempts: u32,
-    pub fallback_admin: Option<(String, String)>,
+    pub oauth_require_pkce: bool,
+    pub oauth_clients: AHashMap<String, OAuthClient>,
+    pub fallback_admins: Vec<FallbackAdmin>,
     pub master_user: Option<(String, String)>,
+    pub mfa_webhook: Option<MfaWebhook>,
 
     pub spam_header: Option<(HeaderName<'static>, String)>,
+    pub spam_scanner: Option<SpamScanner>,
+    pub ingest_headers: Vec<HeaderRewrite>,
+    pub ingest_mirror: Option<IngestMirror>,
     pub default_folders: Vec<DefaultFolder>,
     pub shared_folder: String,
 
     pub http_headers: Vec<(hyper::header::HeaderName, hyper::header::HeaderValue)>,
     pub http_use_forwarded: bool,
+    pub http_resource_max_age: Option<Duration>,
 
     pub encrypt: bool,
     pub encrypt_append: bool,
+    pub append_dedup: Option<AppendDedup>,
+    pub quota_warning: Option<QuotaWarning>,
+    pub keyword_policy: Option<KeywordPolicy>,
 
     pub capabilities: BaseCapabilities,
     pub session_purge_frequency: SimpleCron,
@@ -112,34 +257,10 @@ pub enum SpecialUse {
 impl JmapConfig {
     pub fn parse(config: &mut Config) -> Self {
         // Parse HTTP headers
-        let mut http_headers = config
-            .values("server.http.headers")
-            .map(|(_, v)| {
-                if let Some((k, v)) = v.split_once(':') {
-                    Ok((
-                        hyper::header::HeaderName::from_str(k.trim()).map_err(|err| {
-                            format!(
-                                "Invalid header found in property \"server.http.headers\": {}",
-                                err
-                            )
-                        })?,
-                        hyper::header::HeaderValue::from_str(v.trim()).map_err(|err| {
-                            format!(
-                                "Invalid header found in property \"server.http.headers\": {}",
-                                err
-                            )
-                        })?,
-                    ))
-                } else {
-                    Err(format!(
-                        "Invalid header found in property \"server.http.headers\": {}",
-                        v
-                    ))
-                }
-            })
-            .collect::<Result<Vec<_>, String>>()
-            .map_err(|e| config.new_parse_error("server.http.headers", e))
-            .unwrap_or_default();
+        let mut http_headers = parse_http_headers(config, "server.http.headers")
+            .iter()
+            .map(|(header, value)| (header.clone(), value.clone()))
+            .collect::<Vec<_>>();
 
         // Parse default folders
         let mut default_folders = Vec::new();
@@ -302,6 +423,22 @@ impl JmapConfig {
             mail_autoexpunge_after: config
                 .property_or_default::<Option<Duration>>("jmap.email.auto-expunge", "30d")
                 .unwrap_or_default(),
+            mail_retention: parse_retention_policies(config),
+            mail_retention_exempt: config
+                .property_or_default::<Option<String>>(
+                    "jmap.email.retention.exempt-keyword",
+                    "$retain",
+                )
+                .unwrap_or_default(),
+            mail_import_dir: config
+                .value("jmap.email.import.directory")
+                .map(PathBuf::from),
+            delivery_max_expansion_depth: config
+                .property("jmap.delivery.max-expansion-depth")
+                .unwrap_or(5),
+            delivery_max_recipients: config
+                .property("jmap.delivery.max-recipients")
+                .unwrap_or(1000),
             sieve_max_script_name: config
                 .property("sieve.untrusted.limits.name-length")
                 .unwrap_or(512),
@@ -321,16 +458,11 @@ impl JmapConfig {
             rate_anonymous: config
                 .property_or_default::<Option<Rate>>("jmap.rate-limit.anonymous", "100/1m")
                 .unwrap_or_default(),
-            oauth_key: config
-                .value("oauth.key")
-                .map(|s| s.to_string())
-                .unwrap_or_else(|| {
-                    thread_rng()
-                        .sample_iter(Alphanumeric)
-                        .take(64)
-                        .map(char::from)
-                        .collect::<String>()
-                }),
+            rate_oauth: config
+                .property::<Option<Rate>>("jmap.rate-limit.oauth")
+                .unwrap_or_default(),
+            oauth_keys: OAuthKeyRing::parse(config),
+            oauth_signing_key: OidcSigningKey::parse(config).map(Arc::new),
             oauth_expiry_user_code: config
                 .property_or_default::<Duration>("oauth.expiry.user-code", "30m")
                 .unwrap_or_else(|| Duration::from_secs(30 * 60))
@@ -354,9 +486,21 @@ impl JmapConfig {
             oauth_max_auth_attempts: config
                 .property_or_default("oauth.auth.max-attempts", "3")
                 .unwrap_or(10),
+            oauth_require_pkce: config
+                .property_or_default("oauth.auth.require-pkce", "false")
+                .unwrap_or(false),
             event_source_throttle: config
                 .property_or_default("jmap.event-source.throttle", "1s")
                 .unwrap_or_else(|| Duration::from_secs(1)),
+            event_source_keepalive: config
+                .property_or_default::<Option<Duration>>("jmap.event-source.keepalive", "30s")
+                .unwrap_or_else(|| Some(Duration::from_secs(30))),
+            event_source_idle_timeout: config
+                .property_or_default::<Option<Duration>>("jmap.event-source.idle-timeout", "1h")
+                .unwrap_or_else(|| Some(Duration::from_secs(3600))),
+            event_source_buffer: config
+                .property_or_default("jmap.event-source.buffer-size", "64")
+                .unwrap_or(64),
             web_socket_throttle: config
                 .property_or_default("jmap.web-socket.throttle", "1s")
                 .unwrap_or_else(|| Duration::from_secs(1)),
@@ -390,6 +534,9 @@ impl JmapConfig {
                 .property("server.http.use-x-forwarded")
                 .unwrap_or(false),
             http_headers,
+            http_resource_max_age: config
+                .property_or_default::<Option<Duration>>("server.http.resource-max-age", "false")
+                .unwrap_or_default(),
             push_attempt_interval: config
                 .property_or_default("jmap.push.attempts.interval", "1m")
                 .unwrap_or_else(|| Duration::from_secs(60)),
@@ -408,24 +555,37 @@ impl JmapConfig {
             push_throttle: config
                 .property_or_default("jmap.push.throttle", "1s")
                 .unwrap_or_else(|| Duration::from_secs(1)),
+            state_change_debounce: config
+                .property_or_default::<Option<Duration>>("jmap.state-change.debounce", "50ms")
+                .unwrap_or_else(|| Some(Duration::from_millis(50))),
             session_purge_frequency: config
                 .property_or_default::<SimpleCron>("jmap.session.purge.frequency", "15 * *")
                 .unwrap_or_else(|| SimpleCron::parse_value("15 * *").unwrap()),
             account_purge_frequency: config
                 .property_or_default::<SimpleCron>("jmap.account.purge.frequency", "0 0 *")
                 .unwrap_or_else(|| SimpleCron::parse_value("0 0 *").unwrap()),
-            fallback_admin: config
-                .value("authentication.fallback-admin.user")
-                .and_then(|u| {
+            fallback_admins: parse_fallback_admins(config),
+            oauth_clients: parse_oauth_clients(config),
+            master_user: if config
+                .property_or_default("authentication.master.enable", "true")
+                .unwrap_or(true)
+            {
+                config.value("authentication.master.user").and_then(|u| {
                     config
-                        .value("authentication.fallback-admin.secret")
+                        .value("authentication.master.secret")
                         .map(|p| (u.to_string(), p.to_string()))
-                }),
-            master_user: config.value("authentication.master.user").and_then(|u| {
-                config
-                    .value("authentication.master.secret")
-                    .map(|p| (u.to_string(), p.to_string()))
-            }),
+                })
+            } else {
+                None
+            },
+            mfa_webhook: MfaWebhook::parse(config),
+            spam_scanner: SpamScanner::parse(config),
+            ingest_headers: parse_header_rewrites(config),
+            ingest_mirror: IngestMirror::parse(config),
+            append_dedup: AppendDedup::parse(config),
+            upload_policy: UploadPolicy::parse(config),
+            quota_warning: QuotaWarning::parse(config),
+            keyword_policy: KeywordPolicy::parse(config),
             default_folders,
             shared_folder,
         };
@@ -436,6 +596,541 @@ impl JmapConfig {
     }
 }
 
+impl JmapConfig {
+    pub fn fallback_admin(&self, account_id: u32) -> Option<&FallbackAdmin> {
+        if account_id > FALLBACK_ADMIN_ID_LAST - FALLBACK_ADMIN_ID_RANGE {
+            self.fallback_admins
+                .iter()
+                .find(|admin| admin.id == account_id)
+        } else {
+            None
+        }
+    }
+
+    // Any client is accepted when no clients have been registered, otherwise
+    // the client has to be allowed to use the grant and, for authorization codes,
+    // the redirect URI has to match one of the registered ones exactly.
+    pub fn is_oauth_client_allowed(
+        &self,
+        client_id: &str,
+        grant: OAuthGrant,
+        redirect_uri: Option<&str>,
+    ) -> bool {
+        if self.oauth_clients.is_empty() {
+            return true;
+        }
+
+        self.oauth_clients.get(client_id).map_or(false, |client| {
+            client.grant_types.contains(&grant)
+                && (grant != OAuthGrant::AuthorizationCode
+                    || client
+                        .redirect_uris
+                        .iter()
+                        .any(|uri| Some(uri.as_str()) == redirect_uri))
+        })
+    }
+
+    pub fn oauth_token_expiry(&self, client_id: &str) -> u64 {
+        self.oauth_clients
+            .get(client_id)
+            .and_then(|client| client.expiry_token)
+            .unwrap_or(self.oauth_expiry_token)
+    }
+
+    pub fn is_pkce_required(&self, client_id: &str) -> bool {
+        self.oauth_clients
+            .get(client_id)
+            .and_then(|client| client.require_pkce)
+            .unwrap_or(self.oauth_require_pkce)
+    }
+
+    // Refresh tokens are not issued to clients that may not use them
+    pub fn oauth_refresh_token_expiry(&self, client_id: &str) -> Option<u64> {
+        if self.is_oauth_client_allowed(client_id, OAuthGrant::RefreshToken, None) {
+            Some(
+                self.oauth_clients
+                    .get(client_id)
+                    .and_then(|client| client.expiry_refresh_token)
+                    .unwrap_or(self.oauth_expiry_refresh_token),
+            )
+        } else {
+            None
+        }
+    }
+}
+
+impl AppendDedup {
+    pub fn parse(config: &mut Config) -> Option<Self> {
+        Some(AppendDedup {
+            window: IfBlock::try_parse(
+                config,
+                "jmap.email.append.dedup.window",
+                &TokenMap::default().with_variables(&[V_AUTHENTICATED_AS]),
+            )?,
+            key: config
+                .property_or_default("jmap.email.append.dedup.key", "message-id")
+                .unwrap_or(DedupKey::MessageId),
+        })
+    }
+}
+
+impl UploadPolicy {
+    pub fn parse(config: &mut Config) -> Option<Self> {
+        let token_map = TokenMap::default().with_variables(&[V_AUTHENTICATED_AS]);
+        let mut parse = |key: &str| {
+            IfBlock::try_parse(config, key, &token_map).unwrap_or_else(|| IfBlock::empty(key))
+        };
+        let policy = UploadPolicy {
+            max_size: parse("jmap.protocol.upload.policy.max-size"),
+            allowed_types: parse("jmap.protocol.upload.policy.allowed-types"),
+            denied_types: parse("jmap.protocol.upload.policy.denied-types"),
+        };
+
+        if !policy.max_size.is_empty()
+            || !policy.allowed_types.is_empty()
+            || !policy.denied_types.is_empty()
+        {
+            Some(policy)
+        } else {
+            None
+        }
+    }
+}
+
+impl IngestMirror {
+    pub fn parse(config: &mut Config) -> Option<Self> {
+        let address = config
+            .value("jmap.email.ingest.mirror.address")?
+            .trim()
+            .to_lowercase();
+        if !address.contains('@') {
+            config.new_parse_error(
+                "jmap.email.ingest.mirror.address",
+                format!("Invalid mirror address {address:?}"),
+            );
+            return None;
+        }
+
+        Some(IngestMirror {
+            address,
+            domains: config
+                .values("jmap.email.ingest.mirror.domains")
+                .map(|(_, domain)| domain.trim().to_lowercase())
+                .collect(),
+        })
+    }
+
+    pub fn matches(&self, recipient: &str) -> bool {
+        let recipient = recipient.to_lowercase();
+
+        // Never mirror the copies delivered to the mirror address itself
+        recipient != self.address
+            && (self.domains.is_empty()
+                || recipient.rsplit_once('@').map_or(false, |(_, domain)| {
+                    self.domains.iter().any(|d| d == domain)
+                }))
+    }
+}
+
+impl KeywordPolicy {
+    pub fn parse(config: &mut Config) -> Option<Self> {
+        let rule_ids = config
+            .sub_keys("jmap.email.keywords.rule", ".mailbox")
+            .map(|id| id.to_string())
+            .collect::<Vec<_>>();
+        let mut rules = Vec::with_capacity(rule_ids.len());
+
+        for rule_id in rule_ids {
+            let rule_id = rule_id.as_str();
+            let mailbox = config
+                .value(("jmap.email.keywords.rule", rule_id, "mailbox"))
+                .unwrap_or_default()
+                .trim()
+                .to_string();
+            let mut lists = [Vec::new(), Vec::new()];
+            for (list, key) in lists.iter_mut().zip(["allow", "deny"]) {
+                for (_, keyword) in config.values(("jmap.email.keywords.rule", rule_id, key)) {
+                    let keyword = keyword.trim().to_lowercase();
+                    if !keyword.is_empty() {
+                        list.push(keyword);
+                    }
+                }
+            }
+            let [allow, deny] = lists;
+
+            // The mailbox is matched by role or name, "*" matches all mailboxes
+            rules.push(KeywordRule {
+                mailbox: (mailbox != "*").then_some(mailbox),
+                allow,
+                deny,
+            });
+        }
+
+        let max_custom = config
+            .property::<usize>("jmap.email.keywords.max-custom")
+            .filter(|max| *max > 0);
+
+        if !rules.is_empty() || max_custom.is_some() {
+            Some(KeywordPolicy {
+                rules,
+                action: config
+                    .property_or_default("jmap.email.keywords.action", "reject")
+                    .unwrap_or(KeywordAction::Reject),
+                max_custom,
+            })
+        } else {
+            None
+        }
+    }
+}
+
+impl KeywordRule {
+    // System flags are always allowed, an empty allow list allows any other keyword
+    pub fn is_allowed(&self, keyword: &str) -> bool {
+        !self.deny.iter().any(|k| k == keyword)
+            && (self.allow.is_empty() || self.allow.iter().any(|k| k == keyword))
+    }
+}
+
+impl QuotaWarning {
+    pub fn parse(config: &mut Config) -> Option<Self> {
+        let mut thresholds = Vec::new();
+        for (key, threshold) in config.properties::<u64>("jmap.quota.warning.thresholds") {
+            if (1..=100).contains(&threshold) {
+                thresholds.push(threshold);
+            } else {
+                config.new_parse_error(key, "Quota warning thresholds must be between 1 and 100");
+            }
+        }
+        if thresholds.is_empty() {
+            return None;
+        }
+        thresholds.sort_unstable();
+        thresholds.dedup();
+
+        Some(QuotaWarning {
+            thresholds,
+            interval: config
+                .property_or_default("jmap.quota.warning.interval", "1d")
+                .unwrap_or_else(|| Duration::from_secs(86400)),
+            notify: config
+                .property_or_default("jmap.quota.warning.notify", "false")
+                .unwrap_or(false),
+            from: config
+                .value("jmap.quota.warning.from")
+                .map(|from| from.to_string())
+                .unwrap_or_else(|| {
+                    format!(
+                        "postmaster@{}",
+                        config
+                            .value("lookup.default.hostname")
+                            .unwrap_or("localhost")
+                    )
+                }),
+        })
+    }
+
+    // Returns the highest threshold reached by the used quota, if any
+    pub fn threshold(&self, used: u64, quota: u64) -> Option<u64> {
+        self.thresholds
+            .iter()
+            .rev()
+            .find(|threshold| used.saturating_mul(100) >= quota.saturating_mul(**threshold))
+            .copied()
+    }
+}
+
+impl SpamScanner {
+    pub fn parse(config: &mut Config) -> Option<Self> {
+        let url = config.value("spam.external.url")?.to_string();
+        let mut headers = parse_http_headers(config, "spam.external.headers");
+
+        if let (Some(name), Some(secret)) = (
+            config.value("spam.external.auth.username"),
+            config.value("spam.external.auth.secret"),
+        ) {
+            headers.insert(
+                AUTHORIZATION,
+                format!("Basic {}", STANDARD.encode(format!("{}:{}", name, secret)))
+                    .parse()
+                    .unwrap(),
+            );
+        }
+
+        let client = reqwest::Client::builder()
+            .timeout(
+                config
+                    .property_or_default("spam.external.timeout", "10s")
+                    .unwrap_or_else(|| Duration::from_secs(10)),
+            )
+            .danger_accept_invalid_certs(
+                config
+                    .property_or_default("spam.external.allow-invalid-certs", "false")
+                    .unwrap_or_default(),
+            )
+            .build()
+            .map_err(|err| {
+                config.new_build_error(
+                    "spam.external.url",
+                    format!("Failed to build HTTP client: {err}"),
+                )
+            })
+            .ok()?;
+
+        Some(SpamScanner {
+            url,
+            client,
+            fail_open: config
+                .property_or_default("spam.external.fail-open", "true")
+                .unwrap_or(true),
+            headers,
+        })
+    }
+}
+
+pub fn parse_oauth_clients(config: &mut Config) -> AHashMap<String, OAuthClient> {
+    let client_ids = config
+        .sub_keys("oauth.client", "")
+        .map(|id| id.to_string())
+        .collect::<Vec<_>>();
+    let mut clients = AHashMap::with_capacity(client_ids.len());
+
+    for client_id in client_ids {
+        let redirect_uris = config
+            .values(("oauth.client", client_id.as_str(), "redirect-uris"))
+            .map(|(_, uri)| uri.to_string())
+            .collect::<Vec<_>>();
+        let mut grant_types = config
+            .properties::<OAuthGrant>(("oauth.client", client_id.as_str(), "grant-types"))
+            .into_iter()
+            .map(|(_, grant)| grant)
+            .collect::<Vec<_>>();
+        if grant_types.is_empty() {
+            grant_types = vec![
+                OAuthGrant::AuthorizationCode,
+                OAuthGrant::DeviceCode,
+                OAuthGrant::RefreshToken,
+            ];
+        }
+
+        clients.insert(
+            client_id.clone(),
+            OAuthClient {
+                redirect_uris,
+                grant_types,
+                expiry_token: config
+                    .property::<Duration>(("oauth.client", client_id.as_str(), "expiry.token"))
+                    .map(|d| d.as_secs()),
+                expiry_refresh_token: config
+                    .property::<Duration>((
+                        "oauth.client",
+                        client_id.as_str(),
+                        "expiry.refresh-token",
+                    ))
+                    .map(|d| d.as_secs()),
+                require_pkce: config.property(("oauth.client", client_id.as_str(), "require-pkce")),
+            },
+        );
+    }
+
+    clients
+}
+
+fn parse_retention_policies(config: &mut Config) -> Vec<RetentionPolicy> {
+    let policy_ids = config
+        .sub_keys("jmap.email.retention", ".period")
+        .map(|id| id.to_string())
+        .collect::<Vec<_>>();
+    let mut policies = Vec::with_capacity(policy_ids.len());
+
+    for policy_id in policy_ids {
+        if let Some(period) = config.property_require::<Duration>((
+            "jmap.email.retention",
+            policy_id.as_str(),
+            "period",
+        )) {
+            // The mailbox defaults to the policy id, which is usually its role
+            let mailbox = config
+                .value(("jmap.email.retention", policy_id.as_str(), "mailbox"))
+                .unwrap_or(policy_id.as_str())
+                .to_string();
+            policies.push(RetentionPolicy { mailbox, period });
+        }
+    }
+
+    policies
+}
+
+fn parse_header_rewrites(config: &mut Config) -> Vec<HeaderRewrite> {
+    let rule_ids = config
+        .sub_keys("jmap.email.ingest.header", ".action")
+        .map(|id| id.to_string())
+        .collect::<Vec<_>>();
+    let mut rules = Vec::with_capacity(rule_ids.len());
+
+    for rule_id in rule_ids {
+        let rule_id = rule_id.as_str();
+        let (action, name) = match (
+            config.property_require::<HeaderRewriteAction>((
+                "jmap.email.ingest.header",
+                rule_id,
+                "action",
+            )),
+            config
+                .value_require(("jmap.email.ingest.header", rule_id, "name"))
+                .map(|name| name.trim().to_string()),
+        ) {
+            (Some(action), Some(name)) => (action, name),
+            _ => continue,
+        };
+        if name.is_empty() || !name.bytes().all(|ch| ch.is_ascii_graphic() && ch != b':') {
+            config.new_parse_error(
+                ("jmap.email.ingest.header", rule_id, "name"),
+                format!("Invalid header name {name:?}"),
+            );
+            continue;
+        }
+        let value = config
+            .value(("jmap.email.ingest.header", rule_id, "value"))
+            .unwrap_or_default()
+            .trim()
+            .to_string();
+        if value.contains(['\r', '\n']) {
+            config.new_parse_error(
+                ("jmap.email.ingest.header", rule_id, "value"),
+                "Header values cannot contain line breaks",
+            );
+            continue;
+        } else if value.is_empty() && action != HeaderRewriteAction::Remove {
+            config.new_parse_error(
+                ("jmap.email.ingest.header", rule_id, "value"),
+                "Missing header value",
+            );
+            continue;
+        }
+
+        // Rules apply to messages delivered over SMTP unless configured otherwise
+        let mut sources = config
+            .values(("jmap.email.ingest.header", rule_id, "source"))
+            .map(|(_, source)| source.to_lowercase())
+            .collect::<Vec<_>>();
+        if sources.is_empty() {
+            sources.push("smtp".to_string());
+        }
+
+        rules.push(HeaderRewrite {
+            action,
+            name,
+            value,
+            sources,
+        });
+    }
+
+    rules
+}
+
+fn parse_fallback_admins(config: &mut Config) -> Vec<FallbackAdmin> {
+    let config_keys = std::iter::once("authentication.fallback-admin".to_string())
+        .chain(
+            config
+                .sub_keys("authentication.fallback-admin", ".user")
+                .map(|id| format!("authentication.fallback-admin.{id}")),
+        )
+        .collect::<Vec<_>>();
+    let mut fallback_admins: Vec<FallbackAdmin> = Vec::with_capacity(config_keys.len());
+
+    for config_key in config_keys {
+        let (user, secret) = match (
+            config.value((&config_key, "user")),
+            config.value((&config_key, "secret")),
+        ) {
+            (Some(user), Some(secret)) => (user.to_string(), secret.to_string()),
+            (Some(_), None) => {
+                config.new_parse_error(
+                    (&config_key, "secret"),
+                    "Missing fallback administrator secret",
+                );
+                continue;
+            }
+            _ => continue,
+        };
+
+        if fallback_admins.iter().any(|admin| admin.user == user) {
+            config.new_parse_error(
+                (&config_key, "user"),
+                format!("Duplicate fallback administrator {user:?}"),
+            );
+            continue;
+        }
+
+        let id = if config_key == "authentication.fallback-admin" {
+            FALLBACK_ADMIN_ID
+        } else {
+            FALLBACK_ADMIN_ID_LAST
+                - (xxhash_rust::xxh3::xxh3_64(user.as_bytes()) % FALLBACK_ADMIN_ID_RANGE as u64)
+                    as u32
+        };
+        if fallback_admins.iter().any(|admin| admin.id == id) {
+            config.new_parse_error(
+                (&config_key, "user"),
+                format!("Fallback administrator {user:?} has a conflicting account id"),
+            );
+            continue;
+        }
+
+        fallback_admins.push(FallbackAdmin {
+            id,
+            user,
+            secret,
+            config_key,
+        });
+    }
+
+    fallback_admins
+}
+
+impl ParseValue for OAuthGrant {
+    fn parse_value(value: &str) -> Result<Self, String> {
+        match value {
+            "authorization_code" => Ok(OAuthGrant::AuthorizationCode),
+            "device_code" => Ok(OAuthGrant::DeviceCode),
+            "refresh_token" => Ok(OAuthGrant::RefreshToken),
+            other => Err(format!("Unknown OAuth grant type {other:?}")),
+        }
+    }
+}
+
+impl ParseValue for HeaderRewriteAction {
+    fn parse_value(value: &str) -> Result<Self, String> {
+        match value {
+            "add" => Ok(HeaderRewriteAction::Add),
+            "remove" => Ok(HeaderRewriteAction::Remove),
+            "replace" => Ok(HeaderRewriteAction::Replace),
+            other => Err(format!("Unknown header rewrite action {other:?}")),
+        }
+    }
+}
+
+impl ParseValue for KeywordAction {
+    fn parse_value(value: &str) -> Result<Self, String> {
+        match value {
+            "reject" => Ok(KeywordAction::Reject),
+            "drop" => Ok(KeywordAction::Drop),
+            other => Err(format!("Unknown keyword policy action {other:?}")),
+        }
+    }
+}
+
+impl ParseValue for DedupKey {
+    fn parse_value(value: &str) -> Result<Self, String> {
+        match value {
+            "message-id" => Ok(DedupKey::MessageId),
+            "body-hash" => Ok(DedupKey::BodyHash),
+            other => Err(format!("Unknown deduplication key {other:?}")),
+        }
+    }
+}
+
 impl ParseValue for SpecialUse {
     fn parse_value(value: &str) -> Result<Self, String> {
         match value {
@@ -451,3 +1146,32 @@ impl ParseValue for SpecialUse {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::QuotaWarning;
+
+    #[test]
+    fn quota_warning_threshold() {
+        let warning = QuotaWarning {
+            thresholds: vec![80, 90, 95],
+            interval: Duration::from_secs(86400),
+            notify: false,
+            from: "postmaster@localhost".to_string(),
+        };
+
+        for (used, expected) in [
+            (0, None),
+            (799, None),
+            (800, Some(80)),
+            (899, Some(80)),
+            (900, Some(90)),
+            (950, Some(95)),
+            (1200, Some(95)),
+        ] {
+            assert_eq!(warning.threshold(used, 1000), expected, "used: {used}");
+        }
+    }
+}
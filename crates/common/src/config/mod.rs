@@ -4,14 +4,18 @@
  * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
  */
 
-use std::sync::Arc;
+use std::{str::FromStr, sync::Arc};
 
 use arc_swap::ArcSwap;
 use directory::{Directories, Directory};
+use hyper::{
+    header::{HeaderName, HeaderValue},
+    HeaderMap,
+};
 use store::{BlobBackend, BlobStore, FtsStore, LookupStore, Store, Stores};
 use telemetry::Metrics;
 use utils::{
-    config::Config,
+    config::{utils::AsKey, Config},
     map::ttl_dashmap::{ADashMap, TtlDashMap, TtlMap},
 };
 
@@ -20,13 +24,15 @@ use crate::{
 };
 
 use self::{
-    imap::ImapConfig, jmap::settings::JmapConfig, scripts::Scripting, smtp::SmtpConfig,
-    storage::Storage,
+    imap::ImapConfig, jmap::settings::JmapConfig, managesieve::ManageSieveConfig, pop3::Pop3Config,
+    scripts::Scripting, smtp::SmtpConfig, storage::Storage,
 };
 
 pub mod imap;
 pub mod jmap;
+pub mod managesieve;
 pub mod network;
+pub mod pop3;
 pub mod scripts;
 pub mod server;
 pub mod smtp;
@@ -157,6 +163,8 @@ impl Core {
             )
         }
 
+        let imap = ImapConfig::parse(config);
+
         Self {
             #[cfg(feature = "enterprise")]
             enterprise,
@@ -164,18 +172,27 @@ impl Core {
             network: Network::parse(config),
             smtp: SmtpConfig::parse(config).await,
             jmap: JmapConfig::parse(config),
-            imap: ImapConfig::parse(config),
+            pop3: Pop3Config::parse(config, &imap),
+            managesieve: ManageSieveConfig::parse(config, &imap),
+            imap,
             tls: TlsManager::parse(config),
             metrics: Metrics::parse(config),
             security: Security {
                 access_tokens: TtlDashMap::with_capacity(100, 32),
+                oauth_tokens: TtlDashMap::with_capacity(100, 32),
                 permissions: ADashMap::with_capacity_and_hasher_and_shard_amount(
                     100,
                     ahash::RandomState::new(),
                     32,
                 ),
                 permissions_version: Default::default(),
+                auth_cache_version: Default::default(),
                 logos: Default::default(),
+                account_sessions: Default::default(),
+                ip_sessions: Default::default(),
+                last_logins: Default::default(),
+                bandwidth: Default::default(),
+                session_revocations: Default::default(),
             },
             storage: Storage {
                 data,
@@ -190,6 +207,7 @@ impl Core {
                 lookups: stores.lookup_stores,
                 blobs: stores.blob_stores,
                 ftss: stores.fts_stores,
+                config_keys: config.keys.clone().into(),
             },
         }
     }
@@ -198,3 +216,42 @@ impl Core {
         Arc::new(ArcSwap::from_pointee(self))
     }
 }
+
+// Parses a list of "Name: value" HTTP headers
+pub fn parse_http_headers(config: &mut Config, key: impl AsKey) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+
+    for (header, value) in config
+        .values(key.clone())
+        .map(|(_, v)| {
+            if let Some((k, v)) = v.split_once(':') {
+                Ok((
+                    HeaderName::from_str(k.trim()).map_err(|err| {
+                        format!(
+                            "Invalid header found in property \"{}\": {err}",
+                            key.as_key()
+                        )
+                    })?,
+                    HeaderValue::from_str(v.trim()).map_err(|err| {
+                        format!(
+                            "Invalid header found in property \"{}\": {err}",
+                            key.as_key()
+                        )
+                    })?,
+                ))
+            } else {
+                Err(format!(
+                    "Invalid header found in property \"{}\": {v}",
+                    key.as_key()
+                ))
+            }
+        })
+        .collect::<Result<Vec<_>, String>>()
+        .map_err(|e| config.new_parse_error(key, e))
+        .unwrap_or_default()
+    {
+        headers.insert(header, value);
+    }
+
+    headers
+}
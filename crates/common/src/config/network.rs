@@ -5,6 +5,7 @@
  */
 
 use crate::{
+    auth::{bandwidth::BandwidthAccounting, last_login::LastLoginTracking, throttle::AuthThrottle},
     expr::{if_block::IfBlock, tokenizer::TokenMap},
     listener::blocked::{AllowedIps, BlockedIps},
     Network,
@@ -39,6 +40,11 @@ impl Default for Network {
                 "protocol + '://' + key_get('default', 'hostname') + ':' + local_port",
             ),
             http_allowed_endpoint: IfBlock::new::<()>("server.http.allowed-endpoint", [], "200"),
+            max_sessions_account: None,
+            max_sessions_ip: None,
+            auth_throttle: None,
+            last_login: None,
+            bandwidth: None,
         }
     }
 }
@@ -49,6 +55,15 @@ impl Network {
             node_id: config.property("cluster.node-id").unwrap_or_default(),
             blocked_ips: BlockedIps::parse(config),
             allowed_ips: AllowedIps::parse(config),
+            auth_throttle: AuthThrottle::parse(config),
+            last_login: LastLoginTracking::parse(config),
+            bandwidth: BandwidthAccounting::parse(config),
+            max_sessions_account: config
+                .property::<Option<u64>>("server.session.max-per-account")
+                .unwrap_or_default(),
+            max_sessions_ip: config
+                .property::<Option<u64>>("server.session.max-per-ip")
+                .unwrap_or_default(),
             ..Default::default()
         };
         let token_map = &TokenMap::default().with_variables(HTTP_VARS);
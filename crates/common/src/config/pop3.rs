@@ -0,0 +1,31 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use std::time::Duration;
+
+use utils::config::Config;
+
+use super::imap::ImapConfig;
+
+#[derive(Default, Clone)]
+pub struct Pop3Config {
+    pub timeout_auth: Duration,
+    pub timeout_unauth: Duration,
+}
+
+impl Pop3Config {
+    // Falls back to the IMAP timeouts when not configured
+    pub fn parse(config: &mut Config, imap: &ImapConfig) -> Self {
+        Pop3Config {
+            timeout_auth: config
+                .property("pop3.timeout.authenticated")
+                .unwrap_or(imap.timeout_auth),
+            timeout_unauth: config
+                .property("pop3.timeout.anonymous")
+                .unwrap_or(imap.timeout_unauth),
+        }
+    }
+}
@@ -10,7 +10,7 @@ use std::{
     time::{Duration, Instant},
 };
 
-use ahash::AHashMap;
+use ahash::{AHashMap, AHashSet};
 use nlp::bayes::cache::BayesTokenCache;
 use parking_lot::RwLock;
 use sieve::{compiler::grammar::Capability, Compiler, Runtime, Sieve};
@@ -31,6 +31,8 @@ pub struct Scripting {
     pub sign: IfBlock,
     pub trusted_scripts: AHashMap<String, Arc<Sieve>>,
     pub untrusted_scripts: AHashMap<String, Arc<Sieve>>,
+    pub untrusted_lookups: AHashSet<String>,
+    pub untrusted_max_lookups: usize,
 }
 
 pub struct ScriptCache {
@@ -99,6 +101,12 @@ impl Scripting {
                     .unwrap_or(3),
             );
 
+        // Lookup stores that untrusted scripts may query using the :list match type
+        let untrusted_lookups = config
+            .values("sieve.untrusted.lookup.stores")
+            .map(|(_, v)| v.to_string())
+            .collect::<AHashSet<_>>();
+
         // Parse untrusted runtime
         let untrusted_runtime = Runtime::new()
             .with_max_nested_includes(
@@ -192,6 +200,7 @@ impl Scripting {
                     .unwrap_or("Auto: ")
                     .to_string(),
             )
+            .with_valid_ext_lists(untrusted_lookups.iter().cloned())
             .with_env_variable("name", "Stalwart Mail Server")
             .with_env_variable("version", env!("CARGO_PKG_VERSION"))
             .with_env_variable("location", "MS")
@@ -332,6 +341,10 @@ impl Scripting {
             untrusted_compiler,
             untrusted_runtime,
             trusted_runtime,
+            untrusted_max_lookups: config
+                .property("sieve.untrusted.limits.lookups")
+                .unwrap_or(10),
+            untrusted_lookups,
             from_addr: IfBlock::try_parse(config, "sieve.trusted.from-addr", &token_map)
                 .unwrap_or_else(|| {
                     IfBlock::new::<()>(
@@ -406,6 +419,8 @@ impl Default for Scripting {
             ),
             untrusted_scripts: AHashMap::new(),
             trusted_scripts: AHashMap::new(),
+            untrusted_lookups: AHashSet::new(),
+            untrusted_max_lookups: 10,
         }
     }
 }
@@ -435,6 +450,8 @@ impl Clone for Scripting {
             sign: self.sign.clone(),
             trusted_scripts: self.trusted_scripts.clone(),
             untrusted_scripts: self.untrusted_scripts.clone(),
+            untrusted_lookups: self.untrusted_lookups.clone(),
+            untrusted_max_lookups: self.untrusted_max_lookups,
         }
     }
 }
@@ -7,8 +7,15 @@
 use std::{net::SocketAddr, sync::Arc, time::Duration};
 
 use rustls::{
-    crypto::ring::{default_provider, ALL_CIPHER_SUITES},
-    ServerConfig, SupportedCipherSuite, ALL_VERSIONS,
+    crypto::{
+        ring::{
+            default_provider,
+            kx_group::{SECP256R1, SECP384R1, X25519},
+            ALL_CIPHER_SUITES,
+        },
+        CryptoProvider,
+    },
+    ServerConfig, SupportedCipherSuite, SupportedProtocolVersion, ALL_VERSIONS,
 };
 
 use tokio::net::TcpSocket;
@@ -28,7 +35,7 @@ use crate::{
 
 use super::{
     tls::{TLS12_VERSION, TLS13_VERSION},
-    Listener, Server, ServerProtocol, Servers,
+    Listener, ListenerRole, Server, ServerProtocol, Servers,
 };
 
 impl Servers {
@@ -205,6 +212,7 @@ impl Servers {
                     "8192",
                 )
                 .unwrap_or(8192),
+            role: config.property(("server.listener", id, "role")),
             id: id_,
             protocol,
             listeners,
@@ -227,61 +235,13 @@ impl Servers {
                 .property_or_default(("server.listener", id, "tls.enable"), "true")
                 .unwrap_or(true)
             {
-                // Parse protocol versions
-                let mut tls_v2 = true;
-                let mut tls_v3 = true;
-                let mut proto_err = None;
-                for (_, protocol) in config.values_or_else(
-                    ("server.listener", id, "tls.disable-protocols"),
-                    "server.tls.disable-protocols",
-                ) {
-                    match protocol {
-                        "TLSv1.2" | "0x0303" => tls_v2 = false,
-                        "TLSv1.3" | "0x0304" => tls_v3 = false,
-                        protocol => {
-                            proto_err = format!("Unsupported TLS protocol {protocol:?}").into();
-                        }
-                    }
-                }
-
-                if let Some(proto_err) = proto_err {
-                    config.new_parse_error(
-                        ("server.listener", id, "tls.disable-protocols"),
-                        proto_err,
-                    );
-                }
-
-                // Parse cipher suites
-                let mut disabled_ciphers: Vec<SupportedCipherSuite> = Vec::new();
-                let cipher_keys =
-                    if config.has_prefix(("server.listener", id, "tls.disable-ciphers")) {
-                        ("server.listener", id, "tls.disable-ciphers").as_key()
-                    } else {
-                        "server.tls.disable-ciphers".as_key()
-                    };
-                for (_, protocol) in config.properties::<SupportedCipherSuite>(cipher_keys) {
-                    disabled_ciphers.push(protocol);
-                }
-
-                // Build cert provider
-                let mut provider = default_provider();
-                if !disabled_ciphers.is_empty() {
-                    provider.cipher_suites = ALL_CIPHER_SUITES
-                        .iter()
-                        .filter(|suite| !disabled_ciphers.contains(suite))
-                        .copied()
-                        .collect();
-                }
+                // Build TLS policy
+                let (provider, versions) = parse_tls_policy(config, id);
 
                 // Build server config
                 let mut server_config = match ServerConfig::builder_with_provider(provider.into())
-                    .with_protocol_versions(if tls_v3 == tls_v2 {
-                        ALL_VERSIONS
-                    } else if tls_v3 {
-                        TLS13_VERSION
-                    } else {
-                        TLS12_VERSION
-                    }) {
+                    .with_protocol_versions(versions)
+                {
                     Ok(server_config) => server_config
                         .with_no_client_auth()
                         .with_cert_resolver(resolver.clone()),
@@ -320,6 +280,189 @@ impl Servers {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TlsPreset {
+    Modern,
+    Intermediate,
+}
+
+// Builds the crypto provider and protocol versions of a listener. The presets
+// follow Mozilla's server side TLS recommendations: "modern" only allows TLS 1.3
+// while "intermediate" also allows TLS 1.2, and both restrict the key exchange
+// to X25519, P-256 and P-384. Explicit settings take precedence over the preset.
+// Invalid policies are reported and replaced by the default one, as listeners
+// without an acceptor would otherwise fall back to plain text.
+fn parse_tls_policy(
+    config: &mut Config,
+    id: &str,
+) -> (CryptoProvider, &'static [&'static SupportedProtocolVersion]) {
+    let preset = config
+        .property_or_else::<Option<TlsPreset>>(
+            ("server.listener", id, "tls.preset"),
+            "server.tls.preset",
+            "false",
+        )
+        .flatten();
+
+    // Parse minimum protocol version
+    let mut tls_v2 = preset != Some(TlsPreset::Modern);
+    let mut tls_v3 = true;
+    if let Some(min_version) = config
+        .value_or_else(
+            ("server.listener", id, "tls.min-version"),
+            "server.tls.min-version",
+        )
+        .map(|version| version.to_string())
+    {
+        match min_version.as_str() {
+            "1.2" | "TLSv1.2" | "0x0303" => tls_v2 = true,
+            "1.3" | "TLSv1.3" | "0x0304" => tls_v2 = false,
+            version => {
+                config.new_parse_error(
+                    ("server.listener", id, "tls.min-version"),
+                    format!("Unsupported TLS version {version:?}"),
+                );
+            }
+        }
+    }
+
+    // Parse disabled protocol versions
+    let mut proto_err = None;
+    for (_, protocol) in config.values_or_else(
+        ("server.listener", id, "tls.disable-protocols"),
+        "server.tls.disable-protocols",
+    ) {
+        match protocol {
+            "TLSv1.2" | "0x0303" => tls_v2 = false,
+            "TLSv1.3" | "0x0304" => tls_v3 = false,
+            protocol => {
+                proto_err = format!("Unsupported TLS protocol {protocol:?}").into();
+            }
+        }
+    }
+
+    if let Some(proto_err) = proto_err {
+        config.new_parse_error(("server.listener", id, "tls.disable-protocols"), proto_err);
+    }
+
+    if !tls_v2 && !tls_v3 {
+        config.new_build_error(
+            ("server.listener", id, "tls"),
+            "All TLS protocol versions are disabled",
+        );
+        return (default_provider(), ALL_VERSIONS);
+    } else if !tls_v3 {
+        config.new_build_warning(
+            ("server.listener", id, "tls"),
+            "TLS 1.3 is disabled, clients will be limited to TLS 1.2",
+        );
+    }
+
+    // Parse cipher suites
+    let mut allowed_ciphers: Vec<SupportedCipherSuite> = Vec::new();
+    let cipher_keys = if config.has_prefix(("server.listener", id, "tls.ciphers")) {
+        ("server.listener", id, "tls.ciphers").as_key()
+    } else {
+        "server.tls.ciphers".as_key()
+    };
+    for (_, cipher) in config.properties::<SupportedCipherSuite>(cipher_keys) {
+        allowed_ciphers.push(cipher);
+    }
+    let mut disabled_ciphers: Vec<SupportedCipherSuite> = Vec::new();
+    let cipher_keys = if config.has_prefix(("server.listener", id, "tls.disable-ciphers")) {
+        ("server.listener", id, "tls.disable-ciphers").as_key()
+    } else {
+        "server.tls.disable-ciphers".as_key()
+    };
+    for (_, cipher) in config.properties::<SupportedCipherSuite>(cipher_keys) {
+        disabled_ciphers.push(cipher);
+    }
+
+    // Cipher suites are dropped when their protocol version is disabled
+    let is_enabled = |suite: &SupportedCipherSuite| match suite {
+        SupportedCipherSuite::Tls12(_) => tls_v2,
+        SupportedCipherSuite::Tls13(_) => tls_v3,
+    };
+    for suite in &allowed_ciphers {
+        if !is_enabled(suite) {
+            config.new_build_warning(
+                ("server.listener", id, "tls.ciphers"),
+                format!(
+                    "Cipher suite {:?} will not be used, its TLS version is disabled",
+                    suite.suite()
+                ),
+            );
+        }
+    }
+
+    // Build cert provider
+    let mut provider = default_provider();
+    provider.cipher_suites = ALL_CIPHER_SUITES
+        .iter()
+        .filter(|suite| {
+            (allowed_ciphers.is_empty() || allowed_ciphers.contains(suite))
+                && !disabled_ciphers.contains(suite)
+                && is_enabled(*suite)
+        })
+        .copied()
+        .collect();
+    if provider.cipher_suites.is_empty() {
+        config.new_build_error(
+            ("server.listener", id, "tls.ciphers"),
+            "No cipher suites are available for the enabled TLS versions",
+        );
+        return (default_provider(), ALL_VERSIONS);
+    }
+
+    // Parse key exchange groups
+    let mut curve_err = None;
+    let mut kx_groups = Vec::new();
+    for (_, curve) in
+        config.values_or_else(("server.listener", id, "tls.curves"), "server.tls.curves")
+    {
+        match curve.to_ascii_lowercase().as_str() {
+            "x25519" => kx_groups.push(X25519),
+            "p-256" | "secp256r1" => kx_groups.push(SECP256R1),
+            "p-384" | "secp384r1" => kx_groups.push(SECP384R1),
+            curve => {
+                curve_err = format!("Unsupported TLS curve {curve:?}").into();
+            }
+        }
+    }
+
+    if let Some(curve_err) = curve_err {
+        config.new_parse_error(("server.listener", id, "tls.curves"), curve_err);
+    }
+    if !kx_groups.is_empty() {
+        provider.kx_groups = kx_groups;
+    } else if preset.is_some() {
+        provider.kx_groups = vec![X25519, SECP256R1, SECP384R1];
+    }
+
+    (
+        provider,
+        if tls_v2 && tls_v3 {
+            ALL_VERSIONS
+        } else if tls_v3 {
+            TLS13_VERSION
+        } else {
+            TLS12_VERSION
+        },
+    )
+}
+
+impl ParseValue for TlsPreset {
+    fn parse_value(value: &str) -> Result<Self, String> {
+        if value.eq_ignore_ascii_case("modern") {
+            Ok(Self::Modern)
+        } else if value.eq_ignore_ascii_case("intermediate") {
+            Ok(Self::Intermediate)
+        } else {
+            Err(format!("Invalid TLS preset {:?}.", value,))
+        }
+    }
+}
+
 impl ParseValue for ServerProtocol {
     fn parse_value(value: &str) -> Result<Self, String> {
         if value.eq_ignore_ascii_case("smtp") {
@@ -339,3 +482,15 @@ impl ParseValue for ServerProtocol {
         }
     }
 }
+
+impl ParseValue for ListenerRole {
+    fn parse_value(value: &str) -> Result<Self, String> {
+        if value.eq_ignore_ascii_case("inbound") {
+            Ok(Self::Inbound)
+        } else if value.eq_ignore_ascii_case("submission") {
+            Ok(Self::Submission)
+        } else {
+            Err(format!("Invalid listener role {:?}.", value,))
+        }
+    }
+}
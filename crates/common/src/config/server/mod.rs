@@ -27,6 +27,7 @@ pub struct Servers {
 pub struct Server {
     pub id: String,
     pub protocol: ServerProtocol,
+    pub role: Option<ListenerRole>,
     pub listeners: Vec<Listener>,
     pub proxy_networks: Vec<IpAddrMask>,
     pub max_connections: u64,
@@ -45,6 +46,12 @@ pub struct Listener {
     pub nodelay: bool,
 }
 
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ListenerRole {
+    Inbound,
+    Submission,
+}
+
 #[derive(Debug, PartialEq, Eq, Clone, Copy, Default, Serialize, Deserialize)]
 pub enum ServerProtocol {
     #[default]
@@ -33,6 +33,7 @@ use x509_parser::{
 
 use crate::listener::{
     acme::{directory::LETS_ENCRYPT_PRODUCTION_DIRECTORY, AcmeProvider, ChallengeSettings},
+    ocsp::OcspConfig,
     tls::TlsManager,
 };
 
@@ -177,6 +178,7 @@ impl TlsManager {
                 })
                 .ok()
                 .map(Arc::new),
+            ocsp: OcspConfig::parse(config),
         }
     }
 }
@@ -24,6 +24,8 @@ use crate::{
 
 use super::*;
 
+pub const AUTO_SIGNER: &str = "auto";
+
 #[derive(Clone)]
 pub struct MailAuthConfig {
     pub dkim: DkimAuthConfig,
@@ -34,6 +36,7 @@ pub struct MailAuthConfig {
 
     pub signers: AHashMap<String, Arc<DkimSigner>>,
     pub sealers: AHashMap<String, Arc<ArcSealer>>,
+    pub domain_signers: AHashMap<String, Vec<String>>,
 }
 
 #[derive(Clone)]
@@ -41,6 +44,15 @@ pub struct DkimAuthConfig {
     pub verify: IfBlock,
     pub sign: IfBlock,
     pub strict: bool,
+    pub default_signers: Vec<String>,
+    pub missing_signer: MissingSigner,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MissingSigner {
+    #[default]
+    Unsigned,
+    Reject,
 }
 
 #[derive(Clone)]
@@ -103,6 +115,8 @@ impl Default for MailAuthConfig {
                     "false",
                 ),
                 strict: true,
+                default_signers: vec![],
+                missing_signer: MissingSigner::Unsigned,
             },
             arc: ArcAuthConfig {
                 verify: IfBlock::new::<VerifyStrategy>("auth.arc.verify", [], "relaxed"),
@@ -152,6 +166,7 @@ impl Default for MailAuthConfig {
             },
             signers: Default::default(),
             sealers: Default::default(),
+            domain_signers: Default::default(),
         }
     }
 }
@@ -191,8 +206,9 @@ impl MailAuthConfig {
         mail_auth.dkim.strict = config
             .property_or_default("auth.dkim.strict", "true")
             .unwrap_or(true);
-
-        // Parse signatures
+        mail_auth.dkim.missing_signer = config
+            .property_or_default("auth.dkim.missing-signer", "unsigned")
+            .unwrap_or_default();
         for id in config
             .sub_keys("signature", ".algorithm")
             .map(|k| k.to_string())
@@ -200,11 +216,34 @@ impl MailAuthConfig {
         {
             let id = id.to_string();
             if let Some((signer, sealer)) = build_signature(config, &id) {
+                if let Some(domain) = config.value(("signature", id.as_str(), "domain")) {
+                    mail_auth
+                        .domain_signers
+                        .entry(domain.trim().to_lowercase())
+                        .or_default()
+                        .push(id.clone());
+                }
                 mail_auth.signers.insert(id.clone(), Arc::new(signer));
                 mail_auth.sealers.insert(id, Arc::new(sealer));
             }
         }
 
+        // Parse default signers
+        for name in config
+            .values("auth.dkim.default-signer")
+            .map(|(_, name)| name.to_string())
+            .collect::<Vec<_>>()
+        {
+            if mail_auth.signers.contains_key(&name) {
+                mail_auth.dkim.default_signers.push(name);
+            } else {
+                config.new_parse_error(
+                    "auth.dkim.default-signer",
+                    format!("Signature {name:?} does not exist."),
+                );
+            }
+        }
+
         mail_auth
     }
 }
@@ -456,6 +495,16 @@ impl ConstantValue for VerifyStrategy {
     }
 }
 
+impl ParseValue for MissingSigner {
+    fn parse_value(value: &str) -> Result<Self, String> {
+        match value {
+            "unsigned" | "accept" => Ok(MissingSigner::Unsigned),
+            "reject" => Ok(MissingSigner::Reject),
+            _ => Err(format!("Invalid value {:?}.", value)),
+        }
+    }
+}
+
 impl ParseValue for DkimCanonicalization {
     fn parse_value(value: &str) -> Result<Self, String> {
         if let Some((headers, body)) = value.split_once('/') {
@@ -4,9 +4,12 @@
  * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
  */
 
+use std::net::IpAddr;
+
 use ahash::AHashMap;
 use mail_auth::IpLookupStrategy;
 use mail_send::Credentials;
+use regex::Regex;
 use utils::config::{
     utils::{AsKey, ParseValue},
     Config,
@@ -27,12 +30,14 @@ pub struct QueueConfig {
     pub retry: IfBlock,
     pub notify: IfBlock,
     pub expire: IfBlock,
+    pub max_attempts: IfBlock,
 
     // Outbound
     pub hostname: IfBlock,
     pub next_hop: IfBlock,
     pub max_mx: IfBlock,
     pub max_multihomed: IfBlock,
+    pub max_concurrent: IfBlock,
     pub ip_strategy: IfBlock,
     pub source_ip: QueueOutboundSourceIp,
     pub tls: QueueOutboundTls,
@@ -47,12 +52,42 @@ pub struct QueueConfig {
 
     // Relay hosts
     pub relay_hosts: AHashMap<String, RelayHost>,
+
+    // Transport map
+    pub transport_map: TransportMap,
+}
+
+#[derive(Clone, Default)]
+pub struct TransportMap {
+    pub rules: Vec<TransportRule>,
+    pub max_rewrites: usize,
+}
+
+#[derive(Clone)]
+pub struct TransportRule {
+    pub pattern: TransportPattern,
+    pub rewrite: Option<String>,
+    pub relay: Option<String>,
+}
+
+#[derive(Clone)]
+pub enum TransportPattern {
+    Exact(String),
+    Domain(String),
+    Regex(Regex),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransportError {
+    Loop,
+    TooManyRewrites,
 }
 
 #[derive(Clone)]
 pub struct QueueOutboundSourceIp {
     pub ipv4: IfBlock,
     pub ipv6: IfBlock,
+    pub hostnames: AHashMap<IpAddr, String>,
 }
 
 #[derive(Clone)]
@@ -60,6 +95,23 @@ pub struct Dsn {
     pub name: IfBlock,
     pub address: IfBlock,
     pub sign: IfBlock,
+    pub templates: DsnTemplates,
+}
+
+#[derive(Clone)]
+pub struct DsnTemplates {
+    pub success: DsnTemplate,
+    pub delay: DsnTemplate,
+    pub failure: DsnTemplate,
+    pub partial: DsnTemplate,
+    pub mixed: DsnTemplate,
+}
+
+// The body is written before the list of recipients and their status
+#[derive(Clone)]
+pub struct DsnTemplate {
+    pub subject: String,
+    pub body: String,
 }
 
 #[derive(Clone)]
@@ -133,6 +185,7 @@ impl Default for QueueConfig {
             ),
             notify: IfBlock::new::<()>("queue.schedule.notify", [], "[1d, 3d]"),
             expire: IfBlock::new::<()>("queue.schedule.expire", [], "5d"),
+            max_attempts: IfBlock::new::<()>("queue.schedule.max-attempts", [], "0"),
             hostname: IfBlock::new::<()>(
                 "queue.outbound.hostname",
                 [],
@@ -148,6 +201,7 @@ impl Default for QueueConfig {
             ),
             max_mx: IfBlock::new::<()>("queue.outbound.limits.mx", [], "5"),
             max_multihomed: IfBlock::new::<()>("queue.outbound.limits.multihomed", [], "2"),
+            max_concurrent: IfBlock::new::<()>("queue.outbound.limits.concurrency", [], "0"),
             ip_strategy: IfBlock::new::<IpLookupStrategy>(
                 "queue.outbound.ip-strategy",
                 [],
@@ -156,6 +210,7 @@ impl Default for QueueConfig {
             source_ip: QueueOutboundSourceIp {
                 ipv4: IfBlock::empty("queue.outbound.source-ip.v4"),
                 ipv6: IfBlock::empty("queue.outbound.source-ip.v6"),
+                hostnames: AHashMap::new(),
             },
             tls: QueueOutboundTls {
                 dane: IfBlock::new::<RequireOptional>("queue.outbound.tls.dane", [], "optional"),
@@ -193,6 +248,7 @@ impl Default for QueueConfig {
                     [],
                     "['rsa-' + key_get('default', 'domain'), 'ed25519-' + key_get('default', 'domain')]",
                 ),
+                templates: DsnTemplates::default(),
             },
             timeout: QueueOutboundTimeout {
                 connect: IfBlock::new::<()>("queue.outbound.timeouts.connect", [], "5m"),
@@ -215,6 +271,70 @@ impl Default for QueueConfig {
                 rcpt_domain: Default::default(),
             },
             relay_hosts: Default::default(),
+            transport_map: Default::default(),
+        }
+    }
+}
+
+impl DsnTemplates {
+    pub fn parse(config: &mut Config) -> Self {
+        let default = DsnTemplates::default();
+
+        DsnTemplates {
+            success: DsnTemplate::parse(config, "success", default.success),
+            delay: DsnTemplate::parse(config, "delay", default.delay),
+            failure: DsnTemplate::parse(config, "failure", default.failure),
+            partial: DsnTemplate::parse(config, "partial", default.partial),
+            mixed: DsnTemplate::parse(config, "mixed", default.mixed),
+        }
+    }
+}
+
+impl DsnTemplate {
+    fn parse(config: &mut Config, id: &str, default: DsnTemplate) -> Self {
+        DsnTemplate {
+            subject: config
+                .value(("report.dsn.template", id, "subject"))
+                .map(|v| v.to_string())
+                .unwrap_or(default.subject),
+            body: config
+                .value(("report.dsn.template", id, "body"))
+                .map(|v| v.trim_end().replace("\r\n", "\n").replace('\n', "\r\n"))
+                .unwrap_or(default.body),
+        }
+    }
+
+    fn new(subject: &str, body: &str) -> Self {
+        DsnTemplate {
+            subject: subject.to_string(),
+            body: body.to_string(),
+        }
+    }
+}
+
+impl Default for DsnTemplates {
+    fn default() -> Self {
+        DsnTemplates {
+            success: DsnTemplate::new(
+                "Successfully delivered message",
+                "Your message has been successfully delivered to the following recipients:",
+            ),
+            delay: DsnTemplate::new(
+                "Warning: Delay in message delivery",
+                "There was a temporary problem delivering your message to the following recipients:",
+            ),
+            failure: DsnTemplate::new(
+                "Failed to deliver message",
+                "Your message could not be delivered to the following recipients:",
+            ),
+            partial: DsnTemplate::new(
+                "Partially delivered message",
+                "Your message has been partially delivered:",
+            ),
+            mixed: DsnTemplate::new(
+                "Warning: Temporary and permanent failures during message delivery",
+                "Your message could not be delivered to some recipients:",
+            ),
         }
     }
 }
@@ -234,6 +354,11 @@ impl QueueConfig {
             (&mut queue.retry, "queue.schedule.retry", &host_vars),
             (&mut queue.notify, "queue.schedule.notify", &rcpt_vars),
             (&mut queue.expire, "queue.schedule.expire", &rcpt_vars),
+            (
+                &mut queue.max_attempts,
+                "queue.schedule.max-attempts",
+                &rcpt_vars,
+            ),
             (&mut queue.hostname, "queue.outbound.hostname", &sender_vars),
             (&mut queue.max_mx, "queue.outbound.limits.mx", &rcpt_vars),
             (
@@ -241,6 +366,11 @@ impl QueueConfig {
                 "queue.outbound.limits.multihomed",
                 &rcpt_vars,
             ),
+            (
+                &mut queue.max_concurrent,
+                "queue.outbound.limits.concurrency",
+                &rcpt_vars,
+            ),
             (
                 &mut queue.ip_strategy,
                 "queue.outbound.ip-strategy",
@@ -326,6 +456,34 @@ impl QueueConfig {
             }
         }
 
+        // Parse EHLO hostnames by source IP
+        for (ip, hostname) in config
+            .iterate_prefix("queue.outbound.source-ip.hostname")
+            .map(|(ip, hostname)| (ip.to_string(), hostname.trim().to_lowercase()))
+            .collect::<Vec<_>>()
+        {
+            match ip.parse::<IpAddr>() {
+                Ok(ip) if !hostname.is_empty() => {
+                    queue.source_ip.hostnames.insert(ip, hostname);
+                }
+                Ok(_) => {
+                    config.new_parse_error(
+                        ("queue.outbound.source-ip.hostname", ip.as_str()),
+                        "Hostname cannot be empty",
+                    );
+                }
+                Err(_) => {
+                    config.new_parse_error(
+                        ("queue.outbound.source-ip.hostname", ip.as_str()),
+                        format!("Invalid IP address {ip:?}"),
+                    );
+                }
+            }
+        }
+
+        // Parse DSN templates
+        queue.dsn.templates = DsnTemplates::parse(config);
+
         // Parse queue quotas and throttles
         queue.throttle = parse_queue_throttle(config);
         queue.quota = parse_queue_quota(config);
@@ -352,10 +510,167 @@ impl QueueConfig {
             },
         );
 
+        // Parse transport map
+        queue.transport_map = TransportMap::parse(config, &queue.relay_hosts);
+
         queue
     }
 }
 
+impl TransportMap {
+    pub fn parse(config: &mut Config, relay_hosts: &AHashMap<String, RelayHost>) -> Self {
+        let mut map = TransportMap {
+            rules: Vec::new(),
+            max_rewrites: config
+                .property_or_default("queue.transport-map.max-rewrites", "5")
+                .unwrap_or(5),
+        };
+
+        for id in config
+            .sub_keys("queue.transport-map.rule", ".pattern")
+            .map(|s| s.to_string())
+            .collect::<Vec<_>>()
+        {
+            if let Some(rule) = TransportRule::parse(config, &id, relay_hosts) {
+                map.rules.push(rule);
+            }
+        }
+
+        map
+    }
+
+    // Rewrites a lowercase recipient address using the first matching rule,
+    // the result is matched again until no rule applies. Returns None when the
+    // address is left unchanged.
+    pub fn rewrite(&self, address: &str) -> Result<Option<String>, TransportError> {
+        let mut seen = vec![address.to_string()];
+        let mut current = address.to_string();
+
+        while let Some(next) = self.rules.iter().find_map(|rule| rule.apply(&current)) {
+            if next == current {
+                break;
+            } else if seen.contains(&next) {
+                return Err(TransportError::Loop);
+            } else if seen.len() > self.max_rewrites {
+                return Err(TransportError::TooManyRewrites);
+            }
+            seen.push(next.clone());
+            current = next;
+        }
+
+        Ok((seen.len() > 1).then_some(current))
+    }
+
+    // Returns the relay host of a recipient, matched against the address
+    // obtained after rewriting.
+    pub fn relay(&self, address: &str) -> Option<&str> {
+        self.rules.iter().find_map(|rule| {
+            rule.relay
+                .as_deref()
+                .filter(|_| rule.pattern.matches(address))
+        })
+    }
+}
+
+impl TransportRule {
+    fn parse(
+        config: &mut Config,
+        id: &str,
+        relay_hosts: &AHashMap<String, RelayHost>,
+    ) -> Option<Self> {
+        let key = ("queue.transport-map.rule", id);
+        let pattern = config
+            .value_require(("queue.transport-map.rule", id, "pattern"))?
+            .to_string();
+        let pattern = match config
+            .value(("queue.transport-map.rule", id, "type"))
+            .unwrap_or("exact")
+            .to_string()
+            .as_str()
+        {
+            "exact" => TransportPattern::Exact(pattern.to_lowercase()),
+            "domain" => TransportPattern::Domain(pattern.to_lowercase()),
+            "regex" => match Regex::new(&pattern) {
+                Ok(regex) => TransportPattern::Regex(regex),
+                Err(err) => {
+                    config.new_parse_error(
+                        ("queue.transport-map.rule", id, "pattern"),
+                        format!("Invalid regular expression: {err}"),
+                    );
+                    return None;
+                }
+            },
+            typ => {
+                let err = format!("Invalid rule type {typ:?}");
+                config.new_parse_error(("queue.transport-map.rule", id, "type"), err);
+                return None;
+            }
+        };
+        let rewrite = config
+            .value(("queue.transport-map.rule", id, "rewrite"))
+            .map(|value| value.to_lowercase());
+        let relay = config
+            .value(("queue.transport-map.rule", id, "relay"))
+            .map(|value| value.to_string());
+
+        if let Some(relay) = &relay {
+            if !relay_hosts.contains_key(relay) {
+                config.new_parse_error(
+                    ("queue.transport-map.rule", id, "relay"),
+                    format!("Relay host {relay:?} does not exist"),
+                );
+                return None;
+            }
+        } else if rewrite.is_none() {
+            config.new_parse_error(key, "Transport rule must define a rewrite or a relay");
+            return None;
+        }
+
+        Some(TransportRule {
+            pattern,
+            rewrite,
+            relay,
+        })
+    }
+
+    fn apply(&self, address: &str) -> Option<String> {
+        let rewrite = self.rewrite.as_ref()?;
+        if !self.pattern.matches(address) {
+            return None;
+        }
+
+        let address = match &self.pattern {
+            TransportPattern::Exact(_) => rewrite.clone(),
+            TransportPattern::Domain(_) if !rewrite.contains('@') => {
+                // Only the domain part is replaced
+                format!("{}@{rewrite}", address.rsplit_once('@')?.0)
+            }
+            TransportPattern::Domain(_) => rewrite.clone(),
+            TransportPattern::Regex(regex) => {
+                regex.replace(address, rewrite.as_str()).to_lowercase()
+            }
+        };
+
+        // Ignore rewrites that do not produce a valid address
+        address
+            .rsplit_once('@')
+            .filter(|(local, domain)| !local.is_empty() && !domain.is_empty())
+            .map(|_| address.clone())
+    }
+}
+
+impl TransportPattern {
+    fn matches(&self, address: &str) -> bool {
+        match self {
+            TransportPattern::Exact(pattern) => pattern == address,
+            TransportPattern::Domain(pattern) => address
+                .rsplit_once('@')
+                .map_or(false, |(_, domain)| domain == pattern),
+            TransportPattern::Regex(regex) => regex.is_match(address),
+        }
+    }
+}
+
 fn parse_relay_host(config: &mut Config, id: &str) -> Option<RelayHost> {
     Some(RelayHost {
         address: config.property_require(("remote", id, "address"))?,
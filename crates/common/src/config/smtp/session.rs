@@ -6,21 +6,20 @@
 
 use std::{
     net::{SocketAddr, ToSocketAddrs},
-    str::FromStr,
     time::Duration,
 };
 
 use ahash::AHashSet;
 use base64::{engine::general_purpose::STANDARD, Engine};
 use hyper::{
-    header::{HeaderName, HeaderValue, AUTHORIZATION, CONTENT_TYPE},
+    header::{AUTHORIZATION, CONTENT_TYPE},
     HeaderMap,
 };
 use smtp_proto::*;
-use utils::config::{utils::ParseValue, Config};
+use utils::config::{utils::ParseValue, Config, Rate};
 
 use crate::{
-    config::CONNECTION_VARS,
+    config::{parse_http_headers, CONNECTION_VARS},
     expr::{if_block::IfBlock, tokenizer::TokenMap, *},
 };
 
@@ -33,6 +32,7 @@ pub struct SessionConfig {
     pub timeout: IfBlock,
     pub duration: IfBlock,
     pub transfer_limit: IfBlock,
+    pub pipelining_limit: IfBlock,
     pub throttle: SessionThrottle,
 
     pub connect: Connect,
@@ -46,6 +46,19 @@ pub struct SessionConfig {
 
     pub milters: Vec<Milter>,
     pub hooks: Vec<MTAHook>,
+    pub journals: Vec<Journal>,
+    pub reputation: Vec<IpReputation>,
+    pub submission: SubmissionLimits,
+}
+
+#[derive(Default, Debug, Clone)]
+pub struct SubmissionLimits {
+    pub messages: Option<Rate>,
+    pub recipients: Option<Rate>,
+    pub admin_messages: Option<Rate>,
+    pub admin_recipients: Option<Rate>,
+    pub allowlist: AHashSet<String>,
+    pub suspend: Option<Rate>,
 }
 
 #[derive(Default, Debug, Clone)]
@@ -60,6 +73,7 @@ pub struct Connect {
     pub hostname: IfBlock,
     pub script: IfBlock,
     pub greeting: IfBlock,
+    pub reputation_threshold: f64,
 }
 
 #[derive(Clone)]
@@ -117,6 +131,18 @@ pub struct Rcpt {
     // Catch-all and sub-addressing
     pub catch_all: AddressMapping,
     pub subaddressing: AddressMapping,
+
+    // Greylisting
+    pub greylist: Greylist,
+}
+
+#[derive(Clone)]
+pub struct Greylist {
+    pub enable: IfBlock,
+    pub delay: Duration,
+    pub pending_ttl: Duration,
+    pub allow_ttl: Duration,
+    pub allow_spf_pass: bool,
 }
 
 #[derive(Debug, Default, Clone)]
@@ -193,6 +219,53 @@ pub struct MTAHook {
     pub max_response_size: usize,
 }
 
+#[derive(Clone)]
+pub struct Journal {
+    pub enable: IfBlock,
+    pub id: String,
+    pub target: JournalTarget,
+    pub tempfail_on_error: bool,
+}
+
+#[derive(Clone)]
+pub enum JournalTarget {
+    Address(String),
+    Http {
+        url: String,
+        client: reqwest::Client,
+        headers: HeaderMap,
+    },
+}
+
+#[derive(Clone)]
+pub struct IpReputation {
+    pub enable: IfBlock,
+    pub id: String,
+    pub provider: ReputationProvider,
+    pub cache_ttl: Duration,
+    pub actions: Vec<(String, ReputationAction)>,
+}
+
+#[derive(Clone)]
+pub enum ReputationProvider {
+    Dnsbl {
+        zone: String,
+    },
+    Http {
+        url: String,
+        client: reqwest::Client,
+        headers: HeaderMap,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ReputationAction {
+    Reject,
+    Greylist(Duration),
+    Delay(Duration),
+    Score(f64),
+}
+
 #[derive(Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Stage {
     Connect,
@@ -229,6 +302,35 @@ impl SessionConfig {
             .into_iter()
             .filter_map(|id| parse_hooks(config, &id, &has_rcpt_vars))
             .collect();
+        session.journals = config
+            .sub_keys("session.journal", ".enable")
+            .map(|s| s.to_string())
+            .collect::<Vec<_>>()
+            .into_iter()
+            .filter_map(|id| parse_journal(config, &id, &has_rcpt_vars))
+            .collect();
+        session.reputation = config
+            .sub_keys("session.reputation", ".type")
+            .map(|s| s.to_string())
+            .collect::<Vec<_>>()
+            .into_iter()
+            .filter_map(|id| parse_reputation(config, &id, &has_conn_vars))
+            .collect();
+        session.connect.reputation_threshold = config
+            .property_or_default("session.connect.reputation-threshold", "5.0")
+            .unwrap_or(5.0);
+        session.rcpt.greylist.delay = config
+            .property_or_default("session.rcpt.greylist.delay", "5m")
+            .unwrap_or_else(|| Duration::from_secs(5 * 60));
+        session.rcpt.greylist.pending_ttl = config
+            .property_or_default("session.rcpt.greylist.pending-ttl", "1d")
+            .unwrap_or_else(|| Duration::from_secs(86400));
+        session.rcpt.greylist.allow_ttl = config
+            .property_or_default("session.rcpt.greylist.allow-ttl", "36d")
+            .unwrap_or_else(|| Duration::from_secs(36 * 86400));
+        session.rcpt.greylist.allow_spf_pass = config
+            .property_or_default("session.rcpt.greylist.allow-spf-pass", "true")
+            .unwrap_or(true);
         session.data.pipe_commands = config
             .sub_keys("session.data.pipe", "")
             .map(|s| s.to_string())
@@ -237,6 +339,7 @@ impl SessionConfig {
             .filter_map(|id| parse_pipe(config, &id, &has_rcpt_vars))
             .collect();
         session.throttle = SessionThrottle::parse(config);
+        session.submission = SubmissionLimits::parse(config);
         session.mta_sts_policy = Policy::try_parse(config);
 
         for (value, key, token_map) in [
@@ -247,6 +350,11 @@ impl SessionConfig {
                 &has_conn_vars,
             ),
             (&mut session.timeout, "session.timeout", &has_conn_vars),
+            (
+                &mut session.pipelining_limit,
+                "session.pipelining-limit",
+                &has_conn_vars,
+            ),
             (
                 &mut session.connect.script,
                 "session.connect.script",
@@ -407,6 +515,11 @@ impl SessionConfig {
                 "session.rcpt.rewrite",
                 &has_rcpt_vars,
             ),
+            (
+                &mut session.rcpt.greylist.enable,
+                "session.rcpt.greylist.enable",
+                &has_rcpt_vars,
+            ),
             (
                 &mut session.data.script,
                 "session.data.script",
@@ -519,6 +632,30 @@ impl SessionThrottle {
     }
 }
 
+impl SubmissionLimits {
+    pub fn parse(config: &mut Config) -> Self {
+        SubmissionLimits {
+            messages: config.property::<Rate>("session.submission.limit.messages"),
+            recipients: config.property::<Rate>("session.submission.limit.recipients"),
+            admin_messages: config.property::<Rate>("session.submission.limit.admin.messages"),
+            admin_recipients: config.property::<Rate>("session.submission.limit.admin.recipients"),
+            allowlist: config
+                .values("session.submission.limit.allow")
+                .map(|(_, account)| account.trim().to_lowercase())
+                .filter(|account| !account.is_empty())
+                .collect(),
+            suspend: config.property::<Rate>("session.submission.limit.suspend"),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.messages.is_some()
+            || self.recipients.is_some()
+            || self.admin_messages.is_some()
+            || self.admin_recipients.is_some()
+    }
+}
+
 fn parse_pipe(config: &mut Config, id: &str, token_map: &TokenMap) -> Option<Pipe> {
     Some(Pipe {
         command: IfBlock::try_parse(config, ("session.data.pipe", id, "command"), token_map)?,
@@ -598,36 +735,7 @@ fn parse_milter(config: &mut Config, id: &str, token_map: &TokenMap) -> Option<M
 }
 
 fn parse_hooks(config: &mut Config, id: &str, token_map: &TokenMap) -> Option<MTAHook> {
-    let mut headers = HeaderMap::new();
-
-    for (header, value) in config
-        .values(("session.hook", id, "headers"))
-        .map(|(_, v)| {
-            if let Some((k, v)) = v.split_once(':') {
-                Ok((
-                    HeaderName::from_str(k.trim()).map_err(|err| {
-                        format!(
-                            "Invalid header found in property \"session.hook.{id}.headers\": {err}",
-                        )
-                    })?,
-                    HeaderValue::from_str(v.trim()).map_err(|err| {
-                        format!(
-                            "Invalid header found in property \"session.hook.{id}.headers\": {err}",
-                        )
-                    })?,
-                ))
-            } else {
-                Err(format!(
-                    "Invalid header found in property \"session.hook.{id}.headers\": {v}",
-                ))
-            }
-        })
-        .collect::<Result<Vec<(HeaderName, HeaderValue)>, String>>()
-        .map_err(|e| config.new_parse_error(("session.hook", id, "headers"), e))
-        .unwrap_or_default()
-    {
-        headers.insert(header, value);
-    }
+    let mut headers = parse_http_headers(config, ("session.hook", id, "headers"));
 
     headers.insert(CONTENT_TYPE, "application/json".parse().unwrap());
     if let (Some(name), Some(secret)) = (
@@ -671,6 +779,176 @@ fn parse_hooks(config: &mut Config, id: &str, token_map: &TokenMap) -> Option<MT
     })
 }
 
+fn parse_journal(config: &mut Config, id: &str, token_map: &TokenMap) -> Option<Journal> {
+    let target = if let Some(address) = config.value(("session.journal", id, "address")) {
+        JournalTarget::Address(address.trim().to_lowercase())
+    } else if let Some(url) = config.value(("session.journal", id, "url")) {
+        let url = url.to_string();
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_TYPE, "message/rfc822".parse().unwrap());
+        if let (Some(name), Some(secret)) = (
+            config.value(("session.journal", id, "auth.username")),
+            config.value(("session.journal", id, "auth.secret")),
+        ) {
+            headers.insert(
+                AUTHORIZATION,
+                format!("Basic {}", STANDARD.encode(format!("{}:{}", name, secret)))
+                    .parse()
+                    .unwrap(),
+            );
+        }
+
+        let client = reqwest::Client::builder()
+            .timeout(
+                config
+                    .property_or_default(("session.journal", id, "timeout"), "30s")
+                    .unwrap_or_else(|| Duration::from_secs(30)),
+            )
+            .danger_accept_invalid_certs(
+                config
+                    .property_or_default(("session.journal", id, "allow-invalid-certs"), "false")
+                    .unwrap_or_default(),
+            )
+            .build()
+            .map_err(|err| {
+                config.new_build_error(
+                    ("session.journal", id, "url"),
+                    format!("Failed to build HTTP client: {err}"),
+                )
+            })
+            .ok()?;
+
+        JournalTarget::Http {
+            url,
+            client,
+            headers,
+        }
+    } else {
+        config.new_parse_error(
+            ("session.journal", id),
+            "Journal must define an archive address or url",
+        );
+        return None;
+    };
+
+    Some(Journal {
+        enable: IfBlock::try_parse(config, ("session.journal", id, "enable"), token_map)
+            .unwrap_or_else(|| {
+                IfBlock::new::<()>(format!("session.journal.{id}.enable"), [], "false")
+            }),
+        id: id.to_string(),
+        target,
+        tempfail_on_error: config
+            .property_or_default(("session.journal", id, "options.tempfail-on-error"), "true")
+            .unwrap_or(true),
+    })
+}
+
+fn parse_reputation(config: &mut Config, id: &str, token_map: &TokenMap) -> Option<IpReputation> {
+    let provider = match config.value_require(("session.reputation", id, "type"))? {
+        "dnsbl" => ReputationProvider::Dnsbl {
+            zone: config
+                .value_require(("session.reputation", id, "zone"))?
+                .trim_matches('.')
+                .to_string(),
+        },
+        "http" => {
+            let mut headers = parse_http_headers(config, ("session.reputation", id, "headers"));
+
+            headers.insert(CONTENT_TYPE, "application/json".parse().unwrap());
+            if let (Some(name), Some(secret)) = (
+                config.value(("session.reputation", id, "auth.username")),
+                config.value(("session.reputation", id, "auth.secret")),
+            ) {
+                headers.insert(
+                    AUTHORIZATION,
+                    format!("Basic {}", STANDARD.encode(format!("{}:{}", name, secret)))
+                        .parse()
+                        .unwrap(),
+                );
+            }
+
+            let client = reqwest::Client::builder()
+                .timeout(
+                    config
+                        .property_or_default(("session.reputation", id, "timeout"), "5s")
+                        .unwrap_or_else(|| Duration::from_secs(5)),
+                )
+                .danger_accept_invalid_certs(
+                    config
+                        .property_or_default(
+                            ("session.reputation", id, "allow-invalid-certs"),
+                            "false",
+                        )
+                        .unwrap_or_default(),
+                )
+                .build()
+                .map_err(|err| {
+                    config.new_build_error(
+                        ("session.reputation", id, "url"),
+                        format!("Failed to build HTTP client: {err}"),
+                    )
+                })
+                .ok()?;
+
+            ReputationProvider::Http {
+                url: config
+                    .value_require(("session.reputation", id, "url"))?
+                    .to_string(),
+                client,
+                headers,
+            }
+        }
+        other => {
+            let message = format!("Unknown reputation provider type {other:?}");
+            config.new_parse_error(("session.reputation", id, "type"), message);
+            return None;
+        }
+    };
+
+    // Actions are defined as "<result> <action>", where "*" matches any result
+    let mut actions = Vec::new();
+    let mut invalid_actions = Vec::new();
+    for (_, value) in config.values(("session.reputation", id, "actions")) {
+        if let Some((result, action)) = value.trim().split_once(' ') {
+            match ReputationAction::parse_value(action.trim()) {
+                Ok(action) => {
+                    actions.push((result.to_string(), action));
+                }
+                Err(err) => {
+                    invalid_actions.push(err);
+                }
+            }
+        } else {
+            invalid_actions.push(format!("Invalid reputation action {value:?}"));
+        }
+    }
+
+    if !invalid_actions.is_empty() {
+        config.new_parse_error(
+            ("session.reputation", id, "actions"),
+            invalid_actions.join(", "),
+        );
+    }
+
+    Some(IpReputation {
+        enable: IfBlock::try_parse(config, ("session.reputation", id, "enable"), token_map)
+            .unwrap_or_else(|| {
+                IfBlock::new::<()>(
+                    format!("session.reputation.{id}.enable"),
+                    [("local_port == 25", "true")],
+                    "false",
+                )
+            }),
+        id: id.to_string(),
+        provider,
+        cache_ttl: config
+            .property_or_default(("session.reputation", id, "cache-ttl"), "1h")
+            .unwrap_or_else(|| Duration::from_secs(3600)),
+        actions,
+    })
+}
+
 fn parse_stages(config: &mut Config, prefix: &str, id: &str) -> AHashSet<Stage> {
     let mut stages = AHashSet::default();
     let mut invalid = Vec::new();
@@ -711,6 +989,7 @@ impl Default for SessionConfig {
             timeout: IfBlock::new::<()>("session.timeout", [], "5m"),
             duration: IfBlock::new::<()>("session.duration", [], "10m"),
             transfer_limit: IfBlock::new::<()>("session.transfer-limit", [], "262144000"),
+            pipelining_limit: IfBlock::new::<()>("session.pipelining-limit", [], "250"),
             throttle: SessionThrottle {
                 connect: Default::default(),
                 mail_from: Default::default(),
@@ -728,6 +1007,7 @@ impl Default for SessionConfig {
                     [],
                     "key_get('default', 'hostname') + ' Stalwart ESMTP at your service'",
                 ),
+                reputation_threshold: 5.0,
             },
             ehlo: Ehlo {
                 script: IfBlock::empty("session.ehlo.script"),
@@ -791,9 +1071,20 @@ impl Default for SessionConfig {
                 rewrite: IfBlock::empty("session.rcpt.rewrite"),
                 errors_max: IfBlock::new::<()>("session.rcpt.errors.total", [], "5"),
                 errors_wait: IfBlock::new::<()>("session.rcpt.errors.wait", [], "5s"),
-                max_recipients: IfBlock::new::<()>("session.rcpt.max-recipients", [], "100"),
+                max_recipients: IfBlock::new::<()>(
+                    "session.rcpt.max-recipients",
+                    [("!is_empty(authenticated_as)", "1000")],
+                    "100",
+                ),
                 catch_all: AddressMapping::Enable,
                 subaddressing: AddressMapping::Enable,
+                greylist: Greylist {
+                    enable: IfBlock::new::<()>("session.rcpt.greylist.enable", [], "false"),
+                    delay: Duration::from_secs(5 * 60),
+                    pending_ttl: Duration::from_secs(86400),
+                    allow_ttl: Duration::from_secs(36 * 86400),
+                    allow_spf_pass: true,
+                },
             },
             data: Data {
                 #[cfg(feature = "test_mode")]
@@ -882,6 +1173,9 @@ impl Default for SessionConfig {
             mta_sts_policy: None,
             milters: Default::default(),
             hooks: Default::default(),
+            journals: Default::default(),
+            reputation: Default::default(),
+            submission: Default::default(),
         }
     }
 }
@@ -896,9 +1190,9 @@ impl ParseValue for Mechanism {
             "PLAIN" => AUTH_PLAIN,
             "XOAUTH2" => AUTH_XOAUTH2,
             "OAUTHBEARER" => AUTH_OAUTHBEARER,
-            /*"SCRAM-SHA-256-PLUS" => AUTH_SCRAM_SHA_256_PLUS,
+            "SCRAM-SHA-256-PLUS" => AUTH_SCRAM_SHA_256_PLUS,
             "SCRAM-SHA-256" => AUTH_SCRAM_SHA_256,
-            "SCRAM-SHA-1-PLUS" => AUTH_SCRAM_SHA_1_PLUS,
+            /*"SCRAM-SHA-1-PLUS" => AUTH_SCRAM_SHA_1_PLUS,
             "SCRAM-SHA-1" => AUTH_SCRAM_SHA_1,
             "XOAUTH" => AUTH_XOAUTH,
             "9798-M-DSA-SHA1" => AUTH_9798_M_DSA_SHA1,
@@ -974,7 +1268,9 @@ impl ConstantValue for Mechanism {
             .add_constant("login", Mechanism(AUTH_LOGIN))
             .add_constant("plain", Mechanism(AUTH_PLAIN))
             .add_constant("xoauth2", Mechanism(AUTH_XOAUTH2))
-            .add_constant("oauthbearer", Mechanism(AUTH_OAUTHBEARER));
+            .add_constant("oauthbearer", Mechanism(AUTH_OAUTHBEARER))
+            .add_constant("scram_sha_256", Mechanism(AUTH_SCRAM_SHA_256))
+            .add_constant("scram_sha_256_plus", Mechanism(AUTH_SCRAM_SHA_256_PLUS));
     }
 }
 
@@ -1025,3 +1321,23 @@ impl ConstantValue for MtPriority {
             .add_constant("nsep", MtPriority::Nsep);
     }
 }
+
+impl ParseValue for ReputationAction {
+    fn parse_value(value: &str) -> Result<Self, String> {
+        let (action, argument) = value
+            .split_once(' ')
+            .map(|(action, argument)| (action, Some(argument.trim())))
+            .unwrap_or((value, None));
+
+        match (action, argument) {
+            ("reject", None) => Ok(ReputationAction::Reject),
+            ("greylist", None) => Ok(ReputationAction::Greylist(Duration::from_secs(5 * 60))),
+            ("greylist", Some(period)) => {
+                Duration::parse_value(period).map(ReputationAction::Greylist)
+            }
+            ("delay", Some(delay)) => Duration::parse_value(delay).map(ReputationAction::Delay),
+            ("score", Some(score)) => f64::parse_value(score).map(ReputationAction::Score),
+            _ => Err(format!("Invalid reputation action {value:?}")),
+        }
+    }
+}
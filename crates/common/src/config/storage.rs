@@ -4,7 +4,7 @@
  * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
  */
 
-use std::sync::Arc;
+use std::{collections::BTreeMap, sync::Arc};
 
 use ahash::AHashMap;
 use directory::Directory;
@@ -27,4 +27,7 @@ pub struct Storage {
     pub blobs: AHashMap<String, BlobStore>,
     pub lookups: AHashMap<String, LookupStore>,
     pub ftss: AHashMap<String, FtsStore>,
+
+    // Settings this core was built from, used to report changes on reload
+    pub config_keys: Arc<BTreeMap<String, String>>,
 }
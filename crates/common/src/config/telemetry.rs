@@ -9,7 +9,7 @@ use std::{collections::HashMap, str::FromStr, sync::Arc, time::Duration};
 use ahash::{AHashMap, AHashSet};
 use base64::{engine::general_purpose::STANDARD, Engine};
 use hyper::{
-    header::{HeaderName, HeaderValue, AUTHORIZATION, CONTENT_TYPE},
+    header::{AUTHORIZATION, CONTENT_TYPE},
     HeaderMap,
 };
 use opentelemetry::{InstrumentationLibrary, KeyValue};
@@ -25,7 +25,9 @@ use opentelemetry_sdk::{
 use opentelemetry_semantic_conventions::resource::{SERVICE_NAME, SERVICE_VERSION};
 use store::Stores;
 use trc::{ipc::subscriber::Interests, EventType, Level, TelemetryEvent};
-use utils::config::{utils::ParseValue, Config};
+use utils::config::{utils::ParseValue, Config, Rate};
+
+use super::parse_http_headers;
 
 #[derive(Debug)]
 pub struct TelemetrySubscriber {
@@ -86,6 +88,8 @@ pub struct WebhookTracer {
     pub timeout: Duration,
     pub throttle: Duration,
     pub discard_after: Duration,
+    pub dedup_window: Option<Duration>,
+    pub rate_limit: Option<Rate>,
     pub tls_allow_invalid_certs: bool,
     pub headers: HeaderMap,
 }
@@ -753,32 +757,7 @@ fn parse_webhook(
     id: &str,
     global_interests: &mut Interests,
 ) -> Option<TelemetrySubscriber> {
-    let mut headers = HeaderMap::new();
-
-    for (header, value) in config
-        .values(("webhook", id, "headers"))
-        .map(|(_, v)| {
-            if let Some((k, v)) = v.split_once(':') {
-                Ok((
-                    HeaderName::from_str(k.trim()).map_err(|err| {
-                        format!("Invalid header found in property \"webhook.{id}.headers\": {err}",)
-                    })?,
-                    HeaderValue::from_str(v.trim()).map_err(|err| {
-                        format!("Invalid header found in property \"webhook.{id}.headers\": {err}",)
-                    })?,
-                ))
-            } else {
-                Err(format!(
-                    "Invalid header found in property \"webhook.{id}.headers\": {v}",
-                ))
-            }
-        })
-        .collect::<Result<Vec<(HeaderName, HeaderValue)>, String>>()
-        .map_err(|e| config.new_parse_error(("webhook", id, "headers"), e))
-        .unwrap_or_default()
-    {
-        headers.insert(header, value);
-    }
+    let mut headers = parse_http_headers(config, ("webhook", id, "headers"));
 
     headers.insert(CONTENT_TYPE, "application/json".parse().unwrap());
     if let (Some(name), Some(secret)) = (
@@ -819,6 +798,8 @@ fn parse_webhook(
             discard_after: config
                 .property_or_default(("webhook", id, "discard-after"), "5m")
                 .unwrap_or_else(|| Duration::from_secs(300)),
+            dedup_window: config.property(("webhook", id, "dedup-window")),
+            rate_limit: config.property(("webhook", id, "rate-limit")),
         }),
     };
 
@@ -830,7 +811,12 @@ fn parse_webhook(
             .map(|(_, e)| e),
         true,
         |event_type| {
-            if event_type != EventType::Telemetry(TelemetryEvent::WebhookError) {
+            if !matches!(
+                event_type,
+                EventType::Telemetry(
+                    TelemetryEvent::WebhookError | TelemetryEvent::WebhookSuppressed
+                )
+            ) {
                 tracer.interests.set(event_type);
                 global_interests.set(event_type);
             }
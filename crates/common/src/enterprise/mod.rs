@@ -21,7 +21,7 @@ use directory::{
 };
 use license::LicenseKey;
 use mail_parser::DateTime;
-use store::Store;
+use store::{write::now, Store};
 use trc::{AddContext, EventType, MetricType};
 use utils::config::cron::SimpleCron;
 
@@ -196,7 +196,9 @@ impl Core {
                                 .details("Download exceeded maximum size")
                         })?;
 
-                    logo = Resource::new(content_type, contents).into();
+                    logo = Resource::new(content_type, contents)
+                        .with_validators(now())
+                        .into();
                 }
 
                 self.security
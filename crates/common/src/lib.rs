@@ -7,30 +7,46 @@
 use std::{
     borrow::Cow,
     net::IpAddr,
-    sync::{atomic::AtomicU8, Arc},
+    sync::{
+        atomic::{AtomicU64, AtomicU8},
+        Arc,
+    },
+    time::Duration,
 };
 
 use ahash::AHashMap;
 use arc_swap::ArcSwap;
-use auth::{roles::RolePermissions, AccessToken};
+use auth::{
+    bandwidth::{BandwidthAccounting, BandwidthCounter},
+    last_login::{LastLogin, LastLoginTracking, LoginKind},
+    oauth::CachedToken,
+    roles::RolePermissions,
+    throttle::AuthThrottle,
+    AccessToken,
+};
 use config::{
     imap::ImapConfig,
     jmap::settings::JmapConfig,
+    managesieve::ManageSieveConfig,
+    pop3::Pop3Config,
     scripts::Scripting,
+    server::ServerProtocol,
     smtp::{
-        auth::{ArcSealer, DkimSigner},
-        queue::RelayHost,
+        auth::{ArcSealer, DkimSigner, MissingSigner, AUTO_SIGNER},
+        queue::{RelayHost, TransportError},
         SmtpConfig,
     },
     storage::Storage,
     telemetry::Metrics,
 };
 use directory::{
-    backend::internal::manage::ManageDirectory, core::secret::verify_secret_hash, Directory,
-    Principal, QueryBy, Type,
+    backend::internal::manage::ManageDirectory,
+    core::{cache::CachedAuth, secret::verify_secret_hash},
+    Directory, Principal, QueryBy, Type,
 };
 use expr::if_block::IfBlock;
-use futures::StreamExt;
+use futures::{Stream, StreamExt};
+use hyper::body::Bytes;
 use listener::{
     blocked::{AllowedIps, BlockedIps},
     tls::TlsManager,
@@ -42,10 +58,10 @@ use parking_lot::Mutex;
 use reqwest::Response;
 use sieve::Sieve;
 use store::{
-    write::{QueueClass, ValueClass},
-    IterateParams, LookupStore, ValueKey,
+    write::{key::DeserializeBigEndian, now, QueueClass, QueueDomain, ValueClass},
+    IterateParams, LookupStore, ValueKey, U64_LEN,
 };
-use tokio::sync::{mpsc, oneshot};
+use tokio::sync::{mpsc, oneshot, watch};
 use trc::AddContext;
 use utils::{
     map::ttl_dashmap::{ADashMap, TtlDashMap},
@@ -81,6 +97,8 @@ pub struct Core {
     pub smtp: SmtpConfig,
     pub jmap: JmapConfig,
     pub imap: ImapConfig,
+    pub pop3: Pop3Config,
+    pub managesieve: ManageSieveConfig,
     pub metrics: Metrics,
     pub security: Security,
     #[cfg(feature = "enterprise")]
@@ -92,8 +110,15 @@ pub struct Core {
 pub struct Security {
     pub logos: Mutex<AHashMap<String, Option<Resource<Vec<u8>>>>>,
     pub access_tokens: TtlDashMap<u32, Arc<AccessToken>>,
+    pub oauth_tokens: TtlDashMap<String, CachedToken>,
     pub permissions: ADashMap<u32, Arc<RolePermissions>>,
     pub permissions_version: AtomicU8,
+    pub auth_cache_version: AtomicU8,
+    pub account_sessions: ADashMap<u32, Arc<AtomicU64>>,
+    pub ip_sessions: ADashMap<IpAddr, Arc<AtomicU64>>,
+    pub last_logins: ADashMap<(u32, LoginKind), LastLogin>,
+    pub bandwidth: ADashMap<u32, Arc<BandwidthCounter>>,
+    pub session_revocations: ADashMap<u32, Arc<watch::Sender<u64>>>,
 }
 
 #[derive(Clone)]
@@ -103,13 +128,29 @@ pub struct Network {
     pub allowed_ips: AllowedIps,
     pub http_response_url: IfBlock,
     pub http_allowed_endpoint: IfBlock,
+    pub max_sessions_account: Option<u64>,
+    pub max_sessions_ip: Option<u64>,
+    pub auth_throttle: Option<AuthThrottle>,
+    pub last_login: Option<LastLoginTracking>,
+    pub bandwidth: Option<BandwidthAccounting>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct QueuedDomain {
+    pub count: u64,
+    pub oldest_age: Option<u64>,
+    pub next_retry: Option<u64>,
 }
 
 #[derive(Debug)]
 pub enum DeliveryEvent {
     Ingest {
         message: IngestMessage,
-        result_tx: oneshot::Sender<Vec<DeliveryResult>>,
+        result_tx: oneshot::Sender<Vec<RecipientResult>>,
+    },
+    Drain {
+        timeout: Duration,
+        result_tx: oneshot::Sender<()>,
     },
     Stop,
 }
@@ -118,6 +159,23 @@ pub struct Ipc {
     pub delivery_tx: mpsc::Sender<DeliveryEvent>,
 }
 
+impl Ipc {
+    // Waits for all queued deliveries to complete, after which new deliveries
+    // are rejected with a temporary failure until the delivery manager is
+    // stopped or the timeout elapses, when normal operation resumes. Returns
+    // false if the queued deliveries did not complete in time.
+    pub async fn drain_delivery(&self, timeout: Duration) -> bool {
+        let (result_tx, result_rx) = oneshot::channel();
+        self.delivery_tx
+            .send(DeliveryEvent::Drain { timeout, result_tx })
+            .await
+            .is_ok()
+            && tokio::time::timeout(timeout, result_rx)
+                .await
+                .map_or(false, |result| result.is_ok())
+    }
+}
+
 #[derive(Debug)]
 pub struct IngestMessage {
     pub sender_address: String,
@@ -127,6 +185,12 @@ pub struct IngestMessage {
     pub session_id: u64,
 }
 
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecipientResult {
+    pub recipient: String,
+    pub result: DeliveryResult,
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum DeliveryResult {
     Success,
@@ -170,17 +234,21 @@ impl Core {
     }
 
     pub fn get_lookup_store(&self, name: &str, session_id: u64) -> &LookupStore {
-        self.storage.lookups.get(name).unwrap_or_else(|| {
-            if !name.is_empty() {
-                trc::event!(
-                    Eval(trc::EvalEvent::StoreNotFound),
-                    Id = name.to_string(),
-                    SpanId = session_id,
-                );
-            }
+        self.try_get_lookup_store(name, session_id)
+            .unwrap_or(&self.storage.lookup)
+    }
 
-            &self.storage.lookup
-        })
+    pub fn try_get_lookup_store(&self, name: &str, session_id: u64) -> Option<&LookupStore> {
+        let store = self.storage.lookups.get(name);
+        if store.is_none() && !name.is_empty() {
+            trc::event!(
+                Eval(trc::EvalEvent::StoreNotFound),
+                Id = name.to_string(),
+                SpanId = session_id,
+            );
+        }
+
+        store
     }
 
     pub fn get_arc_sealer(&self, name: &str, session_id: u64) -> Option<&ArcSealer> {
@@ -217,6 +285,73 @@ impl Core {
             })
     }
 
+    pub fn resolve_dkim_signers(
+        &self,
+        names: &[String],
+        domain: &str,
+        session_id: u64,
+    ) -> Option<Vec<&DkimSigner>> {
+        let mut signers = Vec::with_capacity(names.len());
+        for name in names {
+            if name == AUTO_SIGNER {
+                let ids = self.domain_signer_ids(domain);
+                if ids.is_empty() {
+                    trc::event!(
+                        Dkim(trc::DkimEvent::SignerNotFound),
+                        Domain = domain.to_string(),
+                        SpanId = session_id,
+                    );
+
+                    if self.smtp.mail_auth.dkim.missing_signer == MissingSigner::Reject {
+                        return None;
+                    }
+                }
+                signers.extend(
+                    ids.iter()
+                        .filter_map(|id| self.get_dkim_signer(id, session_id)),
+                );
+            } else if let Some(signer) = self.get_dkim_signer(name, session_id) {
+                signers.push(signer);
+            }
+        }
+
+        Some(signers)
+    }
+
+    pub fn resolve_arc_sealer(
+        &self,
+        name: &str,
+        domain: &str,
+        session_id: u64,
+    ) -> Option<&ArcSealer> {
+        if name == AUTO_SIGNER {
+            self.domain_signer_ids(domain)
+                .first()
+                .and_then(|id| self.get_arc_sealer(id, session_id))
+        } else {
+            self.get_arc_sealer(name, session_id)
+        }
+    }
+
+    fn domain_signer_ids(&self, domain: &str) -> &[String] {
+        let mail_auth = &self.smtp.mail_auth;
+        let domain = domain.to_lowercase();
+        let mut name = domain.as_str();
+
+        // Try the domain itself and then each parent domain
+        loop {
+            if let Some(ids) = mail_auth.domain_signers.get(name) {
+                return ids;
+            }
+            match name.split_once('.') {
+                Some((_, parent)) if parent.contains('.') => name = parent,
+                _ => break,
+            }
+        }
+
+        &mail_auth.dkim.default_signers
+    }
+
     pub fn get_trusted_sieve_script(&self, name: &str, session_id: u64) -> Option<&Arc<Sieve>> {
         self.sieve.trusted_scripts.get(name).or_else(|| {
             trc::event!(
@@ -253,20 +388,114 @@ impl Core {
         })
     }
 
+    // Rewrites a recipient using the transport map. Loops and chains longer
+    // than the configured maximum are reported and leave the address unchanged.
+    pub fn rewrite_recipient(&self, address: &str, session_id: u64) -> Option<String> {
+        match self.smtp.queue.transport_map.rewrite(address) {
+            Ok(Some(rewritten)) => {
+                trc::event!(
+                    Queue(trc::QueueEvent::RecipientRewritten),
+                    SpanId = session_id,
+                    From = address.to_string(),
+                    To = rewritten.clone(),
+                );
+
+                Some(rewritten)
+            }
+            Ok(None) => None,
+            Err(err) => {
+                trc::event!(
+                    Queue(trc::QueueEvent::RewriteLoop),
+                    SpanId = session_id,
+                    To = address.to_string(),
+                    Reason = match err {
+                        TransportError::Loop => "Rewrite loop detected",
+                        TransportError::TooManyRewrites => "Too many rewrites",
+                    },
+                );
+
+                None
+            }
+        }
+    }
+
     pub async fn authenticate(
         &self,
         directory: &Directory,
         session_id: u64,
         credentials: &Credentials<String>,
         remote_ip: IpAddr,
+        protocol: ServerProtocol,
         return_member_of: bool,
     ) -> trc::Result<Principal> {
-        // First try to authenticate the user against the default directory
-        let result = match directory
-            .query(QueryBy::Credentials(credentials), return_member_of)
-            .await
-        {
-            Ok(Some(principal)) => {
+        let result = self
+            .authenticate_credentials(
+                directory,
+                session_id,
+                credentials,
+                remote_ip,
+                protocol,
+                return_member_of,
+            )
+            .await;
+
+        // Slow down repeated failures before they reach the fail2ban threshold
+        match &result {
+            Ok(principal) => {
+                self.reset_auth_failures(remote_ip, credentials.login(), session_id)
+                    .await;
+                self.record_login(principal.id(), remote_ip, LoginKind::Login, session_id)
+                    .await;
+            }
+            Err(err) if err.matches(trc::EventType::Auth(trc::AuthEvent::Failed)) => {
+                self.throttle_auth_failure(remote_ip, credentials.login(), session_id)
+                    .await;
+            }
+            Err(_) => {}
+        }
+
+        result
+    }
+
+    async fn authenticate_credentials(
+        &self,
+        directory: &Directory,
+        session_id: u64,
+        credentials: &Credentials<String>,
+        remote_ip: IpAddr,
+        protocol: ServerProtocol,
+        return_member_of: bool,
+    ) -> trc::Result<Principal> {
+        // Known credentials are served from the authentication cache, which
+        // keeps repeated failed attempts from reaching the directory backend
+        let auth_cache = match (&directory.auth_cache, credentials) {
+            (Some(cache), Credentials::Plain { username, secret }) => Some((
+                cache,
+                CachedAuth::key(username, secret, protocol.as_str(), return_member_of),
+            )),
+            _ => None,
+        };
+        let cached = auth_cache.as_ref().and_then(|(cache, key)| cache.get(key));
+        let is_cached_failure = matches!(cached, Some(None));
+
+        // First try to authenticate the user against the default directory, the app
+        // passwords of the account are verified in the same lookup
+        let query_result = if let Some(principal) = cached {
+            Ok(principal.map(|principal| (principal, false)))
+        } else {
+            let result = directory
+                .query_credentials(credentials, protocol.as_str(), return_member_of)
+                .await;
+            if let (Some((cache, key)), Ok(Some((principal, false)))) = (&auth_cache, &result) {
+                cache.set(key.clone(), Some(principal));
+            }
+            result
+        };
+        let is_directory_failure = !is_cached_failure && matches!(query_result, Ok(None));
+        let result = match query_result {
+            Ok(Some((principal, true))) => {
+                // App passwords are meant for clients that cannot complete the
+                // second factor challenge
                 trc::event!(
                     Auth(trc::AuthEvent::Success),
                     AccountName = credentials.login().to_string(),
@@ -277,37 +506,85 @@ impl Core {
 
                 return Ok(principal);
             }
+            Ok(Some((principal, false))) => {
+                // Perform the external second factor challenge, if enabled
+                if self
+                    .verify_mfa(credentials.login(), principal.id(), remote_ip, session_id)
+                    .await
+                {
+                    trc::event!(
+                        Auth(trc::AuthEvent::Success),
+                        AccountName = credentials.login().to_string(),
+                        AccountId = principal.id(),
+                        SpanId = session_id,
+                        Type = principal.typ().as_str(),
+                    );
+
+                    return Ok(principal);
+                }
+
+                trc::event!(
+                    Auth(trc::AuthEvent::MfaDenied),
+                    AccountName = credentials.login().to_string(),
+                    AccountId = principal.id(),
+                    SpanId = session_id,
+                    RemoteIp = remote_ip,
+                );
+
+                Ok(())
+            }
             Ok(None) => Ok(()),
             Err(err) => {
                 if err.matches(trc::EventType::Auth(trc::AuthEvent::MissingTotp)) {
                     return Err(err);
+                } else if is_directory_backend_error(&err) {
+                    Err(directory_unavailable(err, credentials.login()))
                 } else {
-                    Err(err)
+                    // Secrets that cannot be verified, such as hashes in an unsupported
+                    // format, are logged and the credentials rejected
+                    trc::error!(err
+                        .ctx(trc::Key::AccountName, credentials.login().to_string())
+                        .span_id(session_id)
+                        .caused_by(trc::location!()));
+                    Ok(())
                 }
             }
         };
 
-        // Then check if the credentials match the fallback admin or master user
-        match (
-            &self.jmap.fallback_admin,
-            &self.jmap.master_user,
-            credentials,
-        ) {
-            (Some((fallback_admin, fallback_pass)), _, Credentials::Plain { username, secret })
-                if username == fallback_admin =>
+        // Only cache credentials rejected by the directory, not those that
+        // failed the second factor challenge
+        if let (Ok(()), Some((cache, key)), true) = (&result, auth_cache, is_directory_failure) {
+            cache.set(key, None);
+        }
+
+        // Then check if the credentials match the fallback admin or master user,
+        // these are not subject to the external second factor challenge
+        if let Credentials::Plain { username, secret } = credentials {
+            if let Some(fallback_admin) = self
+                .jmap
+                .fallback_admins
+                .iter()
+                .find(|fallback_admin| &fallback_admin.user == username)
             {
-                if verify_secret_hash(fallback_pass, secret).await? {
+                if verify_secret_hash(&fallback_admin.secret, secret).await? {
                     trc::event!(
                         Auth(trc::AuthEvent::Success),
                         AccountName = username.clone(),
+                        AccountId = fallback_admin.id,
                         SpanId = session_id,
                     );
 
-                    return Ok(Principal::fallback_admin(fallback_pass));
+                    return Ok(Principal::fallback_admin(
+                        fallback_admin.id,
+                        &fallback_admin.user,
+                        &fallback_admin.secret,
+                    ));
                 }
-            }
-            (_, Some((master_user, master_pass)), Credentials::Plain { username, secret })
-                if username.ends_with(master_user) =>
+            } else if let Some((master_user, master_pass)) = self
+                .jmap
+                .master_user
+                .as_ref()
+                .filter(|(master_user, _)| username.ends_with(master_user))
             {
                 if verify_secret_hash(master_pass, secret).await? {
                     let username = username.strip_suffix(master_user).unwrap();
@@ -315,13 +592,16 @@ impl Core {
 
                     if let Some(principal) = directory
                         .query(QueryBy::Name(username), return_member_of)
-                        .await?
+                        .await
+                        .map_err(|err| directory_unavailable(err, username))?
                     {
                         trc::event!(
-                            Auth(trc::AuthEvent::Success),
+                            Auth(trc::AuthEvent::Impersonation),
                             AccountName = username.to_string(),
-                            SpanId = session_id,
                             AccountId = principal.id(),
+                            Id = master_user.clone(),
+                            SpanId = session_id,
+                            RemoteIp = remote_ip,
                             Type = principal.typ().as_str(),
                         );
 
@@ -329,7 +609,6 @@ impl Core {
                     }
                 }
             }
-            _ => {}
         }
 
         if let Err(err) = result {
@@ -353,6 +632,92 @@ impl Core {
         }
     }
 
+    pub fn clear_auth_cache(&self) {
+        for cache in self.auth_caches() {
+            cache.clear();
+        }
+        self.security.oauth_tokens.clear();
+    }
+
+    // Removes the cached credentials and OAuth tokens of an account on this node
+    // and records the change in the lookup store, so that other nodes can apply it
+    // once they notice the new auth cache version through gossip. The login names
+    // are only needed when failed attempts may have become valid credentials.
+    pub async fn invalidate_auth_cache(&self, account_id: u32, names: &[String]) {
+        let mut max_ttl = self.jmap.session_cache_ttl;
+        for cache in self.auth_caches() {
+            cache.invalidate(account_id, names, u64::MAX);
+            max_ttl = max_ttl.max(cache.max_ttl());
+        }
+        self.security
+            .oauth_tokens
+            .retain(|_, token| token.item.account_id != account_id);
+
+        if let Err(err) = self
+            .storage
+            .lookup
+            .key_set(
+                format!("ac:{account_id}").into_bytes(),
+                format!("{}\n{}", now(), names.join("\n")).into_bytes(),
+                Some(max_ttl.as_secs()),
+            )
+            .await
+        {
+            trc::error!(err
+                .account_id(account_id)
+                .caused_by(trc::location!())
+                .details("Failed to store auth cache invalidation"));
+        }
+
+        self.security
+            .auth_cache_version
+            .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+    }
+
+    // Applies the invalidations recorded by other nodes, everything is dropped
+    // when they cannot be listed from the lookup store
+    pub async fn apply_auth_cache_invalidations(&self) {
+        if !matches!(self.storage.lookup, LookupStore::Store(_)) {
+            self.clear_auth_cache();
+            return;
+        }
+
+        match self.storage.lookup.key_get_prefix::<String>(b"ac:").await {
+            Ok(invalidations) => {
+                for (key, value) in invalidations {
+                    let account_id = std::str::from_utf8(key.get(3..).unwrap_or_default())
+                        .ok()
+                        .and_then(|account_id| account_id.parse::<u32>().ok());
+                    let mut lines = value.lines();
+                    let cached_until = lines.next().and_then(|ts| ts.parse::<u64>().ok());
+
+                    if let (Some(account_id), Some(cached_until)) = (account_id, cached_until) {
+                        let names = lines.map(|name| name.to_string()).collect::<Vec<_>>();
+                        for cache in self.auth_caches() {
+                            cache.invalidate(account_id, &names, cached_until);
+                        }
+                        self.security.oauth_tokens.retain(|_, token| {
+                            token.item.account_id != account_id
+                                || token.item.cached_at > cached_until
+                        });
+                    }
+                }
+            }
+            Err(err) => {
+                trc::error!(err
+                    .caused_by(trc::location!())
+                    .details("Failed to fetch auth cache invalidations"));
+                self.clear_auth_cache();
+            }
+        }
+    }
+
+    fn auth_caches(&self) -> impl Iterator<Item = &CachedAuth> {
+        std::iter::once(&self.storage.directory)
+            .chain(self.storage.directories.values())
+            .filter_map(|directory| directory.auth_cache.as_ref())
+    }
+
     pub async fn total_queued_messages(&self) -> trc::Result<u64> {
         let mut total = 0;
         self.storage
@@ -373,23 +738,94 @@ impl Core {
             .map(|_| total)
     }
 
-    pub async fn total_accounts(&self) -> trc::Result<u64> {
+    pub async fn queued_messages_for_domain(&self, domain: &str) -> trc::Result<QueuedDomain> {
+        let mut queued = QueuedDomain::default();
+        let domain = domain.to_lowercase();
+        let now = now();
+
+        self.storage
+            .data
+            .iterate(
+                IterateParams::new(
+                    ValueKey::from(ValueClass::Queue(QueueClass::MessageDomain(QueueDomain {
+                        domain: domain.clone(),
+                        queue_id: 0,
+                    }))),
+                    ValueKey::from(ValueClass::Queue(QueueClass::MessageDomain(QueueDomain {
+                        domain,
+                        queue_id: u64::MAX,
+                    }))),
+                ),
+                |_, value| {
+                    let created = value.deserialize_be_u64(0)?;
+                    let next_retry = value.deserialize_be_u64(U64_LEN)?;
+
+                    queued.count += 1;
+                    queued.oldest_age = queued.oldest_age.max(Some(now.saturating_sub(created)));
+                    queued.next_retry = Some(
+                        queued
+                            .next_retry
+                            .map_or(next_retry, |retry| retry.min(next_retry)),
+                    );
+
+                    Ok(true)
+                },
+            )
+            .await
+            .map(|_| queued)
+    }
+
+    // Counts the accounts owned by a tenant or all accounts when no tenant
+    // is provided, unknown tenants have no accounts.
+    pub async fn total_accounts(&self, tenant_id: Option<u32>) -> trc::Result<u64> {
         self.storage
             .data
-            .count_principals(None, Type::Individual.into(), None)
+            .count_principals(None, Type::Individual.into(), tenant_id)
             .await
             .caused_by(trc::location!())
     }
 
-    pub async fn total_domains(&self) -> trc::Result<u64> {
+    pub async fn total_domains(&self, tenant_id: Option<u32>) -> trc::Result<u64> {
         self.storage
             .data
-            .count_principals(None, Type::Domain.into(), None)
+            .count_principals(None, Type::Domain.into(), tenant_id)
+            .await
+            .caused_by(trc::location!())
+    }
+
+    // Counts several principal types with a single scan of the principal index
+    pub async fn total_principals(
+        &self,
+        types: &[Type],
+        tenant_id: Option<u32>,
+    ) -> trc::Result<AHashMap<Type, u64>> {
+        self.storage
+            .data
+            .count_principals_by_type(types, tenant_id)
             .await
             .caused_by(trc::location!())
     }
 }
 
+// Backend failures are reported as a temporary condition so that clients retry
+// later and the attempt is not counted as a credential failure
+pub(crate) fn directory_unavailable(err: trc::Error, login: &str) -> trc::Error {
+    if is_directory_backend_error(&err) {
+        trc::AuthEvent::DirectoryUnavailable
+            .ctx(trc::Key::AccountName, login.to_string())
+            .caused_by(err)
+    } else {
+        err
+    }
+}
+
+fn is_directory_backend_error(err: &trc::Error) -> bool {
+    matches!(
+        err.as_ref(),
+        trc::EventType::Store(_) | trc::EventType::Imap(_) | trc::EventType::Smtp(_)
+    )
+}
+
 trait CredentialsUsername {
     fn login(&self) -> &str;
 }
@@ -408,12 +844,22 @@ impl Clone for Security {
     fn clone(&self) -> Self {
         Self {
             access_tokens: self.access_tokens.clone(),
+            oauth_tokens: self.oauth_tokens.clone(),
             permissions: self.permissions.clone(),
             permissions_version: AtomicU8::new(
                 self.permissions_version
                     .load(std::sync::atomic::Ordering::Relaxed),
             ),
+            auth_cache_version: AtomicU8::new(
+                self.auth_cache_version
+                    .load(std::sync::atomic::Ordering::Relaxed),
+            ),
             logos: Mutex::new(self.logos.lock().clone()),
+            account_sessions: self.account_sessions.clone(),
+            ip_sessions: self.ip_sessions.clone(),
+            last_logins: self.last_logins.clone(),
+            bandwidth: self.bandwidth.clone(),
+            session_revocations: self.session_revocations.clone(),
         }
     }
 }
@@ -423,28 +869,71 @@ pub trait HttpLimitResponse: Sync + Send {
         self,
         limit: usize,
     ) -> impl std::future::Future<Output = reqwest::Result<Option<Vec<u8>>>> + Send;
+
+    // Yields the response body in chunks, returns None when the advertised
+    // content length exceeds the limit and yields Ok(None) as its last item
+    // when the limit is exceeded while streaming.
+    fn stream_with_limit(
+        self,
+        limit: usize,
+    ) -> Option<impl Stream<Item = reqwest::Result<Option<Bytes>>> + Send + Unpin>;
 }
 
 impl HttpLimitResponse for Response {
     async fn bytes_with_limit(self, limit: usize) -> reqwest::Result<Option<Vec<u8>>> {
-        if self
-            .content_length()
-            .map_or(false, |len| len as usize > limit)
-        {
+        let mut stream = if let Some(stream) = self.stream_with_limit(limit) {
+            stream
+        } else {
             return Ok(None);
-        }
-
+        };
         let mut bytes = Vec::with_capacity(std::cmp::min(limit, 1024));
-        let mut stream = self.bytes_stream();
 
         while let Some(chunk) = stream.next().await {
-            let chunk = chunk?;
-            if bytes.len() + chunk.len() > limit {
+            if let Some(chunk) = chunk? {
+                bytes.extend_from_slice(&chunk);
+            } else {
                 return Ok(None);
             }
-            bytes.extend_from_slice(&chunk);
         }
 
         Ok(Some(bytes))
     }
+
+    fn stream_with_limit(
+        self,
+        limit: usize,
+    ) -> Option<impl Stream<Item = reqwest::Result<Option<Bytes>>> + Send + Unpin> {
+        if self
+            .content_length()
+            .map_or(false, |len| len as usize > limit)
+        {
+            return None;
+        }
+
+        Some(
+            self.bytes_stream()
+                .scan(Some(0usize), move |total, chunk| {
+                    let result = if let Some(size) = *total {
+                        match chunk {
+                            Ok(chunk) if size + chunk.len() <= limit => {
+                                *total = Some(size + chunk.len());
+                                Some(Ok(Some(chunk)))
+                            }
+                            Ok(_) => {
+                                *total = None;
+                                Some(Ok(None))
+                            }
+                            Err(err) => {
+                                *total = None;
+                                Some(Err(err))
+                            }
+                        }
+                    } else {
+                        None
+                    };
+
+                    std::future::ready(result)
+                }),
+        )
+    }
 }
@@ -28,7 +28,7 @@ pub struct AcmeProvider {
     pub challenge: ChallengeSettings,
     renew_before: chrono::Duration,
     account_key: ArcSwap<Vec<u8>>,
-    default: bool,
+    pub(crate) default: bool,
 }
 
 #[derive(Clone)]
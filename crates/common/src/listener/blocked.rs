@@ -9,7 +9,7 @@ use std::{fmt::Debug, net::IpAddr, sync::atomic::AtomicU8};
 use ahash::AHashSet;
 use parking_lot::RwLock;
 use utils::config::{
-    ipmask::{IpAddrMask, IpAddrOrMask},
+    ipmask::{IpAddrMask, IpAddrOrMask, IpNetworkTree},
     utils::ParseValue,
     Config, ConfigKey, Rate,
 };
@@ -18,19 +18,16 @@ use crate::Core;
 
 pub struct BlockedIps {
     pub ip_addresses: RwLock<AHashSet<IpAddr>>,
+    pub ip_networks: RwLock<IpNetworkTree>,
     pub version: AtomicU8,
-    ip_networks: Vec<IpAddrMask>,
-    has_networks: bool,
     auth_fail_rate: Option<Rate>,
     rcpt_fail_rate: Option<Rate>,
     loiter_fail_rate: Option<Rate>,
 }
 
-#[derive(Clone)]
 pub struct AllowedIps {
     ip_addresses: AHashSet<IpAddr>,
-    ip_networks: Vec<IpAddrMask>,
-    has_networks: bool,
+    ip_networks: RwLock<IpNetworkTree>,
 }
 
 pub const BLOCKED_IP_KEY: &str = "server.blocked-ip";
@@ -41,7 +38,7 @@ pub const ALLOWED_IP_PREFIX: &str = "server.allowed-ip.";
 impl BlockedIps {
     pub fn parse(config: &mut Config) -> Self {
         let mut ip_addresses = AHashSet::new();
-        let mut ip_networks = Vec::new();
+        let mut ip_networks = IpNetworkTree::default();
 
         for ip in config
             .set_values(BLOCKED_IP_KEY)
@@ -53,7 +50,7 @@ impl BlockedIps {
                     ip_addresses.insert(ip);
                 }
                 Ok(IpAddrOrMask::Mask(ip)) => {
-                    ip_networks.push(ip);
+                    ip_networks.insert(&ip);
                 }
                 Err(err) => {
                     config.new_parse_error(BLOCKED_IP_KEY, err);
@@ -63,8 +60,7 @@ impl BlockedIps {
 
         BlockedIps {
             ip_addresses: RwLock::new(ip_addresses),
-            has_networks: !ip_networks.is_empty(),
-            ip_networks,
+            ip_networks: RwLock::new(ip_networks),
             auth_fail_rate: config
                 .property_or_default::<Option<Rate>>("server.fail2ban.authentication", "100/1d")
                 .unwrap_or_default(),
@@ -82,7 +78,7 @@ impl BlockedIps {
 impl AllowedIps {
     pub fn parse(config: &mut Config) -> Self {
         let mut ip_addresses = AHashSet::new();
-        let mut ip_networks = Vec::new();
+        let mut ip_networks = IpNetworkTree::default();
 
         for ip in config
             .set_values(ALLOWED_IP_KEY)
@@ -94,7 +90,7 @@ impl AllowedIps {
                     ip_addresses.insert(ip);
                 }
                 Ok(IpAddrOrMask::Mask(ip)) => {
-                    ip_networks.push(ip);
+                    ip_networks.insert(&ip);
                 }
                 Err(err) => {
                     config.new_parse_error(ALLOWED_IP_KEY, err);
@@ -111,8 +107,7 @@ impl AllowedIps {
 
         AllowedIps {
             ip_addresses,
-            has_networks: !ip_networks.is_empty(),
-            ip_networks,
+            ip_networks: RwLock::new(ip_networks),
         }
     }
 }
@@ -200,26 +195,72 @@ impl Core {
         self.network.blocked_ips.auth_fail_rate.is_some()
     }
 
+    pub async fn add_blocked_range(&self, network: IpAddrMask) -> trc::Result<()> {
+        // Add network to blocked list
+        self.network.blocked_ips.ip_networks.write().insert(&network);
+
+        // Write blocked network to config
+        self.storage
+            .config
+            .set([ConfigKey {
+                key: format!("{}.{}", BLOCKED_IP_KEY, network),
+                value: String::new(),
+            }])
+            .await?;
+
+        // Increment version
+        self.network.blocked_ips.increment_version();
+
+        Ok(())
+    }
+
+    pub async fn remove_blocked_range(&self, network: &IpAddrMask) -> trc::Result<bool> {
+        if self.network.blocked_ips.ip_networks.write().remove(network) {
+            self.storage
+                .config
+                .clear(format!("{}.{}", BLOCKED_IP_KEY, network))
+                .await?;
+            self.network.blocked_ips.increment_version();
+
+            Ok(true)
+        } else {
+            Ok(false)
+        }
+    }
+
+    pub async fn add_allowed_range(&self, network: IpAddrMask) -> trc::Result<()> {
+        self.network.allowed_ips.ip_networks.write().insert(&network);
+        self.storage
+            .config
+            .set([ConfigKey {
+                key: format!("{}.{}", ALLOWED_IP_KEY, network),
+                value: String::new(),
+            }])
+            .await
+    }
+
+    pub async fn remove_allowed_range(&self, network: &IpAddrMask) -> trc::Result<bool> {
+        if self.network.allowed_ips.ip_networks.write().remove(network) {
+            self.storage
+                .config
+                .clear(format!("{}.{}", ALLOWED_IP_KEY, network))
+                .await
+                .map(|_| true)
+        } else {
+            Ok(false)
+        }
+    }
+
     pub fn is_ip_blocked(&self, ip: &IpAddr) -> bool {
-        self.network.blocked_ips.ip_addresses.read().contains(ip)
-            || (self.network.blocked_ips.has_networks
-                && self
-                    .network
-                    .blocked_ips
-                    .ip_networks
-                    .iter()
-                    .any(|network| network.matches(ip)))
+        // Allowed addresses and networks take precedence over blocked ones
+        (self.network.blocked_ips.ip_addresses.read().contains(ip)
+            || self.network.blocked_ips.ip_networks.read().matches(ip))
+            && !self.is_ip_allowed(ip)
     }
 
     pub fn is_ip_allowed(&self, ip: &IpAddr) -> bool {
         self.network.allowed_ips.ip_addresses.contains(ip)
-            || (self.network.allowed_ips.has_networks
-                && self
-                    .network
-                    .allowed_ips
-                    .ip_networks
-                    .iter()
-                    .any(|network| network.matches(ip)))
+            || self.network.allowed_ips.ip_networks.read().matches(ip)
     }
 }
 
@@ -235,7 +276,6 @@ impl Default for BlockedIps {
         Self {
             ip_addresses: RwLock::new(AHashSet::new()),
             ip_networks: Default::default(),
-            has_networks: Default::default(),
             version: Default::default(),
             auth_fail_rate: Default::default(),
             rcpt_fail_rate: Default::default(),
@@ -257,7 +297,15 @@ impl Default for AllowedIps {
             #[cfg(feature = "test_mode")]
             ip_addresses: Default::default(),
             ip_networks: Default::default(),
-            has_networks: Default::default(),
+        }
+    }
+}
+
+impl Clone for AllowedIps {
+    fn clone(&self) -> Self {
+        Self {
+            ip_addresses: self.ip_addresses.clone(),
+            ip_networks: RwLock::new(self.ip_networks.read().clone()),
         }
     }
 }
@@ -266,8 +314,7 @@ impl Clone for BlockedIps {
     fn clone(&self) -> Self {
         Self {
             ip_addresses: RwLock::new(self.ip_addresses.read().clone()),
-            ip_networks: self.ip_networks.clone(),
-            has_networks: self.has_networks,
+            ip_networks: RwLock::new(self.ip_networks.read().clone()),
             version: self
                 .version
                 .load(std::sync::atomic::Ordering::Relaxed)
@@ -284,7 +331,6 @@ impl Debug for BlockedIps {
         f.debug_struct("BlockedIps")
             .field("ip_addresses", &self.ip_addresses)
             .field("ip_networks", &self.ip_networks)
-            .field("has_networks", &self.has_networks)
             .field("version", &self.version)
             .field("auth_fail_rate", &self.auth_fail_rate)
             .field("rcpt_fail_rate", &self.rcpt_fail_rate)
@@ -43,6 +43,7 @@ impl Server {
         let instance = Arc::new(ServerInstance {
             id: self.id,
             protocol: self.protocol,
+            role: self.role,
             proxy_networks: self.proxy_networks,
             limiter: ConcurrencyLimiter::new(self.max_connections),
             acceptor,
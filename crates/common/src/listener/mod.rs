@@ -17,7 +17,7 @@ use trc::{Event, EventType, Key};
 use utils::{config::ipmask::IpAddrMask, snowflake::SnowflakeIdGenerator};
 
 use crate::{
-    config::server::ServerProtocol,
+    config::server::{ListenerRole, ServerProtocol},
     expr::{functions::ResolveVariable, *},
     Core,
 };
@@ -28,12 +28,14 @@ pub mod acme;
 pub mod blocked;
 pub mod limiter;
 pub mod listen;
+pub mod ocsp;
 pub mod stream;
 pub mod tls;
 
 pub struct ServerInstance {
     pub id: String,
     pub protocol: ServerProtocol,
+    pub role: Option<ListenerRole>,
     pub acceptor: TcpAcceptor,
     pub limiter: ConcurrencyLimiter,
     pub proxy_networks: Vec<IpAddrMask>,
@@ -77,6 +79,7 @@ pub struct SessionData<T: SessionStream> {
 pub trait SessionStream: AsyncRead + AsyncWrite + Unpin + 'static + Sync + Send {
     fn is_tls(&self) -> bool;
     fn tls_version_and_cipher(&self) -> (Cow<'static, str>, Cow<'static, str>);
+    fn tls_exporter(&self) -> Option<Vec<u8>>;
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -84,6 +87,7 @@ pub enum SessionResult {
     Continue,
     Close,
     UpgradeTls,
+    UpgradeCompression,
 }
 
 pub trait SessionManager: Sync + Send + 'static + Clone {
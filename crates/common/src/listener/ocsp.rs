@@ -0,0 +1,843 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use std::{sync::Arc, time::Duration};
+
+use ahash::AHashMap;
+use ring::signature::{self, UnparsedPublicKey, VerificationAlgorithm};
+use rustls::sign::CertifiedKey;
+use sha1::{Digest, Sha1};
+use store::write::now;
+use utils::config::Config;
+use x509_parser::{
+    certificate::X509Certificate,
+    der_parser::asn1_rs::FromDer,
+    extensions::{GeneralName, ParsedExtension},
+};
+use yasna::{models::ObjectIdentifier, tags::TAG_INTEGER, ASN1Result, BERReader, DERWriter, Tag};
+
+use crate::{Core, HttpLimitResponse};
+
+const MAX_RESPONSE_SIZE: usize = 64 * 1024;
+const MIN_REFRESH_INTERVAL: Duration = Duration::from_secs(60);
+
+const MAX_CLOCK_SKEW: u64 = 300;
+
+const OID_SHA1: &[u64] = &[1, 3, 14, 3, 2, 26];
+const OID_OCSP_BASIC: &[u64] = &[1, 3, 6, 1, 5, 5, 7, 48, 1, 1];
+const OID_OCSP_ACCESS_METHOD: &str = "1.3.6.1.5.5.7.48.1";
+
+#[derive(Clone)]
+pub struct OcspConfig {
+    pub enable: bool,
+    pub refresh: Duration,
+    pub client: reqwest::Client,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct OcspStatus {
+    pub next_update: Option<u64>,
+}
+
+impl OcspConfig {
+    pub fn parse(config: &mut Config) -> Self {
+        let timeout = config
+            .property_or_default("server.tls.ocsp.timeout", "10s")
+            .unwrap_or_else(|| Duration::from_secs(10));
+
+        OcspConfig {
+            enable: config
+                .property_or_default("server.tls.ocsp.enable", "false")
+                .unwrap_or(false),
+            refresh: config
+                .property_or_default("server.tls.ocsp.refresh", "12h")
+                .unwrap_or_else(|| Duration::from_secs(12 * 3600)),
+            client: build_ocsp_client(timeout).unwrap_or_else(|err| {
+                config.new_build_error(
+                    "server.tls.ocsp.timeout",
+                    format!("Failed to build HTTP client: {err}"),
+                );
+                reqwest::Client::default()
+            }),
+        }
+    }
+}
+
+impl Default for OcspConfig {
+    fn default() -> Self {
+        OcspConfig {
+            enable: false,
+            refresh: Duration::from_secs(12 * 3600),
+            client: build_ocsp_client(Duration::from_secs(10)).unwrap_or_default(),
+        }
+    }
+}
+
+fn build_ocsp_client(timeout: Duration) -> reqwest::Result<reqwest::Client> {
+    reqwest::Client::builder().timeout(timeout).build()
+}
+
+impl Core {
+    // Fetches fresh OCSP responses for all certificates and returns the time
+    // until the next refresh is due. Responder errors never remove a staple
+    // that has not yet expired.
+    pub async fn refresh_ocsp_staples(&self) -> Duration {
+        let mut next_refresh = self.tls.ocsp.refresh;
+        let certificates = self.tls.certificates.load_full();
+        let mut updates: Vec<(Arc<CertifiedKey>, Arc<CertifiedKey>)> = Vec::new();
+        let mut visited: Vec<&Arc<CertifiedKey>> = Vec::new();
+
+        for (name, cert) in certificates.iter() {
+            if visited.iter().any(|visited| Arc::ptr_eq(visited, cert)) {
+                continue;
+            }
+            visited.push(cert);
+
+            let ocsp = match fetch_ocsp_response(&self.tls.ocsp.client, cert).await {
+                Ok(Some((response, status))) => {
+                    trc::event!(
+                        Tls(trc::TlsEvent::OcspResponseFetched),
+                        Hostname = name.to_string(),
+                        Expires = status.next_update.map(trc::Value::Timestamp),
+                    );
+
+                    Some(response)
+                }
+                Ok(None) => continue,
+                Err(err) => {
+                    let staple_expires = stapled_status(cert).and_then(|status| status.next_update);
+
+                    trc::event!(
+                        Tls(trc::TlsEvent::OcspResponseError),
+                        Hostname = name.to_string(),
+                        Reason = err,
+                        Expires = staple_expires.map(trc::Value::Timestamp),
+                    );
+
+                    // Retry sooner while the responder is unavailable
+                    next_refresh = next_refresh.min(MIN_REFRESH_INTERVAL * 5);
+
+                    if staple_expires.is_some() || cert.ocsp.is_none() {
+                        continue;
+                    }
+                    None
+                }
+            };
+
+            updates.push((
+                cert.clone(),
+                Arc::new(CertifiedKey {
+                    cert: cert.cert.clone(),
+                    key: cert.key.clone(),
+                    ocsp,
+                }),
+            ));
+        }
+
+        // Refresh before the stapled responses expire
+        for (_, cert) in &updates {
+            if let Some(next_update) = stapled_status(cert).and_then(|status| status.next_update) {
+                next_refresh = next_refresh.min(
+                    Duration::from_secs(next_update.saturating_sub(now()) / 2)
+                        .max(MIN_REFRESH_INTERVAL),
+                );
+            }
+        }
+
+        if !updates.is_empty() {
+            let mut certificates = self.tls.certificates.load().as_ref().clone();
+            for cert in certificates.values_mut() {
+                if let Some((_, updated)) = updates.iter().find(|(old, _)| Arc::ptr_eq(old, cert)) {
+                    *cert = updated.clone();
+                }
+            }
+            self.tls.certificates.store(certificates.into());
+        }
+
+        next_refresh
+    }
+}
+
+// Copies the stapled responses of unchanged certificates after a reload
+pub(crate) fn carry_ocsp_staples(
+    previous: &AHashMap<String, Arc<CertifiedKey>>,
+    certificates: &mut AHashMap<String, Arc<CertifiedKey>>,
+) {
+    for cert in certificates.values_mut() {
+        if cert.ocsp.is_none() {
+            if let Some(staple) = previous
+                .values()
+                .find(|previous| previous.ocsp.is_some() && previous.cert == cert.cert)
+                .and_then(|previous| previous.ocsp.clone())
+            {
+                *cert = Arc::new(CertifiedKey {
+                    cert: cert.cert.clone(),
+                    key: cert.key.clone(),
+                    ocsp: Some(staple),
+                });
+            }
+        }
+    }
+}
+
+// Verifies the stapled response of a certificate, discarding it once it expires
+fn stapled_status(cert: &CertifiedKey) -> Option<OcspStatus> {
+    let staple = cert.ocsp.as_deref()?;
+    let (end_entity, issuer) = parse_chain(cert).ok()??;
+    verify_ocsp_response(staple, &end_entity, &issuer, now()).ok()
+}
+
+fn parse_chain(
+    cert: &CertifiedKey,
+) -> Result<Option<(X509Certificate<'_>, X509Certificate<'_>)>, String> {
+    // The issuer has to be included in the chain to build the request
+    let (end_entity, issuer) = match (cert.cert.first(), cert.cert.get(1)) {
+        (Some(end_entity), Some(issuer)) => (end_entity, issuer),
+        _ => return Ok(None),
+    };
+    let (_, end_entity) = X509Certificate::from_der(end_entity.as_ref())
+        .map_err(|err| format!("Failed to parse certificate: {err}"))?;
+    let (_, issuer) = X509Certificate::from_der(issuer.as_ref())
+        .map_err(|err| format!("Failed to parse issuer certificate: {err}"))?;
+
+    Ok(Some((end_entity, issuer)))
+}
+
+async fn fetch_ocsp_response(
+    client: &reqwest::Client,
+    cert: &CertifiedKey,
+) -> Result<Option<(Vec<u8>, OcspStatus)>, String> {
+    let (end_entity, issuer) = if let Some(chain) = parse_chain(cert)? {
+        chain
+    } else {
+        return Ok(None);
+    };
+
+    // Obtain the responder URL
+    let url = if let Some(url) = end_entity.extensions().iter().find_map(|ext| {
+        if let ParsedExtension::AuthorityInfoAccess(aia) = ext.parsed_extension() {
+            aia.accessdescs
+                .iter()
+                .find_map(|desc| match &desc.access_location {
+                    GeneralName::URI(url)
+                        if desc.access_method.to_id_string() == OID_OCSP_ACCESS_METHOD =>
+                    {
+                        Some(url.to_string())
+                    }
+                    _ => None,
+                })
+        } else {
+            None
+        }
+    }) {
+        url
+    } else {
+        return Ok(None);
+    };
+
+    let request = build_ocsp_request(
+        issuer.subject().as_raw(),
+        issuer.public_key().subject_public_key.data.as_ref(),
+        end_entity.raw_serial(),
+    );
+
+    let response = client
+        .post(&url)
+        .header("Content-Type", "application/ocsp-request")
+        .body(request)
+        .send()
+        .await
+        .map_err(|err| format!("OCSP request to {url} failed: {err}"))?;
+
+    if !response.status().is_success() {
+        return Err(format!(
+            "OCSP responder {url} returned code {}",
+            response.status().as_u16()
+        ));
+    }
+
+    let response = response
+        .bytes_with_limit(MAX_RESPONSE_SIZE)
+        .await
+        .map_err(|err| format!("Failed to read OCSP response from {url}: {err}"))?
+        .ok_or_else(|| format!("OCSP response from {url} is too large"))?;
+
+    verify_ocsp_response(&response, &end_entity, &issuer, now())
+        .map(|status| Some((response, status)))
+        .map_err(|err| format!("OCSP responder {url} returned an invalid response: {err}"))
+}
+
+pub(crate) fn build_ocsp_request(issuer_name: &[u8], issuer_key: &[u8], serial: &[u8]) -> Vec<u8> {
+    // OCSPRequest > TBSRequest > requestList > Request > CertID
+    yasna::construct_der(|writer| {
+        writer.write_sequence(|writer| {
+            writer.next().write_sequence(|writer| {
+                writer.next().write_sequence(|writer| {
+                    writer.next().write_sequence(|writer| {
+                        write_cert_id(writer.next(), issuer_name, issuer_key, serial);
+                    });
+                });
+            });
+        });
+    })
+}
+
+fn write_cert_id(writer: DERWriter, issuer_name: &[u8], issuer_key: &[u8], serial: &[u8]) {
+    writer.write_sequence(|writer| {
+        writer.next().write_sequence(|writer| {
+            writer
+                .next()
+                .write_oid(&ObjectIdentifier::from_slice(OID_SHA1));
+            writer.next().write_null();
+        });
+        writer
+            .next()
+            .write_bytes(Sha1::digest(issuer_name).as_slice());
+        writer
+            .next()
+            .write_bytes(Sha1::digest(issuer_key).as_slice());
+        // Reuse the serial encoding of the certificate so it matches byte by byte
+        writer
+            .next()
+            .write_tagged_implicit(TAG_INTEGER, |writer| writer.write_bytes(serial));
+    });
+}
+
+struct SingleResponse {
+    hash_algorithm: ObjectIdentifier,
+    issuer_name_hash: Vec<u8>,
+    issuer_key_hash: Vec<u8>,
+    serial: Vec<u8>,
+    status: Tag,
+    this_update: i64,
+    next_update: Option<i64>,
+}
+
+// Validates an OCSP response for the given certificate as described in RFC 6960:
+// the response has to be signed by the issuer or by a responder it delegated,
+// refer to the same CertID and be current.
+pub(crate) fn verify_ocsp_response(
+    response: &[u8],
+    end_entity: &X509Certificate,
+    issuer: &X509Certificate,
+    now: u64,
+) -> Result<OcspStatus, String> {
+    let invalid = |err: yasna::ASN1Error| format!("Invalid OCSP response: {err}");
+
+    // OCSPResponse
+    let (status, response_bytes) = yasna::parse_der(response, |reader| {
+        reader.read_sequence(|reader| {
+            let status = reader.next().read_enum()?;
+            let response_bytes = reader.read_optional(|reader| {
+                reader.read_tagged(Tag::context(0), |reader| {
+                    reader.read_sequence(|reader| {
+                        Ok((reader.next().read_oid()?, reader.next().read_bytes()?))
+                    })
+                })
+            })?;
+            Ok((status, response_bytes))
+        })
+    })
+    .map_err(invalid)?;
+    if status != 0 {
+        return Err(format!("OCSP responder returned status {status}"));
+    }
+    let basic_response = match response_bytes {
+        Some((response_type, basic_response)) if response_type.components() == OID_OCSP_BASIC => {
+            basic_response
+        }
+        Some(_) => return Err("Unsupported OCSP response type".to_string()),
+        None => return Err("OCSP response is empty".to_string()),
+    };
+
+    // BasicOCSPResponse
+    let (response_data, signature_algorithm, signature, certs) =
+        yasna::parse_der(&basic_response, |reader| {
+            reader.read_sequence(|reader| {
+                let response_data = reader.next().read_der()?;
+                let signature_algorithm = read_algorithm(reader.next())?;
+                let (signature, _) = reader.next().read_bitvec_bytes()?;
+                let certs = reader
+                    .read_optional(|reader| {
+                        reader.read_tagged(Tag::context(0), |reader| {
+                            let mut certs = Vec::new();
+                            reader.read_sequence_of(|reader| {
+                                certs.push(reader.read_der()?);
+                                Ok(())
+                            })?;
+                            Ok(certs)
+                        })
+                    })?
+                    .unwrap_or_default();
+                Ok((response_data, signature_algorithm, signature, certs))
+            })
+        })
+        .map_err(invalid)?;
+
+    // The response has to be signed by the issuer or by an authorized responder
+    let signature_algorithm = signature_algorithm
+        .components()
+        .iter()
+        .map(|component| component.to_string())
+        .collect::<Vec<_>>()
+        .join(".");
+    if !verify_signature(issuer, &signature_algorithm, &response_data, &signature)
+        && !certs.iter().any(|cert| {
+            X509Certificate::from_der(cert).map_or(false, |(_, responder)| {
+                is_authorized_responder(&responder, issuer, now)
+                    && verify_signature(
+                        &responder,
+                        &signature_algorithm,
+                        &response_data,
+                        &signature,
+                    )
+            })
+        })
+    {
+        return Err("OCSP response signature could not be verified".to_string());
+    }
+
+    // ResponseData
+    let responses = yasna::parse_der(&response_data, |reader| {
+        reader.read_sequence(|reader| {
+            reader.read_optional(|reader| {
+                reader.read_tagged(Tag::context(0), |reader| reader.read_u8())
+            })?;
+            // Skip responderID and producedAt
+            reader.next().read_der()?;
+            reader.next().read_generalized_time()?;
+            let mut responses = Vec::new();
+            reader.next().read_sequence_of(|reader| {
+                responses.push(read_single_response(reader)?);
+                Ok(())
+            })?;
+            reader.read_optional(|reader| {
+                reader.read_tagged(Tag::context(1), |reader| reader.read_der())
+            })?;
+            Ok(responses)
+        })
+    })
+    .map_err(invalid)?;
+
+    // Match the full CertID of the certificate
+    let issuer_name_hash = Sha1::digest(issuer.subject().as_raw());
+    let issuer_key_hash = Sha1::digest(issuer.public_key().subject_public_key.data.as_ref());
+    let response = responses
+        .into_iter()
+        .find(|response| {
+            response.hash_algorithm.components() == OID_SHA1
+                && response.issuer_name_hash == issuer_name_hash.as_slice()
+                && response.issuer_key_hash == issuer_key_hash.as_slice()
+                && response.serial == end_entity.raw_serial()
+        })
+        .ok_or_else(|| "OCSP response does not include the certificate".to_string())?;
+
+    if response.status != Tag::context(0) {
+        return Err("OCSP responder reported the certificate as revoked or unknown".to_string());
+    }
+    if response.this_update > (now + MAX_CLOCK_SKEW) as i64 {
+        return Err("OCSP response is not yet valid".to_string());
+    }
+    if response
+        .next_update
+        .is_some_and(|next_update| next_update <= now as i64)
+    {
+        return Err("OCSP response has expired".to_string());
+    }
+
+    Ok(OcspStatus {
+        next_update: response.next_update.map(|next_update| next_update as u64),
+    })
+}
+
+fn read_single_response(reader: BERReader) -> ASN1Result<SingleResponse> {
+    reader.read_sequence(|reader| {
+        let (hash_algorithm, issuer_name_hash, issuer_key_hash, serial) =
+            reader.next().read_sequence(|reader| {
+                Ok((
+                    read_algorithm(reader.next())?,
+                    reader.next().read_bytes()?,
+                    reader.next().read_bytes()?,
+                    reader
+                        .next()
+                        .read_tagged_implicit(TAG_INTEGER, |reader| reader.read_bytes())?,
+                ))
+            })?;
+        let status = reader.next().read_tagged_der()?.tag();
+        let this_update = reader
+            .next()
+            .read_generalized_time()?
+            .datetime()
+            .unix_timestamp();
+        let next_update = reader.read_optional(|reader| {
+            reader.read_tagged(Tag::context(0), |reader| {
+                Ok(reader.read_generalized_time()?.datetime().unix_timestamp())
+            })
+        })?;
+        reader.read_optional(|reader| {
+            reader.read_tagged(Tag::context(1), |reader| reader.read_der())
+        })?;
+
+        Ok(SingleResponse {
+            hash_algorithm,
+            issuer_name_hash,
+            issuer_key_hash,
+            serial,
+            status,
+            this_update,
+            next_update,
+        })
+    })
+}
+
+fn read_algorithm(reader: BERReader) -> ASN1Result<ObjectIdentifier> {
+    reader.read_sequence(|reader| {
+        let algorithm = reader.next().read_oid()?;
+        reader.read_optional(|reader| reader.read_der())?;
+        Ok(algorithm)
+    })
+}
+
+// Delegated responders must be issued directly by the CA and carry the
+// id-kp-OCSPSigning extended key usage
+fn is_authorized_responder(
+    responder: &X509Certificate,
+    issuer: &X509Certificate,
+    now: u64,
+) -> bool {
+    responder.issuer().as_raw() == issuer.subject().as_raw()
+        && responder
+            .extended_key_usage()
+            .ok()
+            .flatten()
+            .is_some_and(|eku| eku.value.ocsp_signing)
+        && responder.validity().not_before.timestamp() <= now as i64
+        && responder.validity().not_after.timestamp() > now as i64
+        && verify_signature(
+            issuer,
+            &responder.signature_algorithm.algorithm.to_id_string(),
+            responder.tbs_certificate.as_ref(),
+            responder.signature_value.data.as_ref(),
+        )
+}
+
+fn verify_signature(
+    signer: &X509Certificate,
+    algorithm: &str,
+    message: &[u8],
+    signature: &[u8],
+) -> bool {
+    let public_key = signer.public_key().subject_public_key.data.as_ref();
+
+    // ECDSA curves are told apart by the length of their uncompressed points
+    let algorithm: &'static dyn VerificationAlgorithm = match (algorithm, public_key.len()) {
+        ("1.2.840.113549.1.1.5", _) => &signature::RSA_PKCS1_2048_8192_SHA1_FOR_LEGACY_USE_ONLY,
+        ("1.2.840.113549.1.1.11", _) => &signature::RSA_PKCS1_2048_8192_SHA256,
+        ("1.2.840.113549.1.1.12", _) => &signature::RSA_PKCS1_2048_8192_SHA384,
+        ("1.2.840.113549.1.1.13", _) => &signature::RSA_PKCS1_2048_8192_SHA512,
+        ("1.2.840.10045.4.3.2", 65) => &signature::ECDSA_P256_SHA256_ASN1,
+        ("1.2.840.10045.4.3.2", 97) => &signature::ECDSA_P384_SHA256_ASN1,
+        ("1.2.840.10045.4.3.3", 65) => &signature::ECDSA_P256_SHA384_ASN1,
+        ("1.2.840.10045.4.3.3", 97) => &signature::ECDSA_P384_SHA384_ASN1,
+        ("1.3.101.112", _) => &signature::ED25519,
+        _ => return false,
+    };
+
+    UnparsedPublicKey::new(algorithm, public_key)
+        .verify(message, signature)
+        .is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use rcgen::{
+        BasicConstraints, Certificate, CertificateParams, ExtendedKeyUsagePurpose, IsCa,
+        PKCS_ED25519,
+    };
+    use ring::signature::Ed25519KeyPair;
+    use yasna::models::GeneralizedTime;
+
+    use super::*;
+
+    const NOW: u64 = 1800000000;
+
+    fn build_certificate(
+        issuer: Option<&Certificate>,
+        ocsp_signing: bool,
+    ) -> (Certificate, Vec<u8>) {
+        let mut params = CertificateParams::new(vec!["mx.example.org".to_string()]);
+        params.alg = &PKCS_ED25519;
+        if issuer.is_none() {
+            params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+        }
+        if ocsp_signing {
+            params.extended_key_usages = vec![ExtendedKeyUsagePurpose::OcspSigning];
+        }
+        let cert = Certificate::from_params(params).unwrap();
+        let der = match issuer {
+            Some(issuer) => cert.serialize_der_with_signer(issuer).unwrap(),
+            None => cert.serialize_der().unwrap(),
+        };
+        (cert, der)
+    }
+
+    fn build_cert_id(issuer: &X509Certificate, serial: &[u8]) -> Vec<u8> {
+        yasna::construct_der(|writer| {
+            write_cert_id(
+                writer,
+                issuer.subject().as_raw(),
+                issuer.public_key().subject_public_key.data.as_ref(),
+                serial,
+            )
+        })
+    }
+
+    fn build_response(
+        signer: &Certificate,
+        certs: &[&[u8]],
+        cert_id: &[u8],
+        cert_status: u64,
+        this_update: &str,
+        next_update: Option<&str>,
+    ) -> Vec<u8> {
+        let time = |time: &str| GeneralizedTime::parse(time.as_bytes()).unwrap();
+        let response_data = yasna::construct_der(|writer| {
+            writer.write_sequence(|writer| {
+                writer
+                    .next()
+                    .write_tagged(Tag::context(2), |writer| writer.write_bytes(&[0; 20]));
+                writer
+                    .next()
+                    .write_generalized_time(&time("20240101000000Z"));
+                writer.next().write_sequence(|writer| {
+                    writer.next().write_sequence(|writer| {
+                        writer.next().write_der(cert_id);
+                        writer
+                            .next()
+                            .write_tagged_implicit(Tag::context(cert_status), |writer| {
+                                writer.write_null()
+                            });
+                        writer.next().write_generalized_time(&time(this_update));
+                        if let Some(next_update) = next_update {
+                            writer.next().write_tagged(Tag::context(0), |writer| {
+                                writer.write_generalized_time(&time(next_update))
+                            });
+                        }
+                    });
+                });
+            });
+        });
+        let signature =
+            Ed25519KeyPair::from_pkcs8_maybe_unchecked(&signer.serialize_private_key_der())
+                .unwrap()
+                .sign(&response_data);
+
+        let basic_response = yasna::construct_der(|writer| {
+            writer.write_sequence(|writer| {
+                writer.next().write_der(&response_data);
+                writer.next().write_sequence(|writer| {
+                    writer
+                        .next()
+                        .write_oid(&ObjectIdentifier::from_slice(&[1, 3, 101, 112]));
+                });
+                writer
+                    .next()
+                    .write_bitvec_bytes(signature.as_ref(), signature.as_ref().len() * 8);
+                if !certs.is_empty() {
+                    writer.next().write_tagged(Tag::context(0), |writer| {
+                        writer.write_sequence(|writer| {
+                            for cert in certs {
+                                writer.next().write_der(cert);
+                            }
+                        })
+                    });
+                }
+            });
+        });
+
+        yasna::construct_der(|writer| {
+            writer.write_sequence(|writer| {
+                writer.next().write_enum(0);
+                writer.next().write_tagged(Tag::context(0), |writer| {
+                    writer.write_sequence(|writer| {
+                        writer
+                            .next()
+                            .write_oid(&ObjectIdentifier::from_slice(OID_OCSP_BASIC));
+                        writer.next().write_bytes(&basic_response);
+                    })
+                });
+            });
+        })
+    }
+
+    #[test]
+    fn ocsp_response_verification() {
+        let (ca, ca_der) = build_certificate(None, false);
+        let (_, end_entity_der) = build_certificate(Some(&ca), false);
+        let (other_ca, other_ca_der) = build_certificate(None, false);
+        let (responder, responder_der) = build_certificate(Some(&ca), true);
+        let (unauthorized, unauthorized_der) = build_certificate(Some(&ca), false);
+        let (_, issuer) = X509Certificate::from_der(&ca_der).unwrap();
+        let (_, end_entity) = X509Certificate::from_der(&end_entity_der).unwrap();
+        let (_, other_issuer) = X509Certificate::from_der(&other_ca_der).unwrap();
+        let cert_id = build_cert_id(&issuer, end_entity.raw_serial());
+        let expected = Ok(OcspStatus {
+            next_update: Some(1893456000),
+        });
+
+        // Signed by the issuer
+        let response = build_response(
+            &ca,
+            &[],
+            &cert_id,
+            0,
+            "20240101000000Z",
+            Some("20300101000000Z"),
+        );
+        assert_eq!(
+            verify_ocsp_response(&response, &end_entity, &issuer, NOW),
+            expected
+        );
+        assert_eq!(
+            verify_ocsp_response(
+                &build_response(&ca, &[], &cert_id, 0, "20240101000000Z", None),
+                &end_entity,
+                &issuer,
+                NOW
+            ),
+            Ok(OcspStatus { next_update: None })
+        );
+        assert!(verify_ocsp_response(&response, &end_entity, &other_issuer, NOW).is_err());
+        assert!(
+            verify_ocsp_response(&response[..response.len() - 1], &end_entity, &issuer, NOW)
+                .is_err()
+        );
+
+        // Signed by a delegated responder
+        assert_eq!(
+            verify_ocsp_response(
+                &build_response(
+                    &responder,
+                    &[&responder_der],
+                    &cert_id,
+                    0,
+                    "20240101000000Z",
+                    Some("20300101000000Z"),
+                ),
+                &end_entity,
+                &issuer,
+                NOW
+            ),
+            expected
+        );
+
+        // Signed by a responder without the OCSPSigning usage or by another CA
+        for (signer, certs) in [
+            (&unauthorized, vec![unauthorized_der.as_slice()]),
+            (&other_ca, vec![]),
+            (&other_ca, vec![other_ca_der.as_slice()]),
+        ] {
+            assert!(verify_ocsp_response(
+                &build_response(
+                    signer,
+                    &certs,
+                    &cert_id,
+                    0,
+                    "20240101000000Z",
+                    Some("20300101000000Z"),
+                ),
+                &end_entity,
+                &issuer,
+                NOW
+            )
+            .is_err());
+        }
+
+        // CertID mismatches
+        for cert_id in [
+            build_cert_id(&issuer, &[0x01]),
+            build_cert_id(&other_issuer, end_entity.raw_serial()),
+        ] {
+            assert!(verify_ocsp_response(
+                &build_response(
+                    &ca,
+                    &[],
+                    &cert_id,
+                    0,
+                    "20240101000000Z",
+                    Some("20300101000000Z"),
+                ),
+                &end_entity,
+                &issuer,
+                NOW
+            )
+            .is_err());
+        }
+
+        // Revoked, expired or not yet valid responses
+        for (cert_status, this_update, next_update) in [
+            (1, "20240101000000Z", "20300101000000Z"),
+            (2, "20240101000000Z", "20300101000000Z"),
+            (0, "20240101000000Z", "20240201000000Z"),
+            (0, "20290101000000Z", "20300101000000Z"),
+        ] {
+            assert!(verify_ocsp_response(
+                &build_response(
+                    &ca,
+                    &[],
+                    &cert_id,
+                    cert_status,
+                    this_update,
+                    Some(next_update),
+                ),
+                &end_entity,
+                &issuer,
+                NOW
+            )
+            .is_err());
+        }
+
+        // Unsuccessful responses
+        assert!(verify_ocsp_response(
+            &yasna::construct_der(|writer| {
+                writer.write_sequence(|writer| writer.next().write_enum(6))
+            }),
+            &end_entity,
+            &issuer,
+            NOW
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn ocsp_request_encoding() {
+        let request = build_ocsp_request(b"issuer", b"key", &[0x00, 0x82]);
+        let (name_hash, key_hash, serial) = yasna::parse_der(&request, |reader| {
+            reader.read_sequence(|reader| {
+                reader.next().read_sequence(|reader| {
+                    reader.next().read_sequence(|reader| {
+                        reader.next().read_sequence(|reader| {
+                            reader.next().read_sequence(|reader| {
+                                assert_eq!(read_algorithm(reader.next())?.components(), OID_SHA1);
+                                Ok((
+                                    reader.next().read_bytes()?,
+                                    reader.next().read_bytes()?,
+                                    reader.next().read_tagged_implicit(TAG_INTEGER, |reader| {
+                                        reader.read_bytes()
+                                    })?,
+                                ))
+                            })
+                        })
+                    })
+                })
+            })
+        })
+        .unwrap();
+        assert_eq!(name_hash, Sha1::digest(b"issuer").as_slice());
+        assert_eq!(key_hash, Sha1::digest(b"key").as_slice());
+        assert_eq!(serial, [0x00, 0x82]);
+    }
+}
@@ -23,6 +23,10 @@ impl SessionStream for TcpStream {
     fn tls_version_and_cipher(&self) -> (Cow<'static, str>, Cow<'static, str>) {
         (Cow::Borrowed(""), Cow::Borrowed(""))
     }
+
+    fn tls_exporter(&self) -> Option<Vec<u8>> {
+        None
+    }
 }
 
 impl<T: SessionStream> SessionStream for TlsStream<T> {
@@ -62,6 +66,19 @@ impl<T: SessionStream> SessionStream for TlsStream<T> {
             .into(),
         )
     }
+
+    // Channel binding data for tls-exporter (RFC 9266), which is only
+    // defined for TLS 1.3 connections
+    fn tls_exporter(&self) -> Option<Vec<u8>> {
+        let (_, conn) = self.get_ref();
+
+        if conn.protocol_version() == Some(rustls::ProtocolVersion::TLSv1_3) {
+            conn.export_keying_material(vec![0u8; 32], b"EXPORTER-Channel-Binding", None)
+                .ok()
+        } else {
+            None
+        }
+    }
 }
 
 impl SessionStream for ProxiedStream<TcpStream> {
@@ -82,6 +99,10 @@ impl SessionStream for ProxiedStream<TcpStream> {
             })
             .unwrap_or((Cow::Borrowed("unknown"), Cow::Borrowed("unknown")))
     }
+
+    fn tls_exporter(&self) -> Option<Vec<u8>> {
+        None
+    }
 }
 
 #[derive(Default)]
@@ -140,4 +161,8 @@ impl SessionStream for NullIo {
             std::borrow::Cow::Borrowed(""),
         )
     }
+
+    fn tls_exporter(&self) -> Option<Vec<u8>> {
+        None
+    }
 }
@@ -28,6 +28,7 @@ use super::{
         resolver::{build_acme_static_resolver, IsTlsAlpnChallenge},
         AcmeProvider,
     },
+    ocsp::OcspConfig,
     ServerInstance, SessionStream, TcpAcceptor, TcpAcceptorResult,
 };
 
@@ -39,6 +40,7 @@ pub struct TlsManager {
     pub certificates: ArcSwap<AHashMap<String, Arc<CertifiedKey>>>,
     pub acme_providers: AHashMap<String, AcmeProvider>,
     pub self_signed_cert: Option<Arc<CertifiedKey>>,
+    pub ocsp: OcspConfig,
 }
 
 #[derive(Clone)]
@@ -65,43 +67,56 @@ impl Core {
     pub(crate) fn resolve_certificate(&self, name: Option<&str>) -> Option<Arc<CertifiedKey>> {
         let certs = self.tls.certificates.load();
 
-        name.map_or_else(
-            || certs.get("*"),
-            |name| {
+        // Clients that do not send SNI or that request an unknown host
+        // are served the default certificate
+        let (cert_id, cert) = name
+            .and_then(|name| {
                 certs
-                    .get(name)
+                    .get_key_value(name)
                     .or_else(|| {
                         // Try with a wildcard certificate
                         name.split_once('.')
-                            .and_then(|(_, domain)| certs.get(domain))
+                            .and_then(|(_, domain)| certs.get_key_value(domain))
                     })
                     .or_else(|| {
                         trc::event!(
                             Tls(trc::TlsEvent::CertificateNotFound),
                             Hostname = name.to_string(),
                         );
-                        certs.get("*")
+                        None
                     })
-            },
-        )
-        .or_else(|| match certs.len().cmp(&1) {
-            Ordering::Equal => certs.values().next(),
-            Ordering::Greater => {
-                trc::event!(
-                    Tls(trc::TlsEvent::MultipleCertificatesAvailable),
-                    Total = certs.len(),
-                );
-                certs.values().next()
-            }
-            Ordering::Less => {
+            })
+            .or_else(|| certs.get_key_value("*"))
+            .or_else(|| match certs.len().cmp(&1) {
+                Ordering::Equal => certs.iter().next(),
+                Ordering::Greater => {
+                    trc::event!(
+                        Tls(trc::TlsEvent::MultipleCertificatesAvailable),
+                        Total = certs.len(),
+                    );
+                    certs.iter().next()
+                }
+                Ordering::Less => None,
+            })
+            .map(|(cert_id, cert)| (cert_id.as_str(), cert.clone()))
+            .or_else(|| {
                 trc::event!(
                     Tls(trc::TlsEvent::NoCertificatesAvailable),
                     Total = certs.len(),
                 );
-                self.tls.self_signed_cert.as_ref()
-            }
-        })
-        .cloned()
+                self.tls
+                    .self_signed_cert
+                    .as_ref()
+                    .map(|cert| ("self-signed", cert.clone()))
+            })?;
+
+        trc::event!(
+            Tls(trc::TlsEvent::CertificateSelected),
+            Hostname = name.map(|name| name.to_string()),
+            Id = cert_id.to_string(),
+        );
+
+        Some(cert)
     }
 }
 
@@ -222,6 +237,66 @@ impl Clone for TlsManager {
             certificates: ArcSwap::from_pointee(self.certificates.load().as_ref().clone()),
             acme_providers: self.acme_providers.clone(),
             self_signed_cert: self.self_signed_cert.clone(),
+            ocsp: self.ocsp.clone(),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use ahash::AHashMap;
+
+    use crate::{config::server::tls::build_self_signed_cert, Core};
+
+    #[test]
+    fn sni_certificate_selection() {
+        let core = Core::default();
+        let build_cert =
+            |name: &str| Arc::new(build_self_signed_cert(vec![name.to_string()]).unwrap());
+        let (example, wildcard, default) = (
+            build_cert("example.org"),
+            build_cert("*.example.net"),
+            build_cert("default.org"),
+        );
+        core.tls.certificates.store(
+            AHashMap::from_iter([
+                ("example.org".to_string(), example.clone()),
+                ("example.net".to_string(), wildcard.clone()),
+                ("*".to_string(), default.clone()),
+            ])
+            .into(),
+        );
+
+        for (name, expected) in [
+            (Some("example.org"), &example),
+            (Some("mail.example.net"), &wildcard),
+            (Some("unknown.org"), &default),
+            (None, &default),
+        ] {
+            assert!(
+                Arc::ptr_eq(&core.resolve_certificate(name).unwrap(), expected),
+                "Unexpected certificate for {name:?}"
+            );
+        }
+
+        // Replacing the certificate map affects new handshakes only
+        let previous = core.resolve_certificate(Some("example.org")).unwrap();
+        let replacement = build_cert("example.org");
+        core.tls
+            .certificates
+            .store(AHashMap::from_iter([("example.org".to_string(), replacement.clone())]).into());
+        assert!(Arc::ptr_eq(
+            &core.resolve_certificate(Some("example.org")).unwrap(),
+            &replacement
+        ));
+        assert!(Arc::ptr_eq(&previous, &example));
+
+        // Without a default certificate the only available one is used
+        assert!(Arc::ptr_eq(
+            &core.resolve_certificate(Some("unknown.org")).unwrap(),
+            &replacement
+        ));
+    }
+}
@@ -20,7 +20,8 @@ use store::{
         LookupClass, QueueClass, QueueEvent, TagValue, ValueClass,
     },
     BitmapKey, Deserialize, IndexKey, IterateParams, LogKey, Serialize, ValueKey,
-    SUBSPACE_BITMAP_ID, SUBSPACE_BITMAP_TAG, SUBSPACE_BITMAP_TEXT, U32_LEN, U64_LEN,
+    QUEUE_DOMAIN_PREFIX, SUBSPACE_BITMAP_ID, SUBSPACE_BITMAP_TAG, SUBSPACE_BITMAP_TEXT,
+    SUBSPACE_REPORT_OUT, U32_LEN, U64_LEN,
 };
 
 use utils::{
@@ -776,6 +777,33 @@ impl Core {
                     )
                     .await
                     .failed("Failed to iterate over data store");
+
+                store
+                    .iterate(
+                        IterateParams::new(
+                            AnyKey {
+                                subspace: SUBSPACE_REPORT_OUT,
+                                key: vec![QUEUE_DOMAIN_PREFIX],
+                            },
+                            AnyKey {
+                                subspace: SUBSPACE_REPORT_OUT,
+                                key: vec![QUEUE_DOMAIN_PREFIX + 1],
+                            },
+                        ),
+                        |key_, value| {
+                            let mut key = Vec::with_capacity(key_.len());
+                            key.push(2);
+                            key.extend_from_slice(key_.get(1..).unwrap_or_default());
+
+                            writer
+                                .send(Op::KeyValue((key, value.to_vec())))
+                                .failed("Failed to send key value");
+
+                            Ok(true)
+                        },
+                    )
+                    .await
+                    .failed("Failed to iterate over data store");
             }),
             handle,
         )
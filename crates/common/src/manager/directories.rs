@@ -0,0 +1,120 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use std::time::{Duration, Instant};
+
+use directory::{backend::internal::PrincipalField, Principal, QueryBy};
+use serde::Serialize;
+
+use crate::Core;
+
+const DIRECTORY_TEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+// LDAP result codes returned when the bind credentials are rejected
+const LDAP_AUTH_ERRORS: [u64; 3] = [48, 49, 50];
+
+// PostgreSQL SQLSTATEs (invalid_authorization_specification, invalid_password)
+// and MySQL error codes (ER_DBACCESS_DENIED_ERROR, ER_ACCESS_DENIED_ERROR)
+// returned when the connection credentials are rejected
+const SQL_AUTH_ERRORS: [&str; 2] = ["28000", "28P01"];
+const MYSQL_AUTH_ERRORS: [u64; 2] = [1044, 1045];
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DirectoryTest {
+    pub status: DirectoryStatus,
+    pub latency_ms: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum DirectoryStatus {
+    Ok,
+    AuthFailed,
+    ConnectionFailed,
+    QueryFailed,
+    Timeout,
+}
+
+impl Core {
+    pub async fn test_directory(&self, name: &str) -> Option<DirectoryTest> {
+        let directory = self.get_directory(name)?;
+        let start_time = Instant::now();
+        let (status, reason) =
+            match tokio::time::timeout(DIRECTORY_TEST_TIMEOUT, directory.ping()).await {
+                Ok(Ok(())) => (DirectoryStatus::Ok, None),
+                Ok(Err(err)) => {
+                    let status = directory_error_status(&err);
+                    let reason = err.to_string();
+                    trc::error!(err.id(name.to_string()).details("Directory test failed"));
+                    (status, Some(reason))
+                }
+                Err(_) => (DirectoryStatus::Timeout, None),
+            };
+
+        Some(DirectoryTest {
+            status,
+            latency_ms: start_time.elapsed().as_millis() as u64,
+            reason,
+        })
+    }
+
+    pub async fn lookup_directory_principal(
+        &self,
+        name: &str,
+        principal: &str,
+    ) -> trc::Result<Option<Principal>> {
+        let directory = self.get_directory(name).ok_or_else(|| {
+            trc::ManageEvent::NotFound
+                .into_err()
+                .details("Directory not found")
+                .id(name.to_string())
+        })?;
+
+        let mut result = directory.query(QueryBy::Name(principal), true).await?;
+        if result.is_none() && principal.contains('@') {
+            if let Some(id) = directory.email_to_ids(principal).await?.into_iter().next() {
+                result = directory.query(QueryBy::Id(id), true).await?;
+            }
+        }
+
+        Ok(result.map(|mut principal| {
+            principal.remove(PrincipalField::Secrets);
+            principal.remove(PrincipalField::AppPasswords);
+            principal
+        }))
+    }
+}
+
+fn directory_error_status(err: &trc::Error) -> DirectoryStatus {
+    match err.as_ref() {
+        trc::EventType::Store(trc::StoreEvent::LdapError)
+            if err
+                .value(trc::Key::Code)
+                .and_then(|code| code.to_uint())
+                .map_or(false, |code| LDAP_AUTH_ERRORS.contains(&code)) =>
+        {
+            DirectoryStatus::AuthFailed
+        }
+        trc::EventType::Store(trc::StoreEvent::PoolError) => match err.value(trc::Key::Code) {
+            Some(code)
+                if code
+                    .as_str()
+                    .map_or(false, |code| SQL_AUTH_ERRORS.contains(&code))
+                    || code
+                        .to_uint()
+                        .map_or(false, |code| MYSQL_AUTH_ERRORS.contains(&code)) =>
+            {
+                DirectoryStatus::AuthFailed
+            }
+            _ => DirectoryStatus::ConnectionFailed,
+        },
+        trc::EventType::Imap(_) | trc::EventType::Smtp(_) => DirectoryStatus::ConnectionFailed,
+        _ => DirectoryStatus::QueryFailed,
+    }
+}
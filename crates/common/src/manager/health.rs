@@ -0,0 +1,104 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use std::{
+    future::Future,
+    time::{Duration, Instant},
+};
+
+use directory::backend::internal::PrincipalInfo;
+use serde::Serialize;
+use store::{
+    write::{DirectoryClass, ValueClass},
+    ValueKey,
+};
+
+use crate::Core;
+
+const HEALTH_CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HealthStatus {
+    pub node_id: u64,
+    pub healthy: bool,
+    pub components: Vec<ComponentHealth>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ComponentHealth {
+    pub name: &'static str,
+    pub status: ComponentStatus,
+    pub latency_ms: u64,
+    // Only used internally, the status is served to unauthenticated probes
+    #[serde(skip)]
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ComponentStatus {
+    Up,
+    Down,
+    Timeout,
+}
+
+impl Core {
+    pub async fn health_check(&self) -> HealthStatus {
+        let (data, lookup, directory) = futures::join!(
+            check_component("data", async {
+                self.storage
+                    .data
+                    .get_value::<PrincipalInfo>(ValueKey::from(ValueClass::Directory(
+                        DirectoryClass::NameToId(b"health-check.invalid".to_vec()),
+                    )))
+                    .await
+                    .map(|_| ())
+            }),
+            check_component("lookup", async {
+                self.storage
+                    .lookup
+                    .key_exists(b"health-check".to_vec())
+                    .await
+                    .map(|_| ())
+            }),
+            check_component("directory", self.storage.directory.ping()),
+        );
+        let components = vec![data, lookup, directory];
+
+        HealthStatus {
+            node_id: self.network.node_id,
+            healthy: components
+                .iter()
+                .all(|component| component.status == ComponentStatus::Up),
+            components,
+        }
+    }
+}
+
+async fn check_component(
+    name: &'static str,
+    check: impl Future<Output = trc::Result<()>>,
+) -> ComponentHealth {
+    let start_time = Instant::now();
+    let (status, reason) = match tokio::time::timeout(HEALTH_CHECK_TIMEOUT, check).await {
+        Ok(Ok(())) => (ComponentStatus::Up, None),
+        Ok(Err(err)) => {
+            let reason = err.to_string();
+            trc::error!(err.details("Health check failed").ctx(trc::Key::Id, name));
+            (ComponentStatus::Down, Some(reason))
+        }
+        Err(_) => (ComponentStatus::Timeout, None),
+    };
+
+    ComponentHealth {
+        name,
+        status,
+        latency_ms: start_time.elapsed().as_millis() as u64,
+        reason,
+    }
+}
@@ -4,31 +4,78 @@
  * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
  */
 
-use ahash::AHashSet;
+use std::{collections::BTreeMap, sync::atomic::Ordering};
+
+use ahash::{AHashMap, AHashSet};
 use arc_swap::ArcSwap;
+use serde::Serialize;
 use store::Stores;
-use utils::config::{ipmask::IpAddrOrMask, utils::ParseValue, Config};
+use utils::config::{
+    ipmask::{IpAddrOrMask, IpNetworkTree},
+    utils::ParseValue,
+    Config,
+};
 
 use crate::{
     config::{
         server::{tls::parse_certificates, Servers},
         telemetry::Telemetry,
     },
-    listener::blocked::BLOCKED_IP_KEY,
-    Core,
+    listener::{blocked::BLOCKED_IP_KEY, ocsp::carry_ocsp_staples},
+    Core, Security,
 };
 
-use super::config::{ConfigManager, Patterns};
+use super::{
+    config::{ConfigManager, Patterns},
+    health::ComponentStatus,
+};
 
 pub struct ReloadResult {
     pub config: Config,
     pub new_core: Option<Core>,
     pub tracers: Option<Telemetry>,
+    pub changes: ConfigChanges,
+    pub applied: bool,
+}
+
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct ConfigChanges {
+    pub added: Vec<String>,
+    pub changed: Vec<String>,
+    pub removed: Vec<String>,
+}
+
+// Settings that affect how access tokens and role permissions are resolved
+const SECURITY_PREFIXES: &[&str] = &[
+    "directory.",
+    "storage.directory",
+    "storage.data",
+    "enterprise.",
+    "authentication.",
+];
+
+// Builds a new core from the configuration and, once it has been validated,
+// swaps it into the shared core without dropping established connections.
+pub async fn reload_core(shared_core: &ArcSwap<Core>, config: Config) -> trc::Result<ReloadResult> {
+    let current = shared_core.load_full();
+    let mut result = current.build_core(config).await?;
+
+    if let Some(mut core) = result.new_core.take() {
+        core.security.inherit(
+            &current.security,
+            !result.changes.affects(SECURITY_PREFIXES),
+        );
+        shared_core.store(core.into());
+        result.applied = true;
+    }
+
+    Ok(result)
 }
 
 impl Core {
     pub async fn reload_blocked_ips(&self) -> trc::Result<ReloadResult> {
         let mut ip_addresses = AHashSet::new();
+        let mut ip_networks = IpNetworkTree::default();
         let mut config = self.storage.config.build_config(BLOCKED_IP_KEY).await?;
 
         for ip in config
@@ -40,7 +87,9 @@ impl Core {
                 Ok(IpAddrOrMask::Ip(ip)) => {
                     ip_addresses.insert(ip);
                 }
-                Ok(IpAddrOrMask::Mask(_)) => {}
+                Ok(IpAddrOrMask::Mask(ip)) => {
+                    ip_networks.insert(&ip);
+                }
                 Err(err) => {
                     config.new_parse_error(BLOCKED_IP_KEY, err);
                 }
@@ -48,16 +97,37 @@ impl Core {
         }
 
         *self.network.blocked_ips.ip_addresses.write() = ip_addresses;
+        *self.network.blocked_ips.ip_networks.write() = ip_networks;
 
         Ok(config.into())
     }
 
     pub async fn reload_certificates(&self) -> trc::Result<ReloadResult> {
         let mut config = self.storage.config.build_config("certificate").await?;
-        let mut certificates = self.tls.certificates.load().as_ref().clone();
+        let mut certificates = AHashMap::new();
 
         parse_certificates(&mut config, &mut certificates, &mut Default::default());
 
+        // Keep the certificates issued by ACME providers, removed or
+        // replaced certificates are no longer offered on new handshakes
+        // while established connections are not affected
+        let current = self.tls.certificates.load();
+        for provider in self.tls.acme_providers.values() {
+            for name in provider
+                .domains
+                .iter()
+                .map(|domain| domain.strip_prefix("*.").unwrap_or(domain.as_str()))
+                .chain(provider.default.then_some("*"))
+            {
+                if let Some(cert) = current.get(name) {
+                    certificates
+                        .entry(name.to_string())
+                        .or_insert_with(|| cert.clone());
+                }
+            }
+        }
+        carry_ocsp_staples(&current, &mut certificates);
+
         self.tls.certificates.store(certificates.into());
 
         Ok(config.into())
@@ -77,12 +147,18 @@ impl Core {
             config,
             new_core: core.into(),
             tracers: None,
+            changes: ConfigChanges::default(),
+            applied: false,
         })
     }
 
     pub async fn reload(&self) -> trc::Result<ReloadResult> {
-        let mut config = self.storage.config.build_config("").await?;
+        let config = self.storage.config.build_config("").await?;
+        self.build_core(config).await
+    }
 
+    // Builds and validates a new core without replacing the running one
+    pub async fn build_core(&self, mut config: Config) -> trc::Result<ReloadResult> {
         // Load stores
         let mut stores = Stores {
             stores: self.storage.stores.clone(),
@@ -121,7 +197,9 @@ impl Core {
 
         // Copy ACME certificates
         let mut certificates = core.tls.certificates.load().as_ref().clone();
-        for (cert_id, cert) in self.tls.certificates.load().iter() {
+        let current = self.tls.certificates.load();
+        carry_ocsp_staples(&current, &mut certificates);
+        for (cert_id, cert) in current.iter() {
             certificates
                 .entry(cert_id.to_string())
                 .or_insert(cert.clone());
@@ -134,12 +212,30 @@ impl Core {
         // Parser servers
         let mut servers = Servers::parse(&mut config);
         servers.parse_tcp_acceptors(&mut config, core.clone().into_shared());
+        if !config.errors.is_empty() {
+            return Ok(config.into());
+        }
+
+        // Make sure the stores are reachable before replacing the running core
+        for component in core.health_check().await.components {
+            if component.status != ComponentStatus::Up {
+                config.new_build_error(
+                    format!("storage.{}", component.name),
+                    format!(
+                        "Store is not reachable: {}",
+                        component.reason.as_deref().unwrap_or("timed out")
+                    ),
+                );
+            }
+        }
 
         Ok(if config.errors.is_empty() {
             ReloadResult {
+                changes: ConfigChanges::new(&self.storage.config_keys, &config.keys),
                 config,
                 new_core: core.into(),
                 tracers: tracers.into(),
+                applied: false,
             }
         } else {
             config.into()
@@ -147,12 +243,107 @@ impl Core {
     }
 }
 
+impl Security {
+    // Carries over the state of the running core. Cached access tokens and role
+    // permissions are only kept when the settings they depend on are unchanged,
+    // otherwise the permissions version is bumped so they are resolved again.
+    pub fn inherit(&mut self, current: &Security, keep_caches: bool) {
+        let version = current.permissions_version.load(Ordering::Relaxed);
+        if keep_caches {
+            self.access_tokens.clone_from(&current.access_tokens);
+            self.oauth_tokens.clone_from(&current.oauth_tokens);
+            self.permissions.clone_from(&current.permissions);
+            self.permissions_version = version.into();
+        } else {
+            self.permissions_version = version.wrapping_add(1).into();
+        }
+
+        // Keep counting the sessions that are already established,
+        // debouncing last login writes and accumulating unflushed bandwidth
+        self.account_sessions.clone_from(&current.account_sessions);
+        self.ip_sessions.clone_from(&current.ip_sessions);
+        self.last_logins.clone_from(&current.last_logins);
+        self.bandwidth.clone_from(&current.bandwidth);
+        self.session_revocations
+            .clone_from(&current.session_revocations);
+        self.auth_cache_version = current.auth_cache_version.load(Ordering::Relaxed).into();
+    }
+}
+
+impl ConfigChanges {
+    pub fn new(old: &BTreeMap<String, String>, new: &BTreeMap<String, String>) -> Self {
+        let mut changes = ConfigChanges::default();
+        for (key, value) in new {
+            match old.get(key) {
+                Some(old_value) if old_value != value => changes.changed.push(key.clone()),
+                None => changes.added.push(key.clone()),
+                _ => (),
+            }
+        }
+        changes.removed = old
+            .keys()
+            .filter(|key| !new.contains_key(*key))
+            .cloned()
+            .collect();
+        changes
+    }
+
+    pub fn affects(&self, prefixes: &[&str]) -> bool {
+        self.added
+            .iter()
+            .chain(&self.changed)
+            .chain(&self.removed)
+            .any(|key| prefixes.iter().any(|prefix| key.starts_with(prefix)))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.changed.is_empty() && self.removed.is_empty()
+    }
+}
+
 impl From<Config> for ReloadResult {
     fn from(config: Config) -> Self {
         Self {
             config,
             new_core: None,
             tracers: None,
+            changes: ConfigChanges::default(),
+            applied: false,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::ConfigChanges;
+
+    #[test]
+    fn config_changes() {
+        let old = BTreeMap::from_iter([
+            ("server.hostname".to_string(), "mx.example.org".to_string()),
+            (
+                "directory.internal.type".to_string(),
+                "internal".to_string(),
+            ),
+            ("imap.auth.max-failures".to_string(), "3".to_string()),
+        ]);
+        let new = BTreeMap::from_iter([
+            (
+                "server.hostname".to_string(),
+                "mail.example.org".to_string(),
+            ),
+            ("imap.auth.max-failures".to_string(), "3".to_string()),
+            ("imap.timeout.idle".to_string(), "10m".to_string()),
+        ]);
+
+        let changes = ConfigChanges::new(&old, &new);
+        assert_eq!(changes.added, vec!["imap.timeout.idle".to_string()]);
+        assert_eq!(changes.changed, vec!["server.hostname".to_string()]);
+        assert_eq!(changes.removed, vec!["directory.internal.type".to_string()]);
+        assert!(changes.affects(&["directory."]));
+        assert!(!changes.affects(&["storage.", "authentication."]));
+        assert!(ConfigChanges::new(&new, &new).is_empty());
+    }
+}
@@ -21,7 +21,7 @@ use store::{
     BlobStore, Serialize, Store, U32_LEN,
 };
 use store::{
-    write::{QueueClass, QueueEvent},
+    write::{QueueClass, QueueDomain, QueueEvent},
     Deserialize, U64_LEN,
 };
 use tokio::{
@@ -265,6 +265,24 @@ async fn restore_file(store: Store, blob_store: BlobStore, path: &Path) {
                                     value,
                                 );
                             }
+                            2 => {
+                                let domain_end = key
+                                    .len()
+                                    .checked_sub(U64_LEN + 1)
+                                    .filter(|&end| end > 0)
+                                    .expect("Failed to read queue domain key");
+                                batch.set(
+                                    ValueClass::Queue(QueueClass::MessageDomain(QueueDomain {
+                                        domain: std::str::from_utf8(&key[1..domain_end])
+                                            .expect("Failed to read queue domain")
+                                            .to_string(),
+                                        queue_id: key
+                                            .deserialize_be_u64(domain_end + 1)
+                                            .expect("Failed to deserialize queue message id"),
+                                    })),
+                                    value,
+                                );
+                            }
                             _ => failed("Invalid queue key"),
                         }
                     }
@@ -6,13 +6,15 @@
 
 use std::{
     borrow::Cow,
+    fmt::Write,
     io::{self, Cursor, Read},
     path::PathBuf,
 };
 
 use ahash::AHashMap;
 use arc_swap::ArcSwap;
-use store::BlobStore;
+use sha2::{Digest, Sha256};
+use store::{write::now, BlobStore};
 
 use crate::Core;
 
@@ -27,6 +29,8 @@ pub struct WebAdminManager {
 pub struct Resource<T> {
     pub content_type: Cow<'static, str>,
     pub contents: T,
+    pub etag: Option<String>,
+    pub last_modified: Option<u64>,
 }
 
 impl<T> Resource<T> {
@@ -34,10 +38,21 @@ impl<T> Resource<T> {
         Self {
             content_type: content_type.into(),
             contents,
+            etag: None,
+            last_modified: None,
         }
     }
 }
 
+impl<T: AsRef<[u8]>> Resource<T> {
+    // Adds the validators used to answer conditional requests
+    pub fn with_validators(mut self, last_modified: u64) -> Self {
+        self.etag = Some(resource_etag(self.contents.as_ref()));
+        self.last_modified = Some(last_modified);
+        self
+    }
+}
+
 impl WebAdminManager {
     pub fn new() -> Self {
         Self {
@@ -54,6 +69,8 @@ impl WebAdminManager {
                 .map(|contents| Resource {
                     content_type: resource.content_type.clone(),
                     contents,
+                    etag: resource.etag.clone(),
+                    last_modified: resource.last_modified,
                 })
                 .map_err(|err| {
                     trc::ResourceEvent::Error
@@ -88,6 +105,7 @@ impl WebAdminManager {
                 .details("Failed to decompress webadmin bundle")
         })?;
         let mut routes = AHashMap::new();
+        let unpacked_at = now();
         for i in 0..bundle.len() {
             let (file_name, contents) = {
                 let mut file = bundle.by_index(i).map_err(|err| {
@@ -105,6 +123,7 @@ impl WebAdminManager {
                 (file.name().to_string(), contents)
             };
             let path = self.bundle_path.path.join(format!("{i:02}"));
+            let etag = resource_etag(&contents);
             tokio::fs::write(&path, contents)
                 .await
                 .map_err(unpack_error)?;
@@ -127,6 +146,8 @@ impl WebAdminManager {
                 }
                 .into(),
                 contents: path,
+                etag: etag.into(),
+                last_modified: unpacked_at.into(),
             };
 
             routes.insert(file_name, resource);
@@ -185,6 +206,19 @@ impl TempDir {
     }
 }
 
+// Strong entity tag derived from the resource contents, so replaced
+// resources are never matched against stale cached copies
+fn resource_etag(contents: &[u8]) -> String {
+    let hash = Sha256::digest(contents);
+    let mut etag = String::with_capacity(34);
+    etag.push('"');
+    for byte in &hash[..16] {
+        let _ = write!(etag, "{byte:02x}");
+    }
+    etag.push('"');
+    etag
+}
+
 fn unpack_error(err: std::io::Error) -> trc::Error {
     trc::ResourceEvent::Error
         .reason(err)
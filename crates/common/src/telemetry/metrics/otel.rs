@@ -47,7 +47,7 @@ impl OtelMetrics {
         }
 
         // Add gauges
-        for gauge in Collector::collect_gauges(is_enterprise) {
+        for gauge in Collector::collect_gauges() {
             metrics.push(Metric {
                 name: gauge.id().name().into(),
                 description: gauge.id().description().into(),
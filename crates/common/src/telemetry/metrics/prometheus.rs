@@ -33,7 +33,7 @@ impl Core {
         }
 
         // Add gauges
-        for gauge in Collector::collect_gauges(is_enterprise) {
+        for gauge in Collector::collect_gauges() {
             let mut metric = MetricFamily::default();
             metric.set_name(metric_name(gauge.id().name()));
             metric.set_help(gauge.id().description().into());
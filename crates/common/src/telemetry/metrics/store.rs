@@ -132,7 +132,7 @@ impl MetricsStore for Store {
                 }
             }
 
-            for gauge in Collector::collect_gauges(true) {
+            for gauge in Collector::collect_gauges() {
                 let gauge_id = gauge.id();
                 if matches!(gauge_id, MetricType::QueueCount | MetricType::ServerMemory) {
                     let value = gauge.get();
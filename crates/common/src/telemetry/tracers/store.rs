@@ -26,6 +26,7 @@ use utils::snowflake::SnowflakeIdGenerator;
 use crate::config::telemetry::StoreTracer;
 
 const MAX_EVENTS: usize = 2048;
+const MAX_TIMELINE_SPANS: usize = 128;
 
 pub(crate) fn spawn_store_tracer(builder: SubscriberBuilder, settings: StoreTracer) {
     let (_, mut rx) = builder.register();
@@ -132,6 +133,13 @@ pub(crate) fn spawn_store_tracer(builder: SubscriberBuilder, settings: StoreTrac
     });
 }
 
+pub struct Timeline {
+    // Earliest span of the chain, usually the inbound session that queued the message.
+    // It is the same regardless of the span the timeline was requested for.
+    pub correlation_id: u64,
+    pub events: Vec<Event<EventDetails>>,
+}
+
 pub enum TracingQuery {
     EventType(EventType),
     QueueId(u64),
@@ -153,6 +161,7 @@ pub trait TracingStore: Sync + Send {
         from_span_id: u64,
         to_span_id: u64,
     ) -> impl Future<Output = trc::Result<Vec<u64>>> + Send;
+    fn get_timeline(&self, span_id: u64) -> impl Future<Output = trc::Result<Timeline>> + Send;
     fn purge_spans(&self, period: Duration) -> impl Future<Output = trc::Result<()>> + Send;
 }
 
@@ -245,6 +254,45 @@ impl TracingStore for Store {
         Ok(spans.into_vec())
     }
 
+    async fn get_timeline(&self, span_id: u64) -> trc::Result<Timeline> {
+        // Follow the queue ids referenced by each span to collect all related spans
+        // (inbound session, delivery attempts, DSNs, etc.)
+        let mut pending = vec![span_id];
+        let mut seen_spans = AHashSet::new();
+        let mut seen_queue_ids = AHashSet::new();
+        let mut timeline = Vec::new();
+
+        while let Some(span_id) = pending.pop() {
+            if seen_spans.len() >= MAX_TIMELINE_SPANS || !seen_spans.insert(span_id) {
+                continue;
+            }
+
+            let events = self.get_span(span_id).await?;
+            for event in &events {
+                for (key, value) in &event.keys {
+                    if let (Key::QueueId, Value::UInt(queue_id)) = (key, value) {
+                        if seen_queue_ids.insert(*queue_id) {
+                            pending.extend(
+                                self.query_spans(&[TracingQuery::QueueId(*queue_id)], 0, 0)
+                                    .await?
+                                    .into_iter()
+                                    .filter(|span_id| !seen_spans.contains(span_id)),
+                            );
+                        }
+                    }
+                }
+            }
+            timeline.extend(events);
+        }
+
+        timeline.sort_by_key(|event| event.inner.timestamp);
+
+        Ok(Timeline {
+            correlation_id: seen_spans.into_iter().min().unwrap_or(span_id),
+            events: timeline,
+        })
+    }
+
     async fn purge_spans(&self, period: Duration) -> trc::Result<()> {
         let until_span_id = SnowflakeIdGenerator::from_duration(period).ok_or_else(|| {
             trc::StoreEvent::UnexpectedError
@@ -394,6 +442,8 @@ impl StoreTracer {
                                 | QueueEvent::RateLimitExceeded
                                 | QueueEvent::ConcurrencyLimitExceeded
                                 | QueueEvent::QuotaExceeded
+                                | QueueEvent::RecipientRewritten
+                                | QueueEvent::RewriteLoop
                         )
                         | EventType::Limit(_)
                         | EventType::Tls(_)
@@ -418,6 +468,11 @@ impl StoreTracer {
                             AuthEvent::Success
                                 | AuthEvent::Failed
                                 | AuthEvent::TooManyAttempts
+                                | AuthEvent::MfaDenied
+                                | AuthEvent::Impersonation
+                                | AuthEvent::Throttled
+                                | AuthEvent::DirectoryUnavailable
+                                | AuthEvent::SessionsRevoked
                                 | AuthEvent::Error
                         )
                         | EventType::Sieve(_)
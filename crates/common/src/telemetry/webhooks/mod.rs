@@ -6,13 +6,14 @@
 
 use std::{
     sync::{
-        atomic::{AtomicBool, Ordering},
+        atomic::{AtomicBool, AtomicU32, Ordering},
         Arc,
     },
-    time::Instant,
+    time::{Duration, Instant},
 };
 
 use crate::config::telemetry::WebhookTracer;
+use ahash::AHashMap;
 use base64::{engine::general_purpose::STANDARD, Engine};
 use ring::hmac;
 use serde::Serialize;
@@ -21,11 +22,15 @@ use tokio::sync::mpsc;
 use trc::{
     ipc::subscriber::{EventBatch, SubscriberBuilder},
     serializers::json::JsonEventSerializer,
-    Event, EventDetails, ServerEvent, TelemetryEvent,
+    Event, EventDetails, EventType, Key, ServerEvent, TelemetryEvent,
 };
+use utils::config::Rate;
 
 use super::LONG_SLUMBER;
 
+const MAX_SEEN_EVENTS: usize = 10_000;
+const MAX_LOGGED_DISCARDED: usize = 100;
+
 pub(crate) fn spawn_webhook_tracer(builder: SubscriberBuilder, settings: WebhookTracer) {
     let (tx, mut rx) = builder.register();
     tokio::spawn(async move {
@@ -35,6 +40,8 @@ pub(crate) fn spawn_webhook_tracer(builder: SubscriberBuilder, settings: Webhook
         let mut pending_events = Vec::new();
         let mut next_delivery = Instant::now();
         let in_flight = Arc::new(AtomicBool::new(false));
+        let failures = Arc::new(AtomicU32::new(0));
+        let mut filter = EventFilter::new(&settings);
 
         loop {
             // Wait for the next event or timeout
@@ -43,20 +50,40 @@ pub(crate) fn spawn_webhook_tracer(builder: SubscriberBuilder, settings: Webhook
 
             match event_or_timeout {
                 Ok(Some(events)) => {
-                    let mut discard_count = 0;
+                    let mut discarded = Vec::new();
+                    let mut total_discarded = 0;
+                    let mut suppressed = 0;
                     for event in events {
-                        if now.saturating_sub(event.inner.timestamp) < discard_after {
+                        if now.saturating_sub(event.inner.timestamp) >= discard_after {
+                            total_discarded += 1;
+                            if discarded.len() < MAX_LOGGED_DISCARDED {
+                                discarded.push(event);
+                            }
+                        } else if filter.is_allowed(&event, now) {
                             pending_events.push(event)
                         } else {
-                            discard_count += 1;
+                            suppressed += 1;
                         }
                     }
 
-                    if discard_count > 0 {
+                    if suppressed > 0 {
+                        trc::event!(
+                            Telemetry(TelemetryEvent::WebhookSuppressed),
+                            Total = suppressed,
+                        );
+                    }
+
+                    if total_discarded > 0 {
+                        // Log the first undeliverable events so they can be replayed
+                        // manually, the dump is capped to avoid flooding the log
                         trc::event!(
                             Telemetry(TelemetryEvent::WebhookError),
                             Details = "Discarded stale events",
-                            Total = discard_count
+                            Total = total_discarded,
+                            Contents = serde_json::to_string(
+                                &JsonEventSerializer::new(discarded).with_id()
+                            )
+                            .unwrap_or_default()
                         );
                     }
                 }
@@ -71,11 +98,12 @@ pub(crate) fn spawn_webhook_tracer(builder: SubscriberBuilder, settings: Webhook
             let now = Instant::now();
             if next_delivery <= now {
                 if !pending_events.is_empty() {
-                    next_delivery = now + settings.throttle;
+                    next_delivery = now + retry_delay(&settings, failures.load(Ordering::Relaxed));
                     if !in_flight.load(Ordering::Relaxed) {
                         spawn_webhook_handler(
                             settings.clone(),
                             in_flight.clone(),
+                            failures.clone(),
                             std::mem::take(&mut pending_events),
                             tx.clone(),
                         );
@@ -96,6 +124,78 @@ pub(crate) fn spawn_webhook_tracer(builder: SubscriberBuilder, settings: Webhook
     });
 }
 
+// Back off exponentially while the endpoint keeps failing, until events
+// start being discarded as stale
+fn retry_delay(settings: &WebhookTracer, failures: u32) -> Duration {
+    settings
+        .throttle
+        .saturating_mul(1 << failures.min(31))
+        .min(settings.discard_after.max(settings.throttle))
+}
+
+// Drops repeated events and events over the rate limit, so that a burst
+// of events such as a ban storm does not flood the endpoint
+struct EventFilter {
+    dedup_window: Option<u64>,
+    seen: AHashMap<(EventType, String), u64>,
+    rate_limit: Option<Rate>,
+    window_start: u64,
+    window_count: u64,
+}
+
+impl EventFilter {
+    fn new(settings: &WebhookTracer) -> Self {
+        EventFilter {
+            dedup_window: settings.dedup_window.map(|window| window.as_secs()),
+            seen: AHashMap::new(),
+            rate_limit: settings.rate_limit.clone(),
+            window_start: 0,
+            window_count: 0,
+        }
+    }
+
+    fn is_allowed(&mut self, event: &Event<EventDetails>, now: u64) -> bool {
+        if let Some(window) = self.dedup_window {
+            // Events are considered duplicates when they have the same type,
+            // account and remote IP
+            let key = (
+                event.inner.typ,
+                [Key::AccountName, Key::AccountId, Key::RemoteIp]
+                    .into_iter()
+                    .filter_map(|key| event.value(key))
+                    .map(|value| value.to_string())
+                    .collect::<Vec<_>>()
+                    .join(","),
+            );
+            match self.seen.get(&key) {
+                Some(last_seen) if now.saturating_sub(*last_seen) < window => {
+                    return false;
+                }
+                _ => {
+                    if self.seen.len() >= MAX_SEEN_EVENTS {
+                        self.seen
+                            .retain(|_, last_seen| now.saturating_sub(*last_seen) < window);
+                    }
+                    self.seen.insert(key, now);
+                }
+            }
+        }
+
+        if let Some(rate_limit) = &self.rate_limit {
+            if now.saturating_sub(self.window_start) >= rate_limit.period.as_secs().max(1) {
+                self.window_start = now;
+                self.window_count = 0;
+            }
+            if self.window_count >= rate_limit.requests {
+                return false;
+            }
+            self.window_count += 1;
+        }
+
+        true
+    }
+}
+
 #[derive(Serialize)]
 struct EventWrapper {
     events: JsonEventSerializer<Vec<Arc<Event<EventDetails>>>>,
@@ -104,17 +204,22 @@ struct EventWrapper {
 fn spawn_webhook_handler(
     settings: Arc<WebhookTracer>,
     in_flight: Arc<AtomicBool>,
+    failures: Arc<AtomicU32>,
     events: EventBatch,
     webhook_tx: mpsc::Sender<EventBatch>,
 ) {
     tokio::spawn(async move {
         in_flight.store(true, Ordering::Relaxed);
         let wrapper = EventWrapper {
-            events: JsonEventSerializer::new(events).with_id(),
+            events: JsonEventSerializer::new(events).with_id().with_spans(),
         };
 
         if let Err(err) = post_webhook_events(&settings, &wrapper).await {
-            trc::event!(Telemetry(TelemetryEvent::WebhookError), Details = err);
+            trc::event!(
+                Telemetry(TelemetryEvent::WebhookError),
+                Details = err,
+                Total = failures.fetch_add(1, Ordering::Relaxed) + 1
+            );
 
             if webhook_tx.send(wrapper.events.into_inner()).await.is_err() {
                 trc::event!(
@@ -123,6 +228,8 @@ fn spawn_webhook_handler(
                     CausedBy = trc::location!()
                 );
             }
+        } else {
+            failures.store(0, Ordering::Relaxed);
         }
 
         in_flight.store(false, Ordering::Relaxed);
@@ -173,3 +280,64 @@ async fn post_webhook_events(
         ))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use hyper::HeaderMap;
+    use trc::{Event, EventDetails, EventType, Key, Level, SecurityEvent};
+    use utils::config::Rate;
+
+    use crate::config::telemetry::WebhookTracer;
+
+    use super::EventFilter;
+
+    fn ban_event(ip: &str) -> Event<EventDetails> {
+        let mut event = Event::new(EventDetails {
+            typ: EventType::Security(SecurityEvent::AuthenticationBan),
+            timestamp: 0,
+            level: Level::Info,
+            span: None,
+        });
+        event.keys.push((Key::RemoteIp, ip.to_string().into()));
+        event
+    }
+
+    fn build_filter(dedup_window: Option<Duration>, rate_limit: Option<Rate>) -> EventFilter {
+        EventFilter::new(&WebhookTracer {
+            url: String::new(),
+            key: String::new(),
+            timeout: Duration::from_secs(30),
+            throttle: Duration::from_secs(1),
+            discard_after: Duration::from_secs(300),
+            dedup_window,
+            rate_limit,
+            tls_allow_invalid_certs: false,
+            headers: HeaderMap::new(),
+        })
+    }
+
+    #[test]
+    fn webhook_event_filter() {
+        // Repeated events are dropped until the window expires
+        let mut filter = build_filter(Some(Duration::from_secs(60)), None);
+        assert!(filter.is_allowed(&ban_event("10.0.0.1"), 1000));
+        assert!(!filter.is_allowed(&ban_event("10.0.0.1"), 1030));
+        assert!(filter.is_allowed(&ban_event("10.0.0.2"), 1030));
+        assert!(filter.is_allowed(&ban_event("10.0.0.1"), 1060));
+
+        // Events over the rate limit are dropped until the next period
+        let mut filter = build_filter(
+            None,
+            Some(Rate {
+                requests: 2,
+                period: Duration::from_secs(60),
+            }),
+        );
+        assert!(filter.is_allowed(&ban_event("10.0.0.1"), 1000));
+        assert!(filter.is_allowed(&ban_event("10.0.0.1"), 1010));
+        assert!(!filter.is_allowed(&ban_event("10.0.0.2"), 1020));
+        assert!(filter.is_allowed(&ban_event("10.0.0.2"), 1060));
+    }
+}
@@ -60,6 +60,14 @@ impl ImapDirectory {
         }
     }
 
+    pub async fn ping(&self) -> trc::Result<()> {
+        self.pool
+            .get()
+            .await
+            .map(|_| ())
+            .map_err(|err| err.into_error().caused_by(trc::location!()))
+    }
+
     pub async fn email_to_ids(&self, _address: &str) -> trc::Result<Vec<u32>> {
         Err(trc::StoreEvent::NotSupported.caused_by(trc::location!()))
     }
@@ -6,12 +6,15 @@
 
 use mail_send::Credentials;
 use store::{
-    write::{DirectoryClass, ValueClass},
-    Deserialize, IterateParams, Store, ValueKey,
+    write::{assert::HashedValue, BatchBuilder, DirectoryClass, MaybeDynamicId, ValueClass},
+    Deserialize, IterateParams, Serialize, Store, ValueKey,
 };
 use trc::AddContext;
 
-use crate::{Principal, QueryBy, Type};
+use crate::{
+    core::{scram::ScramSecret, secret::SecretRehash},
+    Principal, QueryBy, Type,
+};
 
 use super::{manage::ManageDirectory, PrincipalField, PrincipalInfo};
 
@@ -22,6 +25,21 @@ pub trait DirectoryStore: Sync + Send {
         by: QueryBy<'_>,
         return_member_of: bool,
     ) -> trc::Result<Option<Principal>>;
+    async fn query_and_rehash(
+        &self,
+        by: QueryBy<'_>,
+        return_member_of: bool,
+        rehash: Option<&SecretRehash>,
+        store_scram: bool,
+    ) -> trc::Result<Option<Principal>>;
+    async fn query_credentials(
+        &self,
+        by: QueryBy<'_>,
+        return_member_of: bool,
+        rehash: Option<&SecretRehash>,
+        store_scram: bool,
+        app_scope: Option<&str>,
+    ) -> trc::Result<Option<(Principal, bool)>>;
     async fn email_to_ids(&self, email: &str) -> trc::Result<Vec<u32>>;
     async fn is_local_domain(&self, domain: &str) -> trc::Result<bool>;
     async fn rcpt(&self, address: &str) -> trc::Result<bool>;
@@ -35,6 +53,32 @@ impl DirectoryStore for Store {
         by: QueryBy<'_>,
         return_member_of: bool,
     ) -> trc::Result<Option<Principal>> {
+        self.query_and_rehash(by, return_member_of, None, false)
+            .await
+    }
+
+    async fn query_and_rehash(
+        &self,
+        by: QueryBy<'_>,
+        return_member_of: bool,
+        rehash: Option<&SecretRehash>,
+        store_scram: bool,
+    ) -> trc::Result<Option<Principal>> {
+        self.query_credentials(by, return_member_of, rehash, store_scram, None)
+            .await
+            .map(|result| result.map(|(principal, _)| principal))
+    }
+
+    // Returns the principal along with whether it was authenticated with one of its
+    // app passwords, which are only checked when a scope is provided
+    async fn query_credentials(
+        &self,
+        by: QueryBy<'_>,
+        return_member_of: bool,
+        rehash: Option<&SecretRehash>,
+        store_scram: bool,
+        app_scope: Option<&str>,
+    ) -> trc::Result<Option<(Principal, bool)>> {
         let (account_id, secret) = match by {
             QueryBy::Name(name) => (self.get_principal_id(name).await?, None),
             QueryBy::Id(account_id) => (account_id.into(), None),
@@ -55,9 +99,31 @@ impl DirectoryStore for Store {
 
         if let Some(account_id) = account_id {
             if let Some(mut principal) = self.get_principal(account_id).await? {
+                let mut is_app_password = false;
                 if let Some(secret) = secret {
-                    if !principal.verify_secret(secret).await? {
-                        return Ok(None);
+                    match principal.verify_secret_match(secret).await? {
+                        Some((hashed_secret, secret)) => {
+                            // SCRAM credentials are stored first as they are only
+                            // derived while the verified hash is still in place
+                            if store_scram
+                                && !hashed_secret.is_empty()
+                                && !principal.has_scram_secret()
+                            {
+                                store_scram_secret(self, account_id, hashed_secret, secret).await;
+                            }
+                            if let Some(rehash) = rehash.filter(|rehash| {
+                                !hashed_secret.is_empty() && rehash.needs_rehash(hashed_secret)
+                            }) {
+                                rehash_secret(self, account_id, hashed_secret, secret, rehash)
+                                    .await;
+                            }
+                        }
+                        None => match app_scope {
+                            Some(scope) if principal.verify_app_password(secret, scope).await? => {
+                                is_app_password = true;
+                            }
+                            _ => return Ok(None),
+                        },
                     }
                 }
 
@@ -71,7 +137,7 @@ impl DirectoryStore for Store {
                         principal.append_int(field, member.principal_id);
                     }
                 }
-                return Ok(Some(principal));
+                return Ok(Some((principal, is_app_password)));
             }
         }
         Ok(None)
@@ -164,3 +230,108 @@ impl DirectoryStore for Store {
         Ok(results)
     }
 }
+
+// Replaces a password stored in a legacy format with its Argon2id hash. OAuth
+// tokens of internal accounts are not bound to the hash and remain valid.
+async fn rehash_secret(
+    store: &Store,
+    account_id: u32,
+    hashed_secret: &str,
+    secret: &str,
+    rehash: &SecretRehash,
+) {
+    let result = match rehash.hash(secret).await {
+        Ok(new_hashed_secret) => {
+            update_secrets(store, account_id, |principal| {
+                if let Some(value) = principal
+                    .iter_mut_str(PrincipalField::Secrets)
+                    .find(|value| value.as_str() == hashed_secret)
+                {
+                    *value = new_hashed_secret;
+                    true
+                } else {
+                    false
+                }
+            })
+            .await
+        }
+        Err(err) => Err(err),
+    };
+
+    if let Err(err) = result {
+        trc::error!(err
+            .account_id(account_id)
+            .details("Failed to rehash secret")
+            .caused_by(trc::location!()));
+    }
+}
+
+// Derives the SCRAM-SHA-256 credentials of an account from its password,
+// which is only known after a successful plain text authentication.
+async fn store_scram_secret(store: &Store, account_id: u32, hashed_secret: &str, secret: &str) {
+    if let Err(err) = update_secrets(store, account_id, |principal| {
+        if principal.has_str_value(PrincipalField::Secrets, hashed_secret)
+            && !principal.has_scram_secret()
+        {
+            principal.append_str(
+                PrincipalField::Secrets,
+                ScramSecret::generate(secret).to_string(),
+            );
+            true
+        } else {
+            false
+        }
+    })
+    .await
+    {
+        trc::error!(err
+            .account_id(account_id)
+            .details("Failed to store SCRAM credentials")
+            .caused_by(trc::location!()));
+    }
+}
+
+// Updates the secrets of an account in place. The write is asserted against the
+// stored principal, so when concurrent logins race to perform the same update
+// only the first one succeeds and the others find nothing left to do.
+async fn update_secrets(
+    store: &Store,
+    account_id: u32,
+    update: impl FnOnce(&mut Principal) -> bool,
+) -> trc::Result<()> {
+    let mut principal = if let Some(principal) = store
+        .get_value::<HashedValue<Principal>>(ValueKey::from(ValueClass::Directory(
+            DirectoryClass::Principal(account_id),
+        )))
+        .await?
+    {
+        principal
+    } else {
+        return Ok(());
+    };
+
+    let mut batch = BatchBuilder::new();
+    batch.assert_value(
+        ValueClass::Directory(DirectoryClass::Principal(MaybeDynamicId::Static(
+            account_id,
+        ))),
+        &principal,
+    );
+    if !update(&mut principal.inner) {
+        return Ok(());
+    }
+    batch.set(
+        ValueClass::Directory(DirectoryClass::Principal(MaybeDynamicId::Static(
+            account_id,
+        ))),
+        principal.inner.serialize(),
+    );
+
+    match store.write(batch.build()).await {
+        Ok(_) => Ok(()),
+        Err(err) if err.matches(trc::EventType::Store(trc::StoreEvent::AssertValueFailed)) => {
+            Ok(())
+        }
+        Err(err) => Err(err),
+    }
+}
@@ -4,7 +4,7 @@
  * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
  */
 
-use ahash::AHashSet;
+use ahash::{AHashMap, AHashSet};
 use jmap_proto::types::collection::Collection;
 use store::{
     write::{
@@ -15,7 +15,10 @@ use store::{
 };
 use trc::AddContext;
 
-use crate::{Permission, Principal, QueryBy, Type, ROLE_ADMIN, ROLE_TENANT_ADMIN, ROLE_USER};
+use crate::{
+    core::secret::AppPassword, Permission, Principal, QueryBy, Type, ROLE_ADMIN, ROLE_TENANT_ADMIN,
+    ROLE_USER,
+};
 
 use super::{
     lookup::DirectoryStore, PrincipalAction, PrincipalField, PrincipalInfo, PrincipalUpdate,
@@ -70,6 +73,11 @@ pub trait ManageDirectory: Sized {
         typ: Option<Type>,
         tenant_id: Option<u32>,
     ) -> trc::Result<u64>;
+    async fn count_principals_by_type(
+        &self,
+        types: &[Type],
+        tenant_id: Option<u32>,
+    ) -> trc::Result<AHashMap<Type, u64>>;
     async fn map_field_ids(
         &self,
         principal: &mut Principal,
@@ -846,7 +854,25 @@ impl ManageDirectory for Store {
                     PrincipalField::Secrets,
                     value @ (PrincipalValue::StringList(_) | PrincipalValue::String(_)),
                 ) => {
+                    let passwords = principal
+                        .inner
+                        .iter_str(PrincipalField::Secrets)
+                        .filter(|v| v.is_password())
+                        .cloned()
+                        .collect::<Vec<_>>();
                     principal.inner.set(PrincipalField::Secrets, value);
+
+                    // SCRAM credentials are derived from the password, drop them when it changes
+                    if principal
+                        .inner
+                        .iter_str(PrincipalField::Secrets)
+                        .filter(|v| v.is_password())
+                        .ne(passwords.iter())
+                    {
+                        principal
+                            .inner
+                            .retain_str(PrincipalField::Secrets, |v| !v.is_scram());
+                    }
                 }
                 (
                     PrincipalAction::AddItem,
@@ -875,13 +901,71 @@ impl ManageDirectory for Store {
                             *v != secret && !v.starts_with(&secret)
                         });
                     } else if !secret.is_empty() {
-                        principal
-                            .inner
-                            .retain_str(PrincipalField::Secrets, |v| *v != secret);
+                        // Removing a password also removes the SCRAM credentials derived from it
+                        let is_password = secret.is_password();
+                        principal.inner.retain_str(PrincipalField::Secrets, |v| {
+                            *v != secret && !(is_password && v.is_scram())
+                        });
                     } else {
+                        principal.inner.retain_str(PrincipalField::Secrets, |v| {
+                            !v.is_password() && !v.is_scram()
+                        });
+                    }
+                }
+                (
+                    PrincipalAction::Set,
+                    PrincipalField::AppPasswords,
+                    value @ (PrincipalValue::StringList(_) | PrincipalValue::String(_)),
+                ) => {
+                    if let Some(value) = value
+                        .iter_str()
+                        .find(|value| !AppPassword::parse(value).is_some_and(|app| app.is_valid()))
+                    {
+                        return Err(error(
+                            "Invalid app password",
+                            format!("Invalid app password {value:?}").into(),
+                        ));
+                    }
+                    principal.inner.set(PrincipalField::AppPasswords, value);
+                }
+                (
+                    PrincipalAction::AddItem,
+                    PrincipalField::AppPasswords,
+                    PrincipalValue::String(secret),
+                ) => {
+                    let name = AppPassword::parse(&secret)
+                        .filter(|app| app.is_valid())
+                        .ok_or_else(|| {
+                            error(
+                                "Invalid app password",
+                                format!("Invalid app password {secret:?}").into(),
+                            )
+                        })?
+                        .name;
+
+                    // Replace any existing app password with the same name
+                    principal
+                        .inner
+                        .retain_str(PrincipalField::AppPasswords, |v| {
+                            AppPassword::parse(v).map_or(true, |app| app.name != name)
+                        });
+                    principal
+                        .inner
+                        .append_str(PrincipalField::AppPasswords, secret);
+                }
+                (
+                    PrincipalAction::RemoveItem,
+                    PrincipalField::AppPasswords,
+                    PrincipalValue::String(name),
+                ) => {
+                    if !name.is_empty() {
                         principal
                             .inner
-                            .retain_str(PrincipalField::Secrets, |v| !v.is_password());
+                            .retain_str(PrincipalField::AppPasswords, |v| {
+                                AppPassword::parse(v).map_or(true, |app| app.name != name)
+                            });
+                    } else {
+                        principal.inner.remove(PrincipalField::AppPasswords);
                     }
                 }
                 (
@@ -1519,6 +1603,37 @@ impl ManageDirectory for Store {
         .map(|_| count)
     }
 
+    async fn count_principals_by_type(
+        &self,
+        types: &[Type],
+        tenant_id: Option<u32>,
+    ) -> trc::Result<AHashMap<Type, u64>> {
+        let from_key = ValueKey::from(ValueClass::Directory(DirectoryClass::NameToId(vec![])));
+        let to_key = ValueKey::from(ValueClass::Directory(DirectoryClass::NameToId(vec![
+            u8::MAX;
+            10
+        ])));
+
+        let mut counts: AHashMap<Type, u64> = types.iter().map(|typ| (*typ, 0)).collect();
+        self.iterate(
+            IterateParams::new(from_key, to_key).ascending(),
+            |_, value| {
+                let pt = PrincipalInfo::deserialize(value).caused_by(trc::location!())?;
+
+                if pt.has_tenant_access(tenant_id) {
+                    if let Some(count) = counts.get_mut(&pt.typ) {
+                        *count += 1;
+                    }
+                }
+
+                Ok(true)
+            },
+        )
+        .await
+        .caused_by(trc::location!())
+        .map(|_| counts)
+    }
+
     async fn get_member_of(&self, principal_id: u32) -> trc::Result<Vec<MemberOf>> {
         let from_key = ValueKey::from(ValueClass::Directory(DirectoryClass::MemberOf {
             principal_id,
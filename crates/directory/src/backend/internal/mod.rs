@@ -393,6 +393,7 @@ pub enum PrincipalField {
     EnabledPermissions,
     DisabledPermissions,
     Picture,
+    AppPasswords,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
@@ -471,6 +472,7 @@ impl PrincipalField {
             PrincipalField::DisabledPermissions => 12,
             PrincipalField::UsedQuota => 13,
             PrincipalField::Picture => 14,
+            PrincipalField::AppPasswords => 15,
         }
     }
 
@@ -491,6 +493,7 @@ impl PrincipalField {
             12 => Some(PrincipalField::DisabledPermissions),
             13 => Some(PrincipalField::UsedQuota),
             14 => Some(PrincipalField::Picture),
+            15 => Some(PrincipalField::AppPasswords),
             _ => None,
         }
     }
@@ -512,6 +515,7 @@ impl PrincipalField {
             PrincipalField::EnabledPermissions => "enabledPermissions",
             PrincipalField::DisabledPermissions => "disabledPermissions",
             PrincipalField::Picture => "picture",
+            PrincipalField::AppPasswords => "appPasswords",
         }
     }
 
@@ -532,6 +536,7 @@ impl PrincipalField {
             "enabledPermissions" => Some(PrincipalField::EnabledPermissions),
             "disabledPermissions" => Some(PrincipalField::DisabledPermissions),
             "picture" => Some(PrincipalField::Picture),
+            "appPasswords" => Some(PrincipalField::AppPasswords),
             _ => None,
         }
     }
@@ -549,6 +554,7 @@ fn deserialize_string(bytes: &mut Iter<'_, u8>) -> Option<String> {
 pub trait SpecialSecrets {
     fn is_otp_auth(&self) -> bool;
     fn is_app_password(&self) -> bool;
+    fn is_scram(&self) -> bool;
     fn is_password(&self) -> bool;
 }
 
@@ -564,7 +570,11 @@ where
         self.as_ref().starts_with("$app$")
     }
 
+    fn is_scram(&self) -> bool {
+        self.as_ref().starts_with("$scram-sha-256$")
+    }
+
     fn is_password(&self) -> bool {
-        !self.is_otp_auth() && !self.is_app_password()
+        !self.is_otp_auth() && !self.is_app_password() && !self.is_scram()
     }
 }
@@ -11,13 +11,38 @@ use std::{
 };
 
 use parking_lot::Mutex;
+use sha2::{Digest, Sha256};
+use store::write::now;
 use utils::config::{utils::AsKey, Config};
 
+use crate::{
+    backend::internal::{PrincipalField, SpecialSecrets},
+    Principal,
+};
+
 pub struct CachedDirectory {
     cached_domains: Mutex<LookupCache<String>>,
     cached_rcpts: Mutex<LookupCache<String>>,
 }
 
+pub struct CachedAuth {
+    entries: Mutex<lru_cache::LruCache<AuthCacheKey, CachedCredentials, ahash::RandomState>>,
+    ttl_pos: Duration,
+    ttl_neg: Duration,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct AuthCacheKey {
+    username: String,
+    hash: [u8; 32],
+}
+
+struct CachedCredentials {
+    principal: Option<Principal>,
+    valid_until: Instant,
+    cached_at: u64,
+}
+
 #[allow(clippy::type_complexity)]
 #[derive(Debug)]
 pub struct LookupCache<T: Hash + Eq> {
@@ -77,6 +102,110 @@ impl CachedDirectory {
     }
 }
 
+impl CachedAuth {
+    pub fn try_from_config(config: &mut Config, prefix: impl AsKey) -> Option<Self> {
+        let prefix = prefix.as_key();
+        let cached_entries = config.property((&prefix, "cache.auth.entries"))?;
+
+        Some(CachedAuth {
+            entries: Mutex::new(lru_cache::LruCache::with_hasher(
+                cached_entries,
+                ahash::RandomState::new(),
+            )),
+            ttl_pos: config
+                .property((&prefix, "cache.auth.ttl.positive"))
+                .unwrap_or(Duration::from_secs(60)),
+            ttl_neg: config
+                .property((&prefix, "cache.auth.ttl.negative"))
+                .unwrap_or(Duration::from_secs(300)),
+        })
+    }
+
+    pub fn key(
+        username: &str,
+        secret: &str,
+        protocol: &str,
+        return_member_of: bool,
+    ) -> AuthCacheKey {
+        // Secrets are never stored in the clear, the protocol is part of the key
+        // because app passwords may be restricted to specific protocols
+        let mut hasher = Sha256::new();
+        hasher.update(protocol.as_bytes());
+        hasher.update([0, return_member_of as u8]);
+        hasher.update(secret.as_bytes());
+
+        AuthCacheKey {
+            username: username.to_string(),
+            hash: hasher.finalize().into(),
+        }
+    }
+
+    pub fn get(&self, key: &AuthCacheKey) -> Option<Option<Principal>> {
+        let mut entries = self.entries.lock();
+        let entry = entries.get_mut(key)?;
+        if entry.valid_until >= Instant::now() {
+            Some(entry.principal.clone())
+        } else {
+            entries.remove(key);
+            None
+        }
+    }
+
+    pub fn set(&self, key: AuthCacheKey, principal: Option<&Principal>) {
+        let (principal, ttl) = match principal {
+            Some(principal) => {
+                // Credentials including a TOTP token are never cached
+                if principal
+                    .iter_str(PrincipalField::Secrets)
+                    .any(|secret| secret.is_otp_auth())
+                {
+                    return;
+                }
+
+                (Some(principal.clone()), self.ttl_pos)
+            }
+            None => (None, self.ttl_neg),
+        };
+
+        self.entries.lock().insert(
+            key,
+            CachedCredentials {
+                principal,
+                valid_until: Instant::now() + ttl,
+                cached_at: now(),
+            },
+        );
+    }
+
+    // Removes the credentials of an account cached up to the given time. Failed
+    // attempts are not linked to an account, they are matched by login name.
+    pub fn invalidate(&self, account_id: u32, names: &[String], cached_until: u64) {
+        let mut entries = self.entries.lock();
+        let expired = entries
+            .iter()
+            .filter(|(key, entry)| {
+                entry.cached_at <= cached_until
+                    && match &entry.principal {
+                        Some(principal) => principal.id() == account_id,
+                        None => names.contains(&key.username),
+                    }
+            })
+            .map(|(key, _)| key.clone())
+            .collect::<Vec<_>>();
+        for key in expired {
+            entries.remove(&key);
+        }
+    }
+
+    pub fn max_ttl(&self) -> Duration {
+        self.ttl_pos.max(self.ttl_neg)
+    }
+
+    pub fn clear(&self) {
+        self.entries.lock().clear();
+    }
+}
+
 impl<T: Hash + Eq> LookupCache<T> {
     pub fn new(capacity: usize, ttl_pos: Duration, ttl_neg: Duration) -> Self {
         Self {
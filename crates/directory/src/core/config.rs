@@ -22,7 +22,10 @@ use crate::{
     Directories, Directory, DirectoryInner,
 };
 
-use super::cache::CachedDirectory;
+use super::{
+    cache::{CachedAuth, CachedDirectory},
+    secret::SecretRehash,
+};
 
 impl Directories {
     pub async fn parse(config: &mut Config, stores: &Stores, data_store: Store) -> Self {
@@ -86,8 +89,18 @@ impl Directories {
             // Build directory
             if let Some(store) = store {
                 let directory = Arc::new(Directory {
-                    store,
                     cache: CachedDirectory::try_from_config(config, ("directory", id)),
+                    auth_cache: CachedAuth::try_from_config(config, ("directory", id)),
+                    rehash: if matches!(store, DirectoryInner::Internal(_)) {
+                        SecretRehash::try_from_config(config, ("directory", id))
+                    } else {
+                        None
+                    },
+                    store_scram: matches!(store, DirectoryInner::Internal(_))
+                        && config
+                            .property_or_default(("directory", id, "password.scram"), "false")
+                            .unwrap_or(false),
+                    store,
                 });
 
                 // Add directory
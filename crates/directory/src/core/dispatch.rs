@@ -4,6 +4,7 @@
  * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
  */
 
+use mail_send::Credentials;
 use trc::AddContext;
 
 use crate::{
@@ -17,7 +18,11 @@ impl Directory {
         return_member_of: bool,
     ) -> trc::Result<Option<Principal>> {
         match &self.store {
-            DirectoryInner::Internal(store) => store.query(by, return_member_of).await,
+            DirectoryInner::Internal(store) => {
+                store
+                    .query_and_rehash(by, return_member_of, self.rehash.as_ref(), self.store_scram)
+                    .await
+            }
             DirectoryInner::Ldap(store) => store.query(by, return_member_of).await,
             DirectoryInner::Sql(store) => store.query(by, return_member_of).await,
             DirectoryInner::Imap(store) => store.query(by).await,
@@ -27,6 +32,33 @@ impl Directory {
         .caused_by(trc::location!())
     }
 
+    // Verifies the credentials and, for plain credentials against the internal directory,
+    // the app passwords allowed for the protocol in the same lookup. Returns whether
+    // an app password was used.
+    pub async fn query_credentials(
+        &self,
+        credentials: &Credentials<String>,
+        protocol: &str,
+        return_member_of: bool,
+    ) -> trc::Result<Option<(Principal, bool)>> {
+        match (&self.store, credentials) {
+            (DirectoryInner::Internal(store), Credentials::Plain { .. }) => store
+                .query_credentials(
+                    QueryBy::Credentials(credentials),
+                    return_member_of,
+                    self.rehash.as_ref(),
+                    self.store_scram,
+                    Some(protocol),
+                )
+                .await
+                .caused_by(trc::location!()),
+            _ => self
+                .query(QueryBy::Credentials(credentials), return_member_of)
+                .await
+                .map(|principal| principal.map(|principal| (principal, false))),
+        }
+    }
+
     pub async fn email_to_ids(&self, email: &str) -> trc::Result<Vec<u32>> {
         match &self.store {
             DirectoryInner::Internal(store) => store.email_to_ids(email).await,
@@ -65,6 +97,20 @@ impl Directory {
         Ok(result)
     }
 
+    // Queries the backend bypassing the cache, used to verify that it is reachable
+    pub async fn ping(&self) -> trc::Result<()> {
+        let domain = "health-check.invalid";
+        match &self.store {
+            DirectoryInner::Internal(store) => store.is_local_domain(domain).await.map(|_| ()),
+            DirectoryInner::Ldap(store) => store.is_local_domain(domain).await.map(|_| ()),
+            DirectoryInner::Sql(store) => store.is_local_domain(domain).await.map(|_| ()),
+            DirectoryInner::Imap(store) => store.ping().await,
+            DirectoryInner::Smtp(store) => store.ping().await,
+            DirectoryInner::Memory(_) => Ok(()),
+        }
+        .caused_by(trc::location!())
+    }
+
     pub async fn rcpt(&self, email: &str) -> trc::Result<bool> {
         // Check cache
         if let Some(cache) = &self.cache {
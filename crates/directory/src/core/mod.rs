@@ -10,6 +10,7 @@ pub mod cache;
 pub mod config;
 pub mod dispatch;
 pub mod principal;
+pub mod scram;
 pub mod secret;
 
 impl Permission {
@@ -183,6 +184,14 @@ impl Permission {
             Permission::SieveRenameScript => "Rename Sieve scripts",
             Permission::SieveCheckScript => "Validate Sieve scripts",
             Permission::SieveHaveSpace => "Check available space for Sieve scripts",
+            Permission::OauthIntrospect => "Introspect OAuth tokens issued to any account",
+            Permission::ImapGetQuota => "Retrieve quota usage via IMAP",
+            Permission::ImapSetQuota => "Set account quotas via IMAP",
+            Permission::ThreadRebuild => "Rebuild the email threads of an account",
+            Permission::MessageImport => "Import mbox and Maildir archives into an account",
+            Permission::EmailSendAs => {
+                "Send emails from any address, not only the account's aliases"
+            }
         }
     }
 }
@@ -53,6 +53,13 @@ impl Principal {
     pub fn tenant(&self) -> Option<u32> {
         self.get_int(PrincipalField::Tenant).map(|v| v as u32)
     }
+
+    pub fn has_tenant_access(&self, tenant_id: Option<u32>) -> bool {
+        tenant_id.map_or(true, |tenant_id| {
+            self.tenant().map_or(false, |t| tenant_id == t)
+                || (self.typ == Type::Tenant && self.id == tenant_id)
+        })
+    }
     // SPDX-SnippetEnd
 
     pub fn description(&self) -> Option<&str> {
@@ -426,13 +433,18 @@ impl Principal {
         updates
     }
 
-    pub fn fallback_admin(fallback_pass: impl Into<String>) -> Self {
+    pub fn fallback_admin(
+        id: u32,
+        fallback_user: impl Into<String>,
+        fallback_pass: impl Into<String>,
+    ) -> Self {
         Principal {
-            id: u32::MAX,
+            id,
             typ: Type::Individual,
             ..Default::default()
         }
-        .with_field(PrincipalField::Name, "Fallback Administrator")
+        .with_field(PrincipalField::Name, fallback_user.into())
+        .with_field(PrincipalField::Description, "Fallback Administrator")
         .with_field(
             PrincipalField::Secrets,
             PrincipalValue::String(fallback_pass.into()),
@@ -829,6 +841,7 @@ impl<'de> serde::Deserialize<'de> for Principal {
                         }
                         PrincipalField::Quota => map.next_value::<PrincipalValue>()?,
                         PrincipalField::Secrets
+                        | PrincipalField::AppPasswords
                         | PrincipalField::Emails
                         | PrincipalField::MemberOf
                         | PrincipalField::Members
@@ -1063,6 +1076,7 @@ impl Permission {
                 | Permission::ImapStore
                 | Permission::ImapSubscribe
                 | Permission::ImapThread
+                | Permission::ImapGetQuota
                 | Permission::Pop3Authenticate
                 | Permission::Pop3List
                 | Permission::Pop3Uidl
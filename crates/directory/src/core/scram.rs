@@ -0,0 +1,404 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use std::fmt::Display;
+
+use hmac::{Hmac, Mac};
+use mail_builder::encoders::base64::base64_encode;
+use mail_parser::decoders::base64::base64_decode;
+use password_hash::rand_core::{OsRng, RngCore};
+use sha2::{Digest, Sha256};
+
+use crate::backend::internal::{PrincipalField, SpecialSecrets};
+use crate::Principal;
+
+pub const SCRAM_ITERATIONS: u32 = 4096;
+const SCRAM_PREFIX: &str = "$scram-sha-256$";
+const CHANNEL_BINDING: &str = "tls-exporter";
+
+// SCRAM-SHA-256 credentials (RFC 7677) are stored as
+// "$scram-sha-256$<iterations>$<salt>$<stored key>$<server key>".
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScramSecret {
+    pub iterations: u32,
+    pub salt: Vec<u8>,
+    pub stored_key: Vec<u8>,
+    pub server_key: Vec<u8>,
+}
+
+// Server side of a SCRAM-SHA-256 or SCRAM-SHA-256-PLUS exchange (RFC 5802)
+#[derive(Debug, Clone)]
+pub struct ScramExchange {
+    pub username: String,
+    gs2_header: String,
+    client_first_bare: String,
+    nonce: String,
+    server_first: String,
+    secret: Option<ScramSecret>,
+    is_channel_bound: bool,
+}
+
+impl ScramSecret {
+    pub fn generate(password: &str) -> Self {
+        let mut salt = vec![0u8; 16];
+        OsRng.fill_bytes(&mut salt);
+        Self::derive(password, salt, SCRAM_ITERATIONS)
+    }
+
+    pub fn derive(password: &str, salt: Vec<u8>, iterations: u32) -> Self {
+        let mut salted_password = [0u8; 32];
+        pbkdf2::pbkdf2_hmac::<Sha256>(password.as_bytes(), &salt, iterations, &mut salted_password);
+        let client_key = hmac_sha256(&salted_password, b"Client Key");
+
+        ScramSecret {
+            iterations,
+            salt,
+            stored_key: Sha256::digest(client_key).to_vec(),
+            server_key: hmac_sha256(&salted_password, b"Server Key"),
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        let mut parts = value.strip_prefix(SCRAM_PREFIX)?.split('$');
+        let iterations = parts.next()?.parse().ok()?;
+        let salt = base64_decode(parts.next()?.as_bytes())?;
+        let stored_key = base64_decode(parts.next()?.as_bytes())?;
+        let server_key = base64_decode(parts.next()?.as_bytes())?;
+
+        if parts.next().is_none() && stored_key.len() == 32 && server_key.len() == 32 {
+            Some(ScramSecret {
+                iterations,
+                salt,
+                stored_key,
+                server_key,
+            })
+        } else {
+            None
+        }
+    }
+}
+
+impl Display for ScramSecret {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{SCRAM_PREFIX}{}${}${}${}",
+            self.iterations,
+            encode(&self.salt),
+            encode(&self.stored_key),
+            encode(&self.server_key)
+        )
+    }
+}
+
+impl Principal {
+    // Returns the stored SCRAM credentials or, when the password is kept
+    // in plain text, derives them for the current exchange.
+    pub fn scram_secret(&self) -> Option<ScramSecret> {
+        let mut plain_password = None;
+        for secret in self.iter_str(PrincipalField::Secrets) {
+            if secret.is_scram() {
+                if let Some(secret) = ScramSecret::parse(secret) {
+                    return Some(secret);
+                }
+            } else if plain_password.is_none() && secret.is_password() {
+                plain_password = if let Some(value) = secret.strip_prefix('{') {
+                    value.split_once('}').and_then(|(algo, password)| {
+                        matches!(algo, "PLAIN" | "plain" | "CLEAR" | "clear").then_some(password)
+                    })
+                } else if !secret.starts_with(['$', '_']) {
+                    Some(secret)
+                } else {
+                    None
+                };
+            }
+        }
+
+        plain_password.map(ScramSecret::generate)
+    }
+
+    pub fn has_scram_secret(&self) -> bool {
+        self.iter_str(PrincipalField::Secrets)
+            .any(|secret| secret.is_scram())
+    }
+}
+
+impl ScramExchange {
+    // Parses the client-first message. Channel binding data is only
+    // available on TLS connections that support tls-exporter (RFC 9266).
+    pub fn parse(
+        client_first: &[u8],
+        is_plus: bool,
+        has_channel_binding: bool,
+    ) -> Result<Self, &'static str> {
+        let mut nonce = [0u8; 18];
+        OsRng.fill_bytes(&mut nonce);
+        Self::parse_with_nonce(client_first, is_plus, has_channel_binding, encode(&nonce))
+    }
+
+    fn parse_with_nonce(
+        client_first: &[u8],
+        is_plus: bool,
+        has_channel_binding: bool,
+        server_nonce: String,
+    ) -> Result<Self, &'static str> {
+        let client_first =
+            std::str::from_utf8(client_first).map_err(|_| "Invalid client-first message.")?;
+        let (cbind_flag, rest) = client_first
+            .split_once(',')
+            .ok_or("Invalid client-first message.")?;
+        let (authzid, client_first_bare) = rest
+            .split_once(',')
+            .ok_or("Invalid client-first message.")?;
+
+        // Clients supporting channel binding must use it when the server does
+        let is_channel_bound = match cbind_flag {
+            "n" if !is_plus => false,
+            "y" if !is_plus && !has_channel_binding => false,
+            "y" if !is_plus => return Err("Channel binding is supported by the server."),
+            flag if flag.starts_with("p=") => {
+                if !is_plus || !has_channel_binding {
+                    return Err("Channel binding is not supported.");
+                } else if &flag[2..] != CHANNEL_BINDING {
+                    return Err("Unsupported channel binding type.");
+                }
+                true
+            }
+            _ => return Err("Invalid channel binding flag."),
+        };
+
+        let mut username = None;
+        let mut client_nonce = None;
+        for (pos, attribute) in client_first_bare.split(',').enumerate() {
+            match attribute.split_once('=') {
+                Some(("m", _)) if pos == 0 => return Err("Unsupported SCRAM extension."),
+                Some(("n", value)) if pos == 0 => {
+                    username = decode_sasl_name(value);
+                }
+                Some(("r", value)) if pos == 1 && !value.is_empty() => {
+                    client_nonce = Some(value);
+                }
+                _ if pos > 1 => {}
+                _ => return Err("Invalid client-first message."),
+            }
+        }
+        let username = username
+            .filter(|username| !username.is_empty())
+            .ok_or("Invalid username.")?;
+        let client_nonce = client_nonce.ok_or("Missing client nonce.")?;
+
+        // Authorization identities other than the authenticated user are not supported
+        if !authzid.is_empty()
+            && authzid
+                .strip_prefix("a=")
+                .and_then(decode_sasl_name)
+                .map_or(true, |authzid| authzid != username)
+        {
+            return Err("Authorization identity is not supported.");
+        }
+
+        Ok(ScramExchange {
+            username,
+            gs2_header: format!("{cbind_flag},{authzid},"),
+            client_first_bare: client_first_bare.to_string(),
+            nonce: format!("{client_nonce}{server_nonce}"),
+            server_first: String::new(),
+            secret: None,
+            is_channel_bound,
+        })
+    }
+
+    // Builds the server-first message. Unknown accounts receive credentials
+    // that can never be verified so that they are indistinguishable from
+    // existing ones.
+    pub fn server_first(&mut self, secret: Option<ScramSecret>) -> Vec<u8> {
+        let secret = secret.unwrap_or_else(|| {
+            let mut stored_key = vec![0u8; 32];
+            OsRng.fill_bytes(&mut stored_key);
+            ScramSecret {
+                iterations: SCRAM_ITERATIONS,
+                salt: Sha256::digest(self.username.as_bytes())[..16].to_vec(),
+                server_key: stored_key.clone(),
+                stored_key,
+            }
+        });
+
+        self.server_first = format!(
+            "r={},s={},i={}",
+            self.nonce,
+            encode(&secret.salt),
+            secret.iterations
+        );
+        self.secret = Some(secret);
+        self.server_first.as_bytes().to_vec()
+    }
+
+    // Verifies the client proof and returns the server-final message
+    pub fn verify(
+        &self,
+        client_final: &[u8],
+        channel_binding: Option<&[u8]>,
+    ) -> Result<Vec<u8>, &'static str> {
+        let secret = self
+            .secret
+            .as_ref()
+            .ok_or("Missing server-first message.")?;
+        let client_final =
+            std::str::from_utf8(client_final).map_err(|_| "Invalid client-final message.")?;
+        let (client_final_without_proof, proof) = client_final
+            .rsplit_once(",p=")
+            .ok_or("Missing client proof.")?;
+
+        let mut attributes = client_final_without_proof.split(',');
+        let cbind_data = attributes
+            .next()
+            .and_then(|value| value.strip_prefix("c="))
+            .and_then(|value| base64_decode(value.as_bytes()))
+            .ok_or("Invalid channel binding data.")?;
+        let mut expected_cbind_data = self.gs2_header.as_bytes().to_vec();
+        if self.is_channel_bound {
+            expected_cbind_data
+                .extend_from_slice(channel_binding.ok_or("Channel binding is not available.")?);
+        }
+        if cbind_data != expected_cbind_data {
+            return Err("Channel binding mismatch.");
+        }
+        if attributes.next().and_then(|value| value.strip_prefix("r=")) != Some(&self.nonce) {
+            return Err("Nonce mismatch.");
+        }
+
+        let proof = base64_decode(proof.as_bytes())
+            .filter(|proof| proof.len() == 32)
+            .ok_or("Invalid client proof.")?;
+        let auth_message = format!(
+            "{},{},{}",
+            self.client_first_bare, self.server_first, client_final_without_proof
+        );
+        let client_signature = hmac_sha256(&secret.stored_key, auth_message.as_bytes());
+        let client_key = proof
+            .iter()
+            .zip(client_signature.iter())
+            .map(|(a, b)| a ^ b)
+            .collect::<Vec<_>>();
+
+        // Compare in constant time
+        if Sha256::digest(&client_key)
+            .iter()
+            .zip(secret.stored_key.iter())
+            .fold(0, |acc, (a, b)| acc | (a ^ b))
+            != 0
+        {
+            return Err("Invalid client proof.");
+        }
+
+        Ok(format!(
+            "v={}",
+            encode(&hmac_sha256(&secret.server_key, auth_message.as_bytes()))
+        )
+        .into_bytes())
+    }
+}
+
+fn hmac_sha256(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any size");
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
+
+fn encode(bytes: &[u8]) -> String {
+    String::from_utf8(base64_encode(bytes).unwrap_or_default()).unwrap_or_default()
+}
+
+fn decode_sasl_name(value: &str) -> Option<String> {
+    let mut result = String::with_capacity(value.len());
+    let mut chars = value.split('=');
+    result.push_str(chars.next()?);
+    for part in chars {
+        if let Some(part) = part.strip_prefix("2C") {
+            result.push(',');
+            result.push_str(part);
+        } else if let Some(part) = part.strip_prefix("3D") {
+            result.push('=');
+            result.push_str(part);
+        } else {
+            return None;
+        }
+    }
+    Some(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ScramExchange, ScramSecret};
+
+    #[test]
+    fn scram_sha256_exchange() {
+        // Test vector from RFC 7677
+        let mut exchange = ScramExchange::parse_with_nonce(
+            b"n,,n=user,r=rOprNGfwEbeRWgbNEkqO",
+            false,
+            false,
+            "%hvYDpWUa2RaTCAfuxFIlj)hNlF$k0".to_string(),
+        )
+        .unwrap();
+        assert_eq!(exchange.username, "user");
+
+        let secret = ScramSecret::derive(
+            "pencil",
+            mail_parser::decoders::base64::base64_decode(b"W22ZaJ0SNY7soEsUEjb6gQ==").unwrap(),
+            4096,
+        );
+        assert_eq!(
+            ScramSecret::parse(&secret.to_string()),
+            Some(secret.clone())
+        );
+        assert_eq!(
+            String::from_utf8(exchange.server_first(secret.into())).unwrap(),
+            "r=rOprNGfwEbeRWgbNEkqO%hvYDpWUa2RaTCAfuxFIlj)hNlF$k0,s=W22ZaJ0SNY7soEsUEjb6gQ==,i=4096"
+        );
+        assert_eq!(
+            String::from_utf8(
+                exchange
+                    .verify(
+                        concat!(
+                            "c=biws,r=rOprNGfwEbeRWgbNEkqO%hvYDpWUa2RaTCAfuxFIlj)hNlF$k0,",
+                            "p=dHzbZapWIk4jUhN+Ute9ytag9zjfMHgsqmmiz7AndVQ="
+                        )
+                        .as_bytes(),
+                        None
+                    )
+                    .unwrap()
+            )
+            .unwrap(),
+            "v=6rriTRBi23WpRR/wtup+mMhUZUn/dB5nLTJRsjl95G4="
+        );
+
+        // Wrong proof
+        assert!(exchange
+            .verify(
+                concat!(
+                    "c=biws,r=rOprNGfwEbeRWgbNEkqO%hvYDpWUa2RaTCAfuxFIlj)hNlF$k0,",
+                    "p=AHzbZapWIk4jUhN+Ute9ytag9zjfMHgsqmmiz7AndVQ="
+                )
+                .as_bytes(),
+                None
+            )
+            .is_err());
+
+        // Clients must use channel binding when the server supports it
+        assert!(ScramExchange::parse(b"y,,n=user,r=abc", false, true).is_err());
+        assert!(ScramExchange::parse(b"p=tls-exporter,,n=user,r=abc", false, true).is_err());
+        assert!(ScramExchange::parse(b"p=tls-unique,,n=user,r=abc", true, true).is_err());
+        assert!(ScramExchange::parse(b"p=tls-exporter,,n=user,r=abc", true, true).is_ok());
+        assert_eq!(
+            ScramExchange::parse(b"n,a=us=2Cer,n=us=2Cer,r=abc", false, false)
+                .unwrap()
+                .username,
+            "us,er"
+        );
+        assert!(ScramExchange::parse(b"n,a=admin,n=user,r=abc", false, false).is_err());
+    }
+}
@@ -4,10 +4,10 @@
  * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
  */
 
-use argon2::Argon2;
+use argon2::{Algorithm, Argon2, Params, Version, ARGON2ID_IDENT};
 use mail_builder::encoders::base64::base64_encode;
 use mail_parser::decoders::base64::base64_decode;
-use password_hash::PasswordHash;
+use password_hash::{rand_core::OsRng, PasswordHash, PasswordHasher, SaltString};
 use pbkdf2::Pbkdf2;
 use pwhash::{bcrypt, bsdi_crypt, md5_crypt, sha1_crypt, sha256_crypt, sha512_crypt, unix_crypt};
 use scrypt::Scrypt;
@@ -17,19 +17,119 @@ use sha2::Sha256;
 use sha2::Sha512;
 use tokio::sync::oneshot;
 use totp_rs::TOTP;
+use utils::config::{utils::AsKey, Config};
 
 use crate::backend::internal::PrincipalField;
 use crate::backend::internal::SpecialSecrets;
 use crate::Principal;
 
+// App passwords are stored as "$app$<name>$<scope>$<hash>", where an empty
+// scope grants access to all protocols except HTTP, which has to be granted
+// explicitly as it gives access to the account management API.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AppPassword<'x> {
+    pub name: &'x str,
+    pub scope: Option<&'x str>,
+    pub secret: &'x str,
+}
+
+pub const APP_PASSWORD_SCOPES: &[&str] = &["imap", "smtp", "http"];
+
+// Argon2id parameters used to rehash passwords stored in other formats
+#[derive(Debug, Clone)]
+pub struct SecretRehash {
+    pub params: Params,
+}
+
+impl<'x> AppPassword<'x> {
+    pub fn parse(value: &'x str) -> Option<Self> {
+        let (name, value) = value.strip_prefix("$app$")?.split_once('$')?;
+        let (scope, secret) = value.split_once('$')?;
+
+        Some(AppPassword {
+            name,
+            scope: Some(scope).filter(|s| !s.is_empty()),
+            secret,
+        })
+    }
+
+    // Builds the stored value from either a plain text password, which is hashed,
+    // or an existing hash when `is_hashed` is set
+    pub fn build(
+        name: &str,
+        scope: Option<&str>,
+        password: &str,
+        is_hashed: bool,
+    ) -> Result<String, String> {
+        if name.is_empty() || name.contains('$') {
+            Err(format!("Invalid app password name {name:?}"))
+        } else if scope.map_or(false, |scope| !APP_PASSWORD_SCOPES.contains(&scope)) {
+            Err(format!("Invalid app password scope {:?}", scope.unwrap()))
+        } else if is_hashed {
+            if is_secret_hash(password) {
+                Ok(format!(
+                    "$app${name}${}${password}",
+                    scope.unwrap_or_default()
+                ))
+            } else {
+                Err("App password is not a supported hash".to_string())
+            }
+        } else {
+            sha512_crypt::hash(password)
+                .map(|hash| format!("$app${name}${}${hash}", scope.unwrap_or_default()))
+                .map_err(|err| format!("Failed to hash app password: {err}"))
+        }
+    }
+
+    pub fn is_valid(&self) -> bool {
+        !self.name.is_empty()
+            && self
+                .scope
+                .map_or(true, |scope| APP_PASSWORD_SCOPES.contains(&scope))
+            && is_secret_hash(self.secret)
+    }
+
+    pub fn is_allowed(&self, protocol: &str) -> bool {
+        self.scope
+            .map_or(protocol != "http", |scope| scope == protocol)
+    }
+}
+
 impl Principal {
-    pub async fn verify_secret(&self, mut code: &str) -> trc::Result<bool> {
+    pub async fn verify_app_password(&self, code: &str, protocol: &str) -> trc::Result<bool> {
+        for app_password in self
+            .iter_str(PrincipalField::AppPasswords)
+            .filter_map(|value| AppPassword::parse(value))
+        {
+            if app_password.is_allowed(protocol)
+                && verify_secret_hash(app_password.secret, code).await?
+            {
+                return Ok(true);
+            }
+        }
+
+        Ok(false)
+    }
+
+    pub async fn verify_secret(&self, code: &str) -> trc::Result<bool> {
+        self.verify_secret_match(code)
+            .await
+            .map(|matched| matched.is_some())
+    }
+
+    // Returns the password hash that matched along with the password stripped
+    // of any TOTP token. The hash is empty when an app password was used.
+    pub async fn verify_secret_match<'x>(
+        &'x self,
+        mut code: &'x str,
+    ) -> trc::Result<Option<(&'x str, &'x str)>> {
         let mut totp_token = None;
         let mut is_totp_token_missing = false;
         let mut is_totp_required = false;
         let mut is_totp_verified = false;
         let mut is_authenticated = false;
         let mut is_app_authenticated = false;
+        let mut matched_secret = "";
 
         for secret in self.iter_str(PrincipalField::Secrets) {
             if secret.is_otp_auth() {
@@ -63,13 +163,17 @@ impl Principal {
                         .check_current(totp_token)
                         .unwrap_or(false);
                 }
+            } else if secret.is_scram() {
+                // SCRAM credentials can only be used by the SCRAM mechanisms
+                continue;
             } else if !is_authenticated && !is_app_authenticated {
                 if let Some((_, app_secret)) =
                     secret.strip_prefix("$app$").and_then(|s| s.split_once('$'))
                 {
                     is_app_authenticated = verify_secret_hash(app_secret, code).await?;
-                } else {
-                    is_authenticated = verify_secret_hash(secret, code).await?;
+                } else if verify_secret_hash(secret, code).await? {
+                    is_authenticated = true;
+                    matched_secret = secret;
                 }
             }
         }
@@ -78,7 +182,7 @@ impl Principal {
             if !is_totp_required {
                 // Authenticated without TOTP enabled
 
-                Ok(true)
+                Ok(Some((matched_secret, code)))
             } else if is_totp_token_missing {
                 // Only let the client know if the TOTP code is missing
                 // if the password is correct
@@ -87,23 +191,94 @@ impl Principal {
             } else {
                 // Return the TOTP verification status
 
-                Ok(is_totp_verified)
+                Ok(is_totp_verified.then_some((matched_secret, code)))
             }
         } else if is_app_authenticated {
             // App passwords do not require TOTP
 
-            Ok(true)
+            Ok(Some(("", code)))
         } else {
             if is_totp_verified {
                 // TOTP URL appeared after password hash in secrets list
                 for secret in self.iter_str(PrincipalField::Secrets) {
                     if secret.is_password() && verify_secret_hash(secret, code).await? {
-                        return Ok(true);
+                        return Ok(Some((secret, code)));
                     }
                 }
             }
 
-            Ok(false)
+            Ok(None)
+        }
+    }
+}
+
+impl SecretRehash {
+    pub fn try_from_config(config: &mut Config, prefix: impl AsKey) -> Option<Self> {
+        let prefix = prefix.as_key();
+        if !config
+            .property_or_default((&prefix, "password.rehash"), "false")
+            .unwrap_or(false)
+        {
+            return None;
+        }
+
+        match Params::new(
+            config
+                .property_or_default((&prefix, "password.argon2.memory"), "19456")
+                .unwrap_or(Params::DEFAULT_M_COST),
+            config
+                .property_or_default((&prefix, "password.argon2.iterations"), "2")
+                .unwrap_or(Params::DEFAULT_T_COST),
+            config
+                .property_or_default((&prefix, "password.argon2.parallelism"), "1")
+                .unwrap_or(Params::DEFAULT_P_COST),
+            None,
+        ) {
+            Ok(params) => Some(SecretRehash { params }),
+            Err(err) => {
+                config.new_parse_error(
+                    (&prefix, "password.argon2"),
+                    format!("Invalid Argon2 parameters: {err}"),
+                );
+                None
+            }
+        }
+    }
+
+    // Anything other than an Argon2id hash with the configured parameters
+    pub fn needs_rehash(&self, hashed_secret: &str) -> bool {
+        match PasswordHash::new(hashed_secret) {
+            Ok(hash) if hash.algorithm == ARGON2ID_IDENT => {
+                Params::try_from(&hash).map_or(true, |params| {
+                    params.m_cost() != self.params.m_cost()
+                        || params.t_cost() != self.params.t_cost()
+                        || params.p_cost() != self.params.p_cost()
+                })
+            }
+            _ => true,
+        }
+    }
+
+    pub async fn hash(&self, secret: &str) -> trc::Result<String> {
+        let (tx, rx) = oneshot::channel();
+        let secret = secret.to_string();
+        let params = self.params.clone();
+
+        tokio::task::spawn_blocking(move || {
+            tx.send(
+                Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+                    .hash_password(secret.as_bytes(), &SaltString::generate(&mut OsRng))
+                    .map(|hash| hash.to_string())
+                    .map_err(|err| trc::AuthEvent::Error.reason(err)),
+            )
+            .ok();
+        });
+
+        match rx.await {
+            Ok(result) => result,
+            Err(err) => Err(trc::EventType::Server(trc::ServerEvent::ThreadError)
+                .caused_by(trc::location!())
+                .reason(err)),
         }
     }
 }
@@ -160,6 +335,17 @@ async fn verify_hash_prefix(hashed_secret: &str, secret: &str) -> trc::Result<bo
     }
 }
 
+// Plain text schemes are not considered hashes
+pub fn is_secret_hash(value: &str) -> bool {
+    if let Some(value) = value.strip_prefix('{') {
+        value.split_once('}').map_or(false, |(scheme, hash)| {
+            !hash.is_empty() && !matches!(scheme.to_ascii_uppercase().as_str(), "PLAIN" | "CLEAR")
+        })
+    } else {
+        value.len() > 1 && (value.starts_with('$') || value.starts_with('_'))
+    }
+}
+
 pub async fn verify_secret_hash(hashed_secret: &str, secret: &str) -> trc::Result<bool> {
     if hashed_secret.starts_with('$') {
         verify_hash_prefix(hashed_secret, secret).await
@@ -268,3 +454,74 @@ pub async fn verify_secret_hash(hashed_secret: &str, secret: &str) -> trc::Resul
         Ok(hashed_secret == secret)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use argon2::Params;
+
+    use super::{verify_secret_hash, SecretRehash};
+
+    #[tokio::test]
+    async fn verify_known_hashes() {
+        for hash in [
+            "$argon2id$v=19$m=19456,t=2,p=1$c29tZXNhbHQ$PL01amPyeUuxG7H0vIr5X+qHkZvWnHmGBGXFYvh8z2E",
+            "$argon2id$v=19$m=4096,t=3,p=1$c29tZXNhbHQ$qLml5cbqFAO6YxVHhrSBHP0UWdxrIxkNcM8aMX3blzU",
+            "$argon2i$v=19$m=4096,t=3,p=1$c29tZXNhbHQ$iWh06vD8Fy27wf9npn6FXWiCX4K6pW6Ue1Bnzz07Z8A",
+            "{ARGON2ID}$argon2id$v=19$m=4096,t=3,p=1$c29tZXNhbHQ$qLml5cbqFAO6YxVHhrSBHP0UWdxrIxkNcM8aMX3blzU",
+            "$pbkdf2-sha256$i=1000,l=32$c29tZXNhbHQ$j4Aa14inUtOh7Sg/D7hH54ohymuHNQD4+ccfhepGWAY",
+            "$scrypt$ln=10,r=8,p=1$c29tZXNhbHQ$wdXoWEig5T693O7BJbufEPRk+qarG40BYOh1xe9tMAc",
+            "$2y$05$bvIG6Nmid91Mu9RcmmWZfO5HJIMCT8riNW0hEp8f6/FuA2/mHZFpe",
+            "$6$somesalt$A7P/0Yfu8RprY88D5T1n.xKT749BOn/IXBvmR1gXZzU7imsoTfZhCQ1916CB7WNX9eOOeSmBmmMrl5fQn9LAP1",
+            "$5$somesalt$beBO9e7yYxmTyzRRaLTYwhAcZNPwxJUw.jMoLZT.Zz/",
+            "$1$somesalt$W.KCTbPSiFDGffAGOjcBc.",
+            "{PLAIN}password",
+            "password",
+        ] {
+            assert!(
+                verify_secret_hash(hash, "password").await.unwrap(),
+                "failed for {hash}"
+            );
+            assert!(
+                !verify_secret_hash(hash, "wrong password").await.unwrap(),
+                "failed for {hash}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn rehash_secret() {
+        let rehash = SecretRehash {
+            params: Params::new(19456, 2, 1, None).unwrap(),
+        };
+
+        for (hash, expected) in [
+            (
+                "$argon2id$v=19$m=19456,t=2,p=1$c29tZXNhbHQ$PL01amPyeUuxG7H0vIr5X+qHkZvWnHmGBGXFYvh8z2E",
+                false,
+            ),
+            (
+                "$argon2id$v=19$m=4096,t=3,p=1$c29tZXNhbHQ$qLml5cbqFAO6YxVHhrSBHP0UWdxrIxkNcM8aMX3blzU",
+                true,
+            ),
+            (
+                "$argon2i$v=19$m=19456,t=2,p=1$c29tZXNhbHQ$iWh06vD8Fy27wf9npn6FXWiCX4K6pW6Ue1Bnzz07Z8A",
+                true,
+            ),
+            (
+                "$2y$05$bvIG6Nmid91Mu9RcmmWZfO5HJIMCT8riNW0hEp8f6/FuA2/mHZFpe",
+                true,
+            ),
+            ("password", true),
+        ] {
+            assert_eq!(rehash.needs_rehash(hash), expected, "failed for {hash}");
+        }
+
+        let hash = rehash.hash("password").await.unwrap();
+        assert!(
+            hash.starts_with("$argon2id$v=19$m=19456,t=2,p=1$"),
+            "{hash}"
+        );
+        assert!(!rehash.needs_rehash(&hash));
+        assert!(verify_secret_hash(&hash, "password").await.unwrap());
+    }
+}
@@ -4,7 +4,10 @@
  * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
  */
 
-use core::cache::CachedDirectory;
+use core::{
+    cache::{CachedAuth, CachedDirectory},
+    secret::SecretRehash,
+};
 use std::{fmt::Debug, sync::Arc};
 
 use ahash::AHashMap;
@@ -29,6 +32,9 @@ pub mod core;
 pub struct Directory {
     pub store: DirectoryInner,
     pub cache: Option<CachedDirectory>,
+    pub auth_cache: Option<CachedAuth>,
+    pub rehash: Option<SecretRehash>,
+    pub store_scram: bool,
 }
 
 #[derive(Debug, Default, Clone, PartialEq, Eq)]
@@ -39,7 +45,9 @@ pub struct Principal {
     pub(crate) fields: AHashMap<PrincipalField, PrincipalValue>,
 }
 
-#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[derive(
+    Debug, Default, Clone, Copy, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize,
+)]
 #[serde(rename_all = "camelCase")]
 pub enum Type {
     #[default]
@@ -240,6 +248,22 @@ pub enum Permission {
     SieveRenameScript,
     SieveCheckScript,
     SieveHaveSpace,
+
+    // OAuth
+    OauthIntrospect,
+
+    // IMAP QUOTA
+    ImapGetQuota,
+    ImapSetQuota,
+
+    // Threads
+    ThreadRebuild,
+
+    // Bulk import
+    MessageImport,
+
+    // Submission
+    EmailSendAs,
     // WARNING: add new ids at the end (TODO: use static ids)
 }
 
@@ -271,6 +295,9 @@ impl Default for Directory {
         Self {
             store: DirectoryInner::Internal(Store::None),
             cache: None,
+            auth_cache: None,
+            rehash: None,
+            store_scram: false,
         }
     }
 }
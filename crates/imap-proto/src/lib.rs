@@ -72,6 +72,17 @@ pub enum Command {
 
     // RFC 2971
     Id,
+
+    // RFC 4978
+    Compress,
+
+    // RFC 8508
+    Replace(bool),
+
+    // RFC 9208
+    GetQuota,
+    GetQuotaRoot,
+    SetQuota,
 }
 
 impl Command {
@@ -86,6 +97,7 @@ impl Command {
                 | Command::Expunge(true)
                 | Command::Sort(true)
                 | Command::Thread(true)
+                | Command::Replace(true)
         )
     }
 }
@@ -128,6 +140,7 @@ pub enum ResponseCode {
     ReadOnly,
     ReadWrite,
     ServerBug,
+    TooBig,
     TryCreate,
     UidNext,
     UidNotSticky,
@@ -135,6 +148,9 @@ pub enum ResponseCode {
     Unavailable,
     UnknownCte,
 
+    // RFC 4978
+    CompressionActive,
+
     // CONDSTORE
     Modified {
         ids: Vec<u32>,
@@ -150,6 +166,11 @@ pub enum ResponseCode {
 
     // USEATTR
     UseAttr,
+
+    // RFC 4469
+    BadUrl {
+        url: String,
+    },
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -6,7 +6,7 @@
 
 use crate::{
     protocol::{
-        append::{self, Message},
+        append::{self, CatenatePart, Message},
         Flag, ProtocolVersion,
     },
     receiver::{bad, Request, Token},
@@ -14,13 +14,17 @@ use crate::{
     Command,
 };
 
-use super::parse_datetime;
+use super::parse_datetime_with_offset;
 
 enum State {
     None,
     Flags,
     UTF8,
     UTF8Data,
+    Catenate,
+    CatenateData,
+    CatenateUrl,
+    CatenateText,
 }
 
 impl Request<Command> {
@@ -44,8 +48,10 @@ impl Request<Command> {
                     // Parse flags
                     let mut message = Message {
                         message: vec![],
+                        catenate: vec![],
                         flags: vec![],
                         received_at: None,
+                        received_at_offset: 0,
                     };
                     let mut state = State::None;
                     let mut seen_flags = false;
@@ -59,6 +65,7 @@ impl Request<Command> {
                                         State::Flags
                                     }
                                     State::UTF8 => State::UTF8Data,
+                                    State::Catenate => State::CatenateData,
                                     _ => {
                                         return Err(bad(
                                             self.tag.to_string(),
@@ -68,34 +75,47 @@ impl Request<Command> {
                                 };
                             }
                             Token::ParenthesisClose => match state {
-                                State::None | State::UTF8 => {
-                                    return Err(bad(
-                                        self.tag.to_string(),
-                                        "Invalid closing parenthesis found.",
-                                    ))
-                                }
                                 State::Flags => {
                                     state = State::None;
                                 }
                                 State::UTF8Data => {
                                     break;
                                 }
+                                State::CatenateData if !message.catenate.is_empty() => {
+                                    break;
+                                }
+                                _ => {
+                                    return Err(bad(
+                                        self.tag.to_string(),
+                                        "Invalid closing parenthesis found.",
+                                    ))
+                                }
                             },
                             Token::Argument(value) => match state {
                                 State::None => {
                                     if value.eq_ignore_ascii_case(b"utf8") {
                                         state = State::UTF8;
+                                    } else if value.eq_ignore_ascii_case(b"catenate")
+                                        && matches!(tokens.peek(), Some(Token::ParenthesisOpen))
+                                    {
+                                        state = State::Catenate;
                                     } else if matches!(tokens.peek(), Some(Token::Argument(_)))
                                         && value.len() <= 28
                                         && !value.contains(&b'\n')
                                     {
-                                        if let Ok(date_time) = parse_datetime(&value) {
-                                            message.received_at = Some(date_time);
-                                        } else {
-                                            return Err(bad(
-                                                self.tag.to_string(),
-                                                "Failed to parse received time.",
-                                            ));
+                                        match parse_datetime_with_offset(&value) {
+                                            Ok((date_time, offset)) => {
+                                                // Received times are unsigned, dates before
+                                                // 1970 are stored as the epoch
+                                                message.received_at = Some(date_time.max(0));
+                                                message.received_at_offset = offset;
+                                            }
+                                            Err(_) => {
+                                                return Err(bad(
+                                                    self.tag.to_string(),
+                                                    "Failed to parse received time.",
+                                                ));
+                                            }
                                         }
                                     } else {
                                         message.message = value;
@@ -124,6 +144,36 @@ impl Request<Command> {
                                         ));
                                     }
                                 }
+                                State::Catenate => {
+                                    return Err(bad(
+                                        self.tag.to_string(),
+                                        "Expected parenthesis after CATENATE.",
+                                    ));
+                                }
+                                State::CatenateData => {
+                                    state = if value.eq_ignore_ascii_case(b"url") {
+                                        State::CatenateUrl
+                                    } else if value.eq_ignore_ascii_case(b"text") {
+                                        State::CatenateText
+                                    } else {
+                                        return Err(bad(
+                                            self.tag.to_string(),
+                                            "Expected URL or TEXT catenate part.",
+                                        ));
+                                    };
+                                }
+                                State::CatenateUrl => {
+                                    message.catenate.push(CatenatePart::Url(
+                                        String::from_utf8(value).map_err(|_| {
+                                            bad(self.tag.to_string(), "Invalid URL.")
+                                        })?,
+                                    ));
+                                    state = State::CatenateData;
+                                }
+                                State::CatenateText => {
+                                    message.catenate.push(CatenatePart::Text(value));
+                                    state = State::CatenateData;
+                                }
                             },
                             _ => return Err(bad(self.tag.to_string(), "Invalid arguments.")),
                         }
@@ -147,7 +197,7 @@ mod tests {
 
     use crate::{
         protocol::{
-            append::{self, Message},
+            append::{self, CatenatePart, Message},
             Flag, ProtocolVersion,
         },
         receiver::{Error, Receiver},
@@ -165,8 +215,10 @@ mod tests {
                     mailbox_name: "saved-messages".to_string(),
                     messages: vec![Message {
                         message: vec![b'a'],
+                        catenate: vec![],
                         flags: vec![Flag::Seen],
                         received_at: None,
+                        received_at_offset: 0,
                     }],
                 },
             ),
@@ -177,8 +229,10 @@ mod tests {
                     mailbox_name: "hello world".to_string(),
                     messages: vec![Message {
                         message: vec![b'a'],
+                        catenate: vec![],
                         flags: vec![Flag::Seen, Flag::Draft, Flag::MDNSent],
                         received_at: None,
+                        received_at_offset: 0,
                     }],
                 },
             ),
@@ -189,8 +243,24 @@ mod tests {
                     mailbox_name: "hi".to_string(),
                     messages: vec![Message {
                         message: vec![b'a'],
+                        catenate: vec![],
                         flags: vec![Flag::Junk],
                         received_at: Some(760689784),
+                        received_at_offset: -28800,
+                    }],
+                },
+            ),
+            (
+                "A003 APPEND \"hi\" \"02-Mar-1965 10:00:00 +0100\" {1+}\r\na\r\n",
+                append::Arguments {
+                    tag: "A003".to_string(),
+                    mailbox_name: "hi".to_string(),
+                    messages: vec![Message {
+                        message: vec![b'a'],
+                        catenate: vec![],
+                        flags: vec![],
+                        received_at: Some(0),
+                        received_at_offset: 3600,
                     }],
                 },
             ),
@@ -201,8 +271,10 @@ mod tests {
                     mailbox_name: "hi".to_string(),
                     messages: vec![Message {
                         message: vec![b'a'],
+                        catenate: vec![],
                         flags: vec![],
                         received_at: Some(1668977999),
+                        received_at_offset: 10800,
                     }],
                 },
             ),
@@ -213,8 +285,10 @@ mod tests {
                     mailbox_name: "hi".to_string(),
                     messages: vec![Message {
                         message: vec![b'a'],
+                        catenate: vec![],
                         flags: vec![],
                         received_at: Some(1668977999),
+                        received_at_offset: 10800,
                     }],
                 },
             ),
@@ -225,8 +299,10 @@ mod tests {
                     mailbox_name: "Drafts".to_string(),
                     messages: vec![Message {
                         message: vec![b'h', b'e', b'l', b'l', b'o'],
+                        catenate: vec![],
                         flags: vec![Flag::Draft],
                         received_at: None,
+                        received_at_offset: 0,
                     }],
                 },
             ),
@@ -237,8 +313,31 @@ mod tests {
                     mailbox_name: "Drafts".to_string(),
                     messages: vec![Message {
                         message: vec![b'h', b'e', b'l', b'l', b'o'],
+                        catenate: vec![],
                         flags: vec![Flag::Draft],
                         received_at: Some(1668977999),
+                        received_at_offset: 10800,
+                    }],
+                },
+            ),
+            (
+                concat!(
+                    "A003 APPEND Drafts (\\Draft) CATENATE (URL \"/INBOX;UIDVALIDITY=385759045/;UID=20\" ",
+                    "TEXT {4+}\r\ntext URL \"/INBOX/;UID=21\")\r\n"
+                ),
+                append::Arguments {
+                    tag: "A003".to_string(),
+                    mailbox_name: "Drafts".to_string(),
+                    messages: vec![Message {
+                        message: vec![],
+                        catenate: vec![
+                            CatenatePart::Url("/INBOX;UIDVALIDITY=385759045/;UID=20".to_string()),
+                            CatenatePart::Text(b"text".to_vec()),
+                            CatenatePart::Url("/INBOX/;UID=21".to_string()),
+                        ],
+                        flags: vec![Flag::Draft],
+                        received_at: None,
+                        received_at_offset: 0,
                     }],
                 },
             ),
@@ -299,8 +398,10 @@ mod tests {
                                     )
                                     .as_bytes()
                                     .to_vec(),
+                                    catenate: vec![],
                                     flags: vec![Flag::Seen],
                                     received_at: None,
+                                    received_at_offset: 0,
                                 },
                                 Message {
                                     message: concat!(
@@ -315,8 +416,10 @@ mod tests {
                                     )
                                     .as_bytes()
                                     .to_vec(),
+                                    catenate: vec![],
                                     flags: vec![Flag::Seen],
                                     received_at: Some(760689784),
+                                    received_at_offset: -28800,
                                 }
                             ],
                         },
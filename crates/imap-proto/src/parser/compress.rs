@@ -0,0 +1,70 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use crate::{
+    protocol::compress::{self, Algorithm},
+    receiver::{bad, Request},
+    Command,
+};
+
+impl Request<Command> {
+    pub fn parse_compress(self) -> trc::Result<compress::Arguments> {
+        match self.tokens.len() {
+            1 => {
+                let algorithm = self.tokens.into_iter().next().unwrap().unwrap_bytes();
+                if algorithm.eq_ignore_ascii_case(b"DEFLATE") {
+                    Ok(compress::Arguments {
+                        tag: self.tag,
+                        algorithm: Algorithm::Deflate,
+                    })
+                } else {
+                    Err(bad(
+                        self.tag,
+                        format!(
+                            "Unsupported compression algorithm '{}'.",
+                            String::from_utf8_lossy(&algorithm)
+                        ),
+                    ))
+                }
+            }
+            0 => Err(self.into_error("Missing compression algorithm.")),
+            _ => Err(self.into_error("Too many arguments.")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        protocol::compress::{self, Algorithm},
+        receiver::Receiver,
+    };
+
+    #[test]
+    fn parse_compress() {
+        let mut receiver = Receiver::new();
+
+        assert_eq!(
+            receiver
+                .parse(&mut "t1 COMPRESS DEFLATE\r\n".as_bytes().iter())
+                .unwrap()
+                .parse_compress()
+                .unwrap(),
+            compress::Arguments {
+                tag: "t1".to_string(),
+                algorithm: Algorithm::Deflate,
+            }
+        );
+
+        for command in ["t2 COMPRESS LZMA\r\n", "t3 COMPRESS\r\n"] {
+            assert!(receiver
+                .parse(&mut command.as_bytes().iter())
+                .unwrap()
+                .parse_compress()
+                .is_err());
+        }
+    }
+}
@@ -7,6 +7,7 @@
 pub mod acl;
 pub mod append;
 pub mod authenticate;
+pub mod compress;
 pub mod copy_move;
 pub mod create;
 pub mod delete;
@@ -15,7 +16,9 @@ pub mod fetch;
 pub mod list;
 pub mod login;
 pub mod lsub;
+pub mod quota;
 pub mod rename;
+pub mod replace;
 pub mod search;
 pub mod select;
 pub mod sort;
@@ -77,6 +80,11 @@ impl CommandParser for Command {
             b"MYRIGHTS" => Some(Command::MyRights),
             b"UNAUTHENTICATE" => Some(Command::Unauthenticate),
             b"ID" => Some(Command::Id),
+            b"COMPRESS" => Some(Command::Compress),
+            b"REPLACE" => Some(Command::Replace(uid)),
+            b"GETQUOTA" => Some(Command::GetQuota),
+            b"GETQUOTAROOT" => Some(Command::GetQuotaRoot),
+            b"SETQUOTA" => Some(Command::SetQuota),
             _ => None,
         }
     }
@@ -173,12 +181,16 @@ impl Flag {
 }
 
 pub fn parse_datetime(value: &[u8]) -> Result<i64> {
+    parse_datetime_with_offset(value).map(|(timestamp, _)| timestamp)
+}
+
+pub fn parse_datetime_with_offset(value: &[u8]) -> Result<(i64, i32)> {
     let datetime = std::str::from_utf8(value)
         .map_err(|_| Cow::from("Expected date/time, found an invalid UTF-8 string."))?
         .trim();
     DateTime::parse_from_str(datetime, "%d-%b-%Y %H:%M:%S %z")
         .map_err(|_| Cow::from(format!("Failed to parse date/time '{}'.", datetime)))
-        .map(|dt| dt.timestamp())
+        .map(|dt| (dt.timestamp(), dt.offset().local_minus_utc()))
 }
 
 pub fn parse_date(value: &[u8]) -> Result<i64> {
@@ -0,0 +1,181 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use crate::{
+    protocol::{
+        quota::{self, Resource},
+        ProtocolVersion,
+    },
+    receiver::{bad, Request, Token},
+    utf7::utf7_maybe_decode,
+    Command,
+};
+
+use super::parse_number;
+
+/*
+
+   getquota        = "GETQUOTA" SP quota-root-name
+
+   getquotaroot    = "GETQUOTAROOT" SP mailbox
+
+   setquota        = "SETQUOTA" SP quota-root-name
+                       SP setquota-list
+
+   setquota-list   = "(" [setquota-resource
+                       *(SP setquota-resource)] ")"
+
+   setquota-resource = resource-name SP resource-limit
+
+*/
+
+impl Request<Command> {
+    pub fn parse_quota(self, version: ProtocolVersion) -> trc::Result<quota::Arguments> {
+        let mut tokens = self.tokens.into_iter();
+        let name = tokens
+            .next()
+            .ok_or_else(|| bad(self.tag.to_string(), "Missing quota root or mailbox name."))?
+            .unwrap_string()
+            .map_err(|v| bad(self.tag.to_string(), v))?;
+        let name = if self.command == Command::GetQuotaRoot {
+            utf7_maybe_decode(name, version)
+        } else {
+            name
+        };
+
+        let mut limits = Vec::new();
+        if self.command == Command::SetQuota {
+            if tokens.next() != Some(Token::ParenthesisOpen) {
+                return Err(bad(self.tag, "Expected a list of resource limits."));
+            }
+            loop {
+                match tokens.next() {
+                    Some(Token::ParenthesisClose) => break,
+                    Some(Token::Argument(resource)) => {
+                        let resource = Resource::parse(&resource).ok_or_else(|| {
+                            bad(
+                                self.tag.to_string(),
+                                format!(
+                                    "Unsupported resource {:?}.",
+                                    String::from_utf8_lossy(&resource)
+                                ),
+                            )
+                        })?;
+                        let limit = parse_number::<u64>(
+                            &tokens
+                                .next()
+                                .ok_or_else(|| {
+                                    bad(self.tag.to_string(), "Missing resource limit.")
+                                })?
+                                .unwrap_bytes(),
+                        )
+                        .map_err(|v| bad(self.tag.to_string(), v))?;
+                        limits.push((resource, limit));
+                    }
+                    _ => {
+                        return Err(bad(self.tag, "Invalid resource limit list."));
+                    }
+                }
+            }
+        }
+
+        if tokens.next().is_none() {
+            Ok(quota::Arguments {
+                tag: self.tag,
+                name,
+                limits,
+            })
+        } else {
+            Err(bad(self.tag, "Too many arguments."))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        protocol::{
+            quota::{self, Resource},
+            ProtocolVersion,
+        },
+        receiver::Receiver,
+    };
+
+    #[test]
+    fn parse_quota() {
+        let mut receiver = Receiver::new();
+
+        for (command, arguments) in [
+            (
+                "A003 GETQUOTA \"\"\r\n",
+                quota::Arguments {
+                    tag: "A003".to_string(),
+                    name: "".to_string(),
+                    limits: vec![],
+                },
+            ),
+            (
+                "A004 GETQUOTAROOT INBOX\r\n",
+                quota::Arguments {
+                    tag: "A004".to_string(),
+                    name: "INBOX".to_string(),
+                    limits: vec![],
+                },
+            ),
+            (
+                "A005 SETQUOTA \"jane\" (STORAGE 512)\r\n",
+                quota::Arguments {
+                    tag: "A005".to_string(),
+                    name: "jane".to_string(),
+                    limits: vec![(Resource::Storage, 512)],
+                },
+            ),
+            (
+                "A006 SETQUOTA \"\" (storage 100 MESSAGE 20)\r\n",
+                quota::Arguments {
+                    tag: "A006".to_string(),
+                    name: "".to_string(),
+                    limits: vec![(Resource::Storage, 100), (Resource::Message, 20)],
+                },
+            ),
+            (
+                "A007 SETQUOTA \"\" ()\r\n",
+                quota::Arguments {
+                    tag: "A007".to_string(),
+                    name: "".to_string(),
+                    limits: vec![],
+                },
+            ),
+        ] {
+            assert_eq!(
+                receiver
+                    .parse(&mut command.as_bytes().iter())
+                    .unwrap()
+                    .parse_quota(ProtocolVersion::Rev1)
+                    .unwrap(),
+                arguments,
+                "{:?}",
+                command
+            );
+        }
+
+        for command in [
+            "A008 SETQUOTA \"\" (STORAGE)\r\n",
+            "A009 SETQUOTA \"\" (FOOBAR 10)\r\n",
+            "A010 SETQUOTA \"\" STORAGE 10\r\n",
+            "A011 GETQUOTA\r\n",
+        ] {
+            assert!(
+                receiver
+                    .parse(&mut command.as_bytes().iter())
+                    .unwrap()
+                    .parse_quota(ProtocolVersion::Rev1)
+                    .is_err(),
+                "{command}"
+            );
+        }
+    }
+}
@@ -0,0 +1,99 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use crate::{
+    protocol::{replace, ProtocolVersion, Sequence},
+    receiver::{bad, Request},
+    Command,
+};
+
+use super::parse_sequence_set;
+
+impl Request<Command> {
+    pub fn parse_replace(self, version: ProtocolVersion) -> trc::Result<replace::Arguments> {
+        if self.tokens.len() > 2 {
+            let mut tokens = self.tokens.into_iter();
+
+            // Only a single message can be replaced
+            let sequence = parse_sequence_set(&tokens.next().unwrap().unwrap_bytes())
+                .map_err(|v| bad(self.tag.to_string(), v))?;
+            if !matches!(sequence, Sequence::Number { .. }) {
+                return Err(bad(self.tag, "Expected a single message number."));
+            }
+
+            // The remaining arguments follow the APPEND syntax
+            let mut arguments = Request {
+                tag: self.tag,
+                command: Command::Append,
+                tokens: tokens.collect(),
+            }
+            .parse_append(version)?;
+            if arguments.messages.len() != 1 {
+                return Err(bad(arguments.tag, "Expected a single message."));
+            }
+
+            Ok(replace::Arguments {
+                sequence,
+                mailbox_name: arguments.mailbox_name,
+                message: arguments.messages.pop().unwrap(),
+                tag: arguments.tag,
+            })
+        } else {
+            Err(self.into_error("Missing arguments."))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        protocol::{append::Message, replace, Flag, ProtocolVersion, Sequence},
+        receiver::Receiver,
+    };
+
+    #[test]
+    fn parse_replace() {
+        let mut receiver = Receiver::new();
+
+        assert_eq!(
+            receiver
+                .parse(
+                    &mut "A003 REPLACE 4 Drafts (\\Seen \\Draft) {1+}\r\na\r\n"
+                        .as_bytes()
+                        .iter()
+                )
+                .unwrap()
+                .parse_replace(ProtocolVersion::Rev1)
+                .unwrap(),
+            replace::Arguments {
+                tag: "A003".to_string(),
+                sequence: Sequence::Number { value: 4 },
+                mailbox_name: "Drafts".to_string(),
+                message: Message {
+                    message: vec![b'a'],
+                    catenate: vec![],
+                    flags: vec![Flag::Seen, Flag::Draft],
+                    received_at: None,
+                    received_at_offset: 0,
+                },
+            }
+        );
+
+        for command in [
+            "A004 UID REPLACE 1:3 Drafts {1+}\r\na\r\n",
+            "A005 REPLACE 1 Drafts {1+}\r\na {1+}\r\nb\r\n",
+        ] {
+            assert!(
+                receiver
+                    .parse(&mut command.as_bytes().iter())
+                    .unwrap()
+                    .parse_replace(ProtocolVersion::Rev1)
+                    .is_err(),
+                "{command}"
+            );
+        }
+    }
+}
@@ -100,6 +100,7 @@ pub fn parse_filters(
     let mut filters_len = 0;
     let mut filters_stack = Vec::new();
     let mut operator = Filter::And;
+    let mut is_fuzzy = false;
 
     while let Some(token) = tokens.next() {
         let mut found_parenthesis = false;
@@ -312,6 +313,9 @@ pub fn parse_filters(
                             .ok_or_else(|| Cow::from("Expected an THREADID value."))?
                             .unwrap_string()?,
                     ));
+                } else if value.eq_ignore_ascii_case(b"FUZZY") {
+                    is_fuzzy = true;
+                    continue;
                 } else if value.eq_ignore_ascii_case(b"OR") {
                     if filters_stack.len() > 10 {
                         return Err(Cow::from("Too many nested filters"));
@@ -336,6 +340,13 @@ pub fn parse_filters(
                     filters.push(Filter::Sequence(parse_sequence_set(&value)?, false));
                 }
 
+                if is_fuzzy {
+                    is_fuzzy = false;
+                    if let Some(filter) = filters.pop() {
+                        filters.push(filter.into_fuzzy());
+                    }
+                }
+
                 filters_len += 1;
             }
             Token::ParenthesisOpen => {
@@ -741,6 +752,22 @@ mod tests {
                     sort: None,
                 },
             ),
+            (
+                b"6 SEARCH OR FUZZY SUBJECT coffee FROM joe FUZZY SEEN\r\n".to_vec(),
+                search::Arguments {
+                    tag: "6".to_string(),
+                    result_options: vec![],
+                    filter: vec![
+                        Filter::Or,
+                        Filter::Fuzzy(Box::new(Filter::Subject("coffee".to_string()))),
+                        Filter::From("joe".to_string()),
+                        Filter::End,
+                        Filter::Seen,
+                    ],
+                    is_esearch: true,
+                    sort: None,
+                },
+            ),
         ] {
             let command_str = String::from_utf8_lossy(&command).into_owned();
             assert_eq!(
@@ -82,6 +82,8 @@ impl Status {
             Ok(Self::Deleted)
         } else if value.eq_ignore_ascii_case(b"size") {
             Ok(Self::Size)
+        } else if value.eq_ignore_ascii_case(b"deleted-storage") {
+            Ok(Self::DeletedStorage)
         } else if value.eq_ignore_ascii_case(b"highestmodseq") {
             Ok(Self::HighestModSeq)
         } else if value.eq_ignore_ascii_case(b"mailboxid") {
@@ -125,5 +127,26 @@ mod tests {
                 items: vec![status::Status::UidNext, status::Status::Messages],
             }
         );
+
+        assert_eq!(
+            receiver
+                .parse(
+                    &mut "A043 STATUS INBOX (SIZE DELETED DELETED-STORAGE)\r\n"
+                        .as_bytes()
+                        .iter()
+                )
+                .unwrap()
+                .parse_status(ProtocolVersion::Rev2)
+                .unwrap(),
+            status::Arguments {
+                tag: "A043".to_string(),
+                mailbox_name: "INBOX".to_string(),
+                items: vec![
+                    status::Status::Size,
+                    status::Status::Deleted,
+                    status::Status::DeletedStorage
+                ],
+            }
+        );
     }
 }
@@ -16,6 +16,129 @@ pub struct Arguments {
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Message {
     pub message: Vec<u8>,
+    pub catenate: Vec<CatenatePart>,
     pub flags: Vec<Flag>,
     pub received_at: Option<i64>,
+    pub received_at_offset: i32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CatenatePart {
+    Text(Vec<u8>),
+    Url(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImapUrl {
+    pub mailbox_name: String,
+    pub uid_validity: Option<u32>,
+    pub uid: u32,
+}
+
+impl ImapUrl {
+    // Parses an IMAP URL (RFC 5092) referring to a whole message, either
+    // as an absolute path or including the server part.
+    pub fn parse(url: &str) -> Option<Self> {
+        let path = if url
+            .get(..7)
+            .is_some_and(|scheme| scheme.eq_ignore_ascii_case("imap://"))
+        {
+            &url[7 + url[7..].find('/')?..]
+        } else {
+            url
+        };
+
+        // Mailbox names are percent-encoded and never contain ';'
+        let mut parts = path.strip_prefix('/')?.split("/;");
+        let mailbox_name = parts.next()?;
+        let (mailbox_name, uid_validity) = match mailbox_name.split_once(';') {
+            Some((mailbox_name, uid_validity)) => (
+                mailbox_name,
+                Some(strip_param(uid_validity, "UIDVALIDITY=")?.parse().ok()?),
+            ),
+            None => (mailbox_name, None),
+        };
+        let uid = strip_param(parts.next()?, "UID=")?.parse().ok()?;
+
+        // Sections, partial fetches and URLAUTH are not supported
+        if parts.next().is_none() && !mailbox_name.is_empty() {
+            Some(ImapUrl {
+                mailbox_name: percent_decode(mailbox_name)?,
+                uid_validity,
+                uid,
+            })
+        } else {
+            None
+        }
+    }
+}
+
+fn strip_param<'x>(value: &'x str, name: &str) -> Option<&'x str> {
+    value
+        .get(..name.len())
+        .filter(|prefix| prefix.eq_ignore_ascii_case(name))
+        .map(|_| &value[name.len()..])
+}
+
+fn percent_decode(value: &str) -> Option<String> {
+    let mut result = Vec::with_capacity(value.len());
+    let mut bytes = value.bytes();
+    while let Some(ch) = bytes.next() {
+        if ch == b'%' {
+            let hi = (bytes.next()? as char).to_digit(16)?;
+            let lo = (bytes.next()? as char).to_digit(16)?;
+            result.push((hi << 4 | lo) as u8);
+        } else {
+            result.push(ch);
+        }
+    }
+    String::from_utf8(result).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::protocol::append::ImapUrl;
+
+    #[test]
+    fn parse_imap_url() {
+        for (url, expected) in [
+            (
+                "/INBOX;UIDVALIDITY=385759045/;UID=20",
+                Some(ImapUrl {
+                    mailbox_name: "INBOX".to_string(),
+                    uid_validity: Some(385759045),
+                    uid: 20,
+                }),
+            ),
+            (
+                "imap://joe@example.com/Lists/Sent%20Items/;uid=3",
+                Some(ImapUrl {
+                    mailbox_name: "Lists/Sent Items".to_string(),
+                    uid_validity: None,
+                    uid: 3,
+                }),
+            ),
+            (
+                "/%E6%97%A5%E6%9C%AC;UIDVALIDITY=1/;UID=7",
+                Some(ImapUrl {
+                    mailbox_name: "日本".to_string(),
+                    uid_validity: Some(1),
+                    uid: 7,
+                }),
+            ),
+            ("INBOX/;UID=20", None),
+            ("/INBOX", None),
+            ("/;UID=20", None),
+            ("/INBOX/;UID=abc", None),
+            ("/INBOX/;UID=20/;SECTION=1.2", None),
+            (
+                "/INBOX;UIDVALIDITY=1/;UID=20;EXPIRE=2025-01-01T00:00:00Z",
+                None,
+            ),
+            ("/INBOX%2/;UID=20", None),
+            ("imap://example.com", None),
+        ] {
+            assert_eq!(ImapUrl::parse(url), expected, "{url}");
+        }
+    }
 }
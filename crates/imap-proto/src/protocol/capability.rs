@@ -47,6 +47,13 @@ pub enum Capability {
     ObjectId,
     Preview,
     Utf8Accept,
+    CompressDeflate, //COMPRESS=DEFLATE
+    Replace,
+    Quota,
+    QuotaResStorage, //QUOTA=RES-STORAGE
+    QuotaSet,
+    Catenate,
+    SearchFuzzy, //SEARCH=FUZZY
     Auth(Mechanism),
 }
 
@@ -92,6 +99,13 @@ impl Capability {
             Capability::CreateSpecialUse => b"CREATE-SPECIAL-USE",
             Capability::Move => b"MOVE",
             Capability::Utf8Accept => b"UTF8=ACCEPT",
+            Capability::CompressDeflate => b"COMPRESS=DEFLATE",
+            Capability::Replace => b"REPLACE",
+            Capability::Quota => b"QUOTA",
+            Capability::QuotaResStorage => b"QUOTA=RES-STORAGE",
+            Capability::QuotaSet => b"QUOTASET",
+            Capability::Catenate => b"CATENATE",
+            Capability::SearchFuzzy => b"SEARCH=FUZZY",
         });
     }
 
@@ -129,10 +143,16 @@ impl Capability {
                 Capability::Move,
                 Capability::CondStore,
                 Capability::QResync,
-                Capability::UnAuthenticate,
                 Capability::StatusSize,
                 Capability::ObjectId,
                 Capability::Preview,
+                Capability::CompressDeflate,
+                Capability::Replace,
+                Capability::Quota,
+                Capability::QuotaResStorage,
+                Capability::QuotaSet,
+                Capability::Catenate,
+                Capability::SearchFuzzy,
             ]);
         } else {
             capabilities.extend([
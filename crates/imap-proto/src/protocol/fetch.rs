@@ -10,7 +10,7 @@ use mail_parser::DateTime;
 
 use super::{
     literal_string, quoted_or_literal_string, quoted_or_literal_string_or_nil,
-    quoted_rfc2822_or_nil, quoted_timestamp, Flag, ImapResponse, Sequence,
+    quoted_rfc2822_or_nil, quoted_timestamp_with_offset, Flag, ImapResponse, Sequence,
 };
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -105,6 +105,7 @@ pub enum DataItem<'x> {
     },
     InternalDate {
         date: i64,
+        offset: i32,
     },
     Uid {
         uid: u32,
@@ -797,9 +798,9 @@ impl<'x> DataItem<'x> {
                 }
                 buf.push(b')');
             }
-            DataItem::InternalDate { date } => {
+            DataItem::InternalDate { date, offset } => {
                 buf.extend_from_slice(b"INTERNALDATE ");
-                quoted_timestamp(buf, *date);
+                quoted_timestamp_with_offset(buf, *date, *offset);
             }
             DataItem::Uid { uid } => {
                 buf.extend_from_slice(b"UID ");
@@ -1355,9 +1356,19 @@ mod tests {
                 "FLAGS (\\Seen)",
             ),
             (
-                super::DataItem::InternalDate { date: 482374938 },
+                super::DataItem::InternalDate {
+                    date: 482374938,
+                    offset: 0,
+                },
                 "INTERNALDATE \"15-Apr-1985 01:02:18 +0000\"",
             ),
+            (
+                super::DataItem::InternalDate {
+                    date: 482374938,
+                    offset: -28800,
+                },
+                "INTERNALDATE \"14-Apr-1985 17:02:18 -0800\"",
+            ),
         ] {
             let mut buf = Vec::with_capacity(100);
 
@@ -162,6 +162,23 @@ impl TryFrom<&str> for Attribute {
     }
 }
 
+impl Attribute {
+    // Parses a special-use attribute name such as "\Sent" (RFC 6154)
+    pub fn parse_special_use(value: &str) -> Option<Self> {
+        let value = value.strip_prefix('\\')?;
+        [
+            ("Archive", Attribute::Archive),
+            ("Drafts", Attribute::Drafts),
+            ("Junk", Attribute::Junk),
+            ("Sent", Attribute::Sent),
+            ("Trash", Attribute::Trash),
+            ("Important", Attribute::Important),
+        ]
+        .into_iter()
+        .find_map(|(name, attribute)| value.eq_ignore_ascii_case(name).then_some(attribute))
+    }
+}
+
 impl ChildInfo {
     pub fn serialize(&self, buf: &mut Vec<u8>) {
         buf.push(b'\"');
@@ -7,7 +7,7 @@
 use std::{cmp::Ordering, fmt::Display};
 
 use ahash::AHashSet;
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, FixedOffset, Utc};
 use jmap_proto::types::keyword::Keyword;
 
 use crate::{Command, ResponseCode, ResponseType, StatusResponse};
@@ -16,6 +16,7 @@ pub mod acl;
 pub mod append;
 pub mod authenticate;
 pub mod capability;
+pub mod compress;
 pub mod copy_move;
 pub mod create;
 pub mod delete;
@@ -25,7 +26,9 @@ pub mod fetch;
 pub mod list;
 pub mod login;
 pub mod namespace;
+pub mod quota;
 pub mod rename;
+pub mod replace;
 pub mod search;
 pub mod select;
 pub mod status;
@@ -210,6 +213,23 @@ pub fn quoted_timestamp(buf: &mut Vec<u8>, timestamp: i64) {
     buf.push(b'"');
 }
 
+pub fn quoted_timestamp_with_offset(buf: &mut Vec<u8>, timestamp: i64, offset: i32) {
+    let timestamp = DateTime::<Utc>::from_timestamp(timestamp, 0).unwrap_or_default();
+    let timestamp = match FixedOffset::east_opt(offset) {
+        Some(offset) => timestamp.with_timezone(&offset),
+        None => timestamp.fixed_offset(),
+    };
+
+    buf.push(b'"');
+    buf.extend_from_slice(
+        timestamp
+            .format("%d-%b-%Y %H:%M:%S %z")
+            .to_string()
+            .as_bytes(),
+    );
+    buf.push(b'"');
+}
+
 pub fn quoted_rfc2822(buf: &mut Vec<u8>, timestamp: &mail_parser::DateTime) {
     buf.push(b'"');
     buf.extend_from_slice(timestamp.to_rfc822().as_bytes());
@@ -356,6 +376,7 @@ impl ResponseCode {
             ResponseCode::ReadOnly => b"READ-ONLY",
             ResponseCode::ReadWrite => b"READ-WRITE",
             ResponseCode::ServerBug => b"SERVERBUG",
+            ResponseCode::TooBig => b"TOOBIG",
             ResponseCode::TryCreate => b"TRYCREATE",
             ResponseCode::UidNext => b"UIDNEXT",
             ResponseCode::UidNotSticky => b"UIDNOTSTICKY",
@@ -379,6 +400,12 @@ impl ResponseCode {
                 return;
             }
             ResponseCode::UseAttr => b"USEATTR",
+            ResponseCode::CompressionActive => b"COMPRESSIONACTIVE",
+            ResponseCode::BadUrl { url } => {
+                buf.extend_from_slice(b"BADURL ");
+                quoted_string(buf, url);
+                return;
+            }
         });
     }
 
@@ -412,6 +439,7 @@ impl ResponseCode {
             ResponseCode::ReadOnly => "READ-ONLY",
             ResponseCode::ReadWrite => "READ-WRITE",
             ResponseCode::ServerBug => "SERVERBUG",
+            ResponseCode::TooBig => "TOOBIG",
             ResponseCode::TryCreate => "TRYCREATE",
             ResponseCode::UidNext => "UIDNEXT",
             ResponseCode::UidNotSticky => "UIDNOTSTICKY",
@@ -422,6 +450,8 @@ impl ResponseCode {
             ResponseCode::MailboxId { .. } => "MAILBOXID",
             ResponseCode::HighestModseq { .. } => "HIGHESTMODSEQ",
             ResponseCode::UseAttr => "USEATTR",
+            ResponseCode::CompressionActive => "COMPRESSIONACTIVE",
+            ResponseCode::BadUrl { .. } => "BADURL",
         }
     }
 }
@@ -444,7 +474,15 @@ impl ResponseType {
 
 impl From<ResponseCode> for trc::Value {
     fn from(value: ResponseCode) -> Self {
-        trc::Value::Static(value.as_str())
+        match value {
+            // Codes with arguments are stored already serialized
+            ResponseCode::BadUrl { .. } => {
+                let mut buf = Vec::with_capacity(32);
+                value.serialize(&mut buf);
+                trc::Value::String(String::from_utf8(buf).unwrap_or_default())
+            }
+            _ => trc::Value::Static(value.as_str()),
+        }
     }
 }
 
@@ -505,6 +543,9 @@ impl SerializeResponse for trc::Error {
                     Some(ResponseCode::OverQuota.as_str())
                 }
                 trc::EventType::Limit(_) => Some(ResponseCode::Limit.as_str()),
+                trc::EventType::Auth(trc::AuthEvent::DirectoryUnavailable) => {
+                    Some(ResponseCode::Unavailable.as_str())
+                }
                 trc::EventType::Auth(_) => Some(ResponseCode::AuthenticationFailed.as_str()),
                 trc::EventType::Security(_) => Some(ResponseCode::AuthorizationFailed.as_str()),
                 _ => None,
@@ -611,6 +652,12 @@ impl Display for Command {
             Command::MyRights => write!(f, "MYRIGHTS"),
             Command::Unauthenticate => write!(f, "UNAUTHENTICATE"),
             Command::Id => write!(f, "ID"),
+            Command::Compress => write!(f, "COMPRESS"),
+            Command::Replace(false) => write!(f, "REPLACE"),
+            Command::Replace(true) => write!(f, "UID REPLACE"),
+            Command::GetQuota => write!(f, "GETQUOTA"),
+            Command::GetQuotaRoot => write!(f, "GETQUOTAROOT"),
+            Command::SetQuota => write!(f, "SETQUOTA"),
         }
     }
 }
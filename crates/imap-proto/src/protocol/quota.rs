@@ -0,0 +1,157 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use crate::utf7::utf7_encode;
+
+use super::quoted_string;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Arguments {
+    pub tag: String,
+    pub name: String,
+    pub limits: Vec<(Resource, u64)>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resource {
+    Storage,
+    Message,
+    Mailbox,
+    AnnotationStorage,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QuotaResponse {
+    pub quota_root: String,
+    pub resources: Vec<ResourceUsage>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResourceUsage {
+    pub resource: Resource,
+    pub usage: u64,
+    pub limit: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QuotaRootResponse {
+    pub mailbox_name: String,
+    pub quotas: Vec<QuotaResponse>,
+}
+
+impl QuotaResponse {
+    pub fn serialize(&self, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(b"* QUOTA ");
+        quoted_string(buf, &self.quota_root);
+        buf.extend_from_slice(b" (");
+        for (pos, resource) in self.resources.iter().enumerate() {
+            if pos > 0 {
+                buf.push(b' ');
+            }
+            buf.extend_from_slice(resource.resource.as_str().as_bytes());
+            buf.extend_from_slice(format!(" {} {}", resource.usage, resource.limit).as_bytes());
+        }
+        buf.extend_from_slice(b")\r\n");
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(32 + self.quota_root.len());
+        self.serialize(&mut buf);
+        buf
+    }
+}
+
+impl QuotaRootResponse {
+    pub fn into_bytes(self, is_rev2: bool) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(self.mailbox_name.len() + 64 * self.quotas.len());
+        buf.extend_from_slice(b"* QUOTAROOT ");
+        if is_rev2 {
+            quoted_string(&mut buf, &self.mailbox_name);
+        } else {
+            quoted_string(&mut buf, &utf7_encode(&self.mailbox_name));
+        }
+        for quota in &self.quotas {
+            buf.push(b' ');
+            quoted_string(&mut buf, &quota.quota_root);
+        }
+        buf.extend_from_slice(b"\r\n");
+        for quota in &self.quotas {
+            quota.serialize(&mut buf);
+        }
+        buf
+    }
+}
+
+impl Resource {
+    pub fn parse(value: &[u8]) -> Option<Self> {
+        if value.eq_ignore_ascii_case(b"STORAGE") {
+            Some(Resource::Storage)
+        } else if value.eq_ignore_ascii_case(b"MESSAGE") {
+            Some(Resource::Message)
+        } else if value.eq_ignore_ascii_case(b"MAILBOX") {
+            Some(Resource::Mailbox)
+        } else if value.eq_ignore_ascii_case(b"ANNOTATION-STORAGE") {
+            Some(Resource::AnnotationStorage)
+        } else {
+            None
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Resource::Storage => "STORAGE",
+            Resource::Message => "MESSAGE",
+            Resource::Mailbox => "MAILBOX",
+            Resource::AnnotationStorage => "ANNOTATION-STORAGE",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::protocol::quota::{QuotaResponse, QuotaRootResponse, Resource, ResourceUsage};
+
+    #[test]
+    fn serialize_quota() {
+        let quota = QuotaResponse {
+            quota_root: "".to_string(),
+            resources: vec![ResourceUsage {
+                resource: Resource::Storage,
+                usage: 10,
+                limit: 512,
+            }],
+        };
+
+        assert_eq!(
+            String::from_utf8(quota.clone().into_bytes()).unwrap(),
+            "* QUOTA \"\" (STORAGE 10 512)\r\n"
+        );
+
+        assert_eq!(
+            String::from_utf8(
+                QuotaRootResponse {
+                    mailbox_name: "INBOX".to_string(),
+                    quotas: vec![quota],
+                }
+                .into_bytes(true)
+            )
+            .unwrap(),
+            "* QUOTAROOT \"INBOX\" \"\"\r\n* QUOTA \"\" (STORAGE 10 512)\r\n"
+        );
+
+        assert_eq!(
+            String::from_utf8(
+                QuotaResponse {
+                    quota_root: "jane".to_string(),
+                    resources: vec![],
+                }
+                .into_bytes()
+            )
+            .unwrap(),
+            "* QUOTA \"jane\" ()\r\n"
+        );
+    }
+}
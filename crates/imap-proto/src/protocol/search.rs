@@ -112,6 +112,9 @@ pub enum Filter {
     // RFC 8474 - ObjectID
     EmailId(String),
     ThreadId(String),
+
+    // RFC 6203 - FUZZY
+    Fuzzy(Box<Filter>),
 }
 
 impl FilterItem for Filter {
@@ -124,7 +127,8 @@ impl FilterItem for Filter {
             | Filter::Subject(_)
             | Filter::Body(_)
             | Filter::Text(_)
-            | Filter::Header(_, _) => FilterType::Fts,
+            | Filter::Header(_, _)
+            | Filter::Fuzzy(_) => FilterType::Fts,
             Filter::And => FilterType::And,
             Filter::Or => FilterType::Or,
             Filter::Not => FilterType::Not,
@@ -162,6 +166,14 @@ impl Filter {
     pub fn seq_range(start: Option<u32>, end: Option<u32>) -> Filter {
         Filter::Sequence(Sequence::Range { start, end }, false)
     }
+
+    // Only text searches support fuzzy matching, other keys are matched exactly
+    pub fn into_fuzzy(self) -> Filter {
+        match self {
+            Filter::Body(_) | Filter::Subject(_) | Filter::Text(_) => Filter::Fuzzy(Box::new(self)),
+            filter => filter,
+        }
+    }
 }
 
 impl Response {
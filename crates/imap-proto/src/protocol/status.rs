@@ -22,6 +22,7 @@ pub enum Status {
     UidValidity,
     Unseen,
     Deleted,
+    DeletedStorage,
     Size,
     Recent,
     HighestModSeq,
@@ -60,6 +61,7 @@ impl StatusItem {
                 Status::UidValidity => b"UIDVALIDITY ",
                 Status::Unseen => b"UNSEEN ",
                 Status::Deleted => b"DELETED ",
+                Status::DeletedStorage => b"DELETED-STORAGE ",
                 Status::Size => b"SIZE ",
                 Status::HighestModSeq => b"HIGHESTMODSEQ ",
                 Status::MailboxId => b"MAILBOXID ",
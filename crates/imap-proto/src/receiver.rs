@@ -67,6 +67,7 @@ pub struct Receiver<T: CommandParser> {
     pub request: Request<T>,
     pub state: State,
     pub max_request_size: usize,
+    pub max_literal_size: usize,
     pub current_request_size: usize,
     pub start_state: State,
 }
@@ -88,24 +89,48 @@ impl<T: CommandParser> Receiver<T> {
     pub fn with_max_request_size(max_request_size: usize) -> Self {
         Receiver {
             max_request_size,
+            max_literal_size: max_request_size,
             ..Default::default()
         }
     }
 
+    pub fn with_max_literal_size(mut self, max_literal_size: usize) -> Self {
+        self.max_literal_size = max_literal_size;
+        self
+    }
+
     pub fn error_reset(&mut self, message: impl Into<trc::Value>) -> Error {
+        let tag = self.reset();
+        Error::err(tag, message)
+    }
+
+    fn error_too_big(&mut self) -> Error {
+        let tag = self.reset();
+        Error::Error {
+            response: trc::ImapEvent::Error
+                .ctx(
+                    trc::Key::Details,
+                    format!(
+                        "Literal exceeds the maximum message size of {} bytes.",
+                        self.max_literal_size
+                    ),
+                )
+                .ctx_opt(trc::Key::Id, tag)
+                .ctx(trc::Key::Type, ResponseType::No)
+                .code(ResponseCode::TooBig),
+        }
+    }
+
+    fn reset(&mut self) -> Option<String> {
         let request = std::mem::take(&mut self.request);
-        let err = Error::err(
-            if !request.tag.is_empty() {
-                request.tag.into()
-            } else {
-                None
-            },
-            message,
-        );
         self.buf = Vec::with_capacity(10);
         self.state = self.start_state;
         self.current_request_size = 0;
-        err
+        if !request.tag.is_empty() {
+            request.tag.into()
+        } else {
+            None
+        }
     }
 
     fn push_argument(&mut self, in_quote: bool) -> Result<(), Error> {
@@ -313,6 +338,9 @@ impl<T: CommandParser> Receiver<T> {
                                     .map_err(|_| {
                                     self.error_reset("Literal size is not a valid number.")
                                 })?;
+                                if size as usize > self.max_literal_size {
+                                    return Err(self.error_too_big());
+                                }
                                 if self.current_request_size + size as usize > self.max_request_size
                                 {
                                     return Err(self.error_reset(format!(
@@ -481,6 +509,7 @@ impl<T: CommandParser> Default for Receiver<T> {
             state: State::Start,
             start_state: State::Start,
             max_request_size: 25 * 1024 * 1024,
+            max_literal_size: 25 * 1024 * 1024,
             current_request_size: 0,
         }
     }
@@ -9,7 +9,7 @@ use std::{iter::Peekable, sync::Arc, vec::IntoIter};
 use common::listener::{limiter::ConcurrencyLimiter, SessionResult, SessionStream};
 use imap_proto::{
     receiver::{self, Request},
-    Command, ResponseType, StatusResponse,
+    Command, ResponseCode, ResponseType, StatusResponse,
 };
 use jmap::auth::rate_limit::ConcurrencyLimiters;
 
@@ -27,10 +27,17 @@ impl<T: SessionStream> Session<T> {
         let mut bytes = bytes.iter();
         let mut requests = Vec::with_capacity(2);
         let mut needs_literal = None;
+        let mut compress_input = None;
 
         loop {
             match self.receiver.parse(&mut bytes) {
                 Ok(request) => match self.is_allowed(request).await {
+                    Ok(request) if request.command == Command::Compress => {
+                        // Anything pipelined after COMPRESS is already compressed
+                        requests.push(request);
+                        compress_input = Some(bytes.as_slice());
+                        break;
+                    }
                     Ok(request) => {
                         requests.push(request);
                     }
@@ -119,6 +126,10 @@ impl<T: SessionStream> Session<T> {
                     .handle_copy_move(request, true, is_uid)
                     .await
                     .map(|_| SessionResult::Continue),
+                Command::Replace(is_uid) => self
+                    .handle_replace(request, is_uid)
+                    .await
+                    .map(|_| SessionResult::Continue),
                 Command::Sort(is_uid) => self
                     .handle_search(request, true, is_uid)
                     .await
@@ -199,6 +210,18 @@ impl<T: SessionStream> Session<T> {
                     .handle_my_rights(request)
                     .await
                     .map(|_| SessionResult::Continue),
+                Command::GetQuota => self
+                    .handle_get_quota(request)
+                    .await
+                    .map(|_| SessionResult::Continue),
+                Command::GetQuotaRoot => self
+                    .handle_get_quota_root(request)
+                    .await
+                    .map(|_| SessionResult::Continue),
+                Command::SetQuota => self
+                    .handle_set_quota(request)
+                    .await
+                    .map(|_| SessionResult::Continue),
                 Command::Unauthenticate => self
                     .handle_unauthenticate(request)
                     .await
@@ -207,10 +230,18 @@ impl<T: SessionStream> Session<T> {
                     .handle_id(request)
                     .await
                     .map(|_| SessionResult::Continue),
+                Command::Compress => self
+                    .handle_compress(request)
+                    .await
+                    .map(|_| SessionResult::UpgradeCompression),
             };
 
             match result {
                 Ok(SessionResult::Continue) => (),
+                Ok(SessionResult::UpgradeCompression) => {
+                    self.compress_input = compress_input.unwrap_or_default().to_vec();
+                    return SessionResult::UpgradeCompression;
+                }
                 Ok(result) => return result,
                 Err(err) => {
                     if !self.write_error(err).await {
@@ -220,6 +251,11 @@ impl<T: SessionStream> Session<T> {
             }
         }
 
+        // Compression was not enabled, parse the remaining commands uncompressed
+        if let Some(compress_input) = compress_input.filter(|input| !input.is_empty()) {
+            return Box::pin(self.ingest(compress_input)).await;
+        }
+
         if let Some(needs_literal) = needs_literal {
             if let Err(err) = self
                 .write_bytes(format!("+ Ready for {} bytes.\r\n", needs_literal).into_bytes())
@@ -273,7 +309,13 @@ impl<T: SessionStream> Session<T> {
         match &request.command {
             Command::Capability | Command::Noop | Command::Logout | Command::Id => Ok(request),
             Command::StartTls => {
-                if !self.is_tls {
+                if self.is_compressed {
+                    Err(trc::ImapEvent::Error
+                        .into_err()
+                        .details("STARTTLS is not allowed after COMPRESS.")
+                        .ctx(trc::Key::Type, ResponseType::Bad)
+                        .id(request.tag))
+                } else if !self.is_tls {
                     if self.instance.acceptor.is_tls() {
                         Ok(request)
                     } else {
@@ -289,6 +331,23 @@ impl<T: SessionStream> Session<T> {
                         .id(request.tag))
                 }
             }
+            Command::Compress => {
+                if !state.is_authenticated() {
+                    Err(trc::ImapEvent::Error
+                        .into_err()
+                        .details("Not authenticated.")
+                        .id(request.tag))
+                } else if self.is_compressed {
+                    Err(trc::ImapEvent::Error
+                        .into_err()
+                        .details("Compression is already active.")
+                        .code(ResponseCode::CompressionActive)
+                        .ctx(trc::Key::Type, ResponseType::Bad)
+                        .id(request.tag))
+                } else {
+                    Ok(request)
+                }
+            }
             Command::Authenticate => {
                 if let State::NotAuthenticated { .. } = state {
                     Ok(request)
@@ -335,7 +394,9 @@ impl<T: SessionStream> Session<T> {
             | Command::GetAcl
             | Command::ListRights
             | Command::MyRights
-            | Command::Unauthenticate => {
+            | Command::GetQuota
+            | Command::GetQuotaRoot
+            | Command::SetQuota => {
                 if let State::Authenticated { .. } | State::Selected { .. } = state {
                     Ok(request)
                 } else {
@@ -345,6 +406,27 @@ impl<T: SessionStream> Session<T> {
                         .id(request.tag))
                 }
             }
+            Command::Unauthenticate => {
+                if !state.is_authenticated() {
+                    Err(trc::ImapEvent::Error
+                        .into_err()
+                        .details("Not authenticated.")
+                        .id(request.tag))
+                } else if !self
+                    .jmap
+                    .core
+                    .imap
+                    .is_unauthenticate_allowed(&self.remote_addr)
+                {
+                    Err(trc::ImapEvent::Error
+                        .into_err()
+                        .details("UNAUTHENTICATE is not allowed.")
+                        .ctx(trc::Key::Type, ResponseType::Bad)
+                        .id(request.tag))
+                } else {
+                    Ok(request)
+                }
+            }
             Command::Close
             | Command::Unselect
             | Command::Expunge(_)
@@ -353,6 +435,7 @@ impl<T: SessionStream> Session<T> {
             | Command::Store(_)
             | Command::Copy(_)
             | Command::Move(_)
+            | Command::Replace(_)
             | Command::Check
             | Command::Sort(_)
             | Command::Thread(_) => match state {
@@ -360,7 +443,10 @@ impl<T: SessionStream> Session<T> {
                     if mailbox.is_select
                         || !matches!(
                             request.command,
-                            Command::Store(_) | Command::Expunge(_) | Command::Move(_),
+                            Command::Store(_)
+                                | Command::Expunge(_)
+                                | Command::Move(_)
+                                | Command::Replace(_),
                         )
                     {
                         Ok(request)
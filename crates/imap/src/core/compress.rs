@@ -0,0 +1,113 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use std::{
+    borrow::Cow,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use async_compression::tokio::{bufread::DeflateDecoder, write::DeflateEncoder};
+use common::listener::SessionStream;
+use tokio::io::{AsyncRead, AsyncWrite, BufReader, ReadBuf, ReadHalf, WriteHalf};
+
+pub struct DeflateStream<T: SessionStream> {
+    reader: DeflateDecoder<BufReader<PendingInput<ReadHalf<T>>>>,
+    writer: DeflateEncoder<WriteHalf<T>>,
+    is_tls: bool,
+    tls_version_and_cipher: (Cow<'static, str>, Cow<'static, str>),
+    tls_exporter: Option<Vec<u8>>,
+}
+
+impl<T: SessionStream> DeflateStream<T> {
+    // Any bytes the client pipelined after the COMPRESS command are already
+    // compressed and have to be decoded before reading from the stream.
+    pub fn new(stream: T, pending_input: Vec<u8>) -> Self {
+        let is_tls = stream.is_tls();
+        let tls_version_and_cipher = stream.tls_version_and_cipher();
+        let tls_exporter = stream.tls_exporter();
+        let (stream_rx, stream_tx) = tokio::io::split(stream);
+
+        DeflateStream {
+            reader: DeflateDecoder::new(BufReader::new(PendingInput {
+                pending: pending_input,
+                offset: 0,
+                inner: stream_rx,
+            })),
+            writer: DeflateEncoder::new(stream_tx),
+            is_tls,
+            tls_version_and_cipher,
+            tls_exporter,
+        }
+    }
+}
+
+struct PendingInput<R> {
+    pending: Vec<u8>,
+    offset: usize,
+    inner: R,
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for PendingInput<R> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        if self.offset < self.pending.len() {
+            let len = std::cmp::min(self.pending.len() - self.offset, buf.remaining());
+            buf.put_slice(&self.pending[self.offset..self.offset + len]);
+            self.offset += len;
+            Poll::Ready(Ok(()))
+        } else {
+            Pin::new(&mut self.inner).poll_read(cx, buf)
+        }
+    }
+}
+
+impl<T: SessionStream> AsyncRead for DeflateStream<T> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.reader).poll_read(cx, buf)
+    }
+}
+
+impl<T: SessionStream> AsyncWrite for DeflateStream<T> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<std::io::Result<usize>> {
+        Pin::new(&mut self.writer).poll_write(cx, buf)
+    }
+
+    // Flushing performs a deflate sync flush, which makes all
+    // data written so far available to the client.
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.writer).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.writer).poll_shutdown(cx)
+    }
+}
+
+impl<T: SessionStream> SessionStream for DeflateStream<T> {
+    fn is_tls(&self) -> bool {
+        self.is_tls
+    }
+
+    fn tls_version_and_cipher(&self) -> (Cow<'static, str>, Cow<'static, str>) {
+        self.tls_version_and_cipher.clone()
+    }
+
+    fn tls_exporter(&self) -> Option<Vec<u8>> {
+        self.tls_exporter.clone()
+    }
+}
@@ -5,7 +5,7 @@ use std::{
 
 use ahash::AHashMap;
 use common::{
-    auth::AccessToken,
+    auth::{sessions::SessionInFlight, AccessToken},
     config::jmap::settings::SpecialUse,
     listener::{limiter::InFlight, SessionStream},
 };
@@ -28,6 +28,7 @@ impl<T: SessionStream> SessionData<T> {
         session: &Session<T>,
         access_token: Arc<AccessToken>,
         in_flight: Option<InFlight>,
+        session_in_flight: SessionInFlight,
     ) -> trc::Result<Self> {
         let mut session = SessionData {
             stream_tx: session.stream_tx.clone(),
@@ -39,6 +40,7 @@ impl<T: SessionStream> SessionData<T> {
             state: access_token.state().into(),
             access_token,
             in_flight,
+            session_in_flight,
         };
         let access_token = session.access_token.clone();
 
@@ -245,6 +247,8 @@ impl<T: SessionStream> SessionData<T> {
                                     _ => None,
                                 },
                             ),
+                            // UIDs can only be cached by clients if they are persisted
+                            is_uid_sticky: mailbox.get(&Property::Cid).as_uint().is_some(),
                             total_messages: self
                                 .jmap
                                 .get_tag(
@@ -452,6 +456,7 @@ impl<T: SessionStream> SessionData<T> {
                                 v.total_unseen = None;
                                 v.total_messages = None;
                                 v.size = None;
+                                v.total_deleted_storage = None;
                                 v.uid_next = None;
                             });
                             account.state_mailbox = state_mailbox;
@@ -476,6 +481,7 @@ impl<T: SessionStream> SessionData<T> {
                                 v.total_unseen = None;
                                 v.total_messages = None;
                                 v.size = None;
+                                v.total_deleted_storage = None;
                                 v.uid_next = None;
                             });
                             cached_account.state_mailbox = state_mailbox;
@@ -580,6 +586,13 @@ impl<T: SessionStream> SessionData<T> {
         Ok(changes)
     }
 
+    pub fn get_mailbox_by_name_or_role(&self, mailbox_name: &str) -> Option<MailboxId> {
+        // Special-use references such as "\Sent" resolve to the mailbox with that role
+        Attribute::parse_special_use(mailbox_name)
+            .and_then(|role| self.get_mailbox_by_role(&role))
+            .or_else(|| self.get_mailbox_by_name(mailbox_name))
+    }
+
     pub fn get_mailbox_by_name(&self, mailbox_name: &str) -> Option<MailboxId> {
         let is_inbox = mailbox_name.eq_ignore_ascii_case("inbox");
         for account in self.mailboxes.lock().iter() {
@@ -604,6 +617,32 @@ impl<T: SessionStream> SessionData<T> {
         None
     }
 
+    pub fn get_mailbox_by_role(&self, role: &Attribute) -> Option<MailboxId> {
+        self.mailboxes
+            .lock()
+            .iter()
+            .find(|account| account.account_id == self.account_id)
+            .and_then(|account| {
+                account
+                    .mailbox_state
+                    .iter()
+                    .find(|(_, mailbox)| mailbox.special_use.as_ref() == Some(role))
+                    .map(|(mailbox_id, _)| MailboxId {
+                        account_id: account.account_id,
+                        mailbox_id: *mailbox_id,
+                    })
+            })
+    }
+
+    pub fn is_uid_sticky(&self, mailbox: &MailboxId) -> bool {
+        self.mailboxes
+            .lock()
+            .iter()
+            .find(|account| account.account_id == mailbox.account_id)
+            .and_then(|account| account.mailbox_state.get(&mailbox.mailbox_id))
+            .map_or(true, |mailbox| mailbox.is_uid_sticky)
+    }
+
     pub async fn check_mailbox_acl(
         &self,
         account_id: u32,
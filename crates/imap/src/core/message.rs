@@ -163,6 +163,36 @@ impl<T: SessionStream> SessionData<T> {
         Ok(modseq)
     }
 
+    // Synchronizes the mailbox until all appended messages are visible, as a concurrent
+    // append can commit its changes in between and hide ours from the first sync
+    pub async fn synchronize_appended(
+        &self,
+        mailbox: &SelectedMailbox,
+        appended: &[ImapUidToId],
+    ) -> trc::Result<u32> {
+        for _ in 0..MAX_RETRIES {
+            self.synchronize_messages(mailbox).await?;
+
+            {
+                let state = mailbox.state.lock();
+                let uid_to_id = state
+                    .next_state
+                    .as_ref()
+                    .map_or(&state.uid_to_id, |next| &next.next_state.uid_to_id);
+                if appended
+                    .iter()
+                    .all(|id| uid_to_id.get(&id.uid) == Some(&id.id))
+                {
+                    return Ok(state.uid_validity);
+                }
+            }
+        }
+
+        // The UIDs returned to the client are still valid, the messages
+        // will be announced on the next synchronization
+        Ok(mailbox.state.lock().uid_validity)
+    }
+
     pub async fn write_mailbox_changes(
         &self,
         mailbox: &SelectedMailbox,
@@ -220,6 +250,8 @@ impl<T: SessionStream> SessionData<T> {
             })
     }
 
+    // Mailboxes without a persisted UID validity are assigned one for the
+    // duration of the session, their UIDs are not sticky (RFC 4315)
     pub async fn get_uid_validity(&self, mailbox: &MailboxId) -> trc::Result<u32> {
         self.jmap
             .get_property::<Object<Value>>(
@@ -229,7 +261,7 @@ impl<T: SessionStream> SessionData<T> {
                 &Property::Value,
             )
             .await?
-            .and_then(|obj| obj.get(&Property::Cid).as_uint())
+            .map(|obj| obj.get(&Property::Cid).as_uint().unwrap_or(self.session_id))
             .ok_or_else(|| {
                 trc::ImapEvent::Error
                     .caused_by(trc::location!())
@@ -279,9 +311,15 @@ impl SelectedMailbox {
             let mut ids = AHashMap::with_capacity(saved_ids.len());
             let state = self.state.lock();
 
+            // Resolve saved uids against the current state, as sequence
+            // numbers may have shifted since the search was saved
             for imap_id in saved_ids.iter() {
-                if let Some(id) = state.uid_to_id.get(&imap_id.uid) {
-                    ids.insert(*id, *imap_id);
+                if let Some((id, imap_id)) = state
+                    .uid_to_id
+                    .get(&imap_id.uid)
+                    .and_then(|id| state.id_to_imap.get(id).map(|imap_id| (*id, *imap_id)))
+                {
+                    ids.insert(id, imap_id);
                 }
             }
 
@@ -317,27 +355,4 @@ impl SelectedMailbox {
         deleted_ids.sort_unstable();
         deleted_ids
     }
-
-    pub fn append_messages(&self, ids: Vec<ImapUidToId>, modseq: Option<u64>) -> u32 {
-        let mut mailbox = self.state.lock();
-        if modseq.unwrap_or(0) > mailbox.modseq.unwrap_or(0) {
-            let mut uid_max = 0;
-            for id in ids {
-                mailbox.total_messages += 1;
-                let seqnum = mailbox.total_messages as u32;
-                mailbox.uid_to_id.insert(id.uid, id.uid);
-                mailbox.id_to_imap.insert(
-                    id.id,
-                    ImapId {
-                        uid: id.uid,
-                        seqnum,
-                    },
-                );
-                uid_max = id.uid;
-            }
-            mailbox.uid_max = uid_max;
-            mailbox.uid_next = uid_max + 1;
-        }
-        mailbox.uid_validity
-    }
 }
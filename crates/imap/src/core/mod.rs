@@ -8,14 +8,16 @@ use std::{
     collections::BTreeMap,
     net::IpAddr,
     sync::{atomic::AtomicU32, Arc},
+    time::Duration,
 };
 
 use ahash::AHashMap;
 use common::{
-    auth::AccessToken,
+    auth::{sessions::SessionInFlight, AccessToken},
     listener::{limiter::InFlight, ServerInstance, SessionStream},
 };
 use dashmap::DashMap;
+use directory::core::scram::ScramExchange;
 use imap_proto::{
     protocol::{list::Attribute, ProtocolVersion},
     receiver::Receiver,
@@ -30,10 +32,13 @@ use trc::AddContext;
 use utils::lru_cache::LruCache;
 
 pub mod client;
+pub mod compress;
 pub mod mailbox;
 pub mod message;
 pub mod session;
 
+const BACKGROUND_COMMANDS_TIMEOUT: Duration = Duration::from_secs(30);
+
 #[derive(Clone)]
 pub struct ImapSessionManager {
     pub imap: ImapInstance,
@@ -69,6 +74,10 @@ pub struct Session<T: SessionStream> {
     pub is_tls: bool,
     pub is_condstore: bool,
     pub is_qresync: bool,
+    pub is_compressed: bool,
+    pub compress_input: Vec<u8>,
+    pub tls_exporter: Option<Vec<u8>>,
+    pub sasl: Option<SaslExchange>,
     pub stream_rx: ReadHalf<T>,
     pub stream_tx: Arc<tokio::sync::Mutex<WriteHalf<T>>>,
     pub in_flight: InFlight,
@@ -76,6 +85,20 @@ pub struct Session<T: SessionStream> {
     pub session_id: u64,
 }
 
+// Multi-step SASL exchange in progress
+pub enum SaslExchange {
+    // Waiting for the SCRAM client-final message
+    Scram {
+        tag: String,
+        exchange: Box<ScramExchange>,
+    },
+    // Waiting for the client to acknowledge the SCRAM server-final message
+    ScramVerified {
+        tag: String,
+        access_token: Box<AccessToken>,
+    },
+}
+
 pub struct SessionData<T: SessionStream> {
     pub account_id: u32,
     pub access_token: Arc<AccessToken>,
@@ -86,6 +109,7 @@ pub struct SessionData<T: SessionStream> {
     pub stream_tx: Arc<tokio::sync::Mutex<WriteHalf<T>>>,
     pub state: AtomicU32,
     pub in_flight: Option<InFlight>,
+    pub session_in_flight: SessionInFlight,
 }
 
 #[derive(Debug, Default, Clone)]
@@ -93,12 +117,14 @@ pub struct Mailbox {
     pub has_children: bool,
     pub is_subscribed: bool,
     pub special_use: Option<Attribute>,
+    pub is_uid_sticky: bool,
     pub total_messages: Option<u32>,
     pub total_unseen: Option<u32>,
     pub total_deleted: Option<u32>,
     pub uid_validity: Option<u32>,
     pub uid_next: Option<u32>,
-    pub size: Option<u32>,
+    pub size: Option<u64>,
+    pub total_deleted_storage: Option<u64>,
 }
 
 #[derive(Debug, Clone, Default)]
@@ -117,6 +143,7 @@ pub struct SelectedMailbox {
     pub saved_search: parking_lot::Mutex<SavedSearch>,
     pub is_select: bool,
     pub is_condstore: bool,
+    pub is_uid_sticky: bool,
 }
 
 #[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
@@ -224,6 +251,18 @@ impl<T: SessionStream> SessionData<T> {
             .caused_by(trc::location!())
     }
 
+    // Waits until commands running in the background release this session,
+    // returns false if they are still running after the timeout.
+    pub async fn wait_for_background_commands(self: &Arc<Self>) -> bool {
+        tokio::time::timeout(BACKGROUND_COMMANDS_TIMEOUT, async {
+            while Arc::strong_count(self) > 1 {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .is_ok()
+    }
+
     pub fn replace_stream_tx<U: SessionStream>(
         self,
         new_stream: Arc<tokio::sync::Mutex<WriteHalf<U>>>,
@@ -237,6 +276,7 @@ impl<T: SessionStream> SessionData<T> {
             stream_tx: new_stream,
             state: self.state,
             in_flight: self.in_flight,
+            session_in_flight: self.session_in_flight,
             access_token: self.access_token,
         }
     }
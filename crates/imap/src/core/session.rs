@@ -4,7 +4,7 @@
  * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
  */
 
-use std::sync::Arc;
+use std::{future::Future, sync::Arc};
 
 use common::listener::{stream::NullIo, SessionData, SessionManager, SessionResult, SessionStream};
 use imap_proto::{
@@ -17,7 +17,7 @@ use tokio_rustls::server::TlsStream;
 
 use crate::{GREETING_WITHOUT_TLS, GREETING_WITH_TLS};
 
-use super::{ImapSessionManager, Session, State};
+use super::{compress::DeflateStream, ImapSessionManager, Session, State};
 
 impl SessionManager for ImapSessionManager {
     #[allow(clippy::manual_async_fn)]
@@ -27,10 +27,22 @@ impl SessionManager for ImapSessionManager {
     ) -> impl std::future::Future<Output = ()> + Send {
         async move {
             if let Ok(mut session) = Session::new(session, self).await {
-                if session.handle_conn().await && session.instance.acceptor.is_tls() {
-                    if let Ok(mut session) = session.into_tls().await {
-                        session.handle_conn().await;
+                match session.handle_conn().await {
+                    SessionResult::UpgradeTls if session.instance.acceptor.is_tls() => {
+                        if let Ok(mut session) = session.into_tls().await {
+                            if session.handle_conn().await == SessionResult::UpgradeCompression {
+                                if let Ok(mut session) = session.into_compressed().await {
+                                    session.handle_conn().await;
+                                }
+                            }
+                        }
                     }
+                    SessionResult::UpgradeCompression => {
+                        if let Ok(mut session) = session.into_compressed().await {
+                            session.handle_conn().await;
+                        }
+                    }
+                    _ => (),
                 }
             }
         }
@@ -43,11 +55,29 @@ impl SessionManager for ImapSessionManager {
 }
 
 impl<T: SessionStream> Session<T> {
-    pub async fn handle_conn(&mut self) -> bool {
+    pub async fn handle_conn(&mut self) -> SessionResult {
         let mut buf = vec![0; 8192];
         let mut shutdown_rx = self.instance.shutdown_rx.clone();
+        let mut revocation_rx = None;
 
         loop {
+            // Authenticated sessions are closed when the account is signed out everywhere
+            if revocation_rx.is_none() && self.state.is_authenticated() {
+                revocation_rx = Some(
+                    self.jmap
+                        .core
+                        .subscribe_session_revocation(self.state.session_data().account_id),
+                );
+            }
+            let revoked = async {
+                if let Some(revocation_rx) = &mut revocation_rx {
+                    if revocation_rx.changed().await.is_ok() {
+                        return;
+                    }
+                }
+                std::future::pending().await
+            };
+
             tokio::select! {
                 result = tokio::time::timeout(
                     if !matches!(self.state, State::NotAuthenticated {..}) {
@@ -61,8 +91,9 @@ impl<T: SessionStream> Session<T> {
                             if bytes_read > 0 {
                                 match self.ingest(&buf[..bytes_read]).await {
                                     SessionResult::Continue => (),
-                                    SessionResult::UpgradeTls => {
-                                        return true;
+                                    result @ (SessionResult::UpgradeTls
+                                    | SessionResult::UpgradeCompression) => {
+                                        return result;
                                     }
                                     SessionResult::Close => {
                                         break;
@@ -107,10 +138,20 @@ impl<T: SessionStream> Session<T> {
                     self.write_bytes(&b"* BYE Server shutting down.\r\n"[..]).await.ok();
                     break;
                 }
+                _ = revoked => {
+                    trc::event!(
+                        Network(trc::NetworkEvent::Closed),
+                        SpanId = self.session_id,
+                        Reason = "Session revoked",
+                        CausedBy = trc::location!()
+                    );
+                    self.write_bytes(&b"* BYE Session revoked.\r\n"[..]).await.ok();
+                    break;
+                }
             };
         }
 
-        false
+        SessionResult::Close
     }
 
     pub async fn new(
@@ -137,16 +178,22 @@ impl<T: SessionStream> Session<T> {
         let _ = session.stream.flush().await;
 
         // Split stream into read and write halves
+        let tls_exporter = session.stream.tls_exporter();
         let (stream_rx, stream_tx) = tokio::io::split(session.stream);
         let jmap = JMAP::from(manager.imap.jmap_instance);
 
         Ok(Session {
-            receiver: Receiver::with_max_request_size(jmap.core.imap.max_request_size),
+            receiver: Receiver::with_max_request_size(jmap.core.imap.max_request_size)
+                .with_max_literal_size(jmap.core.imap.max_message_size),
             version: ProtocolVersion::Rev1,
             state: State::NotAuthenticated { auth_failures: 0 },
             is_tls,
             is_condstore: false,
             is_qresync: false,
+            is_compressed: false,
+            compress_input: Vec::new(),
+            tls_exporter,
+            sasl: None,
             jmap,
             imap: manager.imap.imap_inner,
             instance: session.instance,
@@ -159,6 +206,30 @@ impl<T: SessionStream> Session<T> {
     }
 
     pub async fn into_tls(self) -> Result<Session<TlsStream<T>>, ()> {
+        let instance = self.instance.clone();
+        let session_id = self.session_id;
+
+        self.upgrade_stream(|stream| async move { instance.tls_accept(stream, session_id).await })
+            .await
+    }
+
+    pub async fn into_compressed(mut self) -> Result<Session<DeflateStream<T>>, ()> {
+        let compress_input = std::mem::take(&mut self.compress_input);
+
+        self.upgrade_stream(|stream| async move { Ok(DeflateStream::new(stream, compress_input)) })
+            .await
+            .map(|mut session| {
+                session.is_compressed = true;
+                session
+            })
+    }
+
+    async fn upgrade_stream<U, F, R>(self, upgrade: F) -> Result<Session<U>, ()>
+    where
+        U: SessionStream,
+        F: FnOnce(T) -> R,
+        R: Future<Output = Result<U, ()>>,
+    {
         // Drop references to write half from state
         let state = if let Some(state) =
             self.state
@@ -190,9 +261,11 @@ impl<T: SessionStream> Session<T> {
             return Err(());
         };
 
-        // Upgrade to TLS
-        let (stream_rx, stream_tx) =
-            tokio::io::split(self.instance.tls_accept(stream, self.session_id).await?);
+        // Upgrade stream
+        let stream = upgrade(stream).await?;
+        let is_tls = stream.is_tls();
+        let tls_exporter = stream.tls_exporter();
+        let (stream_rx, stream_tx) = tokio::io::split(stream);
         let stream_tx = Arc::new(tokio::sync::Mutex::new(stream_tx));
 
         Ok(Session {
@@ -202,9 +275,13 @@ impl<T: SessionStream> Session<T> {
             receiver: self.receiver,
             version: self.version,
             state: state.try_replace_stream_tx(stream_tx.clone()).unwrap(),
-            is_tls: true,
+            is_tls,
             is_condstore: self.is_condstore,
             is_qresync: self.is_qresync,
+            is_compressed: self.is_compressed,
+            compress_input: Vec::new(),
+            tls_exporter,
+            sasl: None,
             session_id: self.session_id,
             in_flight: self.in_flight,
             remote_addr: self.remote_addr,
@@ -8,7 +8,10 @@ use std::{sync::Arc, time::Instant};
 
 use directory::Permission;
 use imap_proto::{
-    protocol::{append::Arguments, select::HighestModSeq},
+    protocol::{
+        append::{Arguments, CatenatePart, ImapUrl},
+        select::HighestModSeq,
+    },
     receiver::Request,
     Command, ResponseCode, StatusResponse,
 };
@@ -18,11 +21,26 @@ use crate::{
     spawn_op,
 };
 use common::listener::SessionStream;
-use jmap::email::ingest::{IngestEmail, IngestSource};
-use jmap_proto::types::{acl::Acl, keyword::Keyword, state::StateChange, type_state::DataType};
+use jmap::{
+    email::{
+        ingest::{IngestEmail, IngestSource},
+        keywords::KeywordRegistry,
+        metadata::MessageMetadata,
+    },
+    mailbox::UidMailbox,
+};
+use jmap_proto::types::{
+    acl::Acl, collection::Collection, keyword::Keyword, property::Property, state::StateChange,
+    type_state::DataType,
+};
 use mail_parser::MessageParser;
+use store::{
+    roaring::RoaringBitmap,
+    write::{log::ChangeLogBuilder, Bincode},
+};
+use trc::AddContext;
 
-use super::{ImapContext, ToModSeq};
+use super::{keyword_error, ImapContext, ToModSeq};
 
 impl<T: SessionStream> Session<T> {
     pub async fn handle_append(&mut self, request: Request<Command>) -> trc::Result<()> {
@@ -39,20 +57,31 @@ impl<T: SessionStream> Session<T> {
             .imap_ctx(&arguments.tag, trc::location!())?;
 
         // Obtain mailbox
-        let mailbox = if let Some(mailbox) = data.get_mailbox_by_name(&arguments.mailbox_name) {
-            mailbox
-        } else {
-            return Err(trc::ImapEvent::Error
-                .into_err()
-                .details("Mailbox does not exist.")
-                .code(ResponseCode::TryCreate)
-                .id(arguments.tag));
-        };
-        let is_qresync = self.is_qresync;
+        let mailbox =
+            if let Some(mailbox) = data.get_mailbox_by_name_or_role(&arguments.mailbox_name) {
+                mailbox
+            } else {
+                return Err(trc::ImapEvent::Error
+                    .into_err()
+                    .details("Mailbox does not exist.")
+                    .code(ResponseCode::TryCreate)
+                    .id(arguments.tag));
+            };
+        let is_condstore = self.is_condstore
+            || selected_mailbox
+                .as_ref()
+                .is_some_and(|mailbox| mailbox.is_condstore);
 
         spawn_op!(data, {
             let response = data
-                .append_messages(arguments, selected_mailbox, mailbox, is_qresync, op_start)
+                .append_messages(
+                    arguments,
+                    selected_mailbox,
+                    mailbox,
+                    is_condstore,
+                    op_start,
+                    None,
+                )
                 .await?
                 .into_bytes();
 
@@ -62,13 +91,14 @@ impl<T: SessionStream> Session<T> {
 }
 
 impl<T: SessionStream> SessionData<T> {
-    async fn append_messages(
+    pub(crate) async fn append_messages(
         &self,
-        arguments: Arguments,
+        mut arguments: Arguments,
         selected_mailbox: Option<Arc<SelectedMailbox>>,
         mailbox: MailboxId,
-        is_qresync: bool,
+        is_condstore: bool,
         op_start: Instant,
+        replace_id: Option<u32>,
     ) -> trc::Result<StatusResponse> {
         // Verify ACLs
         let account_id = mailbox.account_id;
@@ -87,8 +117,27 @@ impl<T: SessionStream> SessionData<T> {
                 .id(arguments.tag));
         }
 
+        // Assemble catenated messages (RFC 4469)
+        for message in &mut arguments.messages {
+            if !message.catenate.is_empty() {
+                message.message = self
+                    .catenate_message(&arguments.tag, std::mem::take(&mut message.catenate))
+                    .await?;
+            }
+        }
+
+        // Reject oversized messages before parsing any of them
+        let max_message_size = self.jmap.core.imap.max_message_size;
+        if arguments
+            .messages
+            .iter()
+            .any(|message| message.message.len() > max_message_size)
+        {
+            return Err(message_too_big(max_message_size).id(arguments.tag));
+        }
+
         // Obtain quota
-        let resource_token = self
+        let mut resource_token = self
             .jmap
             .core
             .get_cached_access_token(mailbox.account_id)
@@ -96,11 +145,73 @@ impl<T: SessionStream> SessionData<T> {
             .imap_ctx(&arguments.tag, trc::location!())?
             .as_resource_token();
 
+        // A replaced message from the selected mailbox is expunged after the append,
+        // only the net change has to fit in the quota
+        let replaced = replace_id.zip(selected_mailbox.as_ref().map(|mailbox| mailbox.id));
+        if let Some((document_id, src_mailbox)) =
+            replaced.filter(|(_, src_mailbox)| src_mailbox.account_id == account_id)
+        {
+            let freed_size = self
+                .replaced_size(account_id, src_mailbox.mailbox_id, document_id)
+                .await
+                .imap_ctx(&arguments.tag, trc::location!())?;
+            if resource_token.quota != 0 {
+                resource_token.quota += freed_size;
+            }
+            if let Some(tenant) = &mut resource_token.tenant {
+                if tenant.quota != 0 {
+                    tenant.quota += freed_size;
+                }
+            }
+        }
+
+        // Make sure all messages fit in the quota before appending any of them
+        self.jmap
+            .has_available_quota(
+                &resource_token,
+                arguments
+                    .messages
+                    .iter()
+                    .map(|message| message.message.len() as u64)
+                    .sum(),
+            )
+            .await
+            .map_err(|err| map_quota_error(err).id(arguments.tag.clone()))?;
+
+        // Validate the keywords against the mailbox keyword policy
+        let mut message_keywords = Vec::with_capacity(arguments.messages.len());
+        let mut registry = KeywordRegistry::default();
+        for message in &mut arguments.messages {
+            let mut keywords = std::mem::take(&mut message.flags)
+                .into_iter()
+                .map(Keyword::from)
+                .collect::<Vec<_>>();
+            if let Some(err) = self
+                .jmap
+                .email_check_keywords(account_id, &[mailbox_id], &mut keywords, &mut registry)
+                .await
+                .imap_ctx(&arguments.tag, trc::location!())?
+            {
+                return Err(keyword_error(err).id(arguments.tag));
+            }
+            message_keywords.push(keywords);
+        }
+
         // Append messages
         let mut response = StatusResponse::completed(Command::Append);
         let mut created_ids = Vec::with_capacity(arguments.messages.len());
+        let mut uids = Vec::with_capacity(arguments.messages.len());
         let mut last_change_id = None;
-        for message in arguments.messages {
+        let dedup = if replaced.is_none() {
+            self.jmap
+                .email_append_dedup(&self.access_token.name, self.session_id)
+                .await
+        } else {
+            // A duplicate would point to the message being replaced
+            None
+        };
+
+        for (message, keywords) in arguments.messages.into_iter().zip(message_keywords) {
             match self
                 .jmap
                 .email_ingest(IngestEmail {
@@ -108,42 +219,111 @@ impl<T: SessionStream> SessionData<T> {
                     message: MessageParser::new().parse(&message.message),
                     resource: resource_token.clone(),
                     mailbox_ids: vec![mailbox_id],
-                    keywords: message.flags.into_iter().map(Keyword::from).collect(),
+                    keywords,
                     received_at: message.received_at.map(|d| d as u64),
+                    received_at_offset: message.received_at_offset,
                     source: IngestSource::Imap,
+                    delivered_to: None,
                     encrypt: self.jmap.core.jmap.encrypt && self.jmap.core.jmap.encrypt_append,
+                    dedup,
                     session_id: self.session_id,
+                    dry_run: false,
                 })
                 .await
             {
                 Ok(email) => {
-                    created_ids.push(ImapUidToId {
-                        uid: email.imap_uids[0],
-                        id: email.id.document_id(),
-                    });
-                    last_change_id = Some(email.change_id);
+                    // Duplicates return the UID of the original message
+                    uids.push(email.imap_uids[0]);
+                    if email.change_id != u64::MAX {
+                        created_ids.push(ImapUidToId {
+                            uid: email.imap_uids[0],
+                            id: email.id.document_id(),
+                        });
+                        last_change_id = Some(email.change_id);
+                    }
                 }
                 Err(err) => {
-                    return Err(
-                        if err.matches(trc::EventType::Limit(trc::LimitEvent::Quota)) {
-                            err.details("Disk quota exceeded.")
-                                .code(ResponseCode::OverQuota)
-                        } else if err.matches(trc::EventType::Limit(trc::LimitEvent::TenantQuota)) {
-                            err.details("Organization disk quota exceeded.")
-                                .code(ResponseCode::OverQuota)
-                        } else {
-                            err
+                    // Roll back any messages appended by this command
+                    if !created_ids.is_empty() {
+                        if let Err(err) = self.rollback_append(account_id, &created_ids).await {
+                            trc::error!(err.span_id(self.session_id));
                         }
-                        .id(arguments.tag),
+                    }
+
+                    return Err(map_quota_error(err).id(arguments.tag));
+                }
+            }
+        }
+
+        // Expunge the replaced message, the append is undone if this fails
+        if let Some((document_id, src_mailbox)) = replaced {
+            let mut changelog = ChangeLogBuilder::new();
+            let result = match self
+                .email_untag_or_delete(
+                    src_mailbox.account_id,
+                    src_mailbox.mailbox_id,
+                    &RoaringBitmap::from_iter([document_id]),
+                    &mut changelog,
+                )
+                .await
+            {
+                Ok(_) if !changelog.is_empty() => self
+                    .jmap
+                    .commit_changes(src_mailbox.account_id, changelog)
+                    .await
+                    .map(Some),
+                Ok(_) => Ok(None),
+                Err(err) => Err(err),
+            };
+
+            match result {
+                Ok(Some(change_id)) => {
+                    trc::event!(
+                        Imap(trc::ImapEvent::Expunge),
+                        SpanId = self.session_id,
+                        AccountId = src_mailbox.account_id,
+                        MailboxId = src_mailbox.mailbox_id,
+                        DocumentId = document_id,
+                        Elapsed = op_start.elapsed()
                     );
+
+                    if src_mailbox.account_id == account_id {
+                        // Coalesce both changes into a single state change
+                        last_change_id = Some(change_id);
+                    } else {
+                        self.jmap
+                            .broadcast_state_change(
+                                StateChange::new(src_mailbox.account_id)
+                                    .with_change(DataType::Email, change_id)
+                                    .with_change(DataType::Mailbox, change_id)
+                                    .with_change(DataType::Thread, change_id),
+                            )
+                            .await;
+                    }
+                }
+                Ok(None) => {}
+                Err(err) => {
+                    if !created_ids.is_empty() {
+                        if let Err(err) = self.rollback_append(account_id, &created_ids).await {
+                            trc::error!(err.span_id(self.session_id));
+                        }
+                    }
+
+                    return Err(err.id(arguments.tag));
                 }
             }
         }
 
+        // Register the custom keywords now that the messages are stored
+        self.jmap
+            .email_register_keywords(account_id, &mut registry)
+            .await
+            .imap_ctx(&arguments.tag, trc::location!())?;
+
         // Broadcast changes
         if let Some(change_id) = last_change_id {
             self.jmap
-                .broadcast_state_change(
+                .broadcast_state_change_batched(
                     StateChange::new(account_id)
                         .with_change(DataType::Email, change_id)
                         .with_change(DataType::Mailbox, change_id)
@@ -165,29 +345,201 @@ impl<T: SessionStream> SessionData<T> {
             Elapsed = op_start.elapsed()
         );
 
-        if !created_ids.is_empty() {
-            let uids = created_ids.iter().map(|id| id.uid).collect();
-            let uid_validity = match selected_mailbox {
-                Some(selected_mailbox) if selected_mailbox.id == mailbox => {
-                    // Write updated modseq
-                    if is_qresync {
-                        self.write_bytes(
-                            HighestModSeq::new(last_change_id.to_modseq()).into_bytes(),
-                        )
-                        .await?;
-                    }
+        if !uids.is_empty() {
+            // Write updated modseq
+            if is_condstore && last_change_id.is_some() {
+                self.write_bytes(HighestModSeq::new(last_change_id.to_modseq()).into_bytes())
+                    .await?;
+            }
 
-                    selected_mailbox.append_messages(created_ids, last_change_id)
-                }
-                _ => self
-                    .get_uid_validity(&mailbox)
-                    .await
-                    .imap_ctx(&arguments.tag, trc::location!())?,
+            let (uid_validity, is_uid_sticky) = match selected_mailbox {
+                Some(selected_mailbox) if selected_mailbox.id == mailbox => (
+                    self.synchronize_appended(&selected_mailbox, &created_ids)
+                        .await
+                        .imap_ctx(&arguments.tag, trc::location!())?
+                        .into(),
+                    selected_mailbox.is_uid_sticky,
+                ),
+                _ => (None, self.is_uid_sticky(&mailbox)),
             };
 
-            response = response.with_code(ResponseCode::AppendUid { uid_validity, uids });
+            // Clients must not cache UIDs of mailboxes that do not persist them (RFC 4315)
+            response = if is_uid_sticky {
+                let uid_validity = match uid_validity {
+                    Some(uid_validity) => uid_validity,
+                    None => self
+                        .get_uid_validity(&mailbox)
+                        .await
+                        .imap_ctx(&arguments.tag, trc::location!())?,
+                };
+                response.with_code(ResponseCode::AppendUid { uid_validity, uids })
+            } else {
+                response.with_code(ResponseCode::UidNotSticky)
+            };
         }
 
         Ok(response.with_tag(arguments.tag))
     }
+
+    async fn catenate_message(&self, tag: &str, parts: Vec<CatenatePart>) -> trc::Result<Vec<u8>> {
+        // Parts are checked against the size limit before being appended
+        let max_message_size = self.jmap.core.imap.max_message_size;
+        let mut message = Vec::new();
+        for part in parts {
+            let max_part_size = max_message_size - message.len();
+            match part {
+                CatenatePart::Text(text) if text.len() <= max_part_size => {
+                    message.extend_from_slice(&text);
+                }
+                CatenatePart::Url(url) => {
+                    message.extend_from_slice(&self.fetch_url(tag, &url, max_part_size).await?);
+                }
+                CatenatePart::Text(_) => {
+                    return Err(message_too_big(max_message_size).id(tag.to_string()));
+                }
+            }
+        }
+
+        Ok(message)
+    }
+
+    async fn fetch_url(&self, tag: &str, url: &str, max_size: usize) -> trc::Result<Vec<u8>> {
+        let bad_url = || {
+            trc::ImapEvent::Error
+                .into_err()
+                .details("Invalid or inaccessible message URL.")
+                .code(ResponseCode::BadUrl {
+                    url: url.to_string(),
+                })
+                .id(tag.to_string())
+        };
+
+        // Only messages in mailboxes readable by the user can be referenced
+        let imap_url = ImapUrl::parse(url).ok_or_else(bad_url)?;
+        let mailbox = self
+            .get_mailbox_by_name(&imap_url.mailbox_name)
+            .ok_or_else(bad_url)?;
+        if !self
+            .check_mailbox_acl(mailbox.account_id, mailbox.mailbox_id, Acl::ReadItems)
+            .await
+            .imap_ctx(tag, trc::location!())?
+        {
+            return Err(bad_url());
+        }
+
+        let state = self
+            .fetch_messages(&mailbox)
+            .await
+            .imap_ctx(tag, trc::location!())?;
+        let document_id = match state.uid_to_id.get(&imap_url.uid) {
+            Some(document_id)
+                if imap_url
+                    .uid_validity
+                    .map_or(true, |uid_validity| uid_validity == state.uid_validity) =>
+            {
+                *document_id
+            }
+            _ => return Err(bad_url()),
+        };
+
+        let metadata = self
+            .jmap
+            .get_property::<Bincode<MessageMetadata>>(
+                mailbox.account_id,
+                Collection::Email,
+                document_id,
+                &Property::BodyStructure,
+            )
+            .await
+            .imap_ctx(tag, trc::location!())?
+            .ok_or_else(bad_url)?
+            .inner;
+        if metadata.size > max_size {
+            return Err(message_too_big(self.jmap.core.imap.max_message_size).id(tag.to_string()));
+        }
+
+        self.jmap
+            .get_blob(&metadata.blob_hash, 0..usize::MAX)
+            .await
+            .imap_ctx(tag, trc::location!())?
+            .ok_or_else(bad_url)
+    }
+
+    async fn replaced_size(
+        &self,
+        account_id: u32,
+        mailbox_id: u32,
+        document_id: u32,
+    ) -> trc::Result<u64> {
+        // Messages also present in other mailboxes are only untagged
+        let is_destroyed = self
+            .jmap
+            .get_property::<Vec<UidMailbox>>(
+                account_id,
+                Collection::Email,
+                document_id,
+                Property::MailboxIds,
+            )
+            .await
+            .caused_by(trc::location!())?
+            .is_some_and(|mailboxes| mailboxes.len() == 1 && mailboxes[0].mailbox_id == mailbox_id);
+
+        if is_destroyed {
+            self.jmap
+                .get_property::<u32>(account_id, Collection::Email, document_id, Property::Size)
+                .await
+                .caused_by(trc::location!())
+                .map(|size| size.unwrap_or_default() as u64)
+        } else {
+            Ok(0)
+        }
+    }
+
+    async fn rollback_append(
+        &self,
+        account_id: u32,
+        created_ids: &[ImapUidToId],
+    ) -> trc::Result<()> {
+        let (changes, _) = self
+            .jmap
+            .emails_tombstone(account_id, created_ids.iter().map(|id| id.id).collect())
+            .await
+            .caused_by(trc::location!())?;
+        let change_id = self
+            .jmap
+            .commit_changes(account_id, changes)
+            .await
+            .caused_by(trc::location!())?;
+        self.jmap
+            .broadcast_state_change(
+                StateChange::new(account_id)
+                    .with_change(DataType::Email, change_id)
+                    .with_change(DataType::Mailbox, change_id)
+                    .with_change(DataType::Thread, change_id),
+            )
+            .await;
+
+        Ok(())
+    }
+}
+
+fn message_too_big(max_message_size: usize) -> trc::Error {
+    trc::LimitEvent::SizeUpload
+        .into_err()
+        .details(format!(
+            "Message exceeds the maximum size of {max_message_size} bytes."
+        ))
+        .code(ResponseCode::TooBig)
+}
+
+fn map_quota_error(err: trc::Error) -> trc::Error {
+    if err.matches(trc::EventType::Limit(trc::LimitEvent::Quota)) {
+        err.details("Disk quota exceeded.")
+            .code(ResponseCode::OverQuota)
+    } else if err.matches(trc::EventType::Limit(trc::LimitEvent::TenantQuota)) {
+        err.details("Organization disk quota exceeded.")
+            .code(ResponseCode::OverQuota)
+    } else {
+        err
+    }
 }
@@ -4,18 +4,20 @@
  * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
  */
 
-use common::listener::SessionStream;
-use directory::Permission;
+use common::{auth::AccessToken, config::server::ServerProtocol, listener::SessionStream};
+use directory::{core::scram::ScramExchange, Permission};
 use imap_proto::{
-    protocol::{authenticate::Mechanism, capability::Capability},
+    protocol::{authenticate::Mechanism, ProtocolVersion},
     receiver::{self, Request},
-    Command, ResponseCode, StatusResponse,
+    Command, ResponseCode, ResponseType, StatusResponse,
 };
+use jmap::auth::oauth::OAuthScope;
+use mail_builder::encoders::base64::base64_encode;
 use mail_parser::decoders::base64::base64_decode;
 use mail_send::Credentials;
-use std::sync::Arc;
+use std::{sync::Arc, time::Instant};
 
-use crate::core::{Session, SessionData, State};
+use crate::core::{SaslExchange, Session, SessionData, State};
 
 impl<T: SessionStream> Session<T> {
     pub async fn handle_authenticate(&mut self, request: Request<Command>) -> trc::Result<()> {
@@ -56,6 +58,10 @@ impl<T: SessionStream> Session<T> {
                     self.write_bytes(b"+ \"\"\r\n".to_vec()).await
                 }
             }
+            Mechanism::ScramSha256 | Mechanism::ScramSha256Plus => {
+                self.handle_scram(args.mechanism, args.tag, args.params.pop())
+                    .await
+            }
             _ => Err(trc::AuthEvent::Error
                 .into_err()
                 .details("Authentication mechanism not supported.")
@@ -64,6 +70,121 @@ impl<T: SessionStream> Session<T> {
         }
     }
 
+    async fn handle_scram(
+        &mut self,
+        mechanism: Mechanism,
+        tag: String,
+        response: Option<String>,
+    ) -> trc::Result<()> {
+        let sasl = self.sasl.take();
+        let response = match response {
+            Some(response) if response == "*" => {
+                return Err(trc::AuthEvent::Error
+                    .into_err()
+                    .details("Authentication cancelled.")
+                    .id(tag)
+                    .ctx(trc::Key::Type, ResponseType::Bad));
+            }
+            Some(response) if response != "=" => {
+                base64_decode(response.as_bytes()).ok_or_else(|| {
+                    trc::AuthEvent::Error
+                        .into_err()
+                        .details("Failed to decode challenge.")
+                        .id(tag.clone())
+                        .code(ResponseCode::Parse)
+                })?
+            }
+            _ => Vec::new(),
+        };
+
+        match sasl {
+            Some(SaslExchange::Scram {
+                tag: pending_tag,
+                exchange,
+            }) if pending_tag == tag => {
+                // Verify the client proof
+                match self
+                    .jmap
+                    .authenticate_scram(
+                        &exchange,
+                        &response,
+                        self.tls_exporter.as_deref(),
+                        self.remote_addr,
+                        self.session_id,
+                    )
+                    .await
+                {
+                    Ok((access_token, server_final)) => {
+                        self.sasl = Some(SaslExchange::ScramVerified {
+                            tag: tag.clone(),
+                            access_token: Box::new(access_token),
+                        });
+                        self.sasl_continue(mechanism, tag, &server_final).await
+                    }
+                    Err(err) => self.complete_authentication(Err(err), tag).await,
+                }
+            }
+            Some(SaslExchange::ScramVerified {
+                tag: pending_tag,
+                access_token,
+            }) if pending_tag == tag => {
+                // The client acknowledged the server signature
+                self.complete_authentication(Ok(*access_token), tag).await
+            }
+            _ if response.is_empty() => self.sasl_continue(mechanism, tag, b"").await,
+            _ => {
+                // Throttle authentication requests
+                self.jmap
+                    .is_auth_allowed_soft(&self.remote_addr)
+                    .await
+                    .map_err(|err| err.id(tag.clone()))?;
+
+                let mut exchange = ScramExchange::parse(
+                    &response,
+                    mechanism == Mechanism::ScramSha256Plus,
+                    self.tls_exporter.is_some(),
+                )
+                .map_err(|err| {
+                    trc::AuthEvent::Error
+                        .into_err()
+                        .details(err)
+                        .id(tag.clone())
+                })?;
+                let server_first = self
+                    .jmap
+                    .core
+                    .scram_challenge(&self.jmap.core.storage.directory, &mut exchange)
+                    .await
+                    .map_err(|err| err.id(tag.clone()))?;
+                self.sasl = Some(SaslExchange::Scram {
+                    tag: tag.clone(),
+                    exchange: Box::new(exchange),
+                });
+                self.sasl_continue(mechanism, tag, &server_first).await
+            }
+        }
+    }
+
+    async fn sasl_continue(
+        &mut self,
+        mechanism: Mechanism,
+        tag: String,
+        challenge: &[u8],
+    ) -> trc::Result<()> {
+        self.receiver.request = receiver::Request {
+            tag,
+            command: Command::Authenticate,
+            tokens: vec![receiver::Token::Argument(mechanism.into_bytes())],
+        };
+        self.receiver.state = receiver::State::Argument { last_ch: b' ' };
+
+        let mut buf = Vec::with_capacity(challenge.len() * 4 / 3 + 8);
+        buf.extend_from_slice(b"+ ");
+        buf.extend_from_slice(&base64_encode(challenge).unwrap_or_default());
+        buf.extend_from_slice(b"\r\n");
+        self.write_bytes(buf).await
+    }
+
     pub async fn authenticate(
         &mut self,
         credentials: Credentials<String>,
@@ -79,21 +200,42 @@ impl<T: SessionStream> Session<T> {
         let access_token = match credentials {
             Credentials::Plain { username, secret } | Credentials::XOauth2 { username, secret } => {
                 self.jmap
-                    .authenticate_plain(&username, &secret, self.remote_addr, self.session_id)
+                    .authenticate_plain(
+                        &username,
+                        &secret,
+                        self.remote_addr,
+                        ServerProtocol::Imap,
+                        self.session_id,
+                    )
                     .await
             }
             Credentials::OAuthBearer { token } => {
                 match self
                     .jmap
-                    .validate_access_token("access_token", &token)
+                    .core
+                    .authenticate_access_token(&token, self.remote_addr, self.session_id)
                     .await
                 {
-                    Ok((account_id, _, _)) => self.jmap.core.get_access_token(account_id).await,
+                    Ok(token_info) if token_info.scopes.contains(OAuthScope::Imap) => {
+                        self.jmap.core.get_access_token(token_info.account_id).await
+                    }
+                    Ok(_) => Err(trc::AuthEvent::Failed
+                        .into_err()
+                        .details("Access token scope does not cover IMAP.")),
                     Err(err) => Err(err),
                 }
             }
-        }
-        .map_err(|err| {
+        };
+
+        self.complete_authentication(access_token, tag).await
+    }
+
+    async fn complete_authentication(
+        &mut self,
+        access_token: trc::Result<AccessToken>,
+        tag: String,
+    ) -> trc::Result<()> {
+        let access_token = access_token.map_err(|err| {
             if err.matches(trc::EventType::Auth(trc::AuthEvent::Failed)) {
                 let auth_failures = self.state.auth_failures();
                 if auth_failures < self.jmap.core.imap.max_auth_failures {
@@ -125,6 +267,13 @@ impl<T: SessionStream> Session<T> {
         // Validate access
         access_token.assert_has_permission(Permission::ImapAuthenticate)?;
 
+        // Enforce session limits
+        let session_in_flight = self
+            .jmap
+            .core
+            .is_session_allowed(&access_token, self.remote_addr)
+            .map_err(|err| err.id(tag.clone()))?;
+
         // Cache access token
         let access_token = Arc::new(access_token);
         self.jmap.core.cache_access_token(access_token.clone());
@@ -132,7 +281,7 @@ impl<T: SessionStream> Session<T> {
         // Create session
         self.state = State::Authenticated {
             data: Arc::new(
-                SessionData::new(self, access_token, in_flight)
+                SessionData::new(self, access_token, in_flight, session_in_flight)
                     .await
                     .map_err(|err| err.id(tag.clone()))?,
             ),
@@ -140,10 +289,7 @@ impl<T: SessionStream> Session<T> {
         self.write_bytes(
             StatusResponse::ok("Authentication successful")
                 .with_code(ResponseCode::Capability {
-                    capabilities: Capability::all_capabilities(
-                        true,
-                        !self.is_tls && self.instance.acceptor.is_tls(),
-                    ),
+                    capabilities: self.capabilities(true),
                 })
                 .with_tag(tag)
                 .into_bytes(),
@@ -152,8 +298,34 @@ impl<T: SessionStream> Session<T> {
     }
 
     pub async fn handle_unauthenticate(&mut self, request: Request<Command>) -> trc::Result<()> {
+        let op_start = Instant::now();
+
+        // Drop the access token, selected mailbox and mailbox cache of the current user
+        let data = self.state.session_data();
+        let account_id = data.account_id;
         self.state = State::NotAuthenticated { auth_failures: 0 };
 
+        // Wait for any in-flight commands so that their responses are not
+        // delivered once another user is authenticated, disconnect otherwise
+        if !data.wait_for_background_commands().await {
+            return Err(trc::NetworkEvent::Timeout
+                .into_err()
+                .details("Pending commands did not complete after UNAUTHENTICATE."));
+        }
+        drop(data);
+
+        // Extensions enabled by the previous user no longer apply
+        self.version = ProtocolVersion::Rev1;
+        self.is_condstore = false;
+        self.is_qresync = false;
+
+        trc::event!(
+            Imap(trc::ImapEvent::Unauthenticate),
+            SpanId = self.session_id,
+            AccountId = account_id,
+            Elapsed = op_start.elapsed()
+        );
+
         self.write_bytes(
             StatusResponse::completed(Command::Unauthenticate)
                 .with_tag(request.tag)
@@ -11,6 +11,7 @@ use common::listener::SessionStream;
 use directory::Permission;
 use imap_proto::{
     protocol::{
+        authenticate::Mechanism,
         capability::{Capability, Response},
         ImapResponse,
     },
@@ -37,10 +38,7 @@ impl<T: SessionStream> Session<T> {
                 .with_tag(request.tag)
                 .serialize(
                     Response {
-                        capabilities: Capability::all_capabilities(
-                            self.state.is_authenticated(),
-                            !self.is_tls && self.instance.acceptor.is_tls(),
-                        ),
+                        capabilities: self.capabilities(self.state.is_authenticated()),
                     }
                     .serialize(),
                 ),
@@ -48,6 +46,29 @@ impl<T: SessionStream> Session<T> {
         .await
     }
 
+    pub fn capabilities(&self, is_authenticated: bool) -> Vec<Capability> {
+        let mut capabilities = Capability::all_capabilities(
+            is_authenticated,
+            !self.is_tls && self.instance.acceptor.is_tls(),
+        );
+        if is_authenticated
+            && self
+                .jmap
+                .core
+                .imap
+                .is_unauthenticate_allowed(&self.remote_addr)
+        {
+            capabilities.push(Capability::UnAuthenticate);
+        }
+        if !is_authenticated && self.jmap.core.storage.directory.store_scram {
+            capabilities.push(Capability::Auth(Mechanism::ScramSha256));
+            if self.tls_exporter.is_some() {
+                capabilities.push(Capability::Auth(Mechanism::ScramSha256Plus));
+            }
+        }
+        capabilities
+    }
+
     pub async fn handle_id(&mut self, request: Request<Command>) -> trc::Result<()> {
         // Validate access
         self.assert_has_permission(Permission::ImapId)?;
@@ -0,0 +1,43 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use std::time::Instant;
+
+use crate::core::{Session, State};
+use common::listener::SessionStream;
+use imap_proto::{receiver::Request, Command, StatusResponse};
+
+impl<T: SessionStream> Session<T> {
+    pub async fn handle_compress(&mut self, request: Request<Command>) -> trc::Result<()> {
+        let op_start = Instant::now();
+        let arguments = request.parse_compress()?;
+
+        // Commands running in the background write their responses uncompressed,
+        // wait for them to finish before the stream is replaced.
+        if let State::Authenticated { data } | State::Selected { data, .. } = &self.state {
+            if !data.wait_for_background_commands().await {
+                return Err(trc::ImapEvent::Error
+                    .into_err()
+                    .details("Pending commands did not complete, try again later.")
+                    .id(arguments.tag));
+            }
+        }
+
+        trc::event!(
+            Imap(trc::ImapEvent::Compress),
+            SpanId = self.session_id,
+            Details = format!("{:?}", arguments.algorithm),
+            Elapsed = op_start.elapsed()
+        );
+
+        self.write_bytes(
+            StatusResponse::ok("DEFLATE active.")
+                .with_tag(arguments.tag)
+                .into_bytes(),
+        )
+        .await
+    }
+}
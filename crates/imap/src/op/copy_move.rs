@@ -17,12 +17,15 @@ use crate::{
     spawn_op,
 };
 use common::listener::SessionStream;
-use jmap::{email::set::TagManager, mailbox::UidMailbox};
+use jmap::{
+    email::{keywords::KeywordRegistry, set::TagManager},
+    mailbox::UidMailbox,
+};
 use jmap_proto::{
     error::set::SetErrorType,
     types::{
-        acl::Acl, collection::Collection, id::Id, property::Property, state::StateChange,
-        type_state::DataType,
+        acl::Acl, collection::Collection, id::Id, keyword::Keyword, property::Property,
+        state::StateChange, type_state::DataType,
     },
 };
 use store::{
@@ -30,7 +33,7 @@ use store::{
     write::{assert::HashedValue, log::ChangeLogBuilder, BatchBuilder, F_VALUE},
 };
 
-use super::ImapContext;
+use super::{keyword_response_code, ImapContext};
 
 impl<T: SessionStream> Session<T> {
     pub async fn handle_copy_move(
@@ -69,10 +72,21 @@ impl<T: SessionStream> Session<T> {
                         .id(arguments.tag));
                 };
 
-            // Check that the destination mailbox is not the same as the source mailbox.
+            // Check that the destination mailbox is not the same as the source mailbox,
+            // moving messages to the mailbox they are already in has no effect.
             if src_mailbox.id.account_id == dest_mailbox.account_id
                 && src_mailbox.id.mailbox_id == dest_mailbox.mailbox_id
             {
+                if is_move {
+                    return data
+                        .write_bytes(
+                            StatusResponse::completed(Command::Move(is_uid))
+                                .with_tag(arguments.tag)
+                                .into_bytes(),
+                        )
+                        .await;
+                }
+
                 return Err(trc::ImapEvent::Error
                     .into_err()
                     .details("Source and destination mailboxes are the same.")
@@ -190,6 +204,49 @@ impl<T: SessionStream> SessionData<T> {
                     continue;
                 }
 
+                // Validate the message keywords against the destination mailbox policy
+                let mut keywords = None;
+                let mut registry = KeywordRegistry::default();
+                if self.jmap.core.jmap.keyword_policy.is_some() {
+                    if let Some(current) = self
+                        .jmap
+                        .get_property::<HashedValue<Vec<Keyword>>>(
+                            account_id,
+                            Collection::Email,
+                            id,
+                            Property::Keywords,
+                        )
+                        .await
+                        .imap_ctx(&arguments.tag, trc::location!())?
+                    {
+                        let mut allowed = current.inner.clone();
+                        if let Some(err) = self
+                            .jmap
+                            .email_check_keywords(
+                                account_id,
+                                &[dest_mailbox_id.mailbox_id],
+                                &mut allowed,
+                                &mut registry,
+                            )
+                            .await
+                            .imap_ctx(&arguments.tag, trc::location!())?
+                        {
+                            response.rtype = ResponseType::No;
+                            response.code = Some(keyword_response_code(&err));
+                            response.message = err.description().into();
+                            continue;
+                        } else if allowed.len() != current.inner.len() {
+                            let mut tags = TagManager::new(current);
+                            for keyword in tags.current().to_vec() {
+                                if !allowed.contains(&keyword) {
+                                    tags.discard(&keyword);
+                                }
+                            }
+                            keywords = Some(tags);
+                        }
+                    }
+                }
+
                 // Add destination folder
                 mailboxes.update(dest_mailbox_id, true);
                 if is_move {
@@ -217,6 +274,9 @@ impl<T: SessionStream> SessionData<T> {
                     .with_collection(Collection::Email)
                     .update_document(id);
                 mailboxes.update_batch(&mut batch, Property::MailboxIds);
+                if let Some(keywords) = keywords {
+                    keywords.update_batch(&mut batch, Property::Keywords);
+                }
                 if changelog.change_id == u64::MAX {
                     changelog.change_id = self
                         .jmap
@@ -229,6 +289,10 @@ impl<T: SessionStream> SessionData<T> {
                     .write_batch(batch)
                     .await
                     .imap_ctx(&arguments.tag, trc::location!())?;
+                self.jmap
+                    .email_register_keywords(account_id, &mut registry)
+                    .await
+                    .imap_ctx(&arguments.tag, trc::location!())?;
                 changelog.log_update(Collection::Email, Id::from_parts(thread_id, id));
                 changelog.log_child_update(Collection::Mailbox, dest_mailbox_id.mailbox_id);
                 if is_move {
@@ -248,6 +312,32 @@ impl<T: SessionStream> SessionData<T> {
                 .await
                 .imap_ctx(&arguments.tag, trc::location!())?
                 .as_resource_token();
+
+            // Make sure the destination account can hold all the messages before
+            // copying any of them, so a failed move leaves the source untouched
+            let src_ids = ids.keys().copied().collect::<RoaringBitmap>();
+            let total_size = self
+                .calculate_mailbox_size(src_account_id, &src_ids)
+                .await
+                .imap_ctx(&arguments.tag, trc::location!())?;
+            if let Err(err) = self
+                .jmap
+                .has_available_quota(&resource_token, total_size)
+                .await
+            {
+                return Err(
+                    if err.matches(trc::EventType::Limit(trc::LimitEvent::Quota))
+                        || err.matches(trc::EventType::Limit(trc::LimitEvent::TenantQuota))
+                    {
+                        err.details("Destination account is over quota.")
+                            .code(ResponseCode::OverQuota)
+                    } else {
+                        err.caused_by(trc::location!())
+                    }
+                    .id(arguments.tag),
+                );
+            }
+
             let mut destroy_ids = RoaringBitmap::new();
             for (id, imap_id) in ids {
                 match self
@@ -325,6 +415,7 @@ impl<T: SessionStream> SessionData<T> {
                 .broadcast_state_change(
                     StateChange::new(src_mailbox.id.account_id)
                         .with_change(DataType::Email, change_id)
+                        .with_change(DataType::Thread, change_id)
                         .with_change(DataType::Mailbox, change_id),
                 )
                 .await;
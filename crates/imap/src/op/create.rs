@@ -151,7 +151,7 @@ impl<T: SessionStream> SessionData<T> {
         // Build response
         Ok(StatusResponse::ok("Mailbox created.")
             .with_code(ResponseCode::MailboxId {
-                mailbox_id: Id::from_parts(params.account_id, parent_id - 1).to_string(),
+                mailbox_id: Id::from(parent_id - 1).to_string(),
             })
             .with_tag(arguments.tag))
     }
@@ -230,6 +230,7 @@ impl<T: SessionStream> SessionData<T> {
                     uid_validity: None,
                     uid_next: None,
                     size: 0.into(),
+                    total_deleted_storage: 0.into(),
                     special_use: if pos == params.path.len() - 1 {
                         params.special_use
                     } else {
@@ -15,7 +15,7 @@ use imap_proto::{
 };
 use trc::AddContext;
 
-use crate::core::{ImapId, SavedSearch, SelectedMailbox, Session, SessionData};
+use crate::core::{ImapId, SelectedMailbox, Session, SessionData};
 use common::listener::SessionStream;
 use jmap::{email::set::TagManager, mailbox::UidMailbox};
 use jmap_proto::types::{
@@ -87,14 +87,14 @@ impl<T: SessionStream> Session<T> {
             .await
             .imap_ctx(&request.tag, trc::location!())?;
 
-        // Clear saved searches
-        *mailbox.saved_search.lock() = SavedSearch::None;
-
         // Synchronize messages
         let modseq = data
             .write_mailbox_changes(&mailbox, self.is_qresync)
             .await
             .imap_ctx(&request.tag, trc::location!())?;
+
+        // Remove expunged messages from the saved search (RFC 5182)
+        mailbox.prune_saved_search();
         let mut response =
             StatusResponse::completed(Command::Expunge(is_uid)).with_tag(request.tag);
 
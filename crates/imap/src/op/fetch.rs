@@ -11,7 +11,7 @@ use crate::{
     spawn_op,
 };
 use ahash::AHashMap;
-use common::listener::SessionStream;
+use common::{auth::bandwidth::Transfer, listener::SessionStream};
 use directory::Permission;
 use imap_proto::{
     parser::PushUnique,
@@ -236,7 +236,7 @@ impl<T: SessionStream> SessionData<T> {
                     }
                     needs_blobs = true;
                 }
-                Attribute::ThreadId => {
+                Attribute::EmailId | Attribute::ThreadId => {
                     needs_thread_id = true;
                 }
                 _ => (),
@@ -276,6 +276,26 @@ impl<T: SessionStream> SessionData<T> {
             .iter()
             .map(|id| trc::Value::from(id.2))
             .collect::<Vec<_>>();
+        let mut bytes_served = 0;
+
+        // Obtain the timezone of the received dates
+        let timezones = if arguments.attributes.contains(&Attribute::InternalDate) {
+            let mut document_ids = ids.iter().map(|(_, _, id)| *id).collect::<Vec<_>>();
+            document_ids.sort_unstable();
+            self.jmap
+                .get_properties::<i64, _, _>(
+                    account_id,
+                    Collection::Email,
+                    &document_ids,
+                    Property::Timezone,
+                )
+                .await
+                .imap_ctx(&arguments.tag, trc::location!())?
+                .into_iter()
+                .collect::<AHashMap<_, _>>()
+        } else {
+            AHashMap::new()
+        };
 
         for (seqnum, uid, id) in ids {
             // Obtain attributes and keywords
@@ -380,6 +400,7 @@ impl<T: SessionStream> SessionData<T> {
                     Attribute::InternalDate => {
                         items.push(DataItem::InternalDate {
                             date: email.received_at as i64,
+                            offset: timezones.get(&id).copied().unwrap_or_default() as i32,
                         });
                     }
                     Attribute::Preview { .. } => {
@@ -488,12 +509,12 @@ impl<T: SessionStream> SessionData<T> {
                     }
                     Attribute::EmailId => {
                         items.push(DataItem::EmailId {
-                            email_id: Id::from_parts(account_id, id).to_string(),
+                            email_id: Id::from_parts(thread_id, id).to_string(),
                         });
                     }
                     Attribute::ThreadId => {
                         items.push(DataItem::ThreadId {
-                            thread_id: Id::from_parts(account_id, thread_id).to_string(),
+                            thread_id: Id::from(thread_id).to_string(),
                         });
                     }
                 }
@@ -513,6 +534,7 @@ impl<T: SessionStream> SessionData<T> {
             // Serialize fetch item
             let mut buf = Vec::with_capacity(128);
             FetchItem { id: seqnum, items }.serialize(&mut buf);
+            bytes_served += buf.len();
             self.write_bytes(buf).await?;
 
             // Add to set flags
@@ -566,6 +588,10 @@ impl<T: SessionStream> SessionData<T> {
             }
         }
 
+        self.jmap
+            .core
+            .record_bandwidth(self.account_id, Transfer::Out, bytes_served);
+
         trc::event!(
             Imap(trc::ImapEvent::Fetch),
             SpanId = self.session_id,
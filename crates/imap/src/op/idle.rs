@@ -70,7 +70,24 @@ impl<T: SessionStream> Session<T> {
 
         let op_start = Instant::now();
         let mut buf = vec![0; 4];
+        let mut idle_deadline = self
+            .jmap
+            .core
+            .imap
+            .idle_max_duration
+            .map(|max_duration| op_start + max_duration);
+        let mut done_requested = false;
+        let mut revocation_rx = self.jmap.core.subscribe_session_revocation(data.account_id);
         loop {
+            let idle_limit = async move {
+                match idle_deadline {
+                    Some(deadline) => {
+                        tokio::time::sleep(deadline.saturating_duration_since(Instant::now())).await
+                    }
+                    None => std::future::pending().await,
+                }
+            };
+
             tokio::select! {
                 result = tokio::time::timeout(self.jmap.core.imap.timeout_idle, self.stream_rx.read_exact(&mut buf)) => {
                     match result {
@@ -95,6 +112,21 @@ impl<T: SessionStream> Session<T> {
                         }
                     }
                 }
+                _ = idle_limit => {
+                    if !done_requested {
+                        // Ask the client to renew the IDLE command
+                        done_requested = true;
+                        idle_deadline = Some(Instant::now() + self.jmap.core.imap.timeout_idle_done);
+                        self.write_bytes(&b"* OK IDLE time limit reached, send 'DONE' to continue.\r\n"[..]).await?;
+                    } else {
+                        self.write_bytes(&b"* BYE IDLE time limit exceeded.\r\n"[..]).await.ok();
+                        return Err(trc::NetworkEvent::Timeout.into_err().details("IMAP IDLE time limit exceeded.").id(request.tag));
+                    }
+                }
+                _ = revocation_rx.changed() => {
+                    self.write_bytes(&b"* BYE Session revoked.\r\n"[..]).await.ok();
+                    return Err(trc::NetworkEvent::Closed.into_err().details("IMAP session revoked.").id(request.tag));
+                }
                 state_change = change_rx.recv() => {
                     if let Some(state_change) = state_change {
                         let mut has_mailbox_changes = false;
@@ -6,12 +6,14 @@
 
 use ::store::query::log::Query;
 use imap_proto::ResponseCode;
+use jmap::email::keywords::KeywordError;
 
 pub mod acl;
 pub mod append;
 pub mod authenticate;
 pub mod capability;
 pub mod close;
+pub mod compress;
 pub mod copy_move;
 pub mod create;
 pub mod delete;
@@ -24,7 +26,9 @@ pub mod login;
 pub mod logout;
 pub mod namespace;
 pub mod noop;
+pub mod quota;
 pub mod rename;
+pub mod replace;
 pub mod search;
 pub mod select;
 pub mod status;
@@ -56,6 +60,20 @@ impl ToModSeq for Option<u64> {
     }
 }
 
+fn keyword_error(err: KeywordError) -> trc::Error {
+    trc::ImapEvent::Error
+        .into_err()
+        .details(err.description())
+        .code(keyword_response_code(&err))
+}
+
+fn keyword_response_code(err: &KeywordError) -> ResponseCode {
+    match err {
+        KeywordError::NotAllowed(_) => ResponseCode::Cannot,
+        KeywordError::TooManyKeywords(_) => ResponseCode::Limit,
+    }
+}
+
 #[macro_export]
 macro_rules! spawn_op {
     ($data:expr, $($code:tt)*) => {
@@ -0,0 +1,268 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use std::time::Instant;
+
+use common::listener::SessionStream;
+use directory::{
+    backend::internal::{
+        manage::{ManageDirectory, UpdatePrincipal},
+        PrincipalField, PrincipalUpdate, PrincipalValue,
+    },
+    Permission, QueryBy,
+};
+use imap_proto::{
+    protocol::quota::{QuotaResponse, QuotaRootResponse, Resource, ResourceUsage},
+    receiver::Request,
+    Command, ResponseCode, StatusResponse,
+};
+
+use crate::{
+    core::{Session, SessionData},
+    op::ImapContext,
+    spawn_op,
+};
+
+impl<T: SessionStream> Session<T> {
+    pub async fn handle_get_quota(&mut self, request: Request<Command>) -> trc::Result<()> {
+        // Validate access
+        self.assert_has_permission(Permission::ImapGetQuota)?;
+
+        let op_start = Instant::now();
+        let arguments = request.parse_quota(self.version)?;
+        let data = self.state.session_data();
+
+        spawn_op!(data, {
+            let is_admin = data.access_token.has_permission(Permission::ImapSetQuota);
+            let account_id = data
+                .quota_root_to_account_id(&arguments.name, is_admin)
+                .await
+                .imap_ctx(&arguments.tag, trc::location!())?;
+            let quota = data
+                .get_quota(arguments.name, account_id)
+                .await
+                .imap_ctx(&arguments.tag, trc::location!())?;
+
+            trc::event!(
+                Imap(trc::ImapEvent::GetQuota),
+                SpanId = data.session_id,
+                AccountId = account_id,
+                Elapsed = op_start.elapsed()
+            );
+
+            data.write_bytes(
+                StatusResponse::completed(Command::GetQuota)
+                    .with_tag(arguments.tag)
+                    .serialize(quota.into_bytes()),
+            )
+            .await
+        })
+    }
+
+    pub async fn handle_get_quota_root(&mut self, request: Request<Command>) -> trc::Result<()> {
+        // Validate access
+        self.assert_has_permission(Permission::ImapGetQuota)?;
+
+        let op_start = Instant::now();
+        let arguments = request.parse_quota(self.version)?;
+        let is_rev2 = self.version.is_rev2();
+        let data = self.state.session_data();
+
+        spawn_op!(data, {
+            // Refresh mailboxes
+            data.synchronize_mailboxes(false)
+                .await
+                .imap_ctx(&arguments.tag, trc::location!())?;
+
+            // Each account has a single quota root covering all its mailboxes
+            let mailbox = if let Some(mailbox) = data.get_mailbox_by_name(&arguments.name) {
+                mailbox
+            } else {
+                return Err(trc::ImapEvent::Error
+                    .into_err()
+                    .details("Mailbox does not exist.")
+                    .code(ResponseCode::NonExistent)
+                    .id(arguments.tag));
+            };
+            let quota_root = data.account_id_to_quota_root(mailbox.account_id);
+            let quota = data
+                .get_quota(quota_root, mailbox.account_id)
+                .await
+                .imap_ctx(&arguments.tag, trc::location!())?;
+
+            trc::event!(
+                Imap(trc::ImapEvent::GetQuota),
+                SpanId = data.session_id,
+                MailboxName = arguments.name.clone(),
+                AccountId = mailbox.account_id,
+                Elapsed = op_start.elapsed()
+            );
+
+            data.write_bytes(
+                StatusResponse::completed(Command::GetQuotaRoot)
+                    .with_tag(arguments.tag)
+                    .serialize(
+                        QuotaRootResponse {
+                            mailbox_name: arguments.name,
+                            quotas: vec![quota],
+                        }
+                        .into_bytes(is_rev2),
+                    ),
+            )
+            .await
+        })
+    }
+
+    pub async fn handle_set_quota(&mut self, request: Request<Command>) -> trc::Result<()> {
+        // Validate access
+        self.assert_has_permission(Permission::ImapSetQuota)?;
+
+        let op_start = Instant::now();
+        let arguments = request.parse_quota(self.version)?;
+        let data = self.state.session_data();
+
+        spawn_op!(data, {
+            // Only storage limits are supported
+            let mut limit = 0;
+            for (resource, value) in &arguments.limits {
+                if *resource == Resource::Storage {
+                    limit = value.saturating_mul(1024);
+                } else {
+                    return Err(trc::ImapEvent::Error
+                        .into_err()
+                        .details(format!("Resource {} is not supported.", resource.as_str()))
+                        .code(ResponseCode::Cannot)
+                        .id(arguments.tag));
+                }
+            }
+
+            let account_id = data
+                .quota_root_to_account_id(&arguments.name, true)
+                .await
+                .imap_ctx(&arguments.tag, trc::location!())?;
+            data.jmap
+                .core
+                .storage
+                .data
+                .update_principal(
+                    UpdatePrincipal::by_id(account_id)
+                        .with_updates(vec![PrincipalUpdate::set(
+                            PrincipalField::Quota,
+                            PrincipalValue::Integer(limit),
+                        )])
+                        .with_tenant(data.access_token.tenant.map(|t| t.id)),
+                )
+                .await
+                .imap_ctx(&arguments.tag, trc::location!())?;
+
+            // Access tokens carry the account quota
+            data.jmap.core.security.access_tokens.remove(&account_id);
+            data.jmap.core.invalidate_auth_cache(account_id, &[]).await;
+
+            let quota = data
+                .get_quota(arguments.name, account_id)
+                .await
+                .imap_ctx(&arguments.tag, trc::location!())?;
+
+            trc::event!(
+                Imap(trc::ImapEvent::SetQuota),
+                SpanId = data.session_id,
+                AccountId = account_id,
+                Limit = limit,
+                Elapsed = op_start.elapsed()
+            );
+
+            data.write_bytes(
+                StatusResponse::completed(Command::SetQuota)
+                    .with_tag(arguments.tag)
+                    .serialize(quota.into_bytes()),
+            )
+            .await
+        })
+    }
+}
+
+impl<T: SessionStream> SessionData<T> {
+    async fn quota_root_to_account_id(&self, quota_root: &str, is_admin: bool) -> trc::Result<u32> {
+        // The empty quota root belongs to the user's own account
+        if quota_root.is_empty() {
+            return Ok(self.account_id);
+        }
+
+        // Shared accounts are named after their owner
+        let shared_prefix = format!("{}/{}", self.jmap.core.jmap.shared_folder, quota_root);
+        if let Some(account_id) = self
+            .mailboxes
+            .lock()
+            .iter()
+            .find(|account| account.prefix.as_ref() == Some(&shared_prefix))
+            .map(|account| account.account_id)
+        {
+            return Ok(account_id);
+        }
+
+        // Administrators can refer to any account by name
+        if is_admin {
+            if let Some(principal) = self
+                .jmap
+                .core
+                .storage
+                .directory
+                .query(QueryBy::Name(quota_root), false)
+                .await?
+            {
+                return Ok(principal.id());
+            }
+        }
+
+        Err(trc::ImapEvent::Error
+            .into_err()
+            .details("Quota root does not exist.")
+            .code(ResponseCode::NonExistent))
+    }
+
+    fn account_id_to_quota_root(&self, account_id: u32) -> String {
+        if account_id == self.account_id {
+            return String::new();
+        }
+
+        let shared_folder = &self.jmap.core.jmap.shared_folder;
+        self.mailboxes
+            .lock()
+            .iter()
+            .find(|account| account.account_id == account_id)
+            .and_then(|account| account.prefix.as_deref())
+            .and_then(|prefix| prefix.strip_prefix(shared_folder.as_str()))
+            .and_then(|name| name.strip_prefix('/'))
+            .unwrap_or_default()
+            .to_string()
+    }
+
+    async fn get_quota(&self, quota_root: String, account_id: u32) -> trc::Result<QuotaResponse> {
+        // Usage is read from the same counter used to enforce quotas on delivery
+        let access_token = self.get_access_token().await?;
+        let limit = self
+            .jmap
+            .get_resource_token(&access_token, account_id)
+            .await?
+            .quota;
+        let usage = self.jmap.get_used_quota(account_id).await?.max(0) as u64;
+
+        // Storage is reported in units of 1024 octets (RFC 9208)
+        Ok(QuotaResponse {
+            quota_root,
+            resources: if limit > 0 {
+                vec![ResourceUsage {
+                    resource: Resource::Storage,
+                    usage: usage.div_ceil(1024),
+                    limit: limit / 1024,
+                }]
+            } else {
+                vec![]
+            },
+        })
+    }
+}
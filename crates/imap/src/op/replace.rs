@@ -0,0 +1,126 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use std::time::Instant;
+
+use directory::Permission;
+use imap_proto::{protocol::append, receiver::Request, Command, ResponseCode, StatusResponse};
+
+use crate::{core::Session, spawn_op};
+use common::listener::SessionStream;
+use jmap_proto::types::acl::Acl;
+
+use super::ImapContext;
+
+impl<T: SessionStream> Session<T> {
+    pub async fn handle_replace(
+        &mut self,
+        request: Request<Command>,
+        is_uid: bool,
+    ) -> trc::Result<()> {
+        // Validate access
+        self.assert_has_permission(Permission::ImapAppend)?;
+        self.assert_has_permission(Permission::ImapExpunge)?;
+
+        let op_start = Instant::now();
+        let arguments = request.parse_replace(self.version)?;
+        let (data, src_mailbox) = self.state.mailbox_state();
+        let is_condstore = self.is_condstore || src_mailbox.is_condstore;
+        let is_qresync = self.is_qresync;
+
+        spawn_op!(data, {
+            // Refresh mailboxes
+            data.synchronize_mailboxes(false)
+                .await
+                .imap_ctx(&arguments.tag, trc::location!())?;
+
+            // Obtain mailbox
+            let mailbox =
+                if let Some(mailbox) = data.get_mailbox_by_name(&arguments.mailbox_name) {
+                    mailbox
+                } else {
+                    return Err(trc::ImapEvent::Error
+                        .into_err()
+                        .details("Mailbox does not exist.")
+                        .code(ResponseCode::TryCreate)
+                        .id(arguments.tag));
+                };
+
+            // Obtain the message to replace
+            let document_id = if let Some(document_id) = src_mailbox
+                .sequence_to_ids(&arguments.sequence, is_uid)
+                .await
+                .imap_ctx(&arguments.tag, trc::location!())?
+                .into_keys()
+                .next()
+            {
+                document_id
+            } else {
+                return Err(trc::ImapEvent::Error
+                    .into_err()
+                    .details("No message was found.")
+                    .id(arguments.tag));
+            };
+
+            // Verify that the user can remove messages from the selected mailbox
+            if !data
+                .check_mailbox_acl(
+                    src_mailbox.id.account_id,
+                    src_mailbox.id.mailbox_id,
+                    Acl::RemoveItems,
+                )
+                .await
+                .imap_ctx(&arguments.tag, trc::location!())?
+            {
+                return Err(trc::ImapEvent::Error
+                    .into_err()
+                    .details(concat!(
+                        "You do not have the required permissions ",
+                        "to remove messages from this mailbox."
+                    ))
+                    .code(ResponseCode::NoPerm)
+                    .id(arguments.tag));
+            }
+
+            // Append the new message and expunge the replaced one
+            let mut response = data
+                .append_messages(
+                    append::Arguments {
+                        tag: arguments.tag,
+                        mailbox_name: arguments.mailbox_name,
+                        messages: vec![arguments.message],
+                    },
+                    src_mailbox.clone().into(),
+                    mailbox,
+                    is_condstore,
+                    op_start,
+                    document_id.into(),
+                )
+                .await?;
+            let tag = response.tag.take().unwrap_or_default();
+
+            // The APPENDUID is sent before the EXPUNGE of the replaced message (RFC 8508)
+            if let Some(code) = response.code.take() {
+                data.write_bytes(
+                    StatusResponse::ok("Replacement message ready")
+                        .with_code(code)
+                        .into_bytes(),
+                )
+                .await?;
+            }
+            data.write_mailbox_changes(&src_mailbox, is_qresync)
+                .await
+                .imap_ctx(&tag, trc::location!())?;
+
+            data.write_bytes(
+                StatusResponse::completed(Command::Replace(is_uid))
+                    .with_tag(tag)
+                    .into_bytes(),
+            )
+            .await
+        })
+    }
+}
@@ -278,6 +278,12 @@ impl<T: SessionStream> SessionData<T> {
                 FilterGroup::Fts(conds) => {
                     let mut fts_filters = Vec::with_capacity(filters.len());
                     for cond in conds {
+                        let (cond, is_fuzzy) = match cond {
+                            search::Filter::Fuzzy(cond) => (*cond, true),
+                            cond => (cond, false),
+                        };
+                        let language = self.jmap.core.jmap.default_language;
+
                         match cond {
                             search::Filter::Bcc(text) => {
                                 fts_filters.push(FtsFilter::has_text(
@@ -287,11 +293,13 @@ impl<T: SessionStream> SessionData<T> {
                                 ));
                             }
                             search::Filter::Body(text) => {
-                                fts_filters.push(FtsFilter::has_text_detect(
+                                push_text_filter(
+                                    &mut fts_filters,
                                     Field::Body,
-                                    text,
-                                    self.jmap.core.jmap.default_language,
-                                ));
+                                    &text,
+                                    language,
+                                    is_fuzzy,
+                                );
                             }
                             search::Filter::Cc(text) => {
                                 fts_filters.push(FtsFilter::has_text(
@@ -347,11 +355,13 @@ impl<T: SessionStream> SessionData<T> {
                                 }
                             }
                             search::Filter::Subject(text) => {
-                                fts_filters.push(FtsFilter::has_text_detect(
+                                push_text_filter(
+                                    &mut fts_filters,
                                     Field::Header(HeaderName::Subject),
-                                    text,
-                                    self.jmap.core.jmap.default_language,
-                                ));
+                                    &text,
+                                    language,
+                                    is_fuzzy,
+                                );
                             }
                             search::Filter::Text(text) => {
                                 fts_filters.push(FtsFilter::Or);
@@ -375,21 +385,19 @@ impl<T: SessionStream> SessionData<T> {
                                     &text,
                                     Language::None,
                                 ));
-                                fts_filters.push(FtsFilter::has_text_detect(
+                                for field in [
                                     Field::Header(HeaderName::Subject),
-                                    &text,
-                                    self.jmap.core.jmap.default_language,
-                                ));
-                                fts_filters.push(FtsFilter::has_text_detect(
                                     Field::Body,
-                                    &text,
-                                    self.jmap.core.jmap.default_language,
-                                ));
-                                fts_filters.push(FtsFilter::has_text_detect(
                                     Field::Attachment,
-                                    text,
-                                    self.jmap.core.jmap.default_language,
-                                ));
+                                ] {
+                                    push_text_filter(
+                                        &mut fts_filters,
+                                        field,
+                                        &text,
+                                        language,
+                                        is_fuzzy,
+                                    );
+                                }
                                 fts_filters.push(FtsFilter::End);
                             }
                             search::Filter::To(text) => {
@@ -682,6 +690,26 @@ impl SelectedMailbox {
         Some(v.clone())
     }
 
+    pub fn prune_saved_search(&self) {
+        let mut saved_search = self.saved_search.lock();
+        if let SavedSearch::Results { items } = &*saved_search {
+            let state = self.state.lock();
+            let items = items
+                .iter()
+                .filter_map(|imap_id| {
+                    state
+                        .uid_to_id
+                        .get(&imap_id.uid)
+                        .and_then(|id| state.id_to_imap.get(id))
+                        .copied()
+                })
+                .collect::<Vec<_>>();
+            *saved_search = SavedSearch::Results {
+                items: Arc::new(items),
+            };
+        }
+    }
+
     #[allow(clippy::too_many_arguments)]
     pub fn map_search_results(
         &self,
@@ -769,3 +797,27 @@ impl SavedSearch {
         }
     }
 }
+
+// Text searches match stemmed words in the detected language, fuzzy searches
+// also match stems in the default language as detection is unreliable on
+// short search terms
+fn push_text_filter<T: Into<u8> + std::fmt::Display + Clone + std::fmt::Debug>(
+    filters: &mut Vec<FtsFilter<T>>,
+    field: Field<T>,
+    text: &str,
+    default_language: Language,
+    is_fuzzy: bool,
+) {
+    if is_fuzzy {
+        filters.push(FtsFilter::Or);
+        filters.push(FtsFilter::has_text_detect(
+            field.clone(),
+            text,
+            default_language,
+        ));
+        filters.push(FtsFilter::has_text(field, text, default_language));
+        filters.push(FtsFilter::End);
+    } else {
+        filters.push(FtsFilter::has_text_detect(field, text, default_language));
+    }
+}
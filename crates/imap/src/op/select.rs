@@ -86,6 +86,7 @@ impl<T: SessionStream> Session<T> {
             let uid_validity = state.uid_validity;
             let uid_next = state.uid_next;
             let total_messages = state.total_messages;
+            let is_uid_sticky = data.is_uid_sticky(&mailbox);
             let highest_modseq = if is_condstore {
                 HighestModSeq::new(state.modseq.to_modseq()).into()
             } else {
@@ -97,6 +98,7 @@ impl<T: SessionStream> Session<T> {
                 saved_search: parking_lot::Mutex::new(SavedSearch::None),
                 is_select,
                 is_condstore,
+                is_uid_sticky,
             });
 
             // Validate QRESYNC arguments
@@ -158,13 +160,22 @@ impl<T: SessionStream> Session<T> {
                 closed_previous,
                 is_rev2,
                 highest_modseq,
-                mailbox_id: Id::from_parts(mailbox.id.account_id, mailbox.id.mailbox_id)
-                    .to_string(),
+                mailbox_id: Id::from(mailbox.id.mailbox_id).to_string(),
             };
 
             // Update state
             self.state = State::Selected { data, mailbox };
 
+            // Warn clients not to cache UIDs (RFC 4315)
+            if !is_uid_sticky {
+                self.write_bytes(
+                    StatusResponse::no("Non-persistent UIDs")
+                        .with_code(ResponseCode::UidNotSticky)
+                        .into_bytes(),
+                )
+                .await?;
+            }
+
             self.write_bytes(
                 StatusResponse::completed(command)
                     .with_tag(arguments.tag)
@@ -19,10 +19,7 @@ use imap_proto::{
     receiver::Request,
     Command, ResponseCode, StatusResponse,
 };
-use jmap_proto::{
-    object::Object,
-    types::{collection::Collection, id::Id, keyword::Keyword, property::Property, value::Value},
-};
+use jmap_proto::types::{collection::Collection, id::Id, keyword::Keyword, property::Property};
 use store::{
     roaring::RoaringBitmap,
     write::{key::DeserializeBigEndian, ValueClass},
@@ -106,6 +103,7 @@ impl<T: SessionStream> SessionData<T> {
                                     | Status::Unseen
                                     | Status::Recent
                                     | Status::Deleted
+                                    | Status::DeletedStorage
                                     | Status::HighestModSeq => StatusItemType::Number(0),
                                     Status::UidNext | Status::UidValidity => {
                                         StatusItemType::Number(1)
@@ -174,7 +172,14 @@ impl<T: SessionStream> SessionData<T> {
                         }
                         Status::Size => {
                             if let Some(value) = mailbox_state.size {
-                                items_response.push((*item, StatusItemType::Number(value as u64)));
+                                items_response.push((*item, StatusItemType::Number(value)));
+                            } else {
+                                items_update.push_unique(*item);
+                            }
+                        }
+                        Status::DeletedStorage => {
+                            if let Some(value) = mailbox_state.total_deleted_storage {
+                                items_response.push((*item, StatusItemType::Number(value)));
                             } else {
                                 items_update.push_unique(*item);
                             }
@@ -188,10 +193,7 @@ impl<T: SessionStream> SessionData<T> {
                         Status::MailboxId => {
                             items_response.push((
                                 *item,
-                                StatusItemType::String(
-                                    Id::from_parts(mailbox.account_id, mailbox.mailbox_id)
-                                        .to_string(),
-                                ),
+                                StatusItemType::String(Id::from(mailbox.mailbox_id).to_string()),
                             ));
                         }
                         Status::Recent => {
@@ -227,6 +229,40 @@ impl<T: SessionStream> SessionData<T> {
                 .await
                 .caused_by(trc::location!())?;
 
+            // Deleted messages are counted from the keyword index, the storage they
+            // use is obtained in the same pass over the size index as SIZE
+            let mut deleted_ids = RoaringBitmap::new();
+            if let Some(mailbox_message_ids) = mailbox_message_ids.as_deref().filter(|_| {
+                items_update
+                    .iter()
+                    .any(|item| matches!(item, Status::Deleted | Status::DeletedStorage))
+            }) {
+                if let Some(keyword_ids) = self
+                    .jmap
+                    .get_tag(
+                        mailbox.account_id,
+                        Collection::Email,
+                        Property::Keywords,
+                        Keyword::Deleted,
+                    )
+                    .await
+                    .caused_by(trc::location!())?
+                {
+                    deleted_ids = keyword_ids & mailbox_message_ids;
+                }
+            }
+            let (size, deleted_size) = match mailbox_message_ids.as_deref().filter(|_| {
+                items_update
+                    .iter()
+                    .any(|item| matches!(item, Status::Size | Status::DeletedStorage))
+            }) {
+                Some(mailbox_message_ids) => self
+                    .calculate_mailbox_usage(mailbox.account_id, mailbox_message_ids, &deleted_ids)
+                    .await
+                    .caused_by(trc::location!())?,
+                None => (0, 0),
+            };
+
             for item in items_update {
                 let result = match item {
                     Status::Messages => mailbox_message_ids.as_ref().map(|v| v.len()).unwrap_or(0),
@@ -246,25 +282,7 @@ impl<T: SessionStream> SessionData<T> {
                             .caused_by(trc::location!())?
                             + 1) as u64
                     }
-                    Status::UidValidity => self
-                        .jmap
-                        .get_property::<Object<Value>>(
-                            mailbox.account_id,
-                            Collection::Mailbox,
-                            mailbox.mailbox_id,
-                            &Property::Value,
-                        )
-                        .await?
-                        .and_then(|obj| obj.get(&Property::Cid).as_uint())
-                        .ok_or_else(|| {
-                            trc::StoreEvent::UnexpectedError
-                                .into_err()
-                                .details("Mailbox unavailable")
-                                .ctx(trc::Key::Reason, "Failed to obtain uid validity")
-                                .caused_by(trc::location!())
-                                .account_id(mailbox.account_id)
-                                .document_id(mailbox.mailbox_id)
-                        })?,
+                    Status::UidValidity => self.get_uid_validity(&mailbox).await? as u64,
                     Status::Unseen => {
                         if let (Some(message_ids), Some(mailbox_message_ids)) =
                             (&message_ids, &mailbox_message_ids)
@@ -290,34 +308,9 @@ impl<T: SessionStream> SessionData<T> {
                             0
                         }
                     }
-                    Status::Deleted => {
-                        if let (Some(mailbox_message_ids), Some(mut deleted)) = (
-                            &mailbox_message_ids,
-                            self.jmap
-                                .get_tag(
-                                    mailbox.account_id,
-                                    Collection::Email,
-                                    Property::Keywords,
-                                    Keyword::Deleted,
-                                )
-                                .await
-                                .caused_by(trc::location!())?,
-                        ) {
-                            deleted &= mailbox_message_ids.as_ref();
-                            deleted.len()
-                        } else {
-                            0
-                        }
-                    }
-                    Status::Size => {
-                        if let Some(mailbox_message_ids) = &mailbox_message_ids {
-                            self.calculate_mailbox_size(mailbox.account_id, mailbox_message_ids)
-                                .await
-                                .caused_by(trc::location!())? as u64
-                        } else {
-                            0
-                        }
-                    }
+                    Status::Deleted => deleted_ids.len(),
+                    Status::Size => size,
+                    Status::DeletedStorage => deleted_size,
                     Status::Recent => {
                         self.fetch_messages(&mailbox).await?;
                         0
@@ -328,7 +321,7 @@ impl<T: SessionStream> SessionData<T> {
                 };
 
                 items_response.push((item, StatusItemType::Number(result)));
-                values_update.push((item, result as u32));
+                values_update.push((item, result));
             }
 
             // Update cache
@@ -341,12 +334,19 @@ impl<T: SessionStream> SessionData<T> {
 
                     for (item, value) in values_update {
                         match item {
-                            Status::Messages => mailbox_state.total_messages = value.into(),
-                            Status::UidNext => mailbox_state.uid_next = value.into(),
-                            Status::UidValidity => mailbox_state.uid_validity = value.into(),
-                            Status::Unseen => mailbox_state.total_unseen = value.into(),
-                            Status::Deleted => mailbox_state.total_deleted = value.into(),
+                            Status::Messages => {
+                                mailbox_state.total_messages = (value as u32).into()
+                            }
+                            Status::UidNext => mailbox_state.uid_next = (value as u32).into(),
+                            Status::UidValidity => {
+                                mailbox_state.uid_validity = (value as u32).into()
+                            }
+                            Status::Unseen => mailbox_state.total_unseen = (value as u32).into(),
+                            Status::Deleted => mailbox_state.total_deleted = (value as u32).into(),
                             Status::Size => mailbox_state.size = value.into(),
+                            Status::DeletedStorage => {
+                                mailbox_state.total_deleted_storage = value.into()
+                            }
                             Status::Recent => {
                                 items_response
                                     .iter_mut()
@@ -372,12 +372,25 @@ impl<T: SessionStream> SessionData<T> {
         })
     }
 
-    async fn calculate_mailbox_size(
+    pub(crate) async fn calculate_mailbox_size(
+        &self,
+        account_id: u32,
+        message_ids: &RoaringBitmap,
+    ) -> trc::Result<u64> {
+        self.calculate_mailbox_usage(account_id, message_ids, &RoaringBitmap::new())
+            .await
+            .map(|(total_size, _)| total_size)
+    }
+
+    // Returns the total size of the messages and the size of the subset of them
+    async fn calculate_mailbox_usage(
         &self,
         account_id: u32,
-        message_ids: &Arc<RoaringBitmap>,
-    ) -> trc::Result<u32> {
-        let mut total_size = 0u32;
+        message_ids: &RoaringBitmap,
+        subset_ids: &RoaringBitmap,
+    ) -> trc::Result<(u64, u64)> {
+        let mut total_size = 0u64;
+        let mut subset_size = 0u64;
         self.jmap
             .core
             .storage
@@ -406,7 +419,10 @@ impl<T: SessionStream> SessionData<T> {
                             .ok_or_else(|| trc::Error::corrupted_key(key, None, trc::location!()))
                             .and_then(u32::deserialize)
                             .map(|size| {
-                                total_size += size;
+                                total_size += size as u64;
+                                if subset_ids.contains(document_id) {
+                                    subset_size += size as u64;
+                                }
                             })?;
                     }
                     Ok(true)
@@ -414,6 +430,6 @@ impl<T: SessionStream> SessionData<T> {
             )
             .await
             .caused_by(trc::location!())
-            .map(|_| total_size)
+            .map(|_| (total_size, subset_size))
     }
 }
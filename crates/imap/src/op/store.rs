@@ -22,7 +22,10 @@ use imap_proto::{
     receiver::Request,
     Command, ResponseCode, ResponseType, StatusResponse,
 };
-use jmap::{email::set::TagManager, mailbox::UidMailbox};
+use jmap::{
+    email::{keywords::KeywordRegistry, set::TagManager},
+    mailbox::UidMailbox,
+};
 use jmap_proto::types::{
     acl::Acl, collection::Collection, id::Id, keyword::Keyword, property::Property,
     state::StateChange, type_state::DataType,
@@ -32,7 +35,7 @@ use store::{
     write::{assert::HashedValue, log::ChangeLogBuilder, BatchBuilder, F_VALUE},
 };
 
-use super::{FromModSeq, ImapContext};
+use super::{keyword_error, FromModSeq, ImapContext};
 
 impl<T: SessionStream> Session<T> {
     pub async fn handle_store(
@@ -104,6 +107,29 @@ impl<T: SessionStream> SessionData<T> {
                 .caused_by(trc::location!()));
         }
 
+        // Validate the keywords being set against the mailbox keyword policy
+        let mut set_keywords = arguments
+            .keywords
+            .iter()
+            .map(|k| Keyword::from(k.clone()))
+            .collect::<Vec<_>>();
+        let mut registry = KeywordRegistry::default();
+        if !matches!(arguments.operation, Operation::Clear) {
+            if let Some(err) = self
+                .jmap
+                .email_check_keywords(
+                    account_id,
+                    &[mailbox.id.mailbox_id],
+                    &mut set_keywords,
+                    &mut registry,
+                )
+                .await
+                .imap_ctx(&arguments.tag, trc::location!())?
+            {
+                return Err(keyword_error(err).id(arguments.tag));
+            }
+        }
+
         // Filter out unchanged since ids
         let mut response_code = None;
         let mut unchanged_failed = false;
@@ -180,11 +206,6 @@ impl<T: SessionStream> SessionData<T> {
         };
 
         // Process each change
-        let set_keywords = arguments
-            .keywords
-            .iter()
-            .map(|k| Keyword::from(k.clone()))
-            .collect::<Vec<_>>();
         let mut changelog = ChangeLogBuilder::new();
         let mut changed_mailboxes = AHashSet::new();
         'outer: for (id, imap_id) in &ids {
@@ -230,9 +251,9 @@ impl<T: SessionStream> SessionData<T> {
 
                 if keywords.has_changes() {
                     // Convert keywords to flags
-                    let seen_changed = keywords
+                    let counters_changed = keywords
                         .changed_tags()
-                        .any(|keyword| keyword == &Keyword::Seen);
+                        .any(|keyword| keyword == &Keyword::Seen || keyword == &Keyword::Deleted);
                     let flags = if !arguments.is_silent {
                         keywords
                             .current()
@@ -261,8 +282,9 @@ impl<T: SessionStream> SessionData<T> {
                     batch.value(Property::Cid, changelog.change_id, F_VALUE);
                     match self.jmap.write_batch(batch).await {
                         Ok(_) => {
-                            // Set all current mailboxes as changed if the Seen tag changed
-                            if seen_changed {
+                            // Set all current mailboxes as changed if the Seen or
+                            // Deleted tags changed, as both are counted by STATUS
+                            if counters_changed {
                                 if let Some(mailboxes) = self
                                     .jmap
                                     .get_property::<Vec<UidMailbox>>(
@@ -334,6 +356,10 @@ impl<T: SessionStream> SessionData<T> {
 
         // Write changes
         if !changelog.is_empty() {
+            self.jmap
+                .email_register_keywords(account_id, &mut registry)
+                .await
+                .imap_ctx(response.tag.as_ref().unwrap(), trc::location!())?;
             let change_id = self
                 .jmap
                 .commit_changes(account_id, changelog)
@@ -5,6 +5,7 @@
  */
 
 use std::{
+    net::IpAddr,
     sync::Arc,
     time::{Duration, Instant},
 };
@@ -15,10 +16,10 @@ use hyper::{
     body::{Bytes, Frame},
     StatusCode,
 };
-use jmap_proto::types::type_state::DataType;
+use jmap_proto::types::{collection::Collection, type_state::DataType};
 use utils::map::bitmap::Bitmap;
 
-use crate::{JMAP, LONG_SLUMBER};
+use crate::{services::state::EventSourceChange, JMAP, LONG_SLUMBER};
 
 use super::{HttpRequest, HttpResponse, HttpResponseBody, StateChangeResponse};
 
@@ -33,6 +34,7 @@ impl JMAP {
         &self,
         req: HttpRequest,
         access_token: Arc<AccessToken>,
+        remote_ip: IpAddr,
     ) -> trc::Result<HttpResponse> {
         // Parse query
         let mut ping = 0;
@@ -91,31 +93,78 @@ impl JMAP {
         };
         let mut response = StateChangeResponse::new();
         let throttle = self.core.jmap.event_source_throttle;
+        let keepalive = self.core.jmap.event_source_keepalive;
+        let idle_timeout = self.core.jmap.event_source_idle_timeout;
+
+        // Clients reconnecting after a disconnect resume from the last event received
+        let last_event_id = req
+            .headers()
+            .get("last-event-id")
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.trim().parse::<u64>().ok());
+
+        // Enforce session limits
+        let session_in_flight = self.core.is_session_allowed(&access_token, remote_ip)?;
 
         // Register with state manager
         let mut change_rx = self
-            .subscribe_state_manager(access_token.primary_id(), types)
+            .subscribe_event_source(access_token.primary_id(), types, last_event_id)
             .await?;
+        let mut revocation_rx = self
+            .core
+            .subscribe_session_revocation(access_token.primary_id());
+        let jmap = self.clone();
 
         Ok(HttpResponse {
             status: StatusCode::OK,
             content_type: "text/event-stream".into(),
             content_disposition: "".into(),
             cache_control: "no-store".into(),
+            etag: "".into(),
+            last_modified: "".into(),
             body: HttpResponseBody::Stream(BoxBody::new(StreamBody::new(async_stream::stream! {
+                let _session_in_flight = session_in_flight;
                 let mut last_message = Instant::now() - throttle;
-                let mut timeout =
-                    ping.as_ref().map(|p| p.interval).unwrap_or(LONG_SLUMBER);
+                let mut last_write = Instant::now();
+                let mut last_state = Instant::now();
+                let mut last_event_id = None;
+                let mut timeout = ping
+                    .as_ref()
+                    .map(|p| p.interval)
+                    .into_iter()
+                    .chain(keepalive)
+                    .min()
+                    .unwrap_or(LONG_SLUMBER);
 
                 loop {
-                    match tokio::time::timeout(timeout, change_rx.recv()).await {
-                        Ok(Some(state_change)) => {
+                    let result = tokio::select! {
+                        result = tokio::time::timeout(timeout, change_rx.recv()) => result,
+                        _ = revocation_rx.changed() => {
+                            break;
+                        }
+                    };
+
+                    match result {
+                        Ok(Some(EventSourceChange::Change { id, state_change })) => {
                             for (type_state, change_id) in state_change.types {
                                 response
                                     .changed
                                     .get_mut_or_insert(state_change.account_id.into())
                                     .set(type_state, change_id.into());
                             }
+                            last_event_id = Some(id);
+                        }
+                        Ok(Some(EventSourceChange::Resync { id })) => {
+                            if let Err(err) = jmap
+                                .current_state(&access_token, &types, &mut response)
+                                .await
+                            {
+                                trc::error!(err
+                                    .account_id(access_token.primary_id())
+                                    .details("Failed to obtain current state."));
+                                break;
+                            }
+                            last_event_id = Some(id);
                         }
                         Ok(None) => {
                             break;
@@ -127,8 +176,13 @@ impl JMAP {
                         let elapsed = last_message.elapsed();
                         if elapsed >= throttle {
                             last_message = Instant::now();
+                            last_write = last_message;
+                            last_state = last_message;
+                            let id = last_event_id
+                                .map(|id| format!("id: {id}\n"))
+                                .unwrap_or_default();
                             yield Ok(Frame::data(Bytes::from(format!(
-                                "event: state\ndata: {}\n\n",
+                                "event: state\n{id}data: {}\n\n",
                                 serde_json::to_string(&response).unwrap()
                             ))));
 
@@ -137,24 +191,109 @@ impl JMAP {
                             }
 
                             response.changed.clear();
-                                ping.as_ref().map(|p| p.interval).unwrap_or(LONG_SLUMBER)
+                            ping.as_ref()
+                                .map(|p| p.interval)
+                                .into_iter()
+                                .chain(keepalive)
+                                .min()
+                                .unwrap_or(LONG_SLUMBER)
                         } else {
                             throttle - elapsed
                         }
-                    } else if let Some(ping) = &mut ping {
-                        let elapsed = ping.last_ping.elapsed();
-                        if elapsed >= ping.interval {
-                            ping.last_ping = Instant::now();
-                            yield Ok(Frame::data(ping.payload.clone()));
-                            ping.interval
-                        } else {
-                            ping.interval - elapsed
-                        }
                     } else {
-                        LONG_SLUMBER
+                        let mut next_wake = LONG_SLUMBER;
+
+                        // Close streams that have not delivered a state change for too long,
+                        // clients reconnect and resume from the last event id
+                        if let Some(idle_timeout) = idle_timeout {
+                            let elapsed = last_state.elapsed();
+                            if elapsed >= idle_timeout {
+                                trc::event!(
+                                    Network(trc::NetworkEvent::Timeout),
+                                    AccountId = access_token.primary_id(),
+                                    Details = "EventSource idle timeout",
+                                    Elapsed = elapsed,
+                                );
+                                break;
+                            }
+                            next_wake = idle_timeout - elapsed;
+                        }
+
+                        if let Some(ping) = &mut ping {
+                            let elapsed = ping.last_ping.elapsed();
+                            if elapsed >= ping.interval {
+                                ping.last_ping = Instant::now();
+                                last_write = ping.last_ping;
+                                yield Ok(Frame::data(ping.payload.clone()));
+                                next_wake = ping.interval;
+                            } else {
+                                next_wake = ping.interval - elapsed;
+                            }
+                        }
+
+                        // Comments keep idle connections from being closed by proxies
+                        if let Some(keepalive) = keepalive {
+                            let elapsed = last_write.elapsed();
+                            if elapsed >= keepalive {
+                                last_write = Instant::now();
+                                yield Ok(Frame::data(Bytes::from_static(b": keepalive\n\n")));
+                                next_wake = next_wake.min(keepalive);
+                            } else {
+                                next_wake = next_wake.min(keepalive - elapsed);
+                            }
+                        }
+
+                        next_wake
                     };
                 }
             }))),
         })
     }
+
+    // Adds the current state of all subscribed types, which clients compare
+    // against their own to find out what changed while they were disconnected
+    async fn current_state(
+        &self,
+        access_token: &AccessToken,
+        types: &Bitmap<DataType>,
+        response: &mut StateChangeResponse,
+    ) -> trc::Result<()> {
+        for collection in [
+            Collection::Email,
+            Collection::Mailbox,
+            Collection::Thread,
+            Collection::Identity,
+            Collection::EmailSubmission,
+            Collection::SieveScript,
+            Collection::PushSubscription,
+        ] {
+            let type_state = if let Ok(type_state) = DataType::try_from(collection) {
+                type_state
+            } else {
+                continue;
+            };
+            let has_type = types.contains(type_state);
+            let has_delivery =
+                collection == Collection::Email && types.contains(DataType::EmailDelivery);
+            if !has_type && !has_delivery {
+                continue;
+            }
+
+            for account_id in [access_token.primary_id()]
+                .iter()
+                .chain(access_token.shared_accounts(collection))
+            {
+                let state = self.get_state(*account_id, collection).await?;
+                let changed = response.changed.get_mut_or_insert((*account_id).into());
+                if has_delivery {
+                    changed.set(DataType::EmailDelivery, state.clone());
+                }
+                if has_type {
+                    changed.set(type_state, state);
+                }
+            }
+        }
+
+        Ok(())
+    }
 }
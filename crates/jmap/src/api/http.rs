@@ -4,10 +4,11 @@
  * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
  */
 
-use std::{borrow::Cow, net::IpAddr, sync::Arc};
+use std::{borrow::Cow, net::IpAddr, sync::Arc, time::Duration};
 
+use chrono::DateTime;
 use common::{
-    auth::AccessToken,
+    auth::{bandwidth::Transfer, AccessToken},
     expr::{functions::ResolveVariable, *},
     listener::{ServerInstance, SessionData, SessionManager, SessionStream},
     manager::webadmin::Resource,
@@ -31,7 +32,10 @@ use jmap_proto::{
 };
 
 use crate::{
-    auth::{authenticate::HttpHeaders, oauth::OAuthMetadata},
+    auth::{
+        authenticate::HttpHeaders,
+        oauth::{OAuthMetadata, OAuthScope, OpenIdMetadata},
+    },
     blob::{DownloadResponse, UploadResponse},
     services::state,
     JmapInstance, JMAP,
@@ -78,8 +82,9 @@ impl JMAP {
                 match (path.next().unwrap_or_default(), req.method()) {
                     ("", &Method::POST) => {
                         // Authenticate request
-                        let (_in_flight, access_token) =
-                            self.authenticate_headers(&req, &session).await?;
+                        let (_in_flight, access_token) = self
+                            .authenticate_headers(&req, &session, OAuthScope::Jmap)
+                            .await?;
 
                         let request = fetch_body(
                             &mut req,
@@ -107,8 +112,9 @@ impl JMAP {
                     }
                     ("download", &Method::GET) => {
                         // Authenticate request
-                        let (_in_flight, access_token) =
-                            self.authenticate_headers(&req, &session).await?;
+                        let (_in_flight, access_token) = self
+                            .authenticate_headers(&req, &session, OAuthScope::Jmap)
+                            .await?;
 
                         if let (Some(_), Some(blob_id), Some(name)) = (
                             path.next().and_then(|p| Id::from_bytes(p.as_bytes())),
@@ -116,53 +122,61 @@ impl JMAP {
                             path.next(),
                         ) {
                             return match self.blob_download(&blob_id, &access_token).await? {
-                                Some(blob) => Ok(DownloadResponse {
-                                    filename: name.to_string(),
-                                    content_type: req
-                                        .uri()
-                                        .query()
-                                        .and_then(|q| {
-                                            form_urlencoded::parse(q.as_bytes())
-                                                .find(|(k, _)| k == "accept")
-                                                .map(|(_, v)| v.into_owned())
-                                        })
-                                        .unwrap_or("application/octet-stream".to_string()),
-                                    blob,
+                                Some(blob) => {
+                                    self.core.record_bandwidth(
+                                        access_token.primary_id(),
+                                        Transfer::Out,
+                                        blob.len(),
+                                    );
+
+                                    Ok(DownloadResponse {
+                                        filename: name.to_string(),
+                                        content_type: req
+                                            .uri()
+                                            .query()
+                                            .and_then(|q| {
+                                                form_urlencoded::parse(q.as_bytes())
+                                                    .find(|(k, _)| k == "accept")
+                                                    .map(|(_, v)| v.into_owned())
+                                            })
+                                            .unwrap_or("application/octet-stream".to_string()),
+                                        blob,
+                                    }
+                                    .into_http_response())
                                 }
-                                .into_http_response()),
                                 None => Err(trc::ResourceEvent::NotFound.into_err()),
                             };
                         }
                     }
                     ("upload", &Method::POST) => {
                         // Authenticate request
-                        let (_in_flight, access_token) =
-                            self.authenticate_headers(&req, &session).await?;
+                        let (_in_flight, access_token) = self
+                            .authenticate_headers(&req, &session, OAuthScope::Jmap)
+                            .await?;
 
                         if let Some(account_id) =
                             path.next().and_then(|p| Id::from_bytes(p.as_bytes()))
                         {
-                            return match fetch_body(
-                                &mut req,
-                                if !access_token.has_permission(Permission::UnlimitedUploads) {
-                                    self.core.jmap.upload_max_size
-                                } else {
-                                    0
-                                },
-                                session.session_id,
-                            )
-                            .await
+                            // Reject disallowed types before reading the body
+                            let limits =
+                                self.upload_limits(&access_token, session.session_id).await;
+                            let content_type = req
+                                .headers()
+                                .get(CONTENT_TYPE)
+                                .and_then(|h| h.to_str().ok())
+                                .unwrap_or("application/octet-stream")
+                                .to_string();
+                            if !limits.is_type_allowed(&content_type) {
+                                return Err(trc::ResourceEvent::BadParameters.into_err().details(
+                                    format!("Uploads of type {content_type:?} are not allowed."),
+                                ));
+                            }
+
+                            return match fetch_body(&mut req, limits.max_size, session.session_id)
+                                .await
                             {
                                 Some(bytes) => Ok(self
-                                    .blob_upload(
-                                        account_id,
-                                        req.headers()
-                                            .get(CONTENT_TYPE)
-                                            .and_then(|h| h.to_str().ok())
-                                            .unwrap_or("application/octet-stream"),
-                                        &bytes,
-                                        access_token,
-                                    )
+                                    .blob_upload(account_id, &content_type, &bytes, access_token)
                                     .await?
                                     .into_http_response()),
                                 None => Err(trc::LimitEvent::SizeUpload.into_err()),
@@ -171,15 +185,19 @@ impl JMAP {
                     }
                     ("eventsource", &Method::GET) => {
                         // Authenticate request
-                        let (_in_flight, access_token) =
-                            self.authenticate_headers(&req, &session).await?;
+                        let (_in_flight, access_token) = self
+                            .authenticate_headers(&req, &session, OAuthScope::Jmap)
+                            .await?;
 
-                        return self.handle_event_source(req, access_token).await;
+                        return self
+                            .handle_event_source(req, access_token, session.remote_ip)
+                            .await;
                     }
                     ("ws", &Method::GET) => {
                         // Authenticate request
-                        let (_in_flight, access_token) =
-                            self.authenticate_headers(&req, &session).await?;
+                        let (_in_flight, access_token) = self
+                            .authenticate_headers(&req, &session, OAuthScope::Jmap)
+                            .await?;
 
                         return self
                             .upgrade_websocket_connection(req, access_token, session)
@@ -194,8 +212,9 @@ impl JMAP {
             ".well-known" => match (path.next().unwrap_or_default(), req.method()) {
                 ("jmap", &Method::GET) => {
                     // Authenticate request
-                    let (_in_flight, access_token) =
-                        self.authenticate_headers(&req, &session).await?;
+                    let (_in_flight, access_token) = self
+                        .authenticate_headers(&req, &session, OAuthScope::Jmap)
+                        .await?;
 
                     return Ok(self
                         .handle_session_resource(
@@ -214,6 +233,20 @@ impl JMAP {
                     ))
                     .into_http_response());
                 }
+                ("openid-configuration", &Method::GET) => {
+                    // Limit anonymous requests
+                    self.is_anonymous_allowed(&session.remote_ip).await?;
+
+                    return Ok(JsonResponse::new(OpenIdMetadata::new(
+                        ctx.resolve_response_url(&self.core).await,
+                        self.core
+                            .jmap
+                            .oauth_signing_key
+                            .as_ref()
+                            .map(|key| key.algorithm()),
+                    ))
+                    .into_http_response());
+                }
                 ("acme-challenge", &Method::GET) if self.core.has_acme_http_providers() => {
                     if let Some(token) = path.next() {
                         return match self
@@ -254,7 +287,7 @@ impl JMAP {
             },
             "auth" => match (path.next().unwrap_or_default(), req.method()) {
                 ("device", &Method::POST) => {
-                    self.is_anonymous_allowed(&session.remote_ip).await?;
+                    self.is_oauth_allowed(&session.remote_ip).await?;
 
                     let url = ctx.resolve_response_url(&self.core).await;
                     return self
@@ -262,10 +295,33 @@ impl JMAP {
                         .await;
                 }
                 ("token", &Method::POST) => {
+                    self.is_oauth_allowed(&session.remote_ip).await?;
+
+                    let url = ctx.resolve_response_url(&self.core).await;
+                    return self
+                        .handle_token_request(&mut req, url, session.session_id)
+                        .await;
+                }
+                ("jwks.json", &Method::GET) => {
                     self.is_anonymous_allowed(&session.remote_ip).await?;
 
+                    return Ok(self.handle_jwks_request());
+                }
+                ("revoke", &Method::POST) => {
+                    self.is_oauth_allowed(&session.remote_ip).await?;
+
+                    return self
+                        .handle_revoke_request(&mut req, session.session_id)
+                        .await;
+                }
+                ("introspect", &Method::POST) => {
+                    // Authenticate request
+                    let (_in_flight, access_token) = self
+                        .authenticate_headers(&req, &session, OAuthScope::Admin)
+                        .await?;
+
                     return self
-                        .handle_token_request(&mut req, session.session_id)
+                        .handle_introspect_request(&mut req, &access_token, session.session_id)
                         .await;
                 }
                 (_, &Method::OPTIONS) => {
@@ -280,7 +336,10 @@ impl JMAP {
                 }
 
                 // Authenticate user
-                match self.authenticate_headers(&req, &session).await {
+                match self
+                    .authenticate_headers(&req, &session, OAuthScope::Admin)
+                    .await
+                {
                     Ok((_, access_token)) => {
                         let body = fetch_body(&mut req, 1024 * 1024, session.session_id).await;
                         return self
@@ -311,8 +370,11 @@ impl JMAP {
                                             })
                                     })
                                 {
-                                    let (account_id, _, _) =
-                                        self.validate_access_token(grant_type, token).await?;
+                                    let account_id = self
+                                        .core
+                                        .validate_access_token(grant_type, None, token)
+                                        .await?
+                                        .account_id;
 
                                     return self
                                         .handle_telemetry_api_request(
@@ -357,21 +419,17 @@ impl JMAP {
                 );
             }
             "healthz" => match path.next().unwrap_or_default() {
-                "live" => {
+                "" | "live" => {
                     return Ok(StatusCode::OK.into_http_response());
                 }
                 "ready" => {
-                    return Ok({
-                        if !self.core.storage.data.is_none() {
-                            StatusCode::OK
-                        } else {
-                            StatusCode::SERVICE_UNAVAILABLE
-                        }
-                    }
-                    .into_http_response());
+                    return Ok(self.handle_readiness_probe().await);
                 }
                 _ => (),
             },
+            "readyz" => {
+                return Ok(self.handle_readiness_probe().await);
+            }
             "metrics" => match path.next().unwrap_or_default() {
                 "prometheus" => {
                     if let Some(prometheus) = &self.core.metrics.prometheus {
@@ -417,7 +475,11 @@ impl JMAP {
                     .await
                 {
                     Ok(Some(resource)) => {
-                        return Ok(resource.into_http_response());
+                        return Ok(cached_resource_response(
+                            &req,
+                            resource,
+                            self.core.jmap.http_resource_max_age,
+                        ));
                     }
                     Ok(None) => (),
                     Err(err) => {
@@ -428,7 +490,11 @@ impl JMAP {
                 let resource = self.inner.webadmin.get("logo.svg").await?;
 
                 return if !resource.is_empty() {
-                    Ok(resource.into_http_response())
+                    Ok(cached_resource_response(
+                        &req,
+                        resource,
+                        self.core.jmap.http_resource_max_age,
+                    ))
                 } else {
                     Err(trc::ResourceEvent::NotFound.into_err())
                 };
@@ -444,7 +510,11 @@ impl JMAP {
                     .await?;
 
                 return if !resource.is_empty() {
-                    Ok(resource.into_http_response())
+                    Ok(cached_resource_response(
+                        &req,
+                        resource,
+                        self.core.jmap.http_resource_max_age,
+                    ))
                 } else {
                     Err(trc::ResourceEvent::NotFound.into_err())
                 };
@@ -453,6 +523,19 @@ impl JMAP {
 
         Err(trc::ResourceEvent::NotFound.into_err())
     }
+
+    async fn handle_readiness_probe(&self) -> HttpResponse {
+        let health = self.core.health_check().await;
+        JsonResponse::with_status(
+            if health.healthy {
+                StatusCode::OK
+            } else {
+                StatusCode::SERVICE_UNAVAILABLE
+            },
+            health,
+        )
+        .into_http_response()
+    }
 }
 
 impl JmapInstance {
@@ -534,6 +617,7 @@ impl JmapInstance {
                         };
 
                         // Parse HTTP request
+                        let mut retry_after = None;
                         let response = match jmap
                             .parse_http_request(
                                 req,
@@ -552,6 +636,16 @@ impl JmapInstance {
                             Ok(response) => response,
                             Err(err) => {
                                 let response = err.into_http_response();
+                                if matches!(
+                                    err.inner,
+                                    trc::EventType::Limit(
+                                        trc::LimitEvent::TooManyRequests
+                                            | trc::LimitEvent::TooManyOAuthRequests
+                                    )
+                                ) {
+                                    retry_after =
+                                        err.value(trc::Key::Expires).and_then(|v| v.to_uint());
+                                }
                                 trc::error!(err.span_id(session.session_id));
                                 response
                             }
@@ -573,6 +667,13 @@ impl JmapInstance {
                         // Build response
                         let mut response = response.build();
 
+                        // Tell rate limited clients when to retry
+                        if let Some(retry_after) = retry_after {
+                            response
+                                .headers_mut()
+                                .insert(header::RETRY_AFTER, retry_after.into());
+                        }
+
                         // Add custom headers
                         if !jmap.core.jmap.http_headers.is_empty() {
                             let headers = response.headers_mut();
@@ -735,6 +836,8 @@ impl HttpResponse {
             content_type: "".into(),
             content_disposition: "".into(),
             cache_control: "".into(),
+            etag: "".into(),
+            last_modified: "".into(),
             body: HttpResponseBody::Empty,
         }
     }
@@ -749,6 +852,8 @@ impl HttpResponse {
             content_type: content_type.into(),
             content_disposition: "".into(),
             cache_control: "".into(),
+            etag: "".into(),
+            last_modified: "".into(),
             body: HttpResponseBody::Text(body.into()),
         }
     }
@@ -763,6 +868,8 @@ impl HttpResponse {
             content_type: content_type.into(),
             content_disposition: "".into(),
             cache_control: "".into(),
+            etag: "".into(),
+            last_modified: "".into(),
             body: HttpResponseBody::Binary(body.into()),
         }
     }
@@ -799,21 +906,21 @@ impl HttpResponse {
                     );
                 }
 
-                if !self.cache_control.is_empty() {
-                    builder = builder.header(header::CACHE_CONTROL, self.cache_control.as_ref());
-                }
-
-                builder.body(
-                    Full::new(Bytes::from(body))
+                builder
+                    .with_validators(&self.cache_control, &self.etag, &self.last_modified)
+                    .body(
+                        Full::new(Bytes::from(body))
+                            .map_err(|never| match never {})
+                            .boxed(),
+                    )
+            }
+            HttpResponseBody::Empty => builder
+                .with_validators(&self.cache_control, &self.etag, &self.last_modified)
+                .body(
+                    Full::new(Bytes::new())
                         .map_err(|never| match never {})
                         .boxed(),
-                )
-            }
-            HttpResponseBody::Empty => builder.body(
-                Full::new(Bytes::new())
-                    .map_err(|never| match never {})
-                    .boxed(),
-            ),
+                ),
             HttpResponseBody::Stream(stream) => builder
                 .header(header::CONTENT_TYPE, self.content_type.as_ref())
                 .header(header::CACHE_CONTROL, self.cache_control.as_ref())
@@ -833,6 +940,25 @@ impl HttpResponse {
     }
 }
 
+trait WithValidators {
+    fn with_validators(self, cache_control: &str, etag: &str, last_modified: &str) -> Self;
+}
+
+impl WithValidators for hyper::http::response::Builder {
+    fn with_validators(mut self, cache_control: &str, etag: &str, last_modified: &str) -> Self {
+        for (name, value) in [
+            (header::CACHE_CONTROL, cache_control),
+            (header::ETAG, etag),
+            (header::LAST_MODIFIED, last_modified),
+        ] {
+            if !value.is_empty() {
+                self = self.header(name, value);
+            }
+        }
+        self
+    }
+}
+
 impl<T: serde::Serialize> ToHttpResponse for JsonResponse<T> {
     fn into_http_response(self) -> HttpResponse {
         HttpResponse::new_text(
@@ -920,13 +1046,16 @@ impl ToRequestError for trc::Error {
                         .and_then(|v| v.to_uint())
                         .unwrap_or_default() as usize,
                 ),
-                trc::LimitEvent::TooManyRequests => RequestError::too_many_requests(),
+                trc::LimitEvent::TooManyRequests | trc::LimitEvent::TooManyOAuthRequests => {
+                    RequestError::too_many_requests()
+                }
             },
             trc::EventType::Auth(cause) => match cause {
                 trc::AuthEvent::MissingTotp => {
                     RequestError::blank(402, "TOTP code required", cause.message())
                 }
                 trc::AuthEvent::TooManyAttempts => RequestError::too_many_auth_attempts(),
+                trc::AuthEvent::DirectoryUnavailable => RequestError::unavailable(),
                 _ => RequestError::unauthorized(),
             },
             trc::EventType::Security(cause) => match cause {
@@ -934,7 +1063,10 @@ impl ToRequestError for trc::Error {
                 | trc::SecurityEvent::BruteForceBan
                 | trc::SecurityEvent::LoiterBan
                 | trc::SecurityEvent::IpBlocked => RequestError::too_many_auth_attempts(),
-                trc::SecurityEvent::Unauthorized => RequestError::forbidden(),
+                trc::SecurityEvent::Unauthorized | trc::SecurityEvent::SubmissionSuspended => {
+                    RequestError::forbidden()
+                }
+                trc::SecurityEvent::SubmissionLimitExceeded => RequestError::too_many_requests(),
             },
             trc::EventType::Resource(cause) => match cause {
                 trc::ResourceEvent::NotFound => RequestError::not_found(),
@@ -1006,6 +1138,8 @@ impl ToHttpResponse for DownloadResponse {
             )
             .into(),
             cache_control: "private, immutable, max-age=31536000".into(),
+            etag: "".into(),
+            last_modified: "".into(),
             body: HttpResponseBody::Binary(self.blob),
         }
     }
@@ -1017,6 +1151,57 @@ impl ToHttpResponse for Resource<Vec<u8>> {
     }
 }
 
+// Serves a cacheable resource, answering with 304 Not Modified when the
+// copy cached by the client is still current
+fn cached_resource_response(
+    req: &HttpRequest,
+    resource: Resource<Vec<u8>>,
+    max_age: Option<Duration>,
+) -> HttpResponse {
+    let etag = resource.etag.clone().unwrap_or_default();
+    let last_modified = resource
+        .last_modified
+        .and_then(|timestamp| DateTime::from_timestamp(timestamp as i64, 0))
+        .map(|date| date.format("%a, %d %b %Y %H:%M:%S GMT").to_string())
+        .unwrap_or_default();
+
+    // If-None-Match takes precedence over If-Modified-Since
+    let is_not_modified = if let Some(if_none_match) = req
+        .headers()
+        .get(header::IF_NONE_MATCH)
+        .and_then(|h| h.to_str().ok())
+    {
+        !etag.is_empty()
+            && if_none_match.split(',').any(|tag| {
+                let tag = tag.trim();
+                tag == "*" || tag.strip_prefix("W/").unwrap_or(tag) == etag
+            })
+    } else if let (Some(if_modified_since), Some(last_modified)) = (
+        req.headers()
+            .get(header::IF_MODIFIED_SINCE)
+            .and_then(|h| h.to_str().ok()),
+        resource.last_modified,
+    ) {
+        DateTime::parse_from_rfc2822(if_modified_since)
+            .map_or(false, |since| since.timestamp() >= last_modified as i64)
+    } else {
+        false
+    };
+
+    let mut response = if is_not_modified {
+        HttpResponse::new_empty(StatusCode::NOT_MODIFIED)
+    } else {
+        resource.into_http_response()
+    };
+    response.cache_control = match max_age {
+        Some(max_age) => format!("public, max-age={}", max_age.as_secs()).into(),
+        None => "no-cache".into(),
+    };
+    response.etag = etag.into();
+    response.last_modified = last_modified.into();
+    response
+}
+
 impl ToHttpResponse for UploadResponse {
     fn into_http_response(self) -> HttpResponse {
         JsonResponse::new(self).into_http_response()
@@ -0,0 +1,82 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use common::auth::AccessToken;
+use directory::{
+    backend::internal::manage::{self, ManageDirectory},
+    Permission,
+};
+use hyper::Method;
+use serde_json::json;
+use store::write::now;
+use utils::url_params::UrlParams;
+
+use crate::{
+    api::{http::ToHttpResponse, HttpRequest, HttpResponse, JsonResponse},
+    JMAP,
+};
+
+use super::{decode_path_element, Timestamp};
+
+impl JMAP {
+    pub async fn handle_manage_bandwidth(
+        &self,
+        req: &HttpRequest,
+        path: Vec<&str>,
+        access_token: &AccessToken,
+    ) -> trc::Result<HttpResponse> {
+        let name = path
+            .get(1)
+            .copied()
+            .map(decode_path_element)
+            .ok_or_else(|| trc::ResourceEvent::NotFound.into_err())?;
+
+        // Validate the access token
+        access_token.assert_has_permission(if req.method() == Method::GET {
+            Permission::IndividualGet
+        } else {
+            Permission::IndividualUpdate
+        })?;
+
+        let account_id = self
+            .core
+            .storage
+            .data
+            .get_principal_info(name.as_ref())
+            .await?
+            .filter(|p| p.has_tenant_access(access_token.tenant.map(|t| t.id)))
+            .map(|p| p.id)
+            .ok_or_else(|| manage::not_found(name.to_string()))?;
+
+        match *req.method() {
+            Method::GET => {
+                let params = UrlParams::new(req.uri().query());
+                let from = params
+                    .parse::<Timestamp>("from")
+                    .map(|t| t.into_inner())
+                    .unwrap_or_default();
+                let to = params
+                    .parse::<Timestamp>("to")
+                    .map(|t| t.into_inner())
+                    .unwrap_or_else(now);
+
+                Ok(JsonResponse::new(json!({
+                    "data": self.core.get_bandwidth_usage(account_id, from, to).await?,
+                }))
+                .into_http_response())
+            }
+            Method::DELETE => {
+                self.core.reset_bandwidth_usage(account_id).await?;
+
+                Ok(JsonResponse::new(json!({
+                    "data": (),
+                }))
+                .into_http_response())
+            }
+            _ => Err(trc::ResourceEvent::NotFound.into_err()),
+        }
+    }
+}
@@ -0,0 +1,71 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use common::auth::AccessToken;
+use directory::Permission;
+use hyper::Method;
+use serde_json::json;
+
+use crate::{
+    api::{http::ToHttpResponse, HttpRequest, HttpResponse, JsonResponse},
+    JMAP,
+};
+
+use super::decode_path_element;
+
+impl JMAP {
+    pub async fn handle_manage_directory(
+        &self,
+        req: &HttpRequest,
+        path: Vec<&str>,
+        access_token: &AccessToken,
+    ) -> trc::Result<HttpResponse> {
+        let name = decode_path_element(path.get(1).copied().unwrap_or_default());
+
+        match (path.get(2).copied(), path.get(3), req.method()) {
+            (Some("test"), None, &Method::GET) => {
+                // Validate the access token
+                access_token.assert_has_permission(Permission::SettingsList)?;
+
+                // Probe the directory
+                let result = self
+                    .core
+                    .test_directory(name.as_ref())
+                    .await
+                    .ok_or_else(|| {
+                        trc::ManageEvent::NotFound
+                            .into_err()
+                            .details("Directory not found")
+                            .id(name.to_string())
+                    })?;
+
+                Ok(JsonResponse::new(json!({
+                    "data": result,
+                }))
+                .into_http_response())
+            }
+            (Some("principal"), Some(principal), &Method::GET) => {
+                // Validate the access token
+                access_token.assert_has_permission(Permission::PrincipalGet)?;
+
+                // Look up the principal in the directory
+                let principal = decode_path_element(principal);
+                let principal = self
+                    .core
+                    .lookup_directory_principal(name.as_ref(), principal.as_ref())
+                    .await?
+                    .filter(|p| p.has_tenant_access(access_token.tenant.map(|t| t.id)))
+                    .ok_or_else(|| trc::ManageEvent::NotFound.into_err())?;
+
+                Ok(JsonResponse::new(json!({
+                    "data": principal,
+                }))
+                .into_http_response())
+            }
+            _ => Err(trc::ResourceEvent::NotFound.into_err()),
+        }
+    }
+}
@@ -20,7 +20,7 @@ use common::{
         tracers::store::{TracingQuery, TracingStore},
     },
 };
-use directory::{backend::internal::manage, Permission};
+use directory::{backend::internal::manage, Permission, Type};
 use http_body_util::{combinators::BoxBody, StreamBody};
 use hyper::{
     body::{Bytes, Frame},
@@ -202,6 +202,8 @@ impl JMAP {
                     content_type: "text/event-stream".into(),
                     content_disposition: "".into(),
                     cache_control: "no-store".into(),
+                    etag: "".into(),
+                    last_modified: "".into(),
                     body: HttpResponseBody::Stream(BoxBody::new(StreamBody::new(
                         async_stream::stream! {
                             let mut last_message = Instant::now() - throttle;
@@ -340,6 +342,36 @@ impl JMAP {
                     .into_http_response())
                 }
             }
+            ("timeline", Some(id), &Method::GET) => {
+                // Validate the access token
+                access_token.assert_has_permission(Permission::TracingGet)?;
+
+                let span_id = id.parse::<u64>().map_err(|_| {
+                    trc::ResourceEvent::BadParameters
+                        .into_err()
+                        .details("Invalid span id")
+                })?;
+                let store = &self
+                    .core
+                    .enterprise
+                    .as_ref()
+                    .and_then(|e| e.trace_store.as_ref())
+                    .ok_or_else(|| manage::unsupported("No tracing store has been configured"))?
+                    .store;
+
+                let timeline = store.get_timeline(span_id).await?;
+
+                Ok(JsonResponse::new(json!({
+                        "data": {
+                            "correlationId": timeline.correlation_id,
+                            "events": JsonEventSerializer::new(timeline.events)
+                                .with_spans()
+                                .with_description()
+                                .with_explanation(),
+                        },
+                }))
+                .into_http_response())
+            }
             ("live", Some("tracing-token"), &Method::GET) => {
                 // Validate the access token
                 access_token.assert_has_permission(Permission::TracingLive)?;
@@ -347,7 +379,7 @@ impl JMAP {
                 // Issue a live telemetry token valid for 60 seconds
 
                 Ok(JsonResponse::new(json!({
-                    "data": self.issue_custom_token(account_id, "live_tracing", "web", 60).await?,
+                    "data": self.issue_custom_token(account_id, "live_tracing", "web", None, 60).await?,
             }))
             .into_http_response())
             }
@@ -358,7 +390,7 @@ impl JMAP {
                 // Issue a live telemetry token valid for 60 seconds
 
                 Ok(JsonResponse::new(json!({
-                    "data": self.issue_custom_token(account_id, "live_metrics", "web", 60).await?,
+                    "data": self.issue_custom_token(account_id, "live_metrics", "web", None, 60).await?,
             }))
             .into_http_response())
             }
@@ -448,19 +480,35 @@ impl JMAP {
                 }
 
                 // Refresh expensive metrics
-                for metric_type in [
-                    MetricType::QueueCount,
-                    MetricType::UserCount,
-                    MetricType::DomainCount,
-                ] {
-                    if metric_types.contains(&metric_type) {
-                        let value = match metric_type {
-                            MetricType::QueueCount => self.core.total_queued_messages().await?,
-                            MetricType::UserCount => self.core.total_accounts().await?,
-                            MetricType::DomainCount => self.core.total_domains().await?,
-                            _ => unreachable!(),
-                        };
-                        Collector::update_gauge(metric_type, value);
+                if metric_types.contains(&MetricType::QueueCount) {
+                    Collector::update_gauge(
+                        MetricType::QueueCount,
+                        self.core.total_queued_messages().await?,
+                    );
+                }
+                let principal_types = [
+                    (Type::Individual, MetricType::UserCount),
+                    (Type::Domain, MetricType::DomainCount),
+                ]
+                .into_iter()
+                .filter(|(_, metric_type)| metric_types.contains(metric_type))
+                .collect::<Vec<_>>();
+                if !principal_types.is_empty() {
+                    let totals = self
+                        .core
+                        .total_principals(
+                            &principal_types
+                                .iter()
+                                .map(|(typ, _)| *typ)
+                                .collect::<Vec<_>>(),
+                            None,
+                        )
+                        .await?;
+                    for (typ, metric_type) in principal_types {
+                        Collector::update_gauge(
+                            metric_type,
+                            totals.get(&typ).copied().unwrap_or_default(),
+                        );
                     }
                 }
 
@@ -469,6 +517,8 @@ impl JMAP {
                     content_type: "text/event-stream".into(),
                     content_disposition: "".into(),
                     cache_control: "no-store".into(),
+                    etag: "".into(),
+                    last_modified: "".into(),
                     body: HttpResponseBody::Stream(BoxBody::new(StreamBody::new(
                         async_stream::stream! {
 
@@ -492,7 +542,7 @@ impl JMAP {
                                         );
                                     }
                                 }
-                                for gauge in Collector::collect_gauges(true) {
+                                for gauge in Collector::collect_gauges() {
                                     if metric_types.is_empty() || metric_types.contains(&gauge.id()) {
                                         if !is_first {
                                             metrics.push(',');
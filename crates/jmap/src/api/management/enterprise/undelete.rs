@@ -181,9 +181,13 @@ impl JMAP {
                                             mailbox_ids: vec![INBOX_ID],
                                             keywords: vec![],
                                             received_at: (request.time as u64).into(),
+                                            received_at_offset: 0,
                                             source: IngestSource::Smtp,
+                                            delivered_to: None,
                                             encrypt: false,
+                                            dedup: None,
                                             session_id: session.session_id,
+                                            dry_run: false,
                                         })
                                         .await
                                     {
@@ -0,0 +1,136 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use common::auth::{
+    last_login::{LastLogin, LoginKind},
+    AccessToken,
+};
+use directory::{
+    backend::internal::{manage::ManageDirectory, PrincipalField},
+    Permission, Type,
+};
+use serde::Serialize;
+use serde_json::json;
+use store::write::now;
+use utils::url_params::UrlParams;
+
+use crate::{
+    api::{http::ToHttpResponse, HttpRequest, HttpResponse, JsonResponse},
+    JMAP,
+};
+
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LoginActivity {
+    pub last_login: Option<LastLogin>,
+    pub last_seen: Option<LastLogin>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DormantAccount {
+    pub name: String,
+    #[serde(flatten)]
+    pub activity: LoginActivity,
+}
+
+impl JMAP {
+    pub async fn get_login_activity(&self, account_id: u32) -> trc::Result<LoginActivity> {
+        Ok(LoginActivity {
+            last_login: self
+                .core
+                .get_last_login(account_id, LoginKind::Login)
+                .await?,
+            last_seen: self
+                .core
+                .get_last_login(account_id, LoginKind::Seen)
+                .await?,
+        })
+    }
+
+    pub async fn handle_account_last_login(
+        &self,
+        access_token: &AccessToken,
+    ) -> trc::Result<HttpResponse> {
+        Ok(JsonResponse::new(json!({
+            "data": self.get_login_activity(access_token.primary_id()).await?,
+        }))
+        .into_http_response())
+    }
+
+    // Lists the accounts without any login or token use after the given time
+    pub async fn handle_dormant_accounts(
+        &self,
+        req: &HttpRequest,
+        access_token: &AccessToken,
+    ) -> trc::Result<HttpResponse> {
+        // Validate the access token
+        access_token.assert_has_permission(Permission::IndividualList)?;
+
+        let params = UrlParams::new(req.uri().query());
+        let before: u64 = params.parse("before").unwrap_or_else(now);
+        let page: usize = params.parse("page").unwrap_or(0);
+        let limit: usize = params.parse("limit").unwrap_or(0);
+
+        // Scan the lookup store once per login kind rather than twice per account
+        let logins = self.core.get_all_last_logins(LoginKind::Login).await?;
+        let seen = self.core.get_all_last_logins(LoginKind::Seen).await?;
+
+        let mut accounts = Vec::new();
+        for principal in self
+            .core
+            .storage
+            .data
+            .list_principals(
+                None,
+                access_token.tenant.map(|t| t.id),
+                &[Type::Individual],
+                &[PrincipalField::Name],
+                0,
+                0,
+            )
+            .await?
+            .items
+        {
+            let activity = match (&logins, &seen) {
+                (Some(logins), Some(seen)) => LoginActivity {
+                    last_login: logins.get(&principal.id()).copied(),
+                    last_seen: seen.get(&principal.id()).copied(),
+                },
+                _ => self.get_login_activity(principal.id()).await?,
+            };
+            if [&activity.last_login, &activity.last_seen]
+                .into_iter()
+                .flatten()
+                .all(|login| login.timestamp < before)
+            {
+                accounts.push(DormantAccount {
+                    name: principal.name().to_string(),
+                    activity,
+                });
+            }
+        }
+
+        let total = accounts.len();
+        let items = if limit > 0 {
+            accounts
+                .into_iter()
+                .skip(page.saturating_sub(1) * limit)
+                .take(limit)
+                .collect()
+        } else {
+            accounts
+        };
+
+        Ok(JsonResponse::new(json!({
+            "data": {
+                "items": items,
+                "total": total,
+            },
+        }))
+        .into_http_response())
+    }
+}
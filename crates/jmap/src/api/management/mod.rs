@@ -4,15 +4,19 @@
  * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
  */
 
+pub mod bandwidth;
+pub mod directories;
 pub mod dkim;
 pub mod dns;
 #[cfg(feature = "enterprise")]
 pub mod enterprise;
+pub mod last_login;
 pub mod log;
 pub mod principal;
 pub mod queue;
 pub mod reload;
 pub mod report;
+pub mod sessions;
 pub mod settings;
 pub mod sieve;
 pub mod stores;
@@ -76,11 +80,17 @@ impl JMAP {
                     .await
             }
             "dns" => self.handle_manage_dns(req, path, &access_token).await,
+            "directory" => self.handle_manage_directory(req, path, &access_token).await,
             "store" => {
                 self.handle_manage_store(req, path, body, session, &access_token)
                     .await
             }
             "reload" => self.handle_manage_reload(req, path, &access_token).await,
+            "bandwidth" => self.handle_manage_bandwidth(req, path, &access_token).await,
+            "sessions" => self.handle_manage_sessions(req, path, &access_token).await,
+            "last-login" if req.method() == Method::GET => {
+                self.handle_dormant_accounts(req, &access_token).await
+            }
             "dkim" => {
                 self.handle_manage_dkim(req, path, body, &access_token)
                     .await
@@ -127,6 +137,12 @@ impl JMAP {
 
                     self.handle_account_auth_post(req, access_token, body).await
                 }
+                ("last-login", &Method::GET) => {
+                    // Validate the access token
+                    access_token.assert_has_permission(Permission::ManagePasswords)?;
+
+                    self.handle_account_last_login(&access_token).await
+                }
                 _ => Err(trc::ResourceEvent::NotFound.into_err()),
             },
             // SPDX-SnippetBegin
@@ -4,15 +4,16 @@
  * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
  */
 
-use std::sync::{atomic::Ordering, Arc};
+use std::sync::Arc;
 
 use common::auth::AccessToken;
 use directory::{
     backend::internal::{
         lookup::DirectoryStore,
-        manage::{self, not_found, ManageDirectory, UpdatePrincipal},
+        manage::{self, not_found, ManageDirectory, PrincipalList, UpdatePrincipal},
         PrincipalAction, PrincipalField, PrincipalUpdate, PrincipalValue, SpecialSecrets,
     },
+    core::secret::AppPassword,
     DirectoryInner, Permission, Principal, QueryBy, Type,
 };
 
@@ -35,7 +36,13 @@ pub enum AccountAuthRequest {
     SetPassword { password: String },
     EnableOtpAuth { url: String },
     DisableOtpAuth { url: Option<String> },
-    AddAppPassword { name: String, password: String },
+    AddAppPassword {
+        name: String,
+        password: String,
+        scope: Option<String>,
+        #[serde(default)]
+        hashed: bool,
+    },
     RemoveAppPassword { name: String },
 }
 
@@ -96,6 +103,7 @@ impl JMAP {
                 }
 
                 // Create principal
+                let names = login_names(&principal);
                 let result = self
                     .core
                     .storage
@@ -103,6 +111,9 @@ impl JMAP {
                     .create_principal(principal, access_token.tenant.map(|t| t.id))
                     .await?;
 
+                // Remove failed attempts cached before the account existed
+                self.core.invalidate_auth_cache(result, &names).await;
+
                 Ok(JsonResponse::new(json!({
                     "data": result,
                 }))
@@ -186,6 +197,14 @@ impl JMAP {
                                 .await?
                                 .filter(|p| p.typ == Type::Tenant)
                                 .map(|p| p.id);
+
+                            // Unknown tenants have no principals
+                            if tenant.is_none() {
+                                return Ok(JsonResponse::new(json!({
+                                        "data": PrincipalList::default(),
+                                }))
+                                .into_http_response());
+                            }
                         }
                     }
                 } else if types.contains(&Type::Tenant) {
@@ -194,6 +213,24 @@ impl JMAP {
 
                 // SPDX-SnippetEnd
 
+                // Count using the name index when no filters are applied
+                if count && filter.is_none() && types.len() == 1 {
+                    let total = self
+                        .core
+                        .storage
+                        .data
+                        .count_principals(None, types.first().copied(), tenant)
+                        .await?;
+
+                    return Ok(JsonResponse::new(json!({
+                            "data": PrincipalList {
+                                items: vec![],
+                                total,
+                            },
+                    }))
+                    .into_http_response());
+                }
+
                 let mut principals = self
                     .core
                     .storage
@@ -249,6 +286,13 @@ impl JMAP {
                             }
                         })?;
 
+                        if path.get(2).copied() == Some("last-login") {
+                            return Ok(JsonResponse::new(json!({
+                                "data": self.get_login_activity(account_id).await?,
+                            }))
+                            .into_http_response());
+                        }
+
                         let mut principal = self
                             .core
                             .storage
@@ -296,16 +340,18 @@ impl JMAP {
                             self.core.storage.fts.remove_all(account_id).await?;
                         }
 
+                        // Remove login activity
+                        if typ == Type::Individual {
+                            self.core.remove_last_login(account_id).await?;
+                        }
+
                         // Remove entries from cache
-                        self.inner.sessions.retain(|_, id| id.item != account_id);
+                        self.remove_cached_sessions(account_id);
+                        self.core.invalidate_auth_cache(account_id, &[]).await;
 
                         if matches!(typ, Type::Role | Type::Tenant) {
                             // Update permissions cache
-                            self.core.security.permissions.clear();
-                            self.core
-                                .security
-                                .permissions_version
-                                .fetch_add(1, Ordering::Relaxed);
+                            self.core.security.bump_permissions_version();
                         }
 
                         Ok(JsonResponse::new(json!({
@@ -340,11 +386,17 @@ impl JMAP {
                         let mut needs_assert = false;
                         let mut expire_session = false;
                         let mut expire_token = false;
+                        let mut revoke_sessions = false;
                         let mut is_role_change = false;
 
                         for change in &changes {
                             match change.field {
                                 PrincipalField::Secrets => {
+                                    expire_session = true;
+                                    revoke_sessions = true;
+                                    needs_assert = true;
+                                }
+                                PrincipalField::AppPasswords => {
                                     expire_session = true;
                                     needs_assert = true;
                                 }
@@ -369,6 +421,9 @@ impl JMAP {
                                                 "Tenants cannot change their tenantId"
                                             ));
                                     }
+
+                                    // OAuth tokens are checked against the cached tenant
+                                    expire_token = true;
                                 }
                                 PrincipalField::Roles
                                 | PrincipalField::EnabledPermissions
@@ -397,22 +452,30 @@ impl JMAP {
                             )
                             .await?;
 
+                        // Cached credentials also hold the principal's details
+                        self.invalidate_account_auth_cache(account_id).await?;
+
                         if expire_session {
                             // Remove entries from cache
-                            self.inner.sessions.retain(|_, id| id.item != account_id);
+                            self.remove_cached_sessions(account_id);
                         }
 
                         if is_role_change {
-                            // Update permissions cache
-                            self.core.security.permissions.clear();
-                            self.core
-                                .security
-                                .permissions_version
-                                .fetch_add(1, Ordering::Relaxed);
+                            // Update permissions cache, tenants are not inherited by other roles
+                            if typ == Type::Tenant {
+                                self.core.security.invalidate_role_permissions(account_id);
+                            } else {
+                                self.core.security.bump_permissions_version();
+                            }
                         }
 
                         if expire_token {
-                            self.core.security.access_tokens.remove(&account_id);
+                            self.core.security.invalidate_access_token(account_id);
+                        }
+
+                        if revoke_sessions {
+                            // OAuth tokens of internal accounts are not bound to the password
+                            self.core.revoke_account_sessions(account_id).await?;
                         }
 
                         Ok(JsonResponse::new(json!({
@@ -437,7 +500,12 @@ impl JMAP {
             app_passwords: Vec::new(),
         };
 
-        if access_token.primary_id() != u32::MAX {
+        if self
+            .core
+            .jmap
+            .fallback_admin(access_token.primary_id())
+            .is_none()
+        {
             let principal = self
                 .core
                 .storage
@@ -455,6 +523,13 @@ impl JMAP {
                     response.app_passwords.push(app_name.to_string());
                 }
             }
+
+            for app_password in principal
+                .iter_str(PrincipalField::AppPasswords)
+                .filter_map(|value| AppPassword::parse(value))
+            {
+                response.app_passwords.push(app_password.name.to_string());
+            }
         }
 
         Ok(JsonResponse::new(json!({
@@ -503,17 +578,20 @@ impl JMAP {
         }
 
         // Handle Fallback admin password changes
-        if access_token.primary_id() == u32::MAX {
+        if let Some(fallback_admin) = self.core.jmap.fallback_admin(access_token.primary_id()) {
             match requests.into_iter().next().unwrap() {
                 AccountAuthRequest::SetPassword { password } => {
                     self.core
                         .storage
                         .config
-                        .set([("authentication.fallback-admin.secret", password)])
+                        .set([(format!("{}.secret", fallback_admin.config_key), password)])
                         .await?;
 
                     // Remove entries from cache
-                    self.inner.sessions.retain(|_, id| id.item != u32::MAX);
+                    self.remove_cached_sessions(fallback_admin.id);
+                    self.core
+                        .invalidate_auth_cache(fallback_admin.id, &[])
+                        .await;
 
                     return Ok(JsonResponse::new(json!({
                         "data": (),
@@ -534,7 +612,13 @@ impl JMAP {
 
         // Build actions
         let mut actions = Vec::with_capacity(requests.len());
+        let mut revoke_sessions = false;
         for request in requests {
+            revoke_sessions |= !matches!(
+                request,
+                AccountAuthRequest::AddAppPassword { .. }
+                    | AccountAuthRequest::RemoveAppPassword { .. }
+            );
             let (action, secret) = match request {
                 AccountAuthRequest::SetPassword { password } => {
                     actions.push(PrincipalUpdate {
@@ -550,11 +634,31 @@ impl JMAP {
                     PrincipalAction::RemoveItem,
                     url.unwrap_or_else(|| "otpauth://".to_string()),
                 ),
-                AccountAuthRequest::AddAppPassword { name, password } => {
-                    (PrincipalAction::AddItem, format!("$app${name}${password}"))
+                AccountAuthRequest::AddAppPassword {
+                    name,
+                    password,
+                    scope,
+                    hashed,
+                } => {
+                    actions.push(PrincipalUpdate {
+                        action: PrincipalAction::AddItem,
+                        field: PrincipalField::AppPasswords,
+                        value: PrincipalValue::String(
+                            AppPassword::build(&name, scope.as_deref(), &password, hashed)
+                                .map_err(|err| manage::error(err, None::<u32>))?,
+                        ),
+                    });
+                    continue;
                 }
                 AccountAuthRequest::RemoveAppPassword { name } => {
-                    (PrincipalAction::RemoveItem, format!("$app${name}"))
+                    actions.push(PrincipalUpdate {
+                        action: PrincipalAction::RemoveItem,
+                        field: PrincipalField::AppPasswords,
+                        value: PrincipalValue::String(name.clone()),
+                    });
+
+                    // Also remove app passwords created before they had their own field
+                    (PrincipalAction::RemoveItem, format!("$app${name}$"))
                 }
             };
 
@@ -577,9 +681,16 @@ impl JMAP {
             .await?;
 
         // Remove entries from cache
-        self.inner
-            .sessions
-            .retain(|_, id| id.item != access_token.primary_id());
+        self.remove_cached_sessions(access_token.primary_id());
+        self.invalidate_account_auth_cache(access_token.primary_id())
+            .await?;
+
+        if revoke_sessions {
+            // OAuth tokens of internal accounts are not bound to the password
+            self.core
+                .revoke_account_sessions(access_token.primary_id())
+                .await?;
+        }
 
         Ok(JsonResponse::new(json!({
             "data": (),
@@ -587,6 +698,20 @@ impl JMAP {
         .into_http_response())
     }
 
+    async fn invalidate_account_auth_cache(&self, account_id: u32) -> trc::Result<()> {
+        let names = self
+            .core
+            .storage
+            .data
+            .get_principal(account_id)
+            .await?
+            .map(|principal| login_names(&principal))
+            .unwrap_or_default();
+        self.core.invalidate_auth_cache(account_id, &names).await;
+
+        Ok(())
+    }
+
     pub fn assert_supported_directory(&self) -> trc::Result<()> {
         let class = match &self.core.storage.directory.store {
             DirectoryInner::Internal(_) => return Ok(()),
@@ -607,3 +732,12 @@ impl JMAP {
         )))
     }
 }
+
+// Names a principal can log in with
+fn login_names(principal: &Principal) -> Vec<String> {
+    principal
+        .iter_str(PrincipalField::Name)
+        .chain(principal.iter_str(PrincipalField::Emails))
+        .cloned()
+        .collect()
+}
@@ -4,6 +4,8 @@
  * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
  */
 
+use std::sync::atomic::Ordering;
+
 use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
 use common::auth::AccessToken;
 use directory::{
@@ -19,9 +21,13 @@ use mail_auth::{
 use mail_parser::DateTime;
 use serde::{Deserializer, Serializer};
 use serde_json::json;
-use smtp::queue::{self, ErrorDetails, HostResponse, QueueId, Status};
+use smtp::queue::{
+    self,
+    spool::{QueueFilter, QueuePagination},
+    QueueId, Status,
+};
 use store::{
-    write::{key::DeserializeBigEndian, now, Bincode, QueueClass, ReportEvent, ValueClass},
+    write::{now, QueueClass, ReportEvent, ValueClass},
     Deserialize, IterateParams, ValueKey,
 };
 use trc::AddContext;
@@ -77,6 +83,14 @@ pub struct Recipient {
     pub orcpt: Option<String>,
 }
 
+#[derive(Debug, serde::Serialize, serde::Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct DomainConcurrency {
+    pub domain: String,
+    pub in_flight: u64,
+    pub limit: u64,
+}
+
 #[derive(Debug, serde::Serialize, serde::Deserialize)]
 #[serde(tag = "type")]
 pub enum Report {
@@ -160,106 +174,41 @@ impl JMAP {
                 // Validate the access token
                 access_token.assert_has_permission(Permission::MessageQueueList)?;
 
-                let text = params.get("text");
-                let from = params.get("from");
-                let to = params.get("to");
-                let before = params
-                    .parse::<FutureTimestamp>("before")
-                    .map(|t| t.into_inner());
-                let after = params
-                    .parse::<FutureTimestamp>("after")
-                    .map(|t| t.into_inner());
-                let page = params.parse::<usize>("page").unwrap_or_default();
-                let limit = params.parse::<usize>("limit").unwrap_or_default();
+                let filter = QueueFilter {
+                    text: params.get("text"),
+                    from: params.get("from"),
+                    to: params.get("to"),
+                    before: params
+                        .parse::<FutureTimestamp>("before")
+                        .map(|t| t.into_inner()),
+                    after: params
+                        .parse::<FutureTimestamp>("after")
+                        .map(|t| t.into_inner()),
+                    domains: tenant_domains.as_deref(),
+                };
+                let pagination = QueuePagination {
+                    page: params.parse::<usize>("page").unwrap_or_default(),
+                    limit: params.parse::<usize>("limit").unwrap_or_default(),
+                    max_total: params.parse::<usize>("max-total").unwrap_or_default(),
+                    range_start: params.parse::<u64>("range-start").unwrap_or_default(),
+                    range_end: params.parse::<u64>("range-end").unwrap_or(u64::MAX),
+                };
                 let values = params.has_key("values");
 
-                let range_start = params.parse::<u64>("range-start").unwrap_or_default();
-                let range_end = params.parse::<u64>("range-end").unwrap_or(u64::MAX);
-                let max_total = params.parse::<usize>("max-total").unwrap_or_default();
-
-                let mut result_ids = Vec::new();
-                let mut result_values = Vec::new();
-                let from_key = ValueKey::from(ValueClass::Queue(QueueClass::Message(range_start)));
-                let to_key = ValueKey::from(ValueClass::Queue(QueueClass::Message(range_end)));
-                let has_filters = text.is_some()
-                    || from.is_some()
-                    || to.is_some()
-                    || before.is_some()
-                    || after.is_some();
-                let mut offset = page.saturating_sub(1) * limit;
-                let mut total = 0;
-                let mut total_returned = 0;
-                self.core
-                    .storage
-                    .data
-                    .iterate(
-                        IterateParams::new(from_key, to_key).ascending(),
-                        |key, value| {
-                            let message = Bincode::<queue::Message>::deserialize(value)?.inner;
-                            let matches = tenant_domains
-                                .as_ref()
-                                .map_or(true, |domains| message.has_domain(domains))
-                                && (!has_filters
-                                    || (text
-                                        .as_ref()
-                                        .map(|text| {
-                                            message.return_path.contains(text)
-                                                || message
-                                                    .recipients
-                                                    .iter()
-                                                    .any(|r| r.address_lcase.contains(text))
-                                        })
-                                        .unwrap_or_else(|| {
-                                            from.as_ref().map_or(true, |from| {
-                                                message.return_path.contains(from)
-                                            }) && to.as_ref().map_or(true, |to| {
-                                                message
-                                                    .recipients
-                                                    .iter()
-                                                    .any(|r| r.address_lcase.contains(to))
-                                            })
-                                        })
-                                        && before.as_ref().map_or(true, |before| {
-                                            message.next_delivery_event() < *before
-                                        })
-                                        && after.as_ref().map_or(true, |after| {
-                                            message.next_delivery_event() > *after
-                                        })));
-
-                            if matches {
-                                if offset == 0 {
-                                    if limit == 0 || total_returned < limit {
-                                        if values {
-                                            result_values.push(Message::from(&message));
-                                        } else {
-                                            result_ids.push(key.deserialize_be_u64(0)?);
-                                        }
-                                        total_returned += 1;
-                                    }
-                                } else {
-                                    offset -= 1;
-                                }
-
-                                total += 1;
-                            }
-
-                            Ok(max_total == 0 || total < max_total)
-                        },
-                    )
-                    .await?;
+                let result = self.smtp.list_queued(&filter, pagination).await?;
 
                 Ok(if values {
                     JsonResponse::new(json!({
                             "data":{
-                                "items": result_values,
-                                "total": total,
+                                "items": result.items.iter().map(Message::from).collect::<Vec<_>>(),
+                                "total": result.total,
                             },
                     }))
                 } else {
                     JsonResponse::new(json!({
                             "data": {
-                                "items": result_ids,
-                                "total": total,
+                                "items": result.items.iter().map(|message| message.queue_id).collect::<Vec<_>>(),
+                                "total": result.total,
                             },
                     }))
                 }
@@ -346,89 +295,58 @@ impl JMAP {
                 // Validate the access token
                 access_token.assert_has_permission(Permission::MessageQueueDelete)?;
 
-                if let Some(mut message) = self
+                let found = self
                     .smtp
-                    .read_message(queue_id.parse().unwrap_or_default())
+                    .cancel_queued(
+                        queue_id.parse().unwrap_or_default(),
+                        params.get("filter"),
+                        tenant_domains.as_deref(),
+                        params.parse::<bool>("bounce").unwrap_or_default(),
+                    )
                     .await
-                    .filter(|message| {
-                        tenant_domains
-                            .as_ref()
-                            .map_or(true, |domains| message.has_domain(domains))
-                    })
-                {
-                    let mut found = false;
-                    let prev_event = message.next_event().unwrap_or_default();
-
-                    if let Some(item) = params.get("filter") {
-                        // Cancel delivery for all recipients that match
-                        for rcpt in &mut message.recipients {
-                            if rcpt.address_lcase.contains(item) {
-                                rcpt.status = Status::PermanentFailure(HostResponse {
-                                    hostname: ErrorDetails::default(),
-                                    response: smtp_proto::Response {
-                                        code: 0,
-                                        esc: [0, 0, 0],
-                                        message: "Delivery canceled.".to_string(),
-                                    },
-                                });
-                                found = true;
-                            }
+                    .map_err(|err| {
+                        if err.matches(trc::EventType::Queue(trc::QueueEvent::Locked)) {
+                            trc::ManageEvent::Error
+                                .into_err()
+                                .details("Message is currently being delivered, try again later.")
+                        } else {
+                            err
                         }
-                        if found {
-                            // Mark as completed domains without any pending deliveries
-                            for (domain_idx, domain) in message.domains.iter_mut().enumerate() {
-                                if matches!(
-                                    domain.status,
-                                    Status::TemporaryFailure(_) | Status::Scheduled
-                                ) {
-                                    let mut total_rcpt = 0;
-                                    let mut total_completed = 0;
-
-                                    for rcpt in &message.recipients {
-                                        if rcpt.domain_idx == domain_idx {
-                                            total_rcpt += 1;
-                                            if matches!(
-                                                rcpt.status,
-                                                Status::PermanentFailure(_) | Status::Completed(_)
-                                            ) {
-                                                total_completed += 1;
-                                            }
-                                        }
-                                    }
+                    })?;
 
-                                    if total_rcpt == total_completed {
-                                        domain.status = Status::Completed(());
-                                    }
-                                }
-                            }
+                Ok(JsonResponse::new(json!({
+                        "data": found,
+                }))
+                .into_http_response())
+            }
+            ("concurrency", None, &Method::GET) => {
+                // Validate the access token
+                access_token.assert_has_permission(Permission::MessageQueueList)?;
 
-                            // Delete message if there are no pending deliveries
-                            if message.domains.iter().any(|domain| {
-                                matches!(
-                                    domain.status,
-                                    Status::TemporaryFailure(_) | Status::Scheduled
-                                )
-                            }) {
-                                let next_event = message.next_event().unwrap_or_default();
-                                message
-                                    .save_changes(&self.smtp, next_event.into(), prev_event.into())
-                                    .await;
-                            } else {
-                                message.remove(&self.smtp, prev_event).await;
-                            }
-                        }
-                    } else {
-                        message.remove(&self.smtp, prev_event).await;
-                        found = true;
-                    }
+                // Deliveries currently in flight to each destination domain
+                let mut items = self
+                    .smtp
+                    .inner
+                    .domain_concurrency
+                    .iter()
+                    .filter(|entry| {
+                        tenant_domains
+                            .as_ref()
+                            .map_or(true, |domains| domains.contains(entry.key()))
+                    })
+                    .map(|entry| DomainConcurrency {
+                        domain: entry.key().clone(),
+                        in_flight: entry.concurrent.load(Ordering::Relaxed),
+                        limit: entry.max_concurrent,
+                    })
+                    .filter(|item| item.in_flight > 0)
+                    .collect::<Vec<_>>();
+                items.sort_unstable_by(|a, b| b.in_flight.cmp(&a.in_flight));
 
-                    Ok(JsonResponse::new(json!({
-                            "data": found,
-                    }))
-                    .into_http_response())
-                } else {
-                    Err(trc::ResourceEvent::NotFound.into_err())
-                }
+                Ok(JsonResponse::new(json!({
+                    "data": items,
+                }))
+                .into_http_response())
             }
             ("reports", None, &Method::GET) => {
                 // Validate the access token
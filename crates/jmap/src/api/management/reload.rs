@@ -4,7 +4,9 @@
  * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
  */
 
-use common::auth::AccessToken;
+use std::time::Instant;
+
+use common::{auth::AccessToken, manager::reload::reload_core};
 use directory::Permission;
 use hyper::Method;
 use serde_json::json;
@@ -39,10 +41,25 @@ impl JMAP {
                 }))
                 .into_http_response())
             }
-            (Some("certificate"), &Method::GET) => Ok(JsonResponse::new(json!({
-                "data": self.core.reload_certificates().await?.config,
-            }))
-            .into_http_response()),
+            (Some("certificate"), &Method::GET) => {
+                let result = self.core.reload_certificates().await?;
+
+                // Staple the reloaded certificates
+                if self.core.tls.ocsp.enable {
+                    self.inner
+                        .housekeeper_tx
+                        .send(Event::OcspReschedule {
+                            refresh_at: Instant::now(),
+                        })
+                        .await
+                        .ok();
+                }
+
+                Ok(JsonResponse::new(json!({
+                    "data": result.config,
+                }))
+                .into_http_response())
+            }
             (Some("server.blocked-ip"), &Method::GET) => {
                 let result = self.core.reload_blocked_ips().await?;
                 // Increment version counter
@@ -54,17 +71,21 @@ impl JMAP {
                 .into_http_response())
             }
             (_, &Method::GET) => {
-                let result = self.core.reload().await?;
-                if !UrlParams::new(req.uri().query()).has_key("dry-run") {
-                    if let Some(core) = result.new_core {
-                        // Update core
-                        self.shared_core.store(core.into());
+                let result = if UrlParams::new(req.uri().query()).has_key("dry-run") {
+                    self.core.reload().await?
+                } else {
+                    let mut result = reload_core(
+                        &self.shared_core,
+                        self.core.storage.config.build_config("").await?,
+                    )
+                    .await?;
 
+                    if result.applied {
                         // Increment version counter
                         self.inner.increment_config_version();
                     }
 
-                    if let Some(tracers) = result.tracers {
+                    if let Some(tracers) = result.tracers.take() {
                         // Update tracers
                         #[cfg(feature = "enterprise")]
                         tracers.update(self.shared_core.load().is_enterprise_edition());
@@ -83,10 +104,13 @@ impl JMAP {
                                 .details("Failed to send settings reload event to housekeeper")
                                 .caused_by(trc::location!())
                         })?;
-                }
+
+                    result
+                };
 
                 Ok(JsonResponse::new(json!({
                     "data": result.config,
+                    "changes": result.changes,
                 }))
                 .into_http_response())
             }
@@ -0,0 +1,62 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use common::auth::AccessToken;
+use directory::{
+    backend::internal::manage::{self, ManageDirectory},
+    Permission,
+};
+use hyper::Method;
+use serde_json::json;
+
+use crate::{
+    api::{http::ToHttpResponse, HttpRequest, HttpResponse, JsonResponse},
+    JMAP,
+};
+
+use super::decode_path_element;
+
+impl JMAP {
+    pub async fn handle_manage_sessions(
+        &self,
+        req: &HttpRequest,
+        path: Vec<&str>,
+        access_token: &AccessToken,
+    ) -> trc::Result<HttpResponse> {
+        let name = path
+            .get(1)
+            .copied()
+            .map(decode_path_element)
+            .ok_or_else(|| trc::ResourceEvent::NotFound.into_err())?;
+
+        if req.method() != Method::DELETE {
+            return Err(trc::ResourceEvent::NotFound.into_err());
+        }
+
+        // Validate the access token
+        access_token.assert_has_permission(Permission::IndividualUpdate)?;
+
+        let account_id = self
+            .core
+            .storage
+            .data
+            .get_principal_info(name.as_ref())
+            .await?
+            .filter(|p| p.has_tenant_access(access_token.tenant.map(|t| t.id)))
+            .map(|p| p.id)
+            .ok_or_else(|| manage::not_found(name.to_string()))?;
+
+        // Sign out everywhere, the HTTP caches are dropped as well so that
+        // cached Authorization headers are validated again
+        self.core.revoke_account_sessions(account_id).await?;
+        self.remove_cached_sessions(account_id);
+
+        Ok(JsonResponse::new(json!({
+            "data": (),
+        }))
+        .into_http_response())
+    }
+}
@@ -19,6 +19,7 @@ use crate::{
         http::{HttpSessionData, ToHttpResponse},
         HttpRequest, HttpResponse, JsonResponse,
     },
+    email::bulk_import::ImportRequest,
     services::housekeeper::{Event, PurgeType},
     JMAP,
 };
@@ -163,6 +164,73 @@ impl JMAP {
                 }))
                 .into_http_response())
             }
+            (Some("rethread"), Some(id), None, &Method::POST) => {
+                // Validate the access token
+                access_token.assert_has_permission(Permission::ThreadRebuild)?;
+
+                let account_id = self
+                    .core
+                    .storage
+                    .data
+                    .get_principal_info(decode_path_element(id).as_ref())
+                    .await?
+                    .filter(|p| p.has_tenant_access(access_token.tenant.map(|t| t.id)))
+                    .map(|p| p.id)
+                    .ok_or_else(|| trc::ManageEvent::NotFound.into_err())?;
+                let mailbox_id =
+                    if let Some(mailbox) = UrlParams::new(req.uri().query()).get("mailbox") {
+                        Some(
+                            self.mailbox_get_by_name(account_id, mailbox)
+                                .await?
+                                .ok_or_else(|| trc::ManageEvent::NotFound.into_err())?,
+                        )
+                    } else {
+                        None
+                    };
+
+                Ok(JsonResponse::new(json!({
+                    "data": self.rebuild_threads(account_id, mailbox_id).await?,
+                }))
+                .into_http_response())
+            }
+            (Some("import"), Some(id), None, method @ (&Method::POST | &Method::GET)) => {
+                // Validate the access token
+                access_token.assert_has_permission(Permission::MessageImport)?;
+
+                let account_id = self
+                    .core
+                    .storage
+                    .data
+                    .get_principal_info(decode_path_element(id).as_ref())
+                    .await?
+                    .filter(|p| p.has_tenant_access(access_token.tenant.map(|t| t.id)))
+                    .map(|p| p.id)
+                    .ok_or_else(|| trc::ManageEvent::NotFound.into_err())?;
+
+                if *method == Method::POST {
+                    let request = serde_json::from_slice::<ImportRequest>(
+                        body.as_deref().unwrap_or_default(),
+                    )
+                    .map_err(|err| {
+                        trc::EventType::Resource(trc::ResourceEvent::BadParameters)
+                            .from_json_error(err)
+                    })?;
+
+                    self.start_import(account_id, request, session.session_id)?;
+
+                    Ok(JsonResponse::new(json!({
+                        "data": (),
+                    }))
+                    .into_http_response())
+                } else {
+                    Ok(JsonResponse::new(json!({
+                        "data": self
+                            .import_status(account_id)
+                            .ok_or_else(|| trc::ManageEvent::NotFound.into_err())?,
+                    }))
+                    .into_http_response())
+                }
+            }
             // SPDX-SnippetBegin
             // SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
             // SPDX-License-Identifier: LicenseRef-SEL
@@ -54,6 +54,8 @@ pub struct HttpResponse {
     pub content_type: Cow<'static, str>,
     pub content_disposition: Cow<'static, str>,
     pub cache_control: Cow<'static, str>,
+    pub etag: Cow<'static, str>,
+    pub last_modified: Cow<'static, str>,
     pub body: HttpResponseBody,
 }
 
@@ -6,8 +6,8 @@
 
 use std::{net::IpAddr, sync::Arc, time::Instant};
 
-use common::listener::limiter::InFlight;
-use directory::Permission;
+use common::{config::server::ServerProtocol, listener::limiter::InFlight};
+use directory::{core::scram::ScramExchange, Permission};
 use hyper::header;
 use mail_parser::decoders::base64::base64_decode;
 use mail_send::Credentials;
@@ -18,6 +18,11 @@ use crate::{
     JMAP,
 };
 
+use super::{
+    oauth::{OAuthScope, OAuthScopes},
+    CachedSession,
+};
+
 use common::auth::AccessToken;
 
 impl JMAP {
@@ -25,62 +30,86 @@ impl JMAP {
         &self,
         req: &HttpRequest,
         session: &HttpSessionData,
+        scope: OAuthScope,
     ) -> trc::Result<(InFlight, Arc<AccessToken>)> {
         if let Some((mechanism, token)) = req.authorization() {
-            let access_token = if let Some(account_id) = self.inner.sessions.get_with_ttl(token) {
-                self.core.get_cached_access_token(account_id).await?
-            } else {
-                let access_token = if mechanism.eq_ignore_ascii_case("basic") {
-                    // Enforce rate limit for authentication requests
-                    self.is_auth_allowed_soft(&session.remote_ip).await?;
-
-                    // Decode the base64 encoded credentials
-                    if let Some((account, secret)) = base64_decode(token.as_bytes())
-                        .and_then(|token| String::from_utf8(token).ok())
-                        .and_then(|token| {
-                            token.split_once(':').map(|(login, secret)| {
-                                (login.trim().to_lowercase(), secret.to_string())
+            let (access_token, scopes) =
+                if let Some(cached) = self.inner.sessions.get_with_ttl(token) {
+                    (
+                        self.core.get_cached_access_token(cached.account_id).await?,
+                        cached.scopes,
+                    )
+                } else {
+                    let (access_token, scopes, grant) = if mechanism.eq_ignore_ascii_case("basic") {
+                        // Enforce rate limit for authentication requests
+                        self.is_auth_allowed_soft(&session.remote_ip).await?;
+
+                        // Decode the base64 encoded credentials
+                        if let Some((account, secret)) = base64_decode(token.as_bytes())
+                            .and_then(|token| String::from_utf8(token).ok())
+                            .and_then(|token| {
+                                token.split_once(':').map(|(login, secret)| {
+                                    (login.trim().to_lowercase(), secret.to_string())
+                                })
                             })
-                        })
-                    {
-                        self.authenticate_plain(
-                            &account,
-                            &secret,
-                            session.remote_ip,
-                            session.session_id,
+                        {
+                            (
+                                self.authenticate_plain(
+                                    &account,
+                                    &secret,
+                                    session.remote_ip,
+                                    ServerProtocol::Http,
+                                    session.session_id,
+                                )
+                                .await?,
+                                OAuthScopes::all(),
+                                None,
+                            )
+                        } else {
+                            return Err(trc::AuthEvent::Error
+                                .into_err()
+                                .details("Failed to decode Basic auth request.")
+                                .id(token.to_string())
+                                .caused_by(trc::location!()));
+                        }
+                    } else if mechanism.eq_ignore_ascii_case("bearer") {
+                        // Enforce anonymous rate limit for bearer auth requests
+                        self.is_anonymous_allowed(&session.remote_ip).await?;
+
+                        let token_info = self
+                            .core
+                            .authenticate_access_token(token, session.remote_ip, session.session_id)
+                            .await?;
+
+                        (
+                            self.core.get_access_token(token_info.account_id).await?,
+                            token_info.scopes,
+                            Some(token_info.grant_id),
                         )
-                        .await?
                     } else {
+                        // Enforce anonymous rate limit
+                        self.is_anonymous_allowed(&session.remote_ip).await?;
                         return Err(trc::AuthEvent::Error
                             .into_err()
-                            .details("Failed to decode Basic auth request.")
-                            .id(token.to_string())
+                            .reason("Unsupported authentication mechanism.")
+                            .details(token.to_string())
                             .caused_by(trc::location!()));
-                    }
-                } else if mechanism.eq_ignore_ascii_case("bearer") {
-                    // Enforce anonymous rate limit for bearer auth requests
-                    self.is_anonymous_allowed(&session.remote_ip).await?;
-
-                    let (account_id, _, _) =
-                        self.validate_access_token("access_token", token).await?;
+                    };
 
-                    self.core.get_access_token(account_id).await?
-                } else {
-                    // Enforce anonymous rate limit
-                    self.is_anonymous_allowed(&session.remote_ip).await?;
-                    return Err(trc::AuthEvent::Error
-                        .into_err()
-                        .reason("Unsupported authentication mechanism.")
-                        .details(token.to_string())
-                        .caused_by(trc::location!()));
+                    // Cache session
+                    let access_token = Arc::new(access_token);
+                    self.cache_session(token.to_string(), &access_token, scopes, grant);
+                    self.core.cache_access_token(access_token.clone());
+                    (access_token, scopes)
                 };
 
-                // Cache session
-                let access_token = Arc::new(access_token);
-                self.cache_session(token.to_string(), &access_token);
-                self.core.cache_access_token(access_token.clone());
-                access_token
-            };
+            // Make sure the token was issued for this resource
+            if !scopes.contains(scope) {
+                return Err(trc::SecurityEvent::Unauthorized
+                    .into_err()
+                    .details("Access token scope does not cover this resource.")
+                    .ctx(trc::Key::Id, scope.as_str()));
+            }
 
             // Enforce authenticated rate limit
             self.is_account_allowed(&access_token)
@@ -97,19 +126,41 @@ impl JMAP {
         }
     }
 
-    pub fn cache_session(&self, session_id: String, access_token: &AccessToken) {
+    pub fn cache_session(
+        &self,
+        session_id: String,
+        access_token: &AccessToken,
+        scopes: OAuthScopes,
+        grant_id: Option<u64>,
+    ) {
         self.inner.sessions.insert_with_ttl(
             session_id,
-            access_token.primary_id(),
+            CachedSession {
+                account_id: access_token.primary_id(),
+                scopes,
+                grant_id,
+            },
             Instant::now() + self.core.jmap.session_cache_ttl,
         );
     }
 
+    // Sessions and OAuth tokens are bound to the account credentials
+    pub fn remove_cached_sessions(&self, account_id: u32) {
+        self.inner
+            .sessions
+            .retain(|_, session| session.item.account_id != account_id);
+        self.core
+            .security
+            .oauth_tokens
+            .retain(|_, token| token.item.account_id != account_id);
+    }
+
     pub async fn authenticate_plain(
         &self,
         username: &str,
         secret: &str,
         remote_ip: IpAddr,
+        protocol: ServerProtocol,
         session_id: u64,
     ) -> trc::Result<AccessToken> {
         match self
@@ -122,6 +173,7 @@ impl JMAP {
                     secret: secret.to_string(),
                 },
                 remote_ip,
+                protocol,
                 true,
             )
             .await
@@ -136,7 +188,49 @@ impl JMAP {
                         .map(|_| token)
                 }),
             Err(err) => {
-                if !err.matches(trc::EventType::Auth(trc::AuthEvent::MissingTotp)) {
+                // Only rejected credentials count towards the rate limit
+                if err.matches(trc::EventType::Auth(trc::AuthEvent::Failed)) {
+                    let _ = self.is_auth_allowed_hard(&remote_ip).await;
+                }
+                Err(err)
+            }
+        }
+    }
+
+    // Verifies the SCRAM client proof, returning the access token of the
+    // account along with the server-final message
+    pub async fn authenticate_scram(
+        &self,
+        exchange: &ScramExchange,
+        client_final: &[u8],
+        channel_binding: Option<&[u8]>,
+        remote_ip: IpAddr,
+        session_id: u64,
+    ) -> trc::Result<(AccessToken, Vec<u8>)> {
+        match self
+            .core
+            .authenticate_scram(
+                &self.core.storage.directory,
+                session_id,
+                exchange,
+                client_final,
+                channel_binding,
+                remote_ip,
+                true,
+            )
+            .await
+        {
+            Ok((principal, server_final)) => self
+                .core
+                .build_access_token(principal)
+                .await
+                .and_then(|token| {
+                    token
+                        .assert_has_permission(Permission::Authenticate)
+                        .map(|_| (token, server_final))
+                }),
+            Err(err) => {
+                if err.matches(trc::EventType::Auth(trc::AuthEvent::Failed)) {
                     let _ = self.is_auth_allowed_hard(&remote_ip).await;
                 }
                 Err(err)
@@ -4,50 +4,16 @@
  * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
  */
 
-use aes_gcm_siv::{
-    aead::{generic_array::GenericArray, Aead},
-    AeadInPlace, Aes256GcmSiv, KeyInit, Nonce,
-};
-
-use store::blake3;
+use self::oauth::OAuthScopes;
 
 pub mod acl;
 pub mod authenticate;
 pub mod oauth;
 pub mod rate_limit;
 
-pub struct SymmetricEncrypt {
-    aes: Aes256GcmSiv,
-}
-
-impl SymmetricEncrypt {
-    pub const ENCRYPT_TAG_LEN: usize = 16;
-    pub const NONCE_LEN: usize = 12;
-
-    pub fn new(key: &[u8], context: &str) -> Self {
-        SymmetricEncrypt {
-            aes: Aes256GcmSiv::new(&GenericArray::clone_from_slice(
-                &blake3::derive_key(context, key)[..],
-            )),
-        }
-    }
-
-    #[allow(clippy::ptr_arg)]
-    pub fn encrypt_in_place(&self, bytes: &mut Vec<u8>, nonce: &[u8]) -> Result<(), String> {
-        self.aes
-            .encrypt_in_place(Nonce::from_slice(nonce), b"", bytes)
-            .map_err(|e| e.to_string())
-    }
-
-    pub fn encrypt(&self, bytes: &[u8], nonce: &[u8]) -> Result<Vec<u8>, String> {
-        self.aes
-            .encrypt(Nonce::from_slice(nonce), bytes)
-            .map_err(|e| e.to_string())
-    }
-
-    pub fn decrypt(&self, bytes: &[u8], nonce: &[u8]) -> Result<Vec<u8>, String> {
-        self.aes
-            .decrypt(Nonce::from_slice(nonce), bytes)
-            .map_err(|e| e.to_string())
-    }
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CachedSession {
+    pub account_id: u32,
+    pub scopes: OAuthScopes,
+    pub grant_id: Option<u64>,
 }
@@ -6,7 +6,7 @@
 
 use std::sync::Arc;
 
-use common::auth::AccessToken;
+use common::{auth::AccessToken, config::jmap::settings::OAuthGrant};
 use rand::distributions::Standard;
 use serde_json::json;
 use store::{
@@ -22,8 +22,8 @@ use crate::{
 };
 
 use super::{
-    DeviceAuthResponse, FormData, OAuthCode, OAuthCodeRequest, CLIENT_ID_MAX_LEN, DEVICE_CODE_LEN,
-    MAX_POST_LEN, USER_CODE_ALPHABET, USER_CODE_LEN,
+    CodeChallenge, DeviceAuthResponse, FormData, OAuthCode, OAuthCodeRequest, OAuthScope,
+    CLIENT_ID_MAX_LEN, DEVICE_CODE_LEN, MAX_POST_LEN, USER_CODE_ALPHABET, USER_CODE_LEN,
 };
 
 impl JMAP {
@@ -42,6 +42,9 @@ impl JMAP {
             OAuthCodeRequest::Code {
                 client_id,
                 redirect_uri,
+                code_challenge,
+                code_challenge_method,
+                scope,
             } => {
                 // Validate clientId
                 if client_id.len() > CLIENT_ID_MAX_LEN {
@@ -55,8 +58,34 @@ impl JMAP {
                     return Err(trc::ManageEvent::Error
                         .into_err()
                         .details("Redirect URI must be HTTPS."));
+                } else if !self.core.jmap.is_oauth_client_allowed(
+                    &client_id,
+                    OAuthGrant::AuthorizationCode,
+                    redirect_uri.as_deref(),
+                ) {
+                    return Err(trc::ManageEvent::Error
+                        .into_err()
+                        .details("Client ID or redirect URI is not registered."));
                 }
 
+                // Validate PKCE code challenge
+                let code_challenge = match code_challenge {
+                    Some(code_challenge) => Some(
+                        CodeChallenge::parse(code_challenge, code_challenge_method.as_deref())
+                            .map_err(|err| trc::ManageEvent::Error.into_err().details(err))?,
+                    ),
+                    None if self.core.jmap.is_pkce_required(&client_id) => {
+                        return Err(trc::ManageEvent::Error
+                            .into_err()
+                            .details("PKCE code challenge is required."));
+                    }
+                    None => None,
+                };
+
+                // Validate requested scopes
+                let scopes = OAuthScope::parse_scopes(scope.as_deref())
+                    .map_err(|err| trc::ManageEvent::Error.into_err().details(err))?;
+
                 // Generate client code
                 let client_code = thread_rng()
                     .sample_iter(Alphanumeric)
@@ -70,6 +99,8 @@ impl JMAP {
                     account_id: access_token.primary_id(),
                     client_id,
                     params: redirect_uri.unwrap_or_default(),
+                    code_challenge,
+                    scopes,
                 })
                 .serialize();
 
@@ -150,8 +181,8 @@ impl JMAP {
         session_id: u64,
     ) -> trc::Result<HttpResponse> {
         // Parse form
-        let client_id = FormData::from_request(req, MAX_POST_LEN, session_id)
-            .await?
+        let mut params = FormData::from_request(req, MAX_POST_LEN, session_id).await?;
+        let client_id = params
             .remove("client_id")
             .filter(|client_id| client_id.len() < CLIENT_ID_MAX_LEN)
             .ok_or_else(|| {
@@ -159,6 +190,17 @@ impl JMAP {
                     .into_err()
                     .details("Client ID is missing.")
             })?;
+        if !self
+            .core
+            .jmap
+            .is_oauth_client_allowed(&client_id, OAuthGrant::DeviceCode, None)
+        {
+            return Err(trc::ResourceEvent::BadParameters
+                .into_err()
+                .details("Client ID is not registered."));
+        }
+        let scopes = OAuthScope::parse_scopes(params.get("scope"))
+            .map_err(|err| trc::ResourceEvent::BadParameters.into_err().details(err))?;
 
         // Generate device code
         let device_code = thread_rng()
@@ -187,6 +229,8 @@ impl JMAP {
             account_id: u32::MAX,
             client_id,
             params: device_code.clone(),
+            code_challenge: None,
+            scopes,
         })
         .serialize();
 
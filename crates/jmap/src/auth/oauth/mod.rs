@@ -6,8 +6,14 @@
 
 use std::collections::HashMap;
 
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
 use hyper::header::CONTENT_TYPE;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+pub use common::auth::oauth::{
+    OAuthScope, OAuthScopes, TokenInfo, CLIENT_ID_MAX_LEN, RANDOM_CODE_LEN,
+};
 
 use crate::api::{http::fetch_body, HttpRequest};
 
@@ -23,8 +29,8 @@ pub enum OAuthStatus {
 
 const DEVICE_CODE_LEN: usize = 40;
 const USER_CODE_LEN: usize = 8;
-const RANDOM_CODE_LEN: usize = 32;
-const CLIENT_ID_MAX_LEN: usize = 20;
+const CODE_VERIFIER_MIN_LEN: usize = 43;
+const CODE_VERIFIER_MAX_LEN: usize = 128;
 
 const MAX_POST_LEN: usize = 2048;
 
@@ -47,6 +53,22 @@ pub struct OAuthCode {
     pub account_id: u32,
     pub client_id: String,
     pub params: String,
+    pub code_challenge: Option<CodeChallenge>,
+    pub scopes: OAuthScopes,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct CodeChallenge {
+    pub method: CodeChallengeMethod,
+    pub challenge: String,
+}
+
+#[derive(Copy, Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub enum CodeChallengeMethod {
+    #[serde(rename = "plain")]
+    Plain,
+    #[serde(rename = "S256")]
+    S256,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -83,6 +105,8 @@ pub struct CodeAuthRequest {
     redirect_uri: String,
     scope: Option<String>,
     state: Option<String>,
+    code_challenge: Option<String>,
+    code_challenge_method: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -100,6 +124,7 @@ pub struct TokenRequest {
     pub client_id: Option<String>,
     pub refresh_token: Option<String>,
     pub redirect_uri: Option<String>,
+    pub code_verifier: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
@@ -118,6 +143,36 @@ pub struct OAuthResponse {
     pub refresh_token: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub scope: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id_token: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct IdTokenClaims {
+    pub iss: String,
+    pub sub: String,
+    pub aud: String,
+    pub iat: u64,
+    pub exp: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct IntrospectResponse {
+    pub active: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exp: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sub: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
@@ -153,6 +208,28 @@ pub struct OAuthMetadata {
     pub response_types_supported: Vec<String>,
     pub scopes_supported: Vec<String>,
     pub authorization_endpoint: String,
+    pub introspection_endpoint: String,
+    pub revocation_endpoint: String,
+    pub code_challenge_methods_supported: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OpenIdMetadata {
+    pub issuer: String,
+    pub authorization_endpoint: String,
+    pub token_endpoint: String,
+    pub introspection_endpoint: String,
+    pub revocation_endpoint: String,
+    pub device_authorization_endpoint: String,
+    pub jwks_uri: String,
+    pub response_types_supported: Vec<String>,
+    pub grant_types_supported: Vec<String>,
+    pub subject_types_supported: Vec<String>,
+    pub id_token_signing_alg_values_supported: Vec<String>,
+    pub scopes_supported: Vec<String>,
+    pub claims_supported: Vec<String>,
+    pub token_endpoint_auth_methods_supported: Vec<String>,
+    pub code_challenge_methods_supported: Vec<String>,
 }
 
 impl OAuthMetadata {
@@ -162,6 +239,8 @@ impl OAuthMetadata {
             issuer: base_url.into(),
             authorization_endpoint: format!("{}/authorize/code", base_url),
             token_endpoint: format!("{}/auth/token", base_url),
+            introspection_endpoint: format!("{}/auth/introspect", base_url),
+            revocation_endpoint: format!("{}/auth/revoke", base_url),
             grant_types_supported: vec![
                 "authorization_code".to_string(),
                 "implicit".to_string(),
@@ -169,7 +248,40 @@ impl OAuthMetadata {
             ],
             device_authorization_endpoint: format!("{}/auth/device", base_url),
             response_types_supported: vec!["code".to_string(), "code token".to_string()],
-            scopes_supported: vec!["offline_access".to_string()],
+            scopes_supported: OAuthScope::list_scopes(OAuthScopes::all())
+                .into_iter()
+                .map(String::from)
+                .collect(),
+            code_challenge_methods_supported: vec!["plain".to_string(), "S256".to_string()],
+        }
+    }
+}
+
+impl OpenIdMetadata {
+    pub fn new(base_url: impl AsRef<str>, signing_algorithm: Option<&str>) -> Self {
+        let metadata = OAuthMetadata::new(base_url);
+
+        OpenIdMetadata {
+            jwks_uri: format!("{}/auth/jwks.json", metadata.issuer),
+            issuer: metadata.issuer,
+            authorization_endpoint: metadata.authorization_endpoint,
+            token_endpoint: metadata.token_endpoint,
+            introspection_endpoint: metadata.introspection_endpoint,
+            revocation_endpoint: metadata.revocation_endpoint,
+            device_authorization_endpoint: metadata.device_authorization_endpoint,
+            response_types_supported: metadata.response_types_supported,
+            grant_types_supported: metadata.grant_types_supported,
+            subject_types_supported: vec!["public".to_string()],
+            id_token_signing_alg_values_supported: signing_algorithm
+                .map(|alg| vec![alg.to_string()])
+                .unwrap_or_default(),
+            scopes_supported: metadata.scopes_supported,
+            claims_supported: ["iss", "sub", "aud", "iat", "exp", "email", "name"]
+                .into_iter()
+                .map(String::from)
+                .collect(),
+            token_endpoint_auth_methods_supported: vec!["none".to_string()],
+            code_challenge_methods_supported: metadata.code_challenge_methods_supported,
         }
     }
 }
@@ -180,12 +292,65 @@ pub enum OAuthCodeRequest {
     Code {
         client_id: String,
         redirect_uri: Option<String>,
+        #[serde(default)]
+        code_challenge: Option<String>,
+        #[serde(default)]
+        code_challenge_method: Option<String>,
+        #[serde(default)]
+        scope: Option<String>,
     },
     Device {
         code: String,
     },
 }
 
+impl CodeChallenge {
+    pub fn parse(challenge: String, method: Option<&str>) -> Result<Self, &'static str> {
+        let method = match method.unwrap_or("plain") {
+            "plain" => CodeChallengeMethod::Plain,
+            "S256" => CodeChallengeMethod::S256,
+            _ => return Err("Unsupported code challenge method."),
+        };
+
+        if is_valid_code_verifier(&challenge) {
+            Ok(CodeChallenge { method, challenge })
+        } else {
+            Err("Invalid code challenge.")
+        }
+    }
+
+    pub fn verify(&self, code_verifier: &str) -> bool {
+        is_valid_code_verifier(code_verifier)
+            && match self.method {
+                CodeChallengeMethod::Plain => code_verifier == self.challenge,
+                CodeChallengeMethod::S256 => {
+                    URL_SAFE_NO_PAD.encode(Sha256::digest(code_verifier.as_bytes()))
+                        == self.challenge
+                }
+            }
+    }
+}
+
+impl OAuthCode {
+    pub fn verify_code_verifier(&self, code_verifier: Option<&str>) -> bool {
+        match (&self.code_challenge, code_verifier) {
+            (Some(challenge), Some(code_verifier)) => challenge.verify(code_verifier),
+            (Some(_), None) => false,
+            // A verifier without a stored challenge indicates a downgrade attempt
+            (None, Some(_)) => false,
+            (None, None) => true,
+        }
+    }
+}
+
+// RFC 7636 section 4.1: 43-128 characters from the unreserved set
+fn is_valid_code_verifier(value: &str) -> bool {
+    (CODE_VERIFIER_MIN_LEN..=CODE_VERIFIER_MAX_LEN).contains(&value.len())
+        && value
+            .bytes()
+            .all(|ch| ch.is_ascii_alphanumeric() || matches!(ch, b'-' | b'.' | b'_' | b'~'))
+}
+
 impl TokenResponse {
     pub fn error(error: ErrorType) -> Self {
         TokenResponse::Error { error }
@@ -4,28 +4,27 @@
  * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
  */
 
-use std::time::SystemTime;
-
-use directory::{backend::internal::PrincipalField, QueryBy};
+use common::{
+    auth::{oauth::token::AUDIENCE_GRANT_TYPE, AccessToken},
+    config::jmap::settings::OAuthGrant,
+};
+use directory::{backend::internal::PrincipalField, Permission, QueryBy};
 use hyper::StatusCode;
-use mail_builder::encoders::base64::base64_encode;
-use mail_parser::decoders::base64::base64_decode;
+use serde_json::json;
 use store::{
     blake3,
     rand::{thread_rng, Rng},
-    write::Bincode,
+    write::{now, Bincode},
 };
-use utils::codec::leb128::{Leb128Iterator, Leb128Vec};
 
 use crate::{
     api::{http::ToHttpResponse, HttpRequest, HttpResponse, JsonResponse},
-    auth::SymmetricEncrypt,
     JMAP,
 };
 
 use super::{
-    ErrorType, FormData, OAuthCode, OAuthResponse, OAuthStatus, TokenResponse, CLIENT_ID_MAX_LEN,
-    MAX_POST_LEN, RANDOM_CODE_LEN,
+    ErrorType, FormData, IdTokenClaims, IntrospectResponse, OAuthCode, OAuthResponse, OAuthScope,
+    OAuthScopes, OAuthStatus, TokenInfo, TokenResponse, MAX_POST_LEN,
 };
 
 impl JMAP {
@@ -33,11 +32,13 @@ impl JMAP {
     pub async fn handle_token_request(
         &self,
         req: &mut HttpRequest,
+        base_url: impl AsRef<str>,
         session_id: u64,
     ) -> trc::Result<HttpResponse> {
         // Parse form
         let params = FormData::from_request(req, MAX_POST_LEN, session_id).await?;
         let grant_type = params.get("grant_type").unwrap_or_default();
+        let issuer = base_url.as_ref();
 
         let mut response = TokenResponse::error(ErrorType::InvalidGrant);
 
@@ -57,8 +58,17 @@ impl JMAP {
                 {
                     Some(auth_code) => {
                         let oauth = auth_code.inner;
-                        if client_id != oauth.client_id || redirect_uri != oauth.params {
+                        if client_id != oauth.client_id
+                            || redirect_uri != oauth.params
+                            || !self.core.jmap.is_oauth_client_allowed(
+                                client_id,
+                                OAuthGrant::AuthorizationCode,
+                                redirect_uri.into(),
+                            )
+                        {
                             TokenResponse::error(ErrorType::InvalidClient)
+                        } else if !oauth.verify_code_verifier(params.get("code_verifier")) {
+                            TokenResponse::error(ErrorType::InvalidGrant)
                         } else if oauth.status == OAuthStatus::Authorized {
                             // Mark this token as issued
                             self.core
@@ -68,7 +78,15 @@ impl JMAP {
                                 .await?;
 
                             // Issue token
-                            self.issue_token(oauth.account_id, &oauth.client_id, true)
+                            let response = self
+                                .issue_token(
+                                    oauth.account_id,
+                                    &oauth.client_id,
+                                    issuer,
+                                    None,
+                                    oauth.scopes,
+                                    self.core.jmap.oauth_refresh_token_expiry(&oauth.client_id),
+                                )
                                 .await
                                 .map(TokenResponse::Granted)
                                 .map_err(|err| {
@@ -76,7 +94,15 @@ impl JMAP {
                                         .into_err()
                                         .details(err)
                                         .caused_by(trc::location!())
-                                })?
+                                })?;
+
+                            trc::event!(
+                                Auth(trc::AuthEvent::TokenIssuedAuthCode),
+                                SpanId = session_id,
+                                AccountId = oauth.account_id,
+                            );
+
+                            response
                         } else {
                             TokenResponse::error(ErrorType::InvalidGrant)
                         }
@@ -101,7 +127,12 @@ impl JMAP {
                     .await?
                 {
                     let oauth = auth_code.inner;
-                    response = if oauth.client_id != client_id {
+                    response = if oauth.client_id != client_id
+                        || !self.core.jmap.is_oauth_client_allowed(
+                            client_id,
+                            OAuthGrant::DeviceCode,
+                            None,
+                        ) {
                         TokenResponse::error(ErrorType::InvalidClient)
                     } else {
                         match oauth.status {
@@ -114,7 +145,15 @@ impl JMAP {
                                     .await?;
 
                                 // Issue token
-                                self.issue_token(oauth.account_id, &oauth.client_id, true)
+                                let response = self
+                                    .issue_token(
+                                        oauth.account_id,
+                                        &oauth.client_id,
+                                        issuer,
+                                        None,
+                                        oauth.scopes,
+                                        self.core.jmap.oauth_refresh_token_expiry(&oauth.client_id),
+                                    )
                                     .await
                                     .map(TokenResponse::Granted)
                                     .map_err(|err| {
@@ -122,7 +161,15 @@ impl JMAP {
                                             .into_err()
                                             .details(err)
                                             .caused_by(trc::location!())
-                                    })?
+                                    })?;
+
+                                trc::event!(
+                                    Auth(trc::AuthEvent::TokenIssuedDeviceCode),
+                                    SpanId = session_id,
+                                    AccountId = oauth.account_id,
+                                );
+
+                                response
                             }
                             OAuthStatus::Pending => {
                                 TokenResponse::error(ErrorType::AuthorizationPending)
@@ -137,23 +184,81 @@ impl JMAP {
         } else if grant_type.eq_ignore_ascii_case("refresh_token") {
             if let Some(refresh_token) = params.get("refresh_token") {
                 response = match self
-                    .validate_access_token("refresh_token", refresh_token)
+                    .core
+                    .validate_access_token("refresh_token", None, refresh_token)
                     .await
                 {
-                    Ok((account_id, client_id, time_left)) => self
-                        .issue_token(
-                            account_id,
-                            &client_id,
-                            time_left <= self.core.jmap.oauth_expiry_refresh_token_renew,
-                        )
-                        .await
-                        .map(TokenResponse::Granted)
-                        .map_err(|err| {
-                            trc::AuthEvent::Error
-                                .into_err()
-                                .details(err)
-                                .caused_by(trc::location!())
-                        })?,
+                    Ok(token_info)
+                        if !self.core.jmap.is_oauth_client_allowed(
+                            &token_info.client_id,
+                            OAuthGrant::RefreshToken,
+                            None,
+                        ) =>
+                    {
+                        TokenResponse::error(ErrorType::InvalidClient)
+                    }
+                    Ok(token_info)
+                        if self
+                            .redeem_refresh_token(refresh_token, &token_info)
+                            .await? =>
+                    {
+                        // Rotate the refresh token, keeping its expiration unless it is
+                        // about to expire
+                        let is_renewal = token_info.expires_in
+                            <= self.core.jmap.oauth_expiry_refresh_token_renew;
+                        let refresh_token_expiry = if is_renewal {
+                            self.core
+                                .jmap
+                                .oauth_refresh_token_expiry(&token_info.client_id)
+                                .unwrap_or_default()
+                        } else {
+                            token_info.expires_in
+                        };
+
+                        let response = self
+                            .issue_token(
+                                token_info.account_id,
+                                &token_info.client_id,
+                                issuer,
+                                token_info.grant_id.into(),
+                                token_info.scopes,
+                                refresh_token_expiry.into(),
+                            )
+                            .await
+                            .map(TokenResponse::Granted)
+                            .map_err(|err| {
+                                trc::AuthEvent::Error
+                                    .into_err()
+                                    .details(err)
+                                    .caused_by(trc::location!())
+                            })?;
+
+                        trc::event!(
+                            Auth(if is_renewal {
+                                trc::AuthEvent::RefreshTokenRenewed
+                            } else {
+                                trc::AuthEvent::TokenIssuedRefresh
+                            }),
+                            SpanId = session_id,
+                            AccountId = token_info.account_id,
+                        );
+
+                        response
+                    }
+                    Ok(token_info) => {
+                        // The refresh token was already used, which means it might have been
+                        // stolen: revoke all tokens issued under the same grant.
+                        self.revoke_token("refresh_token", refresh_token, &token_info)
+                            .await?;
+
+                        trc::error!(trc::AuthEvent::Error
+                            .into_err()
+                            .details("Refresh token reuse detected, revoking grant")
+                            .account_id(token_info.account_id)
+                            .id(token_info.client_id)
+                            .span_id(session_id));
+                        TokenResponse::error(ErrorType::InvalidGrant)
+                    }
                     Err(err) => {
                         trc::error!(err
                             .caused_by(trc::location!())
@@ -178,216 +283,365 @@ impl JMAP {
         .into_http_response())
     }
 
-    async fn password_hash(&self, account_id: u32) -> Result<String, &'static str> {
-        if account_id != u32::MAX {
+    // Introspection endpoint
+    pub async fn handle_introspect_request(
+        &self,
+        req: &mut HttpRequest,
+        access_token: &AccessToken,
+        session_id: u64,
+    ) -> trc::Result<HttpResponse> {
+        // Only callers allowed to introspect tokens may proceed
+        access_token.assert_has_permission(Permission::OauthIntrospect)?;
+
+        // Parse form
+        let params = FormData::from_request(req, MAX_POST_LEN, session_id).await?;
+        let token = params.get("token").ok_or_else(|| {
+            trc::ResourceEvent::BadParameters
+                .into_err()
+                .details("Missing token parameter.")
+        })?;
+
+        // Invalid, expired or unknown tokens are all reported as inactive
+        let mut response = IntrospectResponse::default();
+        for token_type in token_types(params.get("token_type_hint")) {
+            if let Ok(token_info) = self
+                .core
+                .validate_access_token(token_type, None, token)
+                .await
+            {
+                response = IntrospectResponse {
+                    active: true,
+                    scope: OAuthScope::format_scopes(token_info.scopes).into(),
+                    client_id: token_info.client_id.into(),
+                    token_type: token_type.to_string().into(),
+                    exp: (now() + token_info.expires_in).into(),
+                    sub: token_info.account_id.to_string().into(),
+                };
+                break;
+            }
+        }
+
+        Ok(JsonResponse::new(response).into_http_response())
+    }
+
+    // Revocation endpoint
+    pub async fn handle_revoke_request(
+        &self,
+        req: &mut HttpRequest,
+        session_id: u64,
+    ) -> trc::Result<HttpResponse> {
+        // Parse form
+        let params = FormData::from_request(req, MAX_POST_LEN, session_id).await?;
+        let token = if let Some(token) = params.get("token") {
+            token
+        } else {
+            return Ok(JsonResponse::with_status(
+                StatusCode::BAD_REQUEST,
+                TokenResponse::error(ErrorType::InvalidRequest),
+            )
+            .into_http_response());
+        };
+
+        // Clients are public, they authenticate with their registered client id
+        let client_id = match params.get("client_id") {
+            Some(client_id)
+                if self.core.jmap.oauth_clients.is_empty()
+                    || self.core.jmap.oauth_clients.contains_key(client_id) =>
+            {
+                client_id
+            }
+            _ => {
+                return Ok(JsonResponse::with_status(
+                    StatusCode::UNAUTHORIZED,
+                    TokenResponse::error(ErrorType::InvalidClient),
+                )
+                .into_http_response());
+            }
+        };
+
+        // Invalid or already revoked tokens are ignored (RFC 7009, section 2.2)
+        for token_type in token_types(params.get("token_type_hint")) {
+            if let Ok(token_info) = self
+                .core
+                .validate_access_token(token_type, None, token)
+                .await
+            {
+                // Clients may only revoke the tokens issued to them
+                if token_info.client_id != client_id {
+                    return Ok(JsonResponse::with_status(
+                        StatusCode::BAD_REQUEST,
+                        TokenResponse::error(ErrorType::UnauthorizedClient),
+                    )
+                    .into_http_response());
+                }
+
+                self.revoke_token(token_type, token, &token_info).await?;
+                break;
+            }
+        }
+
+        Ok(StatusCode::OK.into_http_response())
+    }
+
+    async fn revoke_token(
+        &self,
+        token_type: &str,
+        token: &str,
+        token_info: &TokenInfo,
+    ) -> trc::Result<()> {
+        if token_type == "refresh_token" {
+            // Revoking a refresh token also revokes all the access tokens issued under
+            // the same grant, which may outlive the refresh token itself.
             self.core
                 .storage
-                .directory
-                .query(QueryBy::Id(account_id), false)
-                .await
-                .map_err(|_| "Temporary lookup error")?
-                .ok_or("Account no longer exists")?
-                .take_str_array(PrincipalField::Secrets)
-                .unwrap_or_default()
-                .into_iter()
-                .next()
-                .ok_or("Failed to obtain password hash")
-        } else if let Some((_, secret)) = &self.core.jmap.fallback_admin {
-            Ok(secret.clone())
+                .lookup
+                .key_set(
+                    format!("oauth:revoked-grant:{}", token_info.grant_id).into_bytes(),
+                    vec![],
+                    std::cmp::max(token_info.expires_in, self.core.jmap.oauth_expiry_token).into(),
+                )
+                .await?;
+            self.inner
+                .sessions
+                .retain(|_, session| session.item.grant_id != Some(token_info.grant_id));
         } else {
-            Err("Invalid account id.")
+            self.core
+                .storage
+                .lookup
+                .key_set(
+                    format!("oauth:revoked:{}", blake3::hash(token.as_bytes()).to_hex())
+                        .into_bytes(),
+                    vec![],
+                    token_info.expires_in.into(),
+                )
+                .await?;
+            self.inner.sessions.remove(token);
         }
+
+        // Validated tokens are cached in memory, drop the account's tokens on all
+        // nodes so they are checked against the revocation list again
+        self.core
+            .invalidate_auth_cache(token_info.account_id, &[])
+            .await;
+
+        Ok(())
+    }
+
+    // Refresh tokens can only be redeemed once, returns false if the token was used before
+    async fn redeem_refresh_token(&self, token: &str, token_info: &TokenInfo) -> trc::Result<bool> {
+        self.core
+            .storage
+            .lookup
+            .counter_incr(
+                format!("oauth:redeemed:{}", blake3::hash(token.as_bytes()).to_hex()).into_bytes(),
+                1,
+                token_info.expires_in.into(),
+                true,
+            )
+            .await
+            .map(|count| count == 1)
     }
 
     pub async fn issue_token(
         &self,
         account_id: u32,
         client_id: &str,
-        with_refresh_token: bool,
+        issuer: &str,
+        grant_id: Option<u64>,
+        scopes: OAuthScopes,
+        refresh_token_expiry: Option<u64>,
     ) -> Result<OAuthResponse, &'static str> {
-        let password_hash = self.password_hash(account_id).await?;
+        let secret = self.core.token_secret(account_id).await?;
+        let grant_id = grant_id.unwrap_or_else(|| thread_rng().gen());
+        let expiry_token = self.core.jmap.oauth_token_expiry(client_id);
 
         Ok(OAuthResponse {
-            access_token: self.encode_access_token(
+            access_token: self.core.encode_access_token(
                 "access_token",
                 account_id,
-                &password_hash,
+                &secret,
                 client_id,
-                self.core.jmap.oauth_expiry_token,
+                grant_id,
+                scopes,
+                None,
+                expiry_token,
             )?,
             token_type: "bearer".to_string(),
-            expires_in: self.core.jmap.oauth_expiry_token,
-            refresh_token: if with_refresh_token {
-                self.encode_access_token(
-                    "refresh_token",
-                    account_id,
-                    &password_hash,
-                    client_id,
-                    self.core.jmap.oauth_expiry_refresh_token,
-                )?
-                .into()
+            expires_in: expiry_token,
+            refresh_token: if let Some(refresh_token_expiry) = refresh_token_expiry {
+                self.core
+                    .encode_access_token(
+                        "refresh_token",
+                        account_id,
+                        &secret,
+                        client_id,
+                        grant_id,
+                        scopes,
+                        None,
+                        refresh_token_expiry,
+                    )?
+                    .into()
+            } else {
+                None
+            },
+            scope: OAuthScope::format_scopes(scopes).into(),
+            id_token: if let (Some(signing_key), true) = (
+                &self.core.jmap.oauth_signing_key,
+                scopes.contains(OAuthScope::OpenId),
+            ) {
+                let claims = self
+                    .id_token_claims(account_id, client_id, issuer, scopes)
+                    .await?;
+                signing_key
+                    .sign_jwt(&claims)
+                    .map_err(|_| "Failed to sign ID token")?
+                    .into()
             } else {
                 None
             },
-            scope: None,
         })
     }
 
-    pub async fn issue_custom_token(
+    async fn id_token_claims(
         &self,
         account_id: u32,
-        grant_type: &str,
         client_id: &str,
-        expiry_in: u64,
-    ) -> trc::Result<String> {
-        self.encode_access_token(
-            grant_type,
-            account_id,
-            &self
-                .password_hash(account_id)
+        issuer: &str,
+        scopes: OAuthScopes,
+    ) -> Result<IdTokenClaims, &'static str> {
+        let (email, name) = if let Some(fallback_admin) = self.core.jmap.fallback_admin(account_id)
+        {
+            (None, Some(fallback_admin.user.clone()))
+        } else {
+            let mut principal = self
+                .core
+                .storage
+                .directory
+                .query(QueryBy::Id(account_id), false)
                 .await
-                .map_err(|err| trc::StoreEvent::UnexpectedError.into_err().details(err))?,
-            client_id,
-            expiry_in,
-        )
-        .map_err(|err| trc::StoreEvent::UnexpectedError.into_err().details(err))
+                .map_err(|_| "Temporary lookup error")?
+                .ok_or("Account no longer exists")?;
+            let email = principal
+                .take_str_array(PrincipalField::Emails)
+                .unwrap_or_default()
+                .into_iter()
+                .next();
+            let name = principal
+                .take_str(PrincipalField::Description)
+                .or_else(|| principal.take_str(PrincipalField::Name));
+            (email, name)
+        };
+        let iat = now();
+
+        // Only disclose the claims covered by the granted scopes
+        let email = email.filter(|_| scopes.contains(OAuthScope::Email));
+        let name = name.filter(|_| scopes.contains(OAuthScope::Profile));
+
+        Ok(IdTokenClaims {
+            iss: issuer.to_string(),
+            sub: account_id.to_string(),
+            aud: client_id.to_string(),
+            iat,
+            exp: iat + self.core.jmap.oauth_token_expiry(client_id),
+            email,
+            name,
+        })
+    }
+
+    // JSON Web Key Set endpoint
+    pub fn handle_jwks_request(&self) -> HttpResponse {
+        JsonResponse::new(json!({
+            "keys": self
+                .core
+                .jmap
+                .oauth_signing_key
+                .as_ref()
+                .map(|key| vec![key.jwk()])
+                .unwrap_or_default(),
+        }))
+        .into_http_response()
     }
 
-    fn encode_access_token(
+    // Tokens issued with an audience are only accepted when validated with
+    // the same audience
+    pub async fn issue_custom_token(
         &self,
-        grant_type: &str,
         account_id: u32,
-        password_hash: &str,
+        grant_type: &str,
         client_id: &str,
+        audience: Option<&str>,
         expiry_in: u64,
-    ) -> Result<String, &'static str> {
-        // Build context
-        if client_id.len() > CLIENT_ID_MAX_LEN {
-            return Err("ClientId is too long");
-        }
-        let key = self.core.jmap.oauth_key.clone();
-        let context = format!(
-            "{} {} {} {}",
-            grant_type, client_id, account_id, password_hash
-        );
-        let context_nonce = format!("{} nonce {}", grant_type, password_hash);
-
-        // Set expiration time
-        let expiry = SystemTime::now()
-                .duration_since(SystemTime::UNIX_EPOCH)
-                .map(|d| d.as_secs())
-                .unwrap_or(0)
-                .saturating_sub(946684800) // Jan 1, 2000
-                + expiry_in;
-
-        // Calculate nonce
-        let mut hasher = blake3::Hasher::new();
-        hasher.update(context_nonce.as_bytes());
-        hasher.update(expiry.to_be_bytes().as_slice());
-        let nonce = hasher
-            .finalize()
-            .as_bytes()
-            .iter()
-            .take(SymmetricEncrypt::NONCE_LEN)
-            .copied()
-            .collect::<Vec<_>>();
-
-        // Encrypt random bytes
-        let mut token = SymmetricEncrypt::new(key.as_bytes(), &context)
-            .encrypt(&thread_rng().gen::<[u8; RANDOM_CODE_LEN]>(), &nonce)
-            .map_err(|_| "Failed to encrypt token.")?;
-        token.push_leb128(account_id);
-        token.push_leb128(expiry);
-        token.extend_from_slice(client_id.as_bytes());
-
-        Ok(String::from_utf8(base64_encode(&token).unwrap_or_default()).unwrap())
+    ) -> trc::Result<String> {
+        self.core
+            .encode_access_token(
+                grant_type,
+                account_id,
+                &self
+                    .core
+                    .token_secret(account_id)
+                    .await
+                    .map_err(|err| trc::StoreEvent::UnexpectedError.into_err().details(err))?,
+                client_id,
+                thread_rng().gen(),
+                OAuthScopes::all(),
+                audience,
+                expiry_in,
+            )
+            .map_err(|err| trc::StoreEvent::UnexpectedError.into_err().details(err))
     }
 
-    pub async fn validate_access_token(
+    // Issues a token that can only be used for a single purpose, such as the
+    // signed action links included in notification emails. The token grants no
+    // OAuth scopes, so it cannot be used to access any protocol.
+    //
+    // The audience is part of the encryption context: changing the purpose
+    // string invalidates all the tokens issued for the previous one.
+    pub async fn issue_audience_token(
         &self,
-        grant_type: &str,
-        token_: &str,
-    ) -> trc::Result<(u32, String, u64)> {
-        // Base64 decode token
-        let token = base64_decode(token_.as_bytes()).ok_or_else(|| {
-            trc::AuthEvent::Error
-                .into_err()
-                .ctx(trc::Key::Reason, "Failed to decode token")
-                .caused_by(trc::location!())
-                .details(token_.to_string())
-        })?;
-        let (account_id, expiry, client_id) = token
-            .get((RANDOM_CODE_LEN + SymmetricEncrypt::ENCRYPT_TAG_LEN)..)
-            .and_then(|bytes| {
-                let mut bytes = bytes.iter();
-                (
-                    bytes.next_leb128()?,
-                    bytes.next_leb128::<u64>()?,
-                    bytes.copied().map(char::from).collect::<String>(),
-                )
-                    .into()
-            })
-            .ok_or_else(|| {
-                trc::AuthEvent::Error
-                    .into_err()
-                    .ctx(trc::Key::Reason, "Failed to decode token")
-                    .caused_by(trc::location!())
-                    .details(token_.to_string())
-            })?;
-
-        // Validate expiration
-        let now = SystemTime::now()
-            .duration_since(SystemTime::UNIX_EPOCH)
-            .map(|d| d.as_secs())
-            .unwrap_or(0)
-            .saturating_sub(946684800); // Jan 1, 2000
-        if expiry <= now {
-            return Err(trc::AuthEvent::Error
+        account_id: u32,
+        audience: &str,
+        expiry_in: u64,
+    ) -> trc::Result<String> {
+        if audience.is_empty() {
+            return Err(trc::ResourceEvent::BadParameters
                 .into_err()
-                .ctx(trc::Key::Reason, "Token expired"));
+                .details("Token audience cannot be empty"));
         }
 
-        // Obtain password hash
-        let password_hash = self
-            .password_hash(account_id)
-            .await
-            .map_err(|err| trc::AuthEvent::Error.into_err().ctx(trc::Key::Details, err))?;
-
-        // Build context
-        let key = self.core.jmap.oauth_key.clone();
-        let context = format!(
-            "{} {} {} {}",
-            grant_type, client_id, account_id, password_hash
-        );
-        let context_nonce = format!("{} nonce {}", grant_type, password_hash);
-
-        // Calculate nonce
-        let mut hasher = blake3::Hasher::new();
-        hasher.update(context_nonce.as_bytes());
-        hasher.update(expiry.to_be_bytes().as_slice());
-        let nonce = hasher
-            .finalize()
-            .as_bytes()
-            .iter()
-            .take(SymmetricEncrypt::NONCE_LEN)
-            .copied()
-            .collect::<Vec<_>>();
-
-        // Decrypt
-        SymmetricEncrypt::new(key.as_bytes(), &context)
-            .decrypt(
-                &token[..RANDOM_CODE_LEN + SymmetricEncrypt::ENCRYPT_TAG_LEN],
-                &nonce,
+        self.core
+            .encode_access_token(
+                AUDIENCE_GRANT_TYPE,
+                account_id,
+                &self
+                    .core
+                    .token_secret(account_id)
+                    .await
+                    .map_err(|err| trc::StoreEvent::UnexpectedError.into_err().details(err))?,
+                "",
+                thread_rng().gen(),
+                OAuthScopes::new(),
+                audience.into(),
+                expiry_in,
             )
-            .map_err(|err| {
-                trc::AuthEvent::Error
-                    .into_err()
-                    .ctx(trc::Key::Details, "Failed to decode token")
-                    .caused_by(trc::location!())
-                    .reason(err)
-            })?;
-
-        // Success
-        Ok((account_id, client_id, expiry - now))
+            .map_err(|err| trc::StoreEvent::UnexpectedError.into_err().details(err))
+    }
+
+    pub async fn validate_audience_token(
+        &self,
+        audience: &str,
+        token: &str,
+    ) -> trc::Result<TokenInfo> {
+        self.core
+            .validate_access_token(AUDIENCE_GRANT_TYPE, audience.into(), token)
+            .await
+    }
+}
+
+// Try the hinted token type first, then fall back to the other one
+fn token_types(token_type_hint: Option<&str>) -> [&'static str; 2] {
+    if token_type_hint == Some("refresh_token") {
+        ["refresh_token", "access_token"]
+    } else {
+        ["access_token", "refresh_token"]
     }
 }
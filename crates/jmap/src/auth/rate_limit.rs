@@ -9,6 +9,7 @@ use std::{net::IpAddr, sync::Arc};
 use common::listener::limiter::{ConcurrencyLimiter, InFlight};
 use directory::Permission;
 use trc::AddContext;
+use utils::config::Rate;
 
 use crate::JMAP;
 
@@ -43,7 +44,7 @@ impl JMAP {
 
     pub async fn is_account_allowed(&self, access_token: &AccessToken) -> trc::Result<InFlight> {
         let limiter = self.get_concurrency_limiter(access_token.primary_id());
-        let is_rate_allowed = if let Some(rate) = &self.core.jmap.rate_authenticated {
+        let retry_after = if let Some(rate) = &self.core.jmap.rate_authenticated {
             self.core
                 .storage
                 .lookup
@@ -54,38 +55,61 @@ impl JMAP {
                 )
                 .await
                 .caused_by(trc::location!())?
-                .is_none()
+                .map(|retry_after| (rate, retry_after))
         } else {
-            true
+            None
         };
 
-        if is_rate_allowed {
-            if let Some(in_flight_request) = limiter.concurrent_requests.is_allowed() {
-                Ok(in_flight_request)
-            } else if access_token.has_permission(Permission::UnlimitedRequests) {
+        if let Some((rate, retry_after)) = retry_after {
+            if access_token.has_permission(Permission::UnlimitedRequests) {
                 Ok(InFlight::default())
             } else {
-                Err(trc::LimitEvent::ConcurrentRequest.into_err())
+                Err(too_many_requests(rate, retry_after).account_id(access_token.primary_id()))
             }
+        } else if let Some(in_flight_request) = limiter.concurrent_requests.is_allowed() {
+            Ok(in_flight_request)
         } else if access_token.has_permission(Permission::UnlimitedRequests) {
             Ok(InFlight::default())
         } else {
-            Err(trc::LimitEvent::TooManyRequests.into_err())
+            Err(trc::LimitEvent::ConcurrentRequest.into_err())
         }
     }
 
     pub async fn is_anonymous_allowed(&self, addr: &IpAddr) -> trc::Result<()> {
         if let Some(rate) = &self.core.jmap.rate_anonymous {
-            if self
+            if let Some(retry_after) = self
                 .core
                 .storage
                 .lookup
                 .is_rate_allowed(format!("jreq:{}", addr).as_bytes(), rate, false)
                 .await
                 .caused_by(trc::location!())?
-                .is_some()
             {
-                return Err(trc::LimitEvent::TooManyRequests.into_err());
+                return Err(too_many_requests(rate, retry_after).ctx(trc::Key::RemoteIp, *addr));
+            }
+        }
+        Ok(())
+    }
+
+    // Token endpoints perform crypto operations and directory lookups, so
+    // they can optionally be limited separately from other anonymous requests.
+    pub async fn is_oauth_allowed(&self, addr: &IpAddr) -> trc::Result<()> {
+        self.is_anonymous_allowed(addr).await?;
+
+        if let Some(rate) = &self.core.jmap.rate_oauth {
+            if let Some(retry_after) = self
+                .core
+                .storage
+                .lookup
+                .is_rate_allowed(format!("joauth:{}", addr).as_bytes(), rate, false)
+                .await
+                .caused_by(trc::location!())?
+            {
+                return Err(trc::LimitEvent::TooManyOAuthRequests
+                    .into_err()
+                    .ctx(trc::Key::Limit, rate.requests)
+                    .ctx(trc::Key::Expires, retry_after)
+                    .ctx(trc::Key::RemoteIp, *addr));
             }
         }
         Ok(())
@@ -140,6 +164,13 @@ impl JMAP {
     }
 }
 
+fn too_many_requests(rate: &Rate, retry_after: u64) -> trc::Error {
+    trc::LimitEvent::TooManyRequests
+        .into_err()
+        .ctx(trc::Key::Limit, rate.requests)
+        .ctx(trc::Key::Expires, retry_after)
+}
+
 impl ConcurrencyLimiters {
     pub fn is_active(&self) -> bool {
         self.concurrent_requests.is_active() || self.concurrent_uploads.is_active()
@@ -6,7 +6,10 @@
 
 use std::sync::Arc;
 
-use common::auth::AccessToken;
+use common::{
+    auth::AccessToken,
+    expr::{functions::ResolveVariable, Variable, V_AUTHENTICATED_AS},
+};
 use directory::Permission;
 use jmap_proto::{
     error::set::SetError,
@@ -27,6 +30,12 @@ use crate::JMAP;
 
 use super::UploadResponse;
 
+pub struct UploadLimits {
+    pub max_size: usize,
+    allowed_types: Vec<String>,
+    denied_types: Vec<String>,
+}
+
 #[cfg(feature = "test_mode")]
 pub static DISABLE_UPLOAD_QUOTA: std::sync::atomic::AtomicBool =
     std::sync::atomic::AtomicBool::new(true);
@@ -47,10 +56,25 @@ impl JMAP {
         if request.create.len() > self.core.jmap.set_max_objects {
             return Err(trc::JmapEvent::RequestTooLarge.into_err());
         }
+        let limits = self.upload_limits(access_token, 0).await;
 
         'outer: for (create_id, upload_object) in request.create {
             let mut data = Vec::new();
 
+            let content_type = upload_object
+                .type_
+                .as_deref()
+                .unwrap_or("application/octet-stream");
+            if !limits.is_type_allowed(content_type) {
+                response.not_created.append(
+                    create_id,
+                    SetError::forbidden().with_description(format!(
+                        "Uploads of type {content_type:?} are not allowed."
+                    )),
+                );
+                continue 'outer;
+            }
+
             for data_source in upload_object.data {
                 let bytes = match data_source {
                     DataSourceObject::Id { id, length, offset } => {
@@ -115,14 +139,14 @@ impl JMAP {
                     DataSourceObject::Value(bytes) => bytes,
                 };
 
-                if bytes.len() + data.len() < self.core.jmap.upload_max_size {
+                if limits.max_size == 0 || bytes.len() + data.len() < limits.max_size {
                     data.extend(bytes);
                 } else {
                     response.not_created.append(
                         create_id,
                         SetError::too_large().with_description(format!(
                             "Upload size exceeds maximum of {} bytes.",
-                            self.core.jmap.upload_max_size
+                            limits.max_size
                         )),
                     );
                     continue 'outer;
@@ -238,6 +262,50 @@ impl JMAP {
         })
     }
 
+    pub async fn upload_limits(&self, access_token: &AccessToken, session_id: u64) -> UploadLimits {
+        let mut limits = UploadLimits {
+            max_size: if !access_token.has_permission(Permission::UnlimitedUploads) {
+                self.core.jmap.upload_max_size
+            } else {
+                0
+            },
+            allowed_types: Vec::new(),
+            denied_types: Vec::new(),
+        };
+
+        if let Some(policy) = &self.core.jmap.upload_policy {
+            let resolver = UploadResolver {
+                account_name: access_token.name.as_str(),
+            };
+            if limits.max_size != 0 {
+                if let Some(max_size) = self
+                    .core
+                    .eval_if::<usize, _>(&policy.max_size, &resolver, session_id)
+                    .await
+                    .filter(|max_size| *max_size > 0)
+                {
+                    limits.max_size = std::cmp::min(limits.max_size, max_size);
+                }
+            }
+            for (if_block, types) in [
+                (&policy.allowed_types, &mut limits.allowed_types),
+                (&policy.denied_types, &mut limits.denied_types),
+            ] {
+                *types = self
+                    .core
+                    .eval_if::<Vec<String>, _>(if_block, &resolver, session_id)
+                    .await
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(|t| t.trim().to_lowercase())
+                    .filter(|t| !t.is_empty())
+                    .collect();
+            }
+        }
+
+        limits
+    }
+
     #[allow(clippy::blocks_in_conditions)]
     pub async fn put_blob(
         &self,
@@ -291,3 +359,38 @@ impl JMAP {
         })
     }
 }
+
+impl UploadLimits {
+    pub fn is_type_allowed(&self, content_type: &str) -> bool {
+        let content_type = content_type
+            .split_once(';')
+            .map_or(content_type, |(c, _)| c)
+            .trim()
+            .to_lowercase();
+        let matches = |pattern: &String| {
+            pattern == "*/*"
+                || pattern.as_str() == content_type
+                || pattern.strip_suffix("/*").map_or(false, |prefix| {
+                    content_type
+                        .split_once('/')
+                        .map_or(false, |(c, _)| c == prefix)
+                })
+        };
+
+        !self.denied_types.iter().any(matches)
+            && (self.allowed_types.is_empty() || self.allowed_types.iter().any(matches))
+    }
+}
+
+struct UploadResolver<'x> {
+    account_name: &'x str,
+}
+
+impl ResolveVariable for UploadResolver<'_> {
+    fn resolve_variable(&self, variable: u32) -> Variable<'_> {
+        match variable {
+            V_AUTHENTICATED_AS => self.account_name.into(),
+            _ => "".into(),
+        }
+    }
+}
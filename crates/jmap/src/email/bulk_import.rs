@@ -0,0 +1,486 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use std::{
+    fs::File,
+    io::{self, BufReader},
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+
+use common::config::jmap::settings::DedupKey;
+use dashmap::mapref::entry::Entry;
+use jmap_proto::types::{keyword::Keyword, state::StateChange, type_state::DataType};
+use mail_parser::{
+    mailbox::{maildir, mbox},
+    MessageParser,
+};
+use serde::{Deserialize, Serialize};
+use trc::AddContext;
+
+use crate::JMAP;
+
+use super::ingest::{IngestDedup, IngestEmail, IngestSource};
+
+const IMPORT_DEDUP_WINDOW: Duration = Duration::from_secs(90 * 86400);
+const MAX_REPORTED_ERRORS: usize = 100;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ImportFormat {
+    Mbox,
+    Maildir,
+    MaildirNested,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ImportRequest {
+    pub format: ImportFormat,
+    pub path: PathBuf,
+    #[serde(default)]
+    pub mailbox: Option<String>,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ImportState {
+    #[default]
+    Running,
+    Completed,
+    Failed,
+}
+
+#[derive(Debug, Default, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportResult {
+    pub state: ImportState,
+    pub messages: u64,
+    pub imported: u64,
+    pub skipped: u64,
+    pub failed: u64,
+    pub errors: Vec<ImportError>,
+}
+
+pub type ImportProgress = parking_lot::Mutex<ImportResult>;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ImportError {
+    pub message: String,
+    pub reason: String,
+}
+
+enum ImportSource {
+    Mbox(mbox::MessageIterator<BufReader<File>>),
+    Maildir(maildir::MessageIterator),
+}
+
+struct ImportMessage {
+    identifier: String,
+    keywords: Vec<Keyword>,
+    received_at: u64,
+    contents: Vec<u8>,
+}
+
+impl JMAP {
+    // Starts an import in the background, its progress can be polled with
+    // import_status. Only one import can run at a time for each account.
+    pub fn start_import(
+        &self,
+        account_id: u32,
+        request: ImportRequest,
+        session_id: u64,
+    ) -> trc::Result<()> {
+        let progress = Arc::new(ImportProgress::default());
+        match self.inner.imports.entry(account_id) {
+            Entry::Occupied(mut entry) => {
+                if entry.get().lock().state == ImportState::Running {
+                    return Err(trc::ManageEvent::AlreadyExists
+                        .into_err()
+                        .details("An import is already running for this account"));
+                }
+                entry.insert(progress.clone());
+            }
+            Entry::Vacant(entry) => {
+                entry.insert(progress.clone());
+            }
+        }
+
+        let jmap = self.clone();
+        tokio::spawn(async move {
+            if let Err(err) = jmap
+                .import_messages(account_id, request, &progress, session_id)
+                .await
+            {
+                {
+                    let mut progress = progress.lock();
+                    progress.state = ImportState::Failed;
+                    progress.errors.push(ImportError {
+                        message: "import".to_string(),
+                        reason: err
+                            .value_as_str(trc::Key::Reason)
+                            .or_else(|| err.value_as_str(trc::Key::Details))
+                            .unwrap_or("Import failed")
+                            .to_string(),
+                    });
+                }
+                trc::error!(err
+                    .account_id(account_id)
+                    .span_id(session_id)
+                    .details("Failed to import messages"));
+            }
+        });
+
+        Ok(())
+    }
+
+    pub fn import_status(&self, account_id: u32) -> Option<ImportResult> {
+        self.inner
+            .imports
+            .get(&account_id)
+            .map(|progress| progress.lock().clone())
+    }
+
+    // Imports an mbox file or a Maildir folder tree located under the
+    // configured import directory, one message at a time. Messages that fail
+    // to parse or ingest are reported and skipped, and messages already
+    // imported into the same mailbox are skipped, so an interrupted import
+    // can be run again.
+    pub async fn import_messages(
+        &self,
+        account_id: u32,
+        request: ImportRequest,
+        progress: &ImportProgress,
+        session_id: u64,
+    ) -> trc::Result<()> {
+        let resource = self
+            .core
+            .get_cached_access_token(account_id)
+            .await
+            .caused_by(trc::location!())?
+            .as_resource_token();
+        let default_mailbox = request.mailbox.unwrap_or_else(|| "Inbox".to_string());
+        let folders = open_sources(
+            request.format,
+            self.core.jmap.mail_import_dir.clone(),
+            request.path,
+        )
+        .await?;
+        let mut last_change_id = None;
+
+        for (folder_name, mut source) in folders {
+            let mailbox_name = folder_name.as_deref().unwrap_or(&default_mailbox);
+            let mailbox_id = match self
+                .mailbox_create_path(account_id, mailbox_name)
+                .await
+                .caused_by(trc::location!())?
+            {
+                Some((mailbox_id, change_id)) => {
+                    if let Some(change_id) = change_id {
+                        last_change_id = Some(change_id);
+                    }
+                    mailbox_id
+                }
+                None => {
+                    progress
+                        .lock()
+                        .add_error(mailbox_name.to_string(), "Invalid mailbox name".to_string());
+                    continue;
+                }
+            };
+
+            loop {
+                let (returned_source, next) = tokio::task::spawn_blocking(move || {
+                    let next = source.next_message();
+                    (source, next)
+                })
+                .await
+                .map_err(|err| {
+                    trc::EventType::Server(trc::ServerEvent::ThreadError)
+                        .reason(err)
+                        .caused_by(trc::location!())
+                })?;
+                source = returned_source;
+
+                let message = match next {
+                    Some(Ok(message)) => message,
+                    Some(Err((identifier, reason))) => {
+                        let mut progress = progress.lock();
+                        progress.messages += 1;
+                        progress.add_error(identifier, reason);
+
+                        // A read error leaves an mbox stream in an unknown state
+                        if matches!(source, ImportSource::Mbox(_)) {
+                            break;
+                        }
+                        continue;
+                    }
+                    None => break,
+                };
+                progress.lock().messages += 1;
+
+                let raw_message = sanitize_line_endings(message.contents);
+                let parsed_message = match MessageParser::new().parse(&raw_message) {
+                    Some(parsed_message) if !parsed_message.root_part().headers().is_empty() => {
+                        parsed_message
+                    }
+                    _ => {
+                        progress.lock().add_error(
+                            message.identifier,
+                            "Failed to parse e-mail message".to_string(),
+                        );
+                        continue;
+                    }
+                };
+                let mut keywords = message.keywords;
+                add_mbox_status_keywords(&parsed_message, &mut keywords);
+
+                match self
+                    .email_ingest(IngestEmail {
+                        raw_message: &raw_message,
+                        message: Some(parsed_message),
+                        resource: resource.clone(),
+                        mailbox_ids: vec![mailbox_id],
+                        keywords,
+                        received_at: (message.received_at > 0).then_some(message.received_at),
+                        received_at_offset: 0,
+                        source: IngestSource::Jmap,
+                        delivered_to: None,
+                        encrypt: self.core.jmap.encrypt && self.core.jmap.encrypt_append,
+                        dedup: Some(IngestDedup {
+                            window: IMPORT_DEDUP_WINDOW,
+                            key: DedupKey::MessageId,
+                        }),
+                        session_id,
+                        dry_run: false,
+                    })
+                    .await
+                {
+                    Ok(ingested) if ingested.change_id == u64::MAX => {
+                        progress.lock().skipped += 1;
+                    }
+                    Ok(ingested) => {
+                        progress.lock().imported += 1;
+                        last_change_id = Some(ingested.change_id);
+                    }
+                    Err(err) if err.matches(trc::EventType::Limit(trc::LimitEvent::Quota)) => {
+                        // No further messages fit, stop here and report what was imported
+                        progress
+                            .lock()
+                            .add_error(message.identifier, "Quota exceeded".to_string());
+                        self.broadcast_import_changes(account_id, last_change_id)
+                            .await;
+                        progress.lock().state = ImportState::Completed;
+                        return Ok(());
+                    }
+                    Err(err) => {
+                        progress.lock().add_error(
+                            message.identifier,
+                            err.value_as_str(trc::Key::Reason)
+                                .unwrap_or("Failed to ingest message")
+                                .to_string(),
+                        );
+                    }
+                }
+            }
+        }
+
+        self.broadcast_import_changes(account_id, last_change_id)
+            .await;
+        progress.lock().state = ImportState::Completed;
+
+        Ok(())
+    }
+
+    async fn broadcast_import_changes(&self, account_id: u32, change_id: Option<u64>) {
+        if let Some(change_id) = change_id {
+            self.broadcast_state_change(
+                StateChange::new(account_id)
+                    .with_change(DataType::Email, change_id)
+                    .with_change(DataType::Mailbox, change_id)
+                    .with_change(DataType::Thread, change_id),
+            )
+            .await;
+        }
+    }
+}
+
+impl ImportResult {
+    fn add_error(&mut self, message: String, reason: String) {
+        self.failed += 1;
+        if self.errors.len() < MAX_REPORTED_ERRORS {
+            self.errors.push(ImportError { message, reason });
+        }
+    }
+}
+
+impl ImportSource {
+    fn next_message(&mut self) -> Option<Result<ImportMessage, (String, String)>> {
+        match self {
+            ImportSource::Mbox(it) => it.next().map(|result| {
+                result
+                    .map(|message| ImportMessage {
+                        identifier: message.from().to_string(),
+                        keywords: Vec::new(),
+                        received_at: message.internal_date(),
+                        contents: message.unwrap_contents(),
+                    })
+                    .map_err(|_| {
+                        (
+                            "mbox".to_string(),
+                            "Failed to read message from mbox file".to_string(),
+                        )
+                    })
+            }),
+            ImportSource::Maildir(it) => it.next().map(|result| {
+                result
+                    .map(|message| ImportMessage {
+                        identifier: message
+                            .path()
+                            .file_name()
+                            .and_then(|f| f.to_str())
+                            .unwrap_or("unknown")
+                            .to_string(),
+                        keywords: message
+                            .flags()
+                            .iter()
+                            .map(|flag| match flag {
+                                maildir::Flag::Passed => Keyword::Forwarded,
+                                maildir::Flag::Replied => Keyword::Answered,
+                                maildir::Flag::Seen => Keyword::Seen,
+                                maildir::Flag::Trashed => Keyword::Deleted,
+                                maildir::Flag::Draft => Keyword::Draft,
+                                maildir::Flag::Flagged => Keyword::Flagged,
+                            })
+                            .collect(),
+                        received_at: message.internal_date(),
+                        contents: message.unwrap_contents(),
+                    })
+                    .map_err(|err| ("maildir".to_string(), err.to_string()))
+            }),
+        }
+    }
+}
+
+async fn open_sources(
+    format: ImportFormat,
+    import_dir: Option<PathBuf>,
+    path: PathBuf,
+) -> trc::Result<Vec<(Option<String>, ImportSource)>> {
+    tokio::task::spawn_blocking(move || -> io::Result<_> {
+        let path = resolve_import_path(import_dir.as_deref(), &path)?;
+        match format {
+            ImportFormat::Mbox => Ok(vec![(
+                None,
+                ImportSource::Mbox(mbox::MessageIterator::new(BufReader::new(File::open(
+                    path,
+                )?))),
+            )]),
+            ImportFormat::Maildir | ImportFormat::MaildirNested => {
+                let (folder_sep, folder_split) = if format == ImportFormat::Maildir {
+                    (Some("."), '.')
+                } else {
+                    (None, '/')
+                };
+                let mut folders = Vec::new();
+                for folder in maildir::FolderIterator::new(path, folder_sep)? {
+                    let folder = folder?;
+                    let folder_name = folder.name().map(|name| {
+                        name.split(folder_split)
+                            .map(|part| part.trim())
+                            .filter(|part| !part.is_empty())
+                            .collect::<Vec<_>>()
+                            .join("/")
+                    });
+                    folders.push((
+                        folder_name.filter(|name| !name.is_empty()),
+                        ImportSource::Maildir(folder),
+                    ));
+                }
+                Ok(folders)
+            }
+        }
+    })
+    .await
+    .map_err(|err| {
+        trc::EventType::Server(trc::ServerEvent::ThreadError)
+            .reason(err)
+            .caused_by(trc::location!())
+    })?
+    .map_err(|err| {
+        trc::ResourceEvent::BadParameters
+            .reason(err)
+            .details("Failed to open import source")
+            .caused_by(trc::location!())
+    })
+}
+
+// Sources must be located under the import directory, symlinks and ".."
+// components are resolved before the check.
+fn resolve_import_path(import_dir: Option<&Path>, path: &Path) -> io::Result<PathBuf> {
+    let import_dir = import_dir
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                "No import directory has been configured",
+            )
+        })?
+        .canonicalize()?;
+    let path = import_dir.join(path).canonicalize()?;
+    if path.starts_with(&import_dir) {
+        Ok(path)
+    } else {
+        Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            "Path is outside the import directory",
+        ))
+    }
+}
+
+// mbox files written by most MUAs keep flags in the Status and X-Status headers
+fn add_mbox_status_keywords(message: &mail_parser::Message<'_>, keywords: &mut Vec<Keyword>) {
+    for header in message.root_part().headers() {
+        if !header.name().eq_ignore_ascii_case("Status")
+            && !header.name().eq_ignore_ascii_case("X-Status")
+        {
+            continue;
+        }
+        if let Some(status) = header.value().as_text() {
+            for flag in status.chars() {
+                let keyword = match flag {
+                    'R' => Keyword::Seen,
+                    'A' => Keyword::Answered,
+                    'F' => Keyword::Flagged,
+                    'D' => Keyword::Deleted,
+                    'T' => Keyword::Draft,
+                    _ => continue,
+                };
+                if !keywords.contains(&keyword) {
+                    keywords.push(keyword);
+                }
+            }
+        }
+    }
+}
+
+fn sanitize_line_endings(contents: Vec<u8>) -> Vec<u8> {
+    if !contents.windows(2).any(|w| w[1] == b'\n' && w[0] != b'\r')
+        && contents.first() != Some(&b'\n')
+    {
+        return contents;
+    }
+
+    let mut sanitized = Vec::with_capacity(contents.len() + (contents.len() / 50));
+    let mut last_ch = 0;
+    for ch in contents {
+        if ch == b'\n' && last_ch != b'\r' {
+            sanitized.push(b'\r');
+        }
+        sanitized.push(ch);
+        last_ch = ch;
+    }
+    sanitized
+}
@@ -47,6 +47,7 @@ use crate::{api::http::HttpSessionData, mailbox::UidMailbox, JMAP};
 use super::{
     index::{EmailIndexBuilder, TrimTextValue, VisitValues, MAX_ID_LENGTH, MAX_SORT_FIELD_LENGTH},
     ingest::{IngestedEmail, LogEmailInsert},
+    keywords::KeywordRegistry,
     metadata::MessageMetadata,
 };
 
@@ -211,6 +212,21 @@ impl JMAP {
                 }
             }
 
+            // Validate the keywords against the mailbox keyword policy
+            let mut registry = KeywordRegistry::default();
+            if let Some(err) = self
+                .email_check_keywords(account_id, &mailboxes, &mut keywords, &mut registry)
+                .await?
+            {
+                response.not_created.append(
+                    id,
+                    SetError::invalid_properties()
+                        .with_property(Property::Keywords)
+                        .with_description(err.description()),
+                );
+                continue 'create;
+            }
+
             // Add response
             match self
                 .copy_message(
@@ -225,6 +241,7 @@ impl JMAP {
                 .await?
             {
                 Ok(email) => {
+                    self.email_register_keywords(account_id, &mut registry).await?;
                     response.created.append(id, email.into());
                 }
                 Err(err) => {
@@ -318,10 +335,19 @@ impl JMAP {
             }
         }
 
-        // Set receivedAt
-        if let Some(received_at) = received_at {
+        // Set receivedAt, the timezone is only kept when the original date is copied
+        let timezone = if let Some(received_at) = received_at {
             metadata.received_at = received_at.timestamp() as u64;
-        }
+            None
+        } else {
+            self.get_property::<i64>(
+                from_account_id,
+                Collection::Email,
+                from_message_id,
+                Property::Timezone,
+            )
+            .await?
+        };
 
         // Obtain threadId
         let mut references = Vec::with_capacity(5);
@@ -414,6 +440,9 @@ impl JMAP {
                 }),
                 0u64.serialize(),
             );
+        if let Some(timezone) = timezone {
+            batch.set(Property::Timezone, timezone.serialize());
+        }
         EmailIndexBuilder::set(metadata).build(
             &mut batch,
             account_id,
@@ -12,9 +12,10 @@ use jmap_proto::types::{
 };
 use store::{
     ahash::AHashMap,
+    query::Filter,
     roaring::RoaringBitmap,
     write::{
-        log::ChangeLogBuilder, BatchBuilder, Bincode, BitmapClass, MaybeDynamicId, TagValue,
+        log::ChangeLogBuilder, now, BatchBuilder, Bincode, BitmapClass, MaybeDynamicId, TagValue,
         ValueClass, F_BITMAP, F_CLEAR, F_VALUE,
     },
     BitmapKey, IterateParams, ValueKey, U32_LEN,
@@ -30,6 +31,8 @@ use crate::{
 use super::{index::EmailIndexBuilder, metadata::MessageMetadata};
 use rand::prelude::SliceRandom;
 
+const EXPUNGE_BATCH_SIZE: usize = 1000;
+
 impl JMAP {
     pub async fn emails_tombstone(
         &self,
@@ -262,6 +265,15 @@ impl JMAP {
             }
         }
 
+        // Apply mailbox retention policies
+        if !self.core.jmap.mail_retention.is_empty() {
+            if let Err(err) = self.emails_apply_retention(account_id).await {
+                trc::error!(err
+                    .details("Failed to apply retention policies.")
+                    .account_id(account_id));
+            }
+        }
+
         // Purge tombstoned messages
         if let Err(err) = self.emails_purge_tombstoned(account_id).await {
             trc::error!(err
@@ -336,29 +348,143 @@ impl JMAP {
             }
         }
 
+        self.emails_expunge(account_id, destroy_ids, None).await
+    }
+
+    pub async fn emails_apply_retention(&self, account_id: u32) -> trc::Result<()> {
+        for policy in &self.core.jmap.mail_retention {
+            // Policies refer to a mailbox either by role or by name
+            let mailbox_id = if let Some(mailbox_id) = self
+                .mailbox_get_by_role(account_id, &policy.mailbox.to_lowercase())
+                .await?
+            {
+                mailbox_id
+            } else if let Some(mailbox_id) = self
+                .mailbox_get_by_name(account_id, &policy.mailbox)
+                .await?
+            {
+                mailbox_id
+            } else {
+                continue;
+            };
+
+            let mut deletion_candidates = self
+                .get_tag(
+                    account_id,
+                    Collection::Email,
+                    Property::MailboxIds,
+                    TagValue::Id(mailbox_id),
+                )
+                .await?
+                .unwrap_or_default();
+
+            // Messages flagged to be kept are exempt
+            if let Some(keyword) = &self.core.jmap.mail_retention_exempt {
+                if !deletion_candidates.is_empty() {
+                    if let Some(exempt_ids) = self
+                        .get_tag(
+                            account_id,
+                            Collection::Email,
+                            Property::Keywords,
+                            Keyword::from(keyword.clone()),
+                        )
+                        .await?
+                    {
+                        deletion_candidates -= exempt_ids;
+                    }
+                }
+            }
+            if deletion_candidates.is_empty() {
+                continue;
+            }
+
+            // Find messages received before the retention period
+            let reference_time = now().saturating_sub(policy.period.as_secs());
+            let mut destroy_ids = self
+                .filter(
+                    account_id,
+                    Collection::Email,
+                    vec![Filter::lt(Property::ReceivedAt, reference_time)],
+                )
+                .await?
+                .results;
+            destroy_ids &= deletion_candidates;
+
+            // Messages that are also filed in other mailboxes are kept
+            if !destroy_ids.is_empty() {
+                for (document_id, mailboxes) in self
+                    .get_properties::<Vec<UidMailbox>, _, _>(
+                        account_id,
+                        Collection::Email,
+                        &destroy_ids,
+                        Property::MailboxIds,
+                    )
+                    .await?
+                {
+                    if mailboxes.iter().any(|m| m.mailbox_id != mailbox_id) {
+                        destroy_ids.remove(document_id);
+                    }
+                }
+            }
+
+            self.emails_expunge(
+                account_id,
+                destroy_ids,
+                Some((mailbox_id, policy.mailbox.as_str())),
+            )
+            .await?;
+        }
+
+        Ok(())
+    }
+
+    async fn emails_expunge(
+        &self,
+        account_id: u32,
+        destroy_ids: RoaringBitmap,
+        mailbox: Option<(u32, &str)>,
+    ) -> trc::Result<()> {
         if destroy_ids.is_empty() {
             return Ok(());
         }
 
-        trc::event!(
-            Purge(trc::PurgeEvent::AutoExpunge),
-            AccountId = account_id,
-            Total = destroy_ids.len(),
-        );
+        if let Some((mailbox_id, mailbox_name)) = mailbox {
+            trc::event!(
+                Purge(trc::PurgeEvent::AutoExpunge),
+                AccountId = account_id,
+                MailboxId = mailbox_id,
+                MailboxName = mailbox_name.to_string(),
+                Total = destroy_ids.len(),
+            );
+        } else {
+            trc::event!(
+                Purge(trc::PurgeEvent::AutoExpunge),
+                AccountId = account_id,
+                Total = destroy_ids.len(),
+            );
+        }
 
-        // Tombstone messages
-        let (changes, _) = self.emails_tombstone(account_id, destroy_ids).await?;
-
-        // Write and broadcast changes
-        if !changes.is_empty() {
-            let change_id = self.commit_changes(account_id, changes).await?;
-            self.broadcast_state_change(
-                StateChange::new(account_id)
-                    .with_change(DataType::Email, change_id)
-                    .with_change(DataType::Mailbox, change_id)
-                    .with_change(DataType::Thread, change_id),
-            )
-            .await;
+        // Tombstone messages in batches so other requests can make progress,
+        // a later run picks up where an interrupted one stopped
+        let mut destroy_ids = destroy_ids.into_iter().peekable();
+        while destroy_ids.peek().is_some() {
+            let batch_ids = destroy_ids
+                .by_ref()
+                .take(EXPUNGE_BATCH_SIZE)
+                .collect::<RoaringBitmap>();
+            let (changes, _) = self.emails_tombstone(account_id, batch_ids).await?;
+
+            // Write and broadcast changes
+            if !changes.is_empty() {
+                let change_id = self.commit_changes(account_id, changes).await?;
+                self.broadcast_state_change(
+                    StateChange::new(account_id)
+                        .with_change(DataType::Email, change_id)
+                        .with_change(DataType::Mailbox, change_id)
+                        .with_change(DataType::Thread, change_id),
+                )
+                .await;
+            }
         }
 
         Ok(())
@@ -416,6 +542,7 @@ impl JMAP {
                 .with_collection(Collection::Email)
                 .delete_document(document_id)
                 .clear(Property::Cid)
+                .clear(Property::Timezone)
                 .tag(
                     Property::MailboxIds,
                     TagValue::Id(MaybeDynamicId::Static(TOMBSTONE_ID)),
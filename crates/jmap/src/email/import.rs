@@ -22,7 +22,10 @@ use utils::map::vec_map::VecMap;
 
 use crate::{api::http::HttpSessionData, JMAP};
 
-use super::ingest::{IngestEmail, IngestSource};
+use super::{
+    ingest::{IngestEmail, IngestSource},
+    keywords::KeywordRegistry,
+};
 
 impl JMAP {
     pub async fn email_import(
@@ -99,6 +102,22 @@ impl JMAP {
                 }
             }
 
+            // Validate the keywords against the mailbox keyword policy
+            let mut keywords = email.keywords;
+            let mut registry = KeywordRegistry::default();
+            if let Some(err) = self
+                .email_check_keywords(account_id, &mailbox_ids, &mut keywords, &mut registry)
+                .await?
+            {
+                response.not_created.append(
+                    id,
+                    SetError::invalid_properties()
+                        .with_property(Property::Keywords)
+                        .with_description(err.description()),
+                );
+                continue;
+            }
+
             // Fetch raw message to import
             let raw_message = match self.blob_download(&email.blob_id, access_token).await? {
                 Some(raw_message) => raw_message,
@@ -119,15 +138,20 @@ impl JMAP {
                     message: MessageParser::new().parse(&raw_message),
                     resource: resource_token.clone(),
                     mailbox_ids,
-                    keywords: email.keywords,
+                    keywords,
                     received_at: email.received_at.map(|r| r.into()),
+                    received_at_offset: 0,
                     source: IngestSource::Jmap,
+                    delivered_to: None,
                     encrypt: self.core.jmap.encrypt && self.core.jmap.encrypt_append,
+                    dedup: None,
                     session_id: session.session_id,
+                    dry_run: false,
                 })
                 .await
             {
                 Ok(email) => {
+                    self.email_register_keywords(account_id, &mut registry).await?;
                     response.created.append(id, email.into());
                 }
                 Err(mut err) => match err.as_ref() {
@@ -9,7 +9,11 @@ use std::{
     time::{Duration, Instant},
 };
 
-use common::auth::ResourceToken;
+use common::{
+    auth::ResourceToken,
+    config::jmap::settings::{DedupKey, IngestMirror},
+    expr::{functions::ResolveVariable, Variable, V_AUTHENTICATED_AS},
+};
 use jmap_proto::{
     object::Object,
     types::{
@@ -22,6 +26,7 @@ use mail_parser::{
 };
 
 use rand::Rng;
+use smtp::queue::MessageSource;
 use store::{
     ahash::AHashSet,
     query::Filter,
@@ -44,6 +49,7 @@ use crate::{
 use super::{
     crypto::{EncryptMessage, EncryptMessageError, EncryptionParams},
     index::{TrimTextValue, MAX_SORT_FIELD_LENGTH},
+    rewrite::rewrite_headers,
 };
 
 #[derive(Default)]
@@ -53,6 +59,17 @@ pub struct IngestedEmail {
     pub blob_id: BlobId,
     pub size: usize,
     pub imap_uids: Vec<u32>,
+    pub disposition: Option<IngestDisposition>,
+}
+
+// Outcome of a dry run, nothing is written when it is computed
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct IngestDisposition {
+    pub mailbox_ids: Vec<u32>,
+    pub keywords: Vec<Keyword>,
+    pub is_spam: bool,
+    pub spam_score: Option<f64>,
+    pub sieve_actions: Vec<String>,
 }
 
 pub struct IngestEmail<'x> {
@@ -62,9 +79,19 @@ pub struct IngestEmail<'x> {
     pub mailbox_ids: Vec<u32>,
     pub keywords: Vec<Keyword>,
     pub received_at: Option<u64>,
+    pub received_at_offset: i32,
     pub source: IngestSource,
+    pub delivered_to: Option<&'x str>,
     pub encrypt: bool,
+    pub dedup: Option<IngestDedup>,
     pub session_id: u64,
+    pub dry_run: bool,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct IngestDedup {
+    pub window: Duration,
+    pub key: DedupKey,
 }
 
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
@@ -89,6 +116,7 @@ impl JMAP {
             .caused_by(trc::location!())?;
 
         // Parse message
+        let rewritten_message;
         let mut raw_message = Cow::from(params.raw_message);
         let mut message = params.message.ok_or_else(|| {
             trc::EventType::MessageIngest(trc::MessageIngestEvent::Error)
@@ -113,8 +141,57 @@ impl JMAP {
             }
         }
 
+        // Report where the message would be filed without storing it
+        if params.dry_run {
+            return Ok(IngestedEmail {
+                change_id: u64::MAX,
+                size: raw_message_len as usize,
+                disposition: Some(IngestDisposition {
+                    spam_score: spam_score(&message),
+                    mailbox_ids: params.mailbox_ids,
+                    keywords: params.keywords,
+                    is_spam,
+                    sieve_actions: Vec::new(),
+                }),
+                ..Default::default()
+            });
+        }
+
+        // Apply header rewrite rules
+        if !self.core.jmap.ingest_headers.is_empty() {
+            if let Some(new_raw_message) = rewrite_headers(
+                &self.core.jmap.ingest_headers,
+                &message,
+                raw_message.as_ref(),
+                params.source,
+                params.delivered_to,
+            ) {
+                rewritten_message = new_raw_message;
+                raw_message = Cow::from(rewritten_message.as_slice());
+
+                // Added headers count towards the quota
+                if raw_message.len() as u64 > raw_message_len {
+                    self.has_available_quota(&params.resource, raw_message.len() as u64)
+                        .await
+                        .caused_by(trc::location!())?;
+                }
+                raw_message_len = raw_message.len() as u64;
+                message = MessageParser::default()
+                    .parse(rewritten_message.as_slice())
+                    .ok_or_else(|| {
+                        trc::EventType::MessageIngest(trc::MessageIngestEvent::Error)
+                            .ctx(trc::Key::Code, 550)
+                            .ctx(
+                                trc::Key::Reason,
+                                "Failed to parse rewritten e-mail message.",
+                            )
+                    })?;
+            }
+        }
+
         // Obtain message references and thread name
         let mut message_id = String::new();
+        let mut dedup_key = None;
         let thread_id = {
             let mut references = Vec::with_capacity(5);
             let mut subject = "";
@@ -187,9 +264,46 @@ impl JMAP {
                     blob_id: BlobId::default(),
                     imap_uids: Vec::new(),
                     size: 0,
+                    disposition: None,
                 });
             }
 
+            // Check for duplicates within the deduplication window
+            if let Some(dedup) = params.dedup {
+                let mailbox_id = params.mailbox_ids.first().copied().unwrap_or(INBOX_ID);
+                let dedup_value = match dedup.key {
+                    DedupKey::MessageId if !message_id.is_empty() => Some(message_id.as_bytes()),
+                    DedupKey::BodyHash => Some(params.raw_message),
+                    DedupKey::MessageId => None,
+                };
+
+                if let Some(dedup_value) = dedup_value {
+                    let key = format!(
+                        "dedup:{account_id}:{mailbox_id}:{}",
+                        blake3::hash(dedup_value).to_hex()
+                    )
+                    .into_bytes();
+
+                    if let Some(ingested) = self
+                        .email_dedup_match(account_id, mailbox_id, &key, dedup.window)
+                        .await
+                        .caused_by(trc::location!())?
+                    {
+                        trc::event!(
+                            MessageIngest(MessageIngestEvent::Duplicate),
+                            SpanId = params.session_id,
+                            AccountId = account_id,
+                            DocumentId = ingested.id.document_id(),
+                            MessageId = message_id,
+                        );
+
+                        return Ok(ingested);
+                    }
+
+                    dedup_key = Some((key, dedup.window));
+                }
+            }
+
             if !references.is_empty() {
                 self.find_or_merge_thread(account_id, subject, &references)
                     .await?
@@ -322,6 +436,14 @@ impl JMAP {
                 0u64.serialize(),
             );
 
+        // Keep the timezone of the received time, if any
+        if params.received_at_offset != 0 {
+            batch.set(
+                Property::Timezone,
+                (params.received_at_offset as i64).serialize(),
+            );
+        }
+
         // Insert and obtain ids
         let ids = self
             .core
@@ -340,6 +462,20 @@ impl JMAP {
         // Request FTS index
         self.inner.request_fts_index();
 
+        // Remember the message for the deduplication window
+        if let Some((key, window)) = dedup_key {
+            self.core
+                .storage
+                .lookup
+                .key_set(
+                    key,
+                    document_id.to_string().into_bytes(),
+                    Some(window.as_secs()),
+                )
+                .await
+                .caused_by(trc::location!())?;
+        }
+
         trc::event!(
             MessageIngest(match params.source {
                 IngestSource::Smtp =>
@@ -362,6 +498,10 @@ impl JMAP {
             Elapsed = start_time.elapsed(),
         );
 
+        // Warn the account owner when approaching the quota
+        self.check_quota_warning(&params.resource, params.session_id)
+            .await;
+
         Ok(IngestedEmail {
             id,
             change_id,
@@ -376,9 +516,138 @@ impl JMAP {
             },
             size: raw_message_len as usize,
             imap_uids,
+            disposition: None,
         })
     }
 
+    pub(crate) async fn mirror_message(
+        &self,
+        mirror: &IngestMirror,
+        raw_message: &[u8],
+        session_id: u64,
+    ) {
+        // Mirrored copies use a null return path so failures never bounce to the sender
+        let mut message = self.smtp.new_message("", "", "", session_id);
+        message
+            .add_recipient(mirror.address.as_str(), &self.smtp)
+            .await;
+
+        if !message
+            .queue(
+                None,
+                raw_message,
+                session_id,
+                &self.smtp,
+                MessageSource::Autogenerated,
+            )
+            .await
+        {
+            trc::event!(
+                MessageIngest(MessageIngestEvent::Error),
+                SpanId = session_id,
+                To = mirror.address.clone(),
+                Reason = "Failed to queue mirror copy.",
+            );
+        }
+    }
+
+    pub async fn email_append_dedup(
+        &self,
+        account_name: &str,
+        session_id: u64,
+    ) -> Option<IngestDedup> {
+        let dedup = self.core.jmap.append_dedup.as_ref()?;
+        self.core
+            .eval_if::<Duration, _>(&dedup.window, &DedupResolver { account_name }, session_id)
+            .await
+            .filter(|window| !window.is_zero())
+            .map(|window| IngestDedup {
+                window,
+                key: dedup.key,
+            })
+    }
+
+    async fn email_dedup_match(
+        &self,
+        account_id: u32,
+        mailbox_id: u32,
+        key: &[u8],
+        window: Duration,
+    ) -> trc::Result<Option<IngestedEmail>> {
+        let document_id = if let Some(document_id) = self
+            .core
+            .storage
+            .lookup
+            .key_get::<String>(key.to_vec())
+            .await?
+            .and_then(|id| id.parse::<u32>().ok())
+        {
+            document_id
+        } else {
+            return Ok(None);
+        };
+
+        // Make sure the original message was not deleted or moved since
+        if !self
+            .get_tag(
+                account_id,
+                Collection::Email,
+                Property::MailboxIds,
+                mailbox_id,
+            )
+            .await?
+            .is_some_and(|ids| ids.contains(document_id))
+        {
+            return Ok(None);
+        }
+        let uid = self
+            .get_property::<Vec<UidMailbox>>(
+                account_id,
+                Collection::Email,
+                document_id,
+                Property::MailboxIds,
+            )
+            .await?
+            .and_then(|mailboxes| {
+                mailboxes
+                    .into_iter()
+                    .find(|mailbox| mailbox.mailbox_id == mailbox_id)
+            })
+            .map(|mailbox| mailbox.uid);
+        let thread_id = self
+            .get_property::<u32>(
+                account_id,
+                Collection::Email,
+                document_id,
+                Property::ThreadId,
+            )
+            .await?;
+
+        if let (Some(uid), Some(thread_id)) = (uid, thread_id) {
+            // Slide the window
+            self.core
+                .storage
+                .lookup
+                .key_set(
+                    key.to_vec(),
+                    document_id.to_string().into_bytes(),
+                    Some(window.as_secs()),
+                )
+                .await?;
+
+            Ok(Some(IngestedEmail {
+                id: Id::from_parts(thread_id, document_id),
+                change_id: u64::MAX,
+                blob_id: BlobId::default(),
+                size: 0,
+                imap_uids: vec![uid],
+                disposition: None,
+            }))
+        } else {
+            Ok(None)
+        }
+    }
+
     pub async fn find_or_merge_thread(
         &self,
         account_id: u32,
@@ -571,3 +840,31 @@ impl From<IngestedEmail> for Object<Value> {
             .with_property(Property::Size, email.size)
     }
 }
+
+// Reads the score added by the spam filter to the X-Spam-Status header
+fn spam_score(message: &Message<'_>) -> Option<f64> {
+    message.root_part().headers().iter().find_map(|header| {
+        if header.name.as_str().eq_ignore_ascii_case("X-Spam-Status") {
+            header.value().as_text()?.split(',').find_map(|part| {
+                part.trim()
+                    .strip_prefix("score=")
+                    .and_then(|score| score.trim().parse().ok())
+            })
+        } else {
+            None
+        }
+    })
+}
+
+struct DedupResolver<'x> {
+    account_name: &'x str,
+}
+
+impl ResolveVariable for DedupResolver<'_> {
+    fn resolve_variable(&self, variable: u32) -> Variable<'_> {
+        match variable {
+            V_AUTHENTICATED_AS => self.account_name.into(),
+            _ => "".into(),
+        }
+    }
+}
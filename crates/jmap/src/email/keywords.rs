@@ -0,0 +1,295 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use common::config::jmap::settings::{KeywordAction, KeywordRule};
+use jmap_proto::types::{collection::Collection, keyword::Keyword, property::Property};
+use store::write::{
+    assert::{AssertValue, HashedValue, ToAssertValue},
+    BatchBuilder, Bincode, F_VALUE,
+};
+use trc::AddContext;
+
+use crate::JMAP;
+
+const MAX_REGISTRY_RETRIES: usize = 3;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum KeywordError {
+    NotAllowed(Keyword),
+    TooManyKeywords(usize),
+}
+
+impl JMAP {
+    // Validates the keywords being set on a message in the given mailboxes
+    // against the configured keyword policy. With the drop action disallowed
+    // keywords are removed from the list, otherwise the first one is returned.
+    // New custom keywords are only added to the registry in memory, callers
+    // write it with email_register_keywords once the message has been stored.
+    pub async fn email_check_keywords(
+        &self,
+        account_id: u32,
+        mailbox_ids: &[u32],
+        keywords: &mut Vec<Keyword>,
+        registry: &mut KeywordRegistry,
+    ) -> trc::Result<Option<KeywordError>> {
+        let policy = match &self.core.jmap.keyword_policy {
+            Some(policy) if keywords.iter().any(|k| !is_system_flag(k)) => policy,
+            _ => return Ok(None),
+        };
+
+        // Obtain the rules that apply to these mailboxes
+        let mut rules = Vec::with_capacity(policy.rules.len());
+        for rule in &policy.rules {
+            let applies = if let Some(mailbox) = &rule.mailbox {
+                if let Some(mailbox_id) = self
+                    .mailbox_get_by_role(account_id, &mailbox.to_lowercase())
+                    .await?
+                {
+                    mailbox_ids.contains(&mailbox_id)
+                } else if let Some(mailbox_id) =
+                    self.mailbox_get_by_name(account_id, mailbox).await?
+                {
+                    mailbox_ids.contains(&mailbox_id)
+                } else {
+                    false
+                }
+            } else {
+                true
+            };
+            if applies {
+                rules.push(rule);
+            }
+        }
+
+        let num_registered = registry.registered.len();
+        let mut idx = 0;
+        while idx < keywords.len() {
+            let keyword = &keywords[idx];
+            let error = if is_system_flag(keyword) {
+                None
+            } else if !is_allowed(&rules, keyword) {
+                Some(KeywordError::NotAllowed(keyword.clone()))
+            } else if let (Some(max_custom), Keyword::Other(name)) = (policy.max_custom, keyword) {
+                // Only keywords not yet used in the account count towards the limit
+                if self
+                    .get_tag(account_id, Collection::Email, Property::Keywords, keyword)
+                    .await?
+                    .map_or(true, |ids| ids.is_empty())
+                {
+                    if registry.custom.is_none() {
+                        registry.custom = Some(self.get_custom_keywords(account_id).await?);
+                    }
+                    let custom = registry.custom.as_mut().unwrap();
+                    if custom.inner.inner.contains(name) {
+                        None
+                    } else {
+                        // Drop registered keywords that are no longer in use
+                        // before rejecting
+                        if custom.inner.inner.len() >= max_custom && !registry.pruned {
+                            let mut in_use = Vec::with_capacity(custom.inner.inner.len());
+                            for name in std::mem::take(&mut custom.inner.inner) {
+                                if registry.registered.contains(&name)
+                                    || self
+                                        .get_tag(
+                                            account_id,
+                                            Collection::Email,
+                                            Property::Keywords,
+                                            name.clone(),
+                                        )
+                                        .await?
+                                        .map_or(false, |ids| !ids.is_empty())
+                                {
+                                    in_use.push(name);
+                                }
+                            }
+                            custom.inner.inner = in_use;
+                            registry.pruned = true;
+                        }
+
+                        if custom.inner.inner.len() >= max_custom {
+                            Some(KeywordError::TooManyKeywords(max_custom))
+                        } else {
+                            custom.inner.inner.push(name.clone());
+                            registry.registered.push(name.clone());
+                            None
+                        }
+                    }
+                } else {
+                    None
+                }
+            } else {
+                None
+            };
+
+            match error {
+                Some(error) if policy.action == KeywordAction::Reject => {
+                    // The message is not stored, so its keywords are not registered
+                    if let Some(custom) = &mut registry.custom {
+                        for name in registry.registered.drain(num_registered..) {
+                            custom.inner.inner.retain(|registered| registered != &name);
+                        }
+                    }
+                    return Ok(Some(error));
+                }
+                Some(_) => {
+                    keywords.remove(idx);
+                }
+                None => {
+                    idx += 1;
+                }
+            }
+        }
+
+        Ok(None)
+    }
+
+    // Writes the custom keywords registered by email_check_keywords, to be
+    // called after the messages using them have been stored
+    pub async fn email_register_keywords(
+        &self,
+        account_id: u32,
+        registry: &mut KeywordRegistry,
+    ) -> trc::Result<()> {
+        let registry = std::mem::take(registry);
+        if let Some(custom) = registry
+            .custom
+            .filter(|_| !registry.registered.is_empty() || registry.pruned)
+        {
+            self.set_custom_keywords(account_id, custom, registry.registered)
+                .await
+        } else {
+            Ok(())
+        }
+    }
+
+    // Custom keywords in use by an account are kept in a registry, so the
+    // limit can be enforced without scanning the keyword tags. The registry
+    // is seeded from the stored tags the first time it is read.
+    async fn get_custom_keywords(&self, account_id: u32) -> trc::Result<CustomKeywords> {
+        if let Some(registry) = self
+            .get_property::<HashedValue<Bincode<Vec<String>>>>(
+                account_id,
+                Collection::Principal,
+                0,
+                Property::Keywords,
+            )
+            .await?
+        {
+            Ok(CustomKeywords {
+                assert_value: registry.to_assert_value(),
+                inner: registry.inner,
+            })
+        } else {
+            let names = self
+                .core
+                .storage
+                .data
+                .get_tag_values(
+                    account_id,
+                    Collection::Email.into(),
+                    Property::Keywords.into(),
+                )
+                .await
+                .caused_by(trc::location!())?
+                .into_iter()
+                .filter_map(|name| String::from_utf8(name).ok())
+                .collect();
+
+            Ok(CustomKeywords {
+                assert_value: AssertValue::None,
+                inner: Bincode::new(names),
+            })
+        }
+    }
+
+    // Writes the registry back, concurrent updates are merged by re-reading
+    // it and adding the newly registered keywords again
+    async fn set_custom_keywords(
+        &self,
+        account_id: u32,
+        mut registry: CustomKeywords,
+        registered: Vec<String>,
+    ) -> trc::Result<()> {
+        for _ in 0..MAX_REGISTRY_RETRIES {
+            let mut batch = BatchBuilder::new();
+            batch
+                .with_account_id(account_id)
+                .with_collection(Collection::Principal)
+                .update_document(0)
+                .assert_value(Property::Keywords, registry.assert_value)
+                .value(Property::Keywords, &registry.inner, F_VALUE);
+
+            match self.write_batch(batch).await {
+                Ok(_) => return Ok(()),
+                Err(err)
+                    if err.matches(trc::EventType::Store(trc::StoreEvent::AssertValueFailed)) =>
+                {
+                    registry = self.get_custom_keywords(account_id).await?;
+                    for name in &registered {
+                        if !registry.inner.inner.contains(name) {
+                            registry.inner.inner.push(name.clone());
+                        }
+                    }
+                }
+                Err(err) => return Err(err.caused_by(trc::location!())),
+            }
+        }
+
+        Err(trc::StoreEvent::AssertValueFailed
+            .into_err()
+            .details("Failed to update custom keyword registry.")
+            .caused_by(trc::location!()))
+    }
+}
+
+// Custom keywords registry loaded while validating keywords, along with
+// the keywords added to it that have not been written yet
+#[derive(Default)]
+pub struct KeywordRegistry {
+    custom: Option<CustomKeywords>,
+    registered: Vec<String>,
+    pruned: bool,
+}
+
+struct CustomKeywords {
+    assert_value: AssertValue,
+    inner: Bincode<Vec<String>>,
+}
+
+impl KeywordError {
+    pub fn description(&self) -> String {
+        match self {
+            KeywordError::NotAllowed(keyword) => {
+                format!("Keyword \"{keyword}\" is not allowed in this mailbox.")
+            }
+            KeywordError::TooManyKeywords(max) => {
+                format!("Accounts cannot have more than {max} custom keywords.")
+            }
+        }
+    }
+}
+
+fn is_allowed(rules: &[&KeywordRule], keyword: &Keyword) -> bool {
+    if !rules.is_empty() {
+        let keyword = keyword.to_string().to_lowercase();
+        rules.iter().all(|rule| rule.is_allowed(&keyword))
+    } else {
+        true
+    }
+}
+
+// IMAP system flags can always be set
+fn is_system_flag(keyword: &Keyword) -> bool {
+    matches!(
+        keyword,
+        Keyword::Seen
+            | Keyword::Answered
+            | Keyword::Flagged
+            | Keyword::Deleted
+            | Keyword::Draft
+            | Keyword::Recent
+    )
+}
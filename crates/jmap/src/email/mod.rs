@@ -5,6 +5,7 @@
  */
 
 pub mod body;
+pub mod bulk_import;
 pub mod cache;
 pub mod copy;
 pub mod crypto;
@@ -14,8 +15,11 @@ pub mod headers;
 pub mod import;
 pub mod index;
 pub mod ingest;
+pub mod keywords;
 pub mod metadata;
 pub mod parse;
 pub mod query;
+pub mod quota;
+pub mod rewrite;
 pub mod set;
 pub mod snippet;
@@ -0,0 +1,137 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use std::{future::Future, pin::Pin};
+
+use common::auth::ResourceToken;
+use mail_builder::MessageBuilder;
+use mail_parser::MessageParser;
+use trc::AddContext;
+use utils::config::Rate;
+
+use crate::{mailbox::INBOX_ID, JMAP};
+
+use super::ingest::{IngestEmail, IngestSource};
+
+impl JMAP {
+    // Warns once per threshold and interval when an account's usage crosses
+    // one of the configured quota warning thresholds. The future is boxed as
+    // the notification is ingested through `email_ingest`, which calls back here.
+    pub fn check_quota_warning<'x>(
+        &'x self,
+        quotas: &'x ResourceToken,
+        session_id: u64,
+    ) -> Pin<Box<dyn Future<Output = ()> + Send + 'x>> {
+        Box::pin(async move {
+            if let Err(err) = self.check_quota_warning_(quotas, session_id).await {
+                trc::error!(err
+                    .span_id(session_id)
+                    .account_id(quotas.account_id)
+                    .details("Failed to check quota warning thresholds"));
+            }
+        })
+    }
+
+    async fn check_quota_warning_(
+        &self,
+        quotas: &ResourceToken,
+        session_id: u64,
+    ) -> trc::Result<()> {
+        let warning = match &self.core.jmap.quota_warning {
+            Some(warning) if quotas.quota != 0 => warning,
+            _ => return Ok(()),
+        };
+
+        // Used quota is kept in a counter, no need to sum the messages
+        let used_quota = self.get_used_quota(quotas.account_id).await? as u64;
+        let threshold = if let Some(threshold) = warning.threshold(used_quota, quotas.quota) {
+            threshold
+        } else {
+            return Ok(());
+        };
+
+        // Skip thresholds already warned about within the interval, the counter
+        // is incremented atomically so concurrent deliveries warn only once
+        let rate = Rate {
+            requests: 1,
+            period: warning.interval,
+        };
+        let lookup = &self.core.storage.lookup;
+        if lookup
+            .is_rate_allowed(
+                format!("qw:{}:{threshold}", quotas.account_id).as_bytes(),
+                &rate,
+                false,
+            )
+            .await
+            .caused_by(trc::location!())?
+            .is_some()
+        {
+            return Ok(());
+        }
+
+        // Lower thresholds are also considered warned about
+        for lower_threshold in warning.thresholds.iter().filter(|t| **t < threshold) {
+            lookup
+                .is_rate_allowed(
+                    format!("qw:{}:{lower_threshold}", quotas.account_id).as_bytes(),
+                    &rate,
+                    false,
+                )
+                .await
+                .caused_by(trc::location!())?;
+        }
+
+        trc::event!(
+            MessageIngest(trc::MessageIngestEvent::QuotaWarning),
+            SpanId = session_id,
+            AccountId = quotas.account_id,
+            Limit = quotas.quota,
+            Size = used_quota,
+            Total = threshold,
+        );
+
+        if warning.notify {
+            let message = MessageBuilder::new()
+                .from(("Mail System", warning.from.as_str()))
+                .subject(format!("Mailbox quota warning: {threshold}% used"))
+                .text_body(format!(
+                    concat!(
+                        "Your mailbox is using {} of {} bytes ({}%) of its storage quota.\r\n\r\n",
+                        "Once the quota is exceeded new messages will be rejected, please ",
+                        "delete messages you no longer need.\r\n"
+                    ),
+                    used_quota,
+                    quotas.quota,
+                    used_quota.saturating_mul(100) / quotas.quota
+                ))
+                .write_to_vec()
+                .unwrap_or_default();
+
+            // The warning level is already stored, so ingesting the
+            // notification does not trigger another one.
+            self.email_ingest(IngestEmail {
+                raw_message: &message,
+                message: MessageParser::new().parse(&message),
+                resource: quotas.clone(),
+                mailbox_ids: vec![INBOX_ID],
+                keywords: vec![],
+                received_at: None,
+                received_at_offset: 0,
+                source: IngestSource::Smtp,
+                delivered_to: None,
+                encrypt: self.core.jmap.encrypt,
+                dedup: None,
+                session_id,
+                dry_run: false,
+            })
+            .await
+            .caused_by(trc::location!())?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,229 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use std::borrow::Cow;
+
+use common::config::jmap::settings::{HeaderRewrite, HeaderRewriteAction};
+use mail_parser::{Header, Message};
+
+use super::ingest::IngestSource;
+
+impl IngestSource {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            IngestSource::Smtp => "smtp",
+            IngestSource::Jmap => "jmap",
+            IngestSource::Imap => "imap",
+        }
+    }
+}
+
+// Applies the configured header rules to the top-level header block,
+// returning None when the message is left unchanged. Only header bytes are
+// rewritten, the body is copied verbatim.
+pub fn rewrite_headers(
+    rules: &[HeaderRewrite],
+    message: &Message<'_>,
+    raw_message: &[u8],
+    source: IngestSource,
+    delivered_to: Option<&str>,
+) -> Option<Vec<u8>> {
+    let headers = message.root_part().headers();
+    let signed_headers = signed_headers(headers, raw_message);
+    let mut added = Vec::new();
+    let mut removed = Vec::new();
+
+    for rule in rules {
+        if !rule.sources.is_empty() && !rule.sources.iter().any(|s| s == source.as_str()) {
+            continue;
+        }
+
+        let mut existing = headers
+            .iter()
+            .filter(|header| header.name.as_str().eq_ignore_ascii_case(&rule.name))
+            .peekable();
+        match rule.action {
+            HeaderRewriteAction::Add => {
+                if existing.peek().is_some() {
+                    continue;
+                }
+            }
+            HeaderRewriteAction::Remove | HeaderRewriteAction::Replace => {
+                // Removing signed or authentication headers would invalidate
+                // the results recorded when the message was received
+                if is_protected(&rule.name)
+                    || signed_headers
+                        .iter()
+                        .any(|name| name.eq_ignore_ascii_case(&rule.name))
+                {
+                    continue;
+                }
+                removed.extend(existing.map(|header| (header.offset_field, header.offset_end)));
+                if rule.action == HeaderRewriteAction::Remove {
+                    continue;
+                }
+            }
+        }
+
+        let value = if rule.value.contains("{recipient}") {
+            if let Some(delivered_to) = delivered_to {
+                Cow::from(rule.value.replace("{recipient}", delivered_to))
+            } else {
+                continue;
+            }
+        } else {
+            Cow::from(rule.value.as_str())
+        };
+        added.extend_from_slice(rule.name.as_bytes());
+        added.extend_from_slice(b": ");
+        added.extend_from_slice(value.as_bytes());
+        added.extend_from_slice(b"\r\n");
+    }
+
+    if added.is_empty() && removed.is_empty() {
+        return None;
+    }
+
+    removed.sort_unstable();
+    let mut output = Vec::with_capacity(added.len() + raw_message.len());
+    output.extend_from_slice(&added);
+    let mut pos = 0;
+    for (start, end) in removed {
+        if start >= pos {
+            output.extend_from_slice(raw_message.get(pos..start).unwrap_or_default());
+            pos = end;
+        }
+    }
+    output.extend_from_slice(raw_message.get(pos..).unwrap_or_default());
+
+    Some(output)
+}
+
+fn signed_headers<'x>(headers: &[Header<'_>], raw_message: &'x [u8]) -> Vec<&'x str> {
+    let mut names = Vec::new();
+    for header in headers {
+        let name = header.name.as_str();
+        if name.eq_ignore_ascii_case("DKIM-Signature")
+            || name.eq_ignore_ascii_case("ARC-Message-Signature")
+        {
+            if let Some(value) = raw_message
+                .get(header.offset_start..header.offset_end)
+                .and_then(|value| std::str::from_utf8(value).ok())
+            {
+                for tag in value.split(';') {
+                    if let Some(("h", value)) = tag.split_once('=').map(|(k, v)| (k.trim(), v)) {
+                        names.extend(
+                            value
+                                .split(':')
+                                .map(|name| name.trim())
+                                .filter(|name| !name.is_empty()),
+                        );
+                    }
+                }
+            }
+        }
+    }
+    names
+}
+
+fn is_protected(name: &str) -> bool {
+    ["DKIM-Signature", "Authentication-Results", "Received-SPF"]
+        .iter()
+        .any(|protected| name.eq_ignore_ascii_case(protected))
+        || name
+            .get(..4)
+            .map_or(false, |prefix| prefix.eq_ignore_ascii_case("ARC-"))
+}
+
+#[cfg(test)]
+mod tests {
+    use common::config::jmap::settings::{HeaderRewrite, HeaderRewriteAction};
+    use mail_parser::MessageParser;
+
+    use crate::email::ingest::IngestSource;
+
+    #[test]
+    fn rewrite_ingest_headers() {
+        let rule = |action, name: &str, value: &str, sources: &[&str]| HeaderRewrite {
+            action,
+            name: name.to_string(),
+            value: value.to_string(),
+            sources: sources.iter().map(|s| s.to_string()).collect(),
+        };
+        let rules = vec![
+            rule(
+                HeaderRewriteAction::Add,
+                "X-Delivered-To",
+                "{recipient}",
+                &["smtp"],
+            ),
+            rule(HeaderRewriteAction::Add, "X-Mailer", "ignored", &[]),
+            rule(HeaderRewriteAction::Remove, "X-Original-To", "", &[]),
+            rule(HeaderRewriteAction::Replace, "X-Priority", "3", &[]),
+            rule(HeaderRewriteAction::Remove, "Subject", "", &[]),
+            rule(
+                HeaderRewriteAction::Remove,
+                "Authentication-Results",
+                "",
+                &[],
+            ),
+        ];
+        let raw_message = concat!(
+            "DKIM-Signature: v=1; a=ed25519-sha256; d=example.org; s=ed;\r\n",
+            "\th=From:To:Subject; bh=AAAA; b=BBBB\r\n",
+            "Authentication-Results: mx.example.org; dkim=pass\r\n",
+            "X-Original-To: old@example.org\r\n",
+            "X-Mailer: Test\r\n",
+            "From: john@example.org\r\n",
+            "X-Priority: 1\r\n",
+            "Subject: Test\r\n",
+            "X-Original-To: other@example.org\r\n",
+            "\r\n",
+            "X-Original-To: body@example.org\r\n",
+        );
+        let message = MessageParser::new().parse(raw_message).unwrap();
+
+        assert_eq!(
+            String::from_utf8(
+                super::rewrite_headers(
+                    &rules,
+                    &message,
+                    raw_message.as_bytes(),
+                    IngestSource::Smtp,
+                    Some("jane@example.org"),
+                )
+                .unwrap()
+            )
+            .unwrap(),
+            concat!(
+                "X-Delivered-To: jane@example.org\r\n",
+                "X-Priority: 3\r\n",
+                "DKIM-Signature: v=1; a=ed25519-sha256; d=example.org; s=ed;\r\n",
+                "\th=From:To:Subject; bh=AAAA; b=BBBB\r\n",
+                "Authentication-Results: mx.example.org; dkim=pass\r\n",
+                "X-Mailer: Test\r\n",
+                "From: john@example.org\r\n",
+                "Subject: Test\r\n",
+                "\r\n",
+                "X-Original-To: body@example.org\r\n",
+            )
+        );
+
+        // Rules limited to other sources are skipped
+        let raw_message = "From: john@example.org\r\nX-Mailer: Test\r\n\r\nHello\r\n";
+        let message = MessageParser::new().parse(raw_message).unwrap();
+        assert_eq!(
+            super::rewrite_headers(
+                &rules[..2],
+                &message,
+                raw_message.as_bytes(),
+                IngestSource::Imap,
+                None,
+            ),
+            None
+        );
+    }
+}
@@ -46,6 +46,7 @@ use crate::{api::http::HttpSessionData, mailbox::UidMailbox, JMAP};
 use super::{
     headers::{BuildHeader, ValueToHeader},
     ingest::{IngestEmail, IngestSource},
+    keywords::KeywordRegistry,
 };
 
 impl JMAP {
@@ -680,6 +681,21 @@ impl JMAP {
                 }
             }
 
+            // Validate the keywords against the mailbox keyword policy
+            let mut registry = KeywordRegistry::default();
+            if let Some(err) = self
+                .email_check_keywords(account_id, &mailboxes, &mut keywords, &mut registry)
+                .await?
+            {
+                response.not_created.append(
+                    id,
+                    SetError::invalid_properties()
+                        .with_property(Property::Keywords)
+                        .with_description(err.description()),
+                );
+                continue 'create;
+            }
+
             // Make sure the message is not empty
             if builder.headers.is_empty()
                 && builder.body.is_none()
@@ -719,13 +735,18 @@ impl JMAP {
                     mailbox_ids: mailboxes,
                     keywords,
                     received_at,
+                    received_at_offset: 0,
                     source: IngestSource::Jmap,
+                    delivered_to: None,
                     encrypt: self.core.jmap.encrypt && self.core.jmap.encrypt_append,
+                    dedup: None,
                     session_id: session.session_id,
+                    dry_run: false,
                 })
                 .await
             {
                 Ok(message) => {
+                    self.email_register_keywords(account_id, &mut registry).await?;
                     response.created.insert(id, message.into());
                 }
                 Err(err) if err.matches(trc::EventType::Limit(trc::LimitEvent::Quota)) => {
@@ -839,6 +860,61 @@ impl JMAP {
                 continue 'update;
             }
 
+            // Validate the keywords against the mailbox keyword policy, added keywords
+            // are checked in all mailboxes and the existing ones in the mailboxes
+            // the message is being added to
+            let mut registry = KeywordRegistry::default();
+            for (check_mailbox_ids, check_keywords) in [
+                (
+                    mailboxes
+                        .current()
+                        .iter()
+                        .map(|mailbox| mailbox.mailbox_id)
+                        .collect::<Vec<_>>(),
+                    keywords.added().to_vec(),
+                ),
+                (
+                    mailboxes
+                        .added()
+                        .iter()
+                        .map(|mailbox| mailbox.mailbox_id)
+                        .collect::<Vec<_>>(),
+                    keywords
+                        .current()
+                        .iter()
+                        .filter(|keyword| !keywords.added().contains(keyword))
+                        .cloned()
+                        .collect::<Vec<_>>(),
+                ),
+            ] {
+                if check_mailbox_ids.is_empty() || check_keywords.is_empty() {
+                    continue;
+                }
+                let mut allowed = check_keywords.clone();
+                if let Some(err) = self
+                    .email_check_keywords(
+                        account_id,
+                        &check_mailbox_ids,
+                        &mut allowed,
+                        &mut registry,
+                    )
+                    .await?
+                {
+                    response.not_updated.append(
+                        id,
+                        SetError::invalid_properties()
+                            .with_property(Property::Keywords)
+                            .with_description(err.description()),
+                    );
+                    continue 'update;
+                }
+                for keyword in check_keywords {
+                    if !allowed.contains(&keyword) {
+                        keywords.discard(&keyword);
+                    }
+                }
+            }
+
             // Log change
             batch.update_document(document_id);
             let mut changed_mailboxes = AHashSet::new();
@@ -856,10 +932,11 @@ impl JMAP {
                     continue 'update;
                 }
 
-                // Set all current mailboxes as changed if the Seen tag changed
+                // Set all current mailboxes as changed if the Seen or Deleted tags
+                // changed, as both are counted by IMAP STATUS
                 if keywords
                     .changed_tags()
-                    .any(|keyword| keyword == &Keyword::Seen)
+                    .any(|keyword| keyword == &Keyword::Seen || keyword == &Keyword::Deleted)
                 {
                     for mailbox_id in mailboxes.current() {
                         changed_mailboxes.insert(mailbox_id.mailbox_id);
@@ -961,6 +1038,8 @@ impl JMAP {
             if !batch.is_empty() {
                 match self.core.storage.data.write(batch.build()).await {
                     Ok(_) => {
+                        self.email_register_keywords(account_id, &mut registry).await?;
+
                         // Add to updated list
                         response.updated.append(id, None);
                     }
@@ -1132,6 +1211,16 @@ impl<
         &self.added
     }
 
+    // Removes a tag regardless of how the tags were last updated
+    pub fn discard(&mut self, tag: &T) {
+        if let Some(index) = self.added.iter().position(|t| t == tag) {
+            self.added.swap_remove(index);
+        } else if self.current.inner.contains(tag) && !self.removed.contains(tag) {
+            self.removed.push(tag.clone());
+        }
+        self.current.inner.retain(|t| t != tag);
+    }
+
     pub fn removed(&self) -> &[T] {
         &self.removed
     }
@@ -144,6 +144,8 @@ impl JMAP {
         let has_many = num_emails > 1;
         for (idx, email) in principal.iter_str(PrincipalField::Emails).enumerate() {
             let document_id = idx as u32;
+            // Catch-all addresses ("@domain") are rejected by the sanitizer,
+            // as they would stand for an unlimited number of identities.
             let email = sanitize_email(email).unwrap_or_default();
             if email.is_empty() {
                 continue;
@@ -4,7 +4,7 @@
  * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
  */
 
-use directory::{backend::internal::PrincipalField, QueryBy};
+use directory::{backend::internal::PrincipalField, Permission, QueryBy};
 use jmap_proto::{
     error::set::SetError,
     method::set::{RequestArguments, SetRequest, SetResponse},
@@ -17,6 +17,7 @@ use jmap_proto::{
     },
 };
 use store::write::{log::ChangeLogBuilder, BatchBuilder, F_CLEAR, F_VALUE};
+use trc::AddContext;
 
 use crate::JMAP;
 
@@ -57,13 +58,9 @@ impl JMAP {
             // Validate email address
             if let Value::Text(email) = identity.get(&Property::Email) {
                 if !self
-                    .core
-                    .storage
-                    .directory
-                    .query(QueryBy::Id(account_id), false)
+                    .identity_allowed_senders(account_id)
                     .await?
-                    .unwrap_or_default()
-                    .has_str_value(PrincipalField::Emails, email)
+                    .map_or(true, |senders| is_allowed_sender(&senders, email))
                 {
                     response.not_created.append(
                         id,
@@ -179,6 +176,46 @@ impl JMAP {
 
         Ok(response)
     }
+
+    // Obtains the addresses an account may send from, or None when the account
+    // holds the send-as permission and may use any address.
+    pub async fn identity_allowed_senders(
+        &self,
+        account_id: u32,
+    ) -> trc::Result<Option<Vec<String>>> {
+        if self
+            .core
+            .get_cached_access_token(account_id)
+            .await
+            .caused_by(trc::location!())?
+            .has_permission(Permission::EmailSendAs)
+        {
+            return Ok(None);
+        }
+
+        Ok(Some(
+            self.core
+                .storage
+                .directory
+                .query(QueryBy::Id(account_id), false)
+                .await
+                .caused_by(trc::location!())?
+                .unwrap_or_default()
+                .iter_str(PrincipalField::Emails)
+                .map(|email| email.to_lowercase())
+                .collect(),
+        ))
+    }
+}
+
+// Addresses in a domain with a catch-all alias ("@domain") are allowed as
+// senders, even though they are never offered as identities.
+pub fn is_allowed_sender(senders: &[String], email: &str) -> bool {
+    let email = email.trim().to_lowercase();
+    let domain = email.rsplit_once('@').map(|(_, domain)| domain);
+    senders
+        .iter()
+        .any(|sender| *sender == email || (sender.starts_with('@') && domain == Some(&sender[1..])))
 }
 
 fn validate_identity_value(
@@ -11,7 +11,7 @@ use std::{
     time::Duration,
 };
 
-use auth::rate_limit::ConcurrencyLimiters;
+use auth::{rate_limit::ConcurrencyLimiters, CachedSession};
 use common::{
     auth::{AccessToken, ResourceToken, TenantInfo},
     manager::webadmin::WebAdminManager,
@@ -19,7 +19,7 @@ use common::{
 };
 use dashmap::DashMap;
 use directory::QueryBy;
-use email::cache::Threads;
+use email::{bulk_import::ImportProgress, cache::Threads};
 use jmap_proto::{
     method::{
         query::{QueryRequest, QueryResponse},
@@ -90,12 +90,13 @@ pub struct JmapInstance {
 }
 
 pub struct Inner {
-    pub sessions: TtlDashMap<String, u32>,
+    pub sessions: TtlDashMap<String, CachedSession>,
     pub snowflake_id: SnowflakeIdGenerator,
     pub webadmin: WebAdminManager,
     pub config_version: AtomicU8,
 
     pub concurrency_limiter: DashMap<u32, Arc<ConcurrencyLimiters>>,
+    pub imports: DashMap<u32, Arc<ImportProgress>>,
 
     pub state_tx: mpsc::Sender<state::Event>,
     pub housekeeper_tx: mpsc::Sender<housekeeper::Event>,
@@ -133,6 +134,7 @@ impl JMAP {
                 RandomState::default(),
                 shard_amount,
             ),
+            imports: DashMap::default(),
             state_tx,
             housekeeper_tx,
             index_tx: index_tx.clone(),
@@ -4,22 +4,94 @@
  * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
  */
 
-use common::DeliveryEvent;
-use tokio::sync::mpsc;
+use common::{DeliveryEvent, DeliveryResult, IngestMessage, RecipientResult};
+use tokio::{sync::mpsc, time::Instant};
 
 use crate::{JmapInstance, JMAP};
 
 pub fn spawn_delivery_manager(core: JmapInstance, mut delivery_rx: mpsc::Receiver<DeliveryEvent>) {
     tokio::spawn(async move {
-        while let Some(event) = delivery_rx.recv().await {
+        let mut drain_deadline = None;
+
+        loop {
+            // Deliveries resume once the drain timeout elapses
+            let event = if let Some(deadline) = drain_deadline {
+                match tokio::time::timeout_at(deadline, delivery_rx.recv()).await {
+                    Ok(event) => event,
+                    Err(_) => {
+                        drain_deadline = None;
+                        continue;
+                    }
+                }
+            } else {
+                delivery_rx.recv().await
+            };
+
             match event {
-                DeliveryEvent::Ingest { message, result_tx } => {
-                    result_tx
-                        .send(JMAP::from(core.clone()).deliver_message(message).await)
-                        .ok();
+                Some(DeliveryEvent::Ingest { message, result_tx }) if drain_deadline.is_some() => {
+                    result_tx.send(reject_message(message)).ok();
                 }
-                DeliveryEvent::Stop => break,
+                Some(DeliveryEvent::Ingest { message, result_tx }) => {
+                    let session_id = message.session_id;
+                    let sender = if !message.sender_address.is_empty() {
+                        message.sender_address.clone()
+                    } else {
+                        "<>".to_string()
+                    };
+                    let results = JMAP::from(core.clone()).deliver_message(message).await;
+
+                    trc::event!(
+                        Delivery(trc::DeliveryEvent::LocalDelivery),
+                        SpanId = session_id,
+                        From = sender,
+                        To = results
+                            .iter()
+                            .map(|result| result.recipient.clone())
+                            .collect::<Vec<_>>(),
+                        Result = results
+                            .iter()
+                            .map(|result| describe_result(&result.result))
+                            .collect::<Vec<_>>(),
+                    );
+
+                    result_tx.send(results).ok();
+                }
+                Some(DeliveryEvent::Drain { timeout, result_tx }) => {
+                    // Deliveries are processed in order, so all the ones queued
+                    // before the drain request have completed at this point
+                    result_tx.send(()).ok();
+
+                    // Reject new deliveries until stopped or the timeout elapses
+                    drain_deadline = Some(Instant::now() + timeout);
+                }
+                Some(DeliveryEvent::Stop) | None => break,
             }
         }
     });
 }
+
+fn reject_message(message: IngestMessage) -> Vec<RecipientResult> {
+    message
+        .recipients
+        .into_iter()
+        .map(|recipient| RecipientResult {
+            recipient,
+            result: DeliveryResult::TemporaryFailure {
+                reason: "Server is shutting down".into(),
+            },
+        })
+        .collect()
+}
+
+fn describe_result(result: &DeliveryResult) -> String {
+    match result {
+        DeliveryResult::Success => "delivered".to_string(),
+        DeliveryResult::TemporaryFailure { reason } => format!("temporary failure: {reason}"),
+        DeliveryResult::PermanentFailure { code, reason } => {
+            format!(
+                "permanent failure: {}.{}.{} {reason}",
+                code[0], code[1], code[2]
+            )
+        }
+    }
+}
@@ -67,6 +67,7 @@ pub struct Peer {
     pub gen_config: GenerationId,
     pub gen_lists: GenerationId,
     pub gen_permissions: GenerationId,
+    pub gen_auth: GenerationId,
     pub state: State,
 
     // Heartbeat state
@@ -85,6 +86,7 @@ pub struct PeerStatus {
     pub gen_config: GenerationId,
     pub gen_lists: GenerationId,
     pub gen_permissions: GenerationId,
+    pub gen_auth: GenerationId,
 }
 
 impl From<&Peer> for PeerStatus {
@@ -95,6 +97,7 @@ impl From<&Peer> for PeerStatus {
             gen_config: peer.gen_config,
             gen_lists: peer.gen_lists,
             gen_permissions: peer.gen_permissions,
+            gen_auth: peer.gen_auth,
         }
     }
 }
@@ -112,6 +115,7 @@ impl From<&Gossiper> for PeerStatus {
                 .load(Ordering::Relaxed),
             gen_lists: core.network.blocked_ips.version.load(Ordering::Relaxed),
             gen_permissions: core.security.permissions_version.load(Ordering::Relaxed),
+            gen_auth: core.security.auth_cache_version.load(Ordering::Relaxed),
         }
     }
 }
@@ -15,6 +15,7 @@ impl Peer {
             gen_config: 0,
             gen_lists: 0,
             gen_permissions: 0,
+            gen_auth: 0,
             addr,
             state: State::Seed,
             last_heartbeat: Instant::now(),
@@ -82,6 +83,7 @@ impl From<PeerStatus> for Peer {
             gen_config: value.gen_config,
             gen_lists: value.gen_lists,
             gen_permissions: value.gen_permissions,
+            gen_auth: value.gen_auth,
             state: State::Alive,
             last_heartbeat: Instant::now(),
             hb_window: vec![0; HEARTBEAT_WINDOW],
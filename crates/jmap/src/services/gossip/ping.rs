@@ -4,6 +4,7 @@
  * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
  */
 
+use common::manager::reload::reload_core;
 use smtp::queue;
 use trc::ClusterEvent;
 
@@ -100,6 +101,7 @@ impl Gossiper {
         let mut update_config = false;
         let mut update_lists = false;
         let mut update_permissions = false;
+        let mut update_auth = false;
 
         'outer: for (pos, peer) in peers.into_iter().enumerate() {
             if peer.addr == self.addr {
@@ -149,6 +151,18 @@ impl Gossiper {
                                     update_permissions = true;
                                 }
                             }
+                            if local_peer.gen_auth != peer.gen_auth {
+                                local_peer.gen_auth = peer.gen_auth;
+                                if local_peer.hb_sum > 0 {
+                                    trc::event!(
+                                        Cluster(ClusterEvent::PeerHasChanges),
+                                        RemoteIp = peer.addr,
+                                        Details = "auth_cache"
+                                    );
+
+                                    update_auth = true;
+                                }
+                            }
                         }
 
                         continue 'outer;
@@ -174,7 +188,14 @@ impl Gossiper {
 
         // Reload settings
         if update_permissions {
-            self.core.core.load().security.permissions.clear();
+            self.core.core.load().security.clear_permissions();
+        }
+
+        if update_auth {
+            let core = self.core.core.clone();
+            tokio::spawn(async move {
+                core.load().apply_auth_cache_invalidations().await;
+            });
         }
 
         if update_config || update_lists {
@@ -183,16 +204,16 @@ impl Gossiper {
 
             tokio::spawn(async move {
                 let result = if update_config {
-                    core.load().reload().await
+                    match core.load().storage.config.build_config("").await {
+                        Ok(config) => reload_core(&core, config).await,
+                        Err(err) => Err(err),
+                    }
                 } else {
                     core.load().reload_blocked_ips().await
                 };
                 match result {
                     Ok(result) => {
-                        if let Some(new_core) = result.new_core {
-                            // Update core
-                            core.store(new_core.into());
-
+                        if result.applied {
                             // Reload ACME
                             if inner
                                 .housekeeper_tx
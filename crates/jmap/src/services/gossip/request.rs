@@ -4,7 +4,7 @@
  * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
  */
 
-use crate::auth::SymmetricEncrypt;
+use common::auth::oauth::crypto::SymmetricEncrypt;
 
 use super::{EpochId, PeerStatus};
 
@@ -58,6 +58,8 @@ impl Request {
                 gen_config: it.next().copied()?,
                 gen_lists: it.next().copied()?,
                 gen_permissions: it.next().copied()?,
+                // Not sent by nodes running previous versions
+                gen_auth: it.next().copied().unwrap_or_default(),
             });
         }
         match flags & !(1 << 7) {
@@ -110,6 +112,7 @@ impl Request {
             bytes.push(peer.gen_config);
             bytes.push(peer.gen_lists);
             bytes.push(peer.gen_permissions);
+            bytes.push(peer.gen_auth);
         }
 
         bytes
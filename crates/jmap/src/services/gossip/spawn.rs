@@ -4,8 +4,8 @@
  * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
  */
 
-use crate::auth::SymmetricEncrypt;
 use crate::JmapInstance;
+use common::auth::oauth::crypto::SymmetricEncrypt;
 
 use super::request::Request;
 use super::{Gossiper, Peer, UDP_MAX_PAYLOAD};
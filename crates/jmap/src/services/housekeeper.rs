@@ -10,6 +10,7 @@ use std::{
 };
 
 use common::{config::telemetry::OtelMetrics, IPC_CHANNEL_BUFFER};
+use directory::Type;
 
 #[cfg(feature = "enterprise")]
 use common::telemetry::{
@@ -33,6 +34,9 @@ pub enum Event {
         provider_id: String,
         renew_at: Instant,
     },
+    OcspReschedule {
+        refresh_at: Instant,
+    },
     Purge(PurgeType),
     ReloadSettings,
     Exit,
@@ -57,7 +61,9 @@ enum ActionClass {
     Account,
     Store(usize),
     Acme(String),
+    Ocsp,
     OtelMetrics,
+    FlushBandwidth,
     #[cfg(feature = "enterprise")]
     InternalMetrics,
     CalculateMetrics,
@@ -114,6 +120,14 @@ pub fn spawn_housekeeper(core: JmapInstance, mut rx: mpsc::Receiver<Event>) {
             // Calculate expensive metrics
             queue.schedule(Instant::now(), ActionClass::CalculateMetrics);
 
+            // Bandwidth accounting
+            if let Some(bandwidth) = &core_.network.bandwidth {
+                queue.schedule(
+                    Instant::now() + bandwidth.flush_interval,
+                    ActionClass::FlushBandwidth,
+                );
+            }
+
             // Add all ACME renewals to heap
             for provider in core_.tls.acme_providers.values() {
                 match core_.init_acme(provider).await {
@@ -129,6 +143,11 @@ pub fn spawn_housekeeper(core: JmapInstance, mut rx: mpsc::Receiver<Event>) {
                 };
             }
 
+            // OCSP stapling
+            if core_.tls.ocsp.enable {
+                queue.schedule(Instant::now(), ActionClass::Ocsp);
+            }
+
             // SPDX-SnippetBegin
             // SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
             // SPDX-License-Identifier: LicenseRef-SEL
@@ -183,6 +202,17 @@ pub fn spawn_housekeeper(core: JmapInstance, mut rx: mpsc::Receiver<Event>) {
                             _ => {}
                         }
 
+                        // Reload bandwidth accounting
+                        match &core_.network.bandwidth {
+                            Some(bandwidth) if !queue.has_action(&ActionClass::FlushBandwidth) => {
+                                queue.schedule(
+                                    Instant::now() + bandwidth.flush_interval,
+                                    ActionClass::FlushBandwidth,
+                                );
+                            }
+                            _ => {}
+                        }
+
                         // SPDX-SnippetBegin
                         // SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
                         // SPDX-License-Identifier: LicenseRef-SEL
@@ -212,6 +242,12 @@ pub fn spawn_housekeeper(core: JmapInstance, mut rx: mpsc::Receiver<Event>) {
                         }
                         // SPDX-SnippetEnd
 
+                        // Refresh OCSP responses for the reloaded certificates
+                        queue.remove_action(&ActionClass::Ocsp);
+                        if core_.tls.ocsp.enable {
+                            queue.schedule(Instant::now(), ActionClass::Ocsp);
+                        }
+
                         // Reload ACME certificates
                         tokio::spawn(async move {
                             for provider in core_.tls.acme_providers.values() {
@@ -242,6 +278,12 @@ pub fn spawn_housekeeper(core: JmapInstance, mut rx: mpsc::Receiver<Event>) {
                         queue.remove_action(&action);
                         queue.schedule(renew_at, action);
                     }
+                    Event::OcspReschedule { refresh_at } => {
+                        queue.remove_action(&ActionClass::Ocsp);
+                        if core.core.load().tls.ocsp.enable {
+                            queue.schedule(refresh_at, ActionClass::Ocsp);
+                        }
+                    }
                     Event::Purge(purge) => match purge {
                         PurgeType::Data(store) => {
                             // SPDX-SnippetBegin
@@ -327,6 +369,9 @@ pub fn spawn_housekeeper(core: JmapInstance, mut rx: mpsc::Receiver<Event>) {
                     Event::Exit => {
                         trc::event!(Housekeeper(HousekeeperEvent::Stop));
 
+                        // Write any bandwidth usage accumulated since the last flush
+                        core.core.load_full().flush_bandwidth().await;
+
                         return;
                     }
                 },
@@ -381,9 +426,33 @@ pub fn spawn_housekeeper(core: JmapInstance, mut rx: mpsc::Receiver<Event>) {
                                             })
                                             .await
                                             .ok();
+
+                                        // Staple the renewed certificates
+                                        inner
+                                            .housekeeper_tx
+                                            .send(Event::OcspReschedule {
+                                                refresh_at: Instant::now(),
+                                            })
+                                            .await
+                                            .ok();
                                     }
                                 });
                             }
+                            ActionClass::Ocsp => {
+                                let inner = core.jmap_inner.clone();
+                                let core = core_.clone();
+                                tokio::spawn(async move {
+                                    let refresh_in = core.refresh_ocsp_staples().await;
+
+                                    inner
+                                        .housekeeper_tx
+                                        .send(Event::OcspReschedule {
+                                            refresh_at: Instant::now() + refresh_in,
+                                        })
+                                        .await
+                                        .ok();
+                                });
+                            }
                             ActionClass::Account => {
                                 let jmap = JMAP::from(core.clone());
                                 tokio::spawn(async move {
@@ -404,6 +473,8 @@ pub fn spawn_housekeeper(core: JmapInstance, mut rx: mpsc::Receiver<Event>) {
                                     trc::event!(Housekeeper(HousekeeperEvent::PurgeSessions));
                                     inner.purge();
                                     core.security.access_tokens.cleanup();
+                                    core.security.oauth_tokens.cleanup();
+                                    core.security.purge_sessions();
                                 });
                                 queue.schedule(
                                     Instant::now()
@@ -464,11 +535,24 @@ pub fn spawn_housekeeper(core: JmapInstance, mut rx: mpsc::Receiver<Event>) {
                                     });
                                 }
                             }
+                            ActionClass::FlushBandwidth => {
+                                if let Some(bandwidth) = &core_.network.bandwidth {
+                                    queue.schedule(
+                                        Instant::now() + bandwidth.flush_interval,
+                                        ActionClass::FlushBandwidth,
+                                    );
+
+                                    let core = core_.clone();
+                                    tokio::spawn(async move {
+                                        core.flush_bandwidth().await;
+                                    });
+                                }
+                            }
                             ActionClass::CalculateMetrics => {
                                 // Calculate expensive metrics every 5 minutes
                                 queue.schedule(
                                     Instant::now() + Duration::from_secs(5 * 60),
-                                    ActionClass::OtelMetrics,
+                                    ActionClass::CalculateMetrics,
                                 );
 
                                 let update_other_metrics = if Instant::now() >= next_metric_update {
@@ -481,50 +565,43 @@ pub fn spawn_housekeeper(core: JmapInstance, mut rx: mpsc::Receiver<Event>) {
 
                                 let core = core_.clone();
                                 tokio::spawn(async move {
-                                    #[cfg(feature = "enterprise")]
-                                    if core.is_enterprise_edition() {
-                                        // Obtain queue size
-                                        match core.total_queued_messages().await {
-                                            Ok(total) => {
-                                                Collector::update_gauge(
-                                                    MetricType::QueueCount,
-                                                    total,
-                                                );
-                                            }
-                                            Err(err) => {
-                                                trc::error!(
-                                                    err.details("Failed to obtain queue size")
-                                                );
-                                            }
+                                    // Obtain queue size, the gauge is otherwise only
+                                    // adjusted as messages are queued and delivered
+                                    match core.total_queued_messages().await {
+                                        Ok(total) => {
+                                            Collector::update_gauge(MetricType::QueueCount, total);
+                                        }
+                                        Err(err) => {
+                                            trc::error!(err.details("Failed to obtain queue size"));
                                         }
                                     }
 
                                     if update_other_metrics {
-                                        match core.total_accounts().await {
-                                            Ok(total) => {
-                                                Collector::update_gauge(
-                                                    MetricType::UserCount,
-                                                    total,
-                                                );
-                                            }
-                                            Err(err) => {
-                                                trc::error!(
-                                                    err.details("Failed to obtain account count")
-                                                );
-                                            }
-                                        }
-
-                                        match core.total_domains().await {
-                                            Ok(total) => {
-                                                Collector::update_gauge(
-                                                    MetricType::DomainCount,
-                                                    total,
-                                                );
+                                        match core
+                                            .total_principals(
+                                                &[Type::Individual, Type::Domain],
+                                                None,
+                                            )
+                                            .await
+                                        {
+                                            Ok(totals) => {
+                                                for (typ, metric_type) in [
+                                                    (Type::Individual, MetricType::UserCount),
+                                                    (Type::Domain, MetricType::DomainCount),
+                                                ] {
+                                                    Collector::update_gauge(
+                                                        metric_type,
+                                                        totals
+                                                            .get(&typ)
+                                                            .copied()
+                                                            .unwrap_or_default(),
+                                                    );
+                                                }
                                             }
                                             Err(err) => {
-                                                trc::error!(
-                                                    err.details("Failed to obtain domain count")
-                                                );
+                                                trc::error!(err.details(
+                                                    "Failed to obtain account and domain counts"
+                                                ));
                                             }
                                         }
                                     }
@@ -605,10 +682,17 @@ pub fn spawn_housekeeper(core: JmapInstance, mut rx: mpsc::Receiver<Event>) {
 
                             #[cfg(feature = "enterprise")]
                             ActionClass::ValidateLicense => {
-                                match core_.reload().await {
+                                let result = match core_.storage.config.build_config("").await {
+                                    Ok(config) => {
+                                        common::manager::reload::reload_core(&core.core, config)
+                                            .await
+                                    }
+                                    Err(err) => Err(err),
+                                };
+                                match result {
                                     Ok(result) => {
-                                        if let Some(new_core) = result.new_core {
-                                            if let Some(enterprise) = &new_core.enterprise {
+                                        if result.applied {
+                                            if let Some(enterprise) = &core.core.load().enterprise {
                                                 queue.schedule(
                                                     Instant::now()
                                                         + enterprise.license.expires_in(),
@@ -616,9 +700,6 @@ pub fn spawn_housekeeper(core: JmapInstance, mut rx: mpsc::Receiver<Event>) {
                                                 );
                                             }
 
-                                            // Update core
-                                            core.core.store(new_core.into());
-
                                             // Increment version counter
                                             core.jmap_inner.increment_config_version();
                                         }
@@ -4,7 +4,10 @@
  * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
  */
 
-use common::{DeliveryResult, IngestMessage};
+use common::{
+    addresses::RecipientExpansion, auth::bandwidth::Transfer, DeliveryResult, IngestMessage,
+    RecipientResult,
+};
 use directory::Permission;
 use jmap_proto::types::{state::StateChange, type_state::DataType};
 use mail_parser::MessageParser;
@@ -12,12 +15,13 @@ use store::ahash::AHashMap;
 
 use crate::{
     email::ingest::{IngestEmail, IngestSource},
-    mailbox::INBOX_ID,
+    mailbox::{INBOX_ID, JUNK_ID},
+    services::scanner::ScannedMessage,
     JMAP,
 };
 
 impl JMAP {
-    pub async fn deliver_message(&self, message: IngestMessage) -> Vec<DeliveryResult> {
+    pub async fn deliver_message(&self, message: IngestMessage) -> Vec<RecipientResult> {
         // Read message
         let raw_message = match self
             .core
@@ -35,11 +39,12 @@ impl JMAP {
                     CausedBy = trc::location!()
                 );
 
-                return (0..message.recipients.len())
-                    .map(|_| DeliveryResult::TemporaryFailure {
+                return failed_delivery(
+                    &message,
+                    DeliveryResult::TemporaryFailure {
                         reason: "Blob not found.".into(),
-                    })
-                    .collect::<Vec<_>>();
+                    },
+                );
             }
             Err(err) => {
                 trc::error!(err
@@ -47,38 +52,78 @@ impl JMAP {
                     .span_id(message.session_id)
                     .caused_by(trc::location!()));
 
-                return (0..message.recipients.len())
-                    .map(|_| DeliveryResult::TemporaryFailure {
+                return failed_delivery(
+                    &message,
+                    DeliveryResult::TemporaryFailure {
                         reason: "Temporary I/O error.".into(),
-                    })
-                    .collect::<Vec<_>>();
+                    },
+                );
             }
         };
 
-        // Obtain the UIDs for each recipient
+        // Submit the message to the external spam scanner
+        let ScannedMessage {
+            raw_message,
+            is_spam,
+        } = match self.scan_message(&message, raw_message).await {
+            Ok(scanned_message) => scanned_message,
+            Err(result) => return failed_delivery(&message, result),
+        };
+
+        // Recipients over the limit are deferred and delivered on a later attempt
+        let max_recipients = self.core.jmap.delivery_max_recipients;
+        if message.recipients.len() > max_recipients {
+            trc::event!(
+                MessageIngest(trc::MessageIngestEvent::TooManyRecipients),
+                SpanId = message.session_id,
+                Limit = max_recipients,
+                Total = message.recipients.len(),
+            );
+        }
+
+        // Expand each recipient into the accounts it delivers to
         let mut recipients = Vec::with_capacity(message.recipients.len());
         let mut deliver_names = AHashMap::with_capacity(message.recipients.len());
-        for rcpt in &message.recipients {
-            match self
+        for (idx, rcpt) in message.recipients.iter().enumerate() {
+            if idx >= max_recipients {
+                recipients.push(Err(DeliveryResult::TemporaryFailure {
+                    reason: "Too many recipients.".into(),
+                }));
+                continue;
+            }
+
+            let failure = match self
                 .core
-                .email_to_ids(&self.core.storage.directory, rcpt, message.session_id)
+                .expand_recipient(&self.core.storage.directory, rcpt, message.session_id)
                 .await
             {
-                Ok(uids) => {
+                Ok(RecipientExpansion::Accounts(uids)) => {
                     for uid in &uids {
                         deliver_names.insert(*uid, (DeliveryResult::Success, rcpt));
                     }
-                    recipients.push(uids);
+                    recipients.push(Ok(uids));
+                    continue;
                 }
+                Ok(RecipientExpansion::Unknown) => DeliveryResult::PermanentFailure {
+                    code: [5, 1, 2],
+                    reason: "Mailbox does not exist.".into(),
+                },
+                Ok(RecipientExpansion::TooDeep) => DeliveryResult::PermanentFailure {
+                    code: [5, 4, 6],
+                    reason: "Too many levels of mailing list expansion.".into(),
+                },
                 Err(err) => {
                     trc::error!(err
                         .details("Failed to lookup recipient.")
                         .ctx(trc::Key::To, rcpt.to_string())
                         .span_id(message.session_id)
                         .caused_by(trc::location!()));
-                    recipients.push(vec![]);
+                    DeliveryResult::TemporaryFailure {
+                        reason: "Address lookup failed.".into(),
+                    }
                 }
-            }
+            };
+            recipients.push(Err(failure));
         }
 
         // Deliver to each recipient
@@ -104,6 +149,8 @@ impl JMAP {
                                 rcpt,
                                 message.session_id,
                                 active_script,
+                                is_spam,
+                                false,
                             )
                             .await
                         }
@@ -113,12 +160,16 @@ impl JMAP {
                                 raw_message: &raw_message,
                                 message: MessageParser::new().parse(&raw_message),
                                 resource: access_token.as_resource_token(),
-                                mailbox_ids: vec![INBOX_ID],
+                                mailbox_ids: vec![if is_spam { JUNK_ID } else { INBOX_ID }],
                                 keywords: vec![],
                                 received_at: None,
+                                received_at_offset: 0,
                                 source: IngestSource::Smtp,
+                                delivered_to: Some(rcpt.as_str()),
                                 encrypt: self.core.jmap.encrypt,
+                                dedup: None,
                                 session_id: message.session_id,
+                                dry_run: false,
                             })
                             .await
                         }
@@ -131,6 +182,9 @@ impl JMAP {
 
             match result {
                 Ok(ingested_message) => {
+                    self.core
+                        .record_bandwidth(*uid, Transfer::In, message.message_size);
+
                     // Notify state change
                     if ingested_message.change_id != u64::MAX {
                         self.broadcast_state_change(
@@ -191,19 +245,31 @@ impl JMAP {
             }
         }
 
-        // Build result
-        recipients
-            .into_iter()
-            .map(|names| {
-                match names.len() {
+        // Build result, one entry per recipient in the same order they were received
+        let results: Vec<RecipientResult> = message
+            .recipients
+            .iter()
+            .zip(recipients)
+            .map(|(rcpt, names)| {
+                let names = match names {
+                    Ok(names) => names,
+                    Err(result) => {
+                        return RecipientResult {
+                            recipient: rcpt.to_string(),
+                            result,
+                        }
+                    }
+                };
+                let result = match names.len() {
                     1 => {
                         // Delivery to single recipient
                         deliver_names.get(&names[0]).unwrap().0.clone()
                     }
                     0 => {
-                        // Something went wrong
-                        DeliveryResult::TemporaryFailure {
-                            reason: "Address lookup failed.".into(),
+                        // Lists without members
+                        DeliveryResult::PermanentFailure {
+                            code: [5, 1, 2],
+                            reason: "Mailing list has no members.".into(),
                         }
                     }
                     _ => {
@@ -231,8 +297,37 @@ impl JMAP {
                             }
                         }
                     }
+                };
+
+                RecipientResult {
+                    recipient: rcpt.to_string(),
+                    result,
                 }
             })
-            .collect()
+            .collect();
+
+        // Copy the message once to the mirror target if it was delivered to a mirrored
+        // recipient, failures do not affect delivery
+        if let Some(mirror) = &self.core.jmap.ingest_mirror {
+            if results.iter().any(|rcpt| {
+                matches!(rcpt.result, DeliveryResult::Success) && mirror.matches(&rcpt.recipient)
+            }) {
+                self.mirror_message(mirror, &raw_message, message.session_id)
+                    .await;
+            }
+        }
+
+        results
     }
 }
+
+fn failed_delivery(message: &IngestMessage, result: DeliveryResult) -> Vec<RecipientResult> {
+    message
+        .recipients
+        .iter()
+        .map(|rcpt| RecipientResult {
+            recipient: rcpt.to_string(),
+            result: result.clone(),
+        })
+        .collect()
+}
@@ -9,4 +9,5 @@ pub mod gossip;
 pub mod housekeeper;
 pub mod index;
 pub mod ingest;
+pub mod scanner;
 pub mod state;
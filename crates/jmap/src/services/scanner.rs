@@ -0,0 +1,244 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use std::time::Instant;
+
+use common::{
+    config::jmap::settings::SpamScanner, DeliveryResult, HttpLimitResponse, IngestMessage,
+};
+use serde::Deserialize;
+use store::ahash::AHashMap;
+
+use crate::JMAP;
+
+const MAX_RESPONSE_SIZE: usize = 1024 * 1024;
+
+pub struct ScannedMessage {
+    pub raw_message: Vec<u8>,
+    pub is_spam: bool,
+}
+
+// Response of the rspamd /checkv2 endpoint
+#[derive(Debug, Deserialize)]
+struct ScanResponse {
+    action: ScanAction,
+    #[serde(default)]
+    score: Option<f64>,
+    #[serde(default)]
+    messages: ScanMessages,
+    #[serde(default)]
+    milter: ScanMilter,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct ScanMessages {
+    #[serde(default)]
+    smtp_message: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct ScanMilter {
+    #[serde(default)]
+    add_headers: AHashMap<String, MilterHeader>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum MilterHeader {
+    Value(String),
+    Object { value: String },
+    List(Vec<MilterHeader>),
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
+enum ScanAction {
+    #[serde(rename = "no action")]
+    NoAction,
+    #[serde(rename = "greylist")]
+    Greylist,
+    #[serde(rename = "add header")]
+    AddHeader,
+    #[serde(rename = "rewrite subject")]
+    RewriteSubject,
+    #[serde(rename = "soft reject")]
+    SoftReject,
+    #[serde(rename = "reject")]
+    Reject,
+}
+
+impl JMAP {
+    // Submits the message to the external spam scanner and returns it with the
+    // headers added by the scanner, or the result to report to all recipients.
+    pub async fn scan_message(
+        &self,
+        message: &IngestMessage,
+        raw_message: Vec<u8>,
+    ) -> Result<ScannedMessage, DeliveryResult> {
+        let scanner = if let Some(scanner) = &self.core.jmap.spam_scanner {
+            scanner
+        } else {
+            return Ok(ScannedMessage {
+                raw_message,
+                is_spam: false,
+            });
+        };
+
+        let time = Instant::now();
+        let response = match send_scan_request(scanner, message, &raw_message).await {
+            Ok(response) => response,
+            Err(err) => {
+                trc::event!(
+                    MessageIngest(trc::MessageIngestEvent::ScanError),
+                    SpanId = message.session_id,
+                    Url = scanner.url.clone(),
+                    Reason = err,
+                    Elapsed = time.elapsed(),
+                );
+
+                return if scanner.fail_open {
+                    Ok(ScannedMessage {
+                        raw_message,
+                        is_spam: false,
+                    })
+                } else {
+                    Err(DeliveryResult::TemporaryFailure {
+                        reason: "Spam filter temporarily unavailable.".into(),
+                    })
+                };
+            }
+        };
+
+        trc::event!(
+            MessageIngest(trc::MessageIngestEvent::ScanVerdict),
+            SpanId = message.session_id,
+            Result = response.action.as_str(),
+            Details = response.score,
+            Reason = response.messages.smtp_message.clone(),
+            Elapsed = time.elapsed(),
+        );
+
+        match response.action {
+            ScanAction::Reject => Err(DeliveryResult::PermanentFailure {
+                code: [5, 7, 1],
+                reason: response
+                    .messages
+                    .smtp_message
+                    .unwrap_or_else(|| "Message rejected by spam filter.".to_string())
+                    .into(),
+            }),
+            ScanAction::Greylist | ScanAction::SoftReject => {
+                Err(DeliveryResult::TemporaryFailure {
+                    reason: response
+                        .messages
+                        .smtp_message
+                        .unwrap_or_else(|| "Greylisted, please try again later.".to_string())
+                        .into(),
+                })
+            }
+            ScanAction::NoAction | ScanAction::AddHeader | ScanAction::RewriteSubject => {
+                let mut headers = String::new();
+                for (name, header) in &response.milter.add_headers {
+                    header.write_to(name, &mut headers);
+                }
+
+                let raw_message = if !headers.is_empty() {
+                    let mut new_message = Vec::with_capacity(headers.len() + raw_message.len());
+                    new_message.extend_from_slice(headers.as_bytes());
+                    new_message.extend_from_slice(&raw_message);
+                    new_message
+                } else {
+                    raw_message
+                };
+
+                Ok(ScannedMessage {
+                    raw_message,
+                    is_spam: response.action != ScanAction::NoAction,
+                })
+            }
+        }
+    }
+}
+
+impl MilterHeader {
+    fn write_to(&self, name: &str, headers: &mut String) {
+        match self {
+            MilterHeader::Value(value) | MilterHeader::Object { value } => {
+                if is_valid_header(name, value) {
+                    headers.push_str(name);
+                    headers.push_str(": ");
+                    headers.push_str(value);
+                    headers.push_str("\r\n");
+                }
+            }
+            MilterHeader::List(values) => {
+                for value in values {
+                    value.write_to(name, headers);
+                }
+            }
+        }
+    }
+}
+
+impl ScanAction {
+    fn as_str(&self) -> &'static str {
+        match self {
+            ScanAction::NoAction => "no action",
+            ScanAction::Greylist => "greylist",
+            ScanAction::AddHeader => "add header",
+            ScanAction::RewriteSubject => "rewrite subject",
+            ScanAction::SoftReject => "soft reject",
+            ScanAction::Reject => "reject",
+        }
+    }
+}
+
+fn is_valid_header(name: &str, value: &str) -> bool {
+    !name.is_empty()
+        && name.bytes().all(|ch| ch.is_ascii_graphic() && ch != b':')
+        && !value.contains(['\r', '\n'])
+}
+
+async fn send_scan_request(
+    scanner: &SpamScanner,
+    message: &IngestMessage,
+    raw_message: &[u8],
+) -> Result<ScanResponse, String> {
+    let mut request = scanner
+        .client
+        .post(&scanner.url)
+        .headers(scanner.headers.clone())
+        .header("Queue-Id", message.session_id.to_string());
+    if !message.sender_address.is_empty() {
+        request = request.header("From", &message.sender_address);
+    }
+    for rcpt in &message.recipients {
+        request = request.header("Rcpt", rcpt);
+    }
+
+    let response = request
+        .body(raw_message.to_vec())
+        .send()
+        .await
+        .map_err(|err| format!("Scan request failed: {err}"))?;
+
+    if response.status().is_success() {
+        serde_json::from_slice(
+            response
+                .bytes_with_limit(MAX_RESPONSE_SIZE)
+                .await
+                .map_err(|err| format!("Failed to parse scan response: {}", err))?
+                .ok_or_else(|| "Scan response too large".to_string())?
+                .as_ref(),
+        )
+        .map_err(|err| format!("Failed to parse scan response: {}", err))
+    } else {
+        Err(format!(
+            "Scan request failed with code {}: {}",
+            response.status().as_u16(),
+            response.status().canonical_reason().unwrap_or("Unknown")
+        ))
+    }
+}
@@ -4,11 +4,14 @@
  * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
  */
 
-use std::time::{Duration, Instant, SystemTime};
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant, SystemTime},
+};
 
 use common::IPC_CHANNEL_BUFFER;
 use jmap_proto::types::{id::Id, state::StateChange, type_state::DataType};
-use store::ahash::AHashMap;
+use store::ahash::{AHashMap, AHashSet};
 use tokio::sync::mpsc;
 use trc::ServerEvent;
 use utils::map::bitmap::Bitmap;
@@ -25,9 +28,18 @@ pub enum Event {
         types: Bitmap<DataType>,
         tx: mpsc::Sender<StateChange>,
     },
+    SubscribeEventSource {
+        account_id: u32,
+        types: Bitmap<DataType>,
+        last_event_id: Option<u64>,
+        tx: mpsc::Sender<EventSourceChange>,
+    },
     Publish {
         state_change: StateChange,
     },
+    PublishBatched {
+        state_change: StateChange,
+    },
     UpdateSharedAccounts {
         account_id: u32,
     },
@@ -47,13 +59,46 @@ struct Subscriber {
 #[derive(Debug)]
 pub enum SubscriberType {
     Ipc { tx: mpsc::Sender<StateChange> },
+    EventSource { tx: mpsc::Sender<EventSourceChange> },
     Push { expires: u64 },
 }
 
+#[derive(Debug)]
+pub enum EventSourceChange {
+    Change { id: u64, state_change: StateChange },
+    // The changes following the last event seen by the client are no longer
+    // available, the client has to be sent the current state of all types.
+    Resync { id: u64 },
+}
+
+#[derive(Debug)]
+struct PendingChange {
+    due: Instant,
+    types: Vec<(DataType, u64)>,
+}
+
+// Recent state changes of each account, kept so that EventSource clients
+// can resume from the last event they received after reconnecting
+#[derive(Debug)]
+struct ChangeLog {
+    last_id: u64,
+    max_size: usize,
+    accounts: AHashMap<u32, ChangeBuffer>,
+}
+
+#[derive(Debug)]
+struct ChangeBuffer {
+    // All changes with an id above this one are in the buffer
+    floor: u64,
+    changes: VecDeque<(u64, StateChange)>,
+    last_active: Instant,
+}
+
 impl Subscriber {
     fn is_valid(&self, current_time: u64) -> bool {
         match &self.subscription {
             SubscriberType::Ipc { tx } => !tx.is_closed(),
+            SubscriberType::EventSource { tx } => !tx.is_closed(),
             SubscriberType::Push { expires } => expires > &current_time,
         }
     }
@@ -83,13 +128,76 @@ pub fn spawn_state_manager(core: JmapInstance, mut change_rx: mpsc::Receiver<Eve
         let mut shared_accounts_map: AHashMap<u32, AHashMap<u32, Bitmap<DataType>>> =
             AHashMap::default();
 
+        let mut pending_changes: AHashMap<u32, PendingChange> = AHashMap::default();
+        let mut change_log = ChangeLog::new();
+
         let mut last_purge = Instant::now();
 
-        while let Some(event) = change_rx.recv().await {
+        loop {
             let mut purge_needed = last_purge.elapsed() >= PURGE_EVERY;
+            change_log.max_size = core.core.load().jmap.event_source_buffer;
+
+            // Publish coalesced state changes that are due
+            if !pending_changes.is_empty() {
+                let now = Instant::now();
+                let due_account_ids = pending_changes
+                    .iter()
+                    .filter(|(_, pending)| pending.due <= now)
+                    .map(|(account_id, _)| *account_id)
+                    .collect::<Vec<_>>();
+
+                for account_id in due_account_ids {
+                    if let Some(pending) = pending_changes.remove(&account_id) {
+                        purge_needed |= publish_state_change(
+                            StateChange {
+                                account_id,
+                                types: pending.types,
+                            },
+                            &mut change_log,
+                            &subscribers,
+                            &shared_accounts_map,
+                            &push_tx,
+                        )
+                        .await;
+                    }
+                }
+            }
+
+            // Wait for the next event or until the next coalesced change is due
+            let event = if let Some(due) = pending_changes.values().map(|pending| pending.due).min()
+            {
+                match tokio::time::timeout(
+                    due.saturating_duration_since(Instant::now()),
+                    change_rx.recv(),
+                )
+                .await
+                {
+                    Ok(Some(event)) => event,
+                    Ok(None) => break,
+                    Err(_) => continue,
+                }
+            } else if let Some(event) = change_rx.recv().await {
+                event
+            } else {
+                break;
+            };
 
             match event {
                 Event::Stop => {
+                    for (account_id, pending) in pending_changes.drain() {
+                        publish_state_change(
+                            StateChange {
+                                account_id,
+                                types: pending.types,
+                            },
+                            &mut change_log,
+                            &subscribers,
+                            &shared_accounts_map,
+                            &push_tx,
+                        )
+                        .await;
+                    }
+
                     if push_tx.send(crate::push::Event::Reset).await.is_err() {
                         trc::event!(
                             Server(ServerEvent::ThreadError),
@@ -143,6 +251,7 @@ pub fn spawn_state_manager(core: JmapInstance, mut change_rx: mpsc::Receiver<Eve
                         Vec::with_capacity(acl.member_of.len() + 1 + acl.access_to.len());
                     for member_id in [acl.primary_id].iter().chain(acl.member_of.iter()) {
                         shared_account_ids.push(*member_id);
+                        change_log.watch(*member_id);
                         shared_accounts_map
                             .entry(*member_id)
                             .or_insert_with(AHashMap::new)
@@ -161,6 +270,7 @@ pub fn spawn_state_manager(core: JmapInstance, mut change_rx: mpsc::Receiver<Eve
                         }
                         if !types.is_empty() {
                             shared_account_ids.push(*shared_account_id);
+                            change_log.watch(*shared_account_id);
                             shared_accounts_map
                                 .entry(*shared_account_id)
                                 .or_insert_with(AHashMap::new)
@@ -185,86 +295,123 @@ pub fn spawn_state_manager(core: JmapInstance, mut change_rx: mpsc::Receiver<Eve
                             },
                         );
                 }
-                Event::Publish { state_change } => {
-                    if let Some(shared_accounts) = shared_accounts_map.get(&state_change.account_id)
-                    {
-                        let current_time = SystemTime::now()
-                            .duration_since(SystemTime::UNIX_EPOCH)
-                            .map(|d| d.as_secs())
-                            .unwrap_or(0);
-                        let mut push_ids = Vec::new();
-
-                        for (owner_account_id, allowed_types) in shared_accounts {
-                            if let Some(subscribers) = subscribers.get(owner_account_id) {
-                                for (subscriber_id, subscriber) in subscribers {
-                                    let mut types = Vec::with_capacity(state_change.types.len());
-                                    for (state_type, change_id) in &state_change.types {
-                                        if subscriber.types.contains(*state_type)
-                                            && allowed_types.contains(*state_type)
-                                        {
-                                            types.push((*state_type, *change_id));
-                                        }
-                                    }
-                                    if !types.is_empty() {
-                                        match &subscriber.subscription {
-                                            SubscriberType::Ipc { tx } if !tx.is_closed() => {
-                                                let subscriber_tx = tx.clone();
-                                                let state_change = state_change.clone();
-
-                                                tokio::spawn(async move {
-                                                    // Timeout after 500ms in case there is a blocked client
-                                                    if subscriber_tx
-                                                        .send_timeout(
-                                                            StateChange {
-                                                                account_id: state_change.account_id,
-                                                                types,
-                                                            },
-                                                            SEND_TIMEOUT,
-                                                        )
-                                                        .await
-                                                        .is_err()
-                                                    {
-                                                        trc::event!(
-                                                            Server(ServerEvent::ThreadError),
-                                                            Details = "Error sending state change to subscriber.",
-                                                            CausedBy = trc::location!()
-                                                        );
-                                                    }
-                                                });
-                                            }
-                                            SubscriberType::Push { expires }
-                                                if expires > &current_time =>
-                                            {
-                                                push_ids.push(Id::from_parts(
-                                                    *owner_account_id,
-                                                    (*subscriber_id).into(),
-                                                ));
-                                            }
-                                            _ => {
-                                                purge_needed = true;
-                                            }
-                                        }
-                                    }
-                                }
+                Event::SubscribeEventSource {
+                    account_id,
+                    types,
+                    last_event_id,
+                    tx,
+                } => {
+                    // Replay the changes missed by a reconnecting client
+                    if let Some(last_event_id) = last_event_id {
+                        let account_ids = shared_accounts
+                            .get(&account_id)
+                            .map(|ids| ids.as_slice())
+                            .unwrap_or_default();
+                        let messages = match change_log.replay(
+                            account_ids,
+                            last_event_id,
+                            |shared_account_id, state_type| {
+                                types.contains(state_type)
+                                    && shared_accounts_map
+                                        .get(&shared_account_id)
+                                        .and_then(|owners| owners.get(&account_id))
+                                        .is_some_and(|allowed| allowed.contains(state_type))
+                            },
+                        ) {
+                            Some(changes) => changes
+                                .into_iter()
+                                .map(|(id, state_change)| EventSourceChange::Change {
+                                    id,
+                                    state_change,
+                                })
+                                .collect::<Vec<_>>(),
+                            None => vec![EventSourceChange::Resync {
+                                id: change_log.last_id,
+                            }],
+                        };
+
+                        for message in messages {
+                            if tx.try_send(message).is_err() {
+                                trc::event!(
+                                    Server(ServerEvent::ThreadError),
+                                    Details = "Error replaying state changes to subscriber.",
+                                    CausedBy = trc::location!()
+                                );
+                                break;
                             }
                         }
+                    }
 
-                        if !push_ids.is_empty()
-                            && push_tx
-                                .send(crate::push::Event::Push {
-                                    ids: push_ids,
-                                    state_change,
-                                })
-                                .await
-                                .is_err()
-                        {
-                            trc::event!(
-                                Server(ServerEvent::ThreadError),
-                                Details = "Error sending push updates.",
-                                CausedBy = trc::location!()
-                            );
+                    subscribers
+                        .entry(account_id)
+                        .or_insert_with(AHashMap::default)
+                        .insert(
+                            SubscriberId::Ipc(rand::random()),
+                            Subscriber {
+                                types,
+                                subscription: SubscriberType::EventSource { tx },
+                            },
+                        );
+                }
+                Event::Publish { mut state_change } => {
+                    // Include any pending changes for the same types, so a newer
+                    // change id is never followed by an older one
+                    if let Some(pending) = pending_changes.get_mut(&state_change.account_id) {
+                        pending.types.retain(|(pending_type, pending_id)| {
+                            if let Some((_, change_id)) = state_change
+                                .types
+                                .iter_mut()
+                                .find(|(state_type, _)| state_type == pending_type)
+                            {
+                                *change_id = (*change_id).max(*pending_id);
+                                false
+                            } else {
+                                true
+                            }
+                        });
+                        if pending.types.is_empty() {
+                            pending_changes.remove(&state_change.account_id);
                         }
                     }
+
+                    purge_needed |= publish_state_change(
+                        state_change,
+                        &mut change_log,
+                        &subscribers,
+                        &shared_accounts_map,
+                        &push_tx,
+                    )
+                    .await;
+                }
+                Event::PublishBatched { state_change } => {
+                    if let Some(debounce) = core.core.load().jmap.state_change_debounce {
+                        let pending = pending_changes
+                            .entry(state_change.account_id)
+                            .or_insert_with(|| PendingChange {
+                                due: Instant::now() + debounce,
+                                types: Vec::with_capacity(state_change.types.len()),
+                            });
+                        for (state_type, change_id) in state_change.types {
+                            if let Some((_, pending_id)) = pending
+                                .types
+                                .iter_mut()
+                                .find(|(pending_type, _)| *pending_type == state_type)
+                            {
+                                *pending_id = (*pending_id).max(change_id);
+                            } else {
+                                pending.types.push((state_type, change_id));
+                            }
+                        }
+                    } else {
+                        purge_needed |= publish_state_change(
+                            state_change,
+                            &mut change_log,
+                            &subscribers,
+                            &shared_accounts_map,
+                            &push_tx,
+                        )
+                        .await;
+                    }
                 }
                 Event::UpdateSubscriptions {
                     account_id,
@@ -382,12 +529,230 @@ pub fn spawn_state_manager(core: JmapInstance, mut change_rx: mpsc::Receiver<Eve
                     subscribers.remove(&remove_account_id);
                 }
 
+                let watched_ids = subscribers
+                    .keys()
+                    .flat_map(|account_id| {
+                        shared_accounts
+                            .get(account_id)
+                            .map(|ids| ids.as_slice())
+                            .unwrap_or_default()
+                            .iter()
+                            .chain([account_id])
+                    })
+                    .copied()
+                    .collect::<AHashSet<_>>();
+                change_log.purge(&watched_ids);
+
                 last_purge = Instant::now();
             }
         }
     });
 }
 
+async fn publish_state_change(
+    state_change: StateChange,
+    change_log: &mut ChangeLog,
+    subscribers: &AHashMap<u32, AHashMap<SubscriberId, Subscriber>>,
+    shared_accounts_map: &AHashMap<u32, AHashMap<u32, Bitmap<DataType>>>,
+    push_tx: &mpsc::Sender<crate::push::Event>,
+) -> bool {
+    let mut purge_needed = false;
+    let event_id = change_log.record(&state_change);
+
+    if let Some(shared_accounts) = shared_accounts_map.get(&state_change.account_id) {
+        let current_time = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        let mut push_ids = Vec::new();
+
+        for (owner_account_id, allowed_types) in shared_accounts {
+            if let Some(subscribers) = subscribers.get(owner_account_id) {
+                for (subscriber_id, subscriber) in subscribers {
+                    let mut types = Vec::with_capacity(state_change.types.len());
+                    for (state_type, change_id) in &state_change.types {
+                        if subscriber.types.contains(*state_type)
+                            && allowed_types.contains(*state_type)
+                        {
+                            types.push((*state_type, *change_id));
+                        }
+                    }
+                    if !types.is_empty() {
+                        match &subscriber.subscription {
+                            SubscriberType::Ipc { tx } if !tx.is_closed() => {
+                                let subscriber_tx = tx.clone();
+                                let state_change = state_change.clone();
+
+                                tokio::spawn(async move {
+                                    // Timeout after 500ms in case there is a blocked client
+                                    if subscriber_tx
+                                        .send_timeout(
+                                            StateChange {
+                                                account_id: state_change.account_id,
+                                                types,
+                                            },
+                                            SEND_TIMEOUT,
+                                        )
+                                        .await
+                                        .is_err()
+                                    {
+                                        trc::event!(
+                                            Server(ServerEvent::ThreadError),
+                                            Details = "Error sending state change to subscriber.",
+                                            CausedBy = trc::location!()
+                                        );
+                                    }
+                                });
+                            }
+                            SubscriberType::EventSource { tx } if !tx.is_closed() => {
+                                let subscriber_tx = tx.clone();
+                                let account_id = state_change.account_id;
+
+                                tokio::spawn(async move {
+                                    if subscriber_tx
+                                        .send_timeout(
+                                            EventSourceChange::Change {
+                                                id: event_id,
+                                                state_change: StateChange { account_id, types },
+                                            },
+                                            SEND_TIMEOUT,
+                                        )
+                                        .await
+                                        .is_err()
+                                    {
+                                        trc::event!(
+                                            Server(ServerEvent::ThreadError),
+                                            Details = "Error sending state change to subscriber.",
+                                            CausedBy = trc::location!()
+                                        );
+                                    }
+                                });
+                            }
+                            SubscriberType::Push { expires } if expires > &current_time => {
+                                push_ids.push(Id::from_parts(
+                                    *owner_account_id,
+                                    (*subscriber_id).into(),
+                                ));
+                            }
+                            _ => {
+                                purge_needed = true;
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        if !push_ids.is_empty()
+            && push_tx
+                .send(crate::push::Event::Push {
+                    ids: push_ids,
+                    state_change,
+                })
+                .await
+                .is_err()
+        {
+            trc::event!(
+                Server(ServerEvent::ThreadError),
+                Details = "Error sending push updates.",
+                CausedBy = trc::location!()
+            );
+        }
+    }
+
+    purge_needed
+}
+
+impl ChangeLog {
+    fn new() -> Self {
+        // Event ids start at the current time, so ids issued before a restart
+        // are always older than any of the buffered changes
+        ChangeLog {
+            last_id: SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .map(|d| d.as_micros() as u64)
+                .unwrap_or(0),
+            max_size: 0,
+            accounts: AHashMap::default(),
+        }
+    }
+
+    fn watch(&mut self, account_id: u32) {
+        let last_id = self.last_id;
+        self.accounts
+            .entry(account_id)
+            .or_insert_with(|| ChangeBuffer {
+                floor: last_id,
+                changes: VecDeque::new(),
+                last_active: Instant::now(),
+            })
+            .last_active = Instant::now();
+    }
+
+    fn record(&mut self, state_change: &StateChange) -> u64 {
+        self.last_id += 1;
+        let id = self.last_id;
+
+        if let Some(buffer) = self.accounts.get_mut(&state_change.account_id) {
+            buffer.last_active = Instant::now();
+            buffer.changes.push_back((id, state_change.clone()));
+            while buffer.changes.len() > self.max_size {
+                if let Some((evicted_id, _)) = buffer.changes.pop_front() {
+                    buffer.floor = evicted_id;
+                }
+            }
+        }
+
+        id
+    }
+
+    // Drops the buffers of accounts that are no longer watched by any subscriber
+    // and have not been active for a while
+    fn purge(&mut self, watched_ids: &AHashSet<u32>) {
+        self.accounts.retain(|account_id, buffer| {
+            watched_ids.contains(account_id) || buffer.last_active.elapsed() < PURGE_EVERY
+        });
+    }
+
+    // Returns the changes that followed the given event, merged by account,
+    // or None if some of them have already been evicted
+    fn replay(
+        &self,
+        account_ids: &[u32],
+        last_event_id: u64,
+        is_allowed: impl Fn(u32, DataType) -> bool,
+    ) -> Option<Vec<(u64, StateChange)>> {
+        let mut replay = Vec::new();
+
+        for account_id in account_ids {
+            let buffer = self.accounts.get(account_id)?;
+            if last_event_id < buffer.floor {
+                return None;
+            }
+
+            let mut last_id = 0;
+            let mut state_change = StateChange::new(*account_id);
+            for (id, change) in &buffer.changes {
+                if *id > last_event_id {
+                    for (state_type, change_id) in &change.types {
+                        if is_allowed(*account_id, *state_type) {
+                            state_change = state_change.with_change(*state_type, *change_id);
+                            last_id = *id;
+                        }
+                    }
+                }
+            }
+            if !state_change.types.is_empty() {
+                replay.push((last_id, state_change));
+            }
+        }
+
+        replay.sort_unstable_by_key(|(id, _)| *id);
+
+        Some(replay)
+    }
+}
+
 impl JMAP {
     pub async fn subscribe_state_manager(
         &self,
@@ -415,6 +780,34 @@ impl JMAP {
         Ok(change_rx)
     }
 
+    pub async fn subscribe_event_source(
+        &self,
+        account_id: u32,
+        types: Bitmap<DataType>,
+        last_event_id: Option<u64>,
+    ) -> trc::Result<mpsc::Receiver<EventSourceChange>> {
+        let (change_tx, change_rx) = mpsc::channel::<EventSourceChange>(IPC_CHANNEL_BUFFER);
+        let state_tx = self.inner.state_tx.clone();
+
+        for event in [
+            Event::UpdateSharedAccounts { account_id },
+            Event::SubscribeEventSource {
+                account_id,
+                types,
+                last_event_id,
+                tx: change_tx,
+            },
+        ] {
+            state_tx.send(event).await.map_err(|err| {
+                trc::EventType::Server(trc::ServerEvent::ThreadError)
+                    .reason(err)
+                    .caused_by(trc::location!())
+            })?;
+        }
+
+        Ok(change_rx)
+    }
+
     pub async fn broadcast_state_change(&self, state_change: StateChange) -> bool {
         match self
             .inner
@@ -436,6 +829,29 @@ impl JMAP {
         }
     }
 
+    // Coalesces the change with others for the same account received within
+    // the configured debounce window, only the highest change id is published.
+    pub async fn broadcast_state_change_batched(&self, state_change: StateChange) -> bool {
+        match self
+            .inner
+            .state_tx
+            .clone()
+            .send(Event::PublishBatched { state_change })
+            .await
+        {
+            Ok(_) => true,
+            Err(_) => {
+                trc::event!(
+                    Server(ServerEvent::ThreadError),
+                    Details = "Error sending state change.",
+                    CausedBy = trc::location!()
+                );
+
+                false
+            }
+        }
+    }
+
     pub async fn update_push_subscriptions(&self, account_id: u32) -> bool {
         let push_subs = match self.fetch_push_subscriptions(account_id).await {
             Ok(push_subs) => push_subs,
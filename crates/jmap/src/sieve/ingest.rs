@@ -10,7 +10,7 @@ use common::{auth::AccessToken, listener::stream::NullIo};
 use directory::{backend::internal::PrincipalField, QueryBy};
 use jmap_proto::types::{collection::Collection, id::Id, keyword::Keyword, property::Property};
 use mail_parser::MessageParser;
-use sieve::{Envelope, Event, Input, Mailbox, Recipient};
+use sieve::{Envelope, Event, Input, Mailbox, MatchAs, Recipient};
 use smtp::core::{Session, SessionAddress};
 use store::{
     ahash::AHashSet,
@@ -19,8 +19,8 @@ use store::{
 use trc::{AddContext, SieveEvent};
 
 use crate::{
-    email::ingest::{IngestEmail, IngestSource, IngestedEmail},
-    mailbox::{INBOX_ID, TRASH_ID},
+    email::ingest::{IngestDisposition, IngestEmail, IngestSource, IngestedEmail},
+    mailbox::{INBOX_ID, JUNK_ID, TRASH_ID},
     sieve::SeenIdHash,
     JMAP,
 };
@@ -43,6 +43,8 @@ impl JMAP {
         envelope_to: &str,
         session_id: u64,
         mut active_script: ActiveScript,
+        is_spam: bool,
+        dry_run: bool,
     ) -> trc::Result<IngestedEmail> {
         // Parse message
         let message = if let Some(message) = MessageParser::new().parse(raw_message) {
@@ -57,10 +59,16 @@ impl JMAP {
 
         // Obtain mailboxIds
         let account_id = access_token.primary_id;
-        let mailbox_ids = self
-            .mailbox_get_or_create(account_id)
-            .await
-            .caused_by(trc::location!())?;
+        let mailbox_ids = if !dry_run {
+            self.mailbox_get_or_create(account_id)
+                .await
+                .caused_by(trc::location!())?
+        } else {
+            self.get_document_ids(account_id, Collection::Mailbox)
+                .await
+                .caused_by(trc::location!())?
+                .unwrap_or_default()
+        };
 
         // Create Sieve instance
         let mut instance = self.core.sieve.untrusted_runtime.filter_parsed(message);
@@ -90,6 +98,7 @@ impl JMAP {
         instance.set_envelope(Envelope::To, envelope_to);
 
         let mut input = Input::script(active_script.script_name, active_script.script.clone());
+        let mut num_lookups = 0;
 
         let mut do_discard = false;
         let mut do_deliver = false;
@@ -108,7 +117,9 @@ impl JMAP {
             blob_id: Default::default(),
             size: raw_message.len(),
             imap_uids: Vec::new(),
+            disposition: dry_run.then(IngestDisposition::default),
         };
+        let mut actions = Vec::new();
 
         while let Some(event) = instance.run(input) {
             match event {
@@ -218,15 +229,18 @@ impl JMAP {
                         input = seen_id.into();
                     }
                     Event::Discard => {
+                        actions.push("discard".to_string());
                         do_discard = true;
                         input = true.into();
                     }
                     Event::Reject { reason, .. } => {
+                        actions.push(format!("reject {reason:?}"));
                         reject_reason = reason.into();
                         do_discard = true;
                         input = true.into();
                     }
                     Event::Keep { flags, message_id } => {
+                        actions.push("keep".to_string());
                         if let Some(message) = messages.get_mut(message_id) {
                             message.flags = flags.into_iter().map(Keyword::from).collect();
                             if !message.file_into.contains(&INBOX_ID) {
@@ -251,6 +265,7 @@ impl JMAP {
                         create,
                         message_id,
                     } => {
+                        actions.push(format!("fileinto {folder:?}"));
                         let mut target_id = u32::MAX;
 
                         // Find mailbox by Id
@@ -291,6 +306,12 @@ impl JMAP {
                                 {
                                     target_id = document_id;
                                 }
+                            } else if dry_run {
+                                if let Ok(Some(document_id)) =
+                                    self.mailbox_get_by_name(account_id, &folder).await
+                                {
+                                    target_id = document_id;
+                                }
                             } else if let Ok(Some((document_id, changes))) =
                                 self.mailbox_create_path(account_id, &folder).await
                             {
@@ -340,7 +361,16 @@ impl JMAP {
                                 }
                             };
 
-                            if message.raw_message.len() <= self.core.jmap.mail_max_size {
+                            if dry_run {
+                                actions.push(format!(
+                                    "send {}",
+                                    recipients
+                                        .iter()
+                                        .map(|r| r.address_lcase.as_str())
+                                        .collect::<Vec<_>>()
+                                        .join(", ")
+                                ));
+                            } else if message.raw_message.len() <= self.core.jmap.mail_max_size {
                                 trc::event!(
                                     Sieve(SieveEvent::SendMessage),
                                     From = mail_from.clone(),
@@ -385,10 +415,72 @@ impl JMAP {
                             continue;
                         }
                     }
-                    Event::ListContains { .. }
-                    | Event::Function { .. }
-                    | Event::Notify { .. }
-                    | Event::SetEnvelope { .. } => {
+                    Event::ListContains {
+                        lists,
+                        values,
+                        match_as,
+                    } => {
+                        // Absent keys and unavailable lists never match
+                        input = false.into();
+                        'outer: for list in lists {
+                            if !self.core.sieve.untrusted_lookups.contains(&list) {
+                                trc::event!(
+                                    Sieve(SieveEvent::ListNotFound),
+                                    SpanId = session_id,
+                                    AccountId = account_id,
+                                    Details = list,
+                                );
+                                continue;
+                            }
+
+                            let store = if let Some(store) =
+                                self.core.try_get_lookup_store(&list, session_id)
+                            {
+                                store
+                            } else {
+                                continue;
+                            };
+
+                            for value in &values {
+                                if num_lookups >= self.core.sieve.untrusted_max_lookups {
+                                    trc::event!(
+                                        Sieve(SieveEvent::QuotaExceeded),
+                                        SpanId = session_id,
+                                        AccountId = account_id,
+                                        Details = "Lookup limit reached.",
+                                        Limit = self.core.sieve.untrusted_max_lookups,
+                                    );
+                                    break 'outer;
+                                }
+                                num_lookups += 1;
+
+                                match store
+                                    .key_exists(
+                                        if !matches!(match_as, MatchAs::Lowercase) {
+                                            value.clone()
+                                        } else {
+                                            value.to_lowercase()
+                                        }
+                                        .into_bytes(),
+                                    )
+                                    .await
+                                {
+                                    Ok(true) => {
+                                        input = true.into();
+                                        break 'outer;
+                                    }
+                                    Ok(false) => {}
+                                    Err(err) => {
+                                        trc::error!(err
+                                            .span_id(session_id)
+                                            .caused_by(trc::location!())
+                                            .details("Failed to query lookup store"));
+                                    }
+                                }
+                            }
+                        }
+                    }
+                    Event::Function { .. } | Event::Notify { .. } | Event::SetEnvelope { .. } => {
                         // Not allowed
                         input = false.into();
                     }
@@ -427,8 +519,13 @@ impl JMAP {
         // Deliver messages
         let mut last_temp_error = None;
         let mut has_delivered = false;
-        for (message_id, sieve_message) in messages.into_iter().enumerate() {
+        for (message_id, mut sieve_message) in messages.into_iter().enumerate() {
             if !sieve_message.file_into.is_empty() {
+                // File into Junk when the spam scanner flagged the message
+                if is_spam && sieve_message.file_into == [INBOX_ID] {
+                    sieve_message.file_into[0] = JUNK_ID;
+                }
+
                 // Parse message if needed
                 let message = if message_id == 0 && !instance.has_message_changed() {
                     instance.take_message()
@@ -455,15 +552,30 @@ impl JMAP {
                         mailbox_ids: sieve_message.file_into,
                         keywords: sieve_message.flags,
                         received_at: None,
+                        received_at_offset: 0,
                         source: IngestSource::Smtp,
+                        delivered_to: Some(envelope_to),
                         encrypt: self.core.jmap.encrypt,
+                        dedup: None,
                         session_id,
+                        dry_run,
                     })
                     .await
                 {
                     Ok(ingested_message_) => {
                         has_delivered = true;
-                        ingested_message = ingested_message_;
+                        if let Some(disposition) = &mut ingested_message.disposition {
+                            // Dry runs collect the outcome of every generated message
+                            if let Some(disposition_) = ingested_message_.disposition {
+                                disposition.mailbox_ids.extend(disposition_.mailbox_ids);
+                                disposition.keywords.extend(disposition_.keywords);
+                                disposition.is_spam |= disposition_.is_spam;
+                                disposition.spam_score =
+                                    disposition.spam_score.or(disposition_.spam_score);
+                            }
+                        } else {
+                            ingested_message = ingested_message_;
+                        }
                     }
                     Err(err) => {
                         last_temp_error = err.into();
@@ -472,6 +584,15 @@ impl JMAP {
             }
         }
 
+        // Dry runs report the Sieve actions instead of rejecting the message
+        if let Some(disposition) = &mut ingested_message.disposition {
+            disposition.sieve_actions = actions;
+            return match last_temp_error {
+                Some(err) if !has_delivered => Err(err),
+                _ => Ok(ingested_message),
+            };
+        }
+
         // Save new ids script changes
         if !new_ids.is_empty() || active_script.seen_ids.has_changes {
             active_script.seen_ids.ids.extend(new_ids);
@@ -494,12 +615,12 @@ impl JMAP {
                     .ctx(trc::Key::Code, 571)
                     .ctx(trc::Key::Reason, reject_reason),
             )
-        } else if has_delivered || last_temp_error.is_none() {
-            Ok(ingested_message)
         } else {
-            // There were problems during delivery
-            #[allow(clippy::unnecessary_unwrap)]
-            Err(last_temp_error.unwrap())
+            match last_temp_error {
+                // There were problems during delivery
+                Some(err) if !has_delivered => Err(err),
+                _ => Ok(ingested_message),
+            }
         }
     }
 }
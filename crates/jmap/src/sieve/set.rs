@@ -77,7 +77,7 @@ impl JMAP {
         // Process creates
         let mut changes = ChangeLogBuilder::new();
         for (id, object) in request.unwrap_create() {
-            if sieve_ids.len() as usize <= self.core.jmap.sieve_max_scripts {
+            if (sieve_ids.len() as usize) < self.core.jmap.sieve_max_scripts {
                 match self
                     .sieve_set_item(object, None, &ctx, session.session_id)
                     .await?
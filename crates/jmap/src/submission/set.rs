@@ -29,12 +29,16 @@ use jmap_proto::{
     },
 };
 use mail_parser::{HeaderName, HeaderValue};
-use smtp::core::{Session, SessionData, State};
+use smtp::core::{throttle::SubmissionStatus, Session, SessionData, State};
 use smtp_proto::{request::parser::Rfc5321Parser, MailFrom, RcptTo};
 use store::write::{assert::HashedValue, log::ChangeLogBuilder, now, BatchBuilder, Bincode};
 use utils::map::vec_map::VecMap;
 
-use crate::{email::metadata::MessageMetadata, identity::set::sanitize_email, JMAP};
+use crate::{
+    email::metadata::MessageMetadata,
+    identity::set::{is_allowed_sender, sanitize_email},
+    JMAP,
+};
 
 pub static SCHEMA: &[IndexProperty] = &[
     IndexProperty::new(Property::UndoStatus).index_as(IndexAs::Text {
@@ -434,6 +438,17 @@ impl JMAP {
                 .with_description("Identity not found.")));
         };
 
+        // Aliases can be removed after an identity was created
+        let allowed_senders = self.identity_allowed_senders(account_id).await?;
+        if allowed_senders.as_ref().map_or(false, |senders| {
+            !is_allowed_sender(senders, &identity_mail_from)
+        }) {
+            return Ok(Err(SetError::new(SetErrorType::ForbiddenFrom)
+                .with_description(
+                    "Identity email address is not configured for this account.",
+                )));
+        }
+
         // Make sure the envelope address matches the identity email address
         let mail_from = if let Some(mail_from) = mail_from {
             if !mail_from.address.eq_ignore_ascii_case(&identity_mail_from) {
@@ -479,6 +494,26 @@ impl JMAP {
                 .with_description("Email not found.")));
         };
 
+        // Make sure the account is allowed to use the From header addresses
+        if let Some(senders) = &allowed_senders {
+            for header in &metadata.contents.parts[0].headers {
+                if let (HeaderName::From, HeaderValue::Address(addr)) =
+                    (&header.name, &header.value)
+                {
+                    for address in addr.iter() {
+                        if let Some(address) = address.address() {
+                            if !is_allowed_sender(senders, address) {
+                                return Ok(Err(SetError::new(SetErrorType::ForbiddenFrom)
+                                    .with_description(format!(
+                                        "Not allowed to send from address {address}."
+                                    ))));
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
         // Add recipients to envelope if missing
         if rcpt_to.is_empty() {
             let mut envelope_values = Vec::new();
@@ -551,6 +586,31 @@ impl JMAP {
                     .with_description("Blob for email not found.")));
             };
 
+        // Enforce outbound submission limits
+        match self
+            .smtp
+            .submission_status(account_id, rcpt_to.len(), 0)
+            .await
+        {
+            SubmissionStatus::Allowed => (),
+            SubmissionStatus::RateLimited => {
+                return Ok(Err(SetError::new(SetErrorType::RateLimit)
+                    .with_description(
+                        "Sending rate limit exceeded, try again later.",
+                    )));
+            }
+            SubmissionStatus::TooManyRecipients => {
+                return Ok(Err(SetError::new(SetErrorType::TooManyRecipients)
+                    .with_description(
+                        "Too many recipients for the sending rate limit.",
+                    )));
+            }
+            SubmissionStatus::Forbidden => {
+                return Ok(Err(SetError::new(SetErrorType::ForbiddenToSend)
+                    .with_description("Sending is suspended for this account.")));
+            }
+        }
+
         // Begin local SMTP session
         let mut session =
             Session::<NullIo>::local(self.smtp.clone(), instance.clone(), SessionData::default());
@@ -0,0 +1,371 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use jmap_proto::types::{
+    collection::Collection, id::Id, property::Property, state::StateChange, type_state::DataType,
+};
+use mail_parser::{parsers::fields::thread::thread_name, HeaderName, HeaderValue};
+use serde::Serialize;
+use store::{
+    ahash::AHashMap,
+    write::{
+        key::DeserializeBigEndian, log::ChangeLogBuilder, BatchBuilder, Bincode, F_BITMAP, F_CLEAR,
+        F_INDEX, F_VALUE,
+    },
+    IndexKeyPrefix, IterateParams, U32_LEN,
+};
+use trc::AddContext;
+use utils::map::vec_map::VecMap;
+
+use crate::{
+    email::{
+        index::{TrimTextValue, VisitValues, MAX_ID_LENGTH, MAX_SORT_FIELD_LENGTH},
+        metadata::MessageMetadata,
+    },
+    JMAP,
+};
+
+const REBUILD_BATCH_SIZE: usize = 500;
+
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ThreadRebuild {
+    pub messages: u64,
+    pub threads_merged: u64,
+    pub messages_moved: u64,
+}
+
+impl JMAP {
+    // Re-links the messages of an account (or a single mailbox) by their
+    // Message-ID, In-Reply-To and References headers, merging any threads
+    // that should have been a single one. Threads are only merged, never split,
+    // so running it again on an already consistent account is a no-op.
+    pub async fn rebuild_threads(
+        &self,
+        account_id: u32,
+        mailbox_id: Option<u32>,
+    ) -> trc::Result<ThreadRebuild> {
+        let mut result = ThreadRebuild::default();
+        let document_ids = if let Some(mailbox_id) = mailbox_id {
+            self.get_tag(
+                account_id,
+                Collection::Email,
+                Property::MailboxIds,
+                mailbox_id,
+            )
+            .await?
+        } else {
+            self.get_document_ids(account_id, Collection::Email).await?
+        }
+        .unwrap_or_default();
+        if document_ids.is_empty() {
+            return Ok(result);
+        }
+
+        // Obtain the current thread assignments, bypassing the cache
+        let thread_ids = self
+            .get_properties::<u32, _, _>(
+                account_id,
+                Collection::Email,
+                &document_ids,
+                Property::ThreadId,
+            )
+            .await
+            .caused_by(trc::location!())?;
+        result.messages = thread_ids.len() as u64;
+
+        // Obtain the indexed references so stale values are removed when re-indexing
+        let mut indexed_values = AHashMap::<u32, Vec<(u8, String)>>::new();
+        for field in [
+            u8::from(Property::MessageId),
+            u8::from(Property::References),
+        ] {
+            self.core
+                .storage
+                .data
+                .iterate(
+                    IterateParams::new(
+                        IndexKeyPrefix {
+                            account_id,
+                            collection: Collection::Email.into(),
+                            field,
+                        },
+                        IndexKeyPrefix {
+                            account_id,
+                            collection: Collection::Email.into(),
+                            field: field + 1,
+                        },
+                    )
+                    .ascending()
+                    .no_values(),
+                    |key, _| {
+                        let id_pos = key.len() - U32_LEN;
+                        let document_id = key.deserialize_be_u32(id_pos)?;
+
+                        if document_ids.contains(document_id) {
+                            let value =
+                                key.get(IndexKeyPrefix::len()..id_pos).ok_or_else(|| {
+                                    trc::Error::corrupted_key(key, None, trc::location!())
+                                })?;
+                            if let Ok(value) = std::str::from_utf8(value) {
+                                indexed_values
+                                    .entry(document_id)
+                                    .or_default()
+                                    .push((field, value.to_string()));
+                            }
+                        }
+                        Ok(true)
+                    },
+                )
+                .await
+                .caused_by(trc::location!())?;
+        }
+
+        // Group messages sharing a thread or a thread name and reference,
+        // re-indexing their references along the way
+        let mut groups = DisjointSet::new(thread_ids.len());
+        let mut thread_keys = AHashMap::with_capacity(thread_ids.len());
+        let mut reference_keys = AHashMap::new();
+        let mut batch = BatchBuilder::new();
+        let mut batch_size = 0;
+        batch
+            .with_account_id(account_id)
+            .with_collection(Collection::Email);
+        for (pos, (document_id, thread_id)) in thread_ids.iter().enumerate() {
+            groups.union(*thread_keys.entry(*thread_id).or_insert(pos), pos);
+
+            let metadata = if let Some(metadata) = self
+                .get_property::<Bincode<MessageMetadata>>(
+                    account_id,
+                    Collection::Email,
+                    *document_id,
+                    Property::BodyStructure,
+                )
+                .await
+                .caused_by(trc::location!())?
+            {
+                metadata.inner
+            } else {
+                continue;
+            };
+            let headers = if let Some(part) = metadata.contents.parts.first() {
+                &part.headers
+            } else {
+                continue;
+            };
+
+            let mut message_ids = Vec::with_capacity(1);
+            let mut references = Vec::with_capacity(5);
+            let mut subject = "";
+            for header in headers.iter().rev() {
+                match &header.name {
+                    HeaderName::MessageId => header.value.visit_text(|id| {
+                        if !id.is_empty() && id.len() < MAX_ID_LENGTH {
+                            message_ids.push(id);
+                            references.push(id);
+                        }
+                    }),
+                    HeaderName::InReplyTo
+                    | HeaderName::References
+                    | HeaderName::ResentMessageId => {
+                        header.value.visit_text(|id| {
+                            if !id.is_empty() && id.len() < MAX_ID_LENGTH {
+                                references.push(id);
+                            }
+                        });
+                    }
+                    HeaderName::Subject if subject.is_empty() => {
+                        subject = thread_name(match &header.value {
+                            HeaderValue::Text(text) => text.as_ref(),
+                            HeaderValue::TextList(list) if !list.is_empty() => {
+                                list.first().unwrap().as_ref()
+                            }
+                            _ => "",
+                        })
+                        .trim_text(MAX_SORT_FIELD_LENGTH);
+                    }
+                    _ => (),
+                }
+            }
+
+            // Clear the previously indexed values before adding the current ones
+            let indexed_values = indexed_values.remove(document_id).unwrap_or_default();
+            if !indexed_values.is_empty() {
+                batch.update_document(*document_id);
+                for (field, value) in indexed_values {
+                    batch.value(field, value, F_INDEX | F_CLEAR);
+                    batch_size += 1;
+                }
+            }
+
+            if !references.is_empty() {
+                if subject.is_empty() {
+                    subject = "!";
+                }
+
+                batch.update_document(*document_id);
+                for message_id in message_ids {
+                    batch.value(Property::MessageId, message_id, F_INDEX);
+                }
+                for reference in references {
+                    batch.value(Property::References, reference, F_INDEX);
+                    let key = (subject.to_string(), reference.to_string());
+                    groups.union(*reference_keys.entry(key).or_insert(pos), pos);
+                    batch_size += 1;
+                }
+            }
+
+            // Flush after clearing stale values as well, messages without references
+            // would otherwise grow the batch past its limit
+            if batch_size >= REBUILD_BATCH_SIZE {
+                self.core
+                    .storage
+                    .data
+                    .write(batch.build_batch())
+                    .await
+                    .caused_by(trc::location!())?;
+                batch
+                    .with_account_id(account_id)
+                    .with_collection(Collection::Email);
+                batch_size = 0;
+            }
+        }
+        if batch_size > 0 {
+            self.core
+                .storage
+                .data
+                .write(batch.build_batch())
+                .await
+                .caused_by(trc::location!())?;
+        }
+
+        // Pick the most common thread of each group, favouring the oldest one
+        let mut group_threads = AHashMap::<usize, VecMap<u32, u32>>::new();
+        for (pos, (_, thread_id)) in thread_ids.iter().enumerate() {
+            *group_threads
+                .entry(groups.find(pos))
+                .or_default()
+                .get_mut_or_insert(*thread_id) += 1;
+        }
+        let mut merges = Vec::new();
+        for threads in group_threads.into_values() {
+            if threads.len() > 1 {
+                let (&target_id, _) = threads
+                    .iter()
+                    .max_by(|(a_id, a_count), (b_id, b_count)| {
+                        a_count.cmp(b_count).then_with(|| b_id.cmp(a_id))
+                    })
+                    .unwrap();
+                for &thread_id in threads.keys() {
+                    if thread_id != target_id {
+                        merges.push((thread_id, target_id));
+                    }
+                }
+            }
+        }
+        result.threads_merged = merges.len() as u64;
+
+        // Move messages to their new threads
+        let mut last_change_id = None;
+        let mut merges = merges.into_iter().peekable();
+        while merges.peek().is_some() {
+            let change_id = self
+                .assign_change_id(account_id)
+                .await
+                .caused_by(trc::location!())?;
+            let mut changes = ChangeLogBuilder::with_change_id(change_id);
+            let mut batch = BatchBuilder::new();
+            let mut batch_size = 0;
+            batch.with_account_id(account_id);
+
+            while batch_size < REBUILD_BATCH_SIZE {
+                let (old_thread_id, thread_id) = if let Some(merge) = merges.next() {
+                    merge
+                } else {
+                    break;
+                };
+
+                batch
+                    .with_collection(Collection::Thread)
+                    .delete_document(old_thread_id);
+                changes.log_delete(Collection::Thread, old_thread_id);
+
+                batch.with_collection(Collection::Email);
+                for document_id in self
+                    .get_tag(
+                        account_id,
+                        Collection::Email,
+                        Property::ThreadId,
+                        old_thread_id,
+                    )
+                    .await?
+                    .unwrap_or_default()
+                {
+                    batch
+                        .update_document(document_id)
+                        .assert_value(Property::ThreadId, old_thread_id)
+                        .value(Property::ThreadId, old_thread_id, F_BITMAP | F_CLEAR)
+                        .value(Property::ThreadId, thread_id, F_VALUE | F_BITMAP);
+                    changes.log_move(
+                        Collection::Email,
+                        Id::from_parts(old_thread_id, document_id),
+                        Id::from_parts(thread_id, document_id),
+                    );
+                    batch_size += 1;
+                    result.messages_moved += 1;
+                }
+            }
+
+            batch.custom(changes);
+            self.core
+                .storage
+                .data
+                .write(batch.build_batch())
+                .await
+                .caused_by(trc::location!())?;
+            last_change_id = Some(change_id);
+        }
+
+        // Notify a single state change once all batches are written
+        if let Some(change_id) = last_change_id {
+            self.broadcast_state_change(
+                StateChange::new(account_id)
+                    .with_change(DataType::Email, change_id)
+                    .with_change(DataType::Thread, change_id),
+            )
+            .await;
+        }
+
+        Ok(result)
+    }
+}
+
+struct DisjointSet {
+    parents: Vec<usize>,
+}
+
+impl DisjointSet {
+    fn new(len: usize) -> Self {
+        DisjointSet {
+            parents: (0..len).collect(),
+        }
+    }
+
+    fn find(&mut self, mut item: usize) -> usize {
+        while self.parents[item] != item {
+            self.parents[item] = self.parents[self.parents[item]];
+            item = self.parents[item];
+        }
+        item
+    }
+
+    fn union(&mut self, a: usize, b: usize) {
+        let (a, b) = (self.find(a), self.find(b));
+        if a != b {
+            self.parents[b.max(a)] = a.min(b);
+        }
+    }
+}
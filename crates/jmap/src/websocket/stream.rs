@@ -72,6 +72,9 @@ impl JMAP {
 
         let mut changes = WebSocketStateChange::new(None);
         let mut change_types: Bitmap<DataType> = Bitmap::new();
+        let mut revocation_rx = self
+            .core
+            .subscribe_session_revocation(access_token.primary_id());
 
         loop {
             tokio::select! {
@@ -165,6 +168,16 @@ impl JMAP {
                         }
                     }
                 }
+                _ = revocation_rx.changed() => {
+                    trc::event!(
+                        Jmap(JmapEvent::WebsocketStop),
+                        SpanId = session.session_id,
+                        Reason = "Session revoked"
+                    );
+
+                    let _ = stream.close(None).await;
+                    break;
+                }
                 state_change = change_rx.recv() => {
                     if let Some(state_change) = state_change {
                         if !change_types.is_empty() && state_change
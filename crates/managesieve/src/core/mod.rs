@@ -278,6 +278,9 @@ impl SerializeResponse for trc::Error {
                 trc::EventType::Store(_) => Some(ResponseCode::TryLater.as_str()),
                 trc::EventType::Limit(trc::LimitEvent::Quota) => Some(ResponseCode::Quota.as_str()),
                 trc::EventType::Limit(_) => Some(ResponseCode::TryLater.as_str()),
+                trc::EventType::Auth(trc::AuthEvent::DirectoryUnavailable) => {
+                    Some(ResponseCode::TryLater.as_str())
+                }
                 _ => None,
             })
         {
@@ -67,9 +67,9 @@ impl<T: SessionStream> Session<T> {
             tokio::select! {
                 result = tokio::time::timeout(
                     if !matches!(self.state, State::NotAuthenticated {..}) {
-                        self.jmap.core.imap.timeout_auth
+                        self.jmap.core.managesieve.timeout_auth
                     } else {
-                        self.jmap.core.imap.timeout_unauth
+                        self.jmap.core.managesieve.timeout_unauth
                     },
                     self.read(&mut buf)) => {
                         match result {
@@ -80,7 +80,7 @@ impl<T: SessionStream> Session<T> {
                                         SessionResult::UpgradeTls => {
                                             return true;
                                         }
-                                        SessionResult::Close => {
+                                        SessionResult::Close | SessionResult::UpgradeCompression => {
                                             break;
                                         }
                                     }
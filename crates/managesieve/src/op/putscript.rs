@@ -59,21 +59,6 @@ impl<T: SessionStream> Session<T> {
             .await
             .caused_by(trc::location!())?;
 
-        if self
-            .jmap
-            .get_document_ids(account_id, Collection::SieveScript)
-            .await
-            .caused_by(trc::location!())?
-            .map(|ids| ids.len() as usize)
-            .unwrap_or(0)
-            > self.jmap.core.jmap.sieve_max_scripts
-        {
-            return Err(trc::ManageSieveEvent::Error
-                .into_err()
-                .details("Too many scripts.")
-                .code(ResponseCode::QuotaMaxScripts));
-        }
-
         // Compile script
         match self
             .jmap
@@ -99,8 +84,25 @@ impl<T: SessionStream> Session<T> {
             }
         }
 
-        // Validate name
-        if let Some(document_id) = self.validate_name(account_id, &name).await? {
+        // Validate name, only new scripts count towards the script limit
+        let document_id = self.validate_name(account_id, &name).await?;
+        if document_id.is_none()
+            && self
+                .jmap
+                .get_document_ids(account_id, Collection::SieveScript)
+                .await
+                .caused_by(trc::location!())?
+                .map(|ids| ids.len() as usize)
+                .unwrap_or(0)
+                >= self.jmap.core.jmap.sieve_max_scripts
+        {
+            return Err(trc::ManageSieveEvent::Error
+                .into_err()
+                .details("Too many scripts.")
+                .code(ResponseCode::QuotaMaxScripts));
+        }
+
+        if let Some(document_id) = document_id {
             // Obtain script values
             let script = self
                 .jmap
@@ -4,10 +4,13 @@
  * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
  */
 
-use common::listener::{limiter::ConcurrencyLimiter, SessionStream};
+use common::{
+    config::server::ServerProtocol,
+    listener::{limiter::ConcurrencyLimiter, SessionStream},
+};
 use directory::Permission;
 use imap::op::authenticate::{decode_challenge_oauth, decode_challenge_plain};
-use jmap::auth::rate_limit::ConcurrencyLimiters;
+use jmap::auth::{oauth::OAuthScope, rate_limit::ConcurrencyLimiters};
 use mail_parser::decoders::base64::base64_decode;
 use mail_send::Credentials;
 use std::sync::Arc;
@@ -66,16 +69,28 @@ impl<T: SessionStream> Session<T> {
         let access_token = match credentials {
             Credentials::Plain { username, secret } | Credentials::XOauth2 { username, secret } => {
                 self.jmap
-                    .authenticate_plain(&username, &secret, self.remote_addr, self.session_id)
+                    .authenticate_plain(
+                        &username,
+                        &secret,
+                        self.remote_addr,
+                        ServerProtocol::Pop3,
+                        self.session_id,
+                    )
                     .await
             }
             Credentials::OAuthBearer { token } => {
                 match self
                     .jmap
-                    .validate_access_token("access_token", &token)
+                    .core
+                    .authenticate_access_token(&token, self.remote_addr, self.session_id)
                     .await
                 {
-                    Ok((account_id, _, _)) => self.jmap.core.get_access_token(account_id).await,
+                    Ok(token_info) if token_info.scopes.contains(OAuthScope::Pop3) => {
+                        self.jmap.core.get_access_token(token_info.account_id).await
+                    }
+                    Ok(_) => Err(trc::AuthEvent::Failed
+                        .into_err()
+                        .details("Access token scope does not cover POP3.")),
                     Err(err) => Err(err),
                 }
             }
@@ -6,7 +6,7 @@
 
 use std::time::Instant;
 
-use common::listener::SessionStream;
+use common::{auth::bandwidth::Transfer, listener::SessionStream};
 use directory::Permission;
 use jmap::email::metadata::MessageMetadata;
 use jmap_proto::types::{collection::Collection, property::Property};
@@ -42,6 +42,10 @@ impl<T: SessionStream> Session<T> {
                     .await
                     .caused_by(trc::location!())?
                 {
+                    self.jmap
+                        .core
+                        .record_bandwidth(mailbox.account_id, Transfer::Out, bytes.len());
+
                     trc::event!(
                         Pop3(trc::Pop3Event::Fetch),
                         SpanId = self.session_id,
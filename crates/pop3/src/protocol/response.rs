@@ -155,8 +155,12 @@ impl SerializeResponse for trc::Error {
         let message = self
             .value_as_str(trc::Key::Details)
             .unwrap_or_else(|| self.as_ref().message());
-        let mut buf = Vec::with_capacity(message.len() + 6);
+        let mut buf = Vec::with_capacity(message.len() + 17);
         buf.extend_from_slice(b"-ERR ");
+        if self.matches(trc::EventType::Auth(trc::AuthEvent::DirectoryUnavailable)) {
+            // Temporary failures are flagged as such (RFC 3206)
+            buf.extend_from_slice(b"[SYS/TEMP] ");
+        }
         buf.extend_from_slice(message.as_bytes());
         buf.extend_from_slice(b"\r\n");
         buf
@@ -71,9 +71,9 @@ impl<T: SessionStream> Session<T> {
             tokio::select! {
                 result = tokio::time::timeout(
                     if !matches!(self.state, State::NotAuthenticated {..}) {
-                        self.jmap.core.imap.timeout_auth
+                        self.jmap.core.pop3.timeout_auth
                     } else {
-                        self.jmap.core.imap.timeout_unauth
+                        self.jmap.core.pop3.timeout_unauth
                     },
                     self.stream.read(&mut buf)) => {
                     match result {
@@ -84,7 +84,7 @@ impl<T: SessionStream> Session<T> {
                                     SessionResult::UpgradeTls => {
                                         return true;
                                     }
-                                    SessionResult::Close => {
+                                    SessionResult::Close | SessionResult::UpgradeCompression => {
                                         break;
                                     }
                                 }
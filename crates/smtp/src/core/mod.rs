@@ -30,7 +30,7 @@ use tokio::{
     sync::mpsc,
 };
 use tokio_rustls::TlsConnector;
-use utils::snowflake::SnowflakeIdGenerator;
+use utils::{map::ttl_dashmap::TtlDashMap, snowflake::SnowflakeIdGenerator};
 
 use crate::{
     inbound::auth::SaslToken,
@@ -78,6 +78,7 @@ pub struct SMTP {
 pub struct Inner {
     pub session_throttle: DashMap<ThrottleKey, ConcurrencyLimiter, ThrottleKeyHasherBuilder>,
     pub queue_throttle: DashMap<ThrottleKey, ConcurrencyLimiter, ThrottleKeyHasherBuilder>,
+    pub domain_concurrency: DashMap<String, ConcurrencyLimiter>,
     pub queue_tx: mpsc::Sender<queue::Event>,
     pub report_tx: mpsc::Sender<reporting::Event>,
     pub queue_id_gen: SnowflakeIdGenerator,
@@ -85,6 +86,7 @@ pub struct Inner {
     pub connectors: TlsConnectors,
     pub ipc: Ipc,
     pub script_cache: ScriptCache,
+    pub reputation_cache: TtlDashMap<(IpAddr, String), Option<String>>,
 }
 
 pub struct TlsConnectors {
@@ -128,14 +130,17 @@ pub struct SessionData {
     pub rcpt_to: Vec<SessionAddress>,
     pub rcpt_errors: usize,
     pub message: Vec<u8>,
+    pub bdat_in_progress: bool,
 
     pub authenticated_as: String,
+    pub authenticated_id: Option<u32>,
     pub authenticated_emails: Vec<String>,
     pub auth_errors: usize,
 
     pub priority: i16,
     pub delivery_by: i64,
     pub future_release: u64,
+    pub declared_size: usize,
 
     pub valid_until: Instant,
     pub bytes_left: usize,
@@ -160,6 +165,7 @@ pub struct SessionAddress {
 pub struct SessionParameters {
     // Global parameters
     pub timeout: Duration,
+    pub pipelining_limit: usize,
 
     // Ehlo parameters
     pub ehlo_require: bool,
@@ -207,16 +213,19 @@ impl SessionData {
             mail_from: None,
             rcpt_to: Vec::new(),
             authenticated_as: String::new(),
+            authenticated_id: None,
             authenticated_emails: Vec::new(),
             priority: 0,
             valid_until: Instant::now(),
             rcpt_errors: 0,
             message: Vec::with_capacity(0),
+            bdat_in_progress: false,
             auth_errors: 0,
             messages_sent: 0,
             bytes_left: 0,
             delivery_by: 0,
             future_release: 0,
+            declared_size: 0,
             iprev: None,
             spf_ehlo: None,
             spf_mail_from: None,
@@ -273,6 +282,7 @@ static SIEVE: LazyLock<Arc<ServerInstance>> = LazyLock::new(|| {
     Arc::new(ServerInstance {
         id: "sieve".to_string(),
         protocol: common::config::server::ServerProtocol::Lmtp,
+        role: None,
         acceptor: common::listener::TcpAcceptor::Plain,
         limiter: ConcurrencyLimiter::new(0),
         shutdown_rx: tokio::sync::watch::channel(false).1,
@@ -292,6 +302,7 @@ impl Session<common::listener::stream::NullIo> {
             data,
             params: SessionParameters {
                 timeout: Default::default(),
+                pipelining_limit: Default::default(),
                 ehlo_require: Default::default(),
                 ehlo_reject_non_fqdn: Default::default(),
                 auth_directory: Default::default(),
@@ -363,12 +374,15 @@ impl SessionData {
             rcpt_to,
             rcpt_errors: 0,
             message,
+            bdat_in_progress: false,
             authenticated_as: "local".into(),
+            authenticated_id: None,
             authenticated_emails: vec![],
             auth_errors: 0,
             priority: 0,
             delivery_by: 0,
             future_release: 0,
+            declared_size: 0,
             valid_until: Instant::now(),
             bytes_left: 0,
             messages_sent: 0,
@@ -405,6 +419,7 @@ impl Default for Inner {
         Self {
             session_throttle: Default::default(),
             queue_throttle: Default::default(),
+            domain_concurrency: Default::default(),
             queue_tx: mpsc::channel(1).0,
             report_tx: mpsc::channel(1).0,
             queue_id_gen: Default::default(),
@@ -417,6 +432,7 @@ impl Default for Inner {
                 delivery_tx: mpsc::channel(1).0,
             },
             script_cache: Default::default(),
+            reputation_cache: Default::default(),
         }
     }
 }
@@ -6,7 +6,10 @@
 
 use std::time::Duration;
 
-use common::{config::smtp::auth::VerifyStrategy, listener::SessionStream};
+use common::{
+    config::{server::ListenerRole, smtp::auth::VerifyStrategy},
+    listener::SessionStream,
+};
 
 use super::Session;
 
@@ -32,6 +35,12 @@ impl<T: SessionStream> Session<T> {
             .eval_if(&c.timeout, self, self.data.session_id)
             .await
             .unwrap_or_else(|| Duration::from_secs(5 * 60));
+        self.params.pipelining_limit = self
+            .core
+            .core
+            .eval_if(&c.pipelining_limit, self, self.data.session_id)
+            .await
+            .unwrap_or(250);
         self.params.spf_ehlo = self
             .core
             .core
@@ -112,6 +121,17 @@ impl<T: SessionStream> Session<T> {
             .await
             .unwrap_or(true);
 
+        // Listener roles override the configured authentication settings
+        match self.instance.role {
+            Some(ListenerRole::Submission) => {
+                self.params.auth_require = true;
+            }
+            Some(ListenerRole::Inbound) => {
+                self.params.auth_directory = None;
+            }
+            None => (),
+        }
+
         // VRFY/EXPN parameters
         let ec = &self.core.core.smtp.session.extensions;
         self.params.can_expn = self
@@ -143,16 +163,17 @@ impl<T: SessionStream> Session<T> {
             .eval_if(&ec.vrfy, self, self.data.session_id)
             .await
             .unwrap_or(false);
-        self.params.auth_match_sender = self
-            .core
-            .core
-            .eval_if(
-                &self.core.core.smtp.session.auth.must_match_sender,
-                self,
-                self.data.session_id,
-            )
-            .await
-            .unwrap_or(true);
+        self.params.auth_match_sender = self.instance.role == Some(ListenerRole::Submission)
+            || self
+                .core
+                .core
+                .eval_if(
+                    &self.core.core.smtp.session.auth.must_match_sender,
+                    self,
+                    self.data.session_id,
+                )
+                .await
+                .unwrap_or(true);
     }
 
     pub async fn eval_rcpt_params(&mut self) {
@@ -10,8 +10,15 @@ use common::{
     listener::{limiter::ConcurrencyLimiter, SessionStream},
 };
 use dashmap::mapref::entry::Entry;
-use trc::SmtpEvent;
-use utils::config::Rate;
+use directory::{
+    backend::internal::{
+        manage::{ManageDirectory, UpdatePrincipal},
+        PrincipalField, PrincipalUpdate, PrincipalValue,
+    },
+    Permission,
+};
+use trc::{SecurityEvent, SmtpEvent};
+use utils::{config::Rate, map::ttl_dashmap::TtlMap};
 
 use std::{
     hash::{BuildHasher, Hash, Hasher},
@@ -307,10 +314,168 @@ impl<T: SessionStream> Session<T> {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SubmissionStatus {
+    Allowed,
+    RateLimited,
+    TooManyRecipients,
+    Forbidden,
+}
+
 impl SMTP {
+    pub async fn submission_status(
+        &self,
+        account_id: u32,
+        num_recipients: usize,
+        session_id: u64,
+    ) -> SubmissionStatus {
+        let limits = &self.core.smtp.session.submission;
+        if !limits.is_enabled() {
+            return SubmissionStatus::Allowed;
+        }
+
+        let access_token = match self.core.get_cached_access_token(account_id).await {
+            Ok(access_token) => access_token,
+            Err(err) => {
+                trc::error!(err
+                    .span_id(session_id)
+                    .caused_by(trc::location!())
+                    .details("Failed to obtain access token"));
+                return SubmissionStatus::Allowed;
+            }
+        };
+        if limits.allowlist.contains(&access_token.name.to_lowercase()) {
+            return SubmissionStatus::Allowed;
+        } else if !access_token.has_permission(Permission::EmailSend) {
+            return SubmissionStatus::Forbidden;
+        }
+
+        // Administrators are subject to their own limits, if configured
+        let (messages, recipients) = if access_token.has_permission(Permission::Impersonate) {
+            (
+                limits.admin_messages.as_ref().or(limits.messages.as_ref()),
+                limits
+                    .admin_recipients
+                    .as_ref()
+                    .or(limits.recipients.as_ref()),
+            )
+        } else {
+            (limits.messages.as_ref(), limits.recipients.as_ref())
+        };
+
+        // Messages with more recipients than the limit allows would never be accepted
+        if recipients.map_or(false, |rate| num_recipients as u64 > rate.requests) {
+            return SubmissionStatus::TooManyRecipients;
+        }
+
+        let mut exceeded = None;
+        for (prefix, rate, count) in [
+            ("sm:", messages, 1),
+            ("sr:", recipients, num_recipients as u64),
+        ] {
+            if let Some(rate) = rate {
+                match self
+                    .core
+                    .storage
+                    .lookup
+                    .is_rate_allowed_by(format!("{prefix}{account_id}").as_bytes(), rate, count)
+                    .await
+                {
+                    Ok(Some(_)) => {
+                        exceeded = Some(rate);
+                        break;
+                    }
+                    Ok(None) => {}
+                    Err(err) => {
+                        trc::error!(err
+                            .span_id(session_id)
+                            .caused_by(trc::location!())
+                            .details("Failed to check submission rate"));
+                    }
+                }
+            }
+        }
+
+        let rate = if let Some(rate) = exceeded {
+            rate
+        } else {
+            return SubmissionStatus::Allowed;
+        };
+        trc::event!(
+            Security(SecurityEvent::SubmissionLimitExceeded),
+            SpanId = session_id,
+            AccountId = account_id,
+            AccountName = access_token.name.clone(),
+            Limit = vec![
+                trc::Value::from(rate.requests),
+                trc::Value::from(rate.period)
+            ],
+        );
+
+        // Suspend outbound for accounts that repeatedly exceed their limits
+        if let Some(suspend) = &limits.suspend {
+            let is_suspended = match self
+                .core
+                .storage
+                .lookup
+                .is_rate_allowed(format!("ss:{account_id}").as_bytes(), suspend, false)
+                .await
+            {
+                Ok(result) => result.is_some(),
+                Err(err) => {
+                    trc::error!(err
+                        .span_id(session_id)
+                        .caused_by(trc::location!())
+                        .details("Failed to check submission suspend rate"));
+                    false
+                }
+            };
+
+            if is_suspended {
+                if let Err(err) = self
+                    .core
+                    .storage
+                    .data
+                    .update_principal(UpdatePrincipal::by_id(account_id).with_updates(vec![
+                        PrincipalUpdate::add_item(
+                            PrincipalField::DisabledPermissions,
+                            PrincipalValue::String(Permission::EmailSend.name().to_string()),
+                        ),
+                    ]))
+                    .await
+                {
+                    trc::error!(err
+                        .span_id(session_id)
+                        .caused_by(trc::location!())
+                        .details("Failed to suspend account"));
+                    return SubmissionStatus::RateLimited;
+                }
+
+                // Access tokens carry the account permissions
+                self.core.security.access_tokens.remove(&account_id);
+                self.core.invalidate_auth_cache(account_id, &[]).await;
+
+                trc::event!(
+                    Security(SecurityEvent::SubmissionSuspended),
+                    SpanId = session_id,
+                    AccountId = account_id,
+                    AccountName = access_token.name.clone(),
+                );
+
+                return SubmissionStatus::Forbidden;
+            }
+        }
+
+        SubmissionStatus::RateLimited
+    }
+
     pub fn cleanup(&self) {
         for throttle in [&self.inner.session_throttle, &self.inner.queue_throttle] {
             throttle.retain(|_, v| v.concurrent.load(Ordering::Relaxed) > 0);
         }
+        self.inner
+            .domain_concurrency
+            .retain(|_, v| v.concurrent.load(Ordering::Relaxed) > 0);
+        self.inner.reputation_cache.cleanup();
     }
 }
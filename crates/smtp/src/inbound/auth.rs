@@ -4,11 +4,18 @@
  * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
  */
 
-use common::listener::SessionStream;
-use directory::{backend::internal::PrincipalField, Permission};
+use common::{auth::oauth::OAuthScope, config::server::ServerProtocol, listener::SessionStream};
+use directory::{
+    backend::internal::PrincipalField, core::scram::ScramExchange, Directory, Permission,
+    Principal, QueryBy,
+};
+use mail_builder::encoders::base64::base64_encode;
 use mail_parser::decoders::base64::base64_decode;
 use mail_send::Credentials;
-use smtp_proto::{IntoString, AUTH_LOGIN, AUTH_OAUTHBEARER, AUTH_PLAIN, AUTH_XOAUTH2};
+use smtp_proto::{
+    IntoString, AUTH_LOGIN, AUTH_OAUTHBEARER, AUTH_PLAIN, AUTH_SCRAM_SHA_256,
+    AUTH_SCRAM_SHA_256_PLUS, AUTH_XOAUTH2,
+};
 use trc::{AddContext, AuthEvent, SmtpEvent};
 
 use crate::core::Session;
@@ -16,17 +23,29 @@ use crate::core::Session;
 pub struct SaslToken {
     mechanism: u64,
     credentials: Credentials<String>,
+    scram: Option<ScramStep>,
+}
+
+enum ScramStep {
+    // Waiting for the client-final message
+    Challenge(Box<ScramExchange>),
+    // Waiting for the client to acknowledge the server-final message
+    Verified {
+        username: String,
+        principal: Box<Principal>,
+    },
 }
 
 impl SaslToken {
     pub fn from_mechanism(mechanism: u64) -> Option<SaslToken> {
         match mechanism {
-            AUTH_PLAIN | AUTH_LOGIN => SaslToken {
+            AUTH_PLAIN | AUTH_LOGIN | AUTH_SCRAM_SHA_256 | AUTH_SCRAM_SHA_256_PLUS => SaslToken {
                 mechanism,
                 credentials: Credentials::Plain {
                     username: String::new(),
                     secret: String::new(),
                 },
+                scram: None,
             }
             .into(),
             AUTH_OAUTHBEARER => SaslToken {
@@ -34,6 +53,7 @@ impl SaslToken {
                 credentials: Credentials::OAuthBearer {
                     token: String::new(),
                 },
+                scram: None,
             }
             .into(),
             AUTH_XOAUTH2 => SaslToken {
@@ -42,6 +62,7 @@ impl SaslToken {
                     username: String::new(),
                     secret: String::new(),
                 },
+                scram: None,
             }
             .into(),
             _ => None,
@@ -55,7 +76,12 @@ impl<T: SessionStream> Session<T> {
         token: &mut SaslToken,
         response: &[u8],
     ) -> Result<bool, ()> {
-        if response.is_empty() {
+        if matches!(
+            token.mechanism,
+            AUTH_SCRAM_SHA_256 | AUTH_SCRAM_SHA_256_PLUS
+        ) {
+            return self.handle_scram_response(token, response).await;
+        } else if response.is_empty() {
             match (token.mechanism, &token.credentials) {
                 (AUTH_PLAIN | AUTH_XOAUTH2 | AUTH_OAUTHBEARER, _) => {
                     self.write(b"334 Go ahead.\r\n").await?;
@@ -158,27 +184,219 @@ impl<T: SessionStream> Session<T> {
         self.auth_error(b"500 5.5.6 Invalid challenge.\r\n").await
     }
 
+    async fn handle_scram_response(
+        &mut self,
+        token: &mut SaslToken,
+        response: &[u8],
+    ) -> Result<bool, ()> {
+        let directory = if let Some(directory) = &self.params.auth_directory {
+            directory.clone()
+        } else {
+            return self.authenticate_with(String::new(), None).await;
+        };
+        let response = if response.is_empty() || response == b"=" {
+            Vec::new()
+        } else if let Some(response) = base64_decode(response) {
+            response
+        } else {
+            return self.auth_error(b"500 5.5.6 Invalid challenge.\r\n").await;
+        };
+        let channel_binding = self.stream.tls_exporter();
+
+        match token.scram.take() {
+            Some(ScramStep::Challenge(exchange)) => {
+                // Verify the client proof
+                match self
+                    .core
+                    .core
+                    .authenticate_scram(
+                        &directory,
+                        self.data.session_id,
+                        &exchange,
+                        &response,
+                        channel_binding.as_deref(),
+                        self.data.remote_ip,
+                        false,
+                    )
+                    .await
+                {
+                    Ok((principal, server_final)) => {
+                        token.scram = Some(ScramStep::Verified {
+                            username: exchange.username,
+                            principal: Box::new(principal),
+                        });
+                        self.write_sasl_challenge(&server_final).await
+                    }
+                    Err(err) => {
+                        self.authenticate_with(exchange.username, Some(Err(err)))
+                            .await
+                    }
+                }
+            }
+            Some(ScramStep::Verified {
+                username,
+                principal,
+            }) => {
+                // The client acknowledged the server signature
+                self.authenticate_with(username, Some(Ok(*principal))).await
+            }
+            None if response.is_empty() => self.write_sasl_challenge(b"").await,
+            None => {
+                match ScramExchange::parse(
+                    &response,
+                    token.mechanism == AUTH_SCRAM_SHA_256_PLUS,
+                    channel_binding.is_some(),
+                ) {
+                    Ok(mut exchange) => {
+                        match self
+                            .core
+                            .core
+                            .scram_challenge(&directory, &mut exchange)
+                            .await
+                        {
+                            Ok(server_first) => {
+                                token.scram = Some(ScramStep::Challenge(Box::new(exchange)));
+                                self.write_sasl_challenge(&server_first).await
+                            }
+                            Err(err) => {
+                                self.authenticate_with(exchange.username, Some(Err(err)))
+                                    .await
+                            }
+                        }
+                    }
+                    Err(reason) => {
+                        trc::event!(
+                            Auth(AuthEvent::Error),
+                            SpanId = self.data.session_id,
+                            Reason = reason,
+                        );
+
+                        self.auth_error(b"500 5.5.6 Invalid challenge.\r\n").await
+                    }
+                }
+            }
+        }
+    }
+
+    async fn write_sasl_challenge(&mut self, challenge: &[u8]) -> Result<bool, ()> {
+        let mut buf = Vec::with_capacity(challenge.len() * 4 / 3 + 8);
+        buf.extend_from_slice(b"334 ");
+        buf.extend_from_slice(&base64_encode(challenge).unwrap_or_default());
+        buf.extend_from_slice(b"\r\n");
+        self.write(&buf).await?;
+        Ok(true)
+    }
+
     pub async fn authenticate(&mut self, credentials: Credentials<String>) -> Result<bool, ()> {
-        if let Some(directory) = &self.params.auth_directory {
-            let authenticated_as = match &credentials {
-                Credentials::Plain { username, .. }
-                | Credentials::XOauth2 { username, .. }
-                | Credentials::OAuthBearer { token: username } => username.to_string(),
+        // Access tokens issued by this server are only accepted with the SMTP scope
+        if let Some(directory) = self.params.auth_directory.clone() {
+            let result = match &credentials {
+                Credentials::XOauth2 { username, secret } => Some(
+                    self.authenticate_token(
+                        &directory,
+                        Some(username),
+                        secret.strip_prefix("Bearer ").unwrap_or(secret).trim(),
+                    )
+                    .await,
+                ),
+                Credentials::OAuthBearer { token } => Some(match decode_oauth_bearer(token) {
+                    Some((authzid, token)) => {
+                        self.authenticate_token(&directory, authzid.as_deref(), token)
+                            .await
+                    }
+                    None => Err(trc::AuthEvent::Failed
+                        .into_err()
+                        .details("Invalid OAUTHBEARER response.")),
+                }),
+                Credentials::Plain { .. } => None,
             };
 
-            // Authenticate
-            let mut result = self
-                .core
-                .core
-                .authenticate(
-                    directory,
-                    self.data.session_id,
-                    &credentials,
-                    self.data.remote_ip,
-                    false,
-                )
-                .await;
+            if let Some(result) = result {
+                let authenticated_as = result
+                    .as_ref()
+                    .map(|principal| principal.name().to_string())
+                    .unwrap_or_default();
+
+                return self.authenticate_with(authenticated_as, Some(result)).await;
+            }
+        }
+
+        let authenticated_as = match &credentials {
+            Credentials::Plain { username, .. }
+            | Credentials::XOauth2 { username, .. }
+            | Credentials::OAuthBearer { token: username } => username.to_string(),
+        };
+        let result = if let Some(directory) = &self.params.auth_directory {
+            Some(
+                self.core
+                    .core
+                    .authenticate(
+                        directory,
+                        self.data.session_id,
+                        &credentials,
+                        self.data.remote_ip,
+                        ServerProtocol::Smtp,
+                        false,
+                    )
+                    .await,
+            )
+        } else {
+            None
+        };
+
+        self.authenticate_with(authenticated_as, result).await
+    }
+
+    async fn authenticate_token(
+        &self,
+        directory: &Directory,
+        authzid: Option<&str>,
+        token: &str,
+    ) -> trc::Result<Principal> {
+        let token_info = self
+            .core
+            .core
+            .authenticate_access_token(token, self.data.remote_ip, self.data.session_id)
+            .await?;
+        if !token_info.scopes.contains(OAuthScope::Smtp) {
+            return Err(trc::AuthEvent::Failed
+                .into_err()
+                .details("Access token scope does not cover SMTP."));
+        }
+
+        let principal = directory
+            .query(QueryBy::Id(token_info.account_id), false)
+            .await
+            .caused_by(trc::location!())?
+            .ok_or_else(|| {
+                trc::AuthEvent::Failed
+                    .into_err()
+                    .details("Account not found.")
+            })?;
+
+        // The authorization identity, when provided, has to be the token's account
+        if let Some(authzid) = authzid {
+            if !principal.name().eq_ignore_ascii_case(authzid)
+                && !principal
+                    .iter_str(PrincipalField::Emails)
+                    .any(|email| email.eq_ignore_ascii_case(authzid))
+            {
+                return Err(trc::AuthEvent::Failed
+                    .into_err()
+                    .details("Authorization identity does not match the access token.")
+                    .id(authzid.to_string()));
+            }
+        }
 
+        Ok(principal)
+    }
+
+    async fn authenticate_with(
+        &mut self,
+        authenticated_as: String,
+        result: Option<trc::Result<Principal>>,
+    ) -> Result<bool, ()> {
+        if let Some(mut result) = result {
             // Validate permissions
             if let Ok(principal) = &result {
                 match self
@@ -207,6 +425,7 @@ impl<T: SessionStream> Session<T> {
             match result {
                 Ok(principal) => {
                     self.data.authenticated_as = authenticated_as.to_lowercase();
+                    self.data.authenticated_id = Some(principal.id());
                     self.data.authenticated_emails = principal
                         .iter_str(PrincipalField::Emails)
                         .map(|e| e.trim().to_lowercase())
@@ -276,3 +495,21 @@ impl<T: SessionStream> Session<T> {
         }
     }
 }
+
+// Extracts the authorization identity from the GS2 header and the bearer
+// token from an OAUTHBEARER initial client response (RFC 7628)
+fn decode_oauth_bearer(response: &str) -> Option<(Option<String>, &str)> {
+    let mut fields = response.split('\x01');
+    let authzid = fields
+        .next()?
+        .split(',')
+        .find_map(|field| field.strip_prefix("a="))
+        .filter(|authzid| !authzid.is_empty())
+        .map(|authzid| authzid.replace("=2C", ",").replace("=3D", "="));
+    let token = fields
+        .find_map(|field| field.strip_prefix("auth=Bearer "))
+        .map(|token| token.trim())
+        .filter(|token| !token.is_empty())?;
+
+    Some((authzid, token))
+}
@@ -12,7 +12,13 @@ use std::{
 };
 
 use common::{
-    config::smtp::{auth::VerifyStrategy, session::Stage},
+    config::{
+        server::ListenerRole,
+        smtp::{
+            auth::{VerifyStrategy, AUTO_SIGNER},
+            session::Stage,
+        },
+    },
     listener::SessionStream,
     psl,
     scripts::ScriptModification,
@@ -28,11 +34,11 @@ use smtp_proto::{
 };
 use store::write::now;
 use tokio::{io::AsyncWriteExt, process::Command};
-use trc::SmtpEvent;
+use trc::{DkimEvent, SmtpEvent};
 use utils::config::Rate;
 
 use crate::{
-    core::{Session, SessionAddress, State},
+    core::{throttle::SubmissionStatus, Session, SessionAddress, State},
     inbound::milter::Modification,
     queue::{self, Message, MessageSource, QueueEnvelope, Schedule},
     scripts::ScriptResult,
@@ -42,6 +48,31 @@ use super::{ArcSeal, AuthResult, DkimSign};
 
 impl<T: SessionStream> Session<T> {
     pub async fn queue_message(&mut self) -> Cow<'static, [u8]> {
+        // Enforce outbound submission limits
+        if let Some(account_id) = self.data.authenticated_id {
+            if self.core.core.smtp.session.submission.is_enabled() {
+                match self
+                    .core
+                    .submission_status(account_id, self.data.rcpt_to.len(), self.data.session_id)
+                    .await
+                {
+                    SubmissionStatus::Allowed => (),
+                    SubmissionStatus::RateLimited => {
+                        return (&b"451 4.7.1 Sending rate limit exceeded, try again later.\r\n"[..])
+                            .into();
+                    }
+                    SubmissionStatus::TooManyRecipients => {
+                        return (&b"550 5.5.3 Too many recipients for this account.\r\n"[..])
+                            .into();
+                    }
+                    SubmissionStatus::Forbidden => {
+                        return (&b"550 5.7.1 Sending is suspended for this account.\r\n"[..])
+                            .into();
+                    }
+                }
+            }
+        }
+
         // Authenticate message
         let raw_message = Arc::new(std::mem::take(&mut self.data.message));
         let auth_message = if let Some(auth_message) = AuthenticatedMessage::parse_with_opts(
@@ -165,7 +196,20 @@ impl<T: SessionStream> Session<T> {
             .core
             .eval_if::<String, _>(&ac.arc.seal, self, self.data.session_id)
             .await
-            .and_then(|name| self.core.core.get_arc_sealer(&name, self.data.session_id));
+            .and_then(|name| {
+                self.core.core.resolve_arc_sealer(
+                    &name,
+                    sender_domain(
+                        &auth_message,
+                        self.data
+                            .mail_from
+                            .as_ref()
+                            .map(|m| m.domain.as_str())
+                            .unwrap_or_default(),
+                    ),
+                    self.data.session_id,
+                )
+            });
         let arc_output = if arc.verify() || arc_sealer.is_some() {
             let time = Instant::now();
             let arc_output = self
@@ -683,27 +727,56 @@ impl<T: SessionStream> Session<T> {
         let raw_message = edited_message
             .as_deref()
             .unwrap_or_else(|| raw_message.as_slice());
-        for signer in self
+        let mut signer_names = self
             .core
             .core
             .eval_if::<Vec<String>, _>(&ac.dkim.sign, self, self.data.session_id)
             .await
-            .unwrap_or_default()
-        {
-            if let Some(signer) = self
-                .core
-                .core
-                .get_dkim_signer(&signer, self.data.session_id)
+            .unwrap_or_default();
+        if signer_names.is_empty() && self.instance.role == Some(ListenerRole::Submission) {
+            // Submitted messages are always signed with the envelope sender domain's signers
+            signer_names.push(AUTO_SIGNER.to_string());
+        }
+        if signer_names.iter().any(|name| name == AUTO_SIGNER) {
+            // Only sign on behalf of the envelope sender domain, never for an
+            // unverified From header domain that differs from it
+            let from_domain = sender_domain(&auth_message, "");
+            if !from_domain.is_empty()
+                && !from_domain.eq_ignore_ascii_case(&message.return_path_domain)
             {
-                match signer.sign_chained(&[headers.as_ref(), raw_message]) {
-                    Ok(signature) => {
-                        signature.write_header(&mut headers);
-                    }
-                    Err(err) => {
-                        trc::error!(trc::Event::from(err)
-                            .span_id(self.data.session_id)
-                            .details("Failed to DKIM sign message"));
-                    }
+                trc::event!(
+                    Dkim(DkimEvent::SignerNotFound),
+                    SpanId = self.data.session_id,
+                    Domain = from_domain.to_string(),
+                    Details = "From header domain does not match envelope sender domain",
+                );
+
+                signer_names.retain(|name| name != AUTO_SIGNER);
+            }
+        }
+        let signers = if !signer_names.is_empty() {
+            if let Some(signers) = self.core.core.resolve_dkim_signers(
+                &signer_names,
+                &message.return_path_domain,
+                self.data.session_id,
+            ) {
+                signers
+            } else {
+                return (b"550 5.7.1 No DKIM signature configured for sender domain.\r\n"[..])
+                    .into();
+            }
+        } else {
+            vec![]
+        };
+        for signer in signers {
+            match signer.sign_chained(&[headers.as_ref(), raw_message]) {
+                Ok(signature) => {
+                    signature.write_header(&mut headers);
+                }
+                Err(err) => {
+                    trc::error!(trc::Event::from(err)
+                        .span_id(self.data.session_id)
+                        .details("Failed to DKIM sign message"));
                 }
             }
         }
@@ -716,6 +789,11 @@ impl<T: SessionStream> Session<T> {
             // Prepare webhook event
             let queue_id = message.queue_id;
 
+            // Journal message
+            if !self.run_journals(&message, &headers, raw_message).await {
+                return (b"451 4.3.0 Unable to journal message, try again later.\r\n"[..]).into();
+            }
+
             // Queue message
             let source = if self.data.authenticated_as.is_empty() {
                 MessageSource::Unauthenticated
@@ -771,15 +849,48 @@ impl<T: SessionStream> Session<T> {
             quota_keys: Vec::new(),
         };
 
-        // Add recipients
+        // Apply transport map rewrites, the original address is kept as ORCPT
+        let transport_map = &self.core.core.smtp.queue.transport_map;
+        if !transport_map.rules.is_empty() {
+            for rcpt in &mut rcpt_to {
+                if let Some(address) = self
+                    .core
+                    .core
+                    .rewrite_recipient(&rcpt.address_lcase, self.data.session_id)
+                {
+                    if rcpt.dsn_info.is_none() {
+                        rcpt.dsn_info = Some(std::mem::take(&mut rcpt.address));
+                    }
+                    rcpt.domain = address.rsplit_once('@').unwrap().1.to_string();
+                    rcpt.address = address.clone();
+                    rcpt.address_lcase = address;
+                }
+            }
+        }
+
+        // Add recipients, grouped by domain and relay host
         let future_release = Duration::from_secs(self.data.future_release);
-        rcpt_to.sort_unstable();
+        rcpt_to.sort_unstable_by(|a, b| {
+            a.domain
+                .cmp(&b.domain)
+                .then_with(|| {
+                    transport_map
+                        .relay(&a.address_lcase)
+                        .cmp(&transport_map.relay(&b.address_lcase))
+                })
+                .then_with(|| a.address_lcase.cmp(&b.address_lcase))
+        });
+        rcpt_to.dedup_by(|a, b| a.address_lcase == b.address_lcase);
+        let mut last_relay = None;
         for rcpt in rcpt_to {
+            let relay = transport_map.relay(&rcpt.address_lcase);
             if message
                 .domains
                 .last()
                 .map_or(true, |d| d.domain != rcpt.domain)
+                || last_relay != Some(relay)
             {
+                last_relay = Some(relay);
                 let rcpt_idx = message.domains.len();
                 message.domains.push(queue::Domain {
                     retry: Schedule::now(),
@@ -962,3 +1073,12 @@ impl<T: SessionStream> Session<T> {
         headers.extend_from_slice(b"\r\n");
     }
 }
+
+fn sender_domain<'x>(message: &'x AuthenticatedMessage<'_>, fallback: &'x str) -> &'x str {
+    message
+        .from()
+        .rsplit_once('@')
+        .map(|(_, domain)| domain)
+        .filter(|domain| !domain.is_empty())
+        .unwrap_or(fallback)
+}
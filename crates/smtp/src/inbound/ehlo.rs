@@ -8,7 +8,10 @@ use std::time::{Duration, Instant, SystemTime};
 
 use crate::{core::Session, scripts::ScriptResult};
 use common::{
-    config::smtp::session::{Mechanism, Stage},
+    config::{
+        server::ListenerRole,
+        smtp::session::{Mechanism, Stage},
+    },
     listener::SessionStream,
 };
 use mail_auth::{spf::verify::HasValidLabels, SpfResult};
@@ -211,7 +214,9 @@ impl<T: SessionStream> Session<T> {
         }
 
         // Authentication
-        if self.data.authenticated_as.is_empty() {
+        if self.data.authenticated_as.is_empty()
+            && self.instance.role != Some(ListenerRole::Inbound)
+        {
             response.auth_mechanisms = self
                 .core
                 .core
@@ -219,6 +224,11 @@ impl<T: SessionStream> Session<T> {
                 .await
                 .unwrap_or_default()
                 .into();
+
+            // Channel binding requires a TLS 1.3 connection
+            if self.stream.tls_exporter().is_none() {
+                response.auth_mechanisms &= !AUTH_SCRAM_SHA_256_PLUS;
+            }
             if response.auth_mechanisms != 0 {
                 response.capabilities |= EXT_AUTH;
             }
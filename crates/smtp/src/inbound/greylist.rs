@@ -0,0 +1,136 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use std::net::IpAddr;
+
+use common::listener::SessionStream;
+use mail_auth::SpfResult;
+use store::write::now;
+use trc::SmtpEvent;
+
+use crate::core::Session;
+
+const GREYLIST_PASSED: &str = "passed";
+
+impl<T: SessionStream> Session<T> {
+    // Greylists the (remote network, sender, recipient) triplet of the last recipient,
+    // returns false if delivery has to be deferred until the sender retries.
+    pub async fn is_greylist_passed(&self) -> bool {
+        let config = &self.core.core.smtp.session.rcpt.greylist;
+        if !self.data.authenticated_as.is_empty()
+            || self.core.core.is_ip_allowed(&self.data.remote_ip)
+            || (config.allow_spf_pass
+                && self
+                    .data
+                    .spf_mail_from
+                    .as_ref()
+                    .is_some_and(|spf| spf.result() == SpfResult::Pass))
+            || !self
+                .core
+                .core
+                .eval_if(&config.enable, self, self.data.session_id)
+                .await
+                .unwrap_or(false)
+        {
+            return true;
+        }
+
+        let rcpt = self.data.rcpt_to.last().unwrap();
+        let key = format!(
+            "greylist:{}:{}:{}",
+            greylist_network(&self.data.remote_ip),
+            self.data
+                .mail_from
+                .as_ref()
+                .map(|mail_from| mail_from.address_lcase.as_str())
+                .unwrap_or_default(),
+            rcpt.address_lcase
+        )
+        .into_bytes();
+        let lookup = &self.core.core.storage.lookup;
+
+        let (is_passed, result) = match lookup.key_get::<String>(key.clone()).await {
+            Ok(Some(value)) if value == GREYLIST_PASSED => (true, Ok(())),
+            Ok(Some(first_seen)) => {
+                if first_seen.parse::<u64>().unwrap_or_default() + config.delay.as_secs() <= now() {
+                    // Triplets that retried are allowed without delay from now on
+                    (
+                        true,
+                        lookup
+                            .key_set(
+                                key,
+                                GREYLIST_PASSED.as_bytes().to_vec(),
+                                Some(config.allow_ttl.as_secs()),
+                            )
+                            .await,
+                    )
+                } else {
+                    (false, Ok(()))
+                }
+            }
+            Ok(None) => (
+                false,
+                lookup
+                    .key_set(
+                        key,
+                        now().to_string().into_bytes(),
+                        Some(config.delay.as_secs() + config.pending_ttl.as_secs()),
+                    )
+                    .await,
+            ),
+            Err(err) => {
+                // Store errors never defer delivery
+                trc::error!(err
+                    .span_id(self.data.session_id)
+                    .details("Failed to obtain greylist entry")
+                    .caused_by(trc::location!()));
+                return true;
+            }
+        };
+
+        if let Err(err) = result {
+            trc::error!(err
+                .span_id(self.data.session_id)
+                .details("Failed to store greylist entry")
+                .caused_by(trc::location!()));
+        }
+
+        trc::event!(
+            Smtp(if is_passed {
+                SmtpEvent::GreylistPassed
+            } else {
+                SmtpEvent::Greylisted
+            }),
+            SpanId = self.data.session_id,
+            RemoteIp = self.data.remote_ip,
+            To = rcpt.address_lcase.clone(),
+        );
+
+        is_passed
+    }
+}
+
+// Senders retrying from a different host of the same pool are not deferred again,
+// so triplets are keyed on the /24 network for IPv4 and the /64 for IPv6.
+fn greylist_network(ip: &IpAddr) -> String {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, c, _] = ip.octets();
+            format!("{a}.{b}.{c}.0/24")
+        }
+        IpAddr::V6(ip) => {
+            if let Some(ip) = ip.to_ipv4_mapped() {
+                greylist_network(&IpAddr::V4(ip))
+            } else {
+                let segments = ip.segments();
+                format!(
+                    "{:x}:{:x}:{:x}:{:x}::/64",
+                    segments[0], segments[1], segments[2], segments[3]
+                )
+            }
+        }
+    }
+}
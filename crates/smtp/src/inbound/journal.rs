@@ -0,0 +1,299 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use std::{fmt::Write, time::Instant};
+
+use common::{
+    config::smtp::session::{Journal, JournalTarget},
+    expr::{functions::ResolveVariable, Variable, V_RECIPIENT, V_RECIPIENT_DOMAIN},
+    listener::SessionStream,
+};
+use mail_builder::{
+    headers::{content_type::ContentType, HeaderType},
+    mime::{make_boundary, BodyPart, MimePart},
+    MessageBuilder,
+};
+use mail_parser::MessageParser;
+use trc::SmtpEvent;
+
+use crate::{
+    core::Session,
+    queue::{Message, MessageSource, Recipient},
+};
+
+impl<T: SessionStream> Session<T> {
+    // Archives a journal report of the message for each enabled journal rule.
+    // Returns false when a failed journal requires the message to be deferred.
+    pub async fn run_journals(
+        &self,
+        message: &Message,
+        headers: &[u8],
+        raw_message: &[u8],
+    ) -> bool {
+        let journals = &self.core.core.smtp.session.journals;
+        if journals.is_empty() {
+            return true;
+        }
+
+        // Messages addressed only to journal archives are not journaled again
+        if message.recipients.iter().all(|rcpt| {
+            journals.iter().any(|journal| match &journal.target {
+                JournalTarget::Address(address) => address == &rcpt.address_lcase,
+                JournalTarget::Http { .. } => false,
+            })
+        }) {
+            return true;
+        }
+
+        let mut report = None;
+        for journal in journals {
+            if !self.is_journal_enabled(journal, message).await {
+                continue;
+            }
+
+            let time = Instant::now();
+            if report.is_none() {
+                report = Some(
+                    self.build_journal_report(message, headers, raw_message)
+                        .await,
+                );
+            }
+
+            match self
+                .send_journal(journal, message, report.as_deref().unwrap_or_default())
+                .await
+            {
+                Ok(_) => {
+                    trc::event!(
+                        Smtp(SmtpEvent::JournalSuccess),
+                        SpanId = self.data.session_id,
+                        Id = journal.id.clone(),
+                        Elapsed = time.elapsed(),
+                    );
+                }
+                Err(err) => {
+                    trc::event!(
+                        Smtp(SmtpEvent::JournalError),
+                        SpanId = self.data.session_id,
+                        Id = journal.id.clone(),
+                        Reason = err,
+                        Elapsed = time.elapsed(),
+                    );
+
+                    if journal.tempfail_on_error {
+                        return false;
+                    }
+                }
+            }
+        }
+
+        true
+    }
+
+    // Journal rules are evaluated for each recipient and apply if any of them matches
+    async fn is_journal_enabled(&self, journal: &Journal, message: &Message) -> bool {
+        for rcpt in &message.recipients {
+            if self
+                .core
+                .core
+                .eval_if(
+                    &journal.enable,
+                    &JournalEnvelope {
+                        session: self,
+                        message,
+                        rcpt,
+                    },
+                    self.data.session_id,
+                )
+                .await
+                .unwrap_or(false)
+            {
+                return true;
+            }
+        }
+
+        false
+    }
+
+    async fn send_journal(
+        &self,
+        journal: &Journal,
+        message: &Message,
+        report: &[u8],
+    ) -> Result<(), String> {
+        match &journal.target {
+            JournalTarget::Address(address) => {
+                let mut journal_message = self.core.new_message("", "", "", self.data.session_id);
+                journal_message.priority = message.priority;
+                journal_message.add_recipient(address, &self.core).await;
+                journal_message.size = report.len();
+
+                if journal_message
+                    .queue(
+                        None,
+                        report,
+                        self.data.session_id,
+                        &self.core,
+                        MessageSource::Autogenerated,
+                    )
+                    .await
+                {
+                    Ok(())
+                } else {
+                    Err("Failed to queue journal report".to_string())
+                }
+            }
+            JournalTarget::Http {
+                url,
+                client,
+                headers,
+            } => {
+                let response = client
+                    .post(url)
+                    .headers(headers.clone())
+                    .body(report.to_vec())
+                    .send()
+                    .await
+                    .map_err(|err| format!("Journal request failed: {err}"))?;
+
+                if response.status().is_success() {
+                    Ok(())
+                } else {
+                    Err(format!(
+                        "Journal request failed with code {}: {}",
+                        response.status().as_u16(),
+                        response.status().canonical_reason().unwrap_or("Unknown")
+                    ))
+                }
+            }
+        }
+    }
+
+    // Wraps the message in a journal report listing its envelope, similar to
+    // the journal reports generated by other mail servers
+    async fn build_journal_report(
+        &self,
+        message: &Message,
+        headers: &[u8],
+        raw_message: &[u8],
+    ) -> Vec<u8> {
+        let config = &self.core.core.smtp.report;
+        let from_name = self
+            .core
+            .core
+            .eval_if(&config.dsn.name, self, self.data.session_id)
+            .await
+            .unwrap_or_else(|| String::from("Mail Delivery Subsystem"));
+        let from_addr = self
+            .core
+            .core
+            .eval_if(&config.dsn.address, self, self.data.session_id)
+            .await
+            .unwrap_or_else(|| String::from("MAILER-DAEMON@localhost"));
+
+        // Obtain the Subject and Message-ID of the original message
+        let mut original = Vec::with_capacity(headers.len() + raw_message.len());
+        original.extend_from_slice(headers);
+        original.extend_from_slice(raw_message);
+        let (subject, message_id) = MessageParser::new()
+            .parse_headers(original.as_slice())
+            .map(|parsed| {
+                (
+                    parsed.subject().unwrap_or_default().to_string(),
+                    parsed.message_id().unwrap_or_default().to_string(),
+                )
+            })
+            .unwrap_or_default();
+
+        // Write envelope
+        let mut envelope = String::with_capacity(128);
+        let _ = write!(envelope, "Sender: {}\r\n", message.return_path);
+        if !subject.is_empty() {
+            let _ = write!(envelope, "Subject: {subject}\r\n");
+        }
+        if !message_id.is_empty() {
+            let _ = write!(envelope, "Message-Id: <{message_id}>\r\n");
+        }
+        for rcpt in &message.recipients {
+            if let Some(orcpt) = &rcpt.orcpt {
+                let _ = write!(
+                    envelope,
+                    "Recipient: {}, Original: {orcpt}\r\n",
+                    rcpt.address
+                );
+            } else {
+                let _ = write!(envelope, "Recipient: {}\r\n", rcpt.address);
+            }
+        }
+        if !self.data.authenticated_as.is_empty() {
+            let _ = write!(
+                envelope,
+                "Authenticated-As: {}\r\n",
+                self.data.authenticated_as
+            );
+        }
+        let _ = write!(
+            envelope,
+            "Remote-IP: {}\r\nHelo: {}\r\nDirection: {}\r\nQueue-Id: {:x}\r\n",
+            self.data.remote_ip,
+            self.data.helo_domain,
+            if self.data.authenticated_as.is_empty() {
+                "inbound"
+            } else {
+                "outbound"
+            },
+            message.queue_id
+        );
+
+        MessageBuilder::new()
+            .from((from_name.as_str(), from_addr.as_str()))
+            .header("Auto-Submitted", HeaderType::Text("auto-generated".into()))
+            .header("X-Journal-Report", HeaderType::Text("Journal".into()))
+            .message_id(format!("<{}@{}>", make_boundary("."), self.hostname))
+            .subject(if !subject.is_empty() {
+                format!("Journal report: {subject}")
+            } else {
+                "Journal report".to_string()
+            })
+            .body(MimePart::new(
+                ContentType::new("multipart/mixed"),
+                BodyPart::Multipart(vec![
+                    MimePart::new(
+                        ContentType::new("text/plain"),
+                        BodyPart::Text(envelope.into()),
+                    ),
+                    MimePart::new(
+                        ContentType::new("message/rfc822"),
+                        BodyPart::Binary(original.into()),
+                    ),
+                ]),
+            ))
+            .write_to_vec()
+            .unwrap_or_default()
+    }
+}
+
+struct JournalEnvelope<'x, T: SessionStream> {
+    session: &'x Session<T>,
+    message: &'x Message,
+    rcpt: &'x Recipient,
+}
+
+impl<'x, T: SessionStream> ResolveVariable for JournalEnvelope<'x, T> {
+    fn resolve_variable(&self, variable: u32) -> Variable<'_> {
+        match variable {
+            V_RECIPIENT => self.rcpt.address_lcase.as_str().into(),
+            V_RECIPIENT_DOMAIN => self
+                .message
+                .domains
+                .get(self.rcpt.domain_idx)
+                .map(|d| d.domain.as_str())
+                .unwrap_or_default()
+                .into(),
+            _ => self.session.resolve_variable(variable),
+        }
+    }
+}
@@ -8,7 +8,9 @@ use std::time::{Duration, Instant, SystemTime};
 
 use common::{config::smtp::session::Stage, listener::SessionStream, scripts::ScriptModification};
 use mail_auth::{IprevOutput, IprevResult, SpfOutput, SpfResult};
-use smtp_proto::{MailFrom, MtPriority, MAIL_BY_NOTIFY, MAIL_BY_RETURN, MAIL_REQUIRETLS};
+use smtp_proto::{
+    MailFrom, MtPriority, MAIL_BY_NOTIFY, MAIL_BY_RETURN, MAIL_REQUIRETLS, MAIL_SMTPUTF8,
+};
 use trc::SmtpEvent;
 use utils::config::Rate;
 
@@ -51,6 +53,16 @@ impl<T: SessionStream> Session<T> {
             return self
                 .write(b"503 5.5.1 You must authenticate first.\r\n")
                 .await;
+        } else if !from.address.is_ascii() && (from.flags & MAIL_SMTPUTF8) == 0 {
+            trc::event!(
+                Smtp(SmtpEvent::SmtpUtf8Required),
+                SpanId = self.data.session_id,
+                From = from.address,
+            );
+
+            return self
+                .write(b"553 5.6.7 SMTPUTF8 is required for non-ASCII addresses.\r\n")
+                .await;
         } else if self.data.iprev.is_none() && self.params.iprev.verify() {
             let time = Instant::now();
             let iprev = self
@@ -259,7 +271,6 @@ impl<T: SessionStream> Session<T> {
 
         // Validate parameters
         let config = &self.core.core.smtp.session.extensions;
-        let config_data = &self.core.core.smtp.session.data;
         if (from.flags & MAIL_REQUIRETLS) != 0
             && !self
                 .core
@@ -348,26 +359,8 @@ impl<T: SessionStream> Session<T> {
                     .await;
             }
         }
-        if from.size > 0
-            && from.size
-                > self
-                    .core
-                    .core
-                    .eval_if(&config_data.max_message_size, self, self.data.session_id)
-                    .await
-                    .unwrap_or(25 * 1024 * 1024)
-        {
-            trc::event!(
-                Smtp(SmtpEvent::MessageTooLarge),
-                SpanId = self.data.session_id,
-                Size = from.size,
-            );
-
-            self.data.mail_from = None;
-            return self
-                .write(b"552 5.3.4 Message too big for system.\r\n")
-                .await;
-        }
+        // The declared size is checked on RCPT against each recipient's limit
+        self.data.declared_size = from.size;
         if from.hold_for != 0 || from.hold_until != 0 {
             if let Some(max_hold) = self
                 .core
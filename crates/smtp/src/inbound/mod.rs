@@ -15,10 +15,13 @@ use mail_auth::{
 pub mod auth;
 pub mod data;
 pub mod ehlo;
+pub mod greylist;
 pub mod hooks;
+pub mod journal;
 pub mod mail;
 pub mod milter;
 pub mod rcpt;
+pub mod reputation;
 pub mod session;
 pub mod spawn;
 pub mod vrfy;
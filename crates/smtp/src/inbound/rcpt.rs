@@ -6,7 +6,8 @@
 
 use common::{config::smtp::session::Stage, listener::SessionStream, scripts::ScriptModification};
 use smtp_proto::{
-    RcptTo, RCPT_NOTIFY_DELAY, RCPT_NOTIFY_FAILURE, RCPT_NOTIFY_NEVER, RCPT_NOTIFY_SUCCESS,
+    RcptTo, MAIL_SMTPUTF8, RCPT_NOTIFY_DELAY, RCPT_NOTIFY_FAILURE, RCPT_NOTIFY_NEVER,
+    RCPT_NOTIFY_SUCCESS,
 };
 use trc::{SecurityEvent, SmtpEvent};
 
@@ -39,7 +40,7 @@ impl<T: SessionStream> Session<T> {
                 SpanId = self.data.session_id,
                 Limit = self.params.rcpt_max,
             );
-            return self.write(b"451 4.5.3 Too many recipients.\r\n").await;
+            return self.write(b"452 4.5.3 Too many recipients.\r\n").await;
         }
 
         // Verify parameters
@@ -55,6 +56,24 @@ impl<T: SessionStream> Session<T> {
                 .await;
         }
 
+        // Non-ASCII addresses require the sender to have requested SMTPUTF8 (RFC 6531)
+        if !to.address.is_ascii()
+            && self
+                .data
+                .mail_from
+                .as_ref()
+                .map_or(true, |mail_from| (mail_from.flags & MAIL_SMTPUTF8) == 0)
+        {
+            trc::event!(
+                Smtp(SmtpEvent::SmtpUtf8Required),
+                SpanId = self.data.session_id,
+                To = to.address,
+            );
+            return self
+                .write(b"553 5.6.7 SMTPUTF8 is required for non-ASCII addresses.\r\n")
+                .await;
+        }
+
         // Build RCPT
         let address_lcase = to.address.to_lowercase();
         let rcpt = SessionAddress {
@@ -286,6 +305,40 @@ impl<T: SessionStream> Session<T> {
             return self.rcpt_error(b"550 5.1.2 Relay not allowed.\r\n").await;
         }
 
+        // Check the declared size against the recipient's message size limit
+        let max_message_size = self
+            .core
+            .core
+            .eval_if(
+                &self.core.core.smtp.session.data.max_message_size,
+                self,
+                self.data.session_id,
+            )
+            .await
+            .unwrap_or(25 * 1024 * 1024);
+        if self.data.declared_size > max_message_size {
+            trc::event!(
+                Smtp(SmtpEvent::MessageTooLarge),
+                SpanId = self.data.session_id,
+                To = self.data.rcpt_to.last().unwrap().address_lcase.clone(),
+                Size = self.data.declared_size,
+                Limit = max_message_size,
+            );
+
+            self.data.rcpt_to.pop();
+            return self
+                .write(b"552 5.3.4 Message too big for recipient.\r\n")
+                .await;
+        }
+
+        // Defer first delivery attempts from unknown triplets
+        if !self.is_greylist_passed().await {
+            self.data.rcpt_to.pop();
+            return self
+                .write(b"451 4.7.1 Greylisted, please try again later.\r\n")
+                .await;
+        }
+
         if self.is_allowed().await {
             trc::event!(
                 Smtp(SmtpEvent::RcptTo),
@@ -305,6 +358,13 @@ impl<T: SessionStream> Session<T> {
                 .await;
         }
 
+        // Apply per-recipient message size limits, the smallest one wins
+        self.params.max_message_size = if self.data.rcpt_to.len() == 1 {
+            max_message_size
+        } else {
+            self.params.max_message_size.min(max_message_size)
+        };
+
         self.write(b"250 2.1.5 OK\r\n").await
     }
 
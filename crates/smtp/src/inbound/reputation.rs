@@ -0,0 +1,276 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use std::{
+    net::IpAddr,
+    time::{Duration, Instant},
+};
+
+use common::{
+    config::smtp::session::{IpReputation, ReputationAction, ReputationProvider},
+    listener::SessionStream,
+    HttpLimitResponse,
+};
+use hyper::HeaderMap;
+use mail_auth::common::resolver::ToReverseName;
+use serde::{Deserialize, Serialize};
+use store::write::now;
+use trc::{SecurityEvent, SmtpEvent};
+use utils::map::ttl_dashmap::TtlMap;
+
+use crate::core::{Session, SMTP};
+
+const MAX_RESPONSE_SIZE: usize = 1024;
+const GREYLIST_EXPIRY: u64 = 7 * 86400;
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ReputationRequest {
+    remote_ip: IpAddr,
+}
+
+#[derive(Debug, Deserialize)]
+struct ReputationResponse {
+    result: Option<String>,
+}
+
+impl<T: SessionStream> Session<T> {
+    // Queries the configured reputation providers for the remote IP, returns
+    // false if the connection was rejected.
+    pub async fn check_ip_reputation(&mut self) -> bool {
+        let core = self.core.core.clone();
+        let mut score = 0.0;
+        let mut delay = Duration::ZERO;
+
+        for check in &core.smtp.session.reputation {
+            if !core
+                .eval_if(&check.enable, self, self.data.session_id)
+                .await
+                .unwrap_or(false)
+            {
+                continue;
+            }
+
+            let result = if let Some(result) = self
+                .core
+                .reputation_lookup(check, self.data.remote_ip, self.data.session_id)
+                .await
+            {
+                result
+            } else {
+                continue;
+            };
+
+            let action = if let Some((_, action)) = check
+                .actions
+                .iter()
+                .find(|(action_result, _)| action_result == &result)
+                .or_else(|| {
+                    check
+                        .actions
+                        .iter()
+                        .find(|(action_result, _)| action_result == "*")
+                }) {
+                action
+            } else {
+                continue;
+            };
+
+            match action {
+                ReputationAction::Reject => {
+                    self.reputation_rejected(&check.id, result);
+                    let _ = self
+                        .write(b"554 5.7.1 Connection rejected due to poor IP reputation.\r\n")
+                        .await;
+                    return false;
+                }
+                ReputationAction::Greylist(period) => {
+                    if self.core.is_greylisted(self.data.remote_ip, *period).await {
+                        self.reputation_rejected(&check.id, result);
+                        let _ = self
+                            .write(b"421 4.7.0 Greylisted, please try again later.\r\n")
+                            .await;
+                        return false;
+                    }
+                }
+                ReputationAction::Delay(duration) => {
+                    delay = delay.max(*duration);
+                }
+                ReputationAction::Score(check_score) => {
+                    score += check_score;
+                }
+            }
+        }
+
+        if score > 0.0 && score >= core.smtp.session.connect.reputation_threshold {
+            self.reputation_rejected("score", score.to_string());
+            let _ = self
+                .write(b"554 5.7.1 Connection rejected due to poor IP reputation.\r\n")
+                .await;
+            return false;
+        }
+
+        // Tarpit connections from poorly rated addresses
+        if !delay.is_zero() {
+            trc::event!(
+                Smtp(SmtpEvent::IpReputationDelay),
+                SpanId = self.data.session_id,
+                RemoteIp = self.data.remote_ip,
+                Elapsed = delay,
+            );
+            tokio::time::sleep(delay).await;
+        }
+
+        true
+    }
+
+    fn reputation_rejected(&self, id: &str, result: String) {
+        trc::event!(
+            Security(SecurityEvent::IpBlocked),
+            SpanId = self.data.session_id,
+            ListenerId = self.instance.id.clone(),
+            LocalPort = self.data.local_port,
+            RemoteIp = self.data.remote_ip,
+            RemotePort = self.data.remote_port,
+            Id = id.to_string(),
+            Reason = result,
+        );
+    }
+}
+
+impl SMTP {
+    async fn reputation_lookup(
+        &self,
+        check: &IpReputation,
+        ip: IpAddr,
+        session_id: u64,
+    ) -> Option<String> {
+        let key = (ip, check.id.clone());
+        if let Some(result) = self.inner.reputation_cache.get_with_ttl(&key) {
+            return result;
+        }
+
+        let result = match &check.provider {
+            ReputationProvider::Dnsbl { zone } => {
+                match self
+                    .core
+                    .smtp
+                    .resolvers
+                    .dns
+                    .ipv4_lookup(&format!("{}.{zone}", ip.to_reverse_name()))
+                    .await
+                {
+                    Ok(result) => Ok(result.first().map(|ip| ip.to_string())),
+                    Err(mail_auth::Error::DnsRecordNotFound(_)) => Ok(None),
+                    Err(err) => Err(err.to_string()),
+                }
+            }
+            ReputationProvider::Http {
+                url,
+                client,
+                headers,
+            } => send_reputation_request(client, url, headers, ip).await,
+        };
+
+        match result {
+            Ok(result) => self.inner.reputation_cache.insert_with_ttl(
+                key,
+                result,
+                Instant::now() + check.cache_ttl,
+            ),
+            Err(err) => {
+                trc::event!(
+                    Smtp(SmtpEvent::IpReputationError),
+                    SpanId = session_id,
+                    Id = check.id.clone(),
+                    RemoteIp = ip,
+                    Reason = err,
+                );
+
+                None
+            }
+        }
+    }
+
+    // Returns true if the remote IP was first seen less than the greylisting
+    // period ago. Store errors never delay the connection.
+    async fn is_greylisted(&self, ip: IpAddr, period: Duration) -> bool {
+        let key = format!("greylist:{ip}").into_bytes();
+
+        match self
+            .core
+            .storage
+            .lookup
+            .key_get::<String>(key.clone())
+            .await
+        {
+            Ok(Some(first_seen)) => {
+                first_seen.parse::<u64>().unwrap_or_default() + period.as_secs() > now()
+            }
+            Ok(None) => {
+                if let Err(err) = self
+                    .core
+                    .storage
+                    .lookup
+                    .key_set(
+                        key,
+                        now().to_string().into_bytes(),
+                        Some(period.as_secs() + GREYLIST_EXPIRY),
+                    )
+                    .await
+                {
+                    trc::error!(err
+                        .details("Failed to store greylist entry")
+                        .caused_by(trc::location!()));
+                }
+                true
+            }
+            Err(err) => {
+                trc::error!(err
+                    .details("Failed to obtain greylist entry")
+                    .caused_by(trc::location!()));
+                false
+            }
+        }
+    }
+}
+
+async fn send_reputation_request(
+    client: &reqwest::Client,
+    url: &str,
+    headers: &HeaderMap,
+    remote_ip: IpAddr,
+) -> Result<Option<String>, String> {
+    let response = client
+        .post(url)
+        .headers(headers.clone())
+        .body(
+            serde_json::to_string(&ReputationRequest { remote_ip })
+                .map_err(|err| format!("Failed to serialize reputation request: {}", err))?,
+        )
+        .send()
+        .await
+        .map_err(|err| format!("Reputation request failed: {err}"))?;
+
+    if response.status().is_success() {
+        serde_json::from_slice::<ReputationResponse>(
+            response
+                .bytes_with_limit(MAX_RESPONSE_SIZE)
+                .await
+                .map_err(|err| format!("Failed to parse reputation response: {}", err))?
+                .ok_or_else(|| "Reputation response too large".to_string())?
+                .as_ref(),
+        )
+        .map(|response| response.result.filter(|result| !result.is_empty()))
+        .map_err(|err| format!("Failed to parse reputation response: {}", err))
+    } else {
+        Err(format!(
+            "Reputation request failed with code {}: {}",
+            response.status().as_u16(),
+            response.status().canonical_reason().unwrap_or("Unknown")
+        ))
+    }
+}
@@ -27,11 +27,30 @@ impl<T: SessionStream> Session<T> {
     pub async fn ingest(&mut self, bytes: &[u8]) -> Result<bool, ()> {
         let mut iter = bytes.iter();
         let mut state = std::mem::replace(&mut self.state, State::None);
+        let mut num_commands = 0;
 
         'outer: loop {
             match &mut state {
                 State::Request(receiver) => loop {
                     match receiver.ingest(&mut iter, bytes) {
+                        Ok(_)
+                            if {
+                                // Limit the number of commands pipelined in a single read
+                                num_commands += 1;
+                                self.params.pipelining_limit > 0
+                                    && num_commands > self.params.pipelining_limit
+                            } =>
+                        {
+                            trc::event!(
+                                Smtp(SmtpEvent::PipeliningLimitExceeded),
+                                SpanId = self.data.session_id,
+                                Limit = self.params.pipelining_limit,
+                            );
+
+                            self.write(b"421 4.7.0 Too many pipelined commands.\r\n")
+                                .await?;
+                            return Err(());
+                        }
                         Ok(request) => match request {
                             Request::Rcpt { to } => {
                                 self.handle_rcpt_to(to).await?;
@@ -52,7 +71,17 @@ impl<T: SessionStream> Session<T> {
                                 }
                             }
                             Request::Data => {
-                                if self.can_send_data().await? {
+                                if self.data.bdat_in_progress {
+                                    // DATA and BDAT cannot be mixed in a transaction (RFC 3030)
+                                    trc::event!(
+                                        Smtp(SmtpEvent::InvalidCommand),
+                                        SpanId = self.data.session_id,
+                                        Details = "DATA after BDAT",
+                                    );
+
+                                    self.write(b"503 5.5.1 DATA cannot be used after BDAT.\r\n")
+                                        .await?;
+                                } else if self.can_send_data().await? {
                                     self.write(b"354 Start mail input; end with <CRLF>.<CRLF>\r\n")
                                         .await?;
                                     self.data.message = Vec::with_capacity(1024);
@@ -67,14 +96,17 @@ impl<T: SessionStream> Session<T> {
                                 state = if chunk_size + self.data.message.len()
                                     < self.params.max_message_size
                                 {
-                                    if self.data.message.is_empty() {
+                                    if !self.data.bdat_in_progress {
+                                        self.data.bdat_in_progress = true;
                                         self.data.message = Vec::with_capacity(chunk_size);
                                     } else {
                                         self.data.message.reserve(chunk_size);
                                     }
                                     State::Bdat(BdatReceiver::new(chunk_size, is_last))
                                 } else {
-                                    // Chunk is too large, ignore.
+                                    // Chunk is too large, ignore it and fail the transaction
+                                    // so that a truncated message is never queued.
+                                    self.reset();
                                     State::DataTooLarge(DummyDataReceiver::new_bdat(chunk_size))
                                 };
                                 continue 'outer;
@@ -360,6 +392,7 @@ impl<T: SessionStream> Session<T> {
                             }
                         } else {
                             self.data.message = Vec::with_capacity(0);
+                            self.data.bdat_in_progress = false;
                         }
                         state = State::default();
                     } else {
@@ -436,9 +469,11 @@ impl<T: AsyncWrite + AsyncRead + Unpin> Session<T> {
         self.data.spf_mail_from = None;
         self.data.rcpt_to.clear();
         self.data.message = Vec::with_capacity(0);
+        self.data.bdat_in_progress = false;
         self.data.priority = 0;
         self.data.delivery_by = 0;
         self.data.future_release = 0;
+        self.data.declared_size = 0;
     }
 
     #[inline(always)]
@@ -4,7 +4,7 @@
  * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
  */
 
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
 use common::{
     config::smtp::session::Stage,
@@ -19,6 +19,8 @@ use crate::{
     scripts::ScriptResult,
 };
 
+const DELIVERY_DRAIN_TIMEOUT: Duration = Duration::from_secs(30);
+
 impl SessionManager for SmtpSessionManager {
     fn handle<T: SessionStream>(
         self,
@@ -66,6 +68,11 @@ impl SessionManager for SmtpSessionManager {
                 .report_tx
                 .send(reporting::Event::Stop)
                 .await;
+            self.inner
+                .inner
+                .ipc
+                .drain_delivery(DELIVERY_DRAIN_TIMEOUT)
+                .await;
             let _ = self
                 .inner
                 .inner
@@ -81,6 +88,11 @@ impl<T: SessionStream> Session<T> {
     pub async fn init_conn(&mut self) -> bool {
         self.eval_session_params().await;
 
+        // IP reputation
+        if !self.check_ip_reputation().await {
+            return false;
+        }
+
         let config = &self.core.core.smtp.session.connect;
 
         // Sieve filtering
@@ -14,7 +14,11 @@ use mail_send::smtp::tls::build_tls_connector;
 use queue::manager::SpawnQueue;
 use reporting::scheduler::SpawnReport;
 use tokio::sync::mpsc;
-use utils::{config::Config, snowflake::SnowflakeIdGenerator};
+use utils::{
+    config::Config,
+    map::ttl_dashmap::{TtlDashMap, TtlMap},
+    snowflake::SnowflakeIdGenerator,
+};
 
 pub mod core;
 pub mod inbound;
@@ -49,6 +53,7 @@ impl SMTP {
                 ThrottleKeyHasherBuilder::default(),
                 shard,
             ),
+            domain_concurrency: DashMap::with_capacity_and_shard_amount(capacity, shard),
             queue_tx,
             report_tx,
             queue_id_gen: config
@@ -62,9 +67,15 @@ impl SMTP {
             },
             ipc,
             script_cache: ScriptCache::parse(config),
+            reputation_cache: TtlDashMap::with_capacity(capacity, shard),
         };
         let inner = SmtpInstance::new(core, inner);
 
+        // Index messages queued by previous versions before delivering them
+        if let Err(err) = SMTP::from(inner.clone()).migrate_queue_domain_index().await {
+            trc::error!(err.details("Failed to index queued messages by domain"));
+        }
+
         // Spawn queue manager
         queue_rx.spawn(inner.clone());
 
@@ -4,8 +4,15 @@
  * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
  */
 
+use std::sync::Arc;
+
 use common::config::smtp::resolver::Tlsa;
-use rustls_pki_types::CertificateDer;
+use rustls::{
+    client::{danger::ServerCertVerifier, WebPkiServerVerifier},
+    crypto::ring::default_provider,
+    RootCertStore,
+};
+use rustls_pki_types::{CertificateDer, ServerName, UnixTime};
 use sha1::Digest;
 use sha2::{Sha256, Sha512};
 use trc::DaneEvent;
@@ -45,7 +52,7 @@ impl TlsaVerify for Tlsa {
         };
 
         let mut matched_end_entity = false;
-        let mut matched_intermediate = false;
+        let mut matched_trust_anchor = None;
         'outer: for (pos, der_certificate) in certificates.iter().enumerate() {
             // Parse certificate
             let certificate = match X509Certificate::from_der(der_certificate.as_ref()) {
@@ -108,25 +115,22 @@ impl TlsaVerify for Tlsa {
 
                         if is_end_entity {
                             matched_end_entity = true;
-                            if !self.has_intermediates {
-                                break 'outer;
-                            }
                         } else {
-                            matched_intermediate = true;
-                            break 'outer;
+                            matched_trust_anchor = Some(pos);
                         }
+                        break 'outer;
                     }
                 }
             }
         }
 
         // DANE is valid if:
-        // - EE matched even if no TA matched
-        // - Both EE and TA matched
-        // - EE is not present and TA matched
-        if (self.has_end_entities && matched_end_entity)
-            || ((self.has_end_entities == matched_end_entity)
-                && (self.has_intermediates == matched_intermediate))
+        // - A DANE-EE record matched the server certificate
+        // - A DANE-TA record matched a certificate in the chain and the
+        //   server certificate chains up to it for this hostname
+        if matched_end_entity
+            || matched_trust_anchor
+                .is_some_and(|pos| verify_chain(session_id, hostname, &certificates[..=pos]))
         {
             trc::event!(
                 Dane(DaneEvent::AuthenticationSuccess),
@@ -142,10 +146,57 @@ impl TlsaVerify for Tlsa {
                 Hostname = hostname.to_string(),
             );
 
-            Err(Status::PermanentFailure(Error::DaneError(ErrorDetails {
+            Err(Status::TemporaryFailure(Error::DaneError(ErrorDetails {
                 entity: hostname.to_string(),
                 details: "No matching certificates found in TLSA records".to_string(),
             })))
         }
     }
 }
+
+fn verify_chain(session_id: u64, hostname: &str, certificates: &[CertificateDer<'_>]) -> bool {
+    let (trust_anchor, intermediates) = match certificates.split_last() {
+        Some((trust_anchor, chain)) if !chain.is_empty() => (trust_anchor, &chain[1..]),
+        _ => return false,
+    };
+    let server_name = match ServerName::try_from(hostname.trim_end_matches('.').to_string()) {
+        Ok(server_name) => server_name,
+        Err(_) => return false,
+    };
+    let mut roots = RootCertStore::empty();
+    let result = roots
+        .add(trust_anchor.clone().into_owned())
+        .map_err(|err| err.to_string())
+        .and_then(|_| {
+            WebPkiServerVerifier::builder_with_provider(
+                Arc::new(roots),
+                Arc::new(default_provider()),
+            )
+            .build()
+            .map_err(|err| err.to_string())
+        })
+        .and_then(|verifier| {
+            verifier
+                .verify_server_cert(
+                    &certificates[0],
+                    intermediates,
+                    &server_name,
+                    &[],
+                    UnixTime::now(),
+                )
+                .map_err(|err| err.to_string())
+        });
+
+    match result {
+        Ok(_) => true,
+        Err(reason) => {
+            trc::event!(
+                Dane(DaneEvent::AuthenticationFailure),
+                SpanId = session_id,
+                Hostname = hostname.to_string(),
+                Reason = reason,
+            );
+            false
+        }
+    }
+}
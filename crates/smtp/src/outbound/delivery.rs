@@ -103,7 +103,7 @@ impl DeliveryAttempt {
 
     async fn deliver_task(mut self, core: SMTP, mut message: Message) {
         // Check that the message still has recipients to be delivered
-        let has_pending_delivery = message.has_pending_delivery();
+        let has_pending_delivery = message.has_pending_delivery(&core).await;
         let span_id = message.span_id;
 
         // Send any due Delivery Status Notifications
@@ -248,13 +248,20 @@ impl DeliveryAttempt {
                 }
             }
 
-            // Obtain next hop
-            let (mut remote_hosts, is_smtp) = match core
-                .core
-                .eval_if::<String, _>(&queue_config.next_hop, &envelope, message.span_id)
-                .await
-                .and_then(|name| core.core.get_relay_host(&name, message.span_id))
-            {
+            // Obtain next hop, transport map relays take precedence
+            let transport_relay = recipients
+                .iter()
+                .find(|r| r.domain_idx == domain_idx)
+                .and_then(|r| queue_config.transport_map.relay(&r.address_lcase));
+            let next_hop = if let Some(name) = transport_relay {
+                core.core.get_relay_host(name, message.span_id)
+            } else {
+                core.core
+                    .eval_if::<String, _>(&queue_config.next_hop, &envelope, message.span_id)
+                    .await
+                    .and_then(|name| core.core.get_relay_host(&name, message.span_id))
+            };
+            let (mut remote_hosts, is_smtp) = match next_hop {
                 Some(next_hop) if next_hop.protocol == ServerProtocol::Http => {
                     // Deliver message locally
                     let delivery_result = message
@@ -284,6 +291,25 @@ impl DeliveryAttempt {
                 None => (Vec::with_capacity(0), true),
             };
 
+            // Limit concurrent deliveries to the destination domain, messages over
+            // the limit are put on hold without consuming a retry attempt
+            let max_concurrent = core
+                .core
+                .eval_if::<u64, _>(&queue_config.max_concurrent, &envelope, message.span_id)
+                .await
+                .unwrap_or(0);
+            let _domain_in_flight = if max_concurrent > 0 {
+                match core.is_domain_allowed(&domain.domain, max_concurrent, message.span_id) {
+                    Ok(domain_in_flight) => Some(domain_in_flight),
+                    Err(err) => {
+                        message.domains[domain_idx].set_throttle_error(err, &mut on_hold);
+                        continue 'next_domain;
+                    }
+                }
+            } else {
+                None
+            };
+
             // Prepare TLS strategy
             let mut tls_strategy = TlsStrategy {
                 mta_sts: core
@@ -566,63 +592,8 @@ impl DeliveryAttempt {
                 .unwrap_or(2);
             let mut last_status = Status::Scheduled;
             'next_host: for remote_host in &remote_hosts {
-                // Validate MTA-STS
-                envelope.mx = remote_host.hostname();
-                if let Some(mta_sts_policy) = &mta_sts_policy {
-                    let strict = mta_sts_policy.enforce();
-                    if !mta_sts_policy.verify(envelope.mx) {
-                        // Report MTA-STS failed verification
-                        if let Some(tls_report) = &tls_report {
-                            core.schedule_report(TlsEvent {
-                                policy: mta_sts_policy.into(),
-                                domain: domain.domain.to_string(),
-                                failure: FailureDetails::new(ResultType::ValidationFailure)
-                                    .with_receiving_mx_hostname(envelope.mx)
-                                    .with_failure_reason_code("MX not authorized by policy.")
-                                    .into(),
-                                tls_record: tls_report.record.clone(),
-                                interval: tls_report.interval,
-                            })
-                            .await;
-                        }
-
-                        trc::event!(
-                            MtaSts(MtaStsEvent::NotAuthorized),
-                            SpanId = message.span_id,
-                            Domain = domain.domain.clone(),
-                            Hostname = envelope.mx.to_string(),
-                            Details = mta_sts_policy
-                                .mx
-                                .iter()
-                                .map(|mx| trc::Value::String(mx.to_string()))
-                                .collect::<Vec<_>>(),
-                            Strict = strict,
-                        );
-
-                        if strict {
-                            last_status = Status::PermanentFailure(Error::MtaStsError(format!(
-                                "MX {:?} not authorized by policy.",
-                                envelope.mx
-                            )));
-                            continue 'next_host;
-                        }
-                    } else {
-                        trc::event!(
-                            MtaSts(MtaStsEvent::Authorized),
-                            SpanId = message.span_id,
-                            Domain = domain.domain.clone(),
-                            Hostname = envelope.mx.to_string(),
-                            Details = mta_sts_policy
-                                .mx
-                                .iter()
-                                .map(|mx| trc::Value::String(mx.to_string()))
-                                .collect::<Vec<_>>(),
-                            Strict = strict,
-                        );
-                    }
-                }
-
                 // Obtain source and remote IPs
+                envelope.mx = remote_host.hostname();
                 let time = Instant::now();
                 let resolve_result = match core
                     .resolve_host(remote_host, &envelope, max_multihomed, message.span_id)
@@ -678,7 +649,7 @@ impl DeliveryAttempt {
                     let strict = tls_strategy.is_dane_required();
                     match core.tlsa_lookup(format!("_25._tcp.{}.", envelope.mx)).await {
                         Ok(Some(tlsa)) => {
-                            if tlsa.has_end_entities {
+                            if tlsa.has_end_entities || tlsa.has_intermediates {
                                 trc::event!(
                                     Dane(DaneEvent::TlsaRecordFetch),
                                     SpanId = message.span_id,
@@ -718,7 +689,7 @@ impl DeliveryAttempt {
 
                                 if strict {
                                     last_status =
-                                        Status::PermanentFailure(Error::DaneError(ErrorDetails {
+                                        Status::TemporaryFailure(Error::DaneError(ErrorDetails {
                                             entity: envelope.mx.to_string(),
                                             details: "No valid TLSA records were found".to_string(),
                                         }));
@@ -756,7 +727,7 @@ impl DeliveryAttempt {
                                 }
 
                                 last_status =
-                                    Status::PermanentFailure(Error::DaneError(ErrorDetails {
+                                    Status::TemporaryFailure(Error::DaneError(ErrorDetails {
                                         entity: envelope.mx.to_string(),
                                         details: "No TLSA DNSSEC records found".to_string(),
                                     }));
@@ -807,7 +778,7 @@ impl DeliveryAttempt {
                                         .await;
                                     }
 
-                                    Status::PermanentFailure(Error::DaneError(ErrorDetails {
+                                    Status::TemporaryFailure(Error::DaneError(ErrorDetails {
                                         entity: envelope.mx.to_string(),
                                         details: "No TLSA records found".to_string(),
                                     }))
@@ -823,6 +794,63 @@ impl DeliveryAttempt {
                     None
                 };
 
+                // Validate MTA-STS, DANE takes precedence when both are present
+                if let Some(mta_sts_policy) =
+                    mta_sts_policy.as_ref().filter(|_| dane_policy.is_none())
+                {
+                    let strict = mta_sts_policy.enforce();
+                    if !mta_sts_policy.verify(envelope.mx) {
+                        // Report MTA-STS failed verification
+                        if let Some(tls_report) = &tls_report {
+                            core.schedule_report(TlsEvent {
+                                policy: mta_sts_policy.into(),
+                                domain: domain.domain.to_string(),
+                                failure: FailureDetails::new(ResultType::ValidationFailure)
+                                    .with_receiving_mx_hostname(envelope.mx)
+                                    .with_failure_reason_code("MX not authorized by policy.")
+                                    .into(),
+                                tls_record: tls_report.record.clone(),
+                                interval: tls_report.interval,
+                            })
+                            .await;
+                        }
+
+                        trc::event!(
+                            MtaSts(MtaStsEvent::NotAuthorized),
+                            SpanId = message.span_id,
+                            Domain = domain.domain.clone(),
+                            Hostname = envelope.mx.to_string(),
+                            Details = mta_sts_policy
+                                .mx
+                                .iter()
+                                .map(|mx| trc::Value::String(mx.to_string()))
+                                .collect::<Vec<_>>(),
+                            Strict = strict,
+                        );
+
+                        if strict {
+                            last_status = Status::PermanentFailure(Error::MtaStsError(format!(
+                                "MX {:?} not authorized by policy.",
+                                envelope.mx
+                            )));
+                            continue 'next_host;
+                        }
+                    } else {
+                        trc::event!(
+                            MtaSts(MtaStsEvent::Authorized),
+                            SpanId = message.span_id,
+                            Domain = domain.domain.clone(),
+                            Hostname = envelope.mx.to_string(),
+                            Details = mta_sts_policy
+                                .mx
+                                .iter()
+                                .map(|mx| trc::Value::String(mx.to_string()))
+                                .collect::<Vec<_>>(),
+                            Strict = strict,
+                        );
+                    }
+                }
+
                 // Try each IP address
                 'next_ip: for remote_ip in resolve_result.remote_ips {
                     // Set source IP, if any
@@ -907,19 +935,29 @@ impl DeliveryAttempt {
                         }
                     };
 
-                    // Obtain session parameters
-                    let local_hostname = core
-                        .core
-                        .eval_if::<String, _>(&queue_config.hostname, &envelope, message.span_id)
-                        .await
-                        .filter(|s| !s.is_empty())
-                        .unwrap_or_else(|| {
-                            trc::event!(
-                                Delivery(DeliveryEvent::MissingOutboundHostname),
-                                SpanId = message.span_id,
-                            );
-                            "local.host".to_string()
-                        });
+                    // Obtain session parameters, the EHLO name matches the source IP's PTR
+                    // when configured and is reused for the EHLO sent after STARTTLS
+                    let local_hostname = if let Some(hostname) =
+                        source_ip.and_then(|ip| queue_config.source_ip.hostnames.get(&ip))
+                    {
+                        hostname.clone()
+                    } else {
+                        core.core
+                            .eval_if::<String, _>(
+                                &queue_config.hostname,
+                                &envelope,
+                                message.span_id,
+                            )
+                            .await
+                            .filter(|s| !s.is_empty())
+                            .unwrap_or_else(|| {
+                                trc::event!(
+                                    Delivery(DeliveryEvent::MissingOutboundHostname),
+                                    SpanId = message.span_id,
+                                );
+                                "local.host".to_string()
+                            })
+                    };
                     let params = SessionParams {
                         session_id: message.span_id,
                         core: &core,
@@ -954,7 +992,12 @@ impl DeliveryAttempt {
                         || (message.flags & MAIL_REQUIRETLS) != 0
                         || mta_sts_policy.is_some()
                         || dane_policy.is_some();
-                    let tls_connector = if allow_invalid_certs || remote_host.allow_invalid_certs()
+                    // DANE-EE and DANE-TA policies are verified after the handshake
+                    // (TA chains are validated against the matched anchor), while
+                    // hosts without usable TLSA records keep the WebPKI verifier.
+                    let tls_connector = if allow_invalid_certs
+                        || remote_host.allow_invalid_certs()
+                        || dane_policy.is_some()
                     {
                         &core.inner.connectors.dummy_verify
                     } else {
@@ -1351,10 +1394,29 @@ impl DeliveryAttempt {
 
 impl Message {
     /// Marks as failed all domains that reached their expiration time
-    pub fn has_pending_delivery(&mut self) -> bool {
+    /// or their maximum number of delivery attempts
+    pub async fn has_pending_delivery(&mut self, core: &SMTP) -> bool {
         let now = now();
         let mut has_pending_delivery = false;
 
+        for idx in 0..self.domains.len() {
+            let domain = &self.domains[idx];
+            if matches!(domain.status, Status::TemporaryFailure(_)) && domain.expires > now {
+                let max_attempts = core
+                    .core
+                    .eval_if::<usize, _>(
+                        &core.core.smtp.queue.max_attempts,
+                        &QueueEnvelope::new(self, idx),
+                        self.span_id,
+                    )
+                    .await
+                    .unwrap_or(0);
+                if max_attempts > 0 && self.domains[idx].retry.inner as usize >= max_attempts {
+                    self.domains[idx].expires = now;
+                }
+            }
+        }
+
         for (idx, domain) in self.domains.iter_mut().enumerate() {
             match &domain.status {
                 Status::TemporaryFailure(_) if domain.expires <= now => {
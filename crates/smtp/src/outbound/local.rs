@@ -80,8 +80,15 @@ impl Message {
             }
         };
 
-        // Process delivery results
-        for (rcpt, result) in pending_recipients.into_iter().zip(delivery_result) {
+        // Process delivery results, returned in the same order as the recipients
+        let mut delivery_result = delivery_result.into_iter();
+        for rcpt in pending_recipients {
+            let result = delivery_result
+                .next()
+                .map(|result| result.result)
+                .unwrap_or_else(|| DeliveryResult::TemporaryFailure {
+                    reason: "Missing delivery result.".into(),
+                });
             rcpt.flags |= RCPT_STATUS_CHANGED;
             match result {
                 DeliveryResult::Success => {
@@ -18,7 +18,7 @@ use crate::{
     queue::{Error, ErrorDetails, Status},
 };
 
-use super::NextHop;
+use super::{is_permanent_dns_error, NextHop};
 
 pub struct IpLookupResult {
     pub source_ipv4: Option<IpAddr>,
@@ -100,7 +100,7 @@ impl SMTP {
             )
             .await
             .map_err(|err| {
-                if let mail_auth::Error::DnsRecordNotFound(_) = &err {
+                if is_permanent_dns_error(&err) {
                     Status::PermanentFailure(Error::ConnectionError(ErrorDetails {
                         entity: remote_host.hostname().to_string(),
                         details: "record not found for MX".to_string(),
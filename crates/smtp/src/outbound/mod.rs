@@ -10,6 +10,7 @@ use common::config::{
     server::ServerProtocol,
     smtp::queue::{RelayHost, RequireOptional},
 };
+use mail_auth::hickory_resolver::proto::op::ResponseCode;
 use mail_send::Credentials;
 use smtp_proto::{Response, Severity};
 
@@ -55,7 +56,7 @@ impl Status<(), Error> {
                     entity: hostname.to_string(),
                     details: command.trim().to_string(),
                 };
-                if reply.severity() == Severity::PermanentNegativeCompletion {
+                if is_permanent_reply(command, &reply) {
                     Status::PermanentFailure(Error::UnexpectedResponse(HostResponse {
                         hostname: details,
                         response: reply,
@@ -88,7 +89,7 @@ impl Status<(), Error> {
                 details: "STARTTLS".to_string(),
             };
 
-            if response.severity() == Severity::PermanentNegativeCompletion {
+            if is_permanent_reply("STARTTLS", &response) {
                 Status::PermanentFailure(Error::UnexpectedResponse(HostResponse {
                     hostname,
                     response,
@@ -152,7 +153,7 @@ impl Status<(), Error> {
 impl From<mail_auth::Error> for Status<(), Error> {
     fn from(err: mail_auth::Error) -> Self {
         match &err {
-            mail_auth::Error::DnsRecordNotFound(code) => {
+            mail_auth::Error::DnsRecordNotFound(code) if is_permanent_dns_error(&err) => {
                 Status::PermanentFailure(Error::DnsError(format!("Domain not found: {code:?}")))
             }
             _ => Status::TemporaryFailure(Error::DnsError(err.to_string())),
@@ -160,6 +161,26 @@ impl From<mail_auth::Error> for Status<(), Error> {
     }
 }
 
+// Negative replies with a 5xx code are permanent, except for 552 replies to
+// RCPT TO which RFC 5321 (section 4.5.3.1.10) asks clients to treat as 452.
+pub fn is_permanent_reply(command: &str, reply: &Response<String>) -> bool {
+    reply.severity() == Severity::PermanentNegativeCompletion
+        && !(reply.code == 552
+            && command
+                .trim_start()
+                .get(..4)
+                .map_or(false, |cmd| cmd.eq_ignore_ascii_case("RCPT")))
+}
+
+// Only authoritative answers stating that a name or record does not exist are
+// permanent, resolver failures such as SERVFAIL or REFUSED are retried.
+pub fn is_permanent_dns_error(err: &mail_auth::Error) -> bool {
+    matches!(
+        err,
+        mail_auth::Error::DnsRecordNotFound(ResponseCode::NXDomain | ResponseCode::NoError)
+    )
+}
+
 impl From<mta_sts::Error> for Status<(), Error> {
     fn from(err: mta_sts::Error) -> Self {
         match &err {
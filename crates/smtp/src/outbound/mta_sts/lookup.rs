@@ -54,13 +54,56 @@ impl SMTP {
         };
 
         // Check if the policy has been cached
-        if let Some(value) = self.core.smtp.resolvers.cache.mta_sts.get(domain) {
+        let cached = self.core.smtp.resolvers.cache.mta_sts.get(domain);
+        if let Some(value) = &cached {
             if value.id == record.id {
-                return Ok(value);
+                return Ok(value.clone());
             }
         }
 
-        // Fetch policy
+        // Fetch and parse the new policy
+        let policy = match self
+            .fetch_mta_sts_policy(domain, record.id.clone(), timeout)
+            .await
+        {
+            Ok(policy) => policy,
+            Err(err) => {
+                // Keep using a previously cached policy until it expires (RFC 8461, section 10.2)
+                return if let Some(value) = cached {
+                    trc::event!(
+                        MtaSts(trc::MtaStsEvent::PolicyFetchError),
+                        Domain = domain.to_string(),
+                        Id = value.id.clone(),
+                        Details = "Using previously cached policy",
+                        Reason = err.to_string(),
+                    );
+
+                    Ok(value)
+                } else {
+                    Err(err)
+                };
+            }
+        };
+        let valid_until = Instant::now()
+            + Duration::from_secs(if (3600..31557600).contains(&policy.max_age) {
+                policy.max_age
+            } else {
+                86400
+            });
+
+        Ok(self.core.smtp.resolvers.cache.mta_sts.insert(
+            domain.to_string(),
+            Arc::new(policy),
+            valid_until,
+        ))
+    }
+
+    async fn fetch_mta_sts_policy(
+        &self,
+        domain: &str,
+        id: String,
+        timeout: Duration,
+    ) -> Result<Policy, Error> {
         #[cfg(not(feature = "test_mode"))]
         let bytes = reqwest::Client::builder()
             .user_agent(common::USER_AGENT)
@@ -70,29 +113,18 @@ impl SMTP {
             .get(format!("https://mta-sts.{domain}/.well-known/mta-sts.txt"))
             .send()
             .await?
+            .error_for_status()?
             .bytes_with_limit(MAX_POLICY_SIZE)
             .await?
             .ok_or_else(|| Error::InvalidPolicy("Policy too large".to_string()))?;
         #[cfg(feature = "test_mode")]
         let bytes = STS_TEST_POLICY.lock().clone();
 
-        // Parse policy
-        let policy = Policy::parse(
+        Policy::parse(
             std::str::from_utf8(&bytes).map_err(|err| Error::InvalidPolicy(err.to_string()))?,
-            record.id.clone(),
-        )?;
-        let valid_until = Instant::now()
-            + Duration::from_secs(if (3600..31557600).contains(&policy.max_age) {
-                policy.max_age
-            } else {
-                86400
-            });
-
-        Ok(self.core.smtp.resolvers.cache.mta_sts.insert(
-            domain.to_string(),
-            Arc::new(policy),
-            valid_until,
-        ))
+            id,
+        )
+        .map_err(Error::InvalidPolicy)
     }
 
     #[cfg(feature = "test_mode")]
@@ -14,8 +14,9 @@ pub trait ParsePolicy {
 
 impl ParsePolicy for Policy {
     fn parse(mut data: &str, id: String) -> Result<Policy, String> {
-        let mut mode = Mode::None;
-        let mut max_age: u64 = 86400;
+        let mut mode = None;
+        let mut max_age = None;
+        let mut has_version = false;
         let mut mx = Vec::new();
 
         while !data.is_empty() {
@@ -38,9 +39,10 @@ impl ParsePolicy for Policy {
                         }
                     }
                     "max_age" => {
-                        if let Ok(value) = value.parse() {
-                            max_age = value;
-                        }
+                        max_age = value
+                            .parse::<u64>()
+                            .map_err(|_| format!("Invalid max_age {value:?}."))?
+                            .into();
                     }
                     "mode" => {
                         mode = match value {
@@ -48,12 +50,14 @@ impl ParsePolicy for Policy {
                             "testing" => Mode::Testing,
                             "none" => Mode::None,
                             _ => return Err(format!("Unsupported mode {value:?}.")),
-                        };
+                        }
+                        .into();
                     }
                     "version" => {
                         if !value.eq_ignore_ascii_case("STSv1") {
                             return Err(format!("Unsupported version {value:?}."));
                         }
+                        has_version = true;
                     }
                     _ => (),
                 }
@@ -62,15 +66,20 @@ impl ParsePolicy for Policy {
             }
         }
 
-        if !mx.is_empty() {
+        if mx.is_empty() {
+            Err("No 'mx' entries found.".to_string())
+        } else if !has_version {
+            Err("Missing 'version' field.".to_string())
+        } else if let (Some(mode), Some(max_age)) = (mode, max_age) {
+            // Policies are cached for at most one year (RFC 8461, section 3.2)
             Ok(Policy {
                 id,
                 mode,
                 mx,
-                max_age,
+                max_age: max_age.min(31557600),
             })
         } else {
-            Err("No 'mx' entries found.".to_string())
+            Err("Missing 'mode' or 'max_age' field.".to_string())
         }
     }
 }
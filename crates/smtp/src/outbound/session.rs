@@ -7,10 +7,11 @@
 use common::config::smtp::queue::RequireOptional;
 use mail_send::Credentials;
 use smtp_proto::{
-    EhloResponse, Severity, EXT_CHUNKING, EXT_DSN, EXT_REQUIRE_TLS, EXT_SIZE, EXT_SMTP_UTF8,
-    MAIL_REQUIRETLS, MAIL_RET_FULL, MAIL_RET_HDRS, MAIL_SMTPUTF8, RCPT_NOTIFY_DELAY,
+    EhloResponse, Response, Severity, EXT_CHUNKING, EXT_DSN, EXT_REQUIRE_TLS, EXT_SIZE,
+    EXT_SMTP_UTF8, MAIL_REQUIRETLS, MAIL_RET_FULL, MAIL_RET_HDRS, MAIL_SMTPUTF8, RCPT_NOTIFY_DELAY,
     RCPT_NOTIFY_FAILURE, RCPT_NOTIFY_NEVER, RCPT_NOTIFY_SUCCESS,
 };
+use std::borrow::Cow;
 use std::time::Duration;
 use std::{fmt::Write, time::Instant};
 use tokio::io::{AsyncRead, AsyncWrite};
@@ -24,7 +25,7 @@ use crate::{
 
 use crate::queue::{Error, Message, Recipient, Status};
 
-use super::{client::SmtpClient, TlsStrategy};
+use super::{client::SmtpClient, is_permanent_reply, TlsStrategy};
 
 pub struct SessionParams<'x> {
     pub core: &'x SMTP,
@@ -117,7 +118,35 @@ impl Message {
         // MAIL FROM
         let time = Instant::now();
         smtp_client.timeout = params.timeout_mail;
-        let cmd = self.build_mail_from(&capabilities);
+        let has_utf8 = capabilities.has_capability(EXT_SMTP_UTF8);
+        let return_path = if has_utf8 {
+            Some(Cow::Borrowed(self.return_path.as_str()))
+        } else {
+            ascii_address(&self.return_path)
+        };
+        let cmd = self.build_mail_from(
+            return_path.as_deref().unwrap_or(&self.return_path),
+            &capabilities,
+        );
+        if return_path.is_none() {
+            // Internationalized local parts cannot be downgraded (RFC 6531)
+            trc::event!(
+                Delivery(DeliveryEvent::MailFromRejected),
+                SpanId = params.session_id,
+                Hostname = params.hostname.to_string(),
+                From = self.return_path.to_string(),
+                Reason = "Remote host does not support SMTPUTF8",
+            );
+
+            smtp_client.quit().await;
+            return Status::PermanentFailure(Error::UnexpectedResponse(HostResponse {
+                hostname: ErrorDetails {
+                    entity: params.hostname.to_string(),
+                    details: cmd.trim().to_string(),
+                },
+                response: smtputf8_not_supported(),
+            }));
+        }
         match smtp_client.cmd(cmd.as_bytes()).await.and_then(|r| {
             if r.is_positive_completion() {
                 Ok(r)
@@ -166,7 +195,37 @@ impl Message {
                 continue;
             }
 
-            let cmd = self.build_rcpt_to(rcpt, &capabilities);
+            let address = if has_utf8 {
+                Some(Cow::Borrowed(rcpt.address.as_str()))
+            } else {
+                ascii_address(&rcpt.address)
+            };
+            let cmd = self.build_rcpt_to(
+                rcpt,
+                address.as_deref().unwrap_or(&rcpt.address),
+                &capabilities,
+            );
+            if address.is_none() {
+                trc::event!(
+                    Delivery(DeliveryEvent::RcptToRejected),
+                    SpanId = params.session_id,
+                    Hostname = params.hostname.to_string(),
+                    To = rcpt.address.to_string(),
+                    Reason = "Remote host does not support SMTPUTF8",
+                );
+
+                rcpt.flags |= RCPT_STATUS_CHANGED;
+                rcpt.status = Status::PermanentFailure(HostResponse {
+                    hostname: ErrorDetails {
+                        entity: params.hostname.to_string(),
+                        details: cmd.trim().to_string(),
+                    },
+                    response: smtputf8_not_supported(),
+                });
+                total_completed += 1;
+                continue;
+            }
+
             match smtp_client.cmd(cmd.as_bytes()).await {
                 Ok(response) => match response.severity() {
                     Severity::PositiveCompletion => {
@@ -188,7 +247,7 @@ impl Message {
                             }),
                         ));
                     }
-                    severity => {
+                    _ => {
                         trc::event!(
                             Delivery(DeliveryEvent::RcptToRejected),
                             SpanId = params.session_id,
@@ -207,7 +266,7 @@ impl Message {
                             response,
                         };
                         rcpt.flags |= RCPT_STATUS_CHANGED;
-                        rcpt.status = if severity == Severity::PermanentNegativeCompletion {
+                        rcpt.status = if is_permanent_reply(&cmd, &response.response) {
                             total_completed += 1;
                             Status::PermanentFailure(response)
                         } else {
@@ -389,9 +448,9 @@ impl Message {
         }
     }
 
-    fn build_mail_from(&self, capabilities: &EhloResponse<String>) -> String {
-        let mut mail_from = String::with_capacity(self.return_path.len() + 60);
-        let _ = write!(mail_from, "MAIL FROM:<{}>", self.return_path);
+    fn build_mail_from(&self, return_path: &str, capabilities: &EhloResponse<String>) -> String {
+        let mut mail_from = String::with_capacity(return_path.len() + 60);
+        let _ = write!(mail_from, "MAIL FROM:<{}>", return_path);
         if capabilities.has_capability(EXT_SIZE) {
             let _ = write!(mail_from, " SIZE={}", self.size);
         }
@@ -416,9 +475,14 @@ impl Message {
         mail_from
     }
 
-    fn build_rcpt_to(&self, rcpt: &Recipient, capabilities: &EhloResponse<String>) -> String {
-        let mut rcpt_to = String::with_capacity(rcpt.address.len() + 60);
-        let _ = write!(rcpt_to, "RCPT TO:<{}>", rcpt.address);
+    fn build_rcpt_to(
+        &self,
+        rcpt: &Recipient,
+        address: &str,
+        capabilities: &EhloResponse<String>,
+    ) -> String {
+        let mut rcpt_to = String::with_capacity(address.len() + 60);
+        let _ = write!(rcpt_to, "RCPT TO:<{}>", address);
         if capabilities.has_capability(EXT_DSN) {
             if rcpt.has_flag(RCPT_NOTIFY_SUCCESS | RCPT_NOTIFY_FAILURE | RCPT_NOTIFY_DELAY) {
                 rcpt_to.push_str(" NOTIFY=");
@@ -505,3 +569,28 @@ impl TlsStrategy {
             || self.is_mta_sts_required()
     }
 }
+
+// Encodes IDN domains as A-labels for hosts without SMTPUTF8,
+// returns None when the local part is not ASCII.
+fn ascii_address(address: &str) -> Option<Cow<'_, str>> {
+    if address.is_ascii() {
+        Some(Cow::Borrowed(address))
+    } else {
+        let (local_part, domain) = address.rsplit_once('@')?;
+        if local_part.is_ascii() {
+            idna::domain_to_ascii(domain)
+                .ok()
+                .map(|domain| Cow::Owned(format!("{local_part}@{domain}")))
+        } else {
+            None
+        }
+    }
+}
+
+fn smtputf8_not_supported() -> Response<String> {
+    Response {
+        code: 553,
+        esc: [5, 6, 7],
+        message: "Remote host does not support SMTPUTF8".to_string(),
+    }
+}
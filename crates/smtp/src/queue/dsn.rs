@@ -249,30 +249,22 @@ impl Message {
         let has_delay = !txt_delay.is_empty();
         let has_failure = !txt_failed.is_empty();
 
-        let mut txt = String::with_capacity(txt_len + 128);
-        let (subject, is_mixed) = if has_success && !has_delay && !has_failure {
-            txt.push_str(
-                "Your message has been successfully delivered to the following recipients:\r\n\r\n",
-            );
-            ("Successfully delivered message", false)
+        let templates = &config.dsn.templates;
+        let (template, is_mixed) = if has_success && !has_delay && !has_failure {
+            (&templates.success, false)
         } else if has_delay && !has_success && !has_failure {
-            txt.push_str("There was a temporary problem delivering your message to the following recipients:\r\n\r\n");
-            ("Warning: Delay in message delivery", false)
+            (&templates.delay, false)
         } else if has_failure && !has_success && !has_delay {
-            txt.push_str(
-                "Your message could not be delivered to the following recipients:\r\n\r\n",
-            );
-            ("Failed to deliver message", false)
+            (&templates.failure, false)
         } else if has_success {
-            txt.push_str("Your message has been partially delivered:\r\n\r\n");
-            ("Partially delivered message", true)
+            (&templates.partial, true)
         } else {
-            txt.push_str("Your message could not be delivered to some recipients:\r\n\r\n");
-            (
-                "Warning: Temporary and permanent failures during message delivery",
-                true,
-            )
+            (&templates.mixed, true)
         };
+        let subject = template.subject.as_str();
+        let mut txt = String::with_capacity(txt_len + template.body.len() + 128);
+        txt.push_str(&template.body);
+        txt.push_str("\r\n\r\n");
 
         if has_success {
             if is_mixed {
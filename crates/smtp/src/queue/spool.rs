@@ -7,17 +7,23 @@
 use crate::queue::DomainPart;
 use std::borrow::Cow;
 use std::time::{Duration, SystemTime};
-use store::write::key::DeserializeBigEndian;
-use store::write::{now, BatchBuilder, Bincode, BlobOp, QueueClass, QueueEvent, ValueClass};
-use store::{Deserialize, IterateParams, Serialize, ValueKey, U64_LEN};
-use trc::ServerEvent;
+use store::write::assert::{AssertValue, HashedValue};
+use store::write::key::{DeserializeBigEndian, KeySerializer};
+use store::write::{
+    now, AnyKey, BatchBuilder, Bincode, BlobOp, QueueClass, QueueDomain, QueueEvent, ValueClass,
+};
+use store::{
+    Deserialize, IterateParams, Serialize, ValueKey, QUEUE_DOMAIN_PREFIX, SUBSPACE_REPORT_OUT,
+    U64_LEN,
+};
+use trc::{AddContext, ServerEvent};
 use utils::BlobHash;
 
 use crate::core::SMTP;
 
 use super::{
-    Domain, Event, Message, MessageSource, QueueEnvelope, QueueId, QuotaKey, Recipient, Schedule,
-    Status,
+    Domain, ErrorDetails, Event, HostResponse, Message, MessageSource, QueueEnvelope, QueueId,
+    QuotaKey, Recipient, Schedule, Status,
 };
 
 pub const LOCK_EXPIRY: u64 = 300;
@@ -29,6 +35,31 @@ pub struct QueueEventLock {
     pub lock_expiry: u64,
 }
 
+#[derive(Debug, Default)]
+pub struct QueueFilter<'x> {
+    pub text: Option<&'x str>,
+    pub from: Option<&'x str>,
+    pub to: Option<&'x str>,
+    pub before: Option<u64>,
+    pub after: Option<u64>,
+    pub domains: Option<&'x [String]>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct QueuePagination {
+    pub page: usize,
+    pub limit: usize,
+    pub max_total: usize,
+    pub range_start: QueueId,
+    pub range_end: QueueId,
+}
+
+#[derive(Debug, Default)]
+pub struct QueuePage {
+    pub items: Vec<Message>,
+    pub total: usize,
+}
+
 impl SMTP {
     pub fn new_message(
         &self,
@@ -145,6 +176,149 @@ impl SMTP {
         }
     }
 
+    // Returns a page of queued messages matching the filter. Only the message
+    // metadata is read, blobs are never loaded.
+    pub async fn list_queued(
+        &self,
+        filter: &QueueFilter<'_>,
+        pagination: QueuePagination,
+    ) -> trc::Result<QueuePage> {
+        let from_key = ValueKey::from(ValueClass::Queue(QueueClass::Message(
+            pagination.range_start,
+        )));
+        let to_key = ValueKey::from(ValueClass::Queue(QueueClass::Message(pagination.range_end)));
+        let mut offset = pagination.page.saturating_sub(1) * pagination.limit;
+        let mut page = QueuePage::default();
+
+        self.core
+            .storage
+            .data
+            .iterate(
+                IterateParams::new(from_key, to_key).ascending(),
+                |_, value| {
+                    let message = Bincode::<Message>::deserialize(value)?.inner;
+
+                    if filter.matches(&message) {
+                        if offset == 0 {
+                            if pagination.limit == 0 || page.items.len() < pagination.limit {
+                                page.items.push(message);
+                            }
+                        } else {
+                            offset -= 1;
+                        }
+
+                        page.total += 1;
+                    }
+
+                    Ok(pagination.max_total == 0 || page.total < pagination.max_total)
+                },
+            )
+            .await
+            .caused_by(trc::location!())
+            .map(|_| page)
+    }
+
+    // Cancels delivery to the recipients matching `rcpt`, or to all recipients
+    // if no filter is provided. Messages that are currently being delivered
+    // are not modified. Returns false if no recipients matched the filter.
+    pub async fn cancel_queued(
+        &self,
+        queue_id: QueueId,
+        rcpt: Option<&str>,
+        domains: Option<&[String]>,
+        bounce: bool,
+    ) -> trc::Result<bool> {
+        let mut message = match self.read_message(queue_id).await {
+            Some(message) if domains.map_or(true, |domains| message.has_domain(domains)) => message,
+            _ => return Err(trc::ResourceEvent::NotFound.into_err()),
+        };
+        let is_cancelable = |recipient: &Recipient| {
+            matches!(
+                recipient.status,
+                Status::Scheduled | Status::TemporaryFailure(_)
+            ) && rcpt.map_or(true, |rcpt| recipient.address_lcase.contains(rcpt))
+        };
+        if rcpt.is_some() && !message.recipients.iter().any(is_cancelable) {
+            return Ok(false);
+        }
+
+        // Lock the message to make sure it is not being delivered
+        let prev_event = message.next_event();
+        if let Some(due) = prev_event {
+            let lock_expiry = self
+                .core
+                .storage
+                .data
+                .get_value::<u64>(ValueKey::from(ValueClass::Queue(QueueClass::MessageEvent(
+                    QueueEvent { due, queue_id },
+                ))))
+                .await
+                .caused_by(trc::location!())?
+                .unwrap_or_default();
+            if lock_expiry > now()
+                || self
+                    .try_lock_event(QueueEventLock {
+                        due,
+                        queue_id,
+                        lock_expiry,
+                    })
+                    .await
+                    .is_none()
+            {
+                return Err(trc::QueueEvent::Locked
+                    .into_err()
+                    .ctx(trc::Key::QueueId, queue_id)
+                    .details("Message is currently being delivered."));
+            }
+        }
+
+        // Cancel delivery for all matching recipients
+        for recipient in &mut message.recipients {
+            if is_cancelable(recipient) {
+                recipient.status = Status::PermanentFailure(HostResponse {
+                    hostname: ErrorDetails::default(),
+                    response: smtp_proto::Response {
+                        code: 0,
+                        esc: [0, 0, 0],
+                        message: "Delivery canceled.".to_string(),
+                    },
+                });
+            }
+        }
+
+        // Mark as completed domains without any pending deliveries
+        for (domain_idx, domain) in message.domains.iter_mut().enumerate() {
+            if matches!(
+                domain.status,
+                Status::TemporaryFailure(_) | Status::Scheduled
+            ) && !message.recipients.iter().any(|rcpt| {
+                rcpt.domain_idx == domain_idx
+                    && matches!(rcpt.status, Status::Scheduled | Status::TemporaryFailure(_))
+            }) {
+                domain.status = Status::Completed(());
+            }
+        }
+
+        if bounce {
+            self.send_dsn(&mut message).await;
+        }
+
+        // Delete the message if there are no pending deliveries, otherwise release the lock
+        match (prev_event, message.next_event()) {
+            (Some(prev_event), Some(next_event)) => {
+                message
+                    .save_changes(self, prev_event.into(), next_event.into())
+                    .await;
+            }
+            (prev_event, _) => {
+                message.remove(self, prev_event.unwrap_or_default()).await;
+            }
+        }
+        let _ = self.inner.queue_tx.send(Event::Reload).await;
+
+        Ok(true)
+    }
+
     pub async fn read_message(&self, id: QueueId) -> Option<Message> {
         match self
             .core
@@ -166,6 +340,81 @@ impl SMTP {
             }
         }
     }
+
+    // Messages queued by previous versions are missing from the domain index,
+    // which is rebuilt when it is empty while there are messages in the queue
+    pub async fn migrate_queue_domain_index(&self) -> trc::Result<()> {
+        let store = &self.core.storage.data;
+        let mut has_index = false;
+        store
+            .iterate(
+                IterateParams::new(
+                    AnyKey {
+                        subspace: SUBSPACE_REPORT_OUT,
+                        key: vec![QUEUE_DOMAIN_PREFIX],
+                    },
+                    AnyKey {
+                        subspace: SUBSPACE_REPORT_OUT,
+                        key: vec![QUEUE_DOMAIN_PREFIX + 1],
+                    },
+                )
+                .no_values(),
+                |_, _| {
+                    has_index = true;
+
+                    Ok(false)
+                },
+            )
+            .await
+            .caused_by(trc::location!())?;
+        if has_index {
+            return Ok(());
+        }
+
+        let mut messages = Vec::new();
+        store
+            .iterate(
+                IterateParams::new(
+                    ValueKey::from(ValueClass::Queue(QueueClass::Message(0))),
+                    ValueKey::from(ValueClass::Queue(QueueClass::Message(u64::MAX))),
+                ),
+                |_, value| {
+                    messages.push(HashedValue::<Bincode<Message>>::deserialize(value)?);
+
+                    Ok(true)
+                },
+            )
+            .await
+            .caused_by(trc::location!())?;
+
+        let total_messages = messages.len();
+        for message in messages {
+            // Messages updated in the meantime were indexed by the update
+            let message_hash = message.hash;
+            let message = message.inner.inner;
+            let mut batch = BatchBuilder::new();
+            batch.assert_value(
+                ValueClass::Queue(QueueClass::Message(message.queue_id)),
+                AssertValue::Hash(message_hash),
+            );
+            message.write_domain_index(&mut batch);
+
+            match store.write(batch.build()).await {
+                Ok(_) => (),
+                Err(err) if err.is_assertion_failure() => (),
+                Err(err) => return Err(err.caused_by(trc::location!())),
+            }
+        }
+
+        if total_messages > 0 {
+            trc::event!(
+                Server(ServerEvent::Startup),
+                Details = format!("Indexed {total_messages} queued messages by domain")
+            );
+        }
+
+        Ok(())
+    }
 }
 
 impl Message {
@@ -293,11 +542,12 @@ impl Message {
                     hash: self.blob_hash.clone(),
                 },
                 vec![],
-            )
-            .set(
-                ValueClass::Queue(QueueClass::Message(self.queue_id)),
-                Bincode::new(self).serialize(),
             );
+        self.write_domain_index(&mut batch);
+        batch.set(
+            ValueClass::Queue(QueueClass::Message(self.queue_id)),
+            Bincode::new(self).serialize(),
+        );
 
         if let Err(err) = core.core.storage.data.write(batch.build()).await {
             trc::error!(err
@@ -328,45 +578,65 @@ impl Message {
         rcpt_domain: impl Into<String>,
         core: &SMTP,
     ) {
-        let rcpt_domain = rcpt_domain.into();
-        let domain_idx =
-            if let Some(idx) = self.domains.iter().position(|d| d.domain == rcpt_domain) {
-                idx
-            } else {
-                let idx = self.domains.len();
-
-                self.domains.push(Domain {
-                    domain: rcpt_domain,
-                    retry: Schedule::now(),
-                    notify: Schedule::now(),
-                    expires: 0,
-                    status: Status::Scheduled,
-                });
-
-                let expires = core
-                    .core
-                    .eval_if(
-                        &core.core.smtp.queue.expire,
-                        &QueueEnvelope::new(self, idx),
-                        self.span_id,
-                    )
-                    .await
-                    .unwrap_or_else(|| Duration::from_secs(5 * 86400));
-
-                // Update expiration
-                let domain = self.domains.last_mut().unwrap();
-                domain.notify = Schedule::later(expires + Duration::from_secs(10));
-                domain.expires = now() + expires.as_secs();
+        let mut rcpt = rcpt.into();
+        let mut rcpt_lcase = rcpt_lcase.into();
+        let mut rcpt_domain = rcpt_domain.into();
+        let mut orcpt = None;
+
+        // Apply transport map rewrites
+        let transport_map = &core.core.smtp.queue.transport_map;
+        if let Some(address) = core.core.rewrite_recipient(&rcpt_lcase, self.span_id) {
+            rcpt_domain = address.rsplit_once('@').unwrap().1.to_string();
+            orcpt = Some(std::mem::replace(&mut rcpt, address.clone()));
+            rcpt_lcase = address;
+        }
 
-                idx
-            };
+        // Recipients of a domain routed through different relays are kept apart
+        let relay = transport_map.relay(&rcpt_lcase);
+        let domain_idx = if let Some(idx) = self.domains.iter().enumerate().position(|(idx, d)| {
+            d.domain == rcpt_domain
+                && self
+                    .recipients
+                    .iter()
+                    .find(|r| r.domain_idx == idx)
+                    .map_or(true, |r| transport_map.relay(&r.address_lcase) == relay)
+        }) {
+            idx
+        } else {
+            let idx = self.domains.len();
+
+            self.domains.push(Domain {
+                domain: rcpt_domain,
+                retry: Schedule::now(),
+                notify: Schedule::now(),
+                expires: 0,
+                status: Status::Scheduled,
+            });
+
+            let expires = core
+                .core
+                .eval_if(
+                    &core.core.smtp.queue.expire,
+                    &QueueEnvelope::new(self, idx),
+                    self.span_id,
+                )
+                .await
+                .unwrap_or_else(|| Duration::from_secs(5 * 86400));
+
+            // Update expiration
+            let domain = self.domains.last_mut().unwrap();
+            domain.notify = Schedule::later(expires + Duration::from_secs(10));
+            domain.expires = now() + expires.as_secs();
+
+            idx
+        };
         self.recipients.push(Recipient {
             domain_idx,
-            address: rcpt.into(),
-            address_lcase: rcpt_lcase.into(),
+            address: rcpt,
+            address_lcase: rcpt_lcase,
             status: Status::Scheduled,
             flags: 0,
-            orcpt: None,
+            orcpt,
         });
     }
 
@@ -408,6 +678,8 @@ impl Message {
                 );
         }
 
+        self.write_domain_index(&mut batch);
+
         let span_id = self.span_id;
         batch.set(
             ValueClass::Queue(QueueClass::Message(self.queue_id)),
@@ -428,6 +700,14 @@ impl Message {
     pub async fn remove(self, core: &SMTP, prev_event: u64) -> bool {
         let mut batch = BatchBuilder::new();
 
+        // Remove domain index entries
+        for domain in &self.domains {
+            batch.clear(ValueClass::Queue(QueueClass::MessageDomain(QueueDomain {
+                domain: domain.domain.clone(),
+                queue_id: self.queue_id,
+            })));
+        }
+
         // Release all quotas
         for quota_key in self.quota_keys {
             match quota_key {
@@ -465,6 +745,31 @@ impl Message {
         }
     }
 
+    fn write_domain_index(&self, batch: &mut BatchBuilder) {
+        // Index pending domains by name, storing the creation time and next retry
+        for domain in &self.domains {
+            let class = ValueClass::Queue(QueueClass::MessageDomain(QueueDomain {
+                domain: domain.domain.clone(),
+                queue_id: self.queue_id,
+            }));
+
+            if matches!(
+                domain.status,
+                Status::Scheduled | Status::TemporaryFailure(_)
+            ) {
+                batch.set(
+                    class,
+                    KeySerializer::new(U64_LEN * 2)
+                        .write(self.created)
+                        .write(domain.retry.due)
+                        .finalize(),
+                );
+            } else {
+                batch.clear(class);
+            }
+        }
+    }
+
     pub fn has_domain(&self, domains: &[String]) -> bool {
         self.domains.iter().any(|d| domains.contains(&d.domain))
             || self
@@ -473,3 +778,44 @@ impl Message {
                 .map_or(false, |(_, domain)| domains.contains(&domain.to_string()))
     }
 }
+
+impl QueueFilter<'_> {
+    pub fn has_filters(&self) -> bool {
+        self.text.is_some()
+            || self.from.is_some()
+            || self.to.is_some()
+            || self.before.is_some()
+            || self.after.is_some()
+    }
+
+    pub fn matches(&self, message: &Message) -> bool {
+        self.domains
+            .map_or(true, |domains| message.has_domain(domains))
+            && (!self.has_filters()
+                || (self
+                    .text
+                    .map(|text| {
+                        message.return_path.contains(text)
+                            || message
+                                .recipients
+                                .iter()
+                                .any(|r| r.address_lcase.contains(text))
+                    })
+                    .unwrap_or_else(|| {
+                        self.from
+                            .map_or(true, |from| message.return_path.contains(from))
+                            && self.to.map_or(true, |to| {
+                                message
+                                    .recipients
+                                    .iter()
+                                    .any(|r| r.address_lcase.contains(to))
+                            })
+                    })
+                    && self
+                        .before
+                        .map_or(true, |before| message.next_delivery_event() < before)
+                    && self
+                        .after
+                        .map_or(true, |after| message.next_delivery_event() > after)))
+    }
+}
@@ -95,6 +95,38 @@ impl SMTP {
 
         Ok(())
     }
+
+    // Limits the number of deliveries in flight to a single destination domain
+    pub fn is_domain_allowed(
+        &self,
+        domain: &str,
+        max_concurrent: u64,
+        session_id: u64,
+    ) -> Result<InFlight, Error> {
+        let mut limiter = self
+            .inner
+            .domain_concurrency
+            .entry(domain.to_string())
+            .or_insert_with(|| ConcurrencyLimiter::new(max_concurrent));
+
+        // Pick up limit changes after a configuration reload
+        limiter.max_concurrent = max_concurrent;
+
+        if let Some(in_flight) = limiter.is_allowed() {
+            Ok(in_flight)
+        } else {
+            trc::event!(
+                Queue(trc::QueueEvent::ConcurrencyLimitExceeded),
+                SpanId = session_id,
+                Domain = domain.to_string(),
+                Limit = max_concurrent,
+            );
+
+            Err(Error::Concurrency {
+                limiter: limiter.clone(),
+            })
+        }
+    }
 }
 
 impl Domain {
@@ -228,18 +228,20 @@ impl SMTP {
             .unwrap_or_default();
         if !signers.is_empty() {
             let mut headers = Vec::with_capacity(64);
-            for signer in signers.iter() {
-                if let Some(signer) = self.core.get_dkim_signer(signer, message.span_id) {
-                    match signer.sign(bytes) {
-                        Ok(signature) => {
-                            signature.write_header(&mut headers);
-                        }
-                        Err(err) => {
-                            trc::error!(trc::Event::from(err)
-                                .span_id(message.span_id)
-                                .details("Failed to sign message")
-                                .caused_by(trc::location!()));
-                        }
+            for signer in self
+                .core
+                .resolve_dkim_signers(&signers, &message.return_path_domain, message.span_id)
+                .unwrap_or_default()
+            {
+                match signer.sign(bytes) {
+                    Ok(signature) => {
+                        signature.write_header(&mut headers);
+                    }
+                    Err(err) => {
+                        trc::error!(trc::Event::from(err)
+                            .span_id(message.span_id)
+                            .details("Failed to sign message")
+                            .caused_by(trc::location!()));
                     }
                 }
             }
@@ -339,8 +341,8 @@ impl From<&Arc<Policy>> for PolicyType {
 impl From<(&Option<Arc<Policy>>, &Option<Arc<Tlsa>>)> for PolicyType {
     fn from(value: (&Option<Arc<Policy>>, &Option<Arc<Tlsa>>)) -> Self {
         match value {
-            (Some(value), _) => PolicyType::Sts(Some(value.clone())),
             (_, Some(value)) => PolicyType::Tlsa(Some(value.clone())),
+            (Some(value), _) => PolicyType::Sts(Some(value.clone())),
             _ => PolicyType::None,
         }
     }
@@ -124,11 +124,13 @@ impl SMTP {
             }
         };
 
-        // Try delivering report over HTTP
+        // Submit the report to every HTTP endpoint (RFC 8460, section 3)
         let mut rcpts = Vec::with_capacity(rua.len());
+        let mut has_http = false;
         for uri in &rua {
             match uri {
                 ReportUri::Http(uri) => {
+                    has_http = true;
                     if let Ok(client) = reqwest::Client::builder()
                         .user_agent(USER_AGENT)
                         .timeout(Duration::from_secs(2 * 60))
@@ -137,8 +139,7 @@ impl SMTP {
                         #[cfg(feature = "test_mode")]
                         if uri == "https://127.0.0.1/tls" {
                             TLS_HTTP_REPORT.lock().extend_from_slice(&json);
-                            self.delete_tls_report(events).await;
-                            return;
+                            continue;
                         }
 
                         match client
@@ -156,9 +157,6 @@ impl SMTP {
                                         Url = uri.to_string(),
                                         Code = response.status().as_u16(),
                                     );
-
-                                    self.delete_tls_report(events).await;
-                                    return;
                                 } else {
                                     trc::event!(
                                         OutgoingReport(OutgoingReportEvent::SubmissionError),
@@ -187,7 +185,7 @@ impl SMTP {
             }
         }
 
-        // Deliver report over SMTP
+        // Deliver report over SMTP to the remaining endpoints
         if !rcpts.is_empty() {
             let config = &self.core.smtp.report.tls;
             let from_addr = self
@@ -230,7 +228,7 @@ impl SMTP {
                 span_id,
             )
             .await;
-        } else {
+        } else if !has_http {
             trc::event!(
                 OutgoingReport(OutgoingReportEvent::NoRecipientsFound),
                 SpanId = span_id,
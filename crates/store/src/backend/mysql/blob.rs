@@ -8,7 +8,7 @@ use std::ops::Range;
 
 use mysql_async::prelude::Queryable;
 
-use super::{into_error, MysqlStore};
+use super::{into_error, into_pool_error, MysqlStore};
 
 impl MysqlStore {
     pub(crate) async fn get_blob(
@@ -16,7 +16,7 @@ impl MysqlStore {
         key: &[u8],
         range: Range<usize>,
     ) -> trc::Result<Option<Vec<u8>>> {
-        let mut conn = self.conn_pool.get_conn().await.map_err(into_error)?;
+        let mut conn = self.conn_pool.get_conn().await.map_err(into_pool_error)?;
         let s = conn
             .prep("SELECT v FROM t WHERE k = ?")
             .await
@@ -39,7 +39,7 @@ impl MysqlStore {
     }
 
     pub(crate) async fn put_blob(&self, key: &[u8], data: &[u8]) -> trc::Result<()> {
-        let mut conn = self.conn_pool.get_conn().await.map_err(into_error)?;
+        let mut conn = self.conn_pool.get_conn().await.map_err(into_pool_error)?;
         let s = conn
             .prep("INSERT INTO t (k, v) VALUES (?, ?) ON DUPLICATE KEY UPDATE v = VALUES(v)")
             .await
@@ -51,7 +51,7 @@ impl MysqlStore {
     }
 
     pub(crate) async fn delete_blob(&self, key: &[u8]) -> trc::Result<bool> {
-        let mut conn = self.conn_pool.get_conn().await.map_err(into_error)?;
+        let mut conn = self.conn_pool.get_conn().await.map_err(into_pool_error)?;
         let s = conn
             .prep("DELETE FROM t WHERE k = ?")
             .await
@@ -8,7 +8,7 @@ use mysql_async::{prelude::Queryable, Params, Row};
 
 use crate::{IntoRows, QueryResult, QueryType, Value};
 
-use super::{into_error, MysqlStore};
+use super::{into_error, into_pool_error, MysqlStore};
 
 impl MysqlStore {
     pub(crate) async fn query<T: QueryResult>(
@@ -16,7 +16,7 @@ impl MysqlStore {
         query: &str,
         params: &[Value<'_>],
     ) -> trc::Result<T> {
-        let mut conn = self.conn_pool.get_conn().await.map_err(into_error)?;
+        let mut conn = self.conn_pool.get_conn().await.map_err(into_pool_error)?;
         let s = conn.prep(query).await.map_err(into_error)?;
         let params = Params::Positional(params.iter().map(Into::into).collect());
 
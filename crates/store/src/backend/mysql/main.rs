@@ -11,7 +11,7 @@ use utils::config::{utils::AsKey, Config};
 
 use crate::*;
 
-use super::{into_error, MysqlStore};
+use super::{into_error, into_pool_error, MysqlStore};
 
 impl MysqlStore {
     pub async fn open(
@@ -88,7 +88,7 @@ impl MysqlStore {
     }
 
     pub(crate) async fn create_tables(&self) -> trc::Result<()> {
-        let mut conn = self.conn_pool.get_conn().await.map_err(into_error)?;
+        let mut conn = self.conn_pool.get_conn().await.map_err(into_pool_error)?;
 
         for table in [
             SUBSPACE_ACL,
@@ -22,3 +22,16 @@ pub struct MysqlStore {
 fn into_error(err: impl Display) -> trc::Error {
     trc::StoreEvent::MysqlError.reason(err)
 }
+
+// Keeps the error code of rejected connections, e.g. invalid credentials
+#[inline(always)]
+fn into_pool_error(err: mysql_async::Error) -> trc::Error {
+    let code = match &err {
+        mysql_async::Error::Server(err) => Some(err.code),
+        _ => None,
+    };
+    trc::StoreEvent::PoolError
+        .into_err()
+        .ctx_opt(trc::Key::Code, code)
+        .reason(err)
+}
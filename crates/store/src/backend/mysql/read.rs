@@ -13,14 +13,14 @@ use crate::{
     BitmapKey, Deserialize, IterateParams, Key, ValueKey, U32_LEN,
 };
 
-use super::{into_error, MysqlStore};
+use super::{into_error, into_pool_error, MysqlStore};
 
 impl MysqlStore {
     pub(crate) async fn get_value<U>(&self, key: impl Key) -> trc::Result<Option<U>>
     where
         U: Deserialize + 'static,
     {
-        let mut conn = self.conn_pool.get_conn().await.map_err(into_error)?;
+        let mut conn = self.conn_pool.get_conn().await.map_err(into_pool_error)?;
         let s = conn
             .prep(format!(
                 "SELECT v FROM {} WHERE k = ?",
@@ -49,7 +49,7 @@ impl MysqlStore {
         key.document_id = u32::MAX;
         let key_len = begin.len();
         let end = key.serialize(0);
-        let mut conn = self.conn_pool.get_conn().await.map_err(into_error)?;
+        let mut conn = self.conn_pool.get_conn().await.map_err(into_pool_error)?;
         let table = char::from(key.subspace());
 
         let mut bm = RoaringBitmap::new();
@@ -75,7 +75,7 @@ impl MysqlStore {
         params: IterateParams<T>,
         mut cb: impl for<'x> FnMut(&'x [u8], &'x [u8]) -> trc::Result<bool> + Sync + Send,
     ) -> trc::Result<()> {
-        let mut conn = self.conn_pool.get_conn().await.map_err(into_error)?;
+        let mut conn = self.conn_pool.get_conn().await.map_err(into_pool_error)?;
         let table = char::from(params.begin.subspace());
         let begin = params.begin.serialize(0);
         let end = params.end.serialize(0);
@@ -145,7 +145,7 @@ impl MysqlStore {
         let key = key.into();
         let table = char::from(key.subspace());
         let key = key.serialize(0);
-        let mut conn = self.conn_pool.get_conn().await.map_err(into_error)?;
+        let mut conn = self.conn_pool.get_conn().await.map_err(into_pool_error)?;
         let s = conn
             .prep(format!("SELECT v FROM {table} WHERE k = ?"))
             .await
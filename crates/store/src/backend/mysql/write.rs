@@ -20,7 +20,7 @@ use crate::{
     BitmapKey, IndexKey, Key, LogKey, SUBSPACE_COUNTER, SUBSPACE_QUOTA, U32_LEN,
 };
 
-use super::{into_error, MysqlStore};
+use super::{into_error, into_pool_error, MysqlStore};
 
 #[derive(Debug)]
 enum CommitError {
@@ -33,7 +33,7 @@ impl MysqlStore {
     pub(crate) async fn write(&self, batch: Batch) -> trc::Result<AssignedIds> {
         let start = Instant::now();
         let mut retry_count = 0;
-        let mut conn = self.conn_pool.get_conn().await.map_err(into_error)?;
+        let mut conn = self.conn_pool.get_conn().await.map_err(into_pool_error)?;
 
         loop {
             match self.write_trx(&mut conn, &batch).await {
@@ -321,7 +321,7 @@ impl MysqlStore {
     }
 
     pub(crate) async fn purge_store(&self) -> trc::Result<()> {
-        let mut conn = self.conn_pool.get_conn().await.map_err(into_error)?;
+        let mut conn = self.conn_pool.get_conn().await.map_err(into_pool_error)?;
         for subspace in [SUBSPACE_QUOTA, SUBSPACE_COUNTER] {
             let s = conn
                 .prep(format!("DELETE FROM {} WHERE v = 0", char::from(subspace),))
@@ -334,7 +334,7 @@ impl MysqlStore {
     }
 
     pub(crate) async fn delete_range(&self, from: impl Key, to: impl Key) -> trc::Result<()> {
-        let mut conn = self.conn_pool.get_conn().await.map_err(into_error)?;
+        let mut conn = self.conn_pool.get_conn().await.map_err(into_pool_error)?;
 
         let s = conn
             .prep(format!(
@@ -6,7 +6,7 @@
 
 use std::ops::Range;
 
-use super::{into_error, PostgresStore};
+use super::{into_error, into_pool_error, PostgresStore};
 
 impl PostgresStore {
     pub(crate) async fn get_blob(
@@ -14,7 +14,7 @@ impl PostgresStore {
         key: &[u8],
         range: Range<usize>,
     ) -> trc::Result<Option<Vec<u8>>> {
-        let conn = self.conn_pool.get().await.map_err(into_error)?;
+        let conn = self.conn_pool.get().await.map_err(into_pool_error)?;
         let s = conn
             .prepare_cached("SELECT v FROM t WHERE k = $1")
             .await
@@ -40,7 +40,7 @@ impl PostgresStore {
     }
 
     pub(crate) async fn put_blob(&self, key: &[u8], data: &[u8]) -> trc::Result<()> {
-        let conn = self.conn_pool.get().await.map_err(into_error)?;
+        let conn = self.conn_pool.get().await.map_err(into_pool_error)?;
         let s = conn
             .prepare_cached(
                 "INSERT INTO t (k, v) VALUES ($1, $2) ON CONFLICT (k) DO UPDATE SET v = EXCLUDED.v",
@@ -54,7 +54,7 @@ impl PostgresStore {
     }
 
     pub(crate) async fn delete_blob(&self, key: &[u8]) -> trc::Result<bool> {
-        let conn = self.conn_pool.get().await.map_err(into_error)?;
+        let conn = self.conn_pool.get().await.map_err(into_pool_error)?;
         let s = conn
             .prepare_cached("DELETE FROM t WHERE k = $1")
             .await
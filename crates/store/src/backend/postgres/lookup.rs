@@ -12,7 +12,7 @@ use tokio_postgres::types::{FromSql, ToSql, Type};
 
 use crate::IntoRows;
 
-use super::{into_error, PostgresStore};
+use super::{into_error, into_pool_error, PostgresStore};
 
 impl PostgresStore {
     pub(crate) async fn query<T: QueryResult>(
@@ -20,7 +20,7 @@ impl PostgresStore {
         query: &str,
         params_: &[crate::Value<'_>],
     ) -> trc::Result<T> {
-        let conn = self.conn_pool.get().await.map_err(into_error)?;
+        let conn = self.conn_pool.get().await.map_err(into_pool_error)?;
         let s = conn.prepare_cached(query).await.map_err(into_error)?;
         let params = params_
             .iter()
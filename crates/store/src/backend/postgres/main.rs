@@ -8,7 +8,7 @@ use std::time::Duration;
 
 use crate::{backend::postgres::tls::MakeRustlsConnect, *};
 
-use super::{into_error, PostgresStore};
+use super::{into_error, into_pool_error, PostgresStore};
 
 use deadpool_postgres::{Config, ManagerConfig, PoolConfig, RecyclingMethod, Runtime};
 use tokio_postgres::NoTls;
@@ -75,7 +75,7 @@ impl PostgresStore {
     }
 
     pub(crate) async fn create_tables(&self) -> trc::Result<()> {
-        let conn = self.conn_pool.get().await.map_err(into_error)?;
+        let conn = self.conn_pool.get().await.map_err(into_pool_error)?;
 
         for table in [
             SUBSPACE_ACL,
@@ -6,7 +6,7 @@
 
 use std::fmt::Display;
 
-use deadpool_postgres::Pool;
+use deadpool_postgres::{Pool, PoolError};
 
 pub mod blob;
 pub mod lookup;
@@ -23,3 +23,16 @@ pub struct PostgresStore {
 fn into_error(err: impl Display) -> trc::Error {
     trc::StoreEvent::PostgresqlError.reason(err)
 }
+
+// Keeps the SQLSTATE of rejected connections, e.g. invalid credentials
+#[inline(always)]
+fn into_pool_error(err: PoolError) -> trc::Error {
+    let code = match &err {
+        PoolError::Backend(err) => err.code().map(|code| code.code().to_string()),
+        _ => None,
+    };
+    trc::StoreEvent::PoolError
+        .into_err()
+        .ctx_opt(trc::Key::Code, code)
+        .reason(err)
+}
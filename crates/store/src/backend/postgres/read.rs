@@ -12,14 +12,14 @@ use crate::{
     BitmapKey, Deserialize, IterateParams, Key, ValueKey, U32_LEN,
 };
 
-use super::{into_error, PostgresStore};
+use super::{into_error, into_pool_error, PostgresStore};
 
 impl PostgresStore {
     pub(crate) async fn get_value<U>(&self, key: impl Key) -> trc::Result<Option<U>>
     where
         U: Deserialize + 'static,
     {
-        let conn = self.conn_pool.get().await.map_err(into_error)?;
+        let conn = self.conn_pool.get().await.map_err(into_pool_error)?;
         let s = conn
             .prepare_cached(&format!(
                 "SELECT v FROM {} WHERE k = $1",
@@ -48,7 +48,7 @@ impl PostgresStore {
         key.document_id = u32::MAX;
         let key_len = begin.len();
         let end = key.serialize(0);
-        let conn = self.conn_pool.get().await.map_err(into_error)?;
+        let conn = self.conn_pool.get().await.map_err(into_pool_error)?;
         let table = char::from(key.subspace());
 
         let mut bm = RoaringBitmap::new();
@@ -77,7 +77,7 @@ impl PostgresStore {
         params: IterateParams<T>,
         mut cb: impl for<'x> FnMut(&'x [u8], &'x [u8]) -> trc::Result<bool> + Sync + Send,
     ) -> trc::Result<()> {
-        let conn = self.conn_pool.get().await.map_err(into_error)?;
+        let conn = self.conn_pool.get().await.map_err(into_pool_error)?;
         let table = char::from(params.begin.subspace());
         let begin = params.begin.serialize(0);
         let end = params.end.serialize(0);
@@ -138,7 +138,7 @@ impl PostgresStore {
         let table = char::from(key.subspace());
         let key = key.serialize(0);
 
-        let conn = self.conn_pool.get().await.map_err(into_error)?;
+        let conn = self.conn_pool.get().await.map_err(into_pool_error)?;
         let s = conn
             .prepare_cached(&format!("SELECT v FROM {table} WHERE k = $1"))
             .await
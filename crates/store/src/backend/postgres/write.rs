@@ -21,7 +21,7 @@ use crate::{
     BitmapKey, IndexKey, Key, LogKey, SUBSPACE_COUNTER, SUBSPACE_QUOTA, U32_LEN,
 };
 
-use super::{into_error, PostgresStore};
+use super::{into_error, into_pool_error, PostgresStore};
 
 #[derive(Debug)]
 enum CommitError {
@@ -32,7 +32,7 @@ enum CommitError {
 
 impl PostgresStore {
     pub(crate) async fn write(&self, batch: Batch) -> trc::Result<AssignedIds> {
-        let mut conn = self.conn_pool.get().await.map_err(into_error)?;
+        let mut conn = self.conn_pool.get().await.map_err(into_pool_error)?;
         let start = Instant::now();
         let mut retry_count = 0;
 
@@ -333,7 +333,7 @@ impl PostgresStore {
     }
 
     pub(crate) async fn purge_store(&self) -> trc::Result<()> {
-        let conn = self.conn_pool.get().await.map_err(into_error)?;
+        let conn = self.conn_pool.get().await.map_err(into_pool_error)?;
 
         for subspace in [SUBSPACE_QUOTA, SUBSPACE_COUNTER] {
             let s = conn
@@ -350,7 +350,7 @@ impl PostgresStore {
     }
 
     pub(crate) async fn delete_range(&self, from: impl Key, to: impl Key) -> trc::Result<()> {
-        let conn = self.conn_pool.get().await.map_err(into_error)?;
+        let conn = self.conn_pool.get().await.map_err(into_pool_error)?;
 
         let s = conn
             .prepare_cached(&format!(
@@ -159,10 +159,13 @@ impl RedisStore {
         value: Vec<u8>,
         expires: Option<u64>,
     ) -> trc::Result<()> {
-        if let Some(expires) = expires {
-            conn.set_ex(key, value, expires).await.map_err(into_error)
-        } else {
-            conn.set(key, value).await.map_err(into_error)
+        match expires {
+            Some(0) => {
+                // Redis rejects a zero TTL, treat the value as already expired
+                conn.del(key).await.map_err(into_error)
+            }
+            Some(expires) => conn.set_ex(key, value, expires).await.map_err(into_error),
+            None => conn.set(key, value).await.map_err(into_error),
         }
     }
 
@@ -8,7 +8,7 @@ use std::ops::Range;
 
 use rusqlite::OptionalExtension;
 
-use super::{into_error, SqliteStore};
+use super::{into_error, into_pool_error, SqliteStore};
 
 impl SqliteStore {
     pub(crate) async fn get_blob(
@@ -16,7 +16,7 @@ impl SqliteStore {
         key: &[u8],
         range: Range<usize>,
     ) -> trc::Result<Option<Vec<u8>>> {
-        let conn = self.conn_pool.get().map_err(into_error)?;
+        let conn = self.conn_pool.get().map_err(into_pool_error)?;
         self.spawn_worker(move || {
             let mut result = conn
                 .prepare_cached("SELECT v FROM t WHERE k = ?")
@@ -42,7 +42,7 @@ impl SqliteStore {
     }
 
     pub(crate) async fn put_blob(&self, key: &[u8], data: &[u8]) -> trc::Result<()> {
-        let conn = self.conn_pool.get().map_err(into_error)?;
+        let conn = self.conn_pool.get().map_err(into_pool_error)?;
         self.spawn_worker(move || {
             conn.prepare_cached("INSERT OR REPLACE INTO t (k, v) VALUES (?, ?)")
                 .map_err(into_error)?
@@ -54,7 +54,7 @@ impl SqliteStore {
     }
 
     pub(crate) async fn delete_blob(&self, key: &[u8]) -> trc::Result<bool> {
-        let conn = self.conn_pool.get().map_err(into_error)?;
+        let conn = self.conn_pool.get().map_err(into_pool_error)?;
         self.spawn_worker(move || {
             conn.prepare_cached("DELETE FROM t WHERE k = ?")
                 .map_err(into_error)?
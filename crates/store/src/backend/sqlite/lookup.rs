@@ -8,7 +8,7 @@ use rusqlite::{types::FromSql, Row, Rows, ToSql};
 
 use crate::{IntoRows, QueryResult, QueryType, Value};
 
-use super::{into_error, SqliteStore};
+use super::{into_error, into_pool_error, SqliteStore};
 
 impl SqliteStore {
     pub(crate) async fn query<T: QueryResult>(
@@ -16,7 +16,7 @@ impl SqliteStore {
         query: &str,
         params_: &[Value<'_>],
     ) -> trc::Result<T> {
-        let conn = self.conn_pool.get().map_err(into_error)?;
+        let conn = self.conn_pool.get().map_err(into_pool_error)?;
         self.spawn_worker(move || {
             let mut s = conn.prepare_cached(query).map_err(into_error)?;
             let params = params_
@@ -10,7 +10,7 @@ use utils::config::{utils::AsKey, Config};
 
 use crate::*;
 
-use super::{into_error, pool::SqliteConnectionManager, SqliteStore};
+use super::{into_error, into_pool_error, pool::SqliteConnectionManager, SqliteStore};
 
 impl SqliteStore {
     pub fn open(config: &mut Config, prefix: impl AsKey) -> Option<Self> {
@@ -86,7 +86,7 @@ impl SqliteStore {
     }
 
     pub(super) fn create_tables(&self) -> trc::Result<()> {
-        let conn = self.conn_pool.get().map_err(into_error)?;
+        let conn = self.conn_pool.get().map_err(into_pool_error)?;
 
         for table in [
             SUBSPACE_ACL,
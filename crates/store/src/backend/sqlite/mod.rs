@@ -26,3 +26,8 @@ pub struct SqliteStore {
 fn into_error(err: impl Display) -> trc::Error {
     trc::StoreEvent::SqliteError.reason(err)
 }
+
+#[inline(always)]
+fn into_pool_error(err: r2d2::Error) -> trc::Error {
+    trc::StoreEvent::PoolError.reason(err)
+}
@@ -12,14 +12,14 @@ use crate::{
     BitmapKey, Deserialize, IterateParams, Key, ValueKey, U32_LEN,
 };
 
-use super::{into_error, SqliteStore};
+use super::{into_error, into_pool_error, SqliteStore};
 
 impl SqliteStore {
     pub(crate) async fn get_value<U>(&self, key: impl Key) -> trc::Result<Option<U>>
     where
         U: Deserialize + 'static,
     {
-        let conn = self.conn_pool.get().map_err(into_error)?;
+        let conn = self.conn_pool.get().map_err(into_pool_error)?;
         self.spawn_worker(move || {
             let mut result = conn
                 .prepare_cached(&format!(
@@ -47,7 +47,7 @@ impl SqliteStore {
         key.document_id = u32::MAX;
         let key_len = begin.len();
         let end = key.serialize(0);
-        let conn = self.conn_pool.get().map_err(into_error)?;
+        let conn = self.conn_pool.get().map_err(into_pool_error)?;
         let table = char::from(key.subspace());
 
         self.spawn_worker(move || {
@@ -77,7 +77,7 @@ impl SqliteStore {
         params: IterateParams<T>,
         mut cb: impl for<'x> FnMut(&'x [u8], &'x [u8]) -> trc::Result<bool> + Sync + Send,
     ) -> trc::Result<()> {
-        let conn = self.conn_pool.get().map_err(into_error)?;
+        let conn = self.conn_pool.get().map_err(into_pool_error)?;
 
         self.spawn_worker(move || {
             let table = char::from(params.begin.subspace());
@@ -152,7 +152,7 @@ impl SqliteStore {
         let key = key.into();
         let table = char::from(key.subspace());
         let key = key.serialize(0);
-        let conn = self.conn_pool.get().map_err(into_error)?;
+        let conn = self.conn_pool.get().map_err(into_pool_error)?;
         self.spawn_worker(move || {
             match conn
                 .prepare_cached(&format!("SELECT v FROM {table} WHERE k = ?"))
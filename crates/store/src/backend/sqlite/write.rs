@@ -15,11 +15,11 @@ use crate::{
     BitmapKey, IndexKey, Key, LogKey, SUBSPACE_COUNTER, SUBSPACE_QUOTA, U32_LEN,
 };
 
-use super::{into_error, SqliteStore};
+use super::{into_error, into_pool_error, SqliteStore};
 
 impl SqliteStore {
     pub(crate) async fn write(&self, batch: Batch) -> trc::Result<AssignedIds> {
-        let mut conn = self.conn_pool.get().map_err(into_error)?;
+        let mut conn = self.conn_pool.get().map_err(into_pool_error)?;
         self.spawn_worker(move || {
             let mut account_id = u32::MAX;
             let mut collection = u8::MAX;
@@ -257,7 +257,7 @@ impl SqliteStore {
     }
 
     pub(crate) async fn purge_store(&self) -> trc::Result<()> {
-        let conn = self.conn_pool.get().map_err(into_error)?;
+        let conn = self.conn_pool.get().map_err(into_pool_error)?;
         self.spawn_worker(move || {
             for subspace in [SUBSPACE_QUOTA, SUBSPACE_COUNTER] {
                 conn.prepare_cached(&format!("DELETE FROM {} WHERE v = 0", char::from(subspace),))
@@ -272,7 +272,7 @@ impl SqliteStore {
     }
 
     pub(crate) async fn delete_range(&self, from: impl Key, to: impl Key) -> trc::Result<()> {
-        let conn = self.conn_pool.get().map_err(into_error)?;
+        let conn = self.conn_pool.get().map_err(into_pool_error)?;
         self.spawn_worker(move || {
             conn.prepare_cached(&format!(
                 "DELETE FROM {} WHERE k >= ? AND k < ?",
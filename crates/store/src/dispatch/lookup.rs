@@ -200,6 +200,43 @@ impl LookupStore {
         .caused_by(trc::location!())
     }
 
+    // Returns all the unexpired keys starting with the given prefix
+    pub async fn key_get_prefix<T: Deserialize + std::fmt::Debug + 'static>(
+        &self,
+        prefix: &[u8],
+    ) -> trc::Result<Vec<(Vec<u8>, T)>> {
+        match self {
+            LookupStore::Store(store) => {
+                let from_key =
+                    ValueKey::from(ValueClass::Lookup(LookupClass::Key(prefix.to_vec())));
+                let mut to_key = prefix.to_vec();
+                to_key.push(u8::MAX);
+                let to_key = ValueKey::from(ValueClass::Lookup(LookupClass::Key(to_key)));
+
+                let mut results = Vec::new();
+                store
+                    .iterate(IterateParams::new(from_key, to_key), |key, value| {
+                        if key.starts_with(prefix) {
+                            if let LookupValue::Value(value) =
+                                LookupValue::<T>::deserialize(value).caused_by(trc::location!())?
+                            {
+                                results.push((key.to_vec(), value));
+                            }
+                        }
+                        Ok(true)
+                    })
+                    .await
+                    .map(|_| results)
+            }
+            #[cfg(feature = "redis")]
+            LookupStore::Redis(_) => Err(trc::StoreEvent::NotSupported.into_err()),
+            LookupStore::Query(_) | LookupStore::Memory(_) => {
+                Err(trc::StoreEvent::NotSupported.into_err())
+            }
+        }
+        .caused_by(trc::location!())
+    }
+
     pub async fn counter_get(&self, key: Vec<u8>) -> trc::Result<i64> {
         match self {
             LookupStore::Store(store) => {
@@ -249,14 +286,7 @@ impl LookupStore {
         rate: &Rate,
         soft_check: bool,
     ) -> trc::Result<Option<u64>> {
-        let now = now();
-        let range_start = now / rate.period.as_secs();
-        let range_end = (range_start * rate.period.as_secs()) + rate.period.as_secs();
-        let expires_in = range_end - now;
-
-        let mut bucket = Vec::with_capacity(key.len() + U64_LEN);
-        bucket.extend_from_slice(key);
-        bucket.extend_from_slice(range_start.to_be_bytes().as_slice());
+        let (bucket, expires_in) = rate_bucket(key, rate);
 
         let requests = if !soft_check {
             self.counter_incr(bucket, 1, expires_in.into(), true)
@@ -273,6 +303,27 @@ impl LookupStore {
         }
     }
 
+    // Same as is_rate_allowed but counts multiple requests at once
+    pub async fn is_rate_allowed_by(
+        &self,
+        key: &[u8],
+        rate: &Rate,
+        count: u64,
+    ) -> trc::Result<Option<u64>> {
+        let (bucket, expires_in) = rate_bucket(key, rate);
+
+        let requests = self
+            .counter_incr(bucket, count as i64, expires_in.into(), true)
+            .await
+            .caused_by(trc::location!())?;
+
+        if requests <= rate.requests as i64 {
+            Ok(None)
+        } else {
+            Ok(Some(expires_in))
+        }
+    }
+
     pub async fn purge_lookup_store(&self) -> trc::Result<()> {
         match self {
             LookupStore::Store(store) => {
@@ -369,6 +420,18 @@ impl LookupStore {
     }
 }
 
+fn rate_bucket(key: &[u8], rate: &Rate) -> (Vec<u8>, u64) {
+    let now = now();
+    let range_start = now / rate.period.as_secs();
+    let range_end = (range_start * rate.period.as_secs()) + rate.period.as_secs();
+
+    let mut bucket = Vec::with_capacity(key.len() + U64_LEN);
+    bucket.extend_from_slice(key);
+    bucket.extend_from_slice(range_start.to_be_bytes().as_slice());
+
+    (bucket, range_end - now)
+}
+
 enum LookupValue<T> {
     Value(T),
     None,
@@ -9,6 +9,7 @@ use std::{
     time::Instant,
 };
 
+use ahash::AHashSet;
 use roaring::RoaringBitmap;
 use trc::{AddContext, StoreEvent};
 
@@ -16,7 +17,7 @@ use crate::{
     write::{
         key::{DeserializeBigEndian, KeySerializer},
         now, AnyClass, AnyKey, AssignedIds, Batch, BatchBuilder, BitmapClass, BitmapHash,
-        Operation, ReportClass, ValueClass, ValueOp,
+        Operation, ReportClass, TagValue, ValueClass, ValueOp,
     },
     BitmapKey, Deserialize, IterateParams, Key, Store, ValueKey, SUBSPACE_BITMAP_ID,
     SUBSPACE_BITMAP_TAG, SUBSPACE_BITMAP_TEXT, SUBSPACE_INDEXES, SUBSPACE_LOGS, U32_LEN,
@@ -101,6 +102,53 @@ impl Store {
         Ok(result)
     }
 
+    // Returns the distinct values of a text tag, such as the custom keywords
+    // used in an account
+    pub async fn get_tag_values(
+        &self,
+        account_id: u32,
+        collection: u8,
+        field: u8,
+    ) -> trc::Result<AHashSet<Vec<u8>>> {
+        let from_key = BitmapKey {
+            account_id,
+            collection,
+            class: BitmapClass::Tag {
+                field,
+                value: TagValue::Text(vec![]),
+            },
+            document_id: 0,
+        };
+        let to_key = BitmapKey {
+            account_id,
+            collection,
+            class: BitmapClass::Tag {
+                field,
+                value: TagValue::Text(vec![u8::MAX]),
+            },
+            document_id: u32::MAX,
+        };
+        let prefix_len = from_key.serialize(0).len() - U32_LEN;
+        let mut values = AHashSet::new();
+
+        self.iterate(
+            IterateParams::new(from_key, to_key).no_values(),
+            |key, _| {
+                if let Some(value) = key.get(prefix_len..key.len().saturating_sub(U32_LEN)) {
+                    if !values.contains(value) {
+                        values.insert(value.to_vec());
+                    }
+                }
+
+                Ok(true)
+            },
+        )
+        .await
+        .caused_by(trc::location!())?;
+
+        Ok(values)
+    }
+
     pub async fn iterate<T: Key>(
         &self,
         params: IterateParams<T>,
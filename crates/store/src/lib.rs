@@ -151,6 +151,9 @@ pub const SUBSPACE_TELEMETRY_SPAN: u8 = b'o';
 pub const SUBSPACE_TELEMETRY_INDEX: u8 = b'w';
 pub const SUBSPACE_TELEMETRY_METRIC: u8 = b'x';
 
+// Key prefix of the queued messages by domain index, stored in SUBSPACE_REPORT_OUT
+pub const QUEUE_DOMAIN_PREFIX: u8 = 3;
+
 pub const SUBSPACE_RESERVED_1: u8 = b'y';
 pub const SUBSPACE_RESERVED_2: u8 = b'z';
 
@@ -189,6 +189,7 @@ pub enum DirectoryClass<T> {
 pub enum QueueClass {
     Message(u64),
     MessageEvent(QueueEvent),
+    MessageDomain(QueueDomain),
     DmarcReportHeader(ReportEvent),
     DmarcReportEvent(ReportEvent),
     TlsReportHeader(ReportEvent),
@@ -226,6 +227,12 @@ pub struct QueueEvent {
     pub queue_id: u64,
 }
 
+#[derive(Debug, PartialEq, Clone, Eq, Hash)]
+pub struct QueueDomain {
+    pub domain: String,
+    pub queue_id: u64,
+}
+
 #[derive(Debug, PartialEq, Clone, Eq, Hash)]
 pub struct ReportEvent {
     pub due: u64,
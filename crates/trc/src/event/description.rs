@@ -245,6 +245,10 @@ impl ImapEvent {
             ImapEvent::Store => "IMAP STORE command",
             ImapEvent::Subscribe => "IMAP SUBSCRIBE command",
             ImapEvent::Unsubscribe => "IMAP UNSUBSCRIBE command",
+            ImapEvent::Compress => "IMAP COMPRESS command",
+            ImapEvent::GetQuota => "IMAP GETQUOTA command",
+            ImapEvent::SetQuota => "IMAP SETQUOTA command",
+            ImapEvent::Unauthenticate => "IMAP UNAUTHENTICATE command",
             ImapEvent::Thread => "IMAP THREAD command",
             ImapEvent::Error => "IMAP error occurred",
             ImapEvent::RawInput => "Raw IMAP input received",
@@ -286,6 +290,10 @@ impl ImapEvent {
             ImapEvent::Store => "Client stored flags",
             ImapEvent::Subscribe => "Client subscribed to a mailbox",
             ImapEvent::Unsubscribe => "Client unsubscribed from a mailbox",
+            ImapEvent::Compress => "Client enabled compression",
+            ImapEvent::GetQuota => "Client requested quota usage",
+            ImapEvent::SetQuota => "Client set a quota limit",
+            ImapEvent::Unauthenticate => "Client reset the session to the unauthenticated state",
             ImapEvent::Thread => "Client requested message threads",
             ImapEvent::Error => "An error occurred during an IMAP command",
             ImapEvent::RawInput => "Raw IMAP input received",
@@ -409,6 +417,8 @@ impl SmtpEvent {
             SmtpEvent::LoopDetected => "Mail loop detected",
             SmtpEvent::PipeSuccess => "Pipe command succeeded",
             SmtpEvent::PipeError => "Pipe command failed",
+            SmtpEvent::JournalSuccess => "Message journaled",
+            SmtpEvent::JournalError => "Message journaling failed",
             SmtpEvent::DkimPass => "DKIM verification passed",
             SmtpEvent::DkimFail => "DKIM verification failed",
             SmtpEvent::ArcPass => "ARC verification passed",
@@ -444,6 +454,12 @@ impl SmtpEvent {
             SmtpEvent::TooManyInvalidRcpt => "Too many invalid recipients",
             SmtpEvent::RawInput => "Raw SMTP input received",
             SmtpEvent::RawOutput => "Raw SMTP output sent",
+            SmtpEvent::IpReputationDelay => "Connection delayed due to IP reputation",
+            SmtpEvent::IpReputationError => "IP reputation lookup failed",
+            SmtpEvent::Greylisted => "Recipient greylisted",
+            SmtpEvent::GreylistPassed => "Recipient passed greylisting",
+            SmtpEvent::PipeliningLimitExceeded => "Pipelining limit exceeded",
+            SmtpEvent::SmtpUtf8Required => "SMTPUTF8 required",
             SmtpEvent::MissingLocalHostname => "Missing local hostname",
             SmtpEvent::Vrfy => "SMTP VRFY command",
             SmtpEvent::VrfyNotFound => "VRFY address not found",
@@ -503,6 +519,8 @@ impl SmtpEvent {
             }
             SmtpEvent::PipeSuccess => "The pipe command succeeded",
             SmtpEvent::PipeError => "The pipe command failed",
+            SmtpEvent::JournalSuccess => "A journal report of the message was archived",
+            SmtpEvent::JournalError => "Failed to archive a journal report of the message",
             SmtpEvent::DkimPass => "Successful DKIM verification",
             SmtpEvent::DkimFail => "Failed to verify DKIM signature",
             SmtpEvent::ArcPass => "Successful ARC verification",
@@ -558,6 +576,22 @@ impl SmtpEvent {
             }
             SmtpEvent::RawInput => "Raw SMTP input received",
             SmtpEvent::RawOutput => "Raw SMTP output sent",
+            SmtpEvent::IpReputationDelay => {
+                "The connection was delayed because of the remote IP reputation"
+            }
+            SmtpEvent::IpReputationError => "The IP reputation provider could not be queried",
+            SmtpEvent::Greylisted => {
+                "Delivery to the recipient was deferred until the sender retries"
+            }
+            SmtpEvent::GreylistPassed => {
+                "The sender retried after the greylisting delay or was previously allowed"
+            }
+            SmtpEvent::PipeliningLimitExceeded => {
+                "The remote client pipelined too many commands and was disconnected"
+            }
+            SmtpEvent::SmtpUtf8Required => {
+                "The remote client used a non-ASCII address without requesting SMTPUTF8"
+            }
             SmtpEvent::MissingLocalHostname => "The local hostname is missing in the configuration",
             SmtpEvent::Vrfy => "The remote client sent a VRFY command",
             SmtpEvent::VrfyNotFound => {
@@ -647,6 +681,7 @@ impl DeliveryEvent {
             DeliveryEvent::DsnSuccess => "DSN success notification",
             DeliveryEvent::DsnTempFail => "DSN temporary failure notification",
             DeliveryEvent::DsnPermFail => "DSN permanent failure notification",
+            DeliveryEvent::LocalDelivery => "Local delivery completed",
             DeliveryEvent::RawInput => "Raw SMTP input received",
             DeliveryEvent::RawOutput => "Raw SMTP output sent",
         }
@@ -704,6 +739,9 @@ impl DeliveryEvent {
             DeliveryEvent::DsnPermFail => {
                 "A permanent failure delivery status notification was created"
             }
+            DeliveryEvent::LocalDelivery => {
+                "The message was delivered to local recipients, the result of each recipient is included"
+            }
             DeliveryEvent::RawInput => "Raw SMTP input received",
             DeliveryEvent::RawOutput => "Raw SMTP output sent",
         }
@@ -725,6 +763,8 @@ impl QueueEvent {
             QueueEvent::QueueReport => "Queued report for delivery",
             QueueEvent::QueueDsn => "Queued DSN for delivery",
             QueueEvent::QueueAutogenerated => "Queued autogenerated message for delivery",
+            QueueEvent::RecipientRewritten => "Recipient rewritten",
+            QueueEvent::RewriteLoop => "Recipient rewrite loop",
         }
     }
 
@@ -744,6 +784,12 @@ impl QueueEvent {
             QueueEvent::QueueReport => "A new report was queued for delivery",
             QueueEvent::QueueDsn => "A delivery status notification was queued for delivery",
             QueueEvent::QueueAutogenerated => "A system generated message was queued for delivery",
+            QueueEvent::RecipientRewritten => {
+                "A recipient address was rewritten by the transport map"
+            }
+            QueueEvent::RewriteLoop => {
+                "The transport map rewrites of a recipient looped or exceeded the maximum, the address was left unchanged"
+            }
         }
     }
 }
@@ -1085,6 +1131,9 @@ impl TlsEvent {
             TlsEvent::HandshakeError => "TLS handshake error",
             TlsEvent::NotConfigured => "TLS not configured",
             TlsEvent::CertificateNotFound => "TLS certificate not found",
+            TlsEvent::CertificateSelected => "TLS certificate selected",
+            TlsEvent::OcspResponseFetched => "OCSP response fetched",
+            TlsEvent::OcspResponseError => "Failed to fetch OCSP response",
             TlsEvent::NoCertificatesAvailable => "No TLS certificates available",
             TlsEvent::MultipleCertificatesAvailable => "Multiple TLS certificates available",
         }
@@ -1096,6 +1145,9 @@ impl TlsEvent {
             TlsEvent::HandshakeError => "An error occurred during the TLS handshake",
             TlsEvent::NotConfigured => "TLS is not configured",
             TlsEvent::CertificateNotFound => "The TLS certificate was not found",
+            TlsEvent::CertificateSelected => "A TLS certificate was selected for the connection",
+            TlsEvent::OcspResponseFetched => "An OCSP response was fetched and will be stapled to the TLS certificate",
+            TlsEvent::OcspResponseError => "The OCSP responder could not be reached or returned an invalid response",
             TlsEvent::NoCertificatesAvailable => "No TLS certificates are available",
             TlsEvent::MultipleCertificatesAvailable => "Multiple TLS certificates are available",
         }
@@ -1177,6 +1229,7 @@ impl TelemetryEvent {
             TelemetryEvent::OtelExporterError => "OpenTelemetry exporter error",
             TelemetryEvent::OtelMetricsExporterError => "OpenTelemetry metrics exporter error",
             TelemetryEvent::PrometheusExporterError => "Prometheus exporter error",
+            TelemetryEvent::WebhookSuppressed => "Webhook events suppressed",
         }
     }
 
@@ -1195,6 +1248,7 @@ impl TelemetryEvent {
             TelemetryEvent::PrometheusExporterError => {
                 "An error occurred with the Prometheus exporter"
             }
+            TelemetryEvent::WebhookSuppressed => "Duplicate or rate limited events were not sent",
         }
     }
 }
@@ -1603,6 +1657,10 @@ impl MessageIngestEvent {
             MessageIngestEvent::ImapAppend => "Message appended via IMAP",
             MessageIngestEvent::JmapAppend => "Message appended via JMAP",
             MessageIngestEvent::Duplicate => "Skipping duplicate message",
+            MessageIngestEvent::ScanVerdict => "External spam scanner verdict",
+            MessageIngestEvent::ScanError => "External spam scanner error",
+            MessageIngestEvent::TooManyRecipients => "Too many recipients",
+            MessageIngestEvent::QuotaWarning => "Quota warning threshold reached",
             MessageIngestEvent::Error => "Message ingestion error",
         }
     }
@@ -1614,6 +1672,14 @@ impl MessageIngestEvent {
             MessageIngestEvent::ImapAppend => "The message has been appended via IMAP",
             MessageIngestEvent::JmapAppend => "The message has been appended via JMAP",
             MessageIngestEvent::Duplicate => "The message is a duplicate and has been skipped",
+            MessageIngestEvent::ScanVerdict => {
+                "The external spam scanner returned a verdict for the message"
+            }
+            MessageIngestEvent::ScanError => "The external spam scanner could not be reached",
+            MessageIngestEvent::TooManyRecipients => {
+                "The message has more recipients than allowed, delivery to the rest is deferred"
+            }
+            MessageIngestEvent::QuotaWarning => "The account is approaching its storage quota",
             MessageIngestEvent::Error => "An error occurred while ingesting the message",
         }
     }
@@ -1690,6 +1756,7 @@ impl LimitEvent {
             LimitEvent::BlobQuota => "Blob quota limit reached",
             LimitEvent::TooManyRequests => "Too many requests",
             LimitEvent::TenantQuota => "Tenant quota limit reached",
+            LimitEvent::TooManyOAuthRequests => "Too many OAuth requests",
         }
     }
 
@@ -1705,6 +1772,9 @@ impl LimitEvent {
             LimitEvent::BlobQuota => "The blob quota limit has been reached",
             LimitEvent::TooManyRequests => "Too many requests have been made",
             LimitEvent::TenantQuota => "One of the tenant quota limits has been reached",
+            LimitEvent::TooManyOAuthRequests => {
+                "Too many requests have been made to the OAuth endpoints"
+            }
         }
     }
 }
@@ -1740,6 +1810,18 @@ impl AuthEvent {
             AuthEvent::Failed => "Authentication failed",
             AuthEvent::MissingTotp => "Missing TOTP for authentication",
             AuthEvent::TooManyAttempts => "Too many authentication attempts",
+            AuthEvent::MfaDenied => "Second factor authentication denied",
+            AuthEvent::Impersonation => "Master user impersonation",
+            AuthEvent::Throttled => "Authentication throttled",
+            AuthEvent::TokenIssuedAuthCode => "OAuth token issued for authorization code",
+            AuthEvent::TokenIssuedDeviceCode => "OAuth token issued for device code",
+            AuthEvent::TokenIssuedRefresh => "OAuth token issued for refresh token",
+            AuthEvent::RefreshTokenRenewed => "OAuth refresh token renewed",
+            AuthEvent::TokenExpired => "OAuth token expired",
+            AuthEvent::TokenInvalid => "Invalid OAuth token",
+            AuthEvent::TokenRevoked => "OAuth token revoked",
+            AuthEvent::DirectoryUnavailable => "Directory unavailable",
+            AuthEvent::SessionsRevoked => "Sessions revoked",
             AuthEvent::Error => "Authentication error",
         }
     }
@@ -1750,6 +1832,34 @@ impl AuthEvent {
             AuthEvent::Failed => "Failed authentication",
             AuthEvent::MissingTotp => "TOTP is missing for authentication",
             AuthEvent::TooManyAttempts => "Too many authentication attempts have been made",
+            AuthEvent::MfaDenied => "The external second factor challenge was denied or failed",
+            AuthEvent::Impersonation => "A master user authenticated as another account",
+            AuthEvent::Throttled => {
+                "The authentication response was delayed after repeated failures"
+            }
+            AuthEvent::TokenIssuedAuthCode => {
+                "An OAuth token was issued using the authorization code grant"
+            }
+            AuthEvent::TokenIssuedDeviceCode => {
+                "An OAuth token was issued using the device authorization grant"
+            }
+            AuthEvent::TokenIssuedRefresh => {
+                "An OAuth token was issued using the refresh token grant"
+            }
+            AuthEvent::RefreshTokenRenewed => {
+                "A refresh token close to expiring was replaced with a new one"
+            }
+            AuthEvent::TokenExpired => "An expired OAuth token was presented",
+            AuthEvent::TokenInvalid => {
+                "An OAuth token could not be decoded or was signed with an unknown key"
+            }
+            AuthEvent::TokenRevoked => "A revoked OAuth token was presented",
+            AuthEvent::DirectoryUnavailable => {
+                "The directory could not be queried to verify the credentials"
+            }
+            AuthEvent::SessionsRevoked => {
+                "All tokens and sessions of an account were revoked by an administrator"
+            }
             AuthEvent::Error => "An error occurred with authentication",
         }
     }
@@ -1785,6 +1895,8 @@ impl SecurityEvent {
             SecurityEvent::LoiterBan => "Banned due to loitering",
             SecurityEvent::IpBlocked => "Blocked IP address",
             SecurityEvent::Unauthorized => "Unauthorized access",
+            SecurityEvent::SubmissionLimitExceeded => "Submission rate limit exceeded",
+            SecurityEvent::SubmissionSuspended => "Submission suspended",
         }
     }
 
@@ -1799,6 +1911,12 @@ impl SecurityEvent {
             SecurityEvent::LoiterBan => "IP address was banned due to multiple loitering events",
             SecurityEvent::IpBlocked => "Rejected connection from blocked IP address",
             SecurityEvent::Unauthorized => "Account does not have permission to access resource",
+            SecurityEvent::SubmissionLimitExceeded => {
+                "Account exceeded its outbound message or recipient rate limit"
+            }
+            SecurityEvent::SubmissionSuspended => {
+                "Outbound sending was suspended for the account after repeatedly exceeding its rate limit"
+            }
         }
     }
 }
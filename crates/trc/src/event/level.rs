@@ -73,6 +73,10 @@ impl EventType {
                 | ImapEvent::Store
                 | ImapEvent::Subscribe
                 | ImapEvent::Unsubscribe
+                | ImapEvent::Compress
+                | ImapEvent::GetQuota
+                | ImapEvent::SetQuota
+                | ImapEvent::Unauthenticate
                 | ImapEvent::Thread
                 | ImapEvent::Error
                 | ImapEvent::IdleStart
@@ -157,7 +161,11 @@ impl EventType {
                 | SmtpEvent::PipeSuccess
                 | SmtpEvent::PipeError
                 | SmtpEvent::Error => Level::Debug,
-                SmtpEvent::MissingLocalHostname | SmtpEvent::RemoteIdNotFound => Level::Warn,
+                SmtpEvent::JournalSuccess => Level::Info,
+                SmtpEvent::JournalError
+                | SmtpEvent::MissingLocalHostname
+                | SmtpEvent::RemoteIdNotFound
+                | SmtpEvent::IpReputationError => Level::Warn,
                 SmtpEvent::ConcurrencyLimitExceeded
                 | SmtpEvent::TransferLimitExceeded
                 | SmtpEvent::RateLimitExceeded
@@ -195,7 +203,12 @@ impl EventType {
                 | SmtpEvent::AuthMechanismNotSupported
                 | SmtpEvent::ExpnDisabled
                 | SmtpEvent::RequestTooLarge
-                | SmtpEvent::TooManyRecipients => Level::Info,
+                | SmtpEvent::TooManyRecipients
+                | SmtpEvent::IpReputationDelay
+                | SmtpEvent::Greylisted
+                | SmtpEvent::GreylistPassed
+                | SmtpEvent::PipeliningLimitExceeded
+                | SmtpEvent::SmtpUtf8Required => Level::Info,
                 SmtpEvent::RawInput | SmtpEvent::RawOutput => Level::Trace,
             },
             EventType::Network(event) => match event {
@@ -222,12 +235,25 @@ impl EventType {
                 LimitEvent::BlobQuota => Level::Debug,
                 LimitEvent::TooManyRequests => Level::Warn,
                 LimitEvent::TenantQuota => Level::Info,
+                LimitEvent::TooManyOAuthRequests => Level::Warn,
             },
             EventType::Manage(_) => Level::Debug,
             EventType::Auth(cause) => match cause {
                 AuthEvent::Failed => Level::Debug,
                 AuthEvent::MissingTotp => Level::Trace,
                 AuthEvent::TooManyAttempts => Level::Warn,
+                AuthEvent::MfaDenied => Level::Info,
+                AuthEvent::Impersonation => Level::Info,
+                AuthEvent::Throttled => Level::Info,
+                AuthEvent::TokenIssuedAuthCode
+                | AuthEvent::TokenIssuedDeviceCode
+                | AuthEvent::TokenIssuedRefresh
+                | AuthEvent::RefreshTokenRenewed => Level::Info,
+                AuthEvent::TokenExpired | AuthEvent::TokenInvalid | AuthEvent::TokenRevoked => {
+                    Level::Debug
+                }
+                AuthEvent::DirectoryUnavailable => Level::Warn,
+                AuthEvent::SessionsRevoked => Level::Info,
                 AuthEvent::Error => Level::Error,
                 AuthEvent::Success => Level::Info,
             },
@@ -314,7 +340,11 @@ impl EventType {
             },
             EventType::Tls(event) => match event {
                 TlsEvent::Handshake => Level::Info,
-                TlsEvent::HandshakeError | TlsEvent::CertificateNotFound => Level::Debug,
+                TlsEvent::HandshakeError
+                | TlsEvent::CertificateNotFound
+                | TlsEvent::CertificateSelected
+                | TlsEvent::OcspResponseFetched => Level::Debug,
+                TlsEvent::OcspResponseError => Level::Warn,
                 TlsEvent::NotConfigured => Level::Error,
                 TlsEvent::NoCertificatesAvailable | TlsEvent::MultipleCertificatesAvailable => {
                     Level::Warn
@@ -453,7 +483,8 @@ impl EventType {
                 | DeliveryEvent::MissingOutboundHostname => Level::Warn,
                 DeliveryEvent::DsnSuccess
                 | DeliveryEvent::DsnTempFail
-                | DeliveryEvent::DsnPermFail => Level::Info,
+                | DeliveryEvent::DsnPermFail
+                | DeliveryEvent::LocalDelivery => Level::Info,
                 DeliveryEvent::MxLookup
                 | DeliveryEvent::IpLookup
                 | DeliveryEvent::Ehlo
@@ -471,7 +502,9 @@ impl EventType {
                 | QueueEvent::RateLimitExceeded
                 | QueueEvent::ConcurrencyLimitExceeded
                 | QueueEvent::Rescheduled
-                | QueueEvent::QuotaExceeded => Level::Info,
+                | QueueEvent::QuotaExceeded
+                | QueueEvent::RecipientRewritten => Level::Info,
+                QueueEvent::RewriteLoop => Level::Warn,
                 QueueEvent::LockBusy | QueueEvent::Locked | QueueEvent::BlobNotFound => {
                     Level::Debug
                 }
@@ -529,7 +562,12 @@ impl EventType {
                 | MessageIngestEvent::Spam
                 | MessageIngestEvent::ImapAppend
                 | MessageIngestEvent::JmapAppend
-                | MessageIngestEvent::Duplicate => Level::Info,
+                | MessageIngestEvent::Duplicate
+                | MessageIngestEvent::ScanVerdict
+                | MessageIngestEvent::QuotaWarning => Level::Info,
+                MessageIngestEvent::ScanError | MessageIngestEvent::TooManyRecipients => {
+                    Level::Warn
+                }
                 MessageIngestEvent::Error => Level::Error,
             },
             EventType::Security(_) => Level::Info,
@@ -225,12 +225,11 @@ impl Collector {
             })
     }
 
-    pub fn collect_gauges(is_enterprise: bool) -> impl Iterator<Item = &'static AtomicGauge> {
-        static E_GAUGES: &[&AtomicGauge] =
+    pub fn collect_gauges() -> impl Iterator<Item = &'static AtomicGauge> {
+        static GAUGES: &[&AtomicGauge] =
             &[&SERVER_MEMORY, &QUEUE_COUNT, &USER_COUNT, &DOMAIN_COUNT];
-        static C_GAUGES: &[&AtomicGauge] = &[&SERVER_MEMORY, &USER_COUNT, &DOMAIN_COUNT];
 
-        if is_enterprise { E_GAUGES } else { C_GAUGES }
+        GAUGES
             .iter()
             .copied()
             .chain(CONNECTION_METRICS.iter().map(|m| &m.active_connections))
@@ -533,6 +532,16 @@ impl EventType {
                 AuthEvent::Success
                 | AuthEvent::Failed
                 | AuthEvent::TooManyAttempts
+                | AuthEvent::MfaDenied
+                | AuthEvent::Impersonation
+                | AuthEvent::Throttled
+                | AuthEvent::TokenIssuedAuthCode
+                | AuthEvent::TokenIssuedDeviceCode
+                | AuthEvent::TokenIssuedRefresh
+                | AuthEvent::RefreshTokenRenewed
+                | AuthEvent::TokenExpired
+                | AuthEvent::TokenInvalid
+                | AuthEvent::TokenRevoked
                 | AuthEvent::Error,
             ) => true,
             EventType::Config(_) => false,
@@ -633,7 +642,8 @@ impl EventType {
                 | DeliveryEvent::DoubleBounce
                 | DeliveryEvent::DsnSuccess
                 | DeliveryEvent::DsnTempFail
-                | DeliveryEvent::DsnPermFail,
+                | DeliveryEvent::DsnPermFail
+                | DeliveryEvent::LocalDelivery,
             ) => true,
             EventType::Queue(
                 QueueEvent::QueueMessage
@@ -645,7 +655,9 @@ impl EventType {
                 | QueueEvent::BlobNotFound
                 | QueueEvent::RateLimitExceeded
                 | QueueEvent::ConcurrencyLimitExceeded
-                | QueueEvent::QuotaExceeded,
+                | QueueEvent::QuotaExceeded
+                | QueueEvent::RecipientRewritten
+                | QueueEvent::RewriteLoop,
             ) => true,
             EventType::TlsRpt(_) => false,
             EventType::MtaSts(
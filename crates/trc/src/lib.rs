@@ -203,6 +203,8 @@ pub enum SecurityEvent {
     LoiterBan,
     IpBlocked,
     Unauthorized,
+    SubmissionLimitExceeded,
+    SubmissionSuspended,
 }
 
 #[event_type]
@@ -278,6 +280,10 @@ pub enum ImapEvent {
     Subscribe,
     Unsubscribe,
     Thread,
+    Compress,
+    GetQuota,
+    SetQuota,
+    Unauthenticate,
 
     // Errors
     Error,
@@ -360,6 +366,8 @@ pub enum SmtpEvent {
     LoopDetected,
     PipeSuccess,
     PipeError,
+    JournalSuccess,
+    JournalError,
     DkimPass,
     DkimFail,
     ArcPass,
@@ -429,6 +437,12 @@ pub enum SmtpEvent {
     UnsupportedParameter,
     SyntaxError,
     RequestTooLarge,
+    IpReputationDelay,
+    IpReputationError,
+    Greylisted,
+    GreylistPassed,
+    PipeliningLimitExceeded,
+    SmtpUtf8Required,
 }
 
 #[event_type]
@@ -469,6 +483,7 @@ pub enum DeliveryEvent {
     DsnSuccess,
     DsnTempFail,
     DsnPermFail,
+    LocalDelivery,
     RawInput,
     RawOutput,
 }
@@ -487,6 +502,8 @@ pub enum QueueEvent {
     RateLimitExceeded,
     ConcurrencyLimitExceeded,
     QuotaExceeded,
+    RecipientRewritten,
+    RewriteLoop,
 }
 
 #[event_type]
@@ -630,6 +647,9 @@ pub enum TlsEvent {
     HandshakeError,
     NotConfigured,
     CertificateNotFound,
+    CertificateSelected,
+    OcspResponseFetched,
+    OcspResponseError,
     NoCertificatesAvailable,
     MultipleCertificatesAvailable,
 }
@@ -669,6 +689,7 @@ pub enum TelemetryEvent {
     OtelMetricsExporterError,
     PrometheusExporterError,
     JournalError,
+    WebhookSuppressed,
 }
 
 #[event_type]
@@ -859,6 +880,10 @@ pub enum MessageIngestEvent {
     ImapAppend,
     JmapAppend,
     Duplicate,
+    ScanVerdict,
+    ScanError,
+    TooManyRecipients,
+    QuotaWarning,
     Error,
 }
 
@@ -907,6 +932,7 @@ pub enum LimitEvent {
     BlobQuota,
     TenantQuota,
     TooManyRequests,
+    TooManyOAuthRequests,
 }
 
 #[event_type]
@@ -925,6 +951,18 @@ pub enum AuthEvent {
     Failed,
     MissingTotp,
     TooManyAttempts,
+    MfaDenied,
+    Impersonation,
+    Throttled,
+    TokenIssuedAuthCode,
+    TokenIssuedDeviceCode,
+    TokenIssuedRefresh,
+    RefreshTokenRenewed,
+    TokenExpired,
+    TokenInvalid,
+    TokenRevoked,
+    DirectoryUnavailable,
+    SessionsRevoked,
     Error,
 }
 
@@ -859,6 +859,44 @@ impl EventType {
             EventType::Smtp(SmtpEvent::MailFromNotAllowed) => 551,
             EventType::Security(SecurityEvent::Unauthorized) => 552,
             EventType::Limit(LimitEvent::TenantQuota) => 553,
+            EventType::Imap(ImapEvent::Compress) => 554,
+            EventType::Auth(AuthEvent::MfaDenied) => 555,
+            EventType::Auth(AuthEvent::Impersonation) => 556,
+            EventType::Tls(TlsEvent::CertificateSelected) => 557,
+            EventType::Tls(TlsEvent::OcspResponseFetched) => 558,
+            EventType::Tls(TlsEvent::OcspResponseError) => 559,
+            EventType::Smtp(SmtpEvent::IpReputationDelay) => 560,
+            EventType::Smtp(SmtpEvent::IpReputationError) => 561,
+            EventType::Auth(AuthEvent::Throttled) => 562,
+            EventType::Delivery(DeliveryEvent::LocalDelivery) => 563,
+            EventType::MessageIngest(MessageIngestEvent::QuotaWarning) => 564,
+            EventType::Auth(AuthEvent::TokenIssuedAuthCode) => 565,
+            EventType::Auth(AuthEvent::TokenIssuedDeviceCode) => 566,
+            EventType::Auth(AuthEvent::TokenIssuedRefresh) => 567,
+            EventType::Auth(AuthEvent::RefreshTokenRenewed) => 568,
+            EventType::Auth(AuthEvent::TokenExpired) => 569,
+            EventType::Auth(AuthEvent::TokenInvalid) => 570,
+            EventType::Auth(AuthEvent::TokenRevoked) => 571,
+            EventType::MessageIngest(MessageIngestEvent::ScanVerdict) => 572,
+            EventType::MessageIngest(MessageIngestEvent::ScanError) => 573,
+            EventType::Imap(ImapEvent::GetQuota) => 574,
+            EventType::Imap(ImapEvent::SetQuota) => 575,
+            EventType::Smtp(SmtpEvent::Greylisted) => 576,
+            EventType::Smtp(SmtpEvent::GreylistPassed) => 577,
+            EventType::Imap(ImapEvent::Unauthenticate) => 578,
+            EventType::Auth(AuthEvent::DirectoryUnavailable) => 579,
+            EventType::Auth(AuthEvent::SessionsRevoked) => 580,
+            EventType::Smtp(SmtpEvent::PipeliningLimitExceeded) => 581,
+            EventType::Smtp(SmtpEvent::SmtpUtf8Required) => 582,
+            EventType::Security(SecurityEvent::SubmissionLimitExceeded) => 583,
+            EventType::Security(SecurityEvent::SubmissionSuspended) => 584,
+            EventType::Queue(QueueEvent::RecipientRewritten) => 585,
+            EventType::Queue(QueueEvent::RewriteLoop) => 586,
+            EventType::Smtp(SmtpEvent::JournalSuccess) => 587,
+            EventType::Smtp(SmtpEvent::JournalError) => 588,
+            EventType::MessageIngest(MessageIngestEvent::TooManyRecipients) => 589,
+            EventType::Limit(LimitEvent::TooManyOAuthRequests) => 590,
+            EventType::Telemetry(TelemetryEvent::WebhookSuppressed) => 591,
         }
     }
 
@@ -1458,6 +1496,46 @@ impl EventType {
             551 => Some(EventType::Smtp(SmtpEvent::MailFromNotAllowed)),
             552 => Some(EventType::Security(SecurityEvent::Unauthorized)),
             553 => Some(EventType::Limit(LimitEvent::TenantQuota)),
+            554 => Some(EventType::Imap(ImapEvent::Compress)),
+            555 => Some(EventType::Auth(AuthEvent::MfaDenied)),
+            556 => Some(EventType::Auth(AuthEvent::Impersonation)),
+            557 => Some(EventType::Tls(TlsEvent::CertificateSelected)),
+            558 => Some(EventType::Tls(TlsEvent::OcspResponseFetched)),
+            559 => Some(EventType::Tls(TlsEvent::OcspResponseError)),
+            560 => Some(EventType::Smtp(SmtpEvent::IpReputationDelay)),
+            561 => Some(EventType::Smtp(SmtpEvent::IpReputationError)),
+            562 => Some(EventType::Auth(AuthEvent::Throttled)),
+            563 => Some(EventType::Delivery(DeliveryEvent::LocalDelivery)),
+            564 => Some(EventType::MessageIngest(MessageIngestEvent::QuotaWarning)),
+            565 => Some(EventType::Auth(AuthEvent::TokenIssuedAuthCode)),
+            566 => Some(EventType::Auth(AuthEvent::TokenIssuedDeviceCode)),
+            567 => Some(EventType::Auth(AuthEvent::TokenIssuedRefresh)),
+            568 => Some(EventType::Auth(AuthEvent::RefreshTokenRenewed)),
+            569 => Some(EventType::Auth(AuthEvent::TokenExpired)),
+            570 => Some(EventType::Auth(AuthEvent::TokenInvalid)),
+            571 => Some(EventType::Auth(AuthEvent::TokenRevoked)),
+            572 => Some(EventType::MessageIngest(MessageIngestEvent::ScanVerdict)),
+            573 => Some(EventType::MessageIngest(MessageIngestEvent::ScanError)),
+            574 => Some(EventType::Imap(ImapEvent::GetQuota)),
+            575 => Some(EventType::Imap(ImapEvent::SetQuota)),
+            576 => Some(EventType::Smtp(SmtpEvent::Greylisted)),
+            577 => Some(EventType::Smtp(SmtpEvent::GreylistPassed)),
+            578 => Some(EventType::Imap(ImapEvent::Unauthenticate)),
+            579 => Some(EventType::Auth(AuthEvent::DirectoryUnavailable)),
+            580 => Some(EventType::Auth(AuthEvent::SessionsRevoked)),
+            581 => Some(EventType::Smtp(SmtpEvent::PipeliningLimitExceeded)),
+            582 => Some(EventType::Smtp(SmtpEvent::SmtpUtf8Required)),
+            583 => Some(EventType::Security(SecurityEvent::SubmissionLimitExceeded)),
+            584 => Some(EventType::Security(SecurityEvent::SubmissionSuspended)),
+            585 => Some(EventType::Queue(QueueEvent::RecipientRewritten)),
+            586 => Some(EventType::Queue(QueueEvent::RewriteLoop)),
+            587 => Some(EventType::Smtp(SmtpEvent::JournalSuccess)),
+            588 => Some(EventType::Smtp(SmtpEvent::JournalError)),
+            589 => Some(EventType::MessageIngest(
+                MessageIngestEvent::TooManyRecipients,
+            )),
+            590 => Some(EventType::Limit(LimitEvent::TooManyOAuthRequests)),
+            591 => Some(EventType::Telemetry(TelemetryEvent::WebhookSuppressed)),
             _ => None,
         }
     }
@@ -4,7 +4,10 @@
  * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
  */
 
-use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::{
+    fmt::Display,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+};
 
 use rustls::{crypto::ring::cipher_suite::*, SupportedCipherSuite};
 
@@ -22,6 +25,18 @@ pub enum IpAddrOrMask {
     Mask(IpAddrMask),
 }
 
+#[derive(Debug, Clone, Default)]
+pub struct IpNetworkTree {
+    nodes: Vec<IpNetworkNode>,
+    len: usize,
+}
+
+#[derive(Debug, Clone, Default)]
+struct IpNetworkNode {
+    children: [u32; 2],
+    is_network: bool,
+}
+
 impl IpAddrMask {
     pub fn matches(&self, remote: &IpAddr) -> bool {
         match self {
@@ -73,6 +88,127 @@ impl IpAddrMask {
     }
 }
 
+impl IpAddrMask {
+    // Returns the network as an IPv6 address (IPv4 networks are mapped)
+    // and the number of significant bits
+    fn prefix(&self) -> (u128, u32) {
+        match self {
+            IpAddrMask::V4 { addr, mask } => (
+                u128::from_be_bytes(addr.to_ipv6_mapped().octets()),
+                96 + mask.leading_ones(),
+            ),
+            IpAddrMask::V6 { addr, mask } => {
+                (u128::from_be_bytes(addr.octets()), mask.leading_ones())
+            }
+        }
+    }
+}
+
+impl Display for IpAddrMask {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IpAddrMask::V4 { addr, mask } => write!(f, "{}/{}", addr, mask.leading_ones()),
+            IpAddrMask::V6 { addr, mask } => write!(f, "{}/{}", addr, mask.leading_ones()),
+        }
+    }
+}
+
+impl IpNetworkTree {
+    pub fn insert(&mut self, network: &IpAddrMask) -> bool {
+        let (addr, prefix) = network.prefix();
+        if self.nodes.is_empty() {
+            self.nodes.push(IpNetworkNode::default());
+        }
+
+        let mut node = 0;
+        for depth in 0..prefix {
+            let bit = ((addr >> (127 - depth)) & 1) as usize;
+            let child = self.nodes[node].children[bit];
+            node = if child != 0 {
+                child as usize
+            } else {
+                let child = self.nodes.len();
+                self.nodes.push(IpNetworkNode::default());
+                self.nodes[node].children[bit] = child as u32;
+                child
+            };
+        }
+
+        if !self.nodes[node].is_network {
+            self.nodes[node].is_network = true;
+            self.len += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn remove(&mut self, network: &IpAddrMask) -> bool {
+        let (addr, prefix) = network.prefix();
+        if self.nodes.is_empty() {
+            return false;
+        }
+
+        let mut node = 0;
+        for depth in 0..prefix {
+            let bit = ((addr >> (127 - depth)) & 1) as usize;
+            match self.nodes[node].children[bit] {
+                0 => return false,
+                child => node = child as usize,
+            }
+        }
+
+        if self.nodes[node].is_network {
+            self.nodes[node].is_network = false;
+            self.len -= 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn matches(&self, remote: &IpAddr) -> bool {
+        if self.len == 0 {
+            return false;
+        }
+
+        let addr = u128::from_be_bytes(match remote {
+            IpAddr::V4(ip) => ip.to_ipv6_mapped().octets(),
+            IpAddr::V6(ip) => ip.octets(),
+        });
+        let mut node = 0;
+        for depth in 0..128 {
+            if self.nodes[node].is_network {
+                return true;
+            }
+            match self.nodes[node].children[((addr >> (127 - depth)) & 1) as usize] {
+                0 => return false,
+                child => node = child as usize,
+            }
+        }
+
+        self.nodes[node].is_network
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+impl FromIterator<IpAddrMask> for IpNetworkTree {
+    fn from_iter<T: IntoIterator<Item = IpAddrMask>>(iter: T) -> Self {
+        let mut tree = IpNetworkTree::default();
+        for network in iter {
+            tree.insert(&network);
+        }
+        tree
+    }
+}
+
 impl ParseValue for IpAddrMask {
     fn parse_value(value: &str) -> super::Result<Self> {
         if let Some((addr, mask)) = value.rsplit_once('/') {
@@ -183,4 +319,37 @@ mod tests {
             assert!(!mask.matches(&ip));
         }
     }
+    #[test]
+    fn test_ip_network_tree() {
+        let mut tree = ["10.0.0.0/8", "203.0.113.0/24", "2001:db8::/32"]
+            .into_iter()
+            .map(|mask| IpAddrMask::parse_value(mask).unwrap())
+            .collect::<IpNetworkTree>();
+        assert_eq!(tree.len(), 3);
+
+        for (ip, expected) in [
+            ("10.30.20.11", true),
+            ("11.30.20.11", false),
+            ("203.0.113.77", true),
+            ("203.0.114.77", false),
+            ("::ffff:203.0.113.1", true),
+            ("2001:db8:1::1", true),
+            ("2001:db9::1", false),
+        ] {
+            assert_eq!(
+                tree.matches(&ip.parse::<IpAddr>().unwrap()),
+                expected,
+                "{ip}"
+            );
+        }
+
+        let network = IpAddrMask::parse_value("203.0.113.0/24").unwrap();
+        assert_eq!(network.to_string(), "203.0.113.0/24");
+        assert!(!tree.insert(&network));
+        assert!(tree.remove(&network));
+        assert!(!tree.remove(&network));
+        assert!(!tree.matches(&"203.0.113.77".parse::<IpAddr>().unwrap()));
+        assert!(tree.matches(&"10.0.0.1".parse::<IpAddr>().unwrap()));
+        assert_eq!(tree.len(), 2);
+    }
 }
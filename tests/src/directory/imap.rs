@@ -6,7 +6,10 @@
 
 use std::sync::Arc;
 
-use common::listener::limiter::{ConcurrencyLimiter, InFlight};
+use common::{
+    config::server::ServerProtocol,
+    listener::limiter::{ConcurrencyLimiter, InFlight},
+};
 use directory::QueryBy;
 use mail_parser::decoders::base64::base64_decode;
 use mail_send::Credentials;
@@ -99,6 +102,54 @@ async fn imap_directory() {
         );
     }
 
+    // Invalid credentials are a permanent failure
+    let core = config.core;
+    let remote_ip = "10.0.0.1".parse().unwrap();
+    let err = core
+        .authenticate(
+            &handle,
+            0,
+            &Credentials::Plain {
+                username: "john".to_string(),
+                secret: "bad".to_string(),
+            },
+            remote_ip,
+            ServerProtocol::Imap,
+            false,
+        )
+        .await
+        .unwrap_err();
+    assert!(
+        err.matches(trc::EventType::Auth(trc::AuthEvent::Failed)),
+        "{err:?}"
+    );
+
+    // A directory that does not respond is reported as a temporary failure
+    let _listener = TcpListener::bind("127.0.0.1:9197").await.unwrap();
+    let handle = config
+        .directories
+        .directories
+        .remove("imap-timeout")
+        .unwrap();
+    let err = core
+        .authenticate(
+            &handle,
+            0,
+            &Credentials::Plain {
+                username: "john".to_string(),
+                secret: "ok".to_string(),
+            },
+            remote_ip,
+            ServerProtocol::Imap,
+            false,
+        )
+        .await
+        .unwrap_err();
+    assert!(
+        err.matches(trc::EventType::Auth(trc::AuthEvent::DirectoryUnavailable)),
+        "{err:?}"
+    );
+
     // Shutdown
     shutdown.send(false).ok();
 }
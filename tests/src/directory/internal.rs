@@ -4,14 +4,16 @@
  * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
  */
 
-use ahash::AHashSet;
+use ahash::{AHashMap, AHashSet};
+use common::config::server::ServerProtocol;
 use directory::{
     backend::internal::{
         lookup::DirectoryStore,
         manage::{self, ManageDirectory, UpdatePrincipal},
         PrincipalField, PrincipalUpdate, PrincipalValue,
     },
-    Principal, QueryBy, Type,
+    core::secret::SecretRehash,
+    Directory, DirectoryInner, Principal, QueryBy, Type,
 };
 use jmap_proto::types::collection::Collection;
 use mail_send::Credentials;
@@ -20,6 +22,7 @@ use store::{
     write::{BatchBuilder, BitmapClass, ValueClass},
     BitmapKey, Store, ValueKey,
 };
+use utils::config::Config;
 
 use crate::directory::{DirectoryTest, IntoTestPrincipal, TestPrincipal};
 
@@ -575,6 +578,17 @@ async fn internal_directory() {
             vec!["list"]
         );
 
+        // Count principals by type
+        assert_eq!(
+            store
+                .count_principals_by_type(&[Type::Individual, Type::Group, Type::List], None)
+                .await
+                .unwrap(),
+            [(Type::Individual, 2), (Type::Group, 2), (Type::List, 1)]
+                .into_iter()
+                .collect::<AHashMap<_, _>>()
+        );
+
         // Write records on John's and Jane's accounts
         let mut document_id = u32::MAX;
         for account_id in [john_id, jane_id] {
@@ -690,6 +704,92 @@ async fn internal_directory() {
                 .unwrap(),
             Some("hello".to_string())
         );
+
+        // Concurrent logins rehash the password and store the SCRAM credentials once
+        let rehash = SecretRehash::try_from_config(
+            &mut Config::new("directory.test.password.rehash = true").unwrap(),
+            "directory.test",
+        )
+        .unwrap();
+        let rehash_id = store
+            .create_principal(
+                TestPrincipal {
+                    name: "rehash".to_string(),
+                    secrets: vec!["rehash-secret".to_string()],
+                    ..Default::default()
+                }
+                .into(),
+                None,
+            )
+            .await
+            .unwrap();
+        let credentials = Credentials::Plain {
+            username: "rehash".to_string(),
+            secret: "rehash-secret".to_string(),
+        };
+        for result in futures::future::join_all((0..4).map(|_| {
+            store.query_and_rehash(
+                QueryBy::Credentials(&credentials),
+                false,
+                Some(&rehash),
+                true,
+            )
+        }))
+        .await
+        {
+            assert_eq!(result.unwrap().map(|p| p.id()), Some(rehash_id));
+        }
+        let principal = store.get_principal(rehash_id).await.unwrap().unwrap();
+        let secrets = principal
+            .get_str_array(PrincipalField::Secrets)
+            .unwrap_or_default();
+        assert_eq!(secrets.len(), 2, "{secrets:?}");
+        assert!(secrets[0].starts_with("$argon2id$"), "{secrets:?}");
+        assert!(principal.has_scram_secret(), "{secrets:?}");
+        assert_eq!(
+            store
+                .query(QueryBy::Credentials(&credentials), false)
+                .await
+                .unwrap()
+                .map(|p| p.id()),
+            Some(rehash_id)
+        );
+
+        // Secrets that cannot be verified reject the credentials
+        store
+            .create_principal(
+                TestPrincipal {
+                    name: "unsupported".to_string(),
+                    secrets: vec!["{FOO}secret".to_string()],
+                    ..Default::default()
+                }
+                .into(),
+                None,
+            )
+            .await
+            .unwrap();
+        let err = config
+            .core
+            .authenticate(
+                &Directory {
+                    store: DirectoryInner::Internal(store.clone()),
+                    ..Default::default()
+                },
+                0,
+                &Credentials::Plain {
+                    username: "unsupported".to_string(),
+                    secret: "secret".to_string(),
+                },
+                "10.0.0.1".parse().unwrap(),
+                ServerProtocol::Imap,
+                false,
+            )
+            .await
+            .unwrap_err();
+        assert!(
+            err.matches(trc::EventType::Auth(trc::AuthEvent::Failed)),
+            "{err:?}"
+        );
     }
 }
 
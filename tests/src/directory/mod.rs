@@ -180,6 +180,19 @@ max-connections = 5
 enable = true
 allow-invalid-certs = true
 
+[directory."imap-timeout"]
+type = "imap"
+host = "127.0.0.1"
+port = 9197
+timeout = "500ms"
+
+[directory."imap-timeout".pool]
+max-connections = 1
+
+[directory."imap-timeout".pool.timeout]
+create = "500ms"
+wait = "500ms"
+
 ##############################################################################
 
 [directory."smtp"]
@@ -282,6 +282,55 @@ pub async fn test(mut imap_john: &mut ImapConnection, _imap_check: &mut ImapConn
         .await
         .assert_contains("copy test");
 
+    // Moves that would exceed the quota of the destination account fail
+    // without removing the messages from the source mailbox
+    let mut imap_admin = ImapConnection::connect(b"_a ").await;
+    imap_admin
+        .assert_read(Type::Untagged, ResponseType::Ok)
+        .await;
+    imap_admin
+        .send("AUTHENTICATE PLAIN {20+}\r\nAGFkbWluAHNlY3JldA==")
+        .await;
+    imap_admin.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap_admin
+        .send("SETQUOTA \"jane.smith@example.com\" (STORAGE 1)")
+        .await;
+    imap_admin.assert_read(Type::Tagged, ResponseType::Ok).await;
+
+    let uid = assert_append_message(
+        imap_john,
+        "INBOX",
+        "From: john\n\nquota test",
+        ResponseType::Ok,
+    )
+    .await
+    .into_append_uid();
+    imap_john.send("SELECT INBOX").await;
+    imap_john.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap_john
+        .send(&format!(
+            "UID MOVE {} \"Shared Folders/jane.smith@example.com/Inbox\"",
+            uid
+        ))
+        .await;
+    imap_john
+        .assert_read(Type::Tagged, ResponseType::No)
+        .await
+        .assert_response_code("OVERQUOTA")
+        .assert_count("EXPUNGE", 0);
+    imap_john
+        .send(&format!("UID FETCH {} (PREVIEW)", uid))
+        .await;
+    imap_john
+        .assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_contains("quota test");
+
+    imap_admin
+        .send("SETQUOTA \"jane.smith@example.com\" ()")
+        .await;
+    imap_admin.assert_read(Type::Tagged, ResponseType::Ok).await;
+
     // Jane stops sharing with Bill, and removes Insert access to John
     imap_jane.send("DELETEACL INBOX foobar@example.com").await;
     imap_jane.assert_read(Type::Tagged, ResponseType::Ok).await;
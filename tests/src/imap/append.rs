@@ -6,13 +6,19 @@
 
 use std::{fs, io};
 
+use directory::backend::internal::manage::ManageDirectory;
 use imap_proto::ResponseType;
+use jmap_proto::{
+    object::Object,
+    types::{collection::Collection, id::Id, property::Property, value::Value},
+};
+use store::{write::BatchBuilder, Serialize};
 
 use crate::jmap::wait_for_index;
 
 use super::{resources_dir, AssertResult, IMAPTest, ImapConnection, Type};
 
-pub async fn test(imap: &mut ImapConnection, _imap_check: &mut ImapConnection, handle: &IMAPTest) {
+pub async fn test(imap: &mut ImapConnection, imap_check: &mut ImapConnection, handle: &IMAPTest) {
     println!("Running APPEND tests...");
 
     // Invalid APPEND commands
@@ -64,6 +70,342 @@ pub async fn test(imap: &mut ImapConnection, _imap_check: &mut ImapConnection, h
         expected_uid += 1;
     }
 
+    // The INTERNALDATE should round-trip with the timezone it was appended with
+    let message = "Subject: Internal date test\r\n\r\ntest\r\n";
+    imap.send("CREATE \"Internal Dates\"").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+    for date in [
+        "01-Jan-2020 00:00:00 +0500",
+        "07-Feb-1994 22:43:04 -0800",
+        "15-Apr-1985 01:02:18 +0000",
+        "10-Jun-1965 12:00:00 -0500",
+    ] {
+        imap.send(&format!(
+            "APPEND \"Internal Dates\" \"{date}\" {{{}+}}\r\n{message}",
+            message.len()
+        ))
+        .await;
+        imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+    }
+    imap.send("EXAMINE \"Internal Dates\"").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap.send("FETCH 1:4 INTERNALDATE").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_contains("INTERNALDATE \"01-Jan-2020 00:00:00 +0500\"")
+        .assert_contains("INTERNALDATE \"07-Feb-1994 22:43:04 -0800\"")
+        .assert_contains("INTERNALDATE \"15-Apr-1985 01:02:18 +0000\"")
+        // Dates before 1970 are stored as the epoch
+        .assert_contains("INTERNALDATE \"31-Dec-1969 19:00:00 -0500\"");
+
+    imap.send("UNSELECT").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap.send("DELETE \"Internal Dates\"").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+
+    // Concurrent MULTIAPPENDs each return the UIDs of their own messages
+    imap.send("CREATE \"Concurrent\"").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+    let mut imap_a = ImapConnection::connect(b"_a ").await;
+    let mut imap_b = ImapConnection::connect(b"_b ").await;
+    for imap in [&mut imap_a, &mut imap_b] {
+        imap.assert_read(Type::Untagged, ResponseType::Ok).await;
+        imap.send("AUTHENTICATE PLAIN {32+}\r\nAGpkb2VAZXhhbXBsZS5jb20Ac2VjcmV0")
+            .await;
+        imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+    }
+    let build_multiappend = |name: &str| {
+        let mut command = "APPEND \"Concurrent\"".to_string();
+        for num in 0..10 {
+            let message = format!("Subject: Batch {name}\r\n\r\nmessage {num}\r\n");
+            command.push_str(&format!(" {{{}+}}\r\n{message}", message.len()));
+        }
+        command
+    };
+    let (command_a, command_b) = (build_multiappend("A"), build_multiappend("B"));
+    tokio::join!(imap_a.send(&command_a), imap_b.send(&command_b));
+    let (response_a, response_b) = tokio::join!(
+        imap_a.assert_read(Type::Tagged, ResponseType::Ok),
+        imap_b.assert_read(Type::Tagged, ResponseType::Ok)
+    );
+    for (name, response) in [("A", response_a), ("B", response_b)] {
+        let uids = response.into_append_uid();
+
+        imap.send("EXAMINE \"Concurrent\"").await;
+        imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+        imap.send(&format!(
+            "UID FETCH {uids} (BODY.PEEK[HEADER.FIELDS (SUBJECT)])"
+        ))
+        .await;
+        imap.assert_read(Type::Tagged, ResponseType::Ok)
+            .await
+            .assert_count("Subject: Batch", 10)
+            .assert_count(&format!("Subject: Batch {name}"), 10);
+    }
+    imap.send("UNSELECT").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap.send("DELETE \"Concurrent\"").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+
+    // Retried appends within the deduplication window return the original UID
+    let mut imap_bill = ImapConnection::connect(b"_z ").await;
+    imap_bill
+        .assert_read(Type::Untagged, ResponseType::Ok)
+        .await;
+    imap_bill
+        .send("AUTHENTICATE PLAIN {36+}\r\nAGZvb2JhckBleGFtcGxlLmNvbQBzZWNyZXQ=")
+        .await;
+    imap_bill.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap_bill.send("CREATE \"Dedup\"").await;
+    imap_bill.assert_read(Type::Tagged, ResponseType::Ok).await;
+    let mut uids = Vec::new();
+    for message in [
+        "Message-ID: <retry@domain>\r\nSubject: Retry\r\n\r\nfirst\r\n",
+        "Message-ID: <retry@domain>\r\nSubject: Retry\r\n\r\nfirst\r\n",
+        "Message-ID: <other@domain>\r\nSubject: Other\r\n\r\nsecond\r\n",
+    ] {
+        let response =
+            assert_append_message(&mut imap_bill, "Dedup", message, ResponseType::Ok).await;
+        uids.push(response.into_append_uid());
+    }
+    assert_eq!(uids[0], uids[1]);
+    assert_ne!(uids[0], uids[2]);
+    imap_bill.send("STATUS \"Dedup\" (MESSAGES)").await;
+    imap_bill
+        .assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_contains("MESSAGES 2");
+    imap_bill.send("DELETE \"Dedup\"").await;
+    imap_bill.assert_read(Type::Tagged, ResponseType::Ok).await;
+
+    // REPLACE appends the new message and expunges the original one
+    imap_bill.send("CREATE \"Replace\"").await;
+    imap_bill.assert_read(Type::Tagged, ResponseType::Ok).await;
+    assert_append_message(
+        &mut imap_bill,
+        "Replace",
+        "Subject: Draft v1\r\n\r\nfirst\r\n",
+        ResponseType::Ok,
+    )
+    .await;
+    imap_bill.send("SELECT \"Replace\"").await;
+    imap_bill.assert_read(Type::Tagged, ResponseType::Ok).await;
+    let message = "Subject: Draft v2\r\n\r\nsecond\r\n";
+    imap_bill
+        .send(&format!(
+            "REPLACE 1 \"Replace\" {{{}+}}\r\n{message}",
+            message.len()
+        ))
+        .await;
+    imap_bill
+        .assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_contains("APPENDUID")
+        .assert_contains("* 1 EXPUNGE");
+    imap_bill.send("REPLACE 5 \"Replace\" {1+}\r\na").await;
+    imap_bill.assert_read(Type::Tagged, ResponseType::No).await;
+    imap_bill
+        .send("FETCH 1:* (BODY.PEEK[HEADER.FIELDS (SUBJECT)])")
+        .await;
+    imap_bill
+        .assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_count("Subject: Draft", 1)
+        .assert_contains("Subject: Draft v2");
+    imap_bill.send("UNSELECT").await;
+    imap_bill.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap_bill.send("DELETE \"Replace\"").await;
+    imap_bill.assert_read(Type::Tagged, ResponseType::Ok).await;
+
+    // CATENATE assembles messages from literal text and existing messages
+    imap_bill.send("CREATE \"Catenate\"").await;
+    imap_bill.assert_read(Type::Tagged, ResponseType::Ok).await;
+    let uid = assert_append_message(
+        &mut imap_bill,
+        "Catenate",
+        "Subject: Original\r\n\r\noriginal\r\n",
+        ResponseType::Ok,
+    )
+    .await
+    .into_append_uid();
+    let header = "Subject: Fwd\r\nContent-Type: message/rfc822\r\n\r\n";
+    imap_bill
+        .send(&format!(
+            "APPEND \"Catenate\" CATENATE (TEXT {{{}+}}\r\n{header} URL \"/Catenate/;UID={uid}\")",
+            header.len()
+        ))
+        .await;
+    imap_bill
+        .assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_contains("APPENDUID");
+    imap_bill
+        .send("APPEND \"Catenate\" CATENATE (URL \"/Catenate/;UID=999\")")
+        .await;
+    imap_bill
+        .assert_read(Type::Tagged, ResponseType::No)
+        .await
+        .assert_contains("[BADURL \"/Catenate/;UID=999\"]");
+    imap_bill.send("EXAMINE \"Catenate\"").await;
+    imap_bill.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap_bill.send("FETCH 2 BODY.PEEK[]").await;
+    imap_bill
+        .assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_contains("Subject: Fwd")
+        .assert_contains("Subject: Original");
+    imap_bill.send("UNSELECT").await;
+    imap_bill.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap_bill.send("DELETE \"Catenate\"").await;
+    imap_bill.assert_read(Type::Tagged, ResponseType::Ok).await;
+
+    // Special-use references resolve to the mailbox with that role
+    assert_append_message(
+        &mut imap_bill,
+        "\\\\Sent",
+        "Subject: Sent copy\r\n\r\nsent\r\n",
+        ResponseType::Ok,
+    )
+    .await;
+    imap_bill.send("STATUS \"Sent Items\" (MESSAGES)").await;
+    imap_bill
+        .assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_contains("MESSAGES 1");
+    assert_append_message(
+        &mut imap_bill,
+        "\\\\Archive",
+        "Subject: Archived\r\n\r\narchived\r\n",
+        ResponseType::No,
+    )
+    .await
+    .assert_response_code("TRYCREATE");
+
+    // Role references are not resolved outside of APPEND
+    imap_bill.send("DELETE \"\\\\Sent\"").await;
+    imap_bill.assert_read(Type::Tagged, ResponseType::No).await;
+    imap_bill.send("STATUS \"Sent Items\" (MESSAGES)").await;
+    imap_bill
+        .assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_contains("MESSAGES 1");
+
+    // Object ids match the JMAP ids of the same objects
+    imap.send("CREATE \"Object Ids\"").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+    assert_append_message(
+        imap,
+        "Object Ids",
+        "Subject: Object ids\r\n\r\ntest\r\n",
+        ResponseType::Ok,
+    )
+    .await;
+    let account_id = handle
+        .jmap
+        .core
+        .storage
+        .data
+        .get_principal_id("jdoe@example.com")
+        .await
+        .unwrap()
+        .unwrap();
+    let mailbox_id = handle
+        .jmap
+        .mailbox_get_by_name(account_id, "Object Ids")
+        .await
+        .unwrap()
+        .unwrap();
+    let document_id = handle
+        .jmap
+        .get_tag(
+            account_id,
+            Collection::Email,
+            Property::MailboxIds,
+            mailbox_id,
+        )
+        .await
+        .unwrap()
+        .unwrap()
+        .min()
+        .unwrap();
+    let thread_id = handle
+        .jmap
+        .get_property::<u32>(
+            account_id,
+            Collection::Email,
+            document_id,
+            Property::ThreadId,
+        )
+        .await
+        .unwrap()
+        .unwrap();
+    let mailbox_id = Id::from(mailbox_id).to_string();
+    imap.send("STATUS \"Object Ids\" (MAILBOXID)").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_contains(&format!("MAILBOXID ({mailbox_id})"));
+    imap.send("SELECT \"Object Ids\"").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_contains(&format!("[MAILBOXID ({mailbox_id})]"));
+    imap.send("FETCH 1 (EMAILID THREADID)").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_contains(&format!(
+            "EMAILID ({})",
+            Id::from_parts(thread_id, document_id)
+        ))
+        .assert_contains(&format!("THREADID ({})", Id::from(thread_id)));
+    imap.send("UNSELECT").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap.send("DELETE \"Object Ids\"").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+
+    // Mailboxes without a persisted UID validity do not have sticky UIDs
+    imap_check.send("CREATE \"Not Sticky\"").await;
+    imap_check.assert_read(Type::Tagged, ResponseType::Ok).await;
+    let mailbox_id = handle
+        .jmap
+        .mailbox_get_by_name(account_id, "Not Sticky")
+        .await
+        .unwrap()
+        .unwrap();
+    let mut mailbox = handle
+        .jmap
+        .get_property::<Object<Value>>(
+            account_id,
+            Collection::Mailbox,
+            mailbox_id,
+            &Property::Value,
+        )
+        .await
+        .unwrap()
+        .unwrap();
+    mailbox.remove(&Property::Cid);
+    let mut batch = BatchBuilder::new();
+    batch
+        .with_account_id(account_id)
+        .with_collection(Collection::Mailbox)
+        .update_document(mailbox_id)
+        .set(Property::Value, mailbox.serialize());
+    handle.jmap.write_batch(batch).await.unwrap();
+    assert_append_message(
+        imap,
+        "Not Sticky",
+        "Subject: Not sticky\r\n\r\ntest\r\n",
+        ResponseType::Ok,
+    )
+    .await
+    .assert_response_code("UIDNOTSTICKY");
+    imap.send("SELECT \"Not Sticky\"").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_contains("[UIDNOTSTICKY]");
+    imap.send("UNSELECT").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap.send("DELETE \"Not Sticky\"").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+
     wait_for_index(&handle.jmap).await;
 }
 
@@ -0,0 +1,210 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use std::time::Duration;
+
+use flate2::{Compress, Compression, Decompress, FlushCompress, FlushDecompress};
+use imap_proto::ResponseType;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+};
+
+use super::{AssertResult, IMAPTest};
+
+pub async fn test(handle: &IMAPTest) {
+    println!("Running COMPRESS tests...");
+
+    // Allow LOGIN on the clear-text port
+    let mut core = handle.jmap.shared_core.load_full().as_ref().clone();
+    core.imap.allow_plain_auth = true;
+    handle.jmap.shared_core.store(core.into());
+
+    let mut imap = CompressedConnection::connect().await;
+    imap.read("*").await;
+
+    // COMPRESS requires authentication
+    imap.send("c0 COMPRESS DEFLATE\r\n").await;
+    imap.assert_read("c0", ResponseType::No).await;
+
+    // Login and make sure the capability is advertised
+    imap.send("c1 LOGIN jdoe@example.com secret\r\n").await;
+    imap.assert_read("c1", ResponseType::Ok).await;
+    imap.send("c2 CAPABILITY\r\n").await;
+    imap.assert_read("c2", ResponseType::Ok)
+        .await
+        .assert_contains("COMPRESS=DEFLATE");
+
+    // Unsupported algorithms are rejected
+    imap.send("c3 COMPRESS LZMA\r\n").await;
+    imap.assert_read("c3", ResponseType::Bad).await;
+
+    // Enable compression and pipeline a compressed command right after it
+    let mut command = b"c4 COMPRESS DEFLATE\r\n".to_vec();
+    command.extend(imap.compress(b"c5 CREATE \"Compressed\"\r\n"));
+    imap.send_raw(&command).await;
+    imap.assert_read("c4", ResponseType::Ok).await;
+    imap.start_compression();
+    imap.assert_read("c5", ResponseType::Ok).await;
+
+    // Append, select and fetch over the compressed channel
+    let message = "From: bill@example.com\r\nSubject: Compressed\r\n\r\nThis message travelled compressed.\r\n";
+    imap.send(&format!(
+        "c6 APPEND \"Compressed\" {{{}+}}\r\n{}\r\n",
+        message.len(),
+        message
+    ))
+    .await;
+    imap.assert_read("c6", ResponseType::Ok).await;
+    imap.send("c7 SELECT \"Compressed\"\r\n").await;
+    imap.assert_read("c7", ResponseType::Ok)
+        .await
+        .assert_contains("* 1 EXISTS");
+    imap.send("c8 FETCH 1 (BODY[])\r\n").await;
+    imap.assert_read("c8", ResponseType::Ok)
+        .await
+        .assert_contains("This message travelled compressed.");
+
+    // Compression cannot be enabled twice and TLS cannot be negotiated on top of it
+    imap.send("c9 COMPRESS DEFLATE\r\n").await;
+    imap.assert_read("c9", ResponseType::Bad)
+        .await
+        .assert_response_code("COMPRESSIONACTIVE");
+    imap.send("c10 STARTTLS\r\n").await;
+    imap.assert_read("c10", ResponseType::Bad).await;
+
+    // Clean up
+    imap.send("c11 UNSELECT\r\n").await;
+    imap.assert_read("c11", ResponseType::Ok).await;
+    imap.send("c12 DELETE \"Compressed\"\r\n").await;
+    imap.assert_read("c12", ResponseType::Ok).await;
+    imap.send("c13 LOGOUT\r\n").await;
+    imap.assert_read("c13", ResponseType::Ok).await;
+
+    let mut core = handle.jmap.shared_core.load_full().as_ref().clone();
+    core.imap.allow_plain_auth = false;
+    handle.jmap.shared_core.store(core.into());
+}
+
+struct CompressedConnection {
+    stream: TcpStream,
+    compressor: Compress,
+    decompressor: Option<Decompress>,
+    input: Vec<u8>,
+}
+
+impl CompressedConnection {
+    async fn connect() -> Self {
+        CompressedConnection {
+            stream: TcpStream::connect("127.0.0.1:9991").await.unwrap(),
+            compressor: Compress::new(Compression::default(), false),
+            decompressor: None,
+            input: Vec::new(),
+        }
+    }
+
+    fn compress(&mut self, bytes: &[u8]) -> Vec<u8> {
+        let mut output = Vec::with_capacity(bytes.len() + 128);
+        let total_in = self.compressor.total_in();
+        loop {
+            let offset = (self.compressor.total_in() - total_in) as usize;
+            output.reserve(bytes.len() + 128);
+            self.compressor
+                .compress_vec(&bytes[offset..], &mut output, FlushCompress::Sync)
+                .unwrap();
+            if (self.compressor.total_in() - total_in) as usize == bytes.len()
+                && output.len() < output.capacity()
+            {
+                return output;
+            }
+        }
+    }
+
+    fn decompress(&mut self, bytes: &[u8]) {
+        let decompressor = self.decompressor.as_mut().unwrap();
+        let total_in = decompressor.total_in();
+        loop {
+            let offset = (decompressor.total_in() - total_in) as usize;
+            self.input.reserve(bytes.len() * 4 + 1024);
+            decompressor
+                .decompress_vec(&bytes[offset..], &mut self.input, FlushDecompress::Sync)
+                .unwrap();
+            if (decompressor.total_in() - total_in) as usize == bytes.len()
+                && self.input.len() < self.input.capacity()
+            {
+                return;
+            }
+        }
+    }
+
+    // Bytes received after the COMPRESS response are compressed
+    fn start_compression(&mut self) {
+        self.decompressor = Some(Decompress::new(false));
+        let pending = std::mem::take(&mut self.input);
+        self.decompress(&pending);
+    }
+
+    async fn send(&mut self, text: &str) {
+        let bytes = if self.decompressor.is_some() {
+            self.compress(text.as_bytes())
+        } else {
+            text.as_bytes().to_vec()
+        };
+        self.send_raw(&bytes).await;
+    }
+
+    async fn send_raw(&mut self, bytes: &[u8]) {
+        self.stream.write_all(bytes).await.unwrap();
+        self.stream.flush().await.unwrap();
+    }
+
+    async fn read(&mut self, tag: &str) -> Vec<String> {
+        let mut lines = Vec::new();
+        let mut buf = vec![0u8; 4096];
+
+        loop {
+            while let Some(pos) = self.input.windows(2).position(|w| w == b"\r\n") {
+                let line = String::from_utf8(self.input.drain(..pos + 2).collect()).unwrap();
+                let line = line.trim_end().to_string();
+                let is_done = line.starts_with(&format!("{tag} "));
+                lines.push(line);
+                if is_done {
+                    return lines;
+                }
+            }
+
+            match tokio::time::timeout(Duration::from_millis(1500), self.stream.read(&mut buf))
+                .await
+            {
+                Ok(Ok(bytes_read)) if bytes_read > 0 => {
+                    if self.decompressor.is_some() {
+                        self.decompress(&buf[..bytes_read]);
+                    } else {
+                        self.input.extend_from_slice(&buf[..bytes_read]);
+                    }
+                }
+                Ok(Ok(_)) => panic!("Connection closed: {:?}", lines),
+                Ok(Err(err)) => panic!("Connection broken: {} ({:?})", err, lines),
+                Err(_) => panic!("Timeout while waiting for server response: {:?}", lines),
+            }
+        }
+    }
+
+    async fn assert_read(&mut self, tag: &str, rt: ResponseType) -> Vec<String> {
+        let lines = self.read(tag).await;
+        let mut expected = format!("{tag} ").into_bytes();
+        rt.serialize(&mut expected);
+        if lines
+            .last()
+            .unwrap()
+            .starts_with(std::str::from_utf8(&expected).unwrap())
+        {
+            lines
+        } else {
+            panic!("Expected {:?} from server but got: {:?}", rt, lines);
+        }
+    }
+}
@@ -277,4 +277,32 @@ pub async fn test(imap: &mut ImapConnection, imap_check: &mut ImapConnection) {
         .await
         .assert_count("FETCH (", 3)
         .assert_contains("VANISHED (EARLIER) 1:2"); // .assert_contains("VANISHED (EARLIER) 2");
+
+    // APPEND returns the new HIGHESTMODSEQ, also when the mailbox is not selected
+    imap.send("CREATE Gruyere").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap.send("STATUS Gruyere (HIGHESTMODSEQ)").await;
+    let mut hms = imap
+        .assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .into_highest_modseq();
+    for message in build_messages().into_iter().take(3) {
+        let append_hms = assert_append_message(imap, "Gruyere", &message, ResponseType::Ok)
+            .await
+            .into_highest_modseq();
+        assert!(
+            append_hms.parse::<u64>().unwrap() > hms.parse::<u64>().unwrap(),
+            "{append_hms} <= {hms}"
+        );
+        imap.send("STATUS Gruyere (HIGHESTMODSEQ)").await;
+        assert_eq!(
+            imap.assert_read(Type::Tagged, ResponseType::Ok)
+                .await
+                .into_highest_modseq(),
+            append_hms
+        );
+        hms = append_hms;
+    }
+    imap.send("DELETE Gruyere").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
 }
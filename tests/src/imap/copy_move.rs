@@ -31,6 +31,13 @@ pub async fn test(_imap: &mut ImapConnection, imap_check: &mut ImapConnection) {
         .await
         .assert_response_code("CANNOT");
 
+    // Moving to the same mailbox has no effect
+    imap_check.send("MOVE 1:* INBOX").await;
+    imap_check
+        .assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_count("EXPUNGE", 0);
+
     // Copying to a non-existent mailbox should fail
     imap_check.send("COPY 1:* \"/dev/null\"").await;
     imap_check
@@ -102,9 +109,36 @@ pub async fn test(_imap: &mut ImapConnection, imap_check: &mut ImapConnection) {
         .await
         .assert_count("\\Recent", 0);*/
 
-    // Move all messages to Burrata
+    // Messages flagged as deleted are reported along with the storage they use
     imap_check.send("SELECT \"Scamorza Affumicata\"").await;
     imap_check.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap_check.send("STORE 1:2 +FLAGS.SILENT (\\Deleted)").await;
+    imap_check.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap_check
+        .send("STATUS \"Scamorza Affumicata\" (SIZE DELETED DELETED-STORAGE)")
+        .await;
+    let deleted_storage = imap_check
+        .assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_contains("SIZE 5851")
+        .assert_contains("DELETED 2")
+        .join("")
+        .split_once("DELETED-STORAGE ")
+        .and_then(|(_, size)| size.split_once(')'))
+        .and_then(|(size, _)| size.parse::<u64>().ok())
+        .unwrap();
+    assert!(deleted_storage > 0 && deleted_storage < 5851);
+    imap_check.send("STORE 1:2 -FLAGS.SILENT (\\Deleted)").await;
+    imap_check.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap_check
+        .send("STATUS \"Scamorza Affumicata\" (SIZE DELETED DELETED-STORAGE)")
+        .await;
+    imap_check
+        .assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_contains("(SIZE 5851 DELETED 0 DELETED-STORAGE 0)");
+
+    // Move all messages to Burrata
     imap_check.send("MOVE 1:* \"Burrata al Tartufo\"").await;
     imap_check
         .assert_read(Type::Tagged, ResponseType::Ok)
@@ -60,6 +60,13 @@ pub async fn test() {
         .await;
     sieve.assert_read(ResponseType::Ok).await;
 
+    // New scripts cannot exceed the per-account limit
+    sieve.send("PUTSCRIPT \"one too many\" \"keep;\"").await;
+    sieve
+        .assert_read(ResponseType::No)
+        .await
+        .assert_contains("QUOTA/MAXSCRIPTS");
+
     // GetScript
     sieve.send("GETSCRIPT \"simple script\"").await;
     sieve
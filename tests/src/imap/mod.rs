@@ -8,6 +8,7 @@ pub mod acl;
 pub mod append;
 pub mod basic;
 pub mod body_structure;
+pub mod compress;
 pub mod condstore;
 pub mod copy_move;
 pub mod fetch;
@@ -15,11 +16,13 @@ pub mod idle;
 pub mod mailbox;
 pub mod managesieve;
 pub mod pop;
+pub mod quota;
 pub mod search;
 pub mod store;
 pub mod thread;
 
 use std::{
+    num::NonZeroU32,
     path::PathBuf,
     sync::Arc,
     time::{Duration, Instant},
@@ -36,6 +39,7 @@ use common::{
 
 use ::store::Stores;
 use ahash::AHashSet;
+use base64::{engine::general_purpose::STANDARD, Engine};
 use imap::core::{ImapSessionManager, Inner, IMAP};
 use imap_proto::ResponseType;
 use jmap::{api::JmapSessionManager, JMAP};
@@ -183,6 +187,12 @@ private-key = "%{file:{PK}}%"
 [imap.protocol]
 uidplus = true
 
+[imap.auth.unauthenticate]
+trusted-networks = ["127.0.0.1"]
+
+[sieve.untrusted.limits]
+max-scripts = 2
+
 [storage]
 data = "{STORE}"
 fts = "{STORE}"
@@ -220,6 +230,11 @@ throttle = "500ms"
 throttle = "500ms"
 attempts.interval = "500ms"
 
+[jmap.email.append.dedup]
+window = [{if = "authenticated_as = 'foobar@example.com'", then = "1h"},
+          {else = false}]
+key = "message-id"
+
 [jmap.folders.inbox]
 name = "Inbox"
 subscribe = false
@@ -256,6 +271,7 @@ domains = "SELECT 1 FROM emails WHERE address LIKE '%@' || ? LIMIT 1"
 [directory."{STORE}"]
 type = "internal"
 store = "{STORE}"
+password.scram = true
 
 [oauth]
 key = "parerga_und_paralipomena"
@@ -483,6 +499,69 @@ pub async fn imap_tests() {
     idle::test(&mut imap, &mut imap_check).await;
     condstore::test(&mut imap, &mut imap_check).await;
     acl::test(&mut imap, &mut imap_check).await;
+    quota::test(&mut imap, &mut imap_check).await;
+    compress::test(&handle).await;
+
+    // Reuse the connection for another user
+    imap.send("CAPABILITY").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_contains("UNAUTHENTICATE");
+    imap.send("ENABLE CONDSTORE").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap.send("SELECT INBOX").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap.send("UNAUTHENTICATE").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap.send("CAPABILITY").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_contains("AUTH=PLAIN")
+        .assert_contains("AUTH=SCRAM-SHA-256")
+        .assert_count("SCRAM-SHA-256-PLUS", 0)
+        .assert_count("UNAUTHENTICATE", 0);
+    imap.send("FETCH 1 UID").await;
+    imap.assert_read(Type::Tagged, ResponseType::No).await;
+    imap.send("LIST \"\" \"*\"").await;
+    imap.assert_read(Type::Tagged, ResponseType::No).await;
+
+    // Authenticate using SCRAM-SHA-256
+    for (password, is_valid) in [("wrong", false), ("secret", true)] {
+        let client_first_bare = "n=jdoe@example.com,r=fyko+d2lbbFgONRv9qkxdawL";
+        imap.send(&format!(
+            "AUTHENTICATE SCRAM-SHA-256 {}",
+            STANDARD.encode(format!("n,,{client_first_bare}"))
+        ))
+        .await;
+        let server_first = imap
+            .assert_read(Type::Continuation, ResponseType::Ok)
+            .await
+            .pop()
+            .unwrap();
+        imap.send_untagged(&scram_client_final(
+            password,
+            client_first_bare,
+            server_first.strip_prefix("+ ").unwrap(),
+        ))
+        .await;
+        if is_valid {
+            imap.assert_read(Type::Continuation, ResponseType::Ok).await;
+            imap.send_untagged("").await;
+            imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+        } else {
+            imap.assert_read(Type::Tagged, ResponseType::No).await;
+        }
+    }
+    imap.send("UNAUTHENTICATE").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+
+    imap.send("AUTHENTICATE PLAIN {32+}\r\nAGpkb2VAZXhhbXBsZS5jb20Ac2VjcmV0")
+        .await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap.send("SELECT INBOX").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_count("HIGHESTMODSEQ", 0);
 
     // Logout
     for imap in [&mut imap, &mut imap_check] {
@@ -513,6 +592,52 @@ pub async fn imap_tests() {
     }
 }
 
+// Computes the SCRAM-SHA-256 client-final message for the given server-first message
+fn scram_client_final(password: &str, client_first_bare: &str, server_first: &str) -> String {
+    let server_first = String::from_utf8(STANDARD.decode(server_first).unwrap()).unwrap();
+    let mut nonce = "";
+    let mut salt = Vec::new();
+    let mut iterations = 0;
+    for attribute in server_first.split(',') {
+        match attribute.split_once('=').unwrap() {
+            ("r", value) => nonce = value,
+            ("s", value) => salt = STANDARD.decode(value).unwrap(),
+            ("i", value) => iterations = value.parse().unwrap(),
+            _ => {}
+        }
+    }
+
+    let mut salted_password = [0u8; 32];
+    ring::pbkdf2::derive(
+        ring::pbkdf2::PBKDF2_HMAC_SHA256,
+        NonZeroU32::new(iterations).unwrap(),
+        &salt,
+        password.as_bytes(),
+        &mut salted_password,
+    );
+    let client_key = ring::hmac::sign(
+        &ring::hmac::Key::new(ring::hmac::HMAC_SHA256, &salted_password),
+        b"Client Key",
+    );
+    let stored_key = ring::digest::digest(&ring::digest::SHA256, client_key.as_ref());
+    let client_final_without_proof = format!("c=biws,r={nonce}");
+    let client_signature = ring::hmac::sign(
+        &ring::hmac::Key::new(ring::hmac::HMAC_SHA256, stored_key.as_ref()),
+        format!("{client_first_bare},{server_first},{client_final_without_proof}").as_bytes(),
+    );
+    let proof = client_key
+        .as_ref()
+        .iter()
+        .zip(client_signature.as_ref())
+        .map(|(a, b)| a ^ b)
+        .collect::<Vec<_>>();
+
+    STANDARD.encode(format!(
+        "{client_final_without_proof},p={}",
+        STANDARD.encode(proof)
+    ))
+}
+
 pub struct ImapConnection {
     tag: &'static [u8],
     reader: Lines<BufReader<ReadHalf<TcpStream>>>,
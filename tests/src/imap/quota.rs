@@ -0,0 +1,78 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use imap_proto::ResponseType;
+
+use super::{AssertResult, ImapConnection, Type};
+
+pub async fn test(imap_john: &mut ImapConnection, _imap_check: &mut ImapConnection) {
+    println!("Running QUOTA tests...");
+
+    // QUOTA support is advertised to authenticated clients
+    imap_john.send("CAPABILITY").await;
+    imap_john
+        .assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_contains("QUOTA=RES-STORAGE")
+        .assert_contains("QUOTASET");
+
+    // Accounts without a quota have no resource limits
+    imap_john.send("GETQUOTAROOT INBOX").await;
+    imap_john
+        .assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_contains("* QUOTAROOT \"INBOX\" \"\"")
+        .assert_contains("* QUOTA \"\" ()");
+    imap_john.send("GETQUOTAROOT \"Does not exist\"").await;
+    imap_john.assert_read(Type::Tagged, ResponseType::No).await;
+    imap_john.send("GETQUOTA \"popper@example.com\"").await;
+    imap_john.assert_read(Type::Tagged, ResponseType::No).await;
+
+    // Only administrators can set quotas
+    imap_john.send("SETQUOTA \"\" (STORAGE 1048576)").await;
+    imap_john.assert_read(Type::Tagged, ResponseType::No).await;
+
+    let mut imap_admin = ImapConnection::connect(b"_a ").await;
+    imap_admin
+        .assert_read(Type::Untagged, ResponseType::Ok)
+        .await;
+    imap_admin
+        .send("AUTHENTICATE PLAIN {20+}\r\nAGFkbWluAHNlY3JldA==")
+        .await;
+    imap_admin.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap_admin
+        .send("SETQUOTA \"jdoe@example.com\" (MESSAGE 100)")
+        .await;
+    imap_admin.assert_read(Type::Tagged, ResponseType::No).await;
+    imap_admin
+        .send("SETQUOTA \"jdoe@example.com\" (STORAGE 1048576)")
+        .await;
+    imap_admin
+        .assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_contains("* QUOTA \"jdoe@example.com\" (STORAGE ")
+        .assert_contains(" 1048576)");
+
+    // The new limit is reported along with the storage used by the account
+    imap_john.send("GETQUOTA \"\"").await;
+    imap_john
+        .assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_contains("* QUOTA \"\" (STORAGE ")
+        .assert_contains(" 1048576)");
+
+    // Removing all limits clears the quota
+    imap_admin.send("SETQUOTA \"jdoe@example.com\" ()").await;
+    imap_admin
+        .assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_contains("* QUOTA \"jdoe@example.com\" ()");
+    imap_john.send("GETQUOTA \"\"").await;
+    imap_john
+        .assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_contains("* QUOTA \"\" ()");
+}
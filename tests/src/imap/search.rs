@@ -60,6 +60,19 @@ pub async fn test(imap: &mut ImapConnection, imap_check: &mut ImapConnection) {
         .await
         .assert_equals("* SEARCH 10");
 
+    // Fuzzy searches match at least the same messages as plain searches
+    imap_check.send("UID SEARCH SUBJECT export").await;
+    imap_check
+        .assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_equals("* SEARCH 10");
+
+    imap_check.send("UID SEARCH FUZZY SUBJECT export").await;
+    imap_check
+        .assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_equals("* SEARCH 10");
+
     imap_check
         .send("UID SEARCH NOT (FROM nathaniel ANSWERED)")
         .await;
@@ -105,11 +105,27 @@ pub async fn test(imap: &mut ImapConnection, _imap_check: &mut ImapConnection) {
         .assert_contains("(1)")
         .assert_count("(", 1);
 
+    // Saved results survive an EXPUNGE and map to the current sequence numbers
+    imap.send("SEARCH RETURN (SAVE) SUBJECT T1").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap.send("STORE 1 +FLAGS.SILENT (\\Deleted)").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap.send("EXPUNGE").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_count("EXPUNGE", 1);
+    imap.send("FETCH $ (UID)").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok)
+        .await
+        .assert_contains("* 4 FETCH (UID 5)")
+        .assert_contains("* 7 FETCH (UID 8)")
+        .assert_count("FETCH (UID", 4);
+
     // Delete all messages
     imap.send("STORE 1:* +FLAGS.SILENT (\\Deleted)").await;
     imap.assert_read(Type::Tagged, ResponseType::Ok).await;
     imap.send("EXPUNGE").await;
     imap.assert_read(Type::Tagged, ResponseType::Ok)
         .await
-        .assert_count("EXPUNGE", 13);
+        .assert_count("EXPUNGE", 12);
 }
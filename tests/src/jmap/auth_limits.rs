@@ -10,8 +10,25 @@ use std::{
     time::Duration,
 };
 
-use common::listener::blocked::BLOCKED_IP_KEY;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use common::{
+    config::{jmap::settings::JmapConfig, server::ServerProtocol},
+    listener::blocked::BLOCKED_IP_KEY,
+    manager::webadmin::Resource,
+    Core,
+};
+use directory::{
+    backend::internal::{
+        manage::{ManageDirectory, UpdatePrincipal},
+        PrincipalField, PrincipalUpdate, PrincipalValue,
+    },
+    core::secret::AppPassword,
+    Directory, Permission, Principal, ROLE_USER,
+};
+use hyper::{body, server::conn::http1, service::service_fn};
+use hyper_util::rt::TokioIo;
 use imap_proto::ResponseType;
+use jmap::api::http::{fetch_body, ToHttpResponse};
 use jmap_client::{
     client::{Client, Credentials},
     core::set::{SetError, SetErrorType},
@@ -19,11 +36,13 @@ use jmap_client::{
 };
 use jmap_proto::types::id::Id;
 use store::write::now;
+use tokio::{net::TcpListener, sync::watch};
+use utils::config::{ipmask::IpAddrMask, utils::ParseValue, Config};
 
 use crate::{
     directory::internal::TestInternalDirectory,
     imap::{ImapConnection, Type},
-    jmap::{assert_is_empty, mailbox::destroy_all_mailboxes},
+    jmap::{assert_is_empty, mailbox::destroy_all_mailboxes, ManagementApi},
 };
 
 use super::JMAPTest;
@@ -156,6 +175,65 @@ pub async fn test(params: &mut JMAPTest) {
         .write()
         .remove(&IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)));
 
+    // Block the whole loopback network
+    let blocked_range = IpAddrMask::parse_value("127.0.0.0/8").unwrap();
+    server
+        .core
+        .add_blocked_range(blocked_range.clone())
+        .await
+        .unwrap();
+    assert_eq!(
+        server
+            .core
+            .storage
+            .config
+            .get(format!("{BLOCKED_IP_KEY}.127.0.0.0/8"))
+            .await
+            .unwrap(),
+        Some(String::new())
+    );
+    ImapConnection::connect(b"_y ")
+        .await
+        .assert_disconnect()
+        .await;
+
+    // Allowed networks take precedence over blocked networks
+    let allowed_range = IpAddrMask::parse_value("127.0.0.1/32").unwrap();
+    server
+        .core
+        .add_allowed_range(allowed_range.clone())
+        .await
+        .unwrap();
+    ImapConnection::connect(b"_y ")
+        .await
+        .assert_read(Type::Untagged, ResponseType::Ok)
+        .await;
+
+    // Remove both networks
+    assert!(server
+        .core
+        .remove_allowed_range(&allowed_range)
+        .await
+        .unwrap());
+    assert!(server
+        .core
+        .remove_blocked_range(&blocked_range)
+        .await
+        .unwrap());
+    assert!(!server
+        .core
+        .is_ip_blocked(&IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1))));
+    assert_eq!(
+        server
+            .core
+            .storage
+            .config
+            .get(format!("{BLOCKED_IP_KEY}.127.0.0.0/8"))
+            .await
+            .unwrap(),
+        None
+    );
+
     // Valid authentication requests should not be rate limited
     for _ in 0..110 {
         Client::new()
@@ -256,6 +334,287 @@ pub async fn test(params: &mut JMAPTest) {
         client.upload(None, b"sleep".to_vec(), None).await,
         Err(jmap_client::Error::Problem(err)) if err.status() == Some(400)));
 
+    // Logins that require a second factor should only succeed if the webhook allows them
+    let mfa_endpoint = spawn_mock_mfa_endpoint();
+    let mut mfa_account_ids = Vec::new();
+    for (account, expected_status) in [
+        ("mfa.allow@example.com", None),
+        ("mfa.deny@example.com", Some(401)),
+        ("mfa.timeout@example.com", Some(401)),
+    ] {
+        mfa_account_ids.push(Id::from(
+            server
+                .core
+                .storage
+                .data
+                .create_test_user(account, "12345", account, &[account])
+                .await,
+        ));
+        let result = Client::new()
+            .credentials(Credentials::basic(account, "12345"))
+            .accept_invalid_certs(true)
+            .connect("https://127.0.0.1:8899")
+            .await;
+        match expected_status {
+            None => assert!(result.is_ok(), "{account}: {result:?}"),
+            Some(status) => assert!(
+                matches!(&result, Err(jmap_client::Error::Problem(err)) if err.status() == Some(status)),
+                "{account}: {result:?}"
+            ),
+        }
+    }
+    mfa_endpoint.send(false).unwrap();
+    for mfa_account_id in mfa_account_ids {
+        params
+            .client
+            .set_default_account_id(mfa_account_id.to_string());
+        destroy_all_mailboxes(params).await;
+    }
+
+    // App passwords are limited to their scope and can be revoked
+    // without affecting the account password
+    let app_account_id = Id::from(
+        server
+            .core
+            .storage
+            .data
+            .create_test_user("app@example.com", "12345", "App User", &["app@example.com"])
+            .await,
+    );
+    server
+        .core
+        .storage
+        .data
+        .update_principal(
+            UpdatePrincipal::by_name("app@example.com").with_updates(vec![
+                PrincipalUpdate::add_item(
+                    PrincipalField::AppPasswords,
+                    PrincipalValue::String(
+                        AppPassword::build("phone", Some("imap"), "app-secret", false).unwrap(),
+                    ),
+                ),
+                PrincipalUpdate::add_item(
+                    PrincipalField::AppPasswords,
+                    PrincipalValue::String(
+                        AppPassword::build("laptop", None, "laptop-secret", false).unwrap(),
+                    ),
+                ),
+            ]),
+        )
+        .await
+        .unwrap();
+
+    // App passwords must be stored hashed
+    assert!(AppPassword::build("tablet", None, "tablet-secret", true).is_err());
+    assert!(server
+        .core
+        .storage
+        .data
+        .update_principal(
+            UpdatePrincipal::by_name("app@example.com").with_updates(vec![
+                PrincipalUpdate::add_item(
+                    PrincipalField::AppPasswords,
+                    PrincipalValue::String("$app$tablet$$tablet-secret".to_string()),
+                ),
+            ]),
+        )
+        .await
+        .is_err());
+
+    // Unscoped app passwords are not valid over HTTP
+    let mut imap = ImapConnection::connect(b"_a ").await;
+    imap.send(&format!(
+        "AUTHENTICATE PLAIN {}",
+        STANDARD.encode("\0app@example.com\0laptop-secret")
+    ))
+    .await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+    assert!(matches!(
+        Client::new()
+            .credentials(Credentials::basic("app@example.com", "laptop-secret"))
+            .accept_invalid_certs(true)
+            .connect("https://127.0.0.1:8899")
+            .await,
+        Err(jmap_client::Error::Problem(err)) if err.status() == Some(401)));
+    let app_login = format!(
+        "AUTHENTICATE PLAIN {}",
+        STANDARD.encode("\0app@example.com\0app-secret")
+    );
+    let mut imap = ImapConnection::connect(b"_a ").await;
+    imap.send(&app_login).await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+    assert!(matches!(
+        Client::new()
+            .credentials(Credentials::basic("app@example.com", "app-secret"))
+            .accept_invalid_certs(true)
+            .connect("https://127.0.0.1:8899")
+            .await,
+        Err(jmap_client::Error::Problem(err)) if err.status() == Some(401)));
+    server
+        .core
+        .storage
+        .data
+        .update_principal(
+            UpdatePrincipal::by_name("app@example.com").with_updates(vec![
+                PrincipalUpdate::remove_item(
+                    PrincipalField::AppPasswords,
+                    PrincipalValue::String("phone".to_string()),
+                ),
+            ]),
+        )
+        .await
+        .unwrap();
+    let mut imap = ImapConnection::connect(b"_b ").await;
+    imap.send(&app_login).await;
+    imap.assert_read(Type::Tagged, ResponseType::No).await;
+    Client::new()
+        .credentials(Credentials::basic("app@example.com", "12345"))
+        .accept_invalid_certs(true)
+        .connect("https://127.0.0.1:8899")
+        .await
+        .unwrap();
+    params
+        .client
+        .set_default_account_id(app_account_id.to_string());
+    destroy_all_mailboxes(params).await;
+
+    // Authentication results are cached until the principal is updated
+    let directory = server.core.storage.directories.get("cached").unwrap();
+    assert!(authenticate_cached(&server.core, directory, "jdoe@example.com", "12345").await);
+    assert!(
+        !authenticate_cached(&server.core, directory, "jdoe@example.com", "cached-secret").await
+    );
+    server
+        .core
+        .storage
+        .data
+        .update_principal(
+            UpdatePrincipal::by_name("jdoe@example.com").with_updates(vec![PrincipalUpdate::set(
+                PrincipalField::Secrets,
+                PrincipalValue::String("cached-secret".to_string()),
+            )]),
+        )
+        .await
+        .unwrap();
+    assert!(authenticate_cached(&server.core, directory, "jdoe@example.com", "12345").await);
+    assert!(
+        !authenticate_cached(&server.core, directory, "jdoe@example.com", "cached-secret").await
+    );
+    let api = ManagementApi::new(8899, "admin", "secret");
+    for (secret, old_secret) in [("cached-secret", "12345"), ("12345", "cached-secret")] {
+        api.patch::<()>(
+            "/api/principal/jdoe@example.com",
+            &vec![PrincipalUpdate::set(
+                PrincipalField::Secrets,
+                PrincipalValue::String(secret.to_string()),
+            )],
+        )
+        .await
+        .unwrap()
+        .unwrap_data();
+        assert!(authenticate_cached(&server.core, directory, "jdoe@example.com", secret).await);
+        assert!(
+            !authenticate_cached(&server.core, directory, "jdoe@example.com", old_secret).await
+        );
+    }
+
+    // Updating an account does not invalidate the credentials cached for others
+    assert!(authenticate_cached(&server.core, directory, "app@example.com", "12345").await);
+    server
+        .core
+        .storage
+        .data
+        .update_principal(
+            UpdatePrincipal::by_name("app@example.com").with_updates(vec![PrincipalUpdate::set(
+                PrincipalField::Secrets,
+                PrincipalValue::String("cached-secret".to_string()),
+            )]),
+        )
+        .await
+        .unwrap();
+    api.patch::<()>(
+        "/api/principal/jdoe@example.com",
+        &vec![PrincipalUpdate::set(
+            PrincipalField::Description,
+            PrincipalValue::String("John Doe".to_string()),
+        )],
+    )
+    .await
+    .unwrap()
+    .unwrap_data();
+    assert!(authenticate_cached(&server.core, directory, "app@example.com", "12345").await);
+    api.patch::<()>(
+        "/api/principal/app@example.com",
+        &vec![PrincipalUpdate::set(
+            PrincipalField::Secrets,
+            PrincipalValue::String("12345".to_string()),
+        )],
+    )
+    .await
+    .unwrap()
+    .unwrap_data();
+    assert!(authenticate_cached(&server.core, directory, "app@example.com", "12345").await);
+    assert!(
+        !authenticate_cached(&server.core, directory, "app@example.com", "cached-secret").await
+    );
+
+    // Each fallback administrator is identified by its own account id, which
+    // depends on the user name only and does not collide with reserved role ids
+    let directory = &server.core.storage.directory;
+    for (user, secret) in [
+        ("fallback-admin", "fallback-secret"),
+        ("oncall-admin", "oncall-secret"),
+    ] {
+        let fallback_id = server
+            .core
+            .jmap
+            .fallback_admins
+            .iter()
+            .find(|admin| admin.user == user)
+            .unwrap()
+            .id;
+        if user == "fallback-admin" {
+            assert_eq!(fallback_id, u32::MAX);
+        } else {
+            assert!(fallback_id < ROLE_USER);
+
+            // Adding administrators does not change the id of existing ones
+            let config = JmapConfig::parse(
+                &mut Config::new(
+                    r#"
+[authentication.fallback-admin.backup]
+user = "backup-admin"
+secret = "backup-secret"
+
+[authentication.fallback-admin.oncall]
+user = "oncall-admin"
+secret = "oncall-secret"
+"#,
+                )
+                .unwrap(),
+            );
+            assert_eq!(
+                config
+                    .fallback_admin(fallback_id)
+                    .map(|admin| admin.user.as_str()),
+                Some(user)
+            );
+        }
+        let principal = authenticate(&server.core, directory, user, secret)
+            .await
+            .unwrap();
+        assert_eq!(principal.id(), fallback_id);
+        assert_eq!(principal.name(), user);
+        let access_token = server.core.get_access_token(fallback_id).await.unwrap();
+        assert_eq!(access_token.name, user);
+        assert!(access_token.has_permission(Permission::Impersonate));
+    }
+    assert!(
+        authenticate(&server.core, directory, "oncall-admin", "fallback-secret")
+            .await
+            .is_none()
+    );
+
     // Destroy test accounts
     params.client.set_default_account_id(&account_id);
     destroy_all_mailboxes(params).await;
@@ -265,4 +624,130 @@ pub async fn test(params: &mut JMAPTest) {
     params
         .webhook
         .assert_contains(&["auth.failed", "auth.success", "security.authentication-ban"]);
+
+    // Normal logins should not be reported as impersonations
+    Client::new()
+        .credentials(Credentials::basic("jdoe@example.com", "12345"))
+        .accept_invalid_certs(true)
+        .connect("https://127.0.0.1:8899")
+        .await
+        .unwrap();
+    tokio::time::sleep(Duration::from_millis(1000)).await;
+    params.webhook.assert_not_contains(&["auth.impersonation"]);
+
+    // Master user logins should emit an impersonation event
+    let client = Client::new()
+        .credentials(Credentials::basic(
+            "jdoe@example.com%master",
+            "master-secret",
+        ))
+        .accept_invalid_certs(true)
+        .connect("https://127.0.0.1:8899")
+        .await
+        .unwrap();
+    assert_eq!(client.session().username(), "jdoe@example.com");
+    tokio::time::sleep(Duration::from_millis(1000)).await;
+    params.webhook.assert_contains(&["auth.impersonation"]);
+    destroy_all_mailboxes(params).await;
+}
+
+pub fn spawn_mock_mfa_endpoint() -> watch::Sender<bool> {
+    let (tx, rx) = watch::channel(true);
+
+    tokio::spawn(async move {
+        let listener = TcpListener::bind("127.0.0.1:8822")
+            .await
+            .unwrap_or_else(|e| {
+                panic!("Failed to bind mock MFA server to 127.0.0.1:8822: {e}");
+            });
+        let mut rx_ = rx.clone();
+
+        loop {
+            tokio::select! {
+                stream = listener.accept() => {
+                    match stream {
+                        Ok((stream, _)) => {
+                            let _ = http1::Builder::new()
+                            .keep_alive(false)
+                            .serve_connection(
+                                TokioIo::new(stream),
+                                service_fn(|mut req: hyper::Request<body::Incoming>| {
+                                    async move {
+                                        #[derive(serde::Deserialize)]
+                                        #[serde(rename_all = "camelCase")]
+                                        struct MfaRequest {
+                                            account_name: String,
+                                        }
+                                        let request = serde_json::from_slice::<MfaRequest>(
+                                            &fetch_body(&mut req, 1024 * 1024, 0).await.unwrap(),
+                                        )
+                                        .expect("Failed to parse JSON");
+
+                                        let action = match request.account_name.as_str() {
+                                            "mfa.allow@example.com" => "allow",
+                                            "mfa.timeout@example.com" => {
+                                                tokio::time::sleep(Duration::from_secs(3)).await;
+                                                "allow"
+                                            }
+                                            _ => "deny",
+                                        };
+
+                                        Ok::<_, hyper::Error>(
+                                            Resource::new(
+                                                "application/json",
+                                                format!("{{\"action\":\"{action}\"}}").into_bytes(),
+                                            )
+                                            .into_http_response()
+                                            .build(),
+                                        )
+                                    }
+                                }),
+                            )
+                            .await;
+                        }
+                        Err(err) => {
+                            panic!("Something went wrong: {err}" );
+                        }
+                    }
+                },
+                _ = rx_.changed() => {
+                    break;
+                }
+            };
+        }
+    });
+
+    tx
+}
+
+async fn authenticate_cached(
+    core: &Core,
+    directory: &Directory,
+    username: &str,
+    secret: &str,
+) -> bool {
+    authenticate(core, directory, username, secret)
+        .await
+        .is_some()
+}
+
+async fn authenticate(
+    core: &Core,
+    directory: &Directory,
+    username: &str,
+    secret: &str,
+) -> Option<Principal> {
+    core.authenticate(
+        directory,
+        0,
+        &mail_send::Credentials::Plain {
+            username: username.to_string(),
+            secret: secret.to_string(),
+        },
+        IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)),
+        ServerProtocol::Imap,
+        false,
+    )
+    .await
+    .ok()
 }
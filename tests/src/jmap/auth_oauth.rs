@@ -4,23 +4,48 @@
  * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
  */
 
-use std::time::{Duration, Instant};
+use std::time::{Duration, Instant, SystemTime};
 
+use base64::{
+    engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD},
+    Engine,
+};
 use bytes::Bytes;
+use common::{
+    auth::{keyring::OAuthKeyRing, oauth::crypto::SymmetricEncrypt},
+    config::jmap::settings::parse_oauth_clients,
+};
+use directory::{
+    backend::internal::{
+        manage::{ManageDirectory, UpdatePrincipal},
+        PrincipalField, PrincipalUpdate, PrincipalValue,
+    },
+    Principal, QueryBy,
+};
+use imap_proto::ResponseType;
 use jmap::auth::oauth::{
-    DeviceAuthResponse, ErrorType, OAuthCodeRequest, OAuthMetadata, TokenResponse,
+    DeviceAuthResponse, ErrorType, IdTokenClaims, IntrospectResponse, OAuthCodeRequest,
+    OAuthMetadata, OpenIdMetadata, TokenResponse,
 };
 use jmap_client::{
     client::{Client, Credentials},
     mailbox::query::Filter,
 };
 use jmap_proto::types::id::Id;
+use ring::signature::{UnparsedPublicKey, ECDSA_P256_SHA256_FIXED};
 use serde::de::DeserializeOwned;
 use store::ahash::AHashMap;
+use utils::{
+    codec::leb128::Leb128Vec,
+    config::{Config, Rate},
+};
 
 use crate::{
     directory::internal::TestInternalDirectory,
-    jmap::{assert_is_empty, mailbox::destroy_all_mailboxes, ManagementApi},
+    imap::{ImapConnection, Type},
+    jmap::{
+        assert_is_empty, delivery::SmtpConnection, mailbox::destroy_all_mailboxes, ManagementApi,
+    },
 };
 
 use super::JMAPTest;
@@ -38,20 +63,18 @@ pub async fn test(params: &mut JMAPTest) {
 
     // Create test account
     let server = params.server.clone();
-    let john_id = Id::from(
-        server
-            .core
-            .storage
-            .data
-            .create_test_user(
-                "jdoe@example.com",
-                "12345",
-                "John Doe",
-                &["jdoe@example.com"],
-            )
-            .await,
-    )
-    .to_string();
+    let john_account_id = server
+        .core
+        .storage
+        .data
+        .create_test_user(
+            "jdoe@example.com",
+            "12345",
+            "John Doe",
+            &["jdoe@example.com"],
+        )
+        .await;
+    let john_id = Id::from(john_account_id).to_string();
 
     // Build API
     let api = ManagementApi::new(8899, "jdoe@example.com", "12345");
@@ -72,6 +95,9 @@ pub async fn test(params: &mut JMAPTest) {
             &OAuthCodeRequest::Code {
                 client_id: "OAuthyMcOAuthFace".to_string(),
                 redirect_uri: "https://localhost".to_string().into(),
+                code_challenge: None,
+                code_challenge_method: None,
+                scope: None,
             },
         )
         .await
@@ -122,6 +148,877 @@ pub async fn test(params: &mut JMAPTest) {
         .ids()
         .is_empty());
 
+    // Introspect the token
+    let mut introspect_params = AHashMap::from_iter([("token".to_string(), token.clone())]);
+    let introspection = introspect(
+        &metadata.introspection_endpoint,
+        ("admin", "secret"),
+        &introspect_params,
+    )
+    .await
+    .unwrap();
+    assert!(introspection.active);
+    assert_eq!(introspection.client_id.unwrap(), "OAuthyMcOAuthFace");
+    assert_eq!(introspection.token_type.unwrap(), "access_token");
+    assert_eq!(introspection.sub.unwrap(), john_account_id.to_string());
+    assert!(introspection.exp.is_some());
+
+    // Introspection requires the appropriate permission
+    assert_eq!(
+        introspect(
+            &metadata.introspection_endpoint,
+            ("jdoe@example.com", "12345"),
+            &introspect_params,
+        )
+        .await
+        .unwrap_err(),
+        reqwest::StatusCode::FORBIDDEN
+    );
+
+    // Invalid or malformed tokens are reported as inactive
+    for invalid_token in [&token[..token.len() - 4], "not-a-token", "!@#$%^&*()", ""] {
+        introspect_params.insert("token".to_string(), invalid_token.to_string());
+        assert_eq!(
+            introspect(
+                &metadata.introspection_endpoint,
+                ("admin", "secret"),
+                &introspect_params,
+            )
+            .await
+            .unwrap(),
+            IntrospectResponse::default()
+        );
+    }
+
+    // ------------------------
+    // OpenID Connect
+    // ------------------------
+
+    // Obtain OpenID Connect metadata and the signing keys
+    let oidc_metadata: OpenIdMetadata =
+        get("https://127.0.0.1:8899/.well-known/openid-configuration").await;
+    assert_eq!(oidc_metadata.issuer, metadata.issuer);
+    assert_eq!(oidc_metadata.token_endpoint, metadata.token_endpoint);
+    assert_eq!(
+        oidc_metadata.id_token_signing_alg_values_supported,
+        vec!["ES256".to_string()]
+    );
+    let jwks: serde_json::Value = get(&oidc_metadata.jwks_uri).await;
+    let jwk = &jwks["keys"][0];
+    assert_eq!(jwk["kty"], "EC");
+    assert_eq!(jwk["crv"], "P-256");
+
+    // The token response should include a signed ID token
+    let response = api
+        .post::<OAuthCodeResponse>(
+            "/api/oauth",
+            &OAuthCodeRequest::Code {
+                client_id: "OAuthyMcOAuthFace".to_string(),
+                redirect_uri: "https://localhost".to_string().into(),
+                code_challenge: None,
+                code_challenge_method: None,
+                scope: None,
+            },
+        )
+        .await
+        .unwrap()
+        .unwrap_data();
+    let id_token = match post::<TokenResponse>(
+        &oidc_metadata.token_endpoint,
+        &AHashMap::from_iter([
+            ("client_id".to_string(), "OAuthyMcOAuthFace".to_string()),
+            ("redirect_uri".to_string(), "https://localhost".to_string()),
+            ("grant_type".to_string(), "authorization_code".to_string()),
+            ("code".to_string(), response.code),
+        ]),
+    )
+    .await
+    {
+        TokenResponse::Granted(granted) => granted.id_token.expect("Missing ID token"),
+        TokenResponse::Error { error } => panic!("Expected granted, got {:?}", error),
+    };
+    let claims = verify_id_token(&id_token, jwk).expect("Invalid ID token signature");
+    assert_eq!(claims.iss, oidc_metadata.issuer);
+    assert_eq!(claims.sub, john_account_id.to_string());
+    assert_eq!(claims.aud, "OAuthyMcOAuthFace");
+    assert_eq!(claims.email.unwrap(), "jdoe@example.com");
+    assert_eq!(claims.name.unwrap(), "John Doe");
+    assert!(claims.exp > claims.iat);
+
+    // Tampered ID tokens should fail verification
+    let (payload, signature) = id_token.rsplit_once('.').unwrap();
+    assert_eq!(
+        verify_id_token(&format!("{payload}x.{signature}"), jwk),
+        None
+    );
+
+    // ------------------------
+    // Scopes
+    // ------------------------
+
+    // Unknown scopes should be rejected
+    assert_eq!(
+        api.post::<OAuthCodeResponse>(
+            "/api/oauth",
+            &OAuthCodeRequest::Code {
+                client_id: "OAuthyMcOAuthFace".to_string(),
+                redirect_uri: "https://localhost".to_string().into(),
+                code_challenge: None,
+                code_challenge_method: None,
+                scope: "imap superpowers".to_string().into(),
+            },
+        )
+        .await
+        .unwrap()
+        .unwrap_error()
+        .1
+        .as_deref(),
+        Some("Invalid scope.")
+    );
+
+    for scope in ["imap", "smtp", "jmap openid", "admin"] {
+        let response = api
+            .post::<OAuthCodeResponse>(
+                "/api/oauth",
+                &OAuthCodeRequest::Code {
+                    client_id: "OAuthyMcOAuthFace".to_string(),
+                    redirect_uri: "https://localhost".to_string().into(),
+                    code_challenge: None,
+                    code_challenge_method: None,
+                    scope: scope.to_string().into(),
+                },
+            )
+            .await
+            .unwrap()
+            .unwrap_data();
+        let granted = match post::<TokenResponse>(
+            &metadata.token_endpoint,
+            &AHashMap::from_iter([
+                ("client_id".to_string(), "OAuthyMcOAuthFace".to_string()),
+                ("redirect_uri".to_string(), "https://localhost".to_string()),
+                ("grant_type".to_string(), "authorization_code".to_string()),
+                ("code".to_string(), response.code),
+            ]),
+        )
+        .await
+        {
+            TokenResponse::Granted(granted) => granted,
+            TokenResponse::Error { error } => panic!("Expected granted, got {:?}", error),
+        };
+        assert_eq!(granted.scope.as_deref(), Some(scope));
+
+        // ID tokens are only issued when the openid scope is granted
+        assert_eq!(granted.id_token.is_some(), scope.contains("openid"));
+        if let Some(id_token) = &granted.id_token {
+            let claims = verify_id_token(id_token, jwk).unwrap();
+            assert_eq!(claims.email, None);
+            assert_eq!(claims.name, None);
+        }
+
+        // JMAP requires the jmap scope
+        let jmap_result = Client::new()
+            .credentials(Credentials::bearer(&granted.access_token))
+            .accept_invalid_certs(true)
+            .connect("https://127.0.0.1:8899")
+            .await;
+        assert_eq!(jmap_result.is_ok(), scope.contains("jmap"), "{scope}");
+
+        // The management API requires the admin scope
+        let api_status = reqwest::Client::builder()
+            .timeout(Duration::from_millis(500))
+            .danger_accept_invalid_certs(true)
+            .build()
+            .unwrap_or_default()
+            .get("https://127.0.0.1:8899/api/account/auth")
+            .bearer_auth(&granted.access_token)
+            .send()
+            .await
+            .unwrap()
+            .status();
+        assert_eq!(
+            api_status,
+            if scope == "admin" {
+                reqwest::StatusCode::OK
+            } else {
+                reqwest::StatusCode::FORBIDDEN
+            },
+            "{scope}"
+        );
+
+        // IMAP requires the imap scope
+        let mut imap = ImapConnection::connect(b"_x ").await;
+        imap.assert_read(Type::Untagged, ResponseType::Ok).await;
+        imap.send(&format!(
+            "AUTHENTICATE OAUTHBEARER {}",
+            STANDARD.encode(format!(
+                "n,a=jdoe@example.com,\x01auth=Bearer {}\x01\x01",
+                granted.access_token
+            ))
+        ))
+        .await;
+        imap.assert_read(
+            Type::Tagged,
+            if scope == "imap" {
+                ResponseType::Ok
+            } else {
+                ResponseType::No
+            },
+        )
+        .await;
+
+        // SMTP requires the smtp scope
+        let mut smtp = SmtpConnection::connect().await;
+        smtp.send(&format!(
+            "AUTH OAUTHBEARER {}",
+            STANDARD.encode(format!(
+                "n,a=jdoe@example.com,\x01auth=Bearer {}\x01\x01",
+                granted.access_token
+            ))
+        ))
+        .await;
+        smtp.read(1, if scope == "smtp" { 2 } else { 5 }).await;
+        let mut smtp = SmtpConnection::connect().await;
+        smtp.send(&format!(
+            "AUTH XOAUTH2 {}",
+            STANDARD.encode(format!(
+                "user=jdoe@example.com\x01auth=Bearer {}\x01\x01",
+                granted.access_token
+            ))
+        ))
+        .await;
+        smtp.read(1, if scope == "smtp" { 2 } else { 5 }).await;
+
+        // The authorization identity has to match the token's account
+        if scope == "smtp" {
+            let mut smtp = SmtpConnection::connect().await;
+            smtp.send(&format!(
+                "AUTH OAUTHBEARER {}",
+                STANDARD.encode(format!(
+                    "n,a=jane@example.com,\x01auth=Bearer {}\x01\x01",
+                    granted.access_token
+                ))
+            ))
+            .await;
+            smtp.read(1, 5).await;
+        }
+    }
+
+    // ------------------------
+    // Authorization code flow with PKCE
+    // ------------------------
+
+    let code_verifier = "dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk";
+    for (code_challenge_method, code_challenge) in [
+        ("plain", code_verifier),
+        ("S256", "E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM"),
+    ] {
+        let response = api
+            .post::<OAuthCodeResponse>(
+                "/api/oauth",
+                &OAuthCodeRequest::Code {
+                    client_id: "OAuthyMcOAuthFace".to_string(),
+                    redirect_uri: "https://localhost".to_string().into(),
+                    code_challenge: code_challenge.to_string().into(),
+                    code_challenge_method: code_challenge_method.to_string().into(),
+                    scope: None,
+                },
+            )
+            .await
+            .unwrap()
+            .unwrap_data();
+
+        // A missing or invalid code verifier should be rejected
+        let mut token_params = AHashMap::from_iter([
+            ("client_id".to_string(), "OAuthyMcOAuthFace".to_string()),
+            ("redirect_uri".to_string(), "https://localhost".to_string()),
+            ("grant_type".to_string(), "authorization_code".to_string()),
+            ("code".to_string(), response.code),
+        ]);
+        assert_eq!(
+            post::<TokenResponse>(&metadata.token_endpoint, &token_params).await,
+            TokenResponse::Error {
+                error: ErrorType::InvalidGrant
+            }
+        );
+        token_params.insert(
+            "code_verifier".to_string(),
+            "this-is-not-the-code-verifier-that-was-used-for-the-challenge".to_string(),
+        );
+        assert_eq!(
+            post::<TokenResponse>(&metadata.token_endpoint, &token_params).await,
+            TokenResponse::Error {
+                error: ErrorType::InvalidGrant
+            }
+        );
+
+        // Obtain token using the correct code verifier
+        token_params.insert("code_verifier".to_string(), code_verifier.to_string());
+        unwrap_token_response(post(&metadata.token_endpoint, &token_params).await);
+    }
+
+    // A code verifier is rejected when no challenge was provided
+    let response = api
+        .post::<OAuthCodeResponse>(
+            "/api/oauth",
+            &OAuthCodeRequest::Code {
+                client_id: "OAuthyMcOAuthFace".to_string(),
+                redirect_uri: "https://localhost".to_string().into(),
+                code_challenge: None,
+                code_challenge_method: None,
+                scope: None,
+            },
+        )
+        .await
+        .unwrap()
+        .unwrap_data();
+    assert_eq!(
+        post::<TokenResponse>(
+            &metadata.token_endpoint,
+            &AHashMap::from_iter([
+                ("client_id".to_string(), "OAuthyMcOAuthFace".to_string()),
+                ("redirect_uri".to_string(), "https://localhost".to_string()),
+                ("grant_type".to_string(), "authorization_code".to_string()),
+                ("code".to_string(), response.code),
+                ("code_verifier".to_string(), code_verifier.to_string()),
+            ]),
+        )
+        .await,
+        TokenResponse::Error {
+            error: ErrorType::InvalidGrant
+        }
+    );
+
+    // ------------------------
+    // Token revocation
+    // ------------------------
+
+    let issue_tokens = || async {
+        // Access tokens expire after one second in this test, wait until the
+        // start of the next second so they remain valid for the checks below
+        let millis = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap()
+            .subsec_millis();
+        tokio::time::sleep(Duration::from_millis(1000 - millis as u64)).await;
+
+        let response = api
+            .post::<OAuthCodeResponse>(
+                "/api/oauth",
+                &OAuthCodeRequest::Code {
+                    client_id: "OAuthyMcOAuthFace".to_string(),
+                    redirect_uri: "https://localhost".to_string().into(),
+                    code_challenge: None,
+                    code_challenge_method: None,
+                    scope: None,
+                },
+            )
+            .await
+            .unwrap()
+            .unwrap_data();
+        let (token, refresh_token, _) = unwrap_token_response(
+            post(
+                &metadata.token_endpoint,
+                &AHashMap::from_iter([
+                    ("client_id".to_string(), "OAuthyMcOAuthFace".to_string()),
+                    ("redirect_uri".to_string(), "https://localhost".to_string()),
+                    ("grant_type".to_string(), "authorization_code".to_string()),
+                    ("code".to_string(), response.code),
+                ]),
+            )
+            .await,
+        );
+        (token, refresh_token.unwrap())
+    };
+
+    // Requests without a token should be rejected
+    assert_eq!(
+        revoke(&metadata.revocation_endpoint, &AHashMap::new()).await,
+        reqwest::StatusCode::BAD_REQUEST
+    );
+
+    // Revoking an access token should only invalidate that token
+    let (token, refresh_token) = issue_tokens().await;
+    Client::new()
+        .credentials(Credentials::bearer(&token))
+        .accept_invalid_certs(true)
+        .connect("https://127.0.0.1:8899")
+        .await
+        .unwrap();
+    let revoke_params = |client_id: &str| {
+        AHashMap::from_iter([
+            ("client_id".to_string(), client_id.to_string()),
+            ("token".to_string(), token.clone()),
+        ])
+    };
+
+    // Clients must identify themselves and may only revoke their own tokens
+    assert_eq!(
+        revoke(
+            &metadata.revocation_endpoint,
+            &AHashMap::from_iter([("token".to_string(), token.clone())]),
+        )
+        .await,
+        reqwest::StatusCode::UNAUTHORIZED
+    );
+    assert_eq!(
+        revoke(&metadata.revocation_endpoint, &revoke_params("OtherClient")).await,
+        reqwest::StatusCode::BAD_REQUEST
+    );
+    Client::new()
+        .credentials(Credentials::bearer(&token))
+        .accept_invalid_certs(true)
+        .connect("https://127.0.0.1:8899")
+        .await
+        .unwrap();
+
+    for _ in 0..2 {
+        // Revocation is idempotent
+        assert_eq!(
+            revoke(
+                &metadata.revocation_endpoint,
+                &revoke_params("OAuthyMcOAuthFace")
+            )
+            .await,
+            reqwest::StatusCode::OK
+        );
+    }
+    assert_unauthorized("https://127.0.0.1:8899", &token).await;
+    let (new_token, _, _) = unwrap_token_response(
+        post(
+            &metadata.token_endpoint,
+            &AHashMap::from_iter([
+                ("client_id".to_string(), "OAuthyMcOAuthFace".to_string()),
+                ("grant_type".to_string(), "refresh_token".to_string()),
+                ("refresh_token".to_string(), refresh_token.clone()),
+            ]),
+        )
+        .await,
+    );
+    Client::new()
+        .credentials(Credentials::bearer(&new_token))
+        .accept_invalid_certs(true)
+        .connect("https://127.0.0.1:8899")
+        .await
+        .unwrap();
+
+    // Revoking a refresh token should also invalidate the access tokens issued with it,
+    // while tokens issued under other grants are not affected
+    let (other_token, _) = issue_tokens().await;
+    Client::new()
+        .credentials(Credentials::bearer(&other_token))
+        .accept_invalid_certs(true)
+        .connect("https://127.0.0.1:8899")
+        .await
+        .unwrap();
+    assert_eq!(
+        revoke(
+            &metadata.revocation_endpoint,
+            &AHashMap::from_iter([
+                ("client_id".to_string(), "OAuthyMcOAuthFace".to_string()),
+                ("token".to_string(), refresh_token.clone()),
+                ("token_type_hint".to_string(), "refresh_token".to_string()),
+            ]),
+        )
+        .await,
+        reqwest::StatusCode::OK
+    );
+    assert_unauthorized("https://127.0.0.1:8899", &new_token).await;
+    Client::new()
+        .credentials(Credentials::bearer(&other_token))
+        .accept_invalid_certs(true)
+        .connect("https://127.0.0.1:8899")
+        .await
+        .unwrap();
+    assert_eq!(
+        post::<TokenResponse>(
+            &metadata.token_endpoint,
+            &AHashMap::from_iter([
+                ("client_id".to_string(), "OAuthyMcOAuthFace".to_string()),
+                ("grant_type".to_string(), "refresh_token".to_string()),
+                ("refresh_token".to_string(), refresh_token),
+            ]),
+        )
+        .await,
+        TokenResponse::Error {
+            error: ErrorType::InvalidGrant
+        }
+    );
+
+    // Tokens issued under other grants should not be affected
+    let (token, _) = issue_tokens().await;
+    Client::new()
+        .credentials(Credentials::bearer(&token))
+        .accept_invalid_certs(true)
+        .connect("https://127.0.0.1:8899")
+        .await
+        .unwrap();
+
+    // Reusing a redeemed refresh token should revoke all the tokens issued under its grant
+    let refresh_params = |refresh_token: String| {
+        AHashMap::from_iter([
+            ("client_id".to_string(), "OAuthyMcOAuthFace".to_string()),
+            ("grant_type".to_string(), "refresh_token".to_string()),
+            ("refresh_token".to_string(), refresh_token),
+        ])
+    };
+    let (_, refresh_token) = issue_tokens().await;
+    let (token, rotated_refresh_token, _) = unwrap_token_response(
+        post::<TokenResponse>(
+            &metadata.token_endpoint,
+            &refresh_params(refresh_token.clone()),
+        )
+        .await,
+    );
+    assert_eq!(
+        post::<TokenResponse>(&metadata.token_endpoint, &refresh_params(refresh_token)).await,
+        TokenResponse::Error {
+            error: ErrorType::InvalidGrant
+        }
+    );
+    assert_unauthorized("https://127.0.0.1:8899", &token).await;
+    assert_eq!(
+        post::<TokenResponse>(
+            &metadata.token_endpoint,
+            &refresh_params(rotated_refresh_token.unwrap())
+        )
+        .await,
+        TokenResponse::Error {
+            error: ErrorType::InvalidGrant
+        }
+    );
+
+    // Unknown tokens are ignored
+    assert_eq!(
+        revoke(
+            &metadata.revocation_endpoint,
+            &AHashMap::from_iter([
+                ("client_id".to_string(), "OAuthyMcOAuthFace".to_string()),
+                ("token".to_string(), "invalid-token".to_string()),
+            ]),
+        )
+        .await,
+        reqwest::StatusCode::OK
+    );
+
+    // ------------------------
+    // Key rotation
+    // ------------------------
+
+    let set_key_ring = |config: &str| {
+        let mut core = server.shared_core.load_full().as_ref().clone();
+        core.jmap.oauth_keys = OAuthKeyRing::parse(&mut Config::new(config).unwrap());
+        server.shared_core.store(core.into());
+    };
+    let is_active = |token: &str| {
+        let url = metadata.introspection_endpoint.clone();
+        let params = AHashMap::from_iter([
+            ("token".to_string(), token.to_string()),
+            ("token_type_hint".to_string(), "refresh_token".to_string()),
+        ]);
+        async move {
+            introspect(&url, ("admin", "secret"), &params)
+                .await
+                .unwrap()
+                .active
+        }
+    };
+    let (_, legacy_refresh_token) = issue_tokens().await;
+
+    // Tokens issued with a retired key remain valid after rotating to a new key
+    set_key_ring(
+        r#"[oauth]
+key = "parerga_und_paralipomena"
+key-ring-active = "2024q4"
+
+[oauth.key-ring.2024q4]
+secret = "die_welt_als_wille_und_vorstellung"
+"#,
+    );
+    let (_, refresh_token) = issue_tokens().await;
+    assert!(is_active(&legacy_refresh_token).await);
+    assert!(is_active(&refresh_token).await);
+
+    // Tokens are tagged with the key they were issued with
+    set_key_ring(
+        r#"[oauth]
+key = "parerga_und_paralipomena"
+"#,
+    );
+    assert!(is_active(&legacy_refresh_token).await);
+    assert!(!is_active(&refresh_token).await);
+
+    // Removing a key from the ring invalidates the tokens issued with it
+    set_key_ring(
+        r#"[oauth]
+key = "the_world_as_will_and_representation"
+key-ring-active = "2024q4"
+
+[oauth.key-ring.2024q4]
+secret = "die_welt_als_wille_und_vorstellung"
+"#,
+    );
+    assert!(!is_active(&legacy_refresh_token).await);
+    assert!(is_active(&refresh_token).await);
+
+    // Restore the original key ring
+    set_key_ring(
+        r#"[oauth]
+key = "parerga_und_paralipomena"
+"#,
+    );
+
+    // Tokens issued before the token format was versioned remain valid
+    let password_hash = server
+        .core
+        .storage
+        .directory
+        .query(QueryBy::Id(john_account_id), false)
+        .await
+        .unwrap()
+        .unwrap()
+        .take_str_array(PrincipalField::Secrets)
+        .unwrap()
+        .remove(0);
+    let expiry = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+        - 946684800
+        + 60;
+    let mut hasher = store::blake3::Hasher::new();
+    hasher.update(format!("access_token nonce {password_hash}").as_bytes());
+    hasher.update(expiry.to_be_bytes().as_slice());
+    let mut legacy_token = SymmetricEncrypt::new(
+        b"parerga_und_paralipomena",
+        &format!("access_token web {john_account_id} {password_hash}"),
+    )
+    .encrypt(
+        &[0u8; 32],
+        &hasher.finalize().as_bytes()[..SymmetricEncrypt::NONCE_LEN],
+    )
+    .unwrap();
+    legacy_token.push_leb128(john_account_id);
+    legacy_token.push_leb128(expiry);
+    legacy_token.extend_from_slice(b"web");
+    let token_info = server
+        .core
+        .validate_access_token("access_token", None, &STANDARD.encode(&legacy_token))
+        .await
+        .unwrap();
+    assert_eq!(token_info.account_id, john_account_id);
+    assert_eq!(token_info.client_id, "web");
+
+    // ------------------------
+    // Tenant realms
+    // ------------------------
+
+    let store = server.core.storage.data.clone();
+    for tenant in ["oauth-tenant-a", "oauth-tenant-b"] {
+        store
+            .create_principal(
+                Principal::new(0, directory::Type::Tenant)
+                    .with_field(PrincipalField::Name, tenant.to_string()),
+                None,
+            )
+            .await
+            .unwrap();
+    }
+    let set_tenant = |tenant: &'static str| {
+        let store = store.clone();
+        let server = server.clone();
+        async move {
+            store
+                .update_principal(
+                    UpdatePrincipal::by_id(john_account_id)
+                        .with_updates(vec![PrincipalUpdate::set(
+                            PrincipalField::Tenant,
+                            PrincipalValue::String(tenant.to_string()),
+                        )])
+                        .no_validate(),
+                )
+                .await
+                .unwrap();
+            server
+                .core
+                .security
+                .invalidate_access_token(john_account_id);
+            server
+                .core
+                .invalidate_auth_cache(john_account_id, &[])
+                .await;
+        }
+    };
+    let issue_token = || async {
+        server
+            .issue_custom_token(john_account_id, "access_token", "web", None, 60)
+            .await
+            .unwrap()
+    };
+    let is_valid = |token: String| {
+        let server = server.clone();
+        async move {
+            server
+                .core
+                .validate_access_token("access_token", None, &token)
+                .await
+                .is_ok()
+        }
+    };
+
+    // Tokens issued without a realm belong to the default tenant
+    let default_token = issue_token().await;
+    assert!(is_valid(default_token.clone()).await);
+    set_tenant("oauth-tenant-a").await;
+    assert!(!is_valid(default_token.clone()).await);
+
+    // Tokens minted for tenant A are rejected when replayed against tenant B,
+    // even after they were validated and cached
+    let tenant_a_token = issue_token().await;
+    assert!(is_valid(tenant_a_token.clone()).await);
+    set_tenant("oauth-tenant-b").await;
+    assert!(!is_valid(tenant_a_token.clone()).await);
+    assert!(is_valid(issue_token().await).await);
+
+    // Moving the account back to the default tenant restores the original tokens
+    set_tenant("").await;
+    assert!(is_valid(default_token).await);
+    assert!(!is_valid(tenant_a_token).await);
+    for tenant in ["oauth-tenant-a", "oauth-tenant-b"] {
+        store.delete_principal(QueryBy::Name(tenant)).await.unwrap();
+    }
+
+    // Single purpose tokens are only accepted for the audience they were issued for
+    let unsubscribe_token = server
+        .issue_audience_token(john_account_id, "unsubscribe", 60)
+        .await
+        .unwrap();
+    let token_info = server
+        .validate_audience_token("unsubscribe", &unsubscribe_token)
+        .await
+        .unwrap();
+    assert_eq!(token_info.account_id, john_account_id);
+    assert!(token_info.scopes.is_empty());
+    assert!(server
+        .validate_audience_token("unsubscribe-all", &unsubscribe_token)
+        .await
+        .is_err());
+    assert!(!is_valid(unsubscribe_token).await);
+    assert!(server
+        .issue_audience_token(john_account_id, "", 60)
+        .await
+        .is_err());
+
+    // Signing out everywhere revokes the tokens issued until then
+    let token = issue_token().await;
+    assert!(is_valid(token.clone()).await);
+    ManagementApi::new(8899, "admin", "secret")
+        .delete::<()>("/api/sessions/jdoe@example.com")
+        .await
+        .unwrap()
+        .unwrap_data();
+    assert!(!is_valid(token).await);
+    assert!(is_valid(issue_token().await).await);
+
+    // ------------------------
+    // Client registry
+    // ------------------------
+
+    let set_clients = |config: &str| {
+        let mut core = server.shared_core.load_full().as_ref().clone();
+        core.jmap.oauth_clients = parse_oauth_clients(&mut Config::new(config).unwrap());
+        server.shared_core.store(core.into());
+    };
+    let code_request = |client_id: &str, redirect_uri: &str| OAuthCodeRequest::Code {
+        client_id: client_id.to_string(),
+        redirect_uri: redirect_uri.to_string().into(),
+        code_challenge: None,
+        code_challenge_method: None,
+        scope: None,
+    };
+    set_clients(
+        r#"[oauth.client.registered]
+redirect-uris = ["https://registered.example/callback"]
+grant-types = ["authorization_code"]
+expiry.token = "5m"
+
+[oauth.client.native]
+redirect-uris = ["https://native.example/callback"]
+require-pkce = true
+"#,
+    );
+
+    // PKCE can be required per client
+    api.post::<OAuthCodeResponse>(
+        "/api/oauth",
+        &code_request("native", "https://native.example/callback"),
+    )
+    .await
+    .unwrap()
+    .expect_error("PKCE code challenge is required");
+    api.post::<OAuthCodeResponse>(
+        "/api/oauth",
+        &OAuthCodeRequest::Code {
+            client_id: "native".to_string(),
+            redirect_uri: "https://native.example/callback".to_string().into(),
+            code_challenge: code_verifier.to_string().into(),
+            code_challenge_method: "plain".to_string().into(),
+            scope: None,
+        },
+    )
+    .await
+    .unwrap()
+    .unwrap_data();
+
+    // Unknown clients and unregistered redirect URIs are rejected
+    for (client_id, redirect_uri) in [
+        ("OAuthyMcOAuthFace", "https://localhost"),
+        ("registered", "https://registered.example/callback/other"),
+        ("registered", "https://registered.example"),
+    ] {
+        api.post::<OAuthCodeResponse>("/api/oauth", &code_request(client_id, redirect_uri))
+            .await
+            .unwrap()
+            .expect_error("not registered");
+    }
+    let response = post::<serde_json::Value>(
+        &metadata.device_authorization_endpoint,
+        &AHashMap::from_iter([("client_id".to_string(), "registered".to_string())]),
+    )
+    .await;
+    assert!(
+        response.get("device_code").is_none(),
+        "Unexpected response: {response:?}"
+    );
+
+    // Registered clients obtain tokens with their own lifetime and no refresh token
+    let response = api
+        .post::<OAuthCodeResponse>(
+            "/api/oauth",
+            &code_request("registered", "https://registered.example/callback"),
+        )
+        .await
+        .unwrap()
+        .unwrap_data();
+    let (token, refresh_token, expires_in) = unwrap_token_response(
+        post(
+            &metadata.token_endpoint,
+            &AHashMap::from_iter([
+                ("client_id".to_string(), "registered".to_string()),
+                (
+                    "redirect_uri".to_string(),
+                    "https://registered.example/callback".to_string(),
+                ),
+                ("grant_type".to_string(), "authorization_code".to_string()),
+                ("code".to_string(), response.code),
+            ]),
+        )
+        .await,
+    );
+    assert_eq!(expires_in, 300);
+    assert_eq!(refresh_token, None);
+    assert!(!token.is_empty());
+    set_clients("");
+
     // ------------------------
     // Device code flow
     // ------------------------
@@ -240,43 +1137,87 @@ pub async fn test(params: &mut JMAPTest) {
         }
     );
 
-    // Refreshing the access token before expiration should not include a new refresh token
+    // Refreshing the access token should rotate the refresh token
     let refresh_params = AHashMap::from_iter([
         ("client_id".to_string(), "1234".to_string()),
         ("grant_type".to_string(), "refresh_token".to_string()),
-        ("refresh_token".to_string(), refresh_token),
+        ("refresh_token".to_string(), refresh_token.clone()),
     ]);
     let time_before_post: Instant = Instant::now();
     let (token, new_refresh_token, _) =
         unwrap_token_response(post(&metadata.token_endpoint, &refresh_params).await);
-    assert_eq!(
-        new_refresh_token,
-        None,
-        "Refreshed token in {:?}, since start {:?}",
-        time_before_post.elapsed(),
-        time_first_token.elapsed()
-    );
+    let new_refresh_token = new_refresh_token.expect("Refresh token was not rotated");
+    assert_ne!(new_refresh_token, refresh_token);
 
     // Wait 1 second and make sure the access token expired
     tokio::time::sleep(Duration::from_secs(1)).await;
     assert_unauthorized("https://127.0.0.1:8899", &token).await;
 
-    // Wait another second for the refresh token to be about to expire
-    // and expect a new refresh token
-    tokio::time::sleep(Duration::from_secs(1)).await;
-    let (_, new_refresh_token, _) =
-        unwrap_token_response(post(&metadata.token_endpoint, &refresh_params).await);
-    //println!("New refresh token: {:?}", new_refresh_token);
-    assert_ne!(new_refresh_token, None);
-
-    // Wait another second and make sure the refresh token expired
-    tokio::time::sleep(Duration::from_secs(1)).await;
+    // Wait two more seconds and make sure the rotated refresh token
+    // expired at the same time as the original one
+    tokio::time::sleep(Duration::from_secs(2)).await;
     assert_eq!(
-        post::<TokenResponse>(&metadata.token_endpoint, &refresh_params).await,
+        post::<TokenResponse>(
+            &metadata.token_endpoint,
+            &AHashMap::from_iter([
+                ("client_id".to_string(), "1234".to_string()),
+                ("grant_type".to_string(), "refresh_token".to_string()),
+                ("refresh_token".to_string(), new_refresh_token),
+            ]),
+        )
+        .await,
         TokenResponse::Error {
             error: ErrorType::InvalidGrant
-        }
+        },
+        "Refreshed token in {:?}, since start {:?}",
+        time_before_post.elapsed(),
+        time_first_token.elapsed()
+    );
+
+    // Token endpoints can be rate limited separately from other anonymous requests
+    let mut core = server.shared_core.load_full().as_ref().clone();
+    core.jmap.rate_oauth = Some(Rate {
+        requests: 1,
+        period: Duration::from_secs(60),
+    });
+    server.shared_core.store(core.into());
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_millis(500))
+        .danger_accept_invalid_certs(true)
+        .build()
+        .unwrap();
+    let mut responses = Vec::new();
+    for _ in 0..2 {
+        responses.push(
+            client
+                .post(&metadata.token_endpoint)
+                .form(&AHashMap::from_iter([
+                    ("client_id".to_string(), "1234".to_string()),
+                    ("grant_type".to_string(), "refresh_token".to_string()),
+                    ("refresh_token".to_string(), "invalid".to_string()),
+                ]))
+                .send()
+                .await
+                .unwrap(),
+        );
+    }
+    assert_ne!(
+        responses[0].status(),
+        reqwest::StatusCode::TOO_MANY_REQUESTS
+    );
+    assert_eq!(
+        responses[1].status(),
+        reqwest::StatusCode::TOO_MANY_REQUESTS
     );
+    assert!(responses[1]
+        .headers()
+        .get(reqwest::header::RETRY_AFTER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<u64>().ok())
+        .is_some_and(|retry_after| retry_after <= 60));
+    let mut core = server.shared_core.load_full().as_ref().clone();
+    core.jmap.rate_oauth = None;
+    server.shared_core.store(core.into());
 
     // Destroy test accounts
     server
@@ -311,6 +1252,44 @@ async fn post<T: DeserializeOwned>(url: &str, params: &AHashMap<String, String>)
     serde_json::from_slice(&post_bytes(url, params).await).unwrap()
 }
 
+async fn introspect(
+    url: &str,
+    (username, secret): (&str, &str),
+    params: &AHashMap<String, String>,
+) -> Result<IntrospectResponse, reqwest::StatusCode> {
+    let response = reqwest::Client::builder()
+        .timeout(Duration::from_millis(500))
+        .danger_accept_invalid_certs(true)
+        .build()
+        .unwrap_or_default()
+        .post(url)
+        .basic_auth(username, Some(secret))
+        .form(params)
+        .send()
+        .await
+        .unwrap();
+
+    if response.status().is_success() {
+        Ok(serde_json::from_slice(&response.bytes().await.unwrap()).unwrap())
+    } else {
+        Err(response.status())
+    }
+}
+
+async fn revoke(url: &str, params: &AHashMap<String, String>) -> reqwest::StatusCode {
+    reqwest::Client::builder()
+        .timeout(Duration::from_millis(500))
+        .danger_accept_invalid_certs(true)
+        .build()
+        .unwrap_or_default()
+        .post(url)
+        .form(params)
+        .send()
+        .await
+        .unwrap()
+        .status()
+}
+
 async fn get_bytes(url: &str) -> Bytes {
     reqwest::Client::builder()
         .timeout(Duration::from_millis(500))
@@ -345,6 +1324,31 @@ async fn assert_unauthorized(base_url: &str, token: &str) {
     }
 }
 
+fn verify_id_token(id_token: &str, jwk: &serde_json::Value) -> Option<IdTokenClaims> {
+    let (message, signature) = id_token.rsplit_once('.').unwrap();
+    let mut public_key = vec![0x04];
+    for coordinate in ["x", "y"] {
+        public_key.extend(
+            URL_SAFE_NO_PAD
+                .decode(jwk[coordinate].as_str().unwrap())
+                .unwrap(),
+        );
+    }
+    UnparsedPublicKey::new(&ECDSA_P256_SHA256_FIXED, public_key)
+        .verify(
+            message.as_bytes(),
+            &URL_SAFE_NO_PAD.decode(signature).unwrap(),
+        )
+        .ok()?;
+
+    let (header, claims) = message.split_once('.').unwrap();
+    let header: serde_json::Value =
+        serde_json::from_slice(&URL_SAFE_NO_PAD.decode(header).unwrap()).unwrap();
+    assert_eq!(header["alg"], "ES256");
+    assert_eq!(header["kid"], jwk["kid"]);
+    serde_json::from_slice(&URL_SAFE_NO_PAD.decode(claims).unwrap()).ok()
+}
+
 fn unwrap_token_response(response: TokenResponse) -> (String, Option<String>, u64) {
     match response {
         TokenResponse::Granted(granted) => {
@@ -0,0 +1,139 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use common::{auth::bandwidth::BandwidthUsage, DeliveryResult, IngestMessage, RecipientResult};
+use jmap_proto::types::id::Id;
+use serde::Deserialize;
+use store::write::now;
+use utils::BlobHash;
+
+use crate::{
+    directory::internal::TestInternalDirectory,
+    jmap::{assert_is_empty, mailbox::destroy_all_mailboxes, ManagementApi},
+};
+
+use super::JMAPTest;
+
+#[derive(Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+struct Usage {
+    bytes_in: u64,
+    bytes_out: u64,
+}
+
+pub async fn test(params: &mut JMAPTest) {
+    println!("Running bandwidth accounting tests...");
+    let server = params.server.clone();
+    let account_id = server
+        .core
+        .storage
+        .data
+        .create_test_user(
+            "bill@example.com",
+            "secret",
+            "Bill Foobar",
+            &["bill@example.com"],
+        )
+        .await;
+
+    // Delivered messages are accounted as incoming transfer
+    let message = b"From: jane@example.com\r\nTo: bill@example.com\r\nSubject: Bandwidth\r\n\r\nTest message.\r\n".to_vec();
+    let message_blob = BlobHash::from(message.as_slice());
+    server
+        .core
+        .storage
+        .blob
+        .put_blob(message_blob.as_ref(), &message)
+        .await
+        .unwrap();
+    for _ in 0..2 {
+        assert_eq!(
+            server
+                .deliver_message(IngestMessage {
+                    sender_address: "jane@example.com".to_string(),
+                    recipients: vec!["bill@example.com".to_string()],
+                    message_blob: message_blob.clone(),
+                    message_size: message.len(),
+                    session_id: 0,
+                })
+                .await,
+            vec![RecipientResult {
+                recipient: "bill@example.com".to_string(),
+                result: DeliveryResult::Success,
+            }]
+        );
+    }
+    let expected = BandwidthUsage {
+        bytes_in: 2 * message.len() as u64,
+        bytes_out: 0,
+    };
+
+    // Usage is reported before and after being flushed to the store
+    assert_eq!(
+        server
+            .core
+            .get_bandwidth_usage(account_id, 0, now())
+            .await
+            .unwrap(),
+        expected
+    );
+    server.core.flush_bandwidth().await;
+    assert_eq!(
+        server
+            .core
+            .get_bandwidth_usage(account_id, 0, now())
+            .await
+            .unwrap(),
+        expected
+    );
+
+    // Windows ending before the transfer report no usage
+    assert_eq!(
+        server
+            .core
+            .get_bandwidth_usage(account_id, 0, now() - 2 * 86400)
+            .await
+            .unwrap(),
+        BandwidthUsage::default()
+    );
+
+    // Query and reset usage using the management API
+    let api = ManagementApi::new(8899, "admin", "secret");
+    assert_eq!(
+        api.get::<Usage>("/api/bandwidth/bill@example.com")
+            .await
+            .unwrap()
+            .unwrap_data(),
+        Usage {
+            bytes_in: expected.bytes_in,
+            bytes_out: 0,
+        }
+    );
+    api.delete::<()>("/api/bandwidth/bill@example.com")
+        .await
+        .unwrap()
+        .unwrap_data();
+    assert_eq!(
+        api.get::<Usage>("/api/bandwidth/bill@example.com")
+            .await
+            .unwrap()
+            .unwrap_data(),
+        Usage {
+            bytes_in: 0,
+            bytes_out: 0,
+        }
+    );
+    api.get::<Usage>("/api/bandwidth/unknown@example.com")
+        .await
+        .unwrap()
+        .expect_error("notFound");
+
+    params
+        .client
+        .set_default_account_id(Id::from(account_id).to_string());
+    destroy_all_mailboxes(params).await;
+    assert_is_empty(server).await;
+}
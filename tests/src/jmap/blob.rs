@@ -5,6 +5,7 @@
  */
 
 use jmap::mailbox::INBOX_ID;
+use jmap_client::client::{Client, Credentials};
 use jmap_proto::types::id::Id;
 use serde_json::Value;
 
@@ -479,6 +480,117 @@ pub async fn test(params: &mut JMAPTest) {
         );
     }
 
+    // Disallowed types should be rejected
+    let response = jmap_json_request(
+        r#"[[
+            "Blob/upload",
+            {
+             "accountId": "$$",
+             "create": {
+              "abc": {
+               "data" : [
+               {
+                "data:asText": "MZ"
+               }
+              ],
+              "type": "application/x-msdownload"
+              }
+             }
+            },
+            "R1"
+           ]]"#
+        .replace("$$", &account_id.to_string()),
+        "jdoe@example.com",
+        "12345",
+    )
+    .await;
+    assert_eq!(
+        response
+            .pointer("/methodResponses/0/1/notCreated/abc/type")
+            .and_then(|v| v.as_str())
+            .unwrap_or_default(),
+        "forbidden",
+        "Response: {:?}",
+        response
+    );
+    let client = Client::new()
+        .credentials(Credentials::basic("jdoe@example.com", "12345"))
+        .accept_invalid_certs(true)
+        .connect("https://127.0.0.1:8899")
+        .await
+        .unwrap();
+    assert!(client
+        .upload(
+            None,
+            b"#!/bin/sh".to_vec(),
+            Some("application/x-sh; charset=utf-8")
+        )
+        .await
+        .is_err());
+
+    // Per-account size limits should be enforced
+    let small_account_id = Id::from(
+        server
+            .core
+            .storage
+            .data
+            .create_test_user(
+                "small.account@example.com",
+                "12345",
+                "Small Account",
+                &["small.account@example.com"],
+            )
+            .await,
+    );
+    let response = jmap_json_request(
+        r#"[[
+            "Blob/upload",
+            {
+             "accountId": "$$",
+             "create": {
+              "abc": {
+               "data" : [
+               {
+                "data:asText": "%%"
+               }
+              ]
+              }
+             }
+            },
+            "R1"
+           ]]"#
+        .replace("$$", &small_account_id.to_string())
+        .replace("%%", &"a".repeat(101)),
+        "small.account@example.com",
+        "12345",
+    )
+    .await;
+    assert_eq!(
+        response
+            .pointer("/methodResponses/0/1/notCreated/abc/type")
+            .and_then(|v| v.as_str())
+            .unwrap_or_default(),
+        "tooLarge",
+        "Response: {:?}",
+        response
+    );
+    let client = Client::new()
+        .credentials(Credentials::basic("small.account@example.com", "12345"))
+        .accept_invalid_certs(true)
+        .connect("https://127.0.0.1:8899")
+        .await
+        .unwrap();
+    assert!(client.upload(None, vec![b'A'; 101], None).await.is_err());
+    assert_eq!(
+        client
+            .upload(None, vec![b'A'; 100], None)
+            .await
+            .unwrap()
+            .size(),
+        100
+    );
+    server.core.storage.data.blob_expire_all().await;
+
     // Remove test data
     params.client.set_default_account_id(account_id.to_string());
     destroy_all_mailboxes(params).await;
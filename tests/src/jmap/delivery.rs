@@ -4,15 +4,32 @@
  * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
  */
 
-use std::time::Duration;
+use std::{sync::Arc, time::Duration};
 
-use jmap::mailbox::{INBOX_ID, JUNK_ID};
+use common::{
+    addresses::RecipientExpansion, config::jmap::settings::SpamScanner,
+    manager::webadmin::Resource, DeliveryEvent, DeliveryResult, IngestMessage,
+};
+use directory::{backend::internal::PrincipalInfo, Type};
+use hyper::{body, server::conn::http1, service::service_fn};
+use hyper_util::rt::TokioIo;
+use jmap::{
+    api::http::{fetch_body, ToHttpResponse},
+    mailbox::{INBOX_ID, JUNK_ID},
+    JMAP,
+};
 use jmap_proto::types::{collection::Collection, id::Id, property::Property};
 
+use store::{
+    write::{BatchBuilder, DirectoryClass, MaybeDynamicId, ValueClass},
+    Serialize,
+};
 use tokio::{
     io::{AsyncBufReadExt, AsyncWriteExt, BufReader, Lines, ReadHalf, WriteHalf},
-    net::TcpStream,
+    net::{TcpListener, TcpStream},
+    sync::{oneshot, watch},
 };
+use utils::{config::Config, BlobHash};
 
 use crate::{
     directory::internal::TestInternalDirectory,
@@ -21,6 +38,47 @@ use crate::{
 
 use super::JMAPTest;
 
+pub async fn test_drain(params: &JMAPTest) {
+    println!("Running delivery drain tests...");
+    let ipc = &params.server.smtp.inner.ipc;
+
+    // Queued deliveries complete before the drain is acknowledged
+    assert!(ipc.drain_delivery(Duration::from_secs(1)).await);
+
+    // New deliveries are rejected with a temporary failure
+    let deliver = || async {
+        let (result_tx, result_rx) = oneshot::channel();
+        ipc.delivery_tx
+            .send(DeliveryEvent::Ingest {
+                message: IngestMessage {
+                    sender_address: "bill@example.com".to_string(),
+                    recipients: vec!["jdoe@example.com".to_string()],
+                    message_blob: BlobHash::default(),
+                    message_size: 0,
+                    session_id: 0,
+                },
+                result_tx,
+            })
+            .await
+            .unwrap();
+        let mut results = result_rx.await.unwrap();
+        assert_eq!(results.len(), 1);
+        results.pop().unwrap().result
+    };
+    assert!(matches!(
+        deliver().await,
+        DeliveryResult::TemporaryFailure { reason } if reason == "Server is shutting down"
+    ));
+
+    // Normal operation resumes once the timeout elapses
+    tokio::time::sleep(Duration::from_millis(1500)).await;
+    assert!(!ipc.delivery_tx.is_closed());
+    assert!(!matches!(
+        deliver().await,
+        DeliveryResult::TemporaryFailure { reason } if reason == "Server is shutting down"
+    ));
+}
+
 pub async fn test(params: &mut JMAPTest) {
     println!("Running message delivery tests...");
 
@@ -292,8 +350,310 @@ pub async fn test(params: &mut JMAPTest) {
         );
     }
 
+    // Aliases pointing to a missing account are skipped
+    let mut batch = BatchBuilder::new();
+    batch.set(
+        ValueClass::Directory(DirectoryClass::EmailToId(b"ghost@example.com".to_vec())),
+        PrincipalInfo::new(u32::MAX - 1, Type::Individual, None).serialize(),
+    );
+    server.core.storage.data.write(batch.build()).await.unwrap();
+    let message = "From: bill@example.com\r\nSubject: Anyone there?\r\n\r\nTPS reports.";
+    let message_blob = BlobHash::from(message.as_bytes());
+    server
+        .core
+        .storage
+        .blob
+        .put_blob(message_blob.as_ref(), message.as_bytes())
+        .await
+        .unwrap();
+    let results = server
+        .deliver_message(IngestMessage {
+            sender_address: "bill@example.com".to_string(),
+            recipients: vec![
+                "ghost@example.com".to_string(),
+                "non_existant@example.com".to_string(),
+                "jane@example.com".to_string(),
+            ],
+            message_blob,
+            message_size: message.len(),
+            session_id: 0,
+        })
+        .await;
+    assert_eq!(
+        results
+            .iter()
+            .map(|r| (r.recipient.as_str(), &r.result))
+            .collect::<Vec<_>>(),
+        vec![
+            (
+                "ghost@example.com",
+                &DeliveryResult::PermanentFailure {
+                    code: [5, 1, 2],
+                    reason: "Mailbox does not exist.".into()
+                }
+            ),
+            (
+                "non_existant@example.com",
+                &DeliveryResult::PermanentFailure {
+                    code: [5, 1, 2],
+                    reason: "Mailbox does not exist.".into()
+                }
+            ),
+            ("jane@example.com", &DeliveryResult::Success),
+        ]
+    );
+    let mut batch = BatchBuilder::new();
+    batch.clear(ValueClass::Directory(DirectoryClass::EmailToId(
+        b"ghost@example.com".to_vec(),
+    )));
+    server.core.storage.data.write(batch.build()).await.unwrap();
+
+    // Nested lists are expanded, missing members and loops are skipped
+    let jane_id = Id::from_bytes(account_id_2.as_bytes())
+        .unwrap()
+        .document_id();
+    let bill_id = Id::from_bytes(account_id_3.as_bytes())
+        .unwrap()
+        .document_id();
+    let inner_id = server
+        .core
+        .storage
+        .data
+        .create_test_list("inner@example.com", "Inner List", &["jane@example.com"])
+        .await;
+    let outer_id = server
+        .core
+        .storage
+        .data
+        .create_test_list(
+            "outer@example.com",
+            "Outer List",
+            &["inner@example.com", "bill@example.com"],
+        )
+        .await;
+    let mut batch = BatchBuilder::new();
+    for (principal_id, member_id) in [(inner_id, outer_id), (inner_id, u32::MAX - 1)] {
+        batch.set(
+            ValueClass::Directory(DirectoryClass::Members {
+                principal_id: MaybeDynamicId::Static(principal_id),
+                has_member: MaybeDynamicId::Static(member_id),
+            }),
+            vec![],
+        );
+    }
+    server.core.storage.data.write(batch.build()).await.unwrap();
+    let mut expansion = server
+        .core
+        .expand_recipient(&server.core.storage.directory, "outer@example.com", 0)
+        .await
+        .unwrap();
+    if let RecipientExpansion::Accounts(accounts) = &mut expansion {
+        accounts.sort_unstable();
+    }
+    let mut expected = vec![jane_id, bill_id];
+    expected.sort_unstable();
+    assert_eq!(expansion, RecipientExpansion::Accounts(expected));
+
+    // Lists nested beyond the maximum depth fail
+    let mut list_name = "inner@example.com".to_string();
+    for depth in 0..server.core.jmap.delivery_max_expansion_depth {
+        let name = format!("depth{depth}@example.com");
+        server
+            .core
+            .storage
+            .data
+            .create_test_list(&name, "Nested List", &[list_name.as_str()])
+            .await;
+        list_name = name;
+    }
+    assert_eq!(
+        server
+            .core
+            .expand_recipient(&server.core.storage.directory, &list_name, 0)
+            .await
+            .unwrap(),
+        RecipientExpansion::TooDeep
+    );
+
+    // Recipients over the limit are deferred
+    let mut core = server.core.as_ref().clone();
+    core.jmap.delivery_max_recipients = 1;
+    let limited_server = JMAP {
+        core: Arc::new(core),
+        ..server.as_ref().clone()
+    };
+    let message = "From: bill@example.com\r\nSubject: Limits\r\n\r\nOne at a time.";
+    let message_blob = BlobHash::from(message.as_bytes());
+    server
+        .core
+        .storage
+        .blob
+        .put_blob(message_blob.as_ref(), message.as_bytes())
+        .await
+        .unwrap();
+    let results = limited_server
+        .deliver_message(IngestMessage {
+            sender_address: "bill@example.com".to_string(),
+            recipients: vec![
+                "jane@example.com".to_string(),
+                "non_existant@example.com".to_string(),
+            ],
+            message_blob,
+            message_size: message.len(),
+            session_id: 0,
+        })
+        .await;
+    assert_eq!(
+        results
+            .iter()
+            .map(|r| (r.recipient.as_str(), &r.result))
+            .collect::<Vec<_>>(),
+        vec![
+            ("jane@example.com", &DeliveryResult::Success),
+            (
+                "non_existant@example.com",
+                &DeliveryResult::TemporaryFailure {
+                    reason: "Too many recipients.".into()
+                }
+            ),
+        ]
+    );
+
+    // Messages for mirrored domains are queued once to the mirror address
+    let mut account_ids = Vec::new();
+    for email in [
+        "kate@legacy.example.com",
+        "archive@example.com",
+        "lee@legacy.example.com",
+    ] {
+        account_ids.push(
+            server
+                .core
+                .storage
+                .data
+                .create_test_user(email, "secret", email, &[email])
+                .await,
+        );
+    }
+    let message = "From: bill@example.com\r\nSubject: Cutover\r\n\r\nMirror me.";
+    let message_blob = BlobHash::from(message.as_bytes());
+    server
+        .core
+        .storage
+        .blob
+        .put_blob(message_blob.as_ref(), message.as_bytes())
+        .await
+        .unwrap();
+    let results = server
+        .deliver_message(IngestMessage {
+            sender_address: "bill@example.com".to_string(),
+            recipients: vec![
+                "kate@legacy.example.com".to_string(),
+                "jane@example.com".to_string(),
+                "lee@legacy.example.com".to_string(),
+            ],
+            message_blob,
+            message_size: message.len(),
+            session_id: 0,
+        })
+        .await;
+    assert!(results
+        .iter()
+        .all(|r| matches!(r.result, DeliveryResult::Success)));
+    let mut num_mirrored = 0;
+    for _ in 0..30 {
+        num_mirrored = server
+            .get_document_ids(account_ids[1], Collection::Email)
+            .await
+            .unwrap()
+            .map_or(0, |ids| ids.len());
+        if num_mirrored > 0 {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert_eq!(num_mirrored, 1);
+    tokio::time::sleep(Duration::from_millis(500)).await;
+    assert_eq!(
+        server
+            .get_document_ids(account_ids[1], Collection::Email)
+            .await
+            .unwrap()
+            .map_or(0, |ids| ids.len()),
+        1
+    );
+    let account_ids = account_ids
+        .into_iter()
+        .map(|id| Id::from(id).to_string())
+        .collect::<Vec<_>>();
+
+    // Spam scanner verdicts are applied to all recipients
+    let scanner = spawn_mock_spam_scanner();
+    let mut core = server.core.as_ref().clone();
+    core.jmap.spam_scanner = SpamScanner::parse(
+        &mut Config::new("spam.external.url = \"http://127.0.0.1:11333/checkv2\"\n").unwrap(),
+    );
+    let scanner_server = JMAP {
+        core: Arc::new(core),
+        ..server.as_ref().clone()
+    };
+    let num_junk = server
+        .get_tag(jane_id, Collection::Email, Property::MailboxIds, JUNK_ID)
+        .await
+        .unwrap()
+        .map_or(0, |bm| bm.len());
+    for (subject, expected_result) in [
+        (
+            "reject",
+            DeliveryResult::PermanentFailure {
+                code: [5, 7, 1],
+                reason: "Spam message rejected".into(),
+            },
+        ),
+        (
+            "soft reject",
+            DeliveryResult::TemporaryFailure {
+                reason: "Try again later".into(),
+            },
+        ),
+        ("add header", DeliveryResult::Success),
+    ] {
+        let message = format!("From: bill@example.com\r\nSubject: {subject}\r\n\r\nScan me.");
+        let message_blob = BlobHash::from(message.as_bytes());
+        server
+            .core
+            .storage
+            .blob
+            .put_blob(message_blob.as_ref(), message.as_bytes())
+            .await
+            .unwrap();
+        let results = scanner_server
+            .deliver_message(IngestMessage {
+                sender_address: "bill@example.com".to_string(),
+                recipients: vec!["jane@example.com".to_string()],
+                message_blob,
+                message_size: message.len(),
+                session_id: 0,
+            })
+            .await;
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].result, expected_result, "{subject}");
+    }
+    assert_eq!(
+        server
+            .get_tag(jane_id, Collection::Email, Property::MailboxIds, JUNK_ID)
+            .await
+            .unwrap()
+            .map_or(0, |bm| bm.len()),
+        num_junk + 1
+    );
+    scanner.send(false).unwrap();
+
     // Remove test data
-    for account_id in [&account_id_1, &account_id_2, &account_id_3] {
+    for account_id in [&account_id_1, &account_id_2, &account_id_3]
+        .into_iter()
+        .chain(&account_ids)
+    {
         params.client.set_default_account_id(account_id);
         destroy_all_mailboxes(params).await;
     }
@@ -308,6 +668,72 @@ pub async fn test(params: &mut JMAPTest) {
     ]);
 }
 
+pub fn spawn_mock_spam_scanner() -> watch::Sender<bool> {
+    let (tx, rx) = watch::channel(true);
+
+    tokio::spawn(async move {
+        let listener = TcpListener::bind("127.0.0.1:11333")
+            .await
+            .unwrap_or_else(|e| {
+                panic!("Failed to bind mock spam scanner to 127.0.0.1:11333: {e}");
+            });
+        let mut rx_ = rx.clone();
+
+        loop {
+            tokio::select! {
+                stream = listener.accept() => {
+                    match stream {
+                        Ok((stream, _)) => {
+                            let _ = http1::Builder::new()
+                            .keep_alive(false)
+                            .serve_connection(
+                                TokioIo::new(stream),
+                                service_fn(|mut req: hyper::Request<body::Incoming>| {
+                                    async move {
+                                        // Expect the raw message along with the envelope
+                                        let is_valid = req.uri().path() == "/checkv2"
+                                            && req.headers().get("From").map_or(false, |v| v == "bill@example.com")
+                                            && req.headers().get_all("Rcpt").iter().count() == 1;
+                                        let message = fetch_body(&mut req, 1024 * 1024, 0).await.unwrap();
+                                        let message = std::str::from_utf8(&message).unwrap();
+
+                                        let response = if !is_valid || !message.starts_with("From: ") {
+                                            r#"{"action":"reject","messages":{"smtp_message":"Invalid scan request"}}"#
+                                        } else if message.contains("Subject: reject") {
+                                            r#"{"action":"reject","score":20.0,"required_score":15.0,"messages":{"smtp_message":"Spam message rejected"}}"#
+                                        } else if message.contains("Subject: soft reject") {
+                                            r#"{"action":"soft reject","score":12.0,"required_score":15.0,"messages":{"smtp_message":"Try again later"}}"#
+                                        } else if message.contains("Subject: add header") {
+                                            r#"{"action":"add header","score":8.0,"required_score":15.0,"milter":{"add_headers":{"X-Spamd-Result":{"value":"default: False [8.00 / 15.00]","order":0}}}}"#
+                                        } else {
+                                            r#"{"action":"no action","score":0.0,"required_score":15.0}"#
+                                        };
+
+                                        Ok::<_, hyper::Error>(
+                                            Resource::new("application/json", response.as_bytes().to_vec())
+                                                .into_http_response()
+                                                .build(),
+                                        )
+                                    }
+                                }),
+                            )
+                            .await;
+                        }
+                        Err(err) => {
+                            panic!("Something went wrong: {err}" );
+                        }
+                    }
+                },
+                _ = rx_.changed() => {
+                    break;
+                }
+            };
+        }
+    });
+
+    tx
+}
+
 pub struct SmtpConnection {
     reader: Lines<BufReader<ReadHalf<TcpStream>>>,
     writer: WriteHalf<TcpStream>,
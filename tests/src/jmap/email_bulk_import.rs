@@ -0,0 +1,220 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use std::{fs, time::Duration};
+
+use jmap::email::bulk_import::{ImportFormat, ImportProgress, ImportRequest, ImportState};
+use jmap_proto::types::{collection::Collection, id::Id, keyword::Keyword, property::Property};
+
+use crate::jmap::{assert_is_empty, mailbox::destroy_all_mailboxes};
+
+use super::JMAPTest;
+
+pub async fn test(params: &mut JMAPTest) {
+    println!("Running Email Bulk Import tests...");
+    let server = params.server.clone();
+    let account_id = 1u32;
+    let base_path = params.temp_dir.path.join("bulk_import");
+    let _ = fs::remove_dir_all(&base_path);
+
+    // Build an mbox file, the second message is not a valid e-mail
+    let mbox_path = base_path.join("import.mbox");
+    fs::create_dir_all(&base_path).unwrap();
+    fs::write(
+        &mbox_path,
+        concat!(
+            "From john@example.com Sat Jan  3 01:05:34 1996\n",
+            "Message-ID: <mbox-1@example.com>\n",
+            "Subject: First\n",
+            "Status: RO\n",
+            "X-Status: A\n",
+            "\n",
+            "first message\n",
+            "\n",
+            "From jane@example.com Sat Jan  3 01:05:34 1996\n",
+            "\n",
+            "not an e-mail message\n",
+            "\n",
+            "From bill@example.com Sun Jan  4 10:00:00 1996\n",
+            "Message-ID: <mbox-2@example.com>\n",
+            "Subject: Second\n",
+            "\n",
+            "second message\n",
+        ),
+    )
+    .unwrap();
+
+    // Build a Maildir++ folder tree
+    let maildir_path = base_path.join("maildir");
+    for folder in ["", ".Archive.2020"] {
+        for dir in ["cur", "new", "tmp"] {
+            fs::create_dir_all(maildir_path.join(folder).join(dir)).unwrap();
+        }
+    }
+    fs::write(
+        maildir_path.join("cur").join("1000.abc.host:2,FS"),
+        "Message-ID: <maildir-1@example.com>\r\nSubject: Inbox\r\n\r\ninbox message\r\n",
+    )
+    .unwrap();
+    fs::write(
+        maildir_path
+            .join(".Archive.2020")
+            .join("cur")
+            .join("1001.abc.host:2,S"),
+        "Message-ID: <maildir-2@example.com>\r\nSubject: Archive\r\n\r\narchived message\r\n",
+    )
+    .unwrap();
+
+    // Import the mbox file, bad messages are reported without aborting
+    let progress = ImportProgress::default();
+    server
+        .import_messages(
+            account_id,
+            ImportRequest {
+                format: ImportFormat::Mbox,
+                path: mbox_path.clone(),
+                mailbox: Some("Imported".to_string()),
+            },
+            &progress,
+            0,
+        )
+        .await
+        .unwrap();
+    let result = progress.into_inner();
+    assert_eq!(result.state, ImportState::Completed, "{result:?}");
+    assert_eq!(result.messages, 3, "{result:?}");
+    assert_eq!(result.imported, 2, "{result:?}");
+    assert_eq!(result.failed, 1, "{result:?}");
+    assert_eq!(result.errors.len(), 1, "{result:?}");
+    let imported_id = server
+        .mailbox_get_by_name(account_id, "Imported")
+        .await
+        .unwrap()
+        .unwrap();
+    let imported_ids = server
+        .get_tag(
+            account_id,
+            Collection::Email,
+            Property::MailboxIds,
+            imported_id,
+        )
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(imported_ids.len(), 2);
+    for keyword in [Keyword::Seen, Keyword::Answered] {
+        assert_eq!(
+            server
+                .get_tag(account_id, Collection::Email, Property::Keywords, keyword)
+                .await
+                .unwrap()
+                .unwrap_or_default()
+                .len(),
+            1
+        );
+    }
+
+    // Importing again skips the messages already imported
+    let progress = ImportProgress::default();
+    server
+        .import_messages(
+            account_id,
+            ImportRequest {
+                format: ImportFormat::Mbox,
+                path: mbox_path,
+                mailbox: Some("Imported".to_string()),
+            },
+            &progress,
+            0,
+        )
+        .await
+        .unwrap();
+    let result = progress.into_inner();
+    assert_eq!(result.imported, 0, "{result:?}");
+    assert_eq!(result.skipped, 2, "{result:?}");
+
+    // Import the Maildir in the background, folders are mapped to mailboxes
+    server
+        .start_import(
+            account_id,
+            ImportRequest {
+                format: ImportFormat::Maildir,
+                path: maildir_path,
+                mailbox: None,
+            },
+            0,
+        )
+        .unwrap();
+    let mut result = server.import_status(account_id).unwrap();
+    for _ in 0..100 {
+        if result.state != ImportState::Running {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        result = server.import_status(account_id).unwrap();
+    }
+    assert_eq!(result.state, ImportState::Completed, "{result:?}");
+    assert_eq!(result.imported, 2, "{result:?}");
+    assert_eq!(result.failed, 0, "{result:?}");
+    let archive_id = server
+        .mailbox_get_by_name(account_id, "Archive/2020")
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        server
+            .get_tag(
+                account_id,
+                Collection::Email,
+                Property::MailboxIds,
+                archive_id,
+            )
+            .await
+            .unwrap()
+            .unwrap()
+            .len(),
+        1
+    );
+    assert_eq!(
+        server
+            .get_tag(
+                account_id,
+                Collection::Email,
+                Property::Keywords,
+                Keyword::Flagged,
+            )
+            .await
+            .unwrap()
+            .unwrap_or_default()
+            .len(),
+        1
+    );
+
+    // Missing sources and paths outside the import directory are rejected
+    for path in [base_path.join("missing.mbox"), base_path.join("../..")] {
+        assert!(server
+            .import_messages(
+                account_id,
+                ImportRequest {
+                    format: ImportFormat::Maildir,
+                    path,
+                    mailbox: None,
+                },
+                &ImportProgress::default(),
+                0,
+            )
+            .await
+            .is_err());
+    }
+
+    // Empty store
+    fs::remove_dir_all(&base_path).unwrap();
+    params
+        .client
+        .set_default_account_id(Id::new(account_id as u64).to_string());
+    destroy_all_mailboxes(params).await;
+    assert_is_empty(server).await;
+}
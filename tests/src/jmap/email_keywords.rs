@@ -0,0 +1,209 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use std::sync::Arc;
+
+use common::config::jmap::settings::KeywordPolicy;
+use jmap::{
+    email::keywords::{KeywordError, KeywordRegistry},
+    mailbox::INBOX_ID,
+    JMAP,
+};
+use jmap_client::mailbox::Role;
+use jmap_proto::types::{collection::Collection, id::Id, keyword::Keyword, property::Property};
+use store::write::{BatchBuilder, Bincode, F_CLEAR, F_VALUE};
+use utils::config::Config;
+
+use crate::jmap::{assert_is_empty, mailbox::destroy_all_mailboxes};
+
+use super::JMAPTest;
+
+const POLICY: &str = r#"
+[jmap.email.keywords]
+max-custom = 3
+
+[jmap.email.keywords.rule.restricted]
+mailbox = "Restricted"
+allow = ["$junk", "work"]
+
+[jmap.email.keywords.rule.all]
+mailbox = "*"
+deny = ["$phishing"]
+"#;
+
+pub async fn test(params: &mut JMAPTest) {
+    println!("Running Email keyword policy tests...");
+    let account_id = 1u32;
+
+    // Create a mailbox holding a message with a custom keyword
+    let mailbox_id = params
+        .client
+        .set_default_account_id(Id::new(account_id as u64).to_string())
+        .mailbox_create("Restricted", None::<String>, Role::None)
+        .await
+        .unwrap()
+        .take_id();
+    params
+        .client
+        .email_import(
+            b"From: bill@example.com\r\nSubject: Keywords\r\n\r\nTest message".to_vec(),
+            [&mailbox_id],
+            Some(["existing"]),
+            None,
+        )
+        .await
+        .unwrap();
+
+    // Enable the keyword policy
+    let server = with_policy(params, POLICY);
+    let restricted_id = server
+        .mailbox_get_by_name(account_id, "Restricted")
+        .await
+        .unwrap()
+        .unwrap();
+
+    // System flags and allowed keywords are accepted
+    let mut keywords = vec![Keyword::Seen, Keyword::Other("work".to_string())];
+    let mut registry = KeywordRegistry::default();
+    assert_eq!(
+        server
+            .email_check_keywords(account_id, &[restricted_id], &mut keywords, &mut registry)
+            .await
+            .unwrap(),
+        None
+    );
+    assert_eq!(keywords.len(), 2);
+
+    // New custom keywords are only registered once the message is stored
+    assert_eq!(get_registry(&server, account_id).await, None);
+    server
+        .email_register_keywords(account_id, &mut registry)
+        .await
+        .unwrap();
+    assert_eq!(
+        get_registry(&server, account_id).await,
+        Some(vec!["existing".to_string(), "work".to_string()])
+    );
+
+    // Keywords outside the allow list are rejected in the restricted mailbox only
+    for (mailbox_id, expected) in [
+        (
+            restricted_id,
+            Some(KeywordError::NotAllowed(Keyword::Other(
+                "personal".to_string(),
+            ))),
+        ),
+        (INBOX_ID, None),
+    ] {
+        assert_eq!(
+            server
+                .email_check_keywords(
+                    account_id,
+                    &[mailbox_id],
+                    &mut vec![Keyword::Other("personal".to_string())],
+                    &mut KeywordRegistry::default()
+                )
+                .await
+                .unwrap(),
+            expected
+        );
+    }
+
+    // Denied keywords are rejected in all mailboxes
+    assert_eq!(
+        server
+            .email_check_keywords(
+                account_id,
+                &[INBOX_ID],
+                &mut vec![Keyword::Phishing],
+                &mut KeywordRegistry::default()
+            )
+            .await
+            .unwrap(),
+        Some(KeywordError::NotAllowed(Keyword::Phishing))
+    );
+
+    // Only new keywords count towards the custom keyword limit
+    assert_eq!(
+        server
+            .email_check_keywords(
+                account_id,
+                &[INBOX_ID],
+                &mut ["existing", "a", "b", "c"]
+                    .into_iter()
+                    .map(|k| Keyword::Other(k.to_string()))
+                    .collect(),
+                &mut KeywordRegistry::default()
+            )
+            .await
+            .unwrap(),
+        Some(KeywordError::TooManyKeywords(3))
+    );
+
+    // Disallowed keywords are removed when the action is drop
+    let server = with_policy(
+        params,
+        &POLICY.replace("max-custom = 3", "max-custom = 3\naction = \"drop\""),
+    );
+    let mut keywords = vec![
+        Keyword::Other("personal".to_string()),
+        Keyword::Seen,
+        Keyword::Other("work".to_string()),
+    ];
+    assert_eq!(
+        server
+            .email_check_keywords(
+                account_id,
+                &[restricted_id],
+                &mut keywords,
+                &mut KeywordRegistry::default()
+            )
+            .await
+            .unwrap(),
+        None
+    );
+    assert_eq!(
+        keywords,
+        vec![Keyword::Seen, Keyword::Other("work".to_string())]
+    );
+
+    // Remove the custom keyword registry
+    let mut batch = BatchBuilder::new();
+    batch
+        .with_account_id(account_id)
+        .with_collection(Collection::Principal)
+        .update_document(0)
+        .value(Property::Keywords, (), F_VALUE | F_CLEAR);
+    server.write_batch(batch).await.unwrap();
+
+    // Empty store
+    destroy_all_mailboxes(params).await;
+    assert_is_empty(params.server.clone()).await;
+}
+
+fn with_policy(params: &JMAPTest, policy: &str) -> JMAP {
+    let mut core = params.server.core.as_ref().clone();
+    core.jmap.keyword_policy = KeywordPolicy::parse(&mut Config::new(policy).unwrap());
+    assert!(core.jmap.keyword_policy.is_some());
+
+    JMAP {
+        core: Arc::new(core),
+        ..params.server.as_ref().clone()
+    }
+}
+
+async fn get_registry(server: &JMAP, account_id: u32) -> Option<Vec<String>> {
+    server
+        .get_property::<Bincode<Vec<String>>>(
+            account_id,
+            Collection::Principal,
+            0,
+            Property::Keywords,
+        )
+        .await
+        .unwrap()
+        .map(|registry| registry.inner)
+}
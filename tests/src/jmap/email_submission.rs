@@ -85,7 +85,11 @@ pub async fn test(params: &mut JMAPTest) {
                 "jdoe@example.com",
                 "12345",
                 "John Doe",
-                &["jdoe@example.com", "john.doe@example.com"],
+                &[
+                    "jdoe@example.com",
+                    "john.doe@example.com",
+                    "@jdoe.example.org",
+                ],
             )
             .await,
     )
@@ -102,6 +106,13 @@ pub async fn test(params: &mut JMAPTest) {
         assert_eq!(identity.name().unwrap(), format!("John Doe <{email}>"));
     }
 
+    // Catch-all addresses should not be offered as identities
+    assert!(client
+        .identity_get(&Id::from(2u64).to_string(), None)
+        .await
+        .unwrap()
+        .is_none());
+
     // Create an identity without using a valid address should fail
     match client
         .set_default_account_id(&account_id)
@@ -120,6 +131,13 @@ pub async fn test(params: &mut JMAPTest) {
         .unwrap()
         .take_id();
 
+    // Addresses in a catch-all domain can be used as identities
+    let catch_all_identity_id = client
+        .identity_create("John Doe", "anything@jdoe.example.org")
+        .await
+        .unwrap()
+        .take_id();
+
     // Create test mailboxes
     let mailbox_id = client
         .mailbox_create("JMAP EmailSubmission", None::<String>, Role::None)
@@ -192,6 +210,45 @@ pub async fn test(params: &mut JMAPTest) {
         }))
     ));
 
+    // Submissions using an identity whose alias was removed should fail
+    server
+        .core
+        .storage
+        .data
+        .remove_test_alias("jdoe@example.com", "john.doe@example.com")
+        .await;
+    assert!(matches!(
+        client
+            .email_submission_create(&email_id, Id::from(1u64).to_string())
+            .await,
+        Err(Error::Set(SetError {
+            type_: SetErrorType::ForbiddenFrom,
+            ..
+        }))
+    ));
+
+    // Submissions with a From header not belonging to the account should fail
+    let foreign_email_id = client
+        .email_import(
+            b"From: jane_smith@remote.org\r\nTo: tim@foobar.com\r\nSubject: hey\r\n\r\ntest"
+                .to_vec(),
+            [&mailbox_id],
+            None::<Vec<&str>>,
+            None,
+        )
+        .await
+        .unwrap()
+        .take_id();
+    assert!(matches!(
+        client
+            .email_submission_create(&foreign_email_id, &identity_id)
+            .await,
+        Err(Error::Set(SetError {
+            type_: SetErrorType::ForbiddenFrom,
+            ..
+        }))
+    ));
+
     // Submit a valid message submission
     let email_body =
         "From: jdoe@example.com\r\nTo: jane_smith@remote.org\r\nSubject: hey\r\n\r\ntest";
@@ -471,6 +528,7 @@ pub async fn test(params: &mut JMAPTest) {
     // Destroy the created mailbox, identity and all submissions
     for identity_id in [
         identity_id,
+        catch_all_identity_id,
         Id::from(0u64).to_string(),
         Id::from(1u64).to_string(),
     ] {
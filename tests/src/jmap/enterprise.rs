@@ -289,6 +289,47 @@ async fn tracing(params: &mut JMAPTest) {
         assert!(spans[0] > spans[1], "keyword: {keyword}");
     }
 
+    // The timeline of the inbound session should include the delivery attempt
+    let session_span = store
+        .query_spans(
+            &[TracingQuery::EventType(EventType::Smtp(
+                SmtpEvent::ConnectionStart,
+            ))],
+            0,
+            0,
+        )
+        .await
+        .unwrap()[0];
+    let timeline = store.get_timeline(session_span).await.unwrap();
+    assert_eq!(timeline.correlation_id, session_span);
+    assert_eq!(
+        timeline.events[0].inner.typ,
+        EventType::Smtp(SmtpEvent::ConnectionStart)
+    );
+    assert!(timeline
+        .events
+        .iter()
+        .any(|event| event.inner.typ == EventType::Delivery(DeliveryEvent::AttemptStart)));
+    assert!(timeline
+        .events
+        .windows(2)
+        .all(|events| events[0].inner.timestamp <= events[1].inner.timestamp));
+
+    // Starting from the delivery attempt yields the same correlation id
+    let delivery_span = store
+        .query_spans(
+            &[TracingQuery::EventType(EventType::Delivery(
+                DeliveryEvent::AttemptStart,
+            ))],
+            0,
+            0,
+        )
+        .await
+        .unwrap()[0];
+    let delivery_timeline = store.get_timeline(delivery_span).await.unwrap();
+    assert_eq!(delivery_timeline.correlation_id, session_span);
+    assert_eq!(delivery_timeline.events.len(), timeline.events.len());
+
     // Purge should delete the span entries
     tokio::time::sleep(Duration::from_millis(800)).await;
     store.purge_spans(Duration::from_secs(1)).await.unwrap();
@@ -15,7 +15,7 @@ use crate::{
 };
 use futures::StreamExt;
 use jmap::mailbox::INBOX_ID;
-use jmap_client::{event_source::Changes, mailbox::Role, TypeState};
+use jmap_client::{client::Client, event_source::Changes, mailbox::Role, TypeState};
 use jmap_proto::types::id::Id;
 use store::ahash::AHashSet;
 
@@ -122,15 +122,56 @@ pub async fn test(params: &mut JMAPTest) {
         .mailbox_destroy(&Id::from(INBOX_ID).to_string(), true)
         .await
         .unwrap();
-    assert_state(
+    let last_event_id = assert_state(
         &mut event_rx,
         &account_id,
         &[TypeState::Email, TypeState::Thread, TypeState::Mailbox],
     )
-    .await;
+    .await
+    .expect("Missing event id");
     assert_ping(&mut event_rx).await;
     assert_ping(&mut event_rx).await;
 
+    // Clients reconnecting with the last event id receive the missed changes
+    let mailbox_id = client
+        .mailbox_create("EventSource Resume", None::<String>, Role::None)
+        .await
+        .unwrap()
+        .take_id();
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    let changes = next_event(&client, &last_event_id).await;
+    assert_eq!(
+        changes
+            .changes(&account_id)
+            .unwrap()
+            .map(|x| x.0)
+            .collect::<AHashSet<&TypeState>>(),
+        [TypeState::Mailbox]
+            .iter()
+            .collect::<AHashSet<&TypeState>>()
+    );
+    assert_ne!(changes.id(), Some(last_event_id.as_str()));
+
+    // Unknown or evicted event ids result in the current state being sent
+    let changes = next_event(&client, "1").await;
+    let types = changes
+        .changes(&account_id)
+        .unwrap()
+        .map(|x| x.0)
+        .collect::<AHashSet<&TypeState>>();
+    for type_state in [
+        TypeState::Email,
+        TypeState::EmailDelivery,
+        TypeState::Mailbox,
+        TypeState::Thread,
+    ] {
+        assert!(
+            types.contains(&type_state),
+            "{type_state:?} not in {types:?}"
+        );
+    }
+    client.mailbox_destroy(&mailbox_id, true).await.unwrap();
+
     destroy_all_mailboxes(params).await;
     assert_is_empty(server).await;
 }
@@ -139,7 +180,7 @@ async fn assert_state(
     event_rx: &mut mpsc::Receiver<Changes>,
     account_id: &str,
     state: &[TypeState],
-) {
+) -> Option<String> {
     match tokio::time::timeout(Duration::from_millis(700), event_rx.recv()).await {
         Ok(Some(changes)) => {
             assert_eq!(
@@ -150,6 +191,7 @@ async fn assert_state(
                     .collect::<AHashSet<&TypeState>>(),
                 state.iter().collect::<AHashSet<&TypeState>>()
             );
+            changes.id().map(|id| id.to_string())
         }
         result => {
             panic!("Timeout waiting for event {:?}: {:?}", state, result);
@@ -157,6 +199,19 @@ async fn assert_state(
     }
 }
 
+async fn next_event(client: &Client, last_event_id: &str) -> Changes {
+    let mut changes = client
+        .event_source(None::<Vec<_>>, true, None, Some(last_event_id))
+        .await
+        .unwrap();
+    match tokio::time::timeout(Duration::from_millis(700), changes.next()).await {
+        Ok(Some(Ok(changes))) => changes,
+        result => {
+            panic!("Timeout waiting for event: {:?}", result);
+        }
+    }
+}
+
 async fn assert_ping(event_rx: &mut mpsc::Receiver<Changes>) {
     match tokio::time::timeout(Duration::from_millis(1100), event_rx.recv()).await {
         Ok(Some(changes)) => {
@@ -0,0 +1,97 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use std::time::Duration;
+
+use common::manager::health::ComponentStatus;
+use reqwest::StatusCode;
+
+use super::{JMAPTest, ManagementApi};
+
+pub async fn test(params: &JMAPTest) {
+    println!("Running health check tests...");
+
+    // All components should be reachable
+    let health = params.server.core.health_check().await;
+    assert!(health.healthy, "{health:?}");
+    assert_eq!(
+        health
+            .components
+            .iter()
+            .map(|component| (component.name, component.status))
+            .collect::<Vec<_>>(),
+        vec![
+            ("data", ComponentStatus::Up),
+            ("lookup", ComponentStatus::Up),
+            ("directory", ComponentStatus::Up)
+        ]
+    );
+
+    // Test the HTTP probes
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(10))
+        .danger_accept_invalid_certs(true)
+        .build()
+        .unwrap();
+    for path in ["healthz", "healthz/live"] {
+        assert_eq!(
+            client
+                .get(format!("https://127.0.0.1:8899/{path}"))
+                .send()
+                .await
+                .unwrap()
+                .status(),
+            StatusCode::OK
+        );
+    }
+    for path in ["readyz", "healthz/ready"] {
+        let response = client
+            .get(format!("https://127.0.0.1:8899/{path}"))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body =
+            serde_json::from_str::<serde_json::Value>(&response.text().await.unwrap()).unwrap();
+        assert_eq!(body["healthy"], true);
+        assert_eq!(body["nodeId"], health.node_id);
+        assert_eq!(body["components"].as_array().unwrap().len(), 3);
+        assert!(body["components"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .all(|component| component.get("reason").is_none()));
+    }
+
+    // Test directory connectivity and principal lookups
+    let api = ManagementApi::new(8899, "admin", "secret");
+    let result = api
+        .get::<serde_json::Value>("/api/directory/cached/test")
+        .await
+        .unwrap()
+        .unwrap_data();
+    assert_eq!(result["status"], "ok", "{result:?}");
+    assert!(result["latencyMs"].is_u64(), "{result:?}");
+    assert!(api
+        .get::<serde_json::Value>("/api/directory/unknown/test")
+        .await
+        .unwrap()
+        .try_unwrap_data()
+        .is_none());
+    let principal = api
+        .get::<serde_json::Value>("/api/directory/cached/principal/admin")
+        .await
+        .unwrap()
+        .unwrap_data();
+    assert_eq!(principal["name"], "admin", "{principal:?}");
+    assert!(principal.get("secrets").is_none(), "{principal:?}");
+    assert!(api
+        .get::<serde_json::Value>("/api/directory/cached/principal/nobody")
+        .await
+        .unwrap()
+        .try_unwrap_data()
+        .is_none());
+}
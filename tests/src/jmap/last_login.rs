@@ -0,0 +1,183 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use std::net::IpAddr;
+
+use common::auth::last_login::LoginKind;
+use serde::Deserialize;
+use store::write::now;
+
+use crate::jmap::{assert_is_empty, ManagementApi};
+
+use super::JMAPTest;
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Activity {
+    last_login: Option<Login>,
+    last_seen: Option<Login>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Login {
+    timestamp: u64,
+    remote_ip: IpAddr,
+}
+
+#[derive(Debug, Deserialize)]
+struct DormantList {
+    items: Vec<DormantAccount>,
+}
+
+#[derive(Debug, Deserialize)]
+struct DormantAccount {
+    name: String,
+}
+
+pub async fn test(params: &mut JMAPTest) {
+    println!("Running last login tracking tests...");
+    let server = params.server.clone();
+    let active_id = server
+        .core
+        .storage
+        .data
+        .create_test_user(
+            "active@example.com",
+            "secret",
+            "Active User",
+            &["active@example.com"],
+        )
+        .await;
+    server
+        .core
+        .storage
+        .data
+        .create_test_user(
+            "dormant@example.com",
+            "secret",
+            "Dormant User",
+            &["dormant@example.com"],
+        )
+        .await;
+
+    // Record a login, token use is not tracked by default
+    let remote_ip: IpAddr = "10.0.0.1".parse().unwrap();
+    let start_time = now();
+    server
+        .core
+        .record_login(active_id, remote_ip, LoginKind::Login, 0)
+        .await;
+    server
+        .core
+        .record_login(active_id, remote_ip, LoginKind::Seen, 0)
+        .await;
+    let last_login = server
+        .core
+        .get_last_login(active_id, LoginKind::Login)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(last_login.remote_ip, remote_ip);
+    assert!(last_login.timestamp >= start_time);
+    assert_eq!(
+        server
+            .core
+            .get_last_login(active_id, LoginKind::Seen)
+            .await
+            .unwrap(),
+        None
+    );
+
+    // Logins from the same address within the interval are not written
+    server
+        .core
+        .storage
+        .lookup
+        .key_delete(format!("ll:{active_id}").into_bytes())
+        .await
+        .unwrap();
+    server
+        .core
+        .record_login(active_id, remote_ip, LoginKind::Login, 0)
+        .await;
+    assert_eq!(
+        server
+            .core
+            .get_last_login(active_id, LoginKind::Login)
+            .await
+            .unwrap(),
+        None
+    );
+
+    // Logins from a different address are always written
+    let remote_ip: IpAddr = "10.0.0.2".parse().unwrap();
+    server
+        .core
+        .record_login(active_id, remote_ip, LoginKind::Login, 0)
+        .await;
+
+    // Query login activity using the management API
+    let api = ManagementApi::new(8899, "admin", "secret");
+    let activity = api
+        .get::<Activity>("/api/principal/active@example.com/last-login")
+        .await
+        .unwrap()
+        .unwrap_data();
+    let last_login = activity.last_login.unwrap();
+    assert_eq!(last_login.remote_ip, remote_ip);
+    assert!(last_login.timestamp >= start_time);
+    assert!(activity.last_seen.is_none());
+    let activity = api
+        .get::<Activity>("/api/principal/dormant@example.com/last-login")
+        .await
+        .unwrap()
+        .unwrap_data();
+    assert!(activity.last_login.is_none());
+    assert!(activity.last_seen.is_none());
+
+    // Accounts without activity after the given time are dormant
+    let dormant = api
+        .get::<DormantList>(&format!("/api/last-login?before={}", start_time - 60))
+        .await
+        .unwrap()
+        .unwrap_data()
+        .items
+        .into_iter()
+        .map(|account| account.name)
+        .collect::<Vec<_>>();
+    assert!(dormant.contains(&"dormant@example.com".to_string()));
+    assert!(!dormant.contains(&"active@example.com".to_string()));
+    let dormant = api
+        .get::<DormantList>(&format!("/api/last-login?before={}", now() + 60))
+        .await
+        .unwrap()
+        .unwrap_data()
+        .items
+        .into_iter()
+        .map(|account| account.name)
+        .collect::<Vec<_>>();
+    assert!(dormant.contains(&"dormant@example.com".to_string()));
+    assert!(dormant.contains(&"active@example.com".to_string()));
+
+    // Login activity is removed along with the account
+    for name in ["active@example.com", "dormant@example.com"] {
+        api.delete::<()>(&format!("/api/principal/{name}"))
+            .await
+            .unwrap()
+            .unwrap_data();
+    }
+    assert_eq!(
+        server
+            .core
+            .get_last_login(active_id, LoginKind::Login)
+            .await
+            .unwrap(),
+        None
+    );
+
+    assert_is_empty(server).await;
+}
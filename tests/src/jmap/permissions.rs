@@ -7,7 +7,7 @@
 use ahash::AHashSet;
 use common::{
     auth::{AccessToken, TenantInfo},
-    DeliveryResult, IngestMessage,
+    DeliveryResult, IngestMessage, RecipientResult,
 };
 use directory::{
     backend::internal::{PrincipalField, PrincipalUpdate, PrincipalValue},
@@ -445,6 +445,34 @@ pub async fn test(params: &JMAPTest) {
         .unwrap()
         .expect_request_error("Tenant quota exceeded");
 
+    // Account and domain counts are scoped to the tenant
+    assert_eq!(core.core.total_accounts(Some(tenant_id)).await.unwrap(), 2);
+    assert_eq!(core.core.total_domains(Some(tenant_id)).await.unwrap(), 2);
+    assert_eq!(
+        core.core
+            .total_domains(Some(other_tenant_id))
+            .await
+            .unwrap(),
+        0
+    );
+    assert_eq!(core.core.total_accounts(Some(u32::MAX)).await.unwrap(), 0);
+    for (query, total) in [
+        ("type=individual&tenant=foobar&count=true", 2),
+        ("type=domain&tenant=foobar&count=true", 2),
+        ("type=domain&tenant=xanadu&count=true", 0),
+        ("type=domain&tenant=unknown&count=true", 0),
+    ] {
+        assert_eq!(
+            api.get::<List<Principal>>(&format!("/api/principal?{query}"))
+                .await
+                .unwrap()
+                .unwrap_data()
+                .total,
+            total,
+            "{query}"
+        );
+    }
+
     // Create an tenant role
     tenant_api
         .post::<u32>(
@@ -603,9 +631,12 @@ pub async fn test(params: &JMAPTest) {
                 session_id: 0,
             })
             .await,
-        vec![DeliveryResult::PermanentFailure {
-            code: [5, 5, 0],
-            reason: "This account is not authorized to receive email.".into()
+        vec![RecipientResult {
+            recipient: "john@foobar.org".to_string(),
+            result: DeliveryResult::PermanentFailure {
+                code: [5, 5, 0],
+                reason: "This account is not authorized to receive email.".into()
+            }
         }]
     );
 
@@ -639,7 +670,10 @@ pub async fn test(params: &JMAPTest) {
                 session_id: 0,
             })
             .await,
-        vec![DeliveryResult::Success]
+        vec![RecipientResult {
+            recipient: "john@foobar.org".to_string(),
+            result: DeliveryResult::Success
+        }]
     );
 
     // Quota for the tenant and user should be updated
@@ -663,8 +697,11 @@ pub async fn test(params: &JMAPTest) {
                 session_id: 0,
             })
             .await,
-        vec![DeliveryResult::TemporaryFailure {
-            reason: "Organization over quota.".into()
+        vec![RecipientResult {
+            recipient: "john@foobar.org".to_string(),
+            result: DeliveryResult::TemporaryFailure {
+                reason: "Organization over quota.".into()
+            }
         }]
     );
 
@@ -11,9 +11,10 @@ use jmap::{
     mailbox::{INBOX_ID, JUNK_ID, TRASH_ID},
     JMAP,
 };
+use jmap_client::mailbox::Role;
 use jmap_proto::types::{collection::Collection, id::Id, property::Property};
 use store::{
-    write::{key::DeserializeBigEndian, TagValue},
+    write::{key::DeserializeBigEndian, now, TagValue},
     IterateParams, LogKey, U32_LEN, U64_LEN,
 };
 
@@ -194,6 +195,48 @@ pub async fn test(params: &mut JMAPTest) {
         );
     }
 
+    // Apply retention policies, keeping messages flagged to be retained
+    // or filed in other mailboxes
+    let reports_id = client
+        .mailbox_create("Retained Reports", None::<String>, Role::None)
+        .await
+        .unwrap()
+        .take_id();
+    let keep_id = client
+        .mailbox_create("Keep", None::<String>, Role::None)
+        .await
+        .unwrap()
+        .take_id();
+    let mut report_ids = Vec::new();
+    for (mailbox_ids, keywords) in [
+        (vec![&reports_id], vec![]),
+        (vec![&reports_id], vec!["$retain"]),
+        (vec![&reports_id, &keep_id], vec![]),
+    ] {
+        report_ids
+            .push(import_report(client, mailbox_ids, keywords, Some(now() as i64 - 3600)).await);
+    }
+    report_ids.push(import_report(client, vec![&reports_id], vec![], None).await);
+    server.purge_account(account_id).await;
+    let reports = server
+        .get_tag(
+            account_id,
+            Collection::Email,
+            Property::MailboxIds,
+            TagValue::Id(Id::from_bytes(reports_id.as_bytes()).unwrap().document_id()),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(reports.len(), 3);
+    for (pos, id) in report_ids.iter().enumerate() {
+        assert_eq!(
+            reports.contains(Id::from_bytes(id.as_bytes()).unwrap().document_id()),
+            pos != 0,
+            "Unexpected retention result for message {pos}"
+        );
+    }
+
     // Delete account
     server
         .core
@@ -205,6 +248,32 @@ pub async fn test(params: &mut JMAPTest) {
     assert_is_empty(server).await;
 }
 
+async fn import_report(
+    client: &mut jmap_client::client::Client,
+    mailbox_ids: Vec<&String>,
+    keywords: Vec<&str>,
+    received_at: Option<i64>,
+) -> String {
+    client
+        .email_import(
+            concat!(
+                "From: reports@example.com\r\n",
+                "To: jdoe@example.com\r\n",
+                "Subject: Daily report\r\n",
+                "\r\n",
+                "Nothing to report."
+            )
+            .as_bytes()
+            .to_vec(),
+            mailbox_ids,
+            Some(keywords),
+            received_at,
+        )
+        .await
+        .unwrap()
+        .take_id()
+}
+
 async fn get_changes(server: &JMAP) -> AHashSet<(u64, u8)> {
     let mut changes = AHashSet::new();
     server
@@ -6,17 +6,21 @@
 
 use crate::{
     directory::internal::TestInternalDirectory,
+    imap::{AssertResult, ImapConnection, Type},
     jmap::{
         assert_is_empty, delivery::SmtpConnection, emails_purge_tombstoned, jmap_raw_request,
         mailbox::destroy_all_mailboxes, test_account_login,
     },
 };
+use common::{DeliveryResult, IngestMessage, RecipientResult};
+use imap_proto::ResponseType;
 use jmap::{blob::upload::DISABLE_UPLOAD_QUOTA, mailbox::INBOX_ID};
 use jmap_client::{
     core::set::{SetErrorType, SetObject},
     email::EmailBodyPart,
 };
 use jmap_proto::types::{collection::Collection, id::Id};
+use utils::BlobHash;
 
 use super::JMAPTest;
 
@@ -238,6 +242,62 @@ pub async fn test(params: &mut JMAPTest) {
         0
     );
 
+    // Test IMAP MULTIAPPEND quota, no messages should be appended when the batch exceeds the quota
+    let mut imap = ImapConnection::connect(b"_x ").await;
+    imap.assert_read(Type::Untagged, ResponseType::Ok).await;
+    imap.send("LOGIN robert@example.com aabbcc").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+    let multi_append = |count: usize| {
+        let mut command = "APPEND INBOX".to_string();
+        for i in 0..count {
+            let message = String::from_utf8(create_message_with_size(
+                "jdoe@example.com",
+                "robert@example.com",
+                &format!("Append test {i}"),
+                400,
+            ))
+            .unwrap();
+            command.push_str(&format!(" {{{}+}}\r\n{}", message.len(), message));
+        }
+        command
+    };
+    imap.send(&multi_append(3)).await;
+    imap.assert_read(Type::Tagged, ResponseType::No)
+        .await
+        .assert_response_code("OVERQUOTA");
+    assert_eq!(
+        server
+            .get_used_quota(account_id.document_id())
+            .await
+            .unwrap(),
+        0
+    );
+    imap.send(&multi_append(2)).await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+    assert_eq!(
+        server
+            .get_used_quota(account_id.document_id())
+            .await
+            .unwrap(),
+        800
+    );
+
+    // Delete messages and check available quota
+    imap.send("SELECT INBOX").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap.send("STORE 1:* +FLAGS.SILENT (\\Deleted)").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+    imap.send("EXPUNGE").await;
+    imap.assert_read(Type::Tagged, ResponseType::Ok).await;
+    emails_purge_tombstoned(&server).await;
+    assert_eq!(
+        server
+            .get_used_quota(account_id.document_id())
+            .await
+            .unwrap(),
+        0
+    );
+
     // Test Email/copy quota
     let other_client = test_account_login("jdoe@example.com", "12345").await;
     let mut other_message_ids = Vec::new();
@@ -331,6 +391,48 @@ pub async fn test(params: &mut JMAPTest) {
             .len(),
         1,
     );
+
+    // Each recipient of a multi-recipient delivery gets its own result
+    let message = create_message_with_size(
+        "jane@example.com",
+        "robert@example.com",
+        "Multi-recipient ingest test",
+        513,
+    );
+    let message_blob = BlobHash::from(message.as_slice());
+    server
+        .core
+        .storage
+        .blob
+        .put_blob(message_blob.as_ref(), &message)
+        .await
+        .unwrap();
+    assert_eq!(
+        server
+            .deliver_message(IngestMessage {
+                sender_address: "jane@example.com".to_string(),
+                recipients: vec![
+                    "robert@example.com".to_string(),
+                    "jdoe@example.com".to_string()
+                ],
+                message_blob,
+                message_size: message.len(),
+                session_id: 0,
+            })
+            .await,
+        vec![
+            RecipientResult {
+                recipient: "robert@example.com".to_string(),
+                result: DeliveryResult::TemporaryFailure {
+                    reason: "Mailbox over quota.".into()
+                }
+            },
+            RecipientResult {
+                recipient: "jdoe@example.com".to_string(),
+                result: DeliveryResult::Success
+            }
+        ]
+    );
     DISABLE_UPLOAD_QUOTA.store(true, std::sync::atomic::Ordering::Relaxed);
 
     // Remove test data
@@ -4,6 +4,7 @@
  * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
  */
 
+use jmap::mailbox::INBOX_ID;
 use jmap_client::{
     core::set::{SetError, SetErrorType},
     email, mailbox,
@@ -128,6 +129,38 @@ pub async fn test(params: &mut JMAPTest) {
         vec![script_ids.first().unwrap().to_string()]
     );
 
+    // Dry runs report the Sieve actions without delivering the message
+    let account_document_id = Id::from_bytes(account_id.as_bytes()).unwrap().document_id();
+    let ingested = server
+        .sieve_script_ingest(
+            &server
+                .core
+                .get_cached_access_token(account_document_id)
+                .await
+                .unwrap(),
+            b"From: bill@remote.org\r\nSubject: Dry run\r\n\r\nTest\r\n",
+            "bill@remote.org",
+            "jdoe@example.com",
+            0,
+            server
+                .sieve_script_get_active(account_document_id)
+                .await
+                .unwrap()
+                .unwrap(),
+            false,
+            true,
+        )
+        .await
+        .unwrap();
+    assert_eq!(ingested.change_id, u64::MAX);
+    let disposition = ingested.disposition.unwrap();
+    assert_eq!(
+        disposition.sieve_actions,
+        vec!["fileinto \"1\"".to_string()]
+    );
+    assert_eq!(disposition.mailbox_ids, vec![INBOX_ID]);
+    assert!(!disposition.is_spam);
+
     // Destroying an active script should not work
     assert!(matches!(
         client
@@ -508,6 +541,54 @@ pub async fn test(params: &mut JMAPTest) {
         panic!("Email {:?} not found in: {:#?}", subject, emails);
     }
 
+    // Run lookup store tests
+    client
+        .sieve_script_create("test_lookup", get_script("test_lookup"), true)
+        .await
+        .unwrap();
+    for sender in ["vip@remote.org", "bill@remote.org"] {
+        lmtp.ingest(
+            sender,
+            &["jdoe@example.com"],
+            &format!(
+                concat!(
+                    "From: {}\r\n",
+                    "To: jdoe@example.com\r\n",
+                    "Subject: Message from {}\r\n",
+                    "\r\n",
+                    "Did you get the memo?"
+                ),
+                sender, sender
+            ),
+        )
+        .await;
+    }
+    let vip_mailbox_id = client
+        .mailbox_query(mailbox::query::Filter::name("VIP").into(), None::<Vec<_>>)
+        .await
+        .unwrap()
+        .take_ids()
+        .pop()
+        .expect("VIP mailbox was not created");
+    let mut request = client.build();
+    request
+        .get_email()
+        .properties([email::Property::MailboxIds, email::Property::Subject]);
+    let emails = request.send_get_email().await.unwrap().take_list();
+    for (sender, is_vip) in [("vip@remote.org", true), ("bill@remote.org", false)] {
+        let subject = format!("Message from {sender}");
+        let email = emails
+            .iter()
+            .find(|email| email.subject() == Some(subject.as_str()))
+            .unwrap_or_else(|| panic!("Email {:?} not found in: {:#?}", subject, emails));
+        assert_eq!(
+            email.mailbox_ids().contains(&vip_mailbox_id.as_str()),
+            is_vip,
+            "Unexpected mailbox for {:#?}",
+            email
+        );
+    }
+
     // Remove test data
     client.sieve_script_deactivate().await.unwrap();
     let mut request = client.build();
@@ -11,19 +11,25 @@ use crate::{
     store::deflate_test_resource,
 };
 use common::auth::AccessToken;
-use jmap::email::ingest::{IngestEmail, IngestSource};
+use jmap::{
+    email::ingest::{IngestEmail, IngestSource},
+    JMAP,
+};
 use jmap_client::{email, mailbox::Role};
-use jmap_proto::types::{collection::Collection, id::Id};
+use jmap_proto::types::{collection::Collection, id::Id, property::Property};
 use mail_parser::{mailbox::mbox::MessageIterator, MessageParser};
 use store::{
     ahash::{AHashMap, AHashSet},
+    query::Filter,
     rand::{self, Rng},
+    write::{BatchBuilder, F_BITMAP, F_CLEAR, F_INDEX, F_VALUE},
 };
 
 use super::JMAPTest;
 
 pub async fn test(params: &mut JMAPTest) {
     test_single_thread(params).await;
+    test_rebuild_threads(params).await;
     test_multi_thread(params).await;
 }
 
@@ -208,6 +214,132 @@ async fn test_single_thread(params: &mut JMAPTest) {
     assert_is_empty(server).await;
 }
 
+async fn test_rebuild_threads(params: &mut JMAPTest) {
+    println!("Running Email Rebuild Threads tests...");
+    let server = params.server.clone();
+    let account_id = 30u32;
+    let mailbox_id = Id::from_bytes(
+        params
+            .client
+            .set_default_account_id(Id::new(account_id as u64).to_string())
+            .mailbox_create("Rebuild", None::<String>, Role::None)
+            .await
+            .unwrap()
+            .id()
+            .unwrap()
+            .as_bytes(),
+    )
+    .unwrap()
+    .document_id();
+
+    // Ingest a conversation, all messages should end up in the same thread
+    let mut document_ids = Vec::new();
+    for message in [
+        "Message-ID: <rebuild-1@example.com>\r\nSubject: Rebuild\r\n\r\nfirst\r\n",
+        concat!(
+            "Message-ID: <rebuild-2@example.com>\r\n",
+            "In-Reply-To: <rebuild-1@example.com>\r\n",
+            "Subject: Re: Rebuild\r\n\r\nsecond\r\n"
+        ),
+        concat!(
+            "Message-ID: <rebuild-3@example.com>\r\n",
+            "References: <rebuild-1@example.com> <rebuild-2@example.com>\r\n",
+            "Subject: Re: Rebuild\r\n\r\nthird\r\n"
+        ),
+    ] {
+        document_ids.push(
+            server
+                .email_ingest(IngestEmail {
+                    raw_message: message.as_bytes(),
+                    message: MessageParser::new().parse(message.as_bytes()),
+                    resource: AccessToken::from_id(account_id).as_resource_token(),
+                    mailbox_ids: vec![mailbox_id],
+                    keywords: vec![],
+                    received_at: None,
+                    received_at_offset: 0,
+                    source: IngestSource::Jmap,
+                    delivered_to: None,
+                    encrypt: false,
+                    dedup: None,
+                    session_id: 0,
+                    dry_run: false,
+                })
+                .await
+                .unwrap()
+                .id
+                .document_id(),
+        );
+    }
+    let thread_ids = email_thread_ids(&server, account_id).await;
+    assert_eq!(thread_ids.len(), 1);
+    let thread_id = thread_ids.into_iter().next().unwrap();
+
+    // Fragment the thread as an out of order migration would
+    let fragment_id = thread_id + 1000;
+    let mut batch = BatchBuilder::new();
+    batch
+        .with_account_id(account_id)
+        .with_collection(Collection::Thread)
+        .create_document_with_id(fragment_id)
+        .with_collection(Collection::Email)
+        .update_document(document_ids[2])
+        .value(Property::ThreadId, thread_id, F_BITMAP | F_CLEAR)
+        .value(Property::ThreadId, fragment_id, F_VALUE | F_BITMAP)
+        .value(Property::References, "stale@example.com", F_INDEX);
+    server.core.storage.data.write(batch.build()).await.unwrap();
+    assert_eq!(email_thread_ids(&server, account_id).await.len(), 2);
+    assert_eq!(stale_references(&server, account_id).await, 1);
+
+    // Rebuild the threads of the mailbox
+    let result = server
+        .rebuild_threads(account_id, Some(mailbox_id))
+        .await
+        .unwrap();
+    assert_eq!(
+        (
+            result.messages,
+            result.threads_merged,
+            result.messages_moved
+        ),
+        (3, 1, 1)
+    );
+    assert_eq!(
+        email_thread_ids(&server, account_id).await,
+        AHashSet::from_iter([thread_id])
+    );
+    assert_eq!(stale_references(&server, account_id).await, 0);
+
+    // Rebuilding again should not change anything
+    let result = server.rebuild_threads(account_id, None).await.unwrap();
+    assert_eq!((result.threads_merged, result.messages_moved), (0, 0));
+
+    destroy_all_mailboxes(params).await;
+    assert_is_empty(server).await;
+}
+
+async fn stale_references(server: &JMAP, account_id: u32) -> u64 {
+    server
+        .filter(
+            account_id,
+            Collection::Email,
+            vec![Filter::eq(Property::References, "stale@example.com")],
+        )
+        .await
+        .unwrap()
+        .results
+        .len()
+}
+
+async fn email_thread_ids(server: &JMAP, account_id: u32) -> AHashSet<u32> {
+    server
+        .get_properties::<u32, _, _>(account_id, Collection::Email, &(), Property::ThreadId)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|(_, thread_id)| thread_id)
+        .collect()
+}
+
 #[allow(dead_code)]
 async fn test_multi_thread(params: &mut JMAPTest) {
     println!("Running Email Merge Threads tests (multi-threaded)...");
@@ -247,9 +379,13 @@ async fn test_multi_thread(params: &mut JMAPTest) {
                         mailbox_ids: vec![mailbox_id],
                         keywords: vec![],
                         received_at: None,
+                        received_at_offset: 0,
                         source: IngestSource::Smtp,
+                        delivered_to: None,
                         encrypt: false,
+                        dedup: None,
                         session_id: 0,
+                        dry_run: false,
                     })
                     .await
                 {
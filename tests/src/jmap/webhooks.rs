@@ -61,6 +61,21 @@ impl MockWebhookEndpoint {
         }
     }
 
+    pub fn assert_not_contains(&self, unexpected: &[&str]) {
+        let events =
+            serde_json::to_string_pretty(&self.events.lock().drain(..).collect::<Vec<_>>())
+                .unwrap();
+
+        for string in unexpected {
+            if events.contains(string) {
+                panic!(
+                    "Expected events to not contain '{}', but it did. Events: {}",
+                    string, events
+                );
+            }
+        }
+    }
+
     pub fn accept(&self) {
         self.reject.store(false, Ordering::Relaxed);
     }
@@ -8,12 +8,21 @@ use std::{fs, net::IpAddr, path::PathBuf, sync::Arc, time::Duration};
 
 use common::{
     config::{
-        server::{Listener, Server, ServerProtocol, Servers},
-        smtp::{throttle::parse_throttle, *},
+        server::{Listener, ListenerRole, Server, ServerProtocol, Servers},
+        smtp::{
+            queue::{QueueConfig, TransportError},
+            throttle::parse_throttle,
+            *,
+        },
     },
     expr::{functions::ResolveVariable, if_block::*, tokenizer::TokenMap, *},
+    listener::TcpAcceptor,
     Core,
 };
+use rustls::{
+    crypto::ring::{cipher_suite::TLS13_AES_256_GCM_SHA384, ALL_CIPHER_SUITES},
+    NamedGroup, SupportedCipherSuite,
+};
 use tokio::net::TcpSocket;
 
 use utils::config::{Config, Rate};
@@ -307,6 +316,7 @@ fn parse_servers() {
         Server {
             id: "smtp".to_string(),
             protocol: ServerProtocol::Smtp,
+            role: None,
             listeners: vec![Listener {
                 socket: TcpSocket::new_v4().unwrap(),
                 addr: "127.0.0.1:9925".parse().unwrap(),
@@ -322,6 +332,7 @@ fn parse_servers() {
         Server {
             id: "smtps".to_string(),
             protocol: ServerProtocol::Smtp,
+            role: None,
             listeners: vec![
                 Listener {
                     socket: TcpSocket::new_v4().unwrap(),
@@ -347,6 +358,7 @@ fn parse_servers() {
         Server {
             id: "submission".to_string(),
             protocol: ServerProtocol::Smtp,
+            role: Some(ListenerRole::Submission),
             listeners: vec![Listener {
                 socket: TcpSocket::new_v4().unwrap(),
                 addr: "127.0.0.1:9991".parse().unwrap(),
@@ -372,6 +384,11 @@ fn parse_servers() {
             "failed for {}",
             expected_server.id
         );
+        assert_eq!(
+            server.role, expected_server.role,
+            "failed for {}",
+            expected_server.id
+        );
         for (listener, expected_listener) in
             server.listeners.into_iter().zip(expected_server.listeners)
         {
@@ -394,6 +411,160 @@ fn parse_servers() {
     }
 }
 
+const TLS_POLICIES: &str = r#"
+[server.listener.submission]
+protocol = "smtp"
+tls.preset = "modern"
+
+[server.listener.imap]
+protocol = "imap"
+tls.preset = "intermediate"
+tls.curves = ["x25519"]
+
+[server.listener.restricted]
+protocol = "smtp"
+tls.min-version = "1.3"
+tls.ciphers = ["TLS13_AES_256_GCM_SHA384", "TLS_ECDHE_RSA_WITH_AES_256_GCM_SHA384"]
+
+[server.listener.invalid]
+protocol = "smtp"
+tls.min-version = "1.3"
+tls.ciphers = ["TLS_ECDHE_RSA_WITH_AES_256_GCM_SHA384"]
+"#;
+
+#[test]
+fn parse_tls_policies() {
+    let mut config = Config::new(TLS_POLICIES).unwrap();
+    let mut servers = Servers::default();
+    servers.parse_tcp_acceptors(&mut config, Core::default().into_shared());
+
+    let provider = |id: &str| match servers.tcp_acceptors.get(id) {
+        Some(TcpAcceptor::Tls { config, .. }) => config.crypto_provider().clone(),
+        _ => panic!("Missing TLS acceptor for {id}"),
+    };
+
+    // The modern preset only allows TLS 1.3
+    let submission = provider("submission");
+    assert!(submission
+        .cipher_suites
+        .iter()
+        .all(|suite| matches!(suite, SupportedCipherSuite::Tls13(_))));
+    assert_eq!(submission.kx_groups.len(), 3);
+
+    // The intermediate preset also allows TLS 1.2
+    let imap = provider("imap");
+    assert_eq!(imap.cipher_suites.len(), ALL_CIPHER_SUITES.len());
+    assert_eq!(
+        imap.kx_groups
+            .iter()
+            .map(|group| group.name())
+            .collect::<Vec<_>>(),
+        vec![NamedGroup::X25519]
+    );
+
+    // TLS 1.2 ciphers are dropped from TLS 1.3 only listeners
+    assert_eq!(
+        provider("restricted").cipher_suites,
+        vec![TLS13_AES_256_GCM_SHA384]
+    );
+    assert!(config
+        .warnings
+        .contains_key("server.listener.restricted.tls.ciphers"));
+
+    // Listeners without usable ciphers are rejected but keep the default policy
+    assert!(config
+        .errors
+        .contains_key("server.listener.invalid.tls.ciphers"));
+    assert_eq!(
+        provider("invalid").cipher_suites.len(),
+        ALL_CIPHER_SUITES.len()
+    );
+}
+
+const TRANSPORT_MAP: &str = r#"
+[remote."backup"]
+address = "backup.example.org"
+protocol = "smtp"
+
+[queue.transport-map]
+max-rewrites = 2
+
+[queue.transport-map.rule."alias"]
+pattern = "Sales@Example.org"
+rewrite = "team@example.org"
+
+[queue.transport-map.rule."legacy"]
+type = "domain"
+pattern = "legacy.example.org"
+rewrite = "example.org"
+
+[queue.transport-map.rule."plus"]
+type = "regex"
+pattern = "^([^+]+)\\+[^@]+@(.+)$"
+rewrite = "$1@$2"
+
+[queue.transport-map.rule."loop-a"]
+pattern = "a@loop.org"
+rewrite = "b@loop.org"
+
+[queue.transport-map.rule."loop-b"]
+pattern = "b@loop.org"
+rewrite = "a@loop.org"
+
+[queue.transport-map.rule."chain"]
+type = "domain"
+pattern = "one.org"
+rewrite = "user@two.org"
+
+[queue.transport-map.rule."chain-two"]
+type = "domain"
+pattern = "two.org"
+rewrite = "user@three.org"
+
+[queue.transport-map.rule."chain-three"]
+type = "domain"
+pattern = "three.org"
+rewrite = "user@four.org"
+
+[queue.transport-map.rule."relay"]
+type = "domain"
+pattern = "example.org"
+relay = "backup"
+
+[queue.transport-map.rule."invalid"]
+pattern = "john@example.org"
+relay = "missing"
+"#;
+
+#[test]
+fn parse_transport_map() {
+    let mut config = Config::new(TRANSPORT_MAP).unwrap();
+    let map = QueueConfig::parse(&mut config).transport_map;
+    assert!(config
+        .errors
+        .contains_key("queue.transport-map.rule.invalid.relay"));
+
+    for (address, expected) in [
+        ("sales@example.org", Ok(Some("team@example.org"))),
+        ("jane@legacy.example.org", Ok(Some("jane@example.org"))),
+        ("jane+news@legacy.example.org", Ok(Some("jane@example.org"))),
+        ("jane@example.org", Ok(None)),
+        ("a@loop.org", Err(TransportError::Loop)),
+        ("jane@two.org", Ok(Some("user@four.org"))),
+        ("jane@one.org", Err(TransportError::TooManyRewrites)),
+    ] {
+        assert_eq!(
+            map.rewrite(address),
+            expected.map(|address| address.map(|address| address.to_string())),
+            "failed for {address}"
+        );
+    }
+
+    // Relays are matched against the rewritten address
+    assert_eq!(map.relay("team@example.org"), Some("backup"));
+    assert_eq!(map.relay("jane@legacy.example.org"), None);
+}
+
 #[tokio::test]
 async fn eval_if() {
     let mut file = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
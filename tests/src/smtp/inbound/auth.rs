@@ -4,7 +4,9 @@
  * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
  */
 
-use common::Core;
+use std::sync::Arc;
+
+use common::{config::server::ListenerRole, Core};
 
 use store::Stores;
 use utils::config::Config;
@@ -12,7 +14,7 @@ use utils::config::Config;
 use crate::{
     smtp::{
         build_smtp,
-        session::{TestSession, VerifyResponse},
+        session::{test_server_instance, TestSession, VerifyResponse},
         TempDir,
     },
     AssertConfig,
@@ -161,4 +163,28 @@ async fn auth() {
     session
         .cmd("AUTH PLAIN AGpvaG4Ac2VjcmV0", "503 5.5.1")
         .await;
+
+    // Inbound listeners never offer AUTH
+    let mut instance = test_server_instance();
+    instance.role = Some(ListenerRole::Inbound);
+    session.instance = Arc::new(instance);
+    session.data.authenticated_as.clear();
+    session.data.remote_ip_str = "10.0.0.1".to_string();
+    session.eval_session_params().await;
+    session
+        .ehlo("mx.foobar.org")
+        .await
+        .assert_not_contains("AUTH ");
+    session
+        .cmd("AUTH PLAIN AGpvaG4Ac2VjcmV0", "503 5.5.1")
+        .await;
+
+    // Submission listeners always require authentication
+    let mut instance = test_server_instance();
+    instance.role = Some(ListenerRole::Submission);
+    session.instance = Arc::new(instance);
+    session.data.remote_ip_str = "10.0.0.2".to_string();
+    session.eval_session_params().await;
+    session.ehlo("mx.foobar.org").await;
+    session.mail_from("john@example.org", "503 5.5.1").await;
 }
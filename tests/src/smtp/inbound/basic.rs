@@ -52,3 +52,39 @@ async fn basic_commands() {
     session.ingest(b"QUIT\r\n").await.unwrap_err();
     session.response().assert_code("221");
 }
+
+#[tokio::test]
+async fn smtputf8_and_pipelining() {
+    // Enable logging
+    crate::enable_logging();
+
+    let mut session = Session::test(build_smtp(Core::default(), Inner::default()));
+    session
+        .ehlo("mx.foobar.org")
+        .await
+        .assert_contains("SMTPUTF8");
+
+    // Non-ASCII addresses are rejected unless SMTPUTF8 was requested
+    session.mail_from("jösé@foobar.org", "553 5.6.7").await;
+    session.mail_from("bill@foobar.org", "250").await;
+    session.rcpt_to("jösé@foobar.org", "553 5.6.7").await;
+    session.rset().await;
+    session.mail_from("<jösé@foobar.org> SMTPUTF8", "250").await;
+    assert_eq!(
+        session.data.mail_from.as_ref().unwrap().address,
+        "jösé@foobar.org"
+    );
+    session.rset().await;
+
+    // Pipelined commands within the limit are accepted
+    session.params.pipelining_limit = 3;
+    session.ingest(b"NOOP\r\nNOOP\r\nNOOP\r\n").await.unwrap();
+    session.response().assert_count("250", 3);
+
+    // Abusive pipelines are rejected
+    session
+        .ingest(b"NOOP\r\nNOOP\r\nNOOP\r\nNOOP\r\n")
+        .await
+        .unwrap_err();
+    session.response().assert_code("421 4.7.0");
+}
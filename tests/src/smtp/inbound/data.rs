@@ -235,6 +235,78 @@ async fn data() {
         )
         .await;
 
+    // Send a message in chunks using BDAT
+    qr.clear_queue(&core).await;
+    session.mail_from("bill@doe.org", "250").await;
+    session.rcpt_to("mike@test.com", "250").await;
+    let chunk = "From: bill@doe.org\r\nSubject: Chunking\r\n";
+    session
+        .ingest(format!("BDAT {}\r\n{chunk}", chunk.len()).as_bytes())
+        .await
+        .unwrap();
+    session.response().assert_code("250 2.6.0");
+
+    // DATA cannot be used after BDAT
+    session.ingest(b"DATA\r\n").await.unwrap();
+    session.response().assert_code("503 5.5.1");
+    let chunk = "\r\nchunked message\r\n";
+    session
+        .ingest(format!("BDAT {} LAST\r\n{chunk}", chunk.len()).as_bytes())
+        .await
+        .unwrap();
+    session.response().assert_code("250");
+    qr.last_queued_message()
+        .await
+        .read_lines(&qr)
+        .await
+        .assert_contains("Subject: Chunking")
+        .assert_contains("chunked message");
+    qr.clear_queue(&core).await;
+
+    // An empty chunk also starts a BDAT transaction
+    session.mail_from("bill@doe.org", "250").await;
+    session.rcpt_to("mike@test.com", "250").await;
+    session.ingest(b"BDAT 0\r\n").await.unwrap();
+    session.response().assert_code("250 2.6.0");
+    session.ingest(b"DATA\r\n").await.unwrap();
+    session.response().assert_code("503 5.5.1");
+    let chunk = "From: bill@doe.org\r\nSubject: Empty chunk\r\n\r\ntest\r\n";
+    session
+        .ingest(format!("BDAT {} LAST\r\n{chunk}", chunk.len()).as_bytes())
+        .await
+        .unwrap();
+    session.response().assert_code("250");
+    qr.last_queued_message()
+        .await
+        .read_lines(&qr)
+        .await
+        .assert_contains("Subject: Empty chunk");
+    qr.clear_queue(&core).await;
+
+    // An oversized chunk fails the transaction, no truncated message is queued
+    session.mail_from("bill@doe.org", "250").await;
+    session.rcpt_to("mike@test.com", "250").await;
+    session.params.max_message_size = 100;
+    let chunk = "From: bill@doe.org\r\nSubject: Chunking\r\n\r\n";
+    session
+        .ingest(format!("BDAT {}\r\n{chunk}", chunk.len()).as_bytes())
+        .await
+        .unwrap();
+    session.response().assert_code("250 2.6.0");
+    let chunk = "a".repeat(100);
+    session
+        .ingest(format!("BDAT {}\r\n{chunk}", chunk.len()).as_bytes())
+        .await
+        .unwrap();
+    session.response().assert_code("552 5.3.4");
+    let chunk = "end\r\n";
+    session
+        .ingest(format!("BDAT {} LAST\r\n{chunk}", chunk.len()).as_bytes())
+        .await
+        .unwrap();
+    session.response().assert_code("503 5.5.1");
+    assert_eq!(qr.read_queued_messages().await, vec![]);
+
     // Make sure store is empty
     qr.clear_queue(&core).await;
     core.core
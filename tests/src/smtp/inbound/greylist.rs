@@ -0,0 +1,114 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use std::time::Duration;
+
+use common::Core;
+
+use store::Stores;
+use utils::config::Config;
+
+use smtp::core::{Inner, Session};
+
+use crate::smtp::{
+    build_smtp,
+    session::{TestSession, VerifyResponse},
+    TempDir,
+};
+
+const CONFIG: &str = r#"
+[storage]
+data = "sqlite"
+lookup = "sqlite"
+blob = "sqlite"
+fts = "sqlite"
+
+[store."sqlite"]
+type = "sqlite"
+path = "{TMP}/queue.db"
+
+[server.allowed-ip]
+"10.0.0.3" = ""
+
+[session.rcpt]
+relay = true
+
+[session.rcpt.greylist]
+enable = [{if = "remote_ip = '10.0.0.2'", then = false},
+          {else = true}]
+delay = "1s"
+"#;
+
+#[tokio::test]
+async fn greylist() {
+    // Enable logging
+    crate::enable_logging();
+
+    let tmp_dir = TempDir::new("smtp_greylist_test", true);
+    let mut config = Config::new(tmp_dir.update_config(CONFIG)).unwrap();
+    let stores = Stores::parse_all(&mut config).await;
+    let core = build_smtp(
+        Core::parse(&mut config, stores, Default::default()).await,
+        Inner::default(),
+    );
+
+    // First attempts from unknown triplets are deferred
+    let mut session = Session::test(core.clone());
+    session.data.remote_ip_str = "10.0.0.1".to_string();
+    session.data.remote_ip = session.data.remote_ip_str.parse().unwrap();
+    session.eval_session_params().await;
+    session.ehlo("mx1.foobar.org").await;
+    session.mail_from("john@example.net", "250").await;
+    session.rcpt_to("jane@foobar.org", "451 4.7.1").await;
+    session.rcpt_to("jane@foobar.org", "451 4.7.1").await;
+
+    // Retries after the delay are accepted, other triplets are still deferred
+    tokio::time::sleep(Duration::from_millis(1100)).await;
+    session.rcpt_to("jane@foobar.org", "250").await;
+    session.rcpt_to("bill@foobar.org", "451 4.7.1").await;
+    session.rset().await;
+    session.mail_from("mike@example.net", "250").await;
+    session.rcpt_to("jane@foobar.org", "451 4.7.1").await;
+
+    // Triplets that passed are allowed from then on
+    session.rset().await;
+    session.mail_from("john@example.net", "250").await;
+    session.rcpt_to("jane@foobar.org", "250").await;
+
+    // Triplets are keyed on the /24 network for IPv4 and the /64 for IPv6
+    for (ip, expected_code) in [
+        ("10.0.0.4", "250"),
+        ("10.0.1.1", "451 4.7.1"),
+        ("2001:db8:0:1::1", "451 4.7.1"),
+    ] {
+        let mut session = Session::test(core.clone());
+        session.data.remote_ip_str = ip.to_string();
+        session.data.remote_ip = session.data.remote_ip_str.parse().unwrap();
+        session.eval_session_params().await;
+        session.ehlo("mx1.foobar.org").await;
+        session.mail_from("john@example.net", "250").await;
+        session.rcpt_to("jane@foobar.org", expected_code).await;
+    }
+    tokio::time::sleep(Duration::from_millis(1100)).await;
+    let mut session = Session::test(core.clone());
+    session.data.remote_ip_str = "2001:db8:0:1::2".to_string();
+    session.data.remote_ip = session.data.remote_ip_str.parse().unwrap();
+    session.eval_session_params().await;
+    session.ehlo("mx1.foobar.org").await;
+    session.mail_from("john@example.net", "250").await;
+    session.rcpt_to("jane@foobar.org", "250").await;
+
+    // Greylisting can be disabled by expression or for allowed IPs
+    for ip in ["10.0.0.2", "10.0.0.3"] {
+        let mut session = Session::test(core.clone());
+        session.data.remote_ip_str = ip.to_string();
+        session.data.remote_ip = session.data.remote_ip_str.parse().unwrap();
+        session.eval_session_params().await;
+        session.ehlo("mx1.foobar.org").await;
+        session.mail_from("john@example.net", "250").await;
+        session.rcpt_to("jane@foobar.org", "250").await;
+    }
+}
@@ -0,0 +1,154 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use common::Core;
+use store::Stores;
+use utils::config::Config;
+
+use crate::{
+    smtp::{
+        build_smtp,
+        inbound::TestMessage,
+        session::{TestSession, VerifyResponse},
+        TempDir, TestSMTP,
+    },
+    AssertConfig,
+};
+use smtp::core::{Inner, Session};
+
+const CONFIG: &str = r#"
+[storage]
+data = "sqlite"
+lookup = "sqlite"
+blob = "sqlite"
+fts = "sqlite"
+
+[store."sqlite"]
+type = "sqlite"
+path = "{TMP}/queue.db"
+
+[session.rcpt]
+relay = true
+
+[session.journal."archive"]
+enable = "sender_domain = 'foobar.org'"
+address = "archive@journal.org"
+
+[session.journal."legal"]
+enable = "rcpt_domain = 'legal.org'"
+address = "legal@journal.org"
+
+[session.journal."remote"]
+enable = "sender = 'fail@foobar.net'"
+url = "http://127.0.0.1:9/journal"
+timeout = "1s"
+"#;
+
+#[tokio::test]
+async fn journal() {
+    // Enable logging
+    crate::enable_logging();
+
+    // Create temp dir for queue
+    let mut inner = Inner::default();
+    let tmp_dir = TempDir::new("smtp_journal_test", true);
+    let mut config = Config::new(tmp_dir.update_config(CONFIG)).unwrap();
+    let stores = Stores::parse_all(&mut config).await;
+    let core = Core::parse(&mut config, stores, Default::default()).await;
+    config.assert_no_errors();
+    let mut qr = inner.init_test_queue(&core);
+
+    // Init session
+    let core = build_smtp(core, inner);
+    let mut session = Session::test(core.clone());
+    session.data.remote_ip_str = "10.0.0.1".to_string();
+    session.eval_session_params().await;
+    session.ehlo("mx.foobar.org").await;
+
+    // Messages from other domains are not journaled
+    session
+        .send_message("john@doe.org", &["bill@example.org"], "test:no_dkim", "250")
+        .await;
+    qr.read_event().await.assert_reload();
+    assert_eq!(qr.read_queued_messages().await.len(), 1);
+    qr.clear_queue(&core).await;
+
+    // Rules are evaluated for each recipient, not only the last one
+    session
+        .send_message(
+            "john@doe.org",
+            &["counsel@legal.org", "bill@example.org"],
+            "test:no_dkim",
+            "250",
+        )
+        .await;
+    qr.read_event().await.assert_reload();
+    qr.read_event().await.assert_reload();
+    let messages = qr.read_queued_messages().await;
+    assert_eq!(messages.len(), 2);
+    let report = messages
+        .iter()
+        .find(|message| message.return_path.is_empty())
+        .expect("Missing journal report");
+    assert_eq!(report.recipients.len(), 1);
+    assert_eq!(report.recipients[0].address, "legal@journal.org");
+    qr.clear_queue(&core).await;
+
+    // A journal report is queued along with the message
+    session
+        .send_message(
+            "jane@foobar.org",
+            &["bill@example.org", "mike@example.net"],
+            "test:no_dkim",
+            "250",
+        )
+        .await;
+    qr.read_event().await.assert_reload();
+    qr.read_event().await.assert_reload();
+    let messages = qr.read_queued_messages().await;
+    assert_eq!(messages.len(), 2);
+    let report = messages
+        .iter()
+        .find(|message| message.return_path.is_empty())
+        .expect("Missing journal report");
+    assert_eq!(report.recipients.len(), 1);
+    assert_eq!(report.recipients[0].address, "archive@journal.org");
+    report
+        .read_lines(&qr)
+        .await
+        .assert_contains("Subject: Journal report")
+        .assert_contains("Sender: jane@foobar.org")
+        .assert_contains("Recipient: bill@example.org")
+        .assert_contains("Recipient: mike@example.net")
+        .assert_contains("Direction: inbound")
+        .assert_contains("Content-Type: message/rfc822");
+    qr.clear_queue(&core).await;
+
+    // Messages sent to the archive address are not journaled again
+    session
+        .send_message(
+            "jane@foobar.org",
+            &["archive@journal.org"],
+            "test:no_dkim",
+            "250",
+        )
+        .await;
+    qr.read_event().await.assert_reload();
+    assert_eq!(qr.read_queued_messages().await.len(), 1);
+    qr.clear_queue(&core).await;
+
+    // Messages are deferred when the journal cannot be archived
+    session
+        .send_message(
+            "fail@foobar.net",
+            &["bill@example.org"],
+            "test:no_dkim",
+            "451 4.3.0",
+        )
+        .await;
+    qr.assert_no_events();
+    qr.assert_queue_is_empty().await;
+}
@@ -180,12 +180,14 @@ async fn mail() {
         session.response().assert_code("501 5.5.4");
     }
 
-    // Test size with a large value
+    // Large sizes are checked against each recipient's limit on RCPT
     session
         .ingest(b"MAIL FROM:<bill@foobar.org> SIZE=1512\r\n")
         .await
         .unwrap();
-    session.response().assert_code("552 5.3.4");
+    session.response().assert_code("250");
+    assert_eq!(session.data.declared_size, 1512);
+    session.rset().await;
 
     // Test strict IPREV
     session.data.remote_ip_str = "10.0.0.2".to_string();
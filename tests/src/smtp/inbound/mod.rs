@@ -26,10 +26,13 @@ pub mod basic;
 pub mod data;
 pub mod dmarc;
 pub mod ehlo;
+pub mod greylist;
+pub mod journal;
 pub mod limits;
 pub mod mail;
 pub mod milter;
 pub mod rcpt;
+pub mod reputation;
 pub mod rewrite;
 pub mod scripts;
 pub mod sign;
@@ -71,6 +71,10 @@ total = [{if = "remote_ip = '10.0.0.1'", then = 3},
 wait = [{if = "remote_ip = '10.0.0.1'", then = '5ms'},
         {else = '1s'}]
 
+[session.data.limits]
+size = [{if = "rcpt_domain = 'foobar.org'", then = 4096},
+        {else = 1024}]
+
 [session.extensions]
 dsn = [{if = "remote_ip = '10.0.0.1'", then = false},
        {else = true}]
@@ -131,7 +135,7 @@ async fn rcpt() {
     // Restore rate limit
     tokio::time::sleep(Duration::from_millis(1100)).await;
     session.rcpt_to("Mike@FooBar.org", "250").await;
-    session.rcpt_to("john@foobar.org", "451 4.5.3").await;
+    session.rcpt_to("john@foobar.org", "452 4.5.3").await;
 
     // Check recipients
     assert_eq!(session.data.rcpt_to.len(), 3);
@@ -163,4 +167,25 @@ async fn rcpt() {
     let rcpt = session.data.rcpt_to.last().unwrap();
     assert!((rcpt.flags & (RCPT_NOTIFY_DELAY | RCPT_NOTIFY_SUCCESS | RCPT_NOTIFY_FAILURE)) != 0);
     assert_eq!(rcpt.dsn_info.as_ref().unwrap(), "Jane.Doe@Foobar.org");
+
+    // The smallest per-recipient message size limit applies
+    assert_eq!(session.params.max_message_size, 1024);
+    session.rset().await;
+    session.mail_from("john@example.net", "250").await;
+    session.rcpt_to("jane@foobar.org", "250").await;
+    assert_eq!(session.params.max_message_size, 4096);
+    session.rcpt_to("external@domain.com", "250").await;
+    assert_eq!(session.params.max_message_size, 1024);
+
+    // The declared size is checked against each recipient's limit
+    session.rset().await;
+    session
+        .ingest(b"MAIL FROM:<john@example.net> SIZE=2048\r\n")
+        .await
+        .unwrap();
+    session.response().assert_code("250");
+    session.rcpt_to("jane@foobar.org", "250").await;
+    session.rcpt_to("external@domain.com", "552 5.3.4").await;
+    assert_eq!(session.data.rcpt_to.len(), 1);
+    assert_eq!(session.params.max_message_size, 4096);
 }
@@ -0,0 +1,120 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use std::time::{Duration, Instant};
+
+use common::Core;
+
+use smtp::core::{Inner, Session};
+use utils::config::Config;
+
+use crate::smtp::{
+    build_smtp,
+    session::{TestSession, VerifyResponse},
+};
+
+const CONFIG: &str = r#"
+[session.reputation.zen]
+type = "dnsbl"
+zone = "zen.dnsbl.test"
+actions = ["127.0.0.2 reject", "127.0.0.3 delay 200ms", "127.0.0.4 score 3"]
+
+[session.reputation.local]
+type = "dnsbl"
+zone = "local.dnsbl.test"
+enable = [{if = "remote_ip = '10.0.0.4'", then = false},
+          {else = true}]
+actions = ["* score 3"]
+
+[session.connect]
+reputation-threshold = 5
+"#;
+
+#[tokio::test]
+async fn ip_reputation() {
+    // Enable logging
+    crate::enable_logging();
+
+    let mut config = Config::new(CONFIG).unwrap();
+    let core = Core::parse(&mut config, Default::default(), Default::default()).await;
+    for (name, result) in [
+        ("1.0.0.10.zen.dnsbl.test", "127.0.0.2"),
+        ("2.0.0.10.zen.dnsbl.test", "127.0.0.3"),
+        ("3.0.0.10.zen.dnsbl.test", "127.0.0.4"),
+        ("3.0.0.10.local.dnsbl.test", "127.0.0.2"),
+        ("4.0.0.10.zen.dnsbl.test", "127.0.0.4"),
+        ("4.0.0.10.local.dnsbl.test", "127.0.0.2"),
+        ("5.0.0.10.zen.dnsbl.test", "127.0.0.2"),
+    ] {
+        core.smtp.resolvers.dns.ipv4_add(
+            name,
+            vec![result.parse().unwrap()],
+            Instant::now() + Duration::from_secs(100),
+        );
+    }
+    let core = build_smtp(core, Inner::default());
+
+    // Listed IPs are rejected
+    let mut session = Session::test(core.clone());
+    session.data.remote_ip_str = "10.0.0.1".to_string();
+    session.data.remote_ip = session.data.remote_ip_str.parse().unwrap();
+    session.data.local_port = 25;
+    session.eval_session_params().await;
+    assert!(!session.check_ip_reputation().await);
+    session.response().assert_code("554 5.7.1");
+    assert_eq!(
+        core.inner
+            .reputation_cache
+            .get(&(session.data.remote_ip, "zen".to_string()))
+            .map(|entry| entry.item.clone()),
+        Some(Some("127.0.0.2".to_string()))
+    );
+
+    // Tarpit
+    let mut session = Session::test(core.clone());
+    session.data.remote_ip_str = "10.0.0.2".to_string();
+    session.data.remote_ip = session.data.remote_ip_str.parse().unwrap();
+    session.data.local_port = 25;
+    session.eval_session_params().await;
+    let time = Instant::now();
+    assert!(session.check_ip_reputation().await);
+    assert!(time.elapsed() >= Duration::from_millis(200));
+
+    // Scores from multiple providers are added up
+    let mut session = Session::test(core.clone());
+    session.data.remote_ip_str = "10.0.0.3".to_string();
+    session.data.remote_ip = session.data.remote_ip_str.parse().unwrap();
+    session.data.local_port = 25;
+    session.eval_session_params().await;
+    assert!(!session.check_ip_reputation().await);
+    session.response().assert_code("554 5.7.1");
+
+    // Disabled providers are not queried
+    let mut session = Session::test(core.clone());
+    session.data.remote_ip_str = "10.0.0.4".to_string();
+    session.data.remote_ip = session.data.remote_ip_str.parse().unwrap();
+    session.data.local_port = 25;
+    session.eval_session_params().await;
+    assert!(session.check_ip_reputation().await);
+    assert!(core
+        .inner
+        .reputation_cache
+        .get(&(session.data.remote_ip, "local".to_string()))
+        .is_none());
+
+    // Submission listeners are not checked by default
+    let mut session = Session::test(core.clone());
+    session.data.remote_ip_str = "10.0.0.5".to_string();
+    session.data.remote_ip = session.data.remote_ip_str.parse().unwrap();
+    session.data.local_port = 587;
+    session.eval_session_params().await;
+    assert!(session.check_ip_reputation().await);
+    assert!(core
+        .inner
+        .reputation_cache
+        .get(&(session.data.remote_ip, "zen".to_string()))
+        .is_none());
+}
@@ -222,3 +222,73 @@ async fn sign_and_seal() {
             "ARC-Message-Signature: i=1; a=ed25519-sha256; s=ed; d=example.com; c=relaxed/simple;",
         );
 }
+
+#[tokio::test]
+async fn sign_by_sender_domain() {
+    // Enable logging
+    crate::enable_logging();
+
+    let tmp_dir = TempDir::new("smtp_sign_domain_test", true);
+    let mut config = Config::new(
+        tmp_dir.update_config(
+            CONFIG
+                .replace(
+                    "sign = \"['rsa']\"",
+                    "sign = \"'auto'\"\nmissing-signer = \"reject\"",
+                )
+                .replace("seal = \"'ed'\"", "seal = \"'auto'\"")
+                + SIGNATURES,
+        ),
+    )
+    .unwrap();
+    let stores = Stores::parse_all(&mut config).await;
+    let core = Core::parse(&mut config, stores, Default::default()).await;
+    let mut inner = Inner::default();
+    let mut qr = inner.init_test_queue(&core);
+
+    // Signers for a parent domain are used for its subdomains
+    let mut session = Session::test(build_smtp(core, inner));
+    session.data.remote_ip_str = "10.0.0.2".to_string();
+    session.eval_session_params().await;
+    session.ehlo("mx.example.com").await;
+    session
+        .send_message(
+            "joe@football.example.com",
+            &["jdoe@example.com"],
+            "test:no_dkim",
+            "250",
+        )
+        .await;
+    qr.expect_message()
+        .await
+        .read_lines(&qr)
+        .await
+        .assert_contains("DKIM-Signature: v=1; a=rsa-sha256; s=rsa; d=example.com;")
+        .assert_contains("DKIM-Signature: v=1; a=ed25519-sha256; s=ed; d=example.com;");
+
+    // From header domains that differ from the envelope sender are not signed
+    session
+        .send_message(
+            "bill@foobar.org",
+            &["jdoe@example.com"],
+            "test:no_dkim",
+            "250",
+        )
+        .await;
+    qr.expect_message()
+        .await
+        .read_lines(&qr)
+        .await
+        .assert_not_contains("DKIM-Signature: v=1");
+
+    // Domains without a signer are rejected
+    session
+        .send_message(
+            "bill@foobar.org",
+            &["jdoe@example.com"],
+            "From: bill@foobar.org\r\nSubject: Test\r\n\r\nHello\r\n.\r\n",
+            "550 5.7.1",
+        )
+        .await;
+    qr.assert_no_events();
+}
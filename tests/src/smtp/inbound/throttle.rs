@@ -6,9 +6,12 @@
 
 use std::time::Duration;
 
-use crate::smtp::{build_smtp, session::TestSession, TempDir};
+use crate::{
+    directory::internal::TestInternalDirectory,
+    smtp::{build_smtp, session::TestSession, TempDir},
+};
 use common::Core;
-use smtp::core::{Inner, Session, SessionAddress};
+use smtp::core::{throttle::SubmissionStatus, Inner, Session, SessionAddress};
 use store::Stores;
 use utils::config::Config;
 
@@ -42,6 +45,31 @@ enable = true
 
 "#;
 
+const CONFIG_SUBMISSION: &str = r#"
+[storage]
+data = "sqlite"
+lookup = "sqlite"
+blob = "sqlite"
+fts = "sqlite"
+directory = "local"
+
+[store."sqlite"]
+type = "sqlite"
+path = "{TMP}/data.db"
+
+[directory."local"]
+type = "internal"
+store = "sqlite"
+
+[session.submission.limit]
+messages = "2/1h"
+recipients = "5/1h"
+admin.messages = "4/1h"
+allow = ["service"]
+suspend = "1/1h"
+
+"#;
+
 #[tokio::test]
 async fn throttle_inbound() {
     // Enable logging
@@ -113,3 +141,87 @@ async fn throttle_inbound() {
     session.data.remote_ip_str = "10.0.0.2".to_string();
     assert!(session.is_allowed().await, "Rate limiter too strict.");
 }
+
+#[tokio::test]
+async fn throttle_submission() {
+    // Enable logging
+    crate::enable_logging();
+
+    let tmp_dir = TempDir::new("smtp_submission_throttle", true);
+    let mut config = Config::new(tmp_dir.update_config(CONFIG_SUBMISSION)).unwrap();
+    let stores = Stores::parse_all(&mut config).await;
+    let core = Core::parse(&mut config, stores, Default::default()).await;
+    let store = core.storage.data.clone();
+    let smtp = build_smtp(core, Inner::default());
+
+    let jane_id = store
+        .create_test_user("jane", "abcde", "Jane", &["jane@example.org"])
+        .await;
+    let bill_id = store
+        .create_test_user("bill", "abcde", "Bill", &["bill@example.org"])
+        .await;
+    let service_id = store
+        .create_test_user("service", "abcde", "Service", &["service@example.org"])
+        .await;
+    let admin_id = store
+        .create_test_user("admin", "abcde", "Admin", &["admin@example.org"])
+        .await;
+
+    // Test message rate limit
+    for _ in 0..2 {
+        assert_eq!(
+            smtp.submission_status(jane_id, 1, 0).await,
+            SubmissionStatus::Allowed
+        );
+    }
+    assert_eq!(
+        smtp.submission_status(jane_id, 1, 0).await,
+        SubmissionStatus::RateLimited
+    );
+
+    // Test recipient rate limit
+    assert_eq!(
+        smtp.submission_status(bill_id, 3, 0).await,
+        SubmissionStatus::Allowed
+    );
+    assert_eq!(
+        smtp.submission_status(bill_id, 3, 0).await,
+        SubmissionStatus::RateLimited
+    );
+
+    // Messages exceeding the recipient limit are rejected permanently
+    assert_eq!(
+        smtp.submission_status(bill_id, 6, 0).await,
+        SubmissionStatus::TooManyRecipients
+    );
+
+    // Allowlisted accounts are not limited
+    for _ in 0..10 {
+        assert_eq!(
+            smtp.submission_status(service_id, 10, 0).await,
+            SubmissionStatus::Allowed
+        );
+    }
+
+    // Administrators have their own limit
+    for _ in 0..4 {
+        assert_eq!(
+            smtp.submission_status(admin_id, 1, 0).await,
+            SubmissionStatus::Allowed
+        );
+    }
+    assert_eq!(
+        smtp.submission_status(admin_id, 1, 0).await,
+        SubmissionStatus::RateLimited
+    );
+
+    // Repeatedly exceeding the limit suspends the account
+    assert_eq!(
+        smtp.submission_status(jane_id, 1, 0).await,
+        SubmissionStatus::Forbidden
+    );
+    assert_eq!(
+        smtp.submission_status(jane_id, 1, 0).await,
+        SubmissionStatus::Forbidden
+    );
+}
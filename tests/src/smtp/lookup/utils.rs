@@ -32,6 +32,10 @@ const CONFIG_V4: &str = r#"
 v4 = "['10.0.0.1', '10.0.0.2', '10.0.0.3', '10.0.0.4']"
 v6 = "['a:b::1', 'a:b::2', 'a:b::3', 'a:b::4']"
 
+[queue.outbound.source-ip.hostname]
+"10.0.0.1" = "mx1.foobar.net"
+"a:b::1" = "MX6.foobar.net"
+
 [queue.outbound]
 ip-strategy = "ipv4_then_ipv6"
 
@@ -69,6 +73,31 @@ async fn lookup_ip() {
         Core::parse(&mut config, Default::default(), Default::default()).await,
         Inner::default(),
     );
+    assert_eq!(
+        core.core.smtp.queue.source_ip.hostnames.len(),
+        2,
+        "{:?}",
+        core.core.smtp.queue.source_ip.hostnames
+    );
+    for (ip, hostname) in [("10.0.0.1", "mx1.foobar.net"), ("a:b::1", "mx6.foobar.net")] {
+        assert_eq!(
+            core.core
+                .smtp
+                .queue
+                .source_ip
+                .hostnames
+                .get(&ip.parse().unwrap())
+                .map(|h| h.as_str()),
+            Some(hostname)
+        );
+    }
+    assert!(!core
+        .core
+        .smtp
+        .queue
+        .source_ip
+        .hostnames
+        .contains_key(&"10.0.0.2".parse().unwrap()));
     core.core.smtp.resolvers.dns.ipv4_add(
         "mx.foobar.org",
         vec![
@@ -221,6 +250,20 @@ max_age: 86400
             expected_policy
         );
     }
+
+    // Malformed policies
+    for policy in [
+        "version: STSv1\nmx: mail.example.com\nmax_age: 604800\n",
+        "version: STSv1\nmode: enforce\nmx: mail.example.com\n",
+        "mode: enforce\nmx: mail.example.com\nmax_age: 604800\n",
+        "version: STSv1\nmode: enforce\nmx: mail.example.com\nmax_age: week\n",
+        "version: STSv1\nmode: enforce\nmax_age: 604800\n",
+    ] {
+        assert!(
+            Policy::parse(policy, "abc".to_string()).is_err(),
+            "{policy:?}"
+        );
+    }
 }
 
 #[test]
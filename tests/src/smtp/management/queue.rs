@@ -19,6 +19,7 @@ use crate::{
     smtp::{outbound::TestServer, session::TestSession},
 };
 use smtp::queue::{manager::SpawnQueue, QueueId, Status};
+use store::{write::AnyKey, QUEUE_DOMAIN_PREFIX, SUBSPACE_REPORT_OUT};
 
 const LOCAL: &str = r#"
 [storage]
@@ -255,6 +256,49 @@ async fn manage_queue() {
     }
     assert_eq!(id_map.len(), 6);
 
+    // Test per-domain queue statistics, including messages queued before
+    // the domain index existed
+    for rebuild_index in [false, true] {
+        if rebuild_index {
+            core.core
+                .storage
+                .data
+                .delete_range(
+                    AnyKey {
+                        subspace: SUBSPACE_REPORT_OUT,
+                        key: vec![QUEUE_DOMAIN_PREFIX],
+                    },
+                    AnyKey {
+                        subspace: SUBSPACE_REPORT_OUT,
+                        key: vec![QUEUE_DOMAIN_PREFIX + 1],
+                    },
+                )
+                .await
+                .unwrap();
+            assert_eq!(
+                core.core
+                    .queued_messages_for_domain("foobar.org")
+                    .await
+                    .unwrap()
+                    .count,
+                0
+            );
+            core.migrate_queue_domain_index().await.unwrap();
+        }
+
+        for (domain, expected_count) in [
+            ("foobar.org", 3),
+            ("Example2.com", 1),
+            ("example1.org", 1),
+            ("example.org", 0),
+        ] {
+            let queued = core.core.queued_messages_for_domain(domain).await.unwrap();
+            assert_eq!(queued.count, expected_count, "failed for {domain}");
+            assert_eq!(queued.next_retry.is_some(), expected_count > 0);
+            assert_eq!(queued.oldest_age.is_some(), expected_count > 0);
+        }
+    }
+
     // Test list search
     for (query, expected_ids) in [
         (
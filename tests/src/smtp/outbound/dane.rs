@@ -125,15 +125,18 @@ async fn dane_verify() {
         .await
         .try_deliver(core.clone())
         .await;
-    local
-        .qr
-        .expect_message()
-        .await
-        .read_lines(&local.qr)
-        .await
-        .assert_contains("<bill@foobar.org> (DANE failed to authenticate")
-        .assert_contains("No TLSA records found");
     local.qr.read_event().await.assert_reload();
+    let message = local.qr.last_queued_message().await;
+    assert!(
+        matches!(message.domains[0].status, Status::TemporaryFailure(_)),
+        "Message: {message:?}"
+    );
+    let status = message.domains[0].status.to_string();
+    assert!(
+        status.contains("DANE failed to authenticate") && status.contains("No TLSA records found"),
+        "Status: {status}"
+    );
+    local.qr.clear_queue(&core).await;
     local.qr.assert_no_events();
 
     // Expect TLS failure report
@@ -178,15 +181,19 @@ async fn dane_verify() {
         .await
         .try_deliver(core.clone())
         .await;
-    local
-        .qr
-        .expect_message()
-        .await
-        .read_lines(&local.qr)
-        .await
-        .assert_contains("<bill@foobar.org> (DANE failed to authenticate")
-        .assert_contains("No matching certificates found");
     local.qr.read_event().await.assert_reload();
+    let message = local.qr.last_queued_message().await;
+    assert!(
+        matches!(message.domains[0].status, Status::TemporaryFailure(_)),
+        "Message: {message:?}"
+    );
+    let status = message.domains[0].status.to_string();
+    assert!(
+        status.contains("DANE failed to authenticate")
+            && status.contains("No matching certificates found"),
+        "Status: {status}"
+    );
+    local.qr.clear_queue(&core).await;
     local.qr.assert_no_events();
 
     // Expect TLS failure report
@@ -348,12 +355,34 @@ async fn dane_test() {
         certs.remove(0);
         assert_eq!(
             tlsa.verify(0, &host, Some(&certs)),
-            Err(Status::PermanentFailure(Error::DaneError(ErrorDetails {
+            Err(Status::TemporaryFailure(Error::DaneError(ErrorDetails {
                 entity: host.to_string(),
                 details: "No matching certificates found in TLSA records".to_string()
             })))
         );
     }
+
+    // A DANE-TA match must not authenticate an unrelated end-entity certificate
+    let tlsa = r
+        .tlsa_lookup("_25._tcp.internet.nl.")
+        .await
+        .unwrap()
+        .unwrap();
+    let certs = ["mail.ietf.org.0.cert", "internet.nl.1.cert"]
+        .into_iter()
+        .map(|name| {
+            let mut file = path.clone();
+            file.push(name);
+            CertificateDer::from(fs::read(file).unwrap())
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        tlsa.verify(0, "internet.nl", Some(&certs)),
+        Err(Status::TemporaryFailure(Error::DaneError(ErrorDetails {
+            entity: "internet.nl".to_string(),
+            details: "No matching certificates found in TLSA records".to_string()
+        })))
+    );
 }
 
 pub fn decode_hex(s: &str) -> Result<Vec<u8>, ParseIntError> {
@@ -27,6 +27,10 @@ retry = "1h"
 notify = "1h"
 expire = "1h"
 
+[queue.outbound.limits]
+concurrency = [{if = "rcpt_domain = 'example.com'", then = 1},
+               {else = 0}]
+
 [[queue.throttle]]
 match = "sender_domain = 'foobar.org'"
 key = 'sender_domain'
@@ -297,6 +301,45 @@ async fn throttle_outbound() {
     local.qr.read_event().await.assert_reload();
     let due = local.qr.last_queued_due().await - now();
     assert!(due > 0, "Due: {}", due);
+
+    // Expect concurrency limit for destination domain 'example.com'
+    let in_flight_domain = core.is_domain_allowed("example.com", 1, 0).unwrap();
+    assert!(core.is_domain_allowed("example.com", 1, 0).is_err());
+    session
+        .send_message(
+            "john@test.net",
+            &["jane@example.com"],
+            "test:no_dkim",
+            "250",
+        )
+        .await;
+    local
+        .qr
+        .expect_message_then_deliver()
+        .await
+        .try_deliver(core.clone())
+        .await;
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    let on_hold = local.qr.read_event().await.unwrap_on_hold();
+    assert_eq!(on_hold.limiters.len(), 1);
+    assert_eq!(on_hold.limiters[0].max_concurrent, 1);
+
+    // Messages on hold do not consume retry attempts
+    let message = local.qr.last_queued_message().await;
+    assert_eq!(message.domains[0].retry.inner, 0);
+
+    // Completed deliveries release the limit
+    drop(in_flight_domain);
+    let _in_flight_domain = core.is_domain_allowed("example.com", 1, 0).unwrap();
+    assert_eq!(
+        core.inner
+            .domain_concurrency
+            .get("example.com")
+            .unwrap()
+            .concurrent
+            .load(std::sync::atomic::Ordering::Relaxed),
+        1
+    );
 }
 
 pub trait TestQueueEnvelope<'x> {
@@ -6,13 +6,15 @@
 
 use std::{fs, path::PathBuf, time::SystemTime};
 
+use common::config::smtp::queue::DsnTemplates;
 use smtp_proto::{Response, RCPT_NOTIFY_DELAY, RCPT_NOTIFY_FAILURE, RCPT_NOTIFY_SUCCESS};
 use store::write::now;
-use utils::BlobHash;
+use utils::{config::Config, BlobHash};
 
 use crate::smtp::{inbound::sign::SIGNATURES, outbound::TestServer, QueueReceiver};
-use smtp::queue::{
-    Domain, Error, ErrorDetails, HostResponse, Message, Recipient, Schedule, Status,
+use smtp::{
+    core::SMTP,
+    queue::{Domain, Error, ErrorDetails, HostResponse, Message, Recipient, Schedule, Status},
 };
 
 const CONFIG: &str = r#"
@@ -32,6 +34,12 @@ sign = "['rsa']"
 
 "#;
 
+const TEMPLATE_CONFIG: &str = r#"
+[report.dsn.template.failure]
+subject = "Undeliverable message"
+body = "We were unable to deliver your message to the following recipients:"
+"#;
+
 #[tokio::test]
 async fn generate_dsn() {
     // Enable logging
@@ -113,6 +121,19 @@ async fn generate_dsn() {
     let dsn_message = qr.expect_message().await;
     qr.compare_dsn(dsn_message, "failure.eml").await;
 
+    // Failure DSN using a custom template
+    let mut custom_core = core.core.as_ref().clone();
+    custom_core.smtp.queue.dsn.templates =
+        DsnTemplates::parse(&mut Config::new(TEMPLATE_CONFIG).unwrap());
+    let custom = SMTP {
+        core: custom_core.into(),
+        inner: core.inner.clone(),
+    };
+    message.recipients[0].flags = flags;
+    custom.send_dsn(&mut message).await;
+    let dsn_message = qr.expect_message().await;
+    qr.compare_dsn(dsn_message, "failure_template.eml").await;
+
     // Success DSN
     message.recipients.push(Recipient {
         domain_idx: 0,
@@ -157,7 +178,7 @@ async fn generate_dsn() {
 
     // Load queue
     let queue = qr.read_queued_messages().await;
-    assert_eq!(queue.len(), 4);
+    assert_eq!(queue.len(), 5);
 }
 
 impl QueueReceiver {
@@ -106,21 +106,21 @@ fn delivery_events() {
     }
 
     message.domain_mut("a").set_status(
-        mail_auth::Error::DnsRecordNotFound(ResponseCode::BADCOOKIE),
+        mail_auth::Error::DnsRecordNotFound(ResponseCode::NXDomain),
         &[],
     );
     assert_eq!(message.next_event().unwrap(), message.domain("b").retry.due);
     assert_eq!(message.next_delivery_event(), message.domain("b").retry.due);
 
     message.domain_mut("b").set_status(
-        mail_auth::Error::DnsRecordNotFound(ResponseCode::BADCOOKIE),
+        mail_auth::Error::DnsRecordNotFound(ResponseCode::NXDomain),
         &[],
     );
     assert_eq!(message.next_event().unwrap(), message.domain("c").retry.due);
     assert_eq!(message.next_delivery_event(), message.domain("c").retry.due);
 
     message.domain_mut("c").set_status(
-        mail_auth::Error::DnsRecordNotFound(ResponseCode::BADCOOKIE),
+        mail_auth::Error::DnsRecordNotFound(ResponseCode::NXDomain),
         &[],
     );
     assert!(message.next_event().is_none());
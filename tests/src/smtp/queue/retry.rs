@@ -11,7 +11,9 @@ use crate::smtp::{
     outbound::TestServer,
     session::{TestSession, VerifyResponse},
 };
-use smtp::queue::{DeliveryAttempt, Event};
+use mail_auth::hickory_resolver::proto::op::ResponseCode;
+use smtp::queue::{DeliveryAttempt, Error, Event, Status};
+use smtp_proto::Response;
 use store::write::now;
 
 const CONFIG: &str = r#"
@@ -178,3 +180,67 @@ async fn queue_retry() {
     let schedule = qr.expect_message().await;
     assert!([3599, 3600].contains(&(schedule.domains.first().unwrap().notify.due - now())));
 }
+
+#[test]
+fn classify_delivery_errors() {
+    let reply = |code: u16| {
+        mail_send::Error::UnexpectedReply(Response {
+            code,
+            esc: [(code / 100) as u8, 0, 0],
+            message: "test".to_string(),
+        })
+    };
+    let is_permanent = |status: Status<(), Error>| match status {
+        Status::PermanentFailure(_) => true,
+        Status::TemporaryFailure(_) => false,
+        _ => unreachable!(),
+    };
+
+    // Genuine 5xx replies are permanent, 4xx replies and network errors are retried
+    for (command, err, expected) in [
+        ("MAIL FROM:<>", reply(550), true),
+        ("RCPT TO:<a@b.org>", reply(550), true),
+        ("RCPT TO:<a@b.org>", reply(552), false),
+        ("DATA", reply(552), true),
+        ("RCPT TO:<a@b.org>", reply(451), false),
+        ("", reply(421), false),
+        (
+            "",
+            mail_send::Error::Io(std::io::ErrorKind::ConnectionRefused.into()),
+            false,
+        ),
+        ("", mail_send::Error::Timeout, false),
+        ("", mail_send::Error::MissingStartTls, true),
+    ] {
+        assert_eq!(
+            is_permanent(Status::from_smtp_error("mx.b.org", command, err)),
+            expected,
+            "failed for {command:?}"
+        );
+    }
+
+    // TLS handshake failures are retried
+    assert!(!is_permanent(Status::from_tls_error(
+        "mx.b.org",
+        mail_send::Error::Timeout
+    )));
+
+    // Only authoritative negative DNS answers are permanent
+    for (err, expected) in [
+        (
+            mail_auth::Error::DnsRecordNotFound(ResponseCode::NXDomain),
+            true,
+        ),
+        (
+            mail_auth::Error::DnsRecordNotFound(ResponseCode::ServFail),
+            false,
+        ),
+        (
+            mail_auth::Error::DnsRecordNotFound(ResponseCode::Refused),
+            false,
+        ),
+        (mail_auth::Error::DnsError("timeout".to_string()), false),
+    ] {
+        assert_eq!(is_permanent(Status::from(err)), expected);
+    }
+}
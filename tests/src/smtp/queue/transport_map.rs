@@ -0,0 +1,92 @@
+/*
+ * SPDX-FileCopyrightText: 2020 Stalwart Labs Ltd <hello@stalw.art>
+ *
+ * SPDX-License-Identifier: AGPL-3.0-only OR LicenseRef-SEL
+ */
+
+use crate::smtp::{outbound::TestServer, queue::manager::new_message, session::TestSession};
+use smtp::queue::Message;
+
+const CONFIG: &str = r#"
+[session.rcpt]
+relay = true
+
+[remote."backup"]
+address = "backup.example.org"
+protocol = "smtp"
+
+[queue.transport-map.rule."alias"]
+pattern = "sales@example.org"
+rewrite = "team@example.org"
+
+[queue.transport-map.rule."vip"]
+pattern = "vip@example.org"
+relay = "backup"
+"#;
+
+#[tokio::test]
+async fn transport_map_queue() {
+    // Enable logging
+    crate::enable_logging();
+
+    let mut local = TestServer::new("smtp_transport_map_test", CONFIG, true).await;
+
+    // Rewritten recipients keep the original address as ORCPT and
+    // recipients routed through a different relay get their own domain entry
+    let core = local.build_smtp();
+    let mut session = local.new_session();
+    session.data.remote_ip_str = "10.0.0.1".to_string();
+    session.eval_session_params().await;
+    session.ehlo("mx.foobar.org").await;
+    session
+        .send_message(
+            "john@foobar.org",
+            &["sales@example.org", "jane@example.org", "vip@example.org"],
+            "test:no_dkim",
+            "250",
+        )
+        .await;
+    assert_transport_map(&local.qr.expect_message().await);
+
+    // Internally generated messages are rewritten and routed the same way
+    let mut message = new_message(0);
+    for rcpt in ["sales@example.org", "jane@example.org", "vip@example.org"] {
+        message.add_recipient(rcpt, &core).await;
+    }
+    assert_transport_map(&message);
+}
+
+fn assert_transport_map(message: &Message) {
+    let rcpt = |address: &str| {
+        message
+            .recipients
+            .iter()
+            .find(|rcpt| rcpt.address_lcase == address)
+            .unwrap_or_else(|| panic!("recipient {address} not found: {message:?}"))
+    };
+
+    assert_eq!(message.recipients.len(), 3);
+    assert!(message
+        .recipients
+        .iter()
+        .all(|rcpt| rcpt.address_lcase != "sales@example.org"));
+    assert_eq!(
+        rcpt("team@example.org").orcpt.as_deref(),
+        Some("sales@example.org")
+    );
+    assert_eq!(rcpt("jane@example.org").orcpt, None);
+
+    assert_eq!(message.domains.len(), 2, "{:?}", message.domains);
+    assert!(message
+        .domains
+        .iter()
+        .all(|domain| domain.domain == "example.org"));
+    assert_eq!(
+        rcpt("team@example.org").domain_idx,
+        rcpt("jane@example.org").domain_idx
+    );
+    assert_ne!(
+        rcpt("jane@example.org").domain_idx,
+        rcpt("vip@example.org").domain_idx
+    );
+}
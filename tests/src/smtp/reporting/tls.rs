@@ -185,8 +185,10 @@ async fn report_tls() {
     assert!(seen[1]);
     assert!(seen[2]);
 
-    // Schedule TLS reports to be delivered via https
-    let tls_record = Arc::new(TlsRpt::parse(b"v=TLSRPTv1;rua=https://127.0.0.1/tls").unwrap());
+    // Schedule TLS reports to be delivered via https and e-mail
+    let tls_record = Arc::new(
+        TlsRpt::parse(b"v=TLSRPTv1;rua=https://127.0.0.1/tls,mailto:reports@foobar.org").unwrap(),
+    );
 
     for _ in 0..2 {
         // Add two successful records
@@ -221,5 +223,12 @@ async fn report_tls() {
         assert_eq!(report.contact_info.unwrap(), "https://foobar.org/contact");
         assert_eq!(report.policies.len(), 1);
     }
+
+    // The report is also submitted to the mailto endpoint
+    let message = qr.expect_message().await;
+    assert_eq!(
+        message.recipients.last().unwrap().address,
+        "reports@foobar.org"
+    );
     qr.assert_report_is_empty().await;
 }
@@ -75,6 +75,10 @@ impl SessionStream for DummyIo {
     fn tls_version_and_cipher(&self) -> (Cow<'static, str>, Cow<'static, str>) {
         ("".into(), "".into())
     }
+
+    fn tls_exporter(&self) -> Option<Vec<u8>> {
+        None
+    }
 }
 
 impl Unpin for DummyIo {}
@@ -353,6 +357,7 @@ impl TestServerInstance for ServerInstance {
         Self {
             id: "smtp".to_string(),
             protocol: ServerProtocol::Smtp,
+            role: None,
             acceptor: TcpAcceptor::Tls {
                 config: tls_config.clone(),
                 acceptor: TlsAcceptor::from(tls_config),
@@ -6,7 +6,7 @@
 
 use std::time::Duration;
 
-use store::{LookupStore, Stores};
+use store::{write::Bincode, LookupStore, Serialize, Stores};
 use utils::config::{Config, Rate};
 
 use crate::{
@@ -60,8 +60,44 @@ pub async fn lookup_tests() {
             store.key_get::<String>(key.clone()).await.unwrap(),
             Some("hello".to_string())
         );
+
+        // Test serialized values with expiry
+        let bin_key = "bin".as_bytes().to_vec();
+        store
+            .key_set(
+                bin_key.clone(),
+                Bincode::new((1234u32, "code".to_string())).serialize(),
+                1.into(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            store
+                .key_get::<Bincode<(u32, String)>>(bin_key.clone())
+                .await
+                .unwrap()
+                .map(|v| v.inner),
+            Some((1234u32, "code".to_string()))
+        );
+        assert!(store.key_exists(bin_key.clone()).await.unwrap());
+        store
+            .key_set(
+                "zero".as_bytes().to_vec(),
+                "expired".to_string().into_bytes(),
+                0.into(),
+            )
+            .await
+            .unwrap();
+        assert!(!store.key_exists("zero".as_bytes().to_vec()).await.unwrap());
+
         tokio::time::sleep(tokio::time::Duration::from_secs(2)).await;
         assert_eq!(None, store.key_get::<String>(key.clone()).await.unwrap());
+        assert!(store
+            .key_get::<Bincode<(u32, String)>>(bin_key.clone())
+            .await
+            .unwrap()
+            .is_none());
+        assert!(!store.key_exists(bin_key).await.unwrap());
 
         store.purge_lookup_store().await.unwrap();
         if let LookupStore::Store(store) = &store {